@@ -0,0 +1,32 @@
+// aegis-core/benches/hashing.rs
+
+// Measures end-to-end seal throughput (hash + sign) for a range of payload
+// sizes. Run with `cargo bench -p aegis-core --bench hashing`.
+
+use aegis_core::{accel, crypto};
+use p256::ecdsa::SigningKey;
+use std::time::Instant;
+
+const SIZES: [usize; 4] = [1 << 20, 10 << 20, 50 << 20, 100 << 20];
+const ITERATIONS: u32 = 5;
+
+fn main() {
+    let key = SigningKey::from_slice(&[0x11; 32]).expect("valid test key");
+    println!("SHA-256 backend: {}", accel::sha256_backend());
+    for size in SIZES {
+        let payload = vec![0xA5u8; size];
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            let sealed = crypto::seal("{}".to_string(), payload.clone(), &key).expect("seal");
+            std::hint::black_box(sealed);
+        }
+        let per_iter = start.elapsed() / ITERATIONS;
+        let mib = size as f64 / (1024.0 * 1024.0);
+        println!(
+            "{:>4} MiB: {:>8.2?} per seal, {:>8.1} MiB/s",
+            mib,
+            per_iter,
+            mib / per_iter.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,24 @@
+// aegis-core/examples/file.rs
+
+// Seal a file on disk and verify the written container.
+//   cargo run -p aegis-core --features verifier --example file -- photo.jpg photo.aegis
+
+use aegis_core::prelude::*;
+
+const DEMO_KEY: &str = "1111111111111111111111111111111111111111111111111111111111111111";
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let (input, output) = match (args.next(), args.next()) {
+        (Some(input), Some(output)) => (input, output),
+        _ => return Err("usage: file <input> <output.aegis>".into()),
+    };
+
+    let sealer = Sealer::from_hex(DEMO_KEY)?;
+    sealer.seal_file(format!(r#"{{"source":"{}"}}"#, input), &input, &output)?;
+    println!("sealed {} -> {}", input, output);
+
+    let verified = Verifier::new().verify_file(&output)?;
+    println!("verified {} bytes signed by {}", verified.payload.len(), verified.fingerprint);
+    Ok(())
+}
@@ -0,0 +1,29 @@
+// aegis-core/examples/in_memory.rs
+
+// Seal a byte buffer and verify the result without touching the filesystem.
+//   cargo run -p aegis-core --features verifier --example in_memory
+
+use aegis_core::prelude::*;
+
+// A fixed demo key. Real deployments load theirs from configuration.
+const DEMO_KEY: &str = "1111111111111111111111111111111111111111111111111111111111111111";
+
+fn main() -> Result<(), AegisError> {
+    let sealer = Sealer::from_hex(DEMO_KEY)?;
+    let sealed = sealer.seal_to_vec(r#"{"title":"in-memory demo"}"#, b"hello, aegis".to_vec())?;
+    println!("sealed {} bytes with key {}", sealed.len(), sealer.fingerprint().to_words());
+
+    let verified = Verifier::new()
+        .trust(sealer.fingerprint())
+        .verify_bytes(&sealed)?;
+    println!("verified: metadata={} payload={:?}", verified.metadata, String::from_utf8_lossy(&verified.payload));
+
+    // Any change to the container is detected.
+    let mut tampered = sealed.clone();
+    *tampered.last_mut().unwrap() ^= 0x01;
+    match Verifier::new().verify_bytes(&tampered) {
+        Ok(_) => println!("tampered container unexpectedly verified"),
+        Err(e) => println!("tampered container rejected: {}", e),
+    }
+    Ok(())
+}
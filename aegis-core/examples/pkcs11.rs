@@ -0,0 +1,38 @@
+// aegis-core/examples/pkcs11.rs
+
+// Seal a file with a P-256 key on a PKCS#11 token, such as a YubiKey.
+//   AEGIS_PKCS11_PIN=123456 cargo run -p aegis-core --features hsm --example pkcs11 -- \
+//       /usr/lib/x86_64-linux-gnu/libykcs11.so id:02 input output.aegis
+//
+// The key is `id:<hex CKA_ID>` (YubiKey PIV slot 9c is `id:02`) or
+// `label:<CKA_LABEL>`. The first slot with a token present is used unless
+// AEGIS_PKCS11_SLOT is set.
+
+use aegis_core::{crypto, keys::Fingerprint, pkcs11};
+use p256::ecdsa::signature::Keypair;
+use std::error::Error;
+use std::path::Path;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [module, key, input, output] = args.as_slice() else {
+        return Err("usage: pkcs11 <module> <id:HEX | label:LABEL> <input> <output.aegis>".into());
+    };
+    let selector = match key.split_once(':') {
+        Some(("id", id)) => pkcs11::KeySelector::Id(hex::decode(id)?),
+        Some(("label", label)) => pkcs11::KeySelector::Label(label.to_string()),
+        _ => return Err(format!("key must be id:<hex> or label:<label>, not '{}'", key).into()),
+    };
+    let slot = std::env::var("AEGIS_PKCS11_SLOT").ok().map(|s| s.parse()).transpose()?;
+    let pin = std::env::var("AEGIS_PKCS11_PIN").map_err(|_| "AEGIS_PKCS11_PIN must be set")?;
+
+    let signer = pkcs11::Pkcs11Signer::open(Path::new(module), slot, &pin, &selector)?;
+    let fingerprint = Fingerprint::of(&signer.verifying_key().to_sec1_bytes());
+    println!("using token key {}", fingerprint.to_hex());
+
+    let metadata = format!(r#"{{"token_key":"{}"}}"#, fingerprint.to_hex());
+    let ancient = crypto::seal(metadata, std::fs::read(input)?, &signer)?;
+    std::fs::write(output, ancient.to_bytes()?)?;
+    println!("sealed {} -> {}", input, output);
+    Ok(())
+}
@@ -0,0 +1,41 @@
+// aegis-core/examples/ssh_agent.rs
+
+// Seal a file with a key held in ssh-agent and write an SSHSIG sidecar.
+//   cargo run -p aegis-core --example ssh_agent -- <key fingerprint or comment> input output.aegis
+//
+// The container itself needs an ecdsa-sha2-nistp256 key. FIDO2 sk-ecdsa
+// keys (and any other agent key) can still produce the SSHSIG sidecar:
+//   ssh-keygen -Y verify -f allowed_signers -I <identity> -n aegis \
+//       -s output.aegis.sshsig < output.aegis
+
+use aegis_core::{crypto, ssh_agent};
+use std::error::Error;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [selector, input, output] = args.as_slice() else {
+        return Err("usage: ssh_agent <key> <input> <output.aegis>".into());
+    };
+
+    let agent = ssh_agent::SshAgent::from_env()?;
+    let key = agent.find_key(selector)?;
+    println!("using {} {} ({})", key.key_type, key.fingerprint(), key.comment);
+
+    let sealed = if key.key_type == ssh_agent::ECDSA_P256 {
+        let signer = ssh_agent::SshAgentSigner::new(ssh_agent::SshAgent::from_env()?, key.clone())?;
+        let metadata = format!(r#"{{"ssh_key":"{}"}}"#, key.fingerprint());
+        let ancient = crypto::seal(metadata, std::fs::read(input)?, &signer)?;
+        let bytes = ancient.to_bytes()?;
+        std::fs::write(output, &bytes)?;
+        println!("sealed {} -> {}", input, output);
+        bytes
+    } else {
+        println!("{} keys can only sign the SSHSIG sidecar; sealing skipped", key.key_type);
+        std::fs::read(output)?
+    };
+
+    let sidecar = format!("{}.sshsig", output);
+    std::fs::write(&sidecar, ssh_agent::sshsig(&agent, &key, ssh_agent::SSHSIG_NAMESPACE, &sealed)?)?;
+    println!("wrote {}", sidecar);
+    Ok(())
+}
@@ -0,0 +1,16 @@
+// aegis-core/examples/stream.rs
+
+// Seal whatever arrives on stdin and write the container to stdout.
+//   echo hello | cargo run -p aegis-core --example stream > hello.aegis
+
+use aegis_core::prelude::*;
+use std::io;
+
+const DEMO_KEY: &str = "1111111111111111111111111111111111111111111111111111111111111111";
+
+fn main() -> Result<(), AegisError> {
+    let sealer = Sealer::from_hex(DEMO_KEY)?;
+    sealer.seal_reader(r#"{"source":"stdin"}"#, &mut io::stdin().lock(), &mut io::stdout().lock())?;
+    eprintln!("sealed stdin with key {}", sealer.fingerprint());
+    Ok(())
+}
@@ -0,0 +1,24 @@
+// aegis-core/src/accel.rs
+
+// `sha2` picks its SHA-256 backend at runtime: SHA-NI on x86/x86_64 and the
+// ARMv8 cryptography extensions on aarch64, falling back to portable code.
+// We repeat the same CPU feature probe here so the choice can be reported.
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+cpufeatures::new!(shani, "sha", "sse2", "ssse3", "sse4.1");
+
+#[cfg(target_arch = "aarch64")]
+cpufeatures::new!(armv8_sha2, "sha2");
+
+/// Describes the SHA-256 implementation `sha2` will use on this CPU.
+pub fn sha256_backend() -> &'static str {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    if shani::get() {
+        return "x86 SHA-NI";
+    }
+    #[cfg(target_arch = "aarch64")]
+    if armv8_sha2::get() {
+        return "ARMv8 SHA2 extensions";
+    }
+    "portable software"
+}
@@ -0,0 +1,488 @@
+// aegis-core/src/bundle.rs
+
+// Offline verification bundles: a tar archive holding a sealed container
+// together with everything needed to judge it without network access — a
+// snapshot of the trusted keys, the revocation list, a log checkpoint, the
+// format specification, and a signed manifest binding them all.
+
+use crate::{
+    crypto::SignatureContext, endorsement::Endorsement, error::AegisError, keys::Fingerprint, spec, tar::TarWriter,
+    time::rfc3339,
+};
+use p256::ecdsa::{Signature, VerifyingKey};
+#[cfg(feature = "sealer")]
+use p256::ecdsa::signature::{Keypair, Signer};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Version 2 manifests are signed in `SignatureContext::BundleManifest`;
+/// version 1 bundles, signed over the bare manifest, are no longer accepted.
+pub const BUNDLE_FORMAT: &str = "aegis-offline-bundle/2";
+
+pub const CONTAINER_FILE: &str = "container.aegis";
+pub const TRUST_FILE: &str = "trust.json";
+pub const REVOCATIONS_FILE: &str = "revocations.json";
+pub const CHECKPOINT_FILE: &str = "checkpoint.json";
+pub const SPEC_FILE: &str = "SPEC.md";
+pub const MANIFEST_FILE: &str = "manifest.json";
+pub const MANIFEST_SIG_FILE: &str = "manifest.sig";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct TrustedKey {
+    /// Hex SHA-256 fingerprint of the SEC1 public key.
+    pub fingerprint: String,
+    /// Hex SEC1 public key.
+    pub public_key: String,
+}
+
+impl TrustedKey {
+    pub fn from_verifying_key(key: &VerifyingKey) -> Self {
+        let sec1 = key.to_sec1_bytes();
+        TrustedKey {
+            fingerprint: Fingerprint::of(&sec1).to_hex(),
+            public_key: hex::encode(&sec1),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct TrustBundle {
+    pub keys: Vec<TrustedKey>,
+    /// Endorsements of other keys by trusted keys, directly or through
+    /// further endorsements; the keys they lead from are trusted too.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub endorsements: Vec<Endorsement>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub format: String,
+    pub created_at: String,
+    /// Hex SEC1 public key of the key that signed this manifest.
+    pub signer_public_key: String,
+    /// File name to hex SHA-256 of its contents.
+    pub files: BTreeMap<String, String>,
+}
+
+/// Inputs for building a bundle.
+pub struct BundleContents {
+    pub container: Vec<u8>,
+    pub trust: TrustBundle,
+    /// Hex fingerprints of revoked keys.
+    pub revoked: Vec<String>,
+    /// Latest log or audit checkpoint, in whatever form the issuer keeps it.
+    pub checkpoint: serde_json::Value,
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<Vec<u8>, AegisError> {
+    serde_json::to_vec_pretty(value).map_err(|e| AegisError::Io(e.into()))
+}
+
+/// A bundle whose manifest still needs signing. Signing is split out so the
+/// manifest can be signed by a remote signer as well as a local key.
+pub struct UnsignedBundle {
+    files: Vec<(&'static str, Vec<u8>)>,
+    manifest: Vec<u8>,
+    mtime: u64,
+}
+
+impl UnsignedBundle {
+    pub fn new(contents: &BundleContents, signer_public_key: &VerifyingKey) -> Result<Self, AegisError> {
+        let files = vec![
+            (CONTAINER_FILE, contents.container.clone()),
+            (TRUST_FILE, to_json(&contents.trust)?),
+            (REVOCATIONS_FILE, to_json(&contents.revoked)?),
+            (CHECKPOINT_FILE, to_json(&contents.checkpoint)?),
+            (SPEC_FILE, spec::to_markdown().into_bytes()),
+        ];
+        let now = SystemTime::now();
+        let manifest = Manifest {
+            format: BUNDLE_FORMAT.to_string(),
+            created_at: rfc3339(now),
+            signer_public_key: hex::encode(signer_public_key.to_sec1_bytes()),
+            files: files
+                .iter()
+                .map(|(name, data)| (name.to_string(), hex::encode(Sha256::digest(data))))
+                .collect(),
+        };
+        Ok(UnsignedBundle {
+            manifest: to_json(&manifest)?,
+            files,
+            mtime: now.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+        })
+    }
+
+    pub fn manifest_bytes(&self) -> &[u8] {
+        &self.manifest
+    }
+
+    /// The bytes to sign with ECDSA P-256 / SHA-256: the manifest in
+    /// `SignatureContext::BundleManifest`.
+    pub fn signing_message(&self) -> Vec<u8> {
+        SignatureContext::BundleManifest.message(&self.manifest)
+    }
+
+    /// Writes the archive with the manifest signature.
+    pub fn finish(self, signature: &Signature) -> Result<Vec<u8>, AegisError> {
+        let mut tar = TarWriter::new(Vec::new());
+        for (name, data) in &self.files {
+            tar.append(name, data, self.mtime)?;
+        }
+        tar.append(MANIFEST_FILE, &self.manifest, self.mtime)?;
+        tar.append(MANIFEST_SIG_FILE, hex::encode(signature.to_bytes()).as_bytes(), self.mtime)?;
+        tar.finish()
+    }
+}
+
+/// Builds the bundle archive, signing the manifest with a local signer.
+#[cfg(feature = "sealer")]
+pub fn build<S>(contents: &BundleContents, signer: &S) -> Result<Vec<u8>, AegisError>
+where
+    S: Signer<Signature> + Keypair<VerifyingKey = VerifyingKey>,
+{
+    let unsigned = UnsignedBundle::new(contents, &signer.verifying_key())?;
+    let signature = SignatureContext::BundleManifest.sign(unsigned.manifest_bytes(), signer)?;
+    unsigned.finish(&signature)
+}
+
+/// Outcome of checking a bundle offline.
+#[cfg(feature = "verifier")]
+#[derive(Serialize, Debug)]
+pub struct BundleReport {
+    pub manifest_created_at: String,
+    pub manifest_signer: String,
+    /// The manifest signer is one of the caller's pinned keys or endorsed
+    /// by one. If not, the bundle is only known to be signed: its trust
+    /// bundle and revocations vouch for nothing, and the container signer
+    /// is not checked against them.
+    pub manifest_signer_trusted: bool,
+    pub container_signer: String,
+    /// How the container signer is endorsed, if it is trusted through an
+    /// endorsement rather than listed in the trust bundle.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container_endorsement: Option<crate::endorsement::ChainReport>,
+    pub metadata: String,
+    pub payload_size: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    pub checkpoint: serde_json::Value,
+}
+
+/// Verifies a bundle offline: file hashes against the manifest, the
+/// manifest signature and the container signature. The manifest signer must
+/// match one of the `pinned` fingerprints (in any form
+/// `Fingerprint::matches` accepts), or be endorsed through the trust
+/// bundle's endorsements by a listed key that does, for the bundle to be
+/// trusted; a bundle cannot vouch for itself. Once it is, the container
+/// signer must be trusted, listed in the trust bundle or endorsed through
+/// its (or the container's) endorsements by a key that is, and neither
+/// signer may be revoked. A bundle whose manifest signer is not pinned is
+/// reported with `manifest_signer_trusted: false`.
+#[cfg(feature = "verifier")]
+pub fn verify(archive: &[u8], pinned: &[String]) -> Result<BundleReport, AegisError> {
+    let fail = |msg: String| AegisError::Crypto(format!("bundle: {}", msg));
+    let entries: BTreeMap<String, Vec<u8>> = crate::tar::read_all(&mut &archive[..], u64::MAX)?
+        .into_iter()
+        .collect();
+    let file = |name: &str| entries.get(name).ok_or_else(|| fail(format!("missing {}", name)));
+    let parse = |name: &str| -> Result<serde_json::Value, AegisError> {
+        serde_json::from_slice(file(name)?).map_err(|e| fail(format!("{}: {}", name, e)))
+    };
+
+    let manifest_bytes = file(MANIFEST_FILE)?;
+    let manifest: Manifest = serde_json::from_slice(manifest_bytes)
+        .map_err(|e| fail(format!("{}: {}", MANIFEST_FILE, e)))?;
+    if manifest.format != BUNDLE_FORMAT {
+        return Err(fail(format!("unsupported format '{}'", manifest.format)));
+    }
+    for (name, expected) in &manifest.files {
+        if hex::encode(Sha256::digest(file(name)?)) != *expected {
+            return Err(fail(format!("{} does not match the manifest", name)));
+        }
+    }
+    for name in entries.keys() {
+        if name != MANIFEST_FILE && name != MANIFEST_SIG_FILE && !manifest.files.contains_key(name) {
+            return Err(fail(format!("{} is not listed in the manifest", name)));
+        }
+    }
+
+    let signer_sec1 = hex::decode(&manifest.signer_public_key).map_err(|e| fail(e.to_string()))?;
+    let signer_key = VerifyingKey::from_sec1_bytes(&signer_sec1).map_err(|e| fail(e.to_string()))?;
+    let sig_hex = std::str::from_utf8(file(MANIFEST_SIG_FILE)?).map_err(|e| fail(e.to_string()))?;
+    let signature = Signature::from_slice(&hex::decode(sig_hex.trim()).map_err(|e| fail(e.to_string()))?)
+        .map_err(|e| fail(e.to_string()))?;
+    if !SignatureContext::BundleManifest.verify(&signer_key, manifest_bytes, &signature)? {
+        return Err(fail("manifest signature is invalid".into()));
+    }
+
+    let trust: TrustBundle =
+        serde_json::from_value(parse(TRUST_FILE)?).map_err(|e| fail(e.to_string()))?;
+    let revoked: Vec<String> =
+        serde_json::from_value(parse(REVOCATIONS_FILE)?).map_err(|e| fail(e.to_string()))?;
+    let roots = trust
+        .keys
+        .iter()
+        .map(|k| {
+            let sec1 = hex::decode(&k.public_key).map_err(|e| fail(e.to_string()))?;
+            if Fingerprint::of(&sec1).to_hex() != k.fingerprint {
+                return Err(fail(format!("trusted key {} does not have that fingerprint", k.fingerprint)));
+            }
+            Ok(sec1)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    // Listed keys are trusted as they are; others if endorsed by one.
+    let check_trusted = |public_key: &[u8], endorsements: &[Endorsement], at: i64, role: &str| {
+        let fingerprint = Fingerprint::of(public_key).to_hex();
+        if revoked.contains(&fingerprint) {
+            return Err(fail(format!("{} key {} is revoked", role, fingerprint)));
+        }
+        if trust.keys.iter().any(|k| k.fingerprint == fingerprint) {
+            return Ok((fingerprint, None));
+        }
+        let endorsed = crate::endorsement::verify_chain(public_key, endorsements, &roots, at);
+        if !endorsed.valid {
+            return Err(fail(format!("{} key {} is not in the trust bundle", role, fingerprint)));
+        }
+        if let Some(link) = endorsed.chain.iter().find(|link| revoked.contains(&link.issuer)) {
+            return Err(fail(format!("{} key {} is endorsed by revoked key {}", role, fingerprint, link.issuer)));
+        }
+        Ok((fingerprint, Some(endorsed)))
+    };
+    // The bundle's own trust counts only once the caller's pins vouch for
+    // its signer: directly, or through an endorsement by a listed key that
+    // is pinned itself.
+    let is_pinned = |public_key: &[u8]| {
+        let fingerprint = Fingerprint::of(public_key);
+        pinned.iter().any(|pin| fingerprint.matches(pin))
+    };
+    let pinned_roots: Vec<Vec<u8>> = roots.iter().filter(|key| is_pinned(key)).cloned().collect();
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    let manifest_signer = Fingerprint::of(&signer_sec1).to_hex();
+    let manifest_signer_trusted = is_pinned(&signer_sec1) || {
+        let endorsed = crate::endorsement::verify_chain(&signer_sec1, &trust.endorsements, &pinned_roots, now);
+        endorsed.valid && !endorsed.chain.iter().any(|link| revoked.contains(&link.issuer))
+    };
+    if manifest_signer_trusted && revoked.contains(&manifest_signer) {
+        return Err(fail(format!("manifest key {} is revoked", manifest_signer)));
+    }
+
+    let ancient = crate::format::AegisAncient::read(&mut &file(CONTAINER_FILE)?[..])?;
+    let verified = crate::crypto::verify(&ancient)?;
+    if !verified.signature_valid || verified.external_metadata_valid == Some(false) {
+        return Err(AegisError::Crypto("signature does not match contents".into()));
+    }
+    let (container_signer, container_endorsement) = if manifest_signer_trusted {
+        let mut endorsements = ancient.header.endorsements()?;
+        endorsements.extend_from_slice(&trust.endorsements);
+        // `crypto::verify()` trusts no TSA, so this is the current time whatever
+        // the container's timestamp claims.
+        let at = crate::crypto::checked_at(&ancient, &verified);
+        check_trusted(&ancient.public_key, &endorsements, at, "container")?
+    } else {
+        (Fingerprint::of(&ancient.public_key).to_hex(), None)
+    };
+
+    Ok(BundleReport {
+        manifest_created_at: manifest.created_at,
+        manifest_signer,
+        manifest_signer_trusted,
+        container_signer,
+        container_endorsement,
+        metadata: verified.metadata,
+        payload_size: verified.payload_size,
+        media_type: verified.media_type,
+        checkpoint: parse(CHECKPOINT_FILE)?,
+    })
+}
+
+#[cfg(all(test, feature = "sealer", feature = "verifier"))]
+mod tests {
+    use super::*;
+    use crate::test_util::{sample_bytes, test_signing_key, SAMPLE_METADATA};
+    use p256::ecdsa::SigningKey;
+    use serde_json::{json, Value};
+
+    fn fingerprint(key: &SigningKey) -> String {
+        Fingerprint::of(&key.verifying_key().to_sec1_bytes()).to_hex()
+    }
+
+    // A bundle signed by test key 1 around the sample container, which test
+    // key 0 signed, with key 0 trusted.
+    fn contents() -> BundleContents {
+        BundleContents {
+            container: sample_bytes(),
+            trust: TrustBundle {
+                keys: vec![TrustedKey::from_verifying_key(test_signing_key(0).verifying_key())],
+                endorsements: vec![],
+            },
+            revoked: vec![],
+            checkpoint: json!({ "tree_size": 3 }),
+        }
+    }
+
+    fn unpack(archive: &[u8]) -> BTreeMap<String, Vec<u8>> {
+        crate::tar::read_all(&mut &archive[..], u64::MAX).unwrap().into_iter().collect()
+    }
+
+    // Archives `files` under a manifest listing them (with `extra` fields)
+    // signed by `signer`.
+    fn repack(mut files: BTreeMap<String, Vec<u8>>, extra: Value, signer: &SigningKey) -> Vec<u8> {
+        files.remove(MANIFEST_FILE);
+        files.remove(MANIFEST_SIG_FILE);
+        let hashes: BTreeMap<_, _> =
+            files.iter().map(|(name, data)| (name.clone(), hex::encode(Sha256::digest(data)))).collect();
+        let mut manifest = json!({
+            "format": BUNDLE_FORMAT,
+            "created_at": "2025-06-01T00:00:00Z",
+            "signer_public_key": hex::encode(signer.verifying_key().to_sec1_bytes()),
+            "files": hashes,
+        });
+        manifest.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+        let manifest = serde_json::to_vec(&manifest).unwrap();
+        let signature = SignatureContext::BundleManifest.sign(&manifest, signer).unwrap();
+        let mut tar = TarWriter::new(Vec::new());
+        for (name, data) in &files {
+            tar.append(name, data, 0).unwrap();
+        }
+        tar.append(MANIFEST_FILE, &manifest, 0).unwrap();
+        tar.append(MANIFEST_SIG_FILE, hex::encode(signature.to_bytes()).as_bytes(), 0).unwrap();
+        tar.finish().unwrap()
+    }
+
+    fn archive(files: &BTreeMap<String, Vec<u8>>) -> Vec<u8> {
+        let mut tar = TarWriter::new(Vec::new());
+        for (name, data) in files {
+            tar.append(name, data, 0).unwrap();
+        }
+        tar.finish().unwrap()
+    }
+
+    fn error(result: Result<BundleReport, AegisError>) -> String {
+        result.expect_err("the bundle should be rejected").to_string()
+    }
+
+    #[test]
+    fn round_trips_a_bundle() {
+        let signer = test_signing_key(1);
+        let bundle = build(&contents(), &signer).unwrap();
+        let report = verify(&bundle, &[fingerprint(&signer)]).unwrap();
+        assert!(report.manifest_signer_trusted);
+        assert_eq!(report.manifest_signer, fingerprint(&signer));
+        assert_eq!(report.container_signer, fingerprint(&test_signing_key(0)));
+        assert!(report.container_endorsement.is_none());
+        assert_eq!(report.metadata, SAMPLE_METADATA);
+        assert_eq!(report.checkpoint, json!({ "tree_size": 3 }));
+
+        let files = unpack(&bundle);
+        let trust: TrustBundle = serde_json::from_slice(&files[TRUST_FILE]).unwrap();
+        assert_eq!(serde_json::to_value(&trust).unwrap(), serde_json::to_value(&contents().trust).unwrap());
+        assert_eq!(files[CONTAINER_FILE], sample_bytes());
+        // Rebuilding the archive from its own files changes nothing.
+        assert!(verify(&repack(files, json!({}), &signer), &[fingerprint(&signer)]).unwrap().manifest_signer_trusted);
+    }
+
+    #[test]
+    fn trusts_only_a_pinned_manifest_signer() {
+        let signer = test_signing_key(1);
+        let bundle = build(&contents(), &signer).unwrap();
+        let report = verify(&bundle, &[fingerprint(&test_signing_key(2))]).unwrap();
+        assert!(!report.manifest_signer_trusted);
+        // Unpinned, the bundle's trust list is not applied to the container.
+        let mut untrusted = contents();
+        untrusted.trust.keys.clear();
+        let bundle = build(&untrusted, &signer).unwrap();
+        assert!(!verify(&bundle, &[]).unwrap().manifest_signer_trusted);
+        assert!(error(verify(&bundle, &[fingerprint(&signer)])).contains("not in the trust bundle"));
+        let mut revoked = contents();
+        revoked.revoked.push(fingerprint(&test_signing_key(0)));
+        let bundle = build(&revoked, &signer).unwrap();
+        assert!(error(verify(&bundle, &[fingerprint(&signer)])).contains("is revoked"));
+    }
+
+    #[test]
+    fn rejects_a_bad_manifest_signature() {
+        let signer = test_signing_key(1);
+        let pinned = [fingerprint(&signer)];
+        let files = unpack(&build(&contents(), &signer).unwrap());
+
+        let mut flipped = files.clone();
+        let signature = flipped.get_mut(MANIFEST_SIG_FILE).unwrap();
+        signature[0] = if signature[0] == b'0' { b'1' } else { b'0' };
+        assert!(error(verify(&archive(&flipped), &pinned)).contains("manifest signature is invalid"));
+
+        // The manifest edited after signing.
+        let mut edited = files.clone();
+        let manifest = String::from_utf8(edited[MANIFEST_FILE].clone()).unwrap();
+        let created_at = serde_json::from_str::<Manifest>(&manifest).unwrap().created_at;
+        let manifest = manifest.replace(&created_at, "2000-01-01T00:00:00Z");
+        edited.insert(MANIFEST_FILE.into(), manifest.into_bytes());
+        assert!(error(verify(&archive(&edited), &pinned)).contains("manifest signature is invalid"));
+
+        // Signed by one key while naming another.
+        let mut impostor = unpack(&repack(files.clone(), json!({}), &test_signing_key(2)));
+        impostor.insert(MANIFEST_FILE.into(), {
+            let manifest = String::from_utf8(impostor[MANIFEST_FILE].clone()).unwrap();
+            let named = hex::encode(test_signing_key(2).verifying_key().to_sec1_bytes());
+            manifest.replace(&named, &hex::encode(signer.verifying_key().to_sec1_bytes())).into_bytes()
+        });
+        assert!(error(verify(&archive(&impostor), &pinned)).contains("manifest signature is invalid"));
+
+        let mut garbage = files.clone();
+        garbage.insert(MANIFEST_SIG_FILE.into(), b"not hex".to_vec());
+        assert!(verify(&archive(&garbage), &pinned).is_err());
+        let mut unsigned = files;
+        unsigned.remove(MANIFEST_SIG_FILE);
+        assert!(error(verify(&archive(&unsigned), &pinned)).contains("missing manifest.sig"));
+    }
+
+    #[test]
+    fn rejects_tampered_and_unlisted_files() {
+        let signer = test_signing_key(1);
+        let pinned = [fingerprint(&signer)];
+        let files = unpack(&build(&contents(), &signer).unwrap());
+
+        let mut tampered = files.clone();
+        tampered.get_mut(CHECKPOINT_FILE).unwrap().push(b' ');
+        assert!(error(verify(&archive(&tampered), &pinned)).contains("checkpoint.json does not match the manifest"));
+
+        let mut extra = files.clone();
+        extra.insert("extra.txt".into(), b"smuggled".to_vec());
+        assert!(error(verify(&archive(&extra), &pinned)).contains("extra.txt is not listed in the manifest"));
+
+        let mut missing = files;
+        missing.remove(REVOCATIONS_FILE);
+        assert!(error(verify(&archive(&missing), &pinned)).contains("missing revocations.json"));
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        let signer = test_signing_key(1);
+        let pinned = [fingerprint(&signer)];
+        let files = unpack(&build(&contents(), &signer).unwrap());
+        let with_trust = |trust: Value| {
+            let mut files = files.clone();
+            files.insert(TRUST_FILE.into(), serde_json::to_vec(&trust).unwrap());
+            repack(files, json!({}), &signer)
+        };
+        let trust: Value = serde_json::from_slice(&files[TRUST_FILE]).unwrap();
+        assert!(verify(&with_trust(trust.clone()), &pinned).is_ok());
+
+        let mut extra = trust.clone();
+        extra["trust_everyone"] = json!(true);
+        assert!(error(verify(&with_trust(extra), &pinned)).contains("unknown field `trust_everyone`"));
+        let mut extra_key_field = trust.clone();
+        extra_key_field["keys"][0]["expires"] = json!("2030-01-01T00:00:00Z");
+        assert!(error(verify(&with_trust(extra_key_field), &pinned)).contains("unknown field `expires`"));
+        let mut mislabelled = trust;
+        mislabelled["keys"][0]["fingerprint"] = json!(fingerprint(&test_signing_key(2)));
+        assert!(error(verify(&with_trust(mislabelled), &pinned)).contains("does not have that fingerprint"));
+
+        let manifest_field = repack(files, json!({ "note": "hi" }), &signer);
+        assert!(error(verify(&manifest_field, &pinned)).contains("unknown field `note`"));
+    }
+}
@@ -0,0 +1,435 @@
+// aegis-core/src/c2pa.rs
+
+// C2PA-style manifests for sealed JPEG and PNG copies, so Content
+// Credentials tooling can find and check the seal in an image. The manifest
+// store is JUMBF, embedded as JPEG APP11 segments after SOI and any JFIF
+// segment, or as a PNG `caBX` chunk after IHDR, and holds one manifest:
+//
+// - `c2pa.assertions`, with a `c2pa.hash.data` assertion binding the image
+//   bytes outside the manifest, and an `aegis.seal` JSON assertion carrying
+//   the container up to its image block, like the XMP copies in `xmp`;
+// - `c2pa.claim`, listing those assertions by hash;
+// - `c2pa.signature`, a COSE_Sign1 ES256 signature over the claim by the
+//   sealing key, in `SignatureContext::C2paClaim`.
+//
+// There is no X.509 chain in the signature (`x5chain`); the key is named by
+// its fingerprint (`kid`) instead, so Content Credentials tools can show
+// the manifest and check its hashes, but report the signer as unknown.
+//
+// Sealed copies are the sanitized image (`xmp::sanitize()`) with the
+// manifest added, so the image block of the container is the copy without
+// the manifest and `extract_sealed()` rebuilds the container from it.
+
+use crate::{
+    crypto::SignatureContext,
+    error::AegisError,
+    keys::Fingerprint,
+    xmp::{self, ImageKind},
+};
+use base64ct::{Base64, Encoding};
+use p256::ecdsa::Signature;
+#[cfg(feature = "sealer")]
+use p256::ecdsa::signature::Signer;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+pub const CLAIM_GENERATOR: &str = concat!("aegis/", env!("CARGO_PKG_VERSION"));
+/// Label of the assertion carrying the aegis seal.
+pub const SEAL_ASSERTION: &str = "aegis.seal";
+
+const HASH_ASSERTION: &str = "c2pa.hash.data";
+/// COSE algorithm ID of ES256 (ECDSA P-256 with SHA-256).
+const COSE_ES256: i64 = -7;
+const COSE_TAG_SIGN1: u64 = 18;
+const COSE_HEADER_ALG: u64 = 1;
+const COSE_HEADER_KID: u64 = 4;
+/// Every COSE_Sign1 `Sig_structure` starts with this CBOR: an array of four
+/// and the text `Signature1`.
+#[cfg(feature = "verifier")]
+pub(crate) const SIG_STRUCTURE_HEADER: &[u8] = b"\x84\x6ASignature1";
+const JPEG_APP11: u8 = 0xEB;
+/// Common identifier, box instance number and packet sequence number at the
+/// start of every APP11 segment.
+const JPEG_JUMBF_HEADER: usize = 8;
+/// JUMBF bytes in an APP11 segment: its length field counts itself and is
+/// 16 bits.
+const MAX_JPEG_SEGMENT_DATA: usize = 65535 - 2 - JPEG_JUMBF_HEADER;
+const PNG_CHUNK: &[u8; 4] = b"caBX";
+
+/// The JUMBF type UUID registered for `tag`.
+fn type_uuid(tag: &[u8; 4]) -> [u8; 16] {
+    let mut uuid = [0, 0, 0, 0, 0x00, 0x11, 0x00, 0x10, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71];
+    uuid[..4].copy_from_slice(tag);
+    uuid
+}
+
+/// A manifest whose claim still needs signing. Signing is split out, as in
+/// `bundle::UnsignedBundle`, so a remote signer can sign it too.
+pub struct UnsignedManifest {
+    image: Vec<u8>,
+    kind: ImageKind,
+    at: usize,
+    label: String,
+    fingerprint: Fingerprint,
+    assertion_store: Vec<u8>,
+    claim: Vec<u8>,
+}
+
+impl UnsignedManifest {
+    /// Describes `image`, a sanitized image sealed as `container_prefix`
+    /// (`format::header_bytes()`) by `public_key`.
+    pub fn new(image: &[u8], container_prefix: &[u8], public_key: &[u8], metadata: &str) -> Result<Self, AegisError> {
+        let kind = ImageKind::sniff(image)
+            .ok_or(AegisError::InvalidImage("only JPEG and PNG images can carry a C2PA manifest"))?;
+        let fingerprint = Fingerprint::of(public_key);
+        let seal = json!({
+            "container": Base64::encode_string(container_prefix),
+            "key_fingerprint": fingerprint.to_hex(),
+            "metadata": metadata,
+        })
+        .to_string();
+        let mut manifest = UnsignedManifest {
+            image: image.to_vec(),
+            kind,
+            at: xmp::packet_position(image)?,
+            label: format!("urn:uuid:{}", uuid_from(&Sha256::digest(container_prefix))),
+            fingerprint,
+            assertion_store: Vec::new(),
+            claim: Vec::new(),
+        };
+        let seal_assertion = superbox(b"json", SEAL_ASSERTION, &[jumbf_box(b"json", seal.as_bytes())]);
+        // The hash assertion names the length of the embedded manifest,
+        // which depends on the length written: repeat until it settles.
+        let mut excluded = 0;
+        loop {
+            manifest.describe(excluded, &seal_assertion);
+            let embedded = embedded_len(kind, manifest.store(&[0; 64]).len());
+            if embedded == excluded {
+                return Ok(manifest);
+            }
+            excluded = embedded;
+        }
+    }
+
+    fn describe(&mut self, excluded: usize, seal_assertion: &[u8]) {
+        // With the manifest left out, the bytes hashed are the image as given.
+        let hash_assertion = Cbor::Map(vec![
+            (
+                Cbor::text("exclusions"),
+                Cbor::Array(vec![Cbor::Map(vec![
+                    (Cbor::text("start"), Cbor::Uint(self.at as u64)),
+                    (Cbor::text("length"), Cbor::Uint(excluded as u64)),
+                ])]),
+            ),
+            (Cbor::text("name"), Cbor::text("jumbf manifest")),
+            (Cbor::text("alg"), Cbor::text("sha256")),
+            (Cbor::text("hash"), Cbor::Bytes(Sha256::digest(&self.image).to_vec())),
+            (Cbor::text("pad"), Cbor::Bytes(Vec::new())),
+        ]);
+        let hash_assertion = superbox(b"cbor", HASH_ASSERTION, &[jumbf_box(b"cbor", &hash_assertion.to_vec())]);
+        let assertions: Vec<Cbor> = [(HASH_ASSERTION, &hash_assertion[..]), (SEAL_ASSERTION, seal_assertion)]
+            .iter()
+            .map(|(label, assertion)| {
+                Cbor::Map(vec![
+                    (Cbor::text("url"), Cbor::text(&format!("self#jumbf=c2pa.assertions/{}", label))),
+                    // Hashed without the superbox's own box header.
+                    (Cbor::text("hash"), Cbor::Bytes(Sha256::digest(&assertion[8..]).to_vec())),
+                ])
+            })
+            .collect();
+        self.claim = Cbor::Map(vec![
+            (Cbor::text("claim_generator"), Cbor::text(CLAIM_GENERATOR)),
+            (Cbor::text("signature"), Cbor::text("self#jumbf=c2pa.signature")),
+            (Cbor::text("assertions"), Cbor::Array(assertions)),
+            (Cbor::text("dc:format"), Cbor::text(self.kind.media_type())),
+            (
+                Cbor::text("instanceID"),
+                Cbor::text(&format!("xmp:iid:{}", self.label.trim_start_matches("urn:uuid:"))),
+            ),
+            (Cbor::text("alg"), Cbor::text("sha256")),
+        ])
+        .to_vec();
+        self.assertion_store = superbox(b"c2as", "c2pa.assertions", &[hash_assertion, seal_assertion.to_vec()]);
+    }
+
+    fn protected_header() -> Vec<u8> {
+        Cbor::Map(vec![(Cbor::Uint(COSE_HEADER_ALG), Cbor::Int(COSE_ES256))]).to_vec()
+    }
+
+    /// The claim as COSE signs it: `["Signature1", protected, b"", claim]`.
+    fn sig_structure(&self) -> Vec<u8> {
+        Cbor::Array(vec![
+            Cbor::text("Signature1"),
+            Cbor::Bytes(Self::protected_header()),
+            Cbor::Bytes(Vec::new()),
+            Cbor::Bytes(self.claim.clone()),
+        ])
+        .to_vec()
+    }
+
+    /// The bytes to sign with ECDSA P-256 / SHA-256: the claim's COSE
+    /// `Sig_structure` in `SignatureContext::C2paClaim`.
+    pub fn signing_message(&self) -> Vec<u8> {
+        SignatureContext::C2paClaim.message(&self.sig_structure())
+    }
+
+    /// The JUMBF manifest store with `signature` (r || s) in place.
+    fn store(&self, signature: &[u8]) -> Vec<u8> {
+        let cose = Cbor::Tag(
+            COSE_TAG_SIGN1,
+            Box::new(Cbor::Array(vec![
+                Cbor::Bytes(Self::protected_header()),
+                Cbor::Map(vec![(Cbor::Uint(COSE_HEADER_KID), Cbor::Bytes(self.fingerprint.as_bytes().to_vec()))]),
+                Cbor::Null,
+                Cbor::Bytes(signature.to_vec()),
+            ])),
+        );
+        let claim = superbox(b"c2cl", "c2pa.claim", &[jumbf_box(b"cbor", &self.claim)]);
+        let signature = superbox(b"c2cs", "c2pa.signature", &[jumbf_box(b"cbor", &cose.to_vec())]);
+        let manifest = superbox(b"c2ma", &self.label, &[self.assertion_store.clone(), claim, signature]);
+        superbox(b"c2pa", "c2pa", &[manifest])
+    }
+
+    /// The image with the signed manifest embedded.
+    pub fn finish(self, signature: &Signature) -> Vec<u8> {
+        let store = self.store(&signature.to_bytes());
+        let mut out = Vec::with_capacity(self.image.len() + embedded_len(self.kind, store.len()));
+        out.extend_from_slice(&self.image[..self.at]);
+        match self.kind {
+            ImageKind::Jpeg => {
+                // Segments after the first repeat the store's box header.
+                let mut rest = &store[..];
+                let mut sequence = 1u32;
+                while !rest.is_empty() {
+                    let repeat = if sequence == 1 { 0 } else { 8 };
+                    let take = rest.len().min(MAX_JPEG_SEGMENT_DATA - repeat);
+                    out.extend_from_slice(&[0xFF, JPEG_APP11]);
+                    out.extend_from_slice(&((2 + JPEG_JUMBF_HEADER + repeat + take) as u16).to_be_bytes());
+                    out.extend_from_slice(b"JP");
+                    out.extend_from_slice(&1u16.to_be_bytes());
+                    out.extend_from_slice(&sequence.to_be_bytes());
+                    out.extend_from_slice(&store[..repeat]);
+                    out.extend_from_slice(&rest[..take]);
+                    rest = &rest[take..];
+                    sequence += 1;
+                }
+            }
+            ImageKind::Png => {
+                out.extend_from_slice(&(store.len() as u32).to_be_bytes());
+                let crc_start = out.len();
+                out.extend_from_slice(PNG_CHUNK);
+                out.extend_from_slice(&store);
+                let crc = xmp::crc32(&out[crc_start..]);
+                out.extend_from_slice(&crc.to_be_bytes());
+            }
+        }
+        out.extend_from_slice(&self.image[self.at..]);
+        out
+    }
+}
+
+/// Embeds a manifest signed with a local key.
+#[cfg(feature = "sealer")]
+pub fn build<S: Signer<Signature>>(
+    image: &[u8],
+    container_prefix: &[u8],
+    public_key: &[u8],
+    metadata: &str,
+    signer: &S,
+) -> Result<Vec<u8>, AegisError> {
+    let unsigned = UnsignedManifest::new(image, container_prefix, public_key, metadata)?;
+    let signature = SignatureContext::C2paClaim.sign(&unsigned.sig_structure(), signer)?;
+    Ok(unsigned.finish(&signature))
+}
+
+/// Rebuilds the container from a copy carrying a manifest from
+/// `UnsignedManifest`: the image block is the copy without the manifest.
+/// Compressed blocks may inflate to `inflate_limit` bytes at most.
+#[cfg(feature = "verifier")]
+pub fn extract_sealed(image: &[u8], inflate_limit: usize) -> Result<crate::format::AegisAncient, AegisError> {
+    let not_sealed = || AegisError::InvalidImage("image carries no aegis C2PA manifest");
+    let (store, range) = match ImageKind::sniff(image).ok_or_else(not_sealed)? {
+        ImageKind::Jpeg => {
+            let segments: Vec<_> = xmp::jpeg_segments(image)
+                .into_iter()
+                .filter(|s| s.marker == JPEG_APP11 && image[s.payload.clone()].starts_with(b"JP"))
+                .collect();
+            let (first, last) = (segments.first().ok_or_else(not_sealed)?, segments.last().ok_or_else(not_sealed)?);
+            let mut store = Vec::new();
+            for (i, segment) in segments.iter().enumerate() {
+                let skip = JPEG_JUMBF_HEADER + if i == 0 { 0 } else { 8 };
+                store.extend_from_slice(image[segment.payload.clone()].get(skip..).ok_or_else(not_sealed)?);
+            }
+            (store, first.start..last.payload.end)
+        }
+        ImageKind::Png => {
+            let chunk = xmp::png_chunks(image)
+                .into_iter()
+                .find(|c| &image[c.kind.clone()] == PNG_CHUNK)
+                .ok_or_else(not_sealed)?;
+            (image[chunk.data.clone()].to_vec(), chunk.start..chunk.end)
+        }
+    };
+    let seal = ["c2pa", "*", "c2pa.assertions", SEAL_ASSERTION]
+        .iter()
+        .try_fold(&store[..], |data, label| find_superbox(data, label))
+        .and_then(|assertion| boxes(assertion).into_iter().find(|(kind, _)| kind == b"json"))
+        .and_then(|(_, json)| serde_json::from_slice::<serde_json::Value>(json).ok())
+        .ok_or_else(not_sealed)?;
+    let container = seal["container"]
+        .as_str()
+        .and_then(|s| Base64::decode_vec(s).ok())
+        .ok_or_else(not_sealed)?;
+    let mut bytes = container;
+    bytes.extend_from_slice(&image[..range.start]);
+    bytes.extend_from_slice(&image[range.end..]);
+    crate::format::AegisAncient::read_limited(&mut &bytes[..], inflate_limit)
+}
+
+/// The contents of the first `jumb` superbox in `data` labelled `label`
+/// (any label for `*`), after its description box.
+#[cfg(feature = "verifier")]
+fn find_superbox<'a>(data: &'a [u8], label: &str) -> Option<&'a [u8]> {
+    boxes(data).into_iter().filter(|(kind, _)| kind == b"jumb").find_map(|(_, payload)| {
+        let (description, contents) = match boxes(payload).first() {
+            Some(([b'j', b'u', b'm', b'd'], description)) => (*description, &payload[8 + description.len()..]),
+            _ => return None,
+        };
+        let name = description.get(17..)?.split(|b| *b == 0).next()?;
+        (label == "*" || name == label.as_bytes()).then_some(contents)
+    })
+}
+
+/// The boxes in `data` as (type, payload), stopping at anything truncated.
+#[cfg(feature = "verifier")]
+fn boxes(mut data: &[u8]) -> Vec<([u8; 4], &[u8])> {
+    let mut out = Vec::new();
+    while data.len() >= 8 {
+        let len = u32::from_be_bytes(data[..4].try_into().expect("4 bytes")) as usize;
+        if len < 8 || len > data.len() {
+            break;
+        }
+        out.push((data[4..8].try_into().expect("4 bytes"), &data[8..len]));
+        data = &data[len..];
+    }
+    out
+}
+
+fn jumbf_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(8 + payload.len());
+    out.extend_from_slice(&(8 + payload.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(payload);
+    out
+}
+
+/// A `jumb` superbox: a description box with the type UUID for `tag` and
+/// `label` (requestable, labelled), then `contents`.
+fn superbox(tag: &[u8; 4], label: &str, contents: &[Vec<u8>]) -> Vec<u8> {
+    let mut description = type_uuid(tag).to_vec();
+    description.push(0x03);
+    description.extend_from_slice(label.as_bytes());
+    description.push(0);
+    let mut payload = jumbf_box(b"jumd", &description);
+    for content in contents {
+        payload.extend_from_slice(content);
+    }
+    jumbf_box(b"jumb", &payload)
+}
+
+/// Bytes added to an image by embedding a manifest store of `store_len`.
+fn embedded_len(kind: ImageKind, store_len: usize) -> usize {
+    match kind {
+        ImageKind::Jpeg => {
+            let rest = store_len.saturating_sub(MAX_JPEG_SEGMENT_DATA);
+            let continuations = rest.div_ceil(MAX_JPEG_SEGMENT_DATA - 8);
+            store_len + (1 + continuations) * (4 + JPEG_JUMBF_HEADER) + continuations * 8
+        }
+        ImageKind::Png => store_len + 12,
+    }
+}
+
+/// A version 4 style UUID made from the first bytes of `hash`.
+fn uuid_from(hash: &[u8]) -> String {
+    let mut b: [u8; 16] = hash[..16].try_into().expect("hash is at least 16 bytes");
+    b[6] = (b[6] & 0x0F) | 0x40;
+    b[8] = (b[8] & 0x3F) | 0x80;
+    let h = hex::encode(b);
+    format!("{}-{}-{}-{}-{}", &h[..8], &h[8..12], &h[12..16], &h[16..20], &h[20..])
+}
+
+/// The CBOR this module writes (RFC 8949, definite lengths only).
+enum Cbor {
+    Uint(u64),
+    Int(i64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Cbor>),
+    Map(Vec<(Cbor, Cbor)>),
+    Tag(u64, Box<Cbor>),
+    Null,
+}
+
+impl Cbor {
+    fn text(s: &str) -> Self {
+        Cbor::Text(s.to_string())
+    }
+
+    fn to_vec(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode(&mut out);
+        out
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Cbor::Uint(n) => head(out, 0, *n),
+            Cbor::Int(n) if *n >= 0 => head(out, 0, *n as u64),
+            Cbor::Int(n) => head(out, 1, (-1 - *n) as u64),
+            Cbor::Bytes(b) => {
+                head(out, 2, b.len() as u64);
+                out.extend_from_slice(b);
+            }
+            Cbor::Text(s) => {
+                head(out, 3, s.len() as u64);
+                out.extend_from_slice(s.as_bytes());
+            }
+            Cbor::Array(items) => {
+                head(out, 4, items.len() as u64);
+                items.iter().for_each(|item| item.encode(out));
+            }
+            Cbor::Map(entries) => {
+                head(out, 5, entries.len() as u64);
+                for (key, value) in entries {
+                    key.encode(out);
+                    value.encode(out);
+                }
+            }
+            Cbor::Tag(tag, item) => {
+                head(out, 6, *tag);
+                item.encode(out);
+            }
+            Cbor::Null => out.push(0xF6),
+        }
+    }
+}
+
+fn head(out: &mut Vec<u8>, major: u8, n: u64) {
+    let major = major << 5;
+    match n {
+        0..=23 => out.push(major | n as u8),
+        24..=0xFF => out.extend_from_slice(&[major | 24, n as u8]),
+        0x100..=0xFFFF => {
+            out.push(major | 25);
+            out.extend_from_slice(&(n as u16).to_be_bytes());
+        }
+        0x1_0000..=0xFFFF_FFFF => {
+            out.push(major | 26);
+            out.extend_from_slice(&(n as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend_from_slice(&n.to_be_bytes());
+        }
+    }
+}
@@ -0,0 +1,362 @@
+// aegis-core/src/chunked.rs
+
+// Chunked image blocks, so that part of a very large payload (a video, a RAW
+// file) can be verified without reading the rest of it. The image block
+// itself is unchanged; a container sealed this way splits it into
+// fixed-size chunks, lists the SHA-256 of each in its header
+// (`format::FIELD_IMAGE_CHUNKS`) and sets `format::FLAG_CHUNKED_IMAGE`, and
+// its signature covers the root of a Merkle tree over the chunks instead of
+// a hash of the whole image:
+//
+//     SHA-256(CHUNKED_DIGEST_PREFIX || SHA-256(metadata) ||
+//             chunk size (4 bytes BE) || image length (8 bytes BE) || root)
+//
+// The tree is hashed as in `merkle`, with each chunk's SHA-256 as a leaf.
+// A reader checks the signature against the root of the listed hashes,
+// which takes only the header, then any byte range against the hashes of
+// the chunks it overlaps (`verify_range()`). A full verification hashes
+// every chunk itself, so it does not depend on the list.
+
+use crate::error::AegisError;
+use crate::merkle::{self, MerkleTree};
+use sha2::{Digest, Sha256};
+use std::ops::Range;
+#[cfg(feature = "verifier")]
+use {
+    crate::{crypto::{self, SignatureScheme}, format, keys::Fingerprint},
+    serde::Serialize,
+    std::io::{Read, Seek, SeekFrom},
+};
+
+pub const MIN_CHUNK_SIZE: u32 = 4 * 1024;
+pub const MAX_CHUNK_SIZE: u32 = 64 * 1024 * 1024;
+pub const DEFAULT_CHUNK_SIZE: u32 = 1024 * 1024;
+
+/// Bytes read for the first look at the header; doubled while the blocks in
+/// front of the image do not fit, up to `MAX_HEADER_READ`.
+#[cfg(feature = "verifier")]
+const INITIAL_HEADER_READ: usize = 64 * 1024;
+#[cfg(feature = "verifier")]
+const MAX_HEADER_READ: usize = 16 * 1024 * 1024;
+
+/// Hashed in front of the fields of a chunked image's contents digest.
+pub const CHUNKED_DIGEST_PREFIX: &[u8] = b"aegis/image-chunks/v1\0";
+
+/// The chunk size, image length and chunk hashes of a chunked image.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChunkTable {
+    pub chunk_size: u32,
+    pub image_len: u64,
+    /// SHA-256 of each chunk, in order; the last chunk may be short.
+    pub hashes: Vec<[u8; 32]>,
+}
+
+/// Checks a chunk size against `MIN_CHUNK_SIZE` and `MAX_CHUNK_SIZE`.
+pub fn check_chunk_size(chunk_size: u32) -> Result<(), AegisError> {
+    if !(MIN_CHUNK_SIZE..=MAX_CHUNK_SIZE).contains(&chunk_size) {
+        return Err(AegisError::InvalidChunks(format!(
+            "chunk size must be from {} to {} bytes",
+            MIN_CHUNK_SIZE, MAX_CHUNK_SIZE
+        )));
+    }
+    Ok(())
+}
+
+impl ChunkTable {
+    /// The table of an image held in memory.
+    pub fn of(image: &[u8], chunk_size: u32) -> Result<Self, AegisError> {
+        let mut hasher = ChunkHasher::new(chunk_size)?;
+        hasher.update(image);
+        Ok(hasher.finish())
+    }
+
+    /// Checks that the hashes are as many as the chunks of the image.
+    pub fn check(&self) -> Result<(), AegisError> {
+        check_chunk_size(self.chunk_size)?;
+        if self.hashes.len() as u64 != self.image_len.div_ceil(self.chunk_size as u64) {
+            return Err(AegisError::InvalidChunks(format!(
+                "{} hashes listed for {} bytes in chunks of {}",
+                self.hashes.len(),
+                self.image_len,
+                self.chunk_size
+            )));
+        }
+        Ok(())
+    }
+
+    /// The Merkle root over the chunk hashes.
+    pub fn root(&self) -> [u8; 32] {
+        let mut tree = MerkleTree::new();
+        for hash in &self.hashes {
+            tree.push(merkle::leaf_hash(hash));
+        }
+        tree.root_at(tree.len()).expect("the tree has that many leaves")
+    }
+
+    /// The digest that stands in for `crypto::signing_digest()` in a
+    /// chunked container.
+    pub fn contents_digest(&self, metadata: &str) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(CHUNKED_DIGEST_PREFIX);
+        hasher.update(Sha256::digest(metadata.as_bytes()));
+        hasher.update(self.chunk_size.to_be_bytes());
+        hasher.update(self.image_len.to_be_bytes());
+        hasher.update(self.root());
+        hasher.finalize().into()
+    }
+
+    /// The indexes of the chunks holding image bytes `range`.
+    pub fn chunks_covering(&self, range: &Range<u64>) -> Range<u64> {
+        let size = self.chunk_size as u64;
+        if range.is_empty() {
+            return range.start / size..range.start / size;
+        }
+        range.start / size..range.end.div_ceil(size)
+    }
+
+    /// The image bytes chunk `index` spans.
+    pub fn chunk_span(&self, index: u64) -> Range<u64> {
+        let start = index * self.chunk_size as u64;
+        start..(start + self.chunk_size as u64).min(self.image_len)
+    }
+
+    /// The `format::FIELD_IMAGE_CHUNKS` value: the chunk size as 4 and the
+    /// image length as 8 big-endian bytes, then the hashes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(12 + 32 * self.hashes.len());
+        out.extend_from_slice(&self.chunk_size.to_be_bytes());
+        out.extend_from_slice(&self.image_len.to_be_bytes());
+        for hash in &self.hashes {
+            out.extend_from_slice(hash);
+        }
+        out
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, AegisError> {
+        let malformed = || AegisError::InvalidChunks("malformed image chunks field".into());
+        if bytes.len() < 12 || !(bytes.len() - 12).is_multiple_of(32) {
+            return Err(malformed());
+        }
+        let table = ChunkTable {
+            chunk_size: u32::from_be_bytes(bytes[..4].try_into().expect("4 bytes")),
+            image_len: u64::from_be_bytes(bytes[4..12].try_into().expect("8 bytes")),
+            hashes: bytes[12..].chunks(32).map(|h| h.try_into().expect("32 bytes")).collect(),
+        };
+        table.check()?;
+        Ok(table)
+    }
+}
+
+/// Builds a `ChunkTable` from an image that arrives in pieces.
+pub struct ChunkHasher {
+    chunk_size: u32,
+    current: Sha256,
+    filled: u32,
+    image_len: u64,
+    hashes: Vec<[u8; 32]>,
+}
+
+impl ChunkHasher {
+    pub fn new(chunk_size: u32) -> Result<Self, AegisError> {
+        check_chunk_size(chunk_size)?;
+        Ok(ChunkHasher { chunk_size, current: Sha256::new(), filled: 0, image_len: 0, hashes: Vec::new() })
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.image_len += data.len() as u64;
+        while !data.is_empty() {
+            let take = data.len().min((self.chunk_size - self.filled) as usize);
+            self.current.update(&data[..take]);
+            self.filled += take as u32;
+            data = &data[take..];
+            if self.filled == self.chunk_size {
+                self.hashes.push(std::mem::take(&mut self.current).finalize().into());
+                self.filled = 0;
+            }
+        }
+    }
+
+    pub fn image_len(&self) -> u64 {
+        self.image_len
+    }
+
+    pub fn finish(mut self) -> ChunkTable {
+        if self.filled > 0 {
+            self.hashes.push(self.current.finalize().into());
+        }
+        ChunkTable { chunk_size: self.chunk_size, image_len: self.image_len, hashes: self.hashes }
+    }
+}
+
+/// The outcome of `verify_range()`.
+#[cfg(feature = "verifier")]
+#[derive(Debug, Clone, Serialize)]
+pub struct RangeReport {
+    /// Whether the signature covers the chunk hashes in the header.
+    pub signature_valid: bool,
+    /// Whether every chunk the range overlaps matches its hash.
+    pub range_valid: bool,
+    pub key_fingerprint: String,
+    pub key_id: Option<String>,
+    #[serde(skip)]
+    pub public_key: Vec<u8>,
+    /// The signed metadata, or the external document it refers to if that
+    /// is present and matches.
+    pub metadata: String,
+    /// For metadata kept in the header, whether the document matches the
+    /// signed reference.
+    pub external_metadata_valid: Option<bool>,
+    pub start: u64,
+    pub end: u64,
+    pub image_len: u64,
+    pub chunk_size: u32,
+    /// Indexes of the chunks read and checked.
+    pub chunks: Range<u64>,
+    pub chunk_count: u64,
+    /// Image bytes read: the range rounded out to whole chunks.
+    pub bytes_read: u64,
+    /// The range's bytes; only meaningful if both checks passed.
+    #[serde(skip)]
+    pub data: Vec<u8>,
+}
+
+/// Reads the blocks in front of the image from the start of `reader`,
+/// doubling the read until they fit or reach `MAX_HEADER_READ`.
+#[cfg(feature = "verifier")]
+fn read_header<R: Read>(reader: &mut R) -> Result<format::ContainerHeader, AegisError> {
+    let mut prefix = Vec::new();
+    let mut want = INITIAL_HEADER_READ;
+    loop {
+        let had = prefix.len();
+        (&mut *reader).take((want - had) as u64).read_to_end(&mut prefix)?;
+        if let Some(header) = format::parse_header(&prefix)? {
+            return Ok(header);
+        }
+        if prefix.len() < want || want >= MAX_HEADER_READ {
+            return Err(AegisError::InvalidFormat);
+        }
+        want = (want * 2).min(MAX_HEADER_READ);
+    }
+}
+
+/// Checks image bytes `range` of the chunked container read from `reader`,
+/// reading only its header and the chunks the range overlaps. Fails if the
+/// container is not chunked or the range is outside the image.
+#[cfg(feature = "verifier")]
+pub fn verify_range<R: Read + Seek>(reader: &mut R, range: Range<u64>) -> Result<RangeReport, AegisError> {
+    reader.seek(SeekFrom::Start(0))?;
+    let header = read_header(reader)?;
+    let table = header
+        .header
+        .image_chunks()?
+        .ok_or_else(|| AegisError::InvalidChunks("the container's image is not chunked".into()))?;
+    if table.image_len != header.image_len {
+        return Err(AegisError::InvalidChunks(format!(
+            "the chunk table is for {} bytes but the image has {}",
+            table.image_len, header.image_len
+        )));
+    }
+    if range.start > range.end || range.end > table.image_len {
+        return Err(AegisError::InvalidChunks(format!(
+            "range {}..{} is outside the image's {} bytes",
+            range.start, range.end, table.image_len
+        )));
+    }
+
+    let scheme = SignatureScheme::of(&header.header)?;
+    let digest = crypto::container_digest(&header.header, &table.contents_digest(&header.metadata))?;
+    let signature_valid = scheme.verify_digest(&header.public_key, &header.signature, &digest)?;
+
+    let chunks = table.chunks_covering(&range);
+    let mut range_valid = true;
+    let mut bytes_read = 0;
+    let mut data = Vec::with_capacity((range.end - range.start) as usize);
+    if !chunks.is_empty() {
+        reader.seek(SeekFrom::Start(header.header_len + table.chunk_span(chunks.start).start))?;
+    }
+    let mut chunk = Vec::with_capacity(table.chunk_size as usize);
+    for index in chunks.clone() {
+        let span = table.chunk_span(index);
+        chunk.clear();
+        (&mut *reader).take(span.end - span.start).read_to_end(&mut chunk)?;
+        if chunk.len() as u64 != span.end - span.start {
+            return Err(AegisError::InvalidFormat);
+        }
+        bytes_read += chunk.len() as u64;
+        range_valid &= <[u8; 32]>::from(Sha256::digest(&chunk)) == table.hashes[index as usize];
+        let from = range.start.max(span.start) - span.start;
+        let to = range.end.min(span.end) - span.start;
+        data.extend_from_slice(&chunk[from as usize..to as usize]);
+    }
+
+    let external = format::external_metadata_digest(&header.metadata)
+        .map(|_| header.header.resolve_metadata(&header.metadata));
+    Ok(RangeReport {
+        signature_valid,
+        range_valid,
+        key_fingerprint: Fingerprint::of(&header.public_key).to_hex(),
+        key_id: header.header.key_id().map(str::to_string),
+        public_key: header.public_key.clone(),
+        metadata: match &external {
+            Some(Ok(document)) => document.clone(),
+            _ => header.metadata,
+        },
+        external_metadata_valid: external.map(|document| document.is_ok()),
+        start: range.start,
+        end: range.end,
+        image_len: table.image_len,
+        chunk_size: table.chunk_size,
+        chunks,
+        chunk_count: table.hashes.len() as u64,
+        bytes_read,
+        data,
+    })
+}
+
+#[cfg(all(test, feature = "sealer", feature = "verifier"))]
+mod tests {
+    use super::*;
+    use crate::{format::FormatHeader, test_util::test_signing_key};
+    use std::io::Cursor;
+
+    fn sealed(header: FormatHeader, metadata: String) -> format::AegisAncient {
+        let image: Vec<u8> = (0..10_000u32).map(|i| i as u8).collect();
+        crypto::seal_chunked(header, metadata, image, MIN_CHUNK_SIZE, &test_signing_key(0)).unwrap()
+    }
+
+    #[test]
+    fn verifies_a_range() {
+        let ancient = sealed(FormatHeader::default(), "{}".into());
+        let report = verify_range(&mut Cursor::new(ancient.to_bytes().unwrap()), 5000..9000).unwrap();
+        assert!(report.signature_valid && report.range_valid);
+        assert_eq!(report.data, ancient.image_data[5000..9000]);
+        assert_eq!(report.chunks, 1..3);
+        assert_eq!(report.external_metadata_valid, None);
+    }
+
+    #[test]
+    fn reports_altered_external_metadata() {
+        let mut header = FormatHeader::default();
+        let reference = header.set_external_metadata(r#"{"title":"signed"}"#);
+        let mut ancient = sealed(header, reference.clone());
+        let report = verify_range(&mut Cursor::new(ancient.to_bytes().unwrap()), 0..100).unwrap();
+        assert_eq!(report.external_metadata_valid, Some(true));
+        assert_eq!(report.metadata, r#"{"title":"signed"}"#);
+
+        // The document is not itself signed, so the signature still holds.
+        ancient.header.set_external_metadata(r#"{"title":"forged"}"#);
+        let report = verify_range(&mut Cursor::new(ancient.to_bytes().unwrap()), 0..100).unwrap();
+        assert!(report.signature_valid && report.range_valid);
+        assert_eq!(report.external_metadata_valid, Some(false));
+        assert_eq!(report.metadata, reference);
+    }
+
+    #[test]
+    fn refuses_a_header_past_the_read_limit() {
+        let ancient = sealed(FormatHeader::default(), "x".repeat(MAX_HEADER_READ));
+        assert!(matches!(
+            verify_range(&mut Cursor::new(ancient.to_bytes().unwrap()), 0..100),
+            Err(AegisError::InvalidFormat)
+        ));
+    }
+}
@@ -0,0 +1,1132 @@
+// aegis-core/src/crypto.rs
+
+use crate::chunked::{ChunkHasher, ChunkTable};
+use crate::format::AegisAncient;
+#[cfg(any(feature = "sealer", feature = "verifier"))]
+use crate::error::AegisError;
+use p256::ecdsa::{Signature, VerifyingKey};
+use sha2::{Digest, Sha256};
+use std::io::{self, Write};
+#[cfg(any(feature = "sealer", feature = "verifier"))]
+use {
+    crate::format::{self, DetachedSignature},
+    std::io::Read,
+};
+#[cfg(feature = "sealer")]
+use {
+    p256::ecdsa::signature::{Keypair, Signer},
+    std::io::{Seek, SeekFrom},
+};
+#[cfg(feature = "verifier")]
+use {
+    crate::keys::Fingerprint,
+    crate::text::{TextRecord, TextReport},
+    p256::ecdsa::signature::Verifier,
+    serde::Serialize,
+};
+
+pub const SIGNATURE_ALGORITHM: &str = "ECDSA over NIST P-256 with SHA-256 (RFC 6979 deterministic nonces)";
+
+/// A signature algorithm a container can be sealed with, recorded in the
+/// `FIELD_SIGNATURE_SCHEME` header field. Verification picks the scheme from
+/// the container, so adding a variant here (and its `verify_digest` arm) is
+/// all a new key type needs on the reading side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureScheme {
+    EcdsaP256,
+}
+
+impl SignatureScheme {
+    pub const ALL: [SignatureScheme; 1] = [SignatureScheme::EcdsaP256];
+
+    /// The ID stored in the container header.
+    pub fn id(self) -> u16 {
+        match self {
+            SignatureScheme::EcdsaP256 => 1,
+        }
+    }
+
+    pub fn from_id(id: u16) -> Option<Self> {
+        Self::ALL.into_iter().find(|s| s.id() == id)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SignatureScheme::EcdsaP256 => "ecdsa-p256",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            SignatureScheme::EcdsaP256 => SIGNATURE_ALGORITHM,
+        }
+    }
+
+    /// The scheme a container was sealed with. Containers without the
+    /// header field are ECDSA P-256.
+    #[cfg(feature = "verifier")]
+    pub fn of(header: &crate::format::FormatHeader) -> Result<Self, AegisError> {
+        match header.field(crate::format::FIELD_SIGNATURE_SCHEME) {
+            None => Ok(SignatureScheme::EcdsaP256),
+            Some(_) => {
+                let id = header.scheme_id().ok_or(AegisError::InvalidFormat)?;
+                Self::from_id(id).ok_or(AegisError::UnsupportedScheme(id))
+            }
+        }
+    }
+
+    /// Checks `signature` over `digest` under this scheme.
+    #[cfg(feature = "verifier")]
+    pub fn verify_digest(self, public_key: &[u8], signature: &[u8], digest: &[u8; 32]) -> Result<bool, AegisError> {
+        match self {
+            SignatureScheme::EcdsaP256 => {
+                let key = VerifyingKey::from_sec1_bytes(public_key)
+                    .map_err(|e| AegisError::Crypto(format!("invalid public key: {}", e)))?;
+                let signature = Signature::from_slice(signature)
+                    .map_err(|e| AegisError::Crypto(format!("invalid signature encoding: {}", e)))?;
+                Ok(key.verify(digest, &signature).is_ok())
+            }
+        }
+    }
+}
+
+/// The kind of object a signature is over. One key may sign containers,
+/// bundle manifests, feeds and telemetry, so every object other than a
+/// container is signed as its context's prefix followed by its bytes, and
+/// verified only under the context the caller expects: a signature over one
+/// kind of object never checks out as another. New signed objects get a
+/// variant of their own.
+///
+/// Containers keep the unprefixed 32-byte `signing_digest()` they have always
+/// been signed over. That message is a SHA-256 output, so passing a prefixed
+/// message off as a container needs a preimage of it, and passing a container
+/// signature off as anything else needs a digest that starts with a prefix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureContext {
+    Container,
+    BundleManifest,
+    Feed,
+    Telemetry,
+    /// The claim of a C2PA manifest (see `c2pa`).
+    C2paClaim,
+    /// A co-signer's statement about a container (see `cosignature_object()`).
+    Cosignature,
+    /// An issuer's endorsement of another key (see `endorsement`).
+    Endorsement,
+    /// A transparency log's signed tree head (see `merkle`).
+    TreeHead,
+    /// The claims of a delegation token (see `delegation`).
+    Delegation,
+    /// The PAE of a DSSE envelope (see `intoto`).
+    Dsse,
+    /// A day's signed rollup of seals (see `rollup`).
+    Rollup,
+}
+
+impl SignatureContext {
+    pub const ALL: [SignatureContext; 11] = [
+        SignatureContext::Container,
+        SignatureContext::BundleManifest,
+        SignatureContext::Feed,
+        SignatureContext::Telemetry,
+        SignatureContext::C2paClaim,
+        SignatureContext::Cosignature,
+        SignatureContext::Endorsement,
+        SignatureContext::TreeHead,
+        SignatureContext::Delegation,
+        SignatureContext::Dsse,
+        SignatureContext::Rollup,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SignatureContext::Container => "container",
+            SignatureContext::BundleManifest => "bundle-manifest",
+            SignatureContext::Feed => "feed",
+            SignatureContext::Telemetry => "telemetry",
+            SignatureContext::C2paClaim => "c2pa-claim",
+            SignatureContext::Cosignature => "cosignature",
+            SignatureContext::Endorsement => "endorsement",
+            SignatureContext::TreeHead => "tree-head",
+            SignatureContext::Delegation => "delegation",
+            SignatureContext::Dsse => "dsse",
+            SignatureContext::Rollup => "rollup",
+        }
+    }
+
+    /// Bytes prepended to the object before signing; empty for containers,
+    /// for C2PA claims, which COSE signs as a `Sig_structure` that starts
+    /// with its own `Signature1` context, and for DSSE envelopes, whose PAE
+    /// starts with `DSSEv1`. Each other prefix ends in a NUL, so none is a
+    /// prefix of another.
+    pub fn prefix(self) -> &'static [u8] {
+        match self {
+            SignatureContext::Container => b"",
+            SignatureContext::BundleManifest => b"aegis/bundle-manifest/v1\0",
+            SignatureContext::Feed => b"aegis/feed/v1\0",
+            SignatureContext::Telemetry => b"aegis/telemetry/v1\0",
+            SignatureContext::C2paClaim => b"",
+            SignatureContext::Cosignature => b"aegis/cosignature/v1\0",
+            SignatureContext::Endorsement => b"aegis/endorsement/v1\0",
+            SignatureContext::TreeHead => b"aegis/tree-head/v1\0",
+            SignatureContext::Delegation => b"aegis/delegation/v1\0",
+            SignatureContext::Dsse => b"",
+            SignatureContext::Rollup => b"aegis/rollup/v1\0",
+        }
+    }
+
+    /// The message actually signed for `object`.
+    pub fn message(self, object: &[u8]) -> Vec<u8> {
+        [self.prefix(), object].concat()
+    }
+
+    /// Signs `object` in this context.
+    #[cfg(feature = "sealer")]
+    pub fn sign<S: Signer<Signature>>(self, object: &[u8], private_key: &S) -> Result<Signature, AegisError> {
+        private_key
+            .try_sign(&self.message(object))
+            .map_err(|e| AegisError::Crypto(e.to_string()))
+    }
+
+    /// Checks that `signature` is over `object` in this context. A container
+    /// context only accepts a 32-byte digest, a C2PA claim context only a
+    /// COSE `Sig_structure`, and a DSSE context only a PAE.
+    #[cfg(feature = "verifier")]
+    pub fn verify(self, public_key: &VerifyingKey, object: &[u8], signature: &Signature) -> Result<bool, AegisError> {
+        if self == SignatureContext::Container && object.len() != 32 {
+            return Err(AegisError::Crypto("container signatures are over a 32-byte digest".into()));
+        }
+        if self == SignatureContext::C2paClaim && !object.starts_with(crate::c2pa::SIG_STRUCTURE_HEADER) {
+            return Err(AegisError::Crypto("C2PA claim signatures are over a COSE Sig_structure".into()));
+        }
+        if self == SignatureContext::Dsse && !object.starts_with(crate::intoto::PAE_PREAMBLE) {
+            return Err(AegisError::Crypto("DSSE signatures are over a pre-authentication encoding".into()));
+        }
+        Ok(public_key.verify(&self.message(object), signature).is_ok())
+    }
+}
+
+pub const DIGEST_ALGORITHM: &str = "SHA-256";
+/// The fields hashed, in order, to produce the signed digest.
+pub const SIGNED_FIELDS: [&str; 2] = ["metadata", "image_data"];
+
+/// Computes the message that is signed for the given contents: the SHA-256
+/// digest of the metadata followed by the image bytes.
+pub fn signing_digest(metadata: &str, image_data: &[u8]) -> [u8; 32] {
+    let mut hasher = SigningHasher::new(metadata);
+    hasher.update(image_data);
+    hasher.finalize()
+}
+
+/// Hashed in front of the two digests combined by `container_digest()`.
+pub const EXTENSIONS_DIGEST_PREFIX: &[u8] = b"aegis/extensions/v1\0";
+
+/// The digest a container's signature is over: `contents_digest` (from
+/// `signing_digest()` or `SigningHasher`) as it is, or, for a header with
+/// `FLAG_SIGNED_EXTENSIONS`, the SHA-256 of `EXTENSIONS_DIGEST_PREFIX`,
+/// `contents_digest` and the SHA-256 of the extensions field. Fails if the
+/// extensions field and flag disagree.
+#[cfg(any(feature = "sealer", feature = "verifier"))]
+pub fn container_digest(header: &format::FormatHeader, contents_digest: &[u8; 32]) -> Result<[u8; 32], AegisError> {
+    let signed = header.flags & format::FLAG_SIGNED_EXTENSIONS != 0;
+    let field = match (header.field(format::FIELD_EXTENSIONS), signed) {
+        (None, false) => return Ok(*contents_digest),
+        (Some(field), true) => field,
+        _ => return Err(AegisError::InvalidExtension("extensions field and flag disagree".into())),
+    };
+    let mut hasher = Sha256::new();
+    hasher.update(EXTENSIONS_DIGEST_PREFIX);
+    hasher.update(contents_digest);
+    hasher.update(Sha256::digest(field));
+    Ok(hasher.finalize().into())
+}
+
+/// What a co-signer signs, in `SignatureContext::Cosignature`: the
+/// `container_digest()`, the SHA-256 of the sealing key (SEC1), the role as
+/// a 2-byte big-endian length and its UTF-8 bytes, and `signed_at` as an
+/// 8-byte big-endian Unix time. The co-signer thereby vouches for the same
+/// contents as the sealer, under that sealer, in that role, at that time.
+pub fn cosignature_object(digest: &[u8; 32], sealing_key: &[u8], role: &str, signed_at: i64) -> Vec<u8> {
+    let mut object = Vec::with_capacity(32 + 32 + 2 + role.len() + 8);
+    object.extend_from_slice(digest);
+    object.extend_from_slice(&Sha256::digest(sealing_key));
+    object.extend_from_slice(&(role.len() as u16).to_be_bytes());
+    object.extend_from_slice(role.as_bytes());
+    object.extend_from_slice(&signed_at.to_be_bytes());
+    object
+}
+
+/// Incremental form of `signing_digest()` for images that arrive in pieces,
+/// or with `chunked()` or `for_header()`, of `ChunkTable::contents_digest()`
+/// for a chunked image (see `chunked`). Implements `Write`, so a reader can
+/// be hashed with `io::copy`.
+pub struct SigningHasher {
+    layout: Layout,
+    image_len: u64,
+}
+
+enum Layout {
+    Flat(Sha256),
+    Chunked { metadata: String, chunks: ChunkHasher },
+}
+
+impl SigningHasher {
+    pub fn new(metadata: &str) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(metadata.as_bytes());
+        SigningHasher { layout: Layout::Flat(hasher), image_len: 0 }
+    }
+
+    /// Hashes the image in chunks of `chunk_size` bytes.
+    pub fn chunked(metadata: &str, chunk_size: u32) -> Result<Self, crate::error::AegisError> {
+        let chunks = ChunkHasher::new(chunk_size)?;
+        Ok(SigningHasher { layout: Layout::Chunked { metadata: metadata.to_string(), chunks }, image_len: 0 })
+    }
+
+    /// Hashes the image the way a container with `header` signs it: in the
+    /// chunks its `format::FIELD_IMAGE_CHUNKS` names, or whole.
+    #[cfg(any(feature = "sealer", feature = "verifier"))]
+    pub fn for_header(header: &format::FormatHeader, metadata: &str) -> Result<Self, AegisError> {
+        match header.image_chunks()? {
+            Some(table) => SigningHasher::chunked(metadata, table.chunk_size),
+            None => Ok(SigningHasher::new(metadata)),
+        }
+    }
+
+    pub fn update(&mut self, image_chunk: &[u8]) {
+        match &mut self.layout {
+            Layout::Flat(hasher) => hasher.update(image_chunk),
+            Layout::Chunked { chunks, .. } => chunks.update(image_chunk),
+        }
+        self.image_len += image_chunk.len() as u64;
+    }
+
+    /// Image bytes hashed so far.
+    pub fn image_len(&self) -> u64 {
+        self.image_len
+    }
+
+    pub fn finalize(self) -> [u8; 32] {
+        self.finish().0
+    }
+
+    /// The digest and, for a chunked image, the chunk table to store with
+    /// `FormatHeader::set_image_chunks()`.
+    pub fn finish(self) -> ([u8; 32], Option<ChunkTable>) {
+        match self.layout {
+            Layout::Flat(hasher) => (hasher.finalize().into(), None),
+            Layout::Chunked { metadata, chunks } => {
+                let table = chunks.finish();
+                (table.contents_digest(&metadata), Some(table))
+            }
+        }
+    }
+}
+
+/// The `signing_digest()` of a container with `header`, or for a chunked
+/// image `ChunkTable::contents_digest()` of the chunks hashed afresh.
+#[cfg(any(feature = "sealer", feature = "verifier"))]
+pub fn contents_digest(header: &format::FormatHeader, metadata: &str, image_data: &[u8]) -> Result<[u8; 32], AegisError> {
+    let mut hasher = SigningHasher::for_header(header, metadata)?;
+    hasher.update(image_data);
+    Ok(hasher.finalize())
+}
+
+impl Write for SigningHasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Signs a digest from `signing_digest()` or `SigningHasher`.
+#[cfg(feature = "sealer")]
+pub fn sign_digest<S: Signer<Signature>>(digest: &[u8; 32], private_key: &S) -> Result<Signature, AegisError> {
+    private_key
+        .try_sign(digest)
+        .map_err(|e| AegisError::Crypto(e.to_string()))
+}
+
+/// Packages contents with a signature produced elsewhere (for example by a
+/// remote signing service) over `signing_digest()`.
+pub fn assemble(
+    metadata: String,
+    image_data: Vec<u8>,
+    public_key: &VerifyingKey,
+    signature: &Signature,
+) -> AegisAncient {
+    AegisAncient {
+        version: crate::format::CURRENT_VERSION,
+        header: crate::format::FormatHeader::default(),
+        public_key: public_key.to_sec1_bytes().into_vec(),
+        metadata,
+        signature: signature.to_bytes().to_vec(),
+        image_data,
+    }
+}
+
+/// Co-signs the container whose `container_digest()` is `digest` and which
+/// was sealed by `sealing_key` (SEC1), for storing with
+/// `FormatHeader::add_cosignature()`. For containers too large to hold in
+/// memory; `countersign()` does the same for an `AegisAncient`.
+#[cfg(feature = "sealer")]
+pub fn cosign<S>(
+    digest: &[u8; 32],
+    sealing_key: &[u8],
+    role: &str,
+    signed_at: i64,
+    private_key: &S,
+) -> Result<format::Cosignature, AegisError>
+where
+    S: Signer<Signature> + Keypair<VerifyingKey = VerifyingKey>,
+{
+    let object = cosignature_object(digest, sealing_key, role, signed_at);
+    let signature = SignatureContext::Cosignature.sign(&object, private_key)?;
+    Ok(format::Cosignature {
+        public_key: private_key.verifying_key().to_sec1_bytes().into_vec(),
+        signature: signature.to_bytes().to_vec(),
+        role: role.to_string(),
+        signed_at,
+    })
+}
+
+/// Adds `private_key`'s signature to a sealed container as a co-signer in
+/// `role` (e.g. the agency, after the photographer sealed it), leaving the
+/// original signature and any earlier co-signatures in place. A version 1
+/// container is upgraded to the current version, which has room for the
+/// co-signature; its own signature is unaffected.
+#[cfg(feature = "sealer")]
+pub fn countersign<S>(ancient: &mut AegisAncient, role: &str, signed_at: i64, private_key: &S) -> Result<(), AegisError>
+where
+    S: Signer<Signature> + Keypair<VerifyingKey = VerifyingKey>,
+{
+    let digest = container_digest(&ancient.header, &contents_digest(&ancient.header, &ancient.metadata, &ancient.image_data)?)?;
+    let cosignature = cosign(&digest, &ancient.public_key, role, signed_at, private_key)?;
+    ancient.header.add_cosignature(&cosignature)?;
+    ancient.version = format::CURRENT_VERSION;
+    Ok(())
+}
+
+/// Hashes, signs, and packages the data into an AegisAncient struct.
+///
+/// Any P-256 signer works here; in practice this is a `SigningKey`.
+#[cfg(feature = "sealer")]
+pub fn seal<S>(
+    metadata: String,
+    image_data: Vec<u8>,
+    private_key: &S,
+) -> Result<AegisAncient, AegisError>
+where
+    S: Signer<Signature> + Keypair<VerifyingKey = VerifyingKey>,
+{
+    let signature = sign_digest(&signing_digest(&metadata, &image_data), private_key)?;
+    Ok(assemble(
+        metadata,
+        image_data,
+        &private_key.verifying_key(),
+        &signature,
+    ))
+}
+
+/// Like `seal()`, with `header` as the container's header block. Signed
+/// extensions in it are covered by the signature (see `container_digest()`).
+#[cfg(feature = "sealer")]
+pub fn seal_with_header<S>(
+    header: format::FormatHeader,
+    metadata: String,
+    image_data: Vec<u8>,
+    private_key: &S,
+) -> Result<AegisAncient, AegisError>
+where
+    S: Signer<Signature> + Keypair<VerifyingKey = VerifyingKey>,
+{
+    let digest = container_digest(&header, &contents_digest(&header, &metadata, &image_data)?)?;
+    let signature = sign_digest(&digest, private_key)?;
+    let mut ancient = assemble(metadata, image_data, &private_key.verifying_key(), &signature);
+    ancient.header = header;
+    Ok(ancient)
+}
+
+/// Like `seal_with_header()`, signing the image in chunks of `chunk_size`
+/// bytes so that any range of it can be verified on its own (see `chunked`).
+#[cfg(feature = "sealer")]
+pub fn seal_chunked<S>(
+    mut header: format::FormatHeader,
+    metadata: String,
+    image_data: Vec<u8>,
+    chunk_size: u32,
+    private_key: &S,
+) -> Result<AegisAncient, AegisError>
+where
+    S: Signer<Signature> + Keypair<VerifyingKey = VerifyingKey>,
+{
+    header.set_image_chunks(&ChunkTable::of(&image_data, chunk_size)?);
+    seal_with_header(header, metadata, image_data, private_key)
+}
+
+/// Like `seal()`, keeping `document` in the header as external metadata and
+/// signing only a reference to it (see `format::FIELD_EXTERNAL_METADATA`).
+#[cfg(feature = "sealer")]
+pub fn seal_external_metadata<S>(document: &str, image_data: Vec<u8>, private_key: &S) -> Result<AegisAncient, AegisError>
+where
+    S: Signer<Signature> + Keypair<VerifyingKey = VerifyingKey>,
+{
+    let mut header = format::FormatHeader::default();
+    let metadata = header.set_external_metadata(document);
+    seal_with_header(header, metadata, image_data, private_key)
+}
+
+/// Like `seal()`, embedding `chain` (leaf first) so verifiers can tie the
+/// seal to the organization its leaf certificate names. The leaf must
+/// certify `private_key`'s public key.
+#[cfg(feature = "sealer")]
+pub fn seal_certified<S>(
+    chain: &[crate::x509::Certificate],
+    metadata: String,
+    image_data: Vec<u8>,
+    private_key: &S,
+) -> Result<AegisAncient, AegisError>
+where
+    S: Signer<Signature> + Keypair<VerifyingKey = VerifyingKey>,
+{
+    crate::x509::check_leaf(chain, &private_key.verifying_key().to_sec1_bytes())?;
+    let mut header = format::FormatHeader::default();
+    header.set_certificate_chain(chain);
+    seal_with_header(header, metadata, image_data, private_key)
+}
+
+/// Like `seal()`, then has a time-stamping authority attest to the time.
+/// `tsa` receives the DER `TimeStampReq` and returns the DER
+/// `TimeStampResp`, e.g. by POSTing it to the TSA's URL with content type
+/// `timestamp::REQUEST_CONTENT_TYPE`. The token is stored in the container
+/// header.
+#[cfg(feature = "sealer")]
+pub fn seal_timestamped<S, T>(
+    metadata: String,
+    image_data: Vec<u8>,
+    private_key: &S,
+    tsa: T,
+) -> Result<AegisAncient, AegisError>
+where
+    S: Signer<Signature> + Keypair<VerifyingKey = VerifyingKey>,
+    T: FnOnce(&[u8]) -> Result<Vec<u8>, AegisError>,
+{
+    let mut ancient = seal(metadata, image_data, private_key)?;
+    let request = crate::timestamp::TimestampRequest::new(&ancient.signature);
+    let token = request.accept(&tsa(request.to_der())?)?;
+    ancient.header.set_timestamp_token(token);
+    Ok(ancient)
+}
+
+/// Seals an image read from `input` straight into `output` without holding
+/// it in memory. The signature precedes the image in the container, so the
+/// input is read twice: once to hash it, then again to copy it out. Returns
+/// the number of container bytes written.
+#[cfg(feature = "sealer")]
+pub fn seal_stream<R, W, S>(
+    metadata: &str,
+    input: &mut R,
+    output: &mut W,
+    private_key: &S,
+) -> Result<u64, AegisError>
+where
+    R: Read + Seek,
+    W: Write,
+    S: Signer<Signature> + Keypair<VerifyingKey = VerifyingKey>,
+{
+    seal_stream_with_header(&format::FormatHeader::default(), metadata, input, output, private_key)
+}
+
+/// Like `seal_stream()`, writing `header` as the container's header block,
+/// e.g. one holding an external metadata document or signed extensions.
+#[cfg(feature = "sealer")]
+pub fn seal_stream_with_header<R, W, S>(
+    header: &format::FormatHeader,
+    metadata: &str,
+    input: &mut R,
+    output: &mut W,
+    private_key: &S,
+) -> Result<u64, AegisError>
+where
+    R: Read + Seek,
+    W: Write,
+    S: Signer<Signature> + Keypair<VerifyingKey = VerifyingKey>,
+{
+    seal_stream_hashed(header.clone(), SigningHasher::for_header(header, metadata)?, metadata, input, output, private_key)
+}
+
+/// Like `seal_stream_with_header()`, signing the image in chunks of
+/// `chunk_size` bytes (see `seal_chunked()`).
+#[cfg(feature = "sealer")]
+pub fn seal_stream_chunked<R, W, S>(
+    header: &format::FormatHeader,
+    metadata: &str,
+    chunk_size: u32,
+    input: &mut R,
+    output: &mut W,
+    private_key: &S,
+) -> Result<u64, AegisError>
+where
+    R: Read + Seek,
+    W: Write,
+    S: Signer<Signature> + Keypair<VerifyingKey = VerifyingKey>,
+{
+    seal_stream_hashed(header.clone(), SigningHasher::chunked(metadata, chunk_size)?, metadata, input, output, private_key)
+}
+
+#[cfg(feature = "sealer")]
+fn seal_stream_hashed<R, W, S>(
+    mut header: format::FormatHeader,
+    mut hasher: SigningHasher,
+    metadata: &str,
+    input: &mut R,
+    output: &mut W,
+    private_key: &S,
+) -> Result<u64, AegisError>
+where
+    R: Read + Seek,
+    W: Write,
+    S: Signer<Signature> + Keypair<VerifyingKey = VerifyingKey>,
+{
+    if header.compression().image {
+        // The image is copied to the output as it is read.
+        return Err(AegisError::Crypto("a streamed image cannot be compressed; seal it in memory".into()));
+    }
+    let start = input.stream_position()?;
+    io::copy(input, &mut hasher)?;
+    let image_len = hasher.image_len();
+    let (contents, chunks) = hasher.finish();
+    if let Some(table) = &chunks {
+        header.set_image_chunks(table);
+    }
+    let signature = sign_digest(&container_digest(&header, &contents)?, private_key)?;
+
+    input.seek(SeekFrom::Start(start))?;
+    let header = format::header_bytes(
+        &header,
+        &private_key.verifying_key().to_sec1_bytes(),
+        metadata,
+        &signature.to_bytes(),
+        image_len,
+    );
+    output.write_all(&header)?;
+    let copied = io::copy(&mut input.take(image_len), output)?;
+    if copied != image_len {
+        // The input shrank between the two passes; what was written does
+        // not match what was signed.
+        return Err(AegisError::Crypto("input changed while sealing".into()));
+    }
+    Ok(header.len() as u64 + image_len)
+}
+
+/// Signs the image read from `input` without embedding it, producing a
+/// detached signature. The input is read once.
+#[cfg(feature = "sealer")]
+pub fn seal_detached<R, S>(metadata: &str, input: &mut R, private_key: &S) -> Result<DetachedSignature, AegisError>
+where
+    R: Read,
+    S: Signer<Signature> + Keypair<VerifyingKey = VerifyingKey>,
+{
+    let mut hashers = DetachedHasher::new(metadata);
+    io::copy(input, &mut hashers)?;
+    let image_len = hashers.signing.image_len();
+    let image_sha256 = hashers.image.finalize().into();
+    let signature = sign_digest(&hashers.signing.finalize(), private_key)?;
+    Ok(DetachedSignature {
+        public_key: private_key.verifying_key().to_sec1_bytes().into_vec(),
+        metadata: metadata.to_string(),
+        signature: signature.to_bytes().to_vec(),
+        image_sha256,
+        image_len,
+    })
+}
+
+/// Hashes an image for both the signature and the plain SHA-256 recorded in
+/// a detached signature.
+#[cfg(any(feature = "sealer", feature = "verifier"))]
+struct DetachedHasher {
+    signing: SigningHasher,
+    image: Sha256,
+    /// For checking a text-mode copy (see `text`).
+    text: Option<crate::text::LineEndingHasher>,
+}
+
+#[cfg(any(feature = "sealer", feature = "verifier"))]
+impl DetachedHasher {
+    fn new(metadata: &str) -> Self {
+        DetachedHasher {
+            signing: SigningHasher::new(metadata),
+            image: Sha256::new(),
+            text: None,
+        }
+    }
+}
+
+#[cfg(any(feature = "sealer", feature = "verifier"))]
+impl Write for DetachedHasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.signing.update(buf);
+        self.image.update(buf);
+        if let Some(text) = &mut self.text {
+            text.update(buf);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The outcome of checking a container's signature against its embedded key.
+#[cfg(feature = "verifier")]
+#[derive(Debug, Clone, Serialize)]
+pub struct VerificationReport {
+    pub signature_valid: bool,
+    /// Hex SHA-256 fingerprint of the embedded SEC1 public key.
+    pub key_fingerprint: String,
+    /// Keyring key ID from the container header, if any.
+    pub key_id: Option<String>,
+    /// `SignatureScheme::name()` of the scheme the signature was checked under.
+    pub signature_scheme: &'static str,
+    /// The RFC 3161 timestamp from the container header, if it has one.
+    pub timestamp: Option<crate::timestamp::TimestampReport>,
+    /// Hex `container_digest()` recomputed from the container's contents.
+    pub digest: String,
+    /// The signed metadata, or the external document it refers to if that
+    /// is present and matches.
+    pub metadata: String,
+    /// For metadata kept in the header, whether the document matches the
+    /// signed reference.
+    pub external_metadata_valid: Option<bool>,
+    /// Signed extensions, as `Extension::to_json()` gives them.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extensions: Vec<serde_json::Value>,
+    /// Each co-signer's status, in the order they signed.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cosigners: Vec<CosignerReport>,
+    /// The X.509 chain from the container header, if it carries one (see
+    /// `verify_with_anchors()`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub certificate_chain: Option<crate::x509::ChainReport>,
+    pub payload_size: usize,
+    /// The payload's media type: as declared in the signed metadata, or else
+    /// as recognized from the payload itself (see `media_type`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    /// For a seal made in text mode, what its metadata records about the
+    /// document.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<crate::text::TextReport>,
+}
+
+/// The outcome of checking one co-signature (see `countersign()`).
+#[cfg(feature = "verifier")]
+#[derive(Debug, Clone, Serialize)]
+pub struct CosignerReport {
+    pub role: String,
+    /// Hex SHA-256 fingerprint of the co-signer's SEC1 public key.
+    pub key_fingerprint: String,
+    pub signed_at: i64,
+    /// False for a signature that does not verify, including one whose key
+    /// or signature cannot be decoded.
+    pub signature_valid: bool,
+}
+
+/// Checks the co-signatures in `header` against the container digest and
+/// sealing key they claim to cover.
+#[cfg(feature = "verifier")]
+pub fn verify_cosignatures(
+    header: &format::FormatHeader,
+    sealing_key: &[u8],
+    digest: &[u8; 32],
+) -> Result<Vec<CosignerReport>, AegisError> {
+    Ok(header
+        .cosignatures()?
+        .into_iter()
+        .map(|c| {
+            let object = cosignature_object(digest, sealing_key, &c.role, c.signed_at);
+            let signature_valid = match (VerifyingKey::from_sec1_bytes(&c.public_key), Signature::from_slice(&c.signature)) {
+                (Ok(key), Ok(signature)) => SignatureContext::Cosignature.verify(&key, &object, &signature).unwrap_or(false),
+                _ => false,
+            };
+            CosignerReport {
+                key_fingerprint: Fingerprint::of(&c.public_key).to_hex(),
+                role: c.role,
+                signed_at: c.signed_at,
+                signature_valid,
+            }
+        })
+        .collect())
+}
+
+#[cfg(feature = "verifier")]
+impl VerificationReport {
+    /// The metadata as a `metadata::Metadata` document, `None` if it is
+    /// free-form rather than a JSON object.
+    pub fn structured_metadata(&self) -> Result<Option<crate::metadata::Metadata>, AegisError> {
+        crate::metadata::Metadata::parse_structured(&self.metadata)
+    }
+}
+
+/// Recomputes the signed digest and checks the signature against the
+/// embedded public key. A well-formed container with a bad signature yields
+/// a report with `signature_valid: false`; an undecodable key or signature
+/// is an error. No TSA is trusted, so a timestamp is reported unverified;
+/// `verify_with_anchors()` checks it against trusted TSAs.
+#[cfg(feature = "verifier")]
+pub fn verify(ancient: &AegisAncient) -> Result<VerificationReport, AegisError> {
+    let scheme = SignatureScheme::of(&ancient.header)?;
+    let digest = container_digest(&ancient.header, &contents_digest(&ancient.header, &ancient.metadata, &ancient.image_data)?)?;
+    let extensions = ancient.header.extensions()?;
+    let external = format::external_metadata_digest(&ancient.metadata).map(|_| ancient.header.resolve_metadata(&ancient.metadata));
+    Ok(VerificationReport {
+        signature_valid: scheme.verify_digest(&ancient.public_key, &ancient.signature, &digest)?,
+        key_fingerprint: Fingerprint::of(&ancient.public_key).to_hex(),
+        key_id: ancient.header.key_id().map(str::to_string),
+        signature_scheme: scheme.name(),
+        timestamp: ancient
+            .header
+            .timestamp_token()
+            .map(|token| crate::timestamp::verify(token, &ancient.signature, &Default::default())),
+        digest: hex::encode(digest),
+        metadata: match &external {
+            Some(Ok(document)) => document.clone(),
+            _ => ancient.metadata.clone(),
+        },
+        external_metadata_valid: external.map(|document| document.is_ok()),
+        extensions: extensions.iter().map(format::Extension::to_json).collect(),
+        cosigners: verify_cosignatures(&ancient.header, &ancient.public_key, &digest)?,
+        certificate_chain: None,
+        payload_size: ancient.image_data.len(),
+        media_type: crate::media_type::from_metadata(&ancient.metadata)
+            .or_else(|| crate::media_type::sniff(&ancient.image_data).map(str::to_string)),
+        text: TextRecord::from_metadata(&ancient.metadata)
+            .map(|record| TextReport { record, differs_only_in_line_endings: false }),
+    })
+}
+
+/// Like `verify()`, also checking the container's timestamp against the
+/// TSAs `tsa` trusts and its certificate chain, if it has one, against
+/// `anchors`. Validity periods are checked at the time attested by a
+/// timestamp from a trusted TSA, so a seal made while its certificate was
+/// current stays valid after the certificate expires; without one they are
+/// checked at the current time.
+#[cfg(feature = "verifier")]
+pub fn verify_with_anchors(
+    ancient: &AegisAncient,
+    anchors: &crate::x509::TrustAnchors,
+    tsa: &crate::timestamp::TsaTrust,
+) -> Result<VerificationReport, AegisError> {
+    let mut report = verify(ancient)?;
+    report.timestamp = ancient
+        .header
+        .timestamp_token()
+        .map(|token| crate::timestamp::verify(token, &ancient.signature, tsa));
+    let chain = ancient.header.certificate_chain()?;
+    if chain.is_empty() {
+        return Ok(report);
+    }
+    report.certificate_chain = Some(crate::x509::verify_chain(
+        &chain,
+        &ancient.public_key,
+        anchors,
+        checked_at(ancient, &report),
+    ));
+    Ok(report)
+}
+
+/// The time to check validity periods at for a container verified as
+/// `report`: that attested by its timestamp if the token covers the
+/// signature and its TSA signature verifies against a trusted TSA, or else
+/// the current time. A timestamp from `verify()`, which trusts no TSA, never
+/// counts, so backdating a seal takes a TSA the verifier chose to trust.
+#[cfg(feature = "verifier")]
+pub fn checked_at(ancient: &AegisAncient, report: &VerificationReport) -> i64 {
+    let trusted = report.timestamp.as_ref().is_some_and(|t| {
+        t.valid && t.imprint_matches && t.tsa_signature_valid == Some(true) && t.tsa_trusted
+    });
+    let attested = ancient
+        .header
+        .timestamp_token()
+        .filter(|_| trusted)
+        .and_then(|token| crate::timestamp::TimestampToken::parse(token).ok())
+        .map(|token| token.time);
+    attested.unwrap_or_else(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0)
+    })
+}
+
+/// Checks a raw ECDSA P-256 signature over a digest computed elsewhere, for
+/// example incrementally with `SigningHasher`.
+#[cfg(feature = "verifier")]
+pub fn verify_digest(public_key: &[u8], signature: &[u8], digest: &[u8; 32]) -> Result<bool, AegisError> {
+    SignatureScheme::EcdsaP256.verify_digest(public_key, signature, digest)
+}
+
+/// Checks a detached signature against the original image read from
+/// `original`. The report's `signature_valid` is false if the image's hash
+/// or length differ from those recorded in the sidecar, or if the signature
+/// does not verify.
+#[cfg(feature = "verifier")]
+pub fn verify_detached<R: Read>(detached: &DetachedSignature, original: &mut R) -> Result<VerificationReport, AegisError> {
+    let mut hashers = DetachedHasher::new(&detached.metadata);
+    let record = TextRecord::from_metadata(&detached.metadata);
+    if record.is_some() {
+        hashers.text = Some(Default::default());
+    }
+    io::copy(original, &mut hashers)?;
+    let payload_size = hashers.signing.image_len();
+    let image_sha256: [u8; 32] = hashers.image.finalize().into();
+    let digest = hashers.signing.finalize();
+    let matches_sidecar = image_sha256 == detached.image_sha256 && payload_size == detached.image_len;
+    // A copy of a text document that fails may have had its line endings
+    // rewritten since; worth telling apart from other changes.
+    let text = record.map(|record| TextReport {
+        record,
+        differs_only_in_line_endings: !matches_sidecar
+            && hashers.text.is_some_and(|text| text.matches(&detached.image_sha256)),
+    });
+    Ok(VerificationReport {
+        signature_valid: matches_sidecar && verify_digest(&detached.public_key, &detached.signature, &digest)?,
+        key_fingerprint: Fingerprint::of(&detached.public_key).to_hex(),
+        key_id: None,
+        signature_scheme: SignatureScheme::EcdsaP256.name(),
+        timestamp: None,
+        digest: hex::encode(digest),
+        metadata: detached.metadata.clone(),
+        external_metadata_valid: None,
+        extensions: Vec::new(),
+        cosigners: Vec::new(),
+        certificate_chain: None,
+        payload_size: payload_size as usize,
+        media_type: crate::media_type::from_metadata(&detached.metadata),
+        text,
+    })
+}
+
+/// Contents released by `unseal()` once the signature has been checked.
+#[cfg(feature = "verifier")]
+#[derive(Debug, Clone)]
+pub struct Unsealed {
+    pub metadata: String,
+    pub image_data: Vec<u8>,
+}
+
+/// Returns the original metadata and image bytes, but only if the signature
+/// is valid for the embedded public key. Metadata kept in the header is
+/// returned in place of its reference, and must match it.
+#[cfg(feature = "verifier")]
+pub fn unseal(ancient: AegisAncient) -> Result<Unsealed, AegisError> {
+    if !verify(&ancient)?.signature_valid {
+        return Err(AegisError::Crypto("signature does not match contents".into()));
+    }
+    Ok(Unsealed {
+        metadata: ancient.header.resolve_metadata(&ancient.metadata)?,
+        image_data: ancient.image_data,
+    })
+}
+
+#[cfg(all(test, feature = "sealer", feature = "verifier"))]
+mod tests {
+    use super::*;
+    use crate::test_util::{
+        corrupted_bytes, sample_bytes, sample_container, test_signing_key, Corruption, TestCertificate, TestTimestamp,
+        SAMPLE_METADATA, SAMPLE_PAYLOAD,
+    };
+    use crate::timestamp::TsaTrust;
+    use crate::x509::{Certificate, TrustAnchors};
+
+    // 2024-06-01.
+    const TIME: i64 = 1_717_200_000;
+
+    fn read(bytes: &[u8]) -> Result<AegisAncient, AegisError> {
+        AegisAncient::read(&mut &bytes[..])
+    }
+
+    fn sample_digest(ancient: &AegisAncient) -> [u8; 32] {
+        container_digest(&ancient.header, &contents_digest(&ancient.header, SAMPLE_METADATA, SAMPLE_PAYLOAD).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn seals_verifies_and_unseals() {
+        let ancient = read(&sample_bytes()).unwrap();
+        let report = verify(&ancient).unwrap();
+        assert!(report.signature_valid);
+        assert_eq!(report.key_fingerprint, Fingerprint::of(&ancient.public_key).to_hex());
+        assert_eq!(report.metadata, SAMPLE_METADATA);
+        assert_eq!(report.payload_size, SAMPLE_PAYLOAD.len());
+        assert_eq!(report.external_metadata_valid, None);
+        let unsealed = unseal(ancient).unwrap();
+        assert_eq!(unsealed.metadata, SAMPLE_METADATA);
+        assert_eq!(unsealed.image_data, SAMPLE_PAYLOAD);
+    }
+
+    #[test]
+    fn streamed_seals_match_sealing_in_memory() {
+        let mut streamed = Vec::new();
+        seal_stream(SAMPLE_METADATA, &mut std::io::Cursor::new(SAMPLE_PAYLOAD), &mut streamed, &test_signing_key(0))
+            .unwrap();
+        let ancient = read(&streamed).unwrap();
+        let report = verify(&ancient).unwrap();
+        assert!(report.signature_valid);
+        assert_eq!(ancient.image_data, SAMPLE_PAYLOAD);
+        assert_eq!(report.digest, verify(&sample_container()).unwrap().digest);
+    }
+
+    #[test]
+    fn unseals_external_metadata_in_place_of_its_reference() {
+        let ancient = seal_external_metadata(r#"{"title":"kept apart"}"#, SAMPLE_PAYLOAD.to_vec(), &test_signing_key(0))
+            .unwrap();
+        let report = verify(&ancient).unwrap();
+        assert!(report.signature_valid);
+        assert_eq!(report.external_metadata_valid, Some(true));
+        assert_eq!(unseal(ancient).unwrap().metadata, r#"{"title":"kept apart"}"#);
+    }
+
+    #[test]
+    fn rejects_a_flipped_image_byte() {
+        let ancient = read(&corrupted_bytes(Corruption::Payload)).unwrap();
+        assert!(!verify(&ancient).unwrap().signature_valid);
+        assert!(matches!(unseal(ancient), Err(AegisError::Crypto(_))));
+    }
+
+    #[test]
+    fn rejects_altered_metadata() {
+        let ancient = read(&corrupted_bytes(Corruption::Metadata)).unwrap();
+        assert!(!verify(&ancient).unwrap().signature_valid);
+        assert!(unseal(ancient).is_err());
+
+        let mut ancient = seal_external_metadata("{}", SAMPLE_PAYLOAD.to_vec(), &test_signing_key(0)).unwrap();
+        ancient.header.set_external_metadata(r#"{"title":"forged"}"#);
+        let report = verify(&ancient).unwrap();
+        assert!(report.signature_valid);
+        assert_eq!(report.external_metadata_valid, Some(false));
+        assert!(matches!(unseal(ancient), Err(AegisError::ExternalMetadataMismatch)));
+    }
+
+    #[test]
+    fn rejects_a_signature_checked_against_the_wrong_key() {
+        let ancient = read(&corrupted_bytes(Corruption::PublicKey)).unwrap();
+        assert!(!verify(&ancient).unwrap().signature_valid);
+        assert!(unseal(ancient).is_err());
+
+        let ancient = sample_container();
+        let digest = sample_digest(&ancient);
+        let other = test_signing_key(1).verifying_key().to_sec1_bytes();
+        assert!(verify_digest(&ancient.public_key, &ancient.signature, &digest).unwrap());
+        assert!(!verify_digest(&other, &ancient.signature, &digest).unwrap());
+    }
+
+    #[test]
+    fn rejects_a_flipped_signature_bit() {
+        let ancient = read(&corrupted_bytes(Corruption::Signature)).unwrap();
+        // Either the signature no longer decodes or it no longer verifies.
+        assert!(!verify(&ancient).is_ok_and(|report| report.signature_valid));
+        assert!(unseal(ancient).is_err());
+    }
+
+    #[test]
+    fn rejects_a_truncated_container() {
+        assert!(read(&corrupted_bytes(Corruption::Truncated)).is_err());
+        let bytes = sample_bytes();
+        for len in [0, 4, bytes.len() / 2, bytes.len() - 1] {
+            assert!(read(&bytes[..len]).is_err(), "{} bytes", len);
+        }
+    }
+
+    /// A container sealed with test key 0 under a certificate that expired
+    /// soon after `TIME`, timestamped at `TIME`, and the anchor for it.
+    fn certified_and_timestamped() -> (AegisAncient, TrustAnchors, TestCertificate) {
+        let root = TestCertificate::ca("Test Root", 10);
+        let leaf = TestCertificate { not_after: TIME + 30 * 86_400, ..TestCertificate::leaf("Test Sealer", 0) };
+        let chain = [Certificate::from_der(&leaf.issued_by(&root)).unwrap()];
+        let mut ancient =
+            seal_certified(&chain, SAMPLE_METADATA.into(), SAMPLE_PAYLOAD.to_vec(), &test_signing_key(0)).unwrap();
+        let tsa_root = TestCertificate::ca("Test TSA Root", 12);
+        let tsa = TestCertificate::tsa("Test TSA", 11);
+        let token = TestTimestamp::new(&tsa, &tsa_root, TIME).token(&ancient.signature);
+        ancient.header.set_timestamp_token(token);
+        let anchors = TrustAnchors::new(vec![Certificate::from_der(&root.self_signed()).unwrap()]);
+        (ancient, anchors, tsa_root)
+    }
+
+    #[test]
+    fn does_not_trust_a_timestamp_without_tsa_anchors() {
+        let (ancient, anchors, _) = certified_and_timestamped();
+        let report = verify_with_anchors(&ancient, &anchors, &TsaTrust::default()).unwrap();
+        assert!(report.signature_valid);
+        let timestamp = report.timestamp.as_ref().unwrap();
+        assert!(timestamp.imprint_matches && timestamp.tsa_signature_valid == Some(true));
+        assert!(!timestamp.valid && !timestamp.tsa_trusted);
+        // So the certificate is checked now, when it has expired.
+        assert!(checked_at(&ancient, &report) > TIME);
+        let chain = report.certificate_chain.unwrap();
+        assert!(!chain.valid);
+        assert!(chain.error.unwrap().contains("is not valid at the time checked"));
+
+        // `verify()` trusts no TSA either.
+        assert!(!verify(&ancient).unwrap().timestamp.unwrap().tsa_trusted);
+    }
+
+    #[test]
+    fn checks_certificates_at_a_trusted_timestamp() {
+        let (ancient, anchors, tsa_root) = certified_and_timestamped();
+        let tsa = TsaTrust {
+            fingerprints: Vec::new(),
+            anchors: TrustAnchors::new(vec![Certificate::from_der(&tsa_root.self_signed()).unwrap()]),
+        };
+        let report = verify_with_anchors(&ancient, &anchors, &tsa).unwrap();
+        assert!(report.timestamp.as_ref().unwrap().valid);
+        assert_eq!(checked_at(&ancient, &report), TIME);
+        assert!(report.certificate_chain.unwrap().valid);
+    }
+
+    #[test]
+    fn verifies_co_signatures() {
+        let mut ancient = sample_container();
+        countersign(&mut ancient, "agency", TIME, &test_signing_key(1)).unwrap();
+        let ancient = read(&ancient.to_bytes().unwrap()).unwrap();
+        let report = verify(&ancient).unwrap();
+        assert!(report.signature_valid);
+        assert_eq!(report.cosigners.len(), 1);
+        assert!(report.cosigners[0].signature_valid);
+        assert_eq!(report.cosigners[0].role, "agency");
+        assert_eq!(report.cosigners[0].signed_at, TIME);
+    }
+
+    #[test]
+    fn rejects_mismatched_co_signatures() {
+        let mut ancient = sample_container();
+        let digest = sample_digest(&ancient);
+        let genuine = cosign(&digest, &ancient.public_key, "agency", TIME, &test_signing_key(1)).unwrap();
+        // Over another container, claimed by another key, for another role or
+        // time, or for another sealing key.
+        let other_digest = [0u8; 32];
+        let forgeries = [
+            cosign(&other_digest, &ancient.public_key, "agency", TIME, &test_signing_key(1)).unwrap(),
+            format::Cosignature { public_key: TestCertificate::leaf("Other", 2).public_key(), ..genuine.clone() },
+            format::Cosignature { role: "editor".into(), ..genuine.clone() },
+            format::Cosignature { signed_at: TIME + 1, ..genuine.clone() },
+            cosign(&digest, &test_signing_key(3).verifying_key().to_sec1_bytes(), "agency", TIME, &test_signing_key(1))
+                .unwrap(),
+        ];
+        for forgery in &forgeries {
+            ancient.header.add_cosignature(forgery).unwrap();
+        }
+        ancient.header.add_cosignature(&genuine).unwrap();
+        let report = verify(&ancient).unwrap();
+        // Co-signatures do not affect the sealer's own signature.
+        assert!(report.signature_valid);
+        let valid: Vec<bool> = report.cosigners.iter().map(|c| c.signature_valid).collect();
+        assert_eq!(valid, [false, false, false, false, false, true]);
+    }
+}
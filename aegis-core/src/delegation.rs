@@ -0,0 +1,440 @@
+// aegis-core/src/delegation.rs
+
+// Delegation tokens, so that a client without an API key can be allowed to
+// request seals under constraints: a field app, say, may seal only photos
+// tagged with its assignment ID, only up to a size, and only until the
+// token expires. An administrator signs the token's claims with a key the
+// sealing service is configured to accept; the service checks the
+// signature when the token is presented and the constraints before it
+// signs anything.
+//
+// A token is the base64url (unpadded) of its JSON claims, a `.`, and the
+// base64url of the issuer's signature over those bytes in
+// `SignatureContext::Delegation`:
+//
+//     {"id": "a1b2c3d4", "subject": "field-app", "issuer": "<fingerprint>",
+//      "not_before": "2025-06-01T00:00:00Z", "not_after": "2025-06-02T00:00:00Z",
+//      "max_payload": 20000000,
+//      "metadata": [{"field": "assignment_id", "equals": "A-1042"}]}
+//
+// Each metadata constraint names a field of the seal's metadata, a JSON
+// object, by its dot-separated path, and requires it to equal a value, to
+// be one of several, or to be a string starting with a prefix. All
+// constraints must hold.
+
+use crate::crypto::SignatureContext;
+use crate::error::AegisError;
+use crate::keys::Fingerprint;
+use crate::time::{parse_rfc3339, rfc3339};
+use base64ct::{Base64UrlUnpadded, Encoding};
+use p256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::{Duration, UNIX_EPOCH};
+#[cfg(feature = "sealer")]
+use p256::ecdsa::signature::{Keypair, Signer};
+
+/// Longest token accepted, encoded.
+pub const MAX_TOKEN: usize = 8 * 1024;
+
+/// What a metadata field must be.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Predicate {
+    Equals(Value),
+    OneOf(Vec<Value>),
+    Prefix(String),
+}
+
+/// A constraint on one metadata field.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Constraint {
+    /// Dot-separated path into the metadata object, e.g. `device.make`.
+    pub field: String,
+    #[serde(flatten)]
+    pub predicate: Predicate,
+}
+
+impl Constraint {
+    /// Parses `FIELD=VALUE`, where VALUE is JSON if it parses as JSON and a
+    /// string otherwise.
+    pub fn equals(spec: &str) -> Result<Self, AegisError> {
+        let (field, value) = split_spec(spec)?;
+        let value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()));
+        Ok(Constraint { field: field.to_string(), predicate: Predicate::Equals(value) })
+    }
+
+    /// Parses `FIELD=PREFIX`.
+    pub fn prefix(spec: &str) -> Result<Self, AegisError> {
+        let (field, prefix) = split_spec(spec)?;
+        Ok(Constraint { field: field.to_string(), predicate: Predicate::Prefix(prefix.to_string()) })
+    }
+
+    fn check(&self, metadata: &Value) -> Result<(), String> {
+        let value = self.field.split('.').try_fold(metadata, |value, name| value.get(name));
+        let holds = match (&self.predicate, value) {
+            (_, None) => false,
+            (Predicate::Equals(expected), Some(value)) => value == expected,
+            (Predicate::OneOf(allowed), Some(value)) => allowed.contains(value),
+            (Predicate::Prefix(prefix), Some(value)) => value.as_str().is_some_and(|s| s.starts_with(prefix.as_str())),
+        };
+        if holds {
+            return Ok(());
+        }
+        Err(match &self.predicate {
+            Predicate::Equals(expected) => format!("metadata field '{}' must be {}", self.field, expected),
+            Predicate::OneOf(allowed) => format!(
+                "metadata field '{}' must be one of {}",
+                self.field,
+                Value::Array(allowed.clone())
+            ),
+            Predicate::Prefix(prefix) => format!("metadata field '{}' must start with '{}'", self.field, prefix),
+        })
+    }
+}
+
+fn split_spec(spec: &str) -> Result<(&str, &str), AegisError> {
+    spec.split_once('=')
+        .filter(|(field, _)| !field.is_empty())
+        .ok_or_else(|| AegisError::InvalidDelegation(format!("constraint '{}' must be FIELD=VALUE", spec)))
+}
+
+/// The claims of a delegation token, times in Unix seconds.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "ClaimsJson", into = "ClaimsJson")]
+pub struct Delegation {
+    /// Names the token in the audit trail.
+    pub id: String,
+    /// Who the token was issued to.
+    pub subject: String,
+    /// Hex fingerprint of the issuing key.
+    pub issuer: String,
+    pub not_before: i64,
+    pub not_after: i64,
+    /// Largest payload, in bytes, the token may seal.
+    pub max_payload: Option<u64>,
+    pub metadata: Vec<Constraint>,
+}
+
+// The JSON form: RFC 3339 times.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ClaimsJson {
+    id: String,
+    subject: String,
+    issuer: String,
+    not_before: String,
+    not_after: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_payload: Option<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    metadata: Vec<Constraint>,
+}
+
+impl TryFrom<ClaimsJson> for Delegation {
+    type Error = AegisError;
+
+    fn try_from(json: ClaimsJson) -> Result<Self, AegisError> {
+        let time = |name: &str, value: &str| {
+            parse_rfc3339(value).ok_or_else(|| AegisError::InvalidDelegation(format!("{} is not an RFC 3339 time", name)))
+        };
+        let delegation = Delegation {
+            not_before: time("not_before", &json.not_before)?,
+            not_after: time("not_after", &json.not_after)?,
+            id: json.id,
+            subject: json.subject,
+            issuer: json.issuer,
+            max_payload: json.max_payload,
+            metadata: json.metadata,
+        };
+        if delegation.id.is_empty() || delegation.id.chars().any(|c| !c.is_ascii_graphic()) {
+            return Err(AegisError::InvalidDelegation("id must be printable ASCII".into()));
+        }
+        if delegation.not_after <= delegation.not_before {
+            return Err(AegisError::InvalidDelegation("validity period ends before it starts".into()));
+        }
+        Ok(delegation)
+    }
+}
+
+impl From<Delegation> for ClaimsJson {
+    fn from(delegation: Delegation) -> Self {
+        let time = |secs: i64| rfc3339(UNIX_EPOCH + Duration::from_secs(secs.max(0) as u64));
+        ClaimsJson {
+            id: delegation.id,
+            subject: delegation.subject,
+            issuer: delegation.issuer,
+            not_before: time(delegation.not_before),
+            not_after: time(delegation.not_after),
+            max_payload: delegation.max_payload,
+            metadata: delegation.metadata,
+        }
+    }
+}
+
+impl Delegation {
+    /// Whether a seal of `metadata` with a payload of `payload_size` bytes
+    /// at time `at` is within the token's constraints; the error says which
+    /// one it breaks.
+    pub fn permits(&self, metadata: &str, payload_size: u64, at: i64) -> Result<(), String> {
+        if at < self.not_before {
+            return Err("the delegation token is not yet valid".into());
+        }
+        if at >= self.not_after {
+            return Err("the delegation token has expired".into());
+        }
+        if let Some(max) = self.max_payload.filter(|max| payload_size > *max) {
+            return Err(format!("the payload exceeds the token's limit of {} bytes", max));
+        }
+        if self.metadata.is_empty() {
+            return Ok(());
+        }
+        let metadata: Value = serde_json::from_str(metadata)
+            .ok()
+            .filter(Value::is_object)
+            .ok_or("the token constrains metadata, which must be a JSON object")?;
+        self.metadata.iter().try_for_each(|constraint| constraint.check(&metadata))
+    }
+}
+
+/// Signs `delegation` as a token. Its `issuer` is set to the fingerprint of
+/// the signing key.
+#[cfg(feature = "sealer")]
+pub fn issue<S>(mut delegation: Delegation, issuer: &S) -> Result<String, AegisError>
+where
+    S: Signer<Signature> + Keypair<VerifyingKey = VerifyingKey>,
+{
+    delegation.issuer = Fingerprint::of(&issuer.verifying_key().to_sec1_bytes()).to_hex();
+    let claims = serde_json::to_vec(&delegation).map_err(|e| AegisError::InvalidDelegation(e.to_string()))?;
+    let signature = SignatureContext::Delegation.sign(&claims, issuer)?;
+    Ok(format!(
+        "{}.{}",
+        Base64UrlUnpadded::encode_string(&claims),
+        Base64UrlUnpadded::encode_string(&signature.to_bytes())
+    ))
+}
+
+/// Reads a token's claims without checking its signature.
+pub fn decode(token: &str) -> Result<Delegation, AegisError> {
+    Ok(split(token)?.0)
+}
+
+fn split(token: &str) -> Result<(Delegation, Vec<u8>, Signature), AegisError> {
+    let malformed = || AegisError::InvalidDelegation("malformed token".into());
+    if token.len() > MAX_TOKEN {
+        return Err(AegisError::InvalidDelegation("token is too long".into()));
+    }
+    let (claims, signature) = token.trim().split_once('.').ok_or_else(malformed)?;
+    let claims = Base64UrlUnpadded::decode_vec(claims).map_err(|_| malformed())?;
+    let signature = Base64UrlUnpadded::decode_vec(signature).map_err(|_| malformed())?;
+    let signature = Signature::from_slice(&signature).map_err(|_| malformed())?;
+    let delegation = serde_json::from_slice(&claims).map_err(|e| AegisError::InvalidDelegation(e.to_string()))?;
+    Ok((delegation, claims, signature))
+}
+
+/// Checks a token's signature by whichever of `issuers` its `issuer`
+/// names, returning its claims. Constraints, expiry included, are left to
+/// `Delegation::permits()`.
+pub fn verify(token: &str, issuers: &[VerifyingKey]) -> Result<Delegation, AegisError> {
+    let (delegation, claims, signature) = split(token)?;
+    let issuer = issuers
+        .iter()
+        .find(|key| Fingerprint::of(&key.to_sec1_bytes()).to_hex() == delegation.issuer)
+        .ok_or_else(|| AegisError::InvalidDelegation("the token's issuer is not trusted".into()))?;
+    issuer
+        .verify(&SignatureContext::Delegation.message(&claims), &signature)
+        .map_err(|_| AegisError::InvalidDelegation("the token's signature is invalid".into()))?;
+    Ok(delegation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const DAY: i64 = 24 * 60 * 60;
+    const START: i64 = 1_750_000_000;
+
+    fn delegation(metadata: Vec<Constraint>) -> Delegation {
+        Delegation {
+            id: "a1b2c3d4".into(),
+            subject: "field-app".into(),
+            issuer: String::new(),
+            not_before: START,
+            not_after: START + DAY,
+            max_payload: Some(1000),
+            metadata,
+        }
+    }
+
+    fn constraint(field: &str, predicate: Predicate) -> Constraint {
+        Constraint { field: field.into(), predicate }
+    }
+
+    fn permits(constraint: Constraint, metadata: &Value) -> bool {
+        delegation(vec![constraint]).permits(&metadata.to_string(), 0, START).is_ok()
+    }
+
+    #[cfg(feature = "sealer")]
+    #[test]
+    fn round_trips_a_signed_token() {
+        let key = crate::test_util::test_signing_key(1);
+        let original = delegation(vec![constraint("assignment_id", Predicate::Equals(json!("A-1042")))]);
+        let token = issue(original.clone(), &key).unwrap();
+        let verified = verify(&token, &[*key.verifying_key()]).unwrap();
+        assert_eq!(verified.issuer, Fingerprint::of(&key.verifying_key().to_sec1_bytes()).to_hex());
+        assert_eq!(Delegation { issuer: String::new(), ..verified.clone() }, original);
+        assert_eq!(decode(&token).unwrap(), verified);
+    }
+
+    #[cfg(feature = "sealer")]
+    #[test]
+    fn rejects_a_token_from_an_untrusted_issuer() {
+        let key = crate::test_util::test_signing_key(1);
+        let other = crate::test_util::test_signing_key(2);
+        let token = issue(delegation(vec![]), &key).unwrap();
+        assert!(verify(&token, &[]).is_err());
+        assert!(verify(&token, &[*other.verifying_key()]).is_err());
+        assert!(verify(&token, &[*other.verifying_key(), *key.verifying_key()]).is_ok());
+
+        // Claims naming the other key's fingerprint, still signed by the first.
+        let mut claimed = delegation(vec![]);
+        claimed.issuer = Fingerprint::of(&other.verifying_key().to_sec1_bytes()).to_hex();
+        let claims = serde_json::to_vec(&claimed).unwrap();
+        let signature = SignatureContext::Delegation.sign(&claims, &key).unwrap();
+        let forged = format!(
+            "{}.{}",
+            Base64UrlUnpadded::encode_string(&claims),
+            Base64UrlUnpadded::encode_string(&signature.to_bytes())
+        );
+        assert!(verify(&forged, &[*other.verifying_key(), *key.verifying_key()]).is_err());
+    }
+
+    #[cfg(feature = "sealer")]
+    #[test]
+    fn rejects_a_tampered_claims_segment() {
+        let key = crate::test_util::test_signing_key(1);
+        let token = issue(delegation(vec![]), &key).unwrap();
+        let (claims, signature) = token.split_once('.').unwrap();
+        let claims = String::from_utf8(Base64UrlUnpadded::decode_vec(claims).unwrap()).unwrap();
+        let widened = claims.replace("\"max_payload\":1000", "\"max_payload\":1000000");
+        assert_ne!(widened, claims);
+        let tampered = format!("{}.{}", Base64UrlUnpadded::encode_string(widened.as_bytes()), signature);
+        let err = verify(&tampered, &[*key.verifying_key()]).unwrap_err();
+        assert!(err.to_string().contains("signature is invalid"), "{}", err);
+        // The unchecked claims read fine, which is why only `verify` may be
+        // trusted.
+        assert_eq!(decode(&tampered).unwrap().max_payload, Some(1_000_000));
+
+        assert!(verify("", &[*key.verifying_key()]).is_err());
+        assert!(verify(claims.as_str(), &[*key.verifying_key()]).is_err());
+        assert!(verify(&format!("{}.AAAA", token.split_once('.').unwrap().0), &[*key.verifying_key()]).is_err());
+        assert!(verify(&"a".repeat(MAX_TOKEN + 1), &[*key.verifying_key()]).is_err());
+    }
+
+    #[test]
+    fn is_valid_from_not_before_until_not_after() {
+        let delegation = delegation(vec![]);
+        assert!(delegation.permits("", 0, START - 1).unwrap_err().contains("not yet valid"));
+        assert!(delegation.permits("", 0, START).is_ok());
+        assert!(delegation.permits("", 0, START + DAY - 1).is_ok());
+        assert!(delegation.permits("", 0, START + DAY).unwrap_err().contains("expired"));
+    }
+
+    #[test]
+    fn limits_the_payload_size() {
+        let limited = delegation(vec![]);
+        assert!(limited.permits("", 1000, START).is_ok());
+        assert!(limited.permits("", 1001, START).unwrap_err().contains("1000 bytes"));
+        let unlimited = Delegation { max_payload: None, ..limited };
+        assert!(unlimited.permits("", u64::MAX, START).is_ok());
+    }
+
+    #[test]
+    fn checks_each_predicate() {
+        let metadata = json!({ "assignment_id": "A-1042", "count": 3, "tags": ["a"] });
+        assert!(permits(constraint("assignment_id", Predicate::Equals(json!("A-1042"))), &metadata));
+        assert!(!permits(constraint("assignment_id", Predicate::Equals(json!("A-1043"))), &metadata));
+        assert!(permits(constraint("count", Predicate::Equals(json!(3))), &metadata));
+        assert!(!permits(constraint("count", Predicate::Equals(json!("3"))), &metadata));
+        assert!(permits(constraint("tags", Predicate::Equals(json!(["a"]))), &metadata));
+
+        assert!(permits(constraint("count", Predicate::OneOf(vec![json!(1), json!(3)])), &metadata));
+        assert!(!permits(constraint("count", Predicate::OneOf(vec![json!(1), json!(2)])), &metadata));
+        assert!(!permits(constraint("count", Predicate::OneOf(vec![])), &metadata));
+
+        assert!(permits(constraint("assignment_id", Predicate::Prefix("A-".into())), &metadata));
+        assert!(permits(constraint("assignment_id", Predicate::Prefix(String::new())), &metadata));
+        assert!(!permits(constraint("assignment_id", Predicate::Prefix("B-".into())), &metadata));
+        assert!(!permits(constraint("count", Predicate::Prefix("3".into())), &metadata));
+
+        // A missing field breaks any constraint on it.
+        assert!(!permits(constraint("absent", Predicate::Equals(Value::Null)), &metadata));
+        // All constraints must hold.
+        let both = delegation(vec![
+            constraint("assignment_id", Predicate::Prefix("A-".into())),
+            constraint("count", Predicate::Equals(json!(4))),
+        ]);
+        let err = both.permits(&metadata.to_string(), 0, START).unwrap_err();
+        assert!(err.contains("'count' must be 4"), "{}", err);
+    }
+
+    #[test]
+    fn follows_dotted_paths() {
+        let metadata = json!({ "device": { "make": "Acme", "lens": { "mm": 35 } }, "device.make": "Other" });
+        assert!(permits(constraint("device.make", Predicate::Equals(json!("Acme"))), &metadata));
+        assert!(permits(constraint("device.lens.mm", Predicate::OneOf(vec![json!(35)])), &metadata));
+        assert!(permits(constraint("device", Predicate::Equals(metadata["device"].clone())), &metadata));
+        assert!(!permits(constraint("device.model", Predicate::Equals(json!("Acme"))), &metadata));
+        assert!(!permits(constraint("device.make.name", Predicate::Prefix("A".into())), &metadata));
+        assert!(!permits(constraint("device..make", Predicate::Prefix("A".into())), &metadata));
+    }
+
+    #[test]
+    fn requires_object_metadata_when_constrained() {
+        let constrained = delegation(vec![constraint("assignment_id", Predicate::Prefix("A-".into()))]);
+        for metadata in ["", "not json", "[]", "\"A-1042\"", "null", "42"] {
+            let err = constrained.permits(metadata, 0, START).unwrap_err();
+            assert!(err.contains("must be a JSON object"), "{}: {}", metadata, err);
+        }
+        assert!(delegation(vec![]).permits("not json", 0, START).is_ok());
+    }
+
+    #[test]
+    fn parses_constraint_specs() {
+        assert_eq!(
+            Constraint::equals("assignment_id=A-1042").unwrap(),
+            constraint("assignment_id", Predicate::Equals(json!("A-1042")))
+        );
+        assert_eq!(Constraint::equals("count=3").unwrap(), constraint("count", Predicate::Equals(json!(3))));
+        assert_eq!(Constraint::equals("note=a=b").unwrap(), constraint("note", Predicate::Equals(json!("a=b"))));
+        assert_eq!(Constraint::prefix("id=42").unwrap(), constraint("id", Predicate::Prefix("42".into())));
+        assert!(Constraint::equals("no value").is_err());
+        assert!(Constraint::prefix("=A-").is_err());
+    }
+
+    #[test]
+    fn validates_claims_json() {
+        let claims = serde_json::to_value(delegation(vec![
+            constraint("a", Predicate::Equals(json!(1))),
+            constraint("b", Predicate::OneOf(vec![json!("x")])),
+            constraint("c", Predicate::Prefix("p".into())),
+        ]))
+        .unwrap();
+        assert_eq!(claims["not_before"], "2025-06-15T15:06:40Z");
+        assert_eq!(claims["metadata"][1], json!({ "field": "b", "one_of": ["x"] }));
+        let parsed: Delegation = serde_json::from_value(claims.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), claims);
+
+        let with = |key: &str, value: Value| {
+            let mut claims = claims.clone();
+            claims[key] = value;
+            serde_json::from_value::<Delegation>(claims)
+        };
+        assert!(with("extra", json!(1)).is_err());
+        assert!(with("id", json!("")).is_err());
+        assert!(with("id", json!("a b")).is_err());
+        assert!(with("not_after", claims["not_before"].clone()).is_err());
+        assert!(with("not_before", json!("yesterday")).is_err());
+    }
+}
@@ -0,0 +1,159 @@
+// aegis-core/src/der.rs
+
+// The little DER this crate reads and writes, for RFC 3161 timestamps
+// (`timestamp`) and X.509 certificates (`x509`): definite lengths up to
+// 4 bytes, single-byte tags, and no attempt to check that an encoding is
+// the canonical one.
+
+use crate::error::AegisError;
+
+pub(crate) const TAG_BOOLEAN: u8 = 0x01;
+pub(crate) const TAG_INTEGER: u8 = 0x02;
+pub(crate) const TAG_BIT_STRING: u8 = 0x03;
+pub(crate) const TAG_OCTET_STRING: u8 = 0x04;
+pub(crate) const TAG_NULL: u8 = 0x05;
+pub(crate) const TAG_OID: u8 = 0x06;
+pub(crate) const TAG_UTC_TIME: u8 = 0x17;
+pub(crate) const TAG_GENERALIZED_TIME: u8 = 0x18;
+pub(crate) const TAG_SEQUENCE: u8 = 0x30;
+pub(crate) const TAG_SET: u8 = 0x31;
+pub(crate) const TAG_CONTEXT_0: u8 = 0xa0;
+
+fn error(msg: &str) -> AegisError {
+    AegisError::Crypto(format!("invalid DER: {}", msg))
+}
+
+pub(crate) fn encode(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = contents.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes = len.to_be_bytes();
+        let skip = bytes.iter().take_while(|b| **b == 0).count();
+        out.push(0x80 | (bytes.len() - skip) as u8);
+        out.extend_from_slice(&bytes[skip..]);
+    }
+    out.extend_from_slice(contents);
+    out
+}
+
+pub(crate) fn encode_unsigned(value: &[u8]) -> Vec<u8> {
+    let value = &value[value.iter().take_while(|b| **b == 0).count().min(value.len().saturating_sub(1))..];
+    let mut contents = Vec::with_capacity(value.len() + 1);
+    if value.first().is_some_and(|b| b & 0x80 != 0) {
+        contents.push(0);
+    }
+    contents.extend_from_slice(value);
+    encode(TAG_INTEGER, &contents)
+}
+
+// Cursor over a run of DER elements.
+pub(crate) struct Der<'a>(pub(crate) &'a [u8]);
+
+impl<'a> Der<'a> {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn peek_tag(&self) -> Option<u8> {
+        self.0.first().copied()
+    }
+
+    /// Reads the next element: its tag, its contents, and the whole encoded
+    /// element.
+    pub(crate) fn element(&mut self) -> Result<(u8, &'a [u8], &'a [u8]), AegisError> {
+        let truncated = || error("truncated");
+        let (&tag, rest) = self.0.split_first().ok_or_else(truncated)?;
+        let (&first, mut rest) = rest.split_first().ok_or_else(truncated)?;
+        let len = if first < 0x80 {
+            first as usize
+        } else {
+            let n = (first & 0x7f) as usize;
+            if n == 0 || n > 4 || rest.len() < n {
+                return Err(error("unsupported length"));
+            }
+            let len = rest[..n].iter().fold(0usize, |acc, b| (acc << 8) | *b as usize);
+            rest = &rest[n..];
+            len
+        };
+        if rest.len() < len {
+            return Err(truncated());
+        }
+        let header = self.0.len() - rest.len();
+        let element = &self.0[..header + len];
+        self.0 = &rest[len..];
+        Ok((tag, &rest[..len], element))
+    }
+
+    pub(crate) fn expect(&mut self, tag: u8) -> Result<&'a [u8], AegisError> {
+        match self.element()? {
+            (t, contents, _) if t == tag => Ok(contents),
+            (t, _, _) => Err(error(&format!("expected tag {:#04x}, found {:#04x}", tag, t))),
+        }
+    }
+
+    /// Reads the element if it has `tag`.
+    pub(crate) fn optional(&mut self, tag: u8) -> Result<Option<&'a [u8]>, AegisError> {
+        if self.peek_tag() == Some(tag) {
+            self.expect(tag).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub(crate) fn sequence(&mut self) -> Result<Der<'a>, AegisError> {
+        self.expect(TAG_SEQUENCE).map(Der)
+    }
+}
+
+/// Dotted form of an encoded object identifier, e.g. `1.2.3.4`.
+pub(crate) fn oid_string(oid: &[u8]) -> String {
+    let mut arcs = Vec::new();
+    let mut value: u64 = 0;
+    for (i, byte) in oid.iter().enumerate() {
+        value = (value << 7) | (byte & 0x7f) as u64;
+        if byte & 0x80 == 0 {
+            if arcs.is_empty() {
+                let first = (value / 40).min(2);
+                arcs.push(first);
+                arcs.push(value - first * 40);
+            } else {
+                arcs.push(value);
+            }
+            value = 0;
+        } else if i + 1 == oid.len() {
+            arcs.push(value);
+        }
+    }
+    arcs.iter().map(u64::to_string).collect::<Vec<_>>().join(".")
+}
+
+/// Unix seconds of a `GeneralizedTime` such as `20240501120000Z` or
+/// `20240501120000.123Z`.
+pub(crate) fn generalized_time(value: &[u8]) -> Option<i64> {
+    let s = std::str::from_utf8(value).ok()?;
+    let digits = s.get(..14)?;
+    let rest = s.get(14..)?;
+    if !digits.bytes().all(|b| b.is_ascii_digit()) || !rest.ends_with('Z') {
+        return None;
+    }
+    crate::time::parse_rfc3339(&format!(
+        "{}-{}-{}T{}:{}:{}{}",
+        &digits[..4],
+        &digits[4..6],
+        &digits[6..8],
+        &digits[8..10],
+        &digits[10..12],
+        &digits[12..14],
+        rest
+    ))
+}
+
+/// Unix seconds of a `UTCTime` such as `240501120000Z`. Two-digit years
+/// below 50 are in the 2000s, as RFC 5280 has it.
+pub(crate) fn utc_time(value: &[u8]) -> Option<i64> {
+    let year: u32 = std::str::from_utf8(value.get(..2)?).ok()?.parse().ok()?;
+    let century = if year < 50 { b"20" } else { b"19" };
+    generalized_time(&[&century[..], value].concat())
+}
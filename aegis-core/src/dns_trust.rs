@@ -0,0 +1,160 @@
+// aegis-core/src/dns_trust.rs
+
+// Publishing sealing keys in DNS, the way DKIM publishes mail signing keys,
+// so a verifier can check that a domain vouches for the key a container was
+// sealed with without being handed the key beforehand.
+//
+// A sealer picks a domain it controls and a selector, publishes a TXT record
+// at `<selector>._aegis.<domain>`:
+//
+//     v=AEGIS1; k=p256; p=<base64 SEC1 public key>
+//
+// and names the domain and selector in every container's trust hint
+// (`format::FIELD_TRUST_HINT`). Several records may share a name, one per
+// key, so that keys being rotated in and out all verify. The hint is not
+// covered by the signature, like a certificate chain: it only says where to
+// look, and a record vouches for nothing but the key it publishes. Zones
+// signed with DNSSEC let the resolver authenticate the answer, which
+// `check()` reports.
+//
+// Resolving the record is left to the caller; see the service's `dns`
+// module for DNS over HTTPS.
+
+use crate::error::AegisError;
+use base64ct::{Base64, Encoding};
+use serde::Serialize;
+
+/// Label under the domain that records are published beneath.
+pub const RECORD_LABEL: &str = "_aegis";
+
+/// The `v=` tag of records this implementation reads and writes.
+pub const RECORD_VERSION: &str = "AEGIS1";
+
+/// The selector used when none is given.
+pub const DEFAULT_SELECTOR: &str = "aegis";
+
+/// Suggested TTL of published records, in seconds.
+pub const RECORD_TTL: u32 = 3600;
+
+/// Where a container's sealing key is published (see `FIELD_TRUST_HINT`).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TrustHint {
+    pub domain: String,
+    pub selector: String,
+}
+
+impl TrustHint {
+    /// A hint for `domain` and `selector`, both lowercased. The domain is
+    /// one or more dot-separated labels and the selector a single label, of
+    /// letters, digits and hyphens.
+    pub fn new(domain: &str, selector: &str) -> Result<Self, AegisError> {
+        let domain = domain.trim().trim_end_matches('.').to_ascii_lowercase();
+        let selector = selector.trim().to_ascii_lowercase();
+        if domain.len() > 253 || !domain.contains('.') || !domain.split('.').all(valid_label) {
+            return Err(AegisError::InvalidTrustHint(format!("'{}' is not a domain name", domain)));
+        }
+        if !valid_label(&selector) {
+            return Err(AegisError::InvalidTrustHint(format!("'{}' is not a valid selector", selector)));
+        }
+        Ok(TrustHint { domain, selector })
+    }
+
+    /// Parses `DOMAIN` or `DOMAIN:SELECTOR`, as given on the command line
+    /// and in `AEGIS_TRUST_HINT`.
+    pub fn parse(spec: &str) -> Result<Self, AegisError> {
+        match spec.split_once(':') {
+            Some((domain, selector)) => TrustHint::new(domain, selector),
+            None => TrustHint::new(spec, DEFAULT_SELECTOR),
+        }
+    }
+
+    /// The name the TXT record is published at, without a trailing dot.
+    pub fn record_name(&self) -> String {
+        format!("{}.{}.{}", self.selector, RECORD_LABEL, self.domain)
+    }
+}
+
+fn valid_label(label: &str) -> bool {
+    !label.is_empty()
+        && label.len() <= 63
+        && !label.starts_with('-')
+        && !label.ends_with('-')
+        && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// The TXT record text publishing the SEC1 `public_key`.
+pub fn txt_record(public_key: &[u8]) -> String {
+    format!("v={}; k=p256; p={}", RECORD_VERSION, Base64::encode_string(public_key))
+}
+
+/// A zone file line publishing `public_key` for `hint`.
+pub fn zone_line(hint: &TrustHint, public_key: &[u8]) -> String {
+    format!("{}. {} IN TXT \"{}\"", hint.record_name(), RECORD_TTL, txt_record(public_key))
+}
+
+/// The key a TXT record publishes. `None` for records of another version
+/// or key type, or without a readable key, which are skipped rather than
+/// treated as errors since other records may share the name.
+pub fn published_key(record: &str) -> Option<Vec<u8>> {
+    let mut tags = record.split(';').filter_map(|tag| tag.split_once('=')).map(|(k, v)| (k.trim(), v.trim()));
+    if tags.next()? != ("v", RECORD_VERSION) {
+        return None;
+    }
+    let mut key = None;
+    for (name, value) in tags {
+        match name {
+            "k" if value != "p256" => return None,
+            "p" => key = Base64::decode_vec(&value.replace(char::is_whitespace, "")).ok(),
+            _ => {}
+        }
+    }
+    key.filter(|k| !k.is_empty())
+}
+
+/// The outcome of checking a container's key against DNS.
+#[derive(Clone, Debug, Serialize)]
+pub struct DnsTrustReport {
+    pub domain: String,
+    pub selector: String,
+    pub record_name: String,
+    /// Whether one of the records publishes the container's key.
+    pub key_published: bool,
+    /// Whether the resolver validated the answer with DNSSEC.
+    pub authenticated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Checks the TXT `records` found at `hint`'s name for `public_key`.
+/// `authenticated` is whether the resolver validated them with DNSSEC.
+pub fn check(hint: &TrustHint, public_key: &[u8], records: &[String], authenticated: bool) -> DnsTrustReport {
+    let keys: Vec<Vec<u8>> = records.iter().filter_map(|r| published_key(r)).collect();
+    let key_published = keys.iter().any(|k| k == public_key);
+    let error = if keys.is_empty() {
+        Some(format!("no {} records at {}", RECORD_VERSION, hint.record_name()))
+    } else if !key_published {
+        Some(format!("{} does not publish the sealing key", hint.record_name()))
+    } else {
+        None
+    };
+    DnsTrustReport {
+        domain: hint.domain.clone(),
+        selector: hint.selector.clone(),
+        record_name: hint.record_name(),
+        key_published,
+        authenticated,
+        error,
+    }
+}
+
+/// A report for a hint that could not be resolved.
+pub fn unresolved(hint: &TrustHint, error: String) -> DnsTrustReport {
+    DnsTrustReport {
+        domain: hint.domain.clone(),
+        selector: hint.selector.clone(),
+        record_name: hint.record_name(),
+        key_published: false,
+        authenticated: false,
+        error: Some(error),
+    }
+}
@@ -0,0 +1,438 @@
+// aegis-core/src/endorsement.rs
+
+// Key endorsements, so that sealing keys can be organized under an
+// organization's root key the way teams sit under an organization: the org
+// key signs a statement over each team key, and a verifier that trusts only
+// the org key accepts seals made by any team key it endorsed.
+//
+// An endorsement names the endorsed (subject) key by the SHA-256 of its SEC1
+// encoding, the role it is endorsed for, and the period it is valid in, and
+// is signed by the issuer in `SignatureContext::Endorsement`. Endorsed keys
+// may endorse keys of their own, so a chain runs from the sealing key up
+// through one endorsement per level to a root the verifier configured;
+// `verify_chain()` finds one whose every endorsement verifies and is valid
+// at the time checked. Roles are reported, not interpreted.
+//
+// Endorsements travel as JSON in trust bundles (see `bundle::TrustBundle`)
+// and configuration files, and may also be embedded in containers
+// (`format::FIELD_ENDORSEMENTS`) so that a seal carries its own chain.
+
+use crate::error::AegisError;
+use crate::time::{parse_rfc3339, rfc3339};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::time::{Duration, UNIX_EPOCH};
+#[cfg(feature = "sealer")]
+use {
+    crate::crypto::SignatureContext,
+    p256::ecdsa::{
+        signature::{Keypair, Signer},
+        Signature, VerifyingKey,
+    },
+};
+#[cfg(feature = "verifier")]
+use {crate::keys::Fingerprint, std::collections::HashSet};
+
+/// Longest role an endorsement may name.
+pub const MAX_ROLE: usize = 64;
+
+/// Most endorsements followed from a sealing key to a root.
+pub const MAX_CHAIN: usize = 8;
+
+/// An issuer's signed statement that a subject key may seal in a role
+/// between two times, in Unix seconds.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "EndorsementJson", into = "EndorsementJson")]
+pub struct Endorsement {
+    /// SEC1 public key of the issuer.
+    pub issuer: Vec<u8>,
+    /// SEC1 public key of the endorsed key.
+    pub subject: Vec<u8>,
+    /// What the subject is endorsed as, e.g. `team:newsroom`.
+    pub role: String,
+    pub not_before: i64,
+    pub not_after: i64,
+    pub signature: Vec<u8>,
+}
+
+// The JSON form: hex keys and signature, RFC 3339 times.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EndorsementJson {
+    issuer: String,
+    subject: String,
+    role: String,
+    not_before: String,
+    not_after: String,
+    signature: String,
+}
+
+impl TryFrom<EndorsementJson> for Endorsement {
+    type Error = AegisError;
+
+    fn try_from(json: EndorsementJson) -> Result<Self, AegisError> {
+        let bytes = |name: &str, value: &str| {
+            hex::decode(value).map_err(|_| AegisError::InvalidEndorsement(format!("{} is not hex", name)))
+        };
+        let time = |name: &str, value: &str| {
+            parse_rfc3339(value).ok_or_else(|| AegisError::InvalidEndorsement(format!("{} is not an RFC 3339 time", name)))
+        };
+        let endorsement = Endorsement {
+            issuer: bytes("issuer", &json.issuer)?,
+            subject: bytes("subject", &json.subject)?,
+            role: json.role,
+            not_before: time("not_before", &json.not_before)?,
+            not_after: time("not_after", &json.not_after)?,
+            signature: bytes("signature", &json.signature)?,
+        };
+        check(&endorsement.role, endorsement.not_before, endorsement.not_after)?;
+        Ok(endorsement)
+    }
+}
+
+impl From<Endorsement> for EndorsementJson {
+    fn from(endorsement: Endorsement) -> Self {
+        EndorsementJson {
+            issuer: hex::encode(&endorsement.issuer),
+            subject: hex::encode(&endorsement.subject),
+            role: endorsement.role,
+            not_before: unix_rfc3339(endorsement.not_before),
+            not_after: unix_rfc3339(endorsement.not_after),
+            signature: hex::encode(&endorsement.signature),
+        }
+    }
+}
+
+fn unix_rfc3339(secs: i64) -> String {
+    rfc3339(UNIX_EPOCH + Duration::from_secs(secs.max(0) as u64))
+}
+
+fn check(role: &str, not_before: i64, not_after: i64) -> Result<(), AegisError> {
+    if role.is_empty() || role.len() > MAX_ROLE || role.chars().any(char::is_control) {
+        return Err(AegisError::InvalidEndorsement(format!(
+            "role '{}' must be 1 to {} bytes without control characters",
+            role, MAX_ROLE
+        )));
+    }
+    if not_before < 0 || not_after <= not_before {
+        return Err(AegisError::InvalidEndorsement("validity period ends before it starts".into()));
+    }
+    Ok(())
+}
+
+/// What an issuer signs, in `SignatureContext::Endorsement`: the SHA-256 of
+/// the subject key (SEC1), the role as a 2-byte big-endian length and its
+/// UTF-8 bytes, then `not_before` and `not_after` as 8-byte big-endian Unix
+/// times.
+pub fn statement(subject: &[u8], role: &str, not_before: i64, not_after: i64) -> Vec<u8> {
+    let mut object = Vec::with_capacity(32 + 2 + role.len() + 16);
+    object.extend_from_slice(&Sha256::digest(subject));
+    object.extend_from_slice(&(role.len() as u16).to_be_bytes());
+    object.extend_from_slice(role.as_bytes());
+    object.extend_from_slice(&not_before.to_be_bytes());
+    object.extend_from_slice(&not_after.to_be_bytes());
+    object
+}
+
+/// Has `issuer` endorse the SEC1 key `subject` in `role` from `not_before`
+/// until `not_after`.
+#[cfg(feature = "sealer")]
+pub fn endorse<S>(subject: &[u8], role: &str, not_before: i64, not_after: i64, issuer: &S) -> Result<Endorsement, AegisError>
+where
+    S: Signer<Signature> + Keypair<VerifyingKey = VerifyingKey>,
+{
+    check(role, not_before, not_after)?;
+    VerifyingKey::from_sec1_bytes(subject)
+        .map_err(|_| AegisError::InvalidEndorsement("subject is not a P-256 public key".into()))?;
+    let signature = SignatureContext::Endorsement.sign(&statement(subject, role, not_before, not_after), issuer)?;
+    Ok(Endorsement {
+        issuer: issuer.verifying_key().to_sec1_bytes().into_vec(),
+        subject: subject.to_vec(),
+        role: role.to_string(),
+        not_before,
+        not_after,
+        signature: signature.to_bytes().to_vec(),
+    })
+}
+
+/// Reads endorsements from JSON: a single endorsement, or an array of them.
+pub fn from_json(text: &str) -> Result<Vec<Endorsement>, AegisError> {
+    let value: serde_json::Value =
+        serde_json::from_str(text).map_err(|e| AegisError::InvalidEndorsement(e.to_string()))?;
+    let result = match value {
+        serde_json::Value::Array(_) => serde_json::from_value(value),
+        _ => serde_json::from_value(value).map(|endorsement| vec![endorsement]),
+    };
+    result.map_err(|e| AegisError::InvalidEndorsement(e.to_string()))
+}
+
+/// The endorsements among `endorsements` that a chain from `key` could
+/// use: those of `key`, of their issuers, and so on, in that order. What a
+/// sealer embeds so the container carries its own chain.
+pub fn chain_of(key: &[u8], endorsements: &[Endorsement]) -> Vec<Endorsement> {
+    let mut keys = vec![key.to_vec()];
+    let mut chain: Vec<Endorsement> = Vec::new();
+    let mut next = 0;
+    while next < keys.len() && keys.len() <= MAX_CHAIN * 4 {
+        let key = keys[next].clone();
+        for endorsement in endorsements.iter().filter(|e| e.subject == key) {
+            if !chain.contains(endorsement) {
+                chain.push(endorsement.clone());
+            }
+            if !keys.contains(&endorsement.issuer) {
+                keys.push(endorsement.issuer.clone());
+            }
+        }
+        next += 1;
+    }
+    chain
+}
+
+#[cfg(feature = "verifier")]
+impl Endorsement {
+    /// Whether the issuer's signature over the statement verifies.
+    pub fn signature_valid(&self) -> bool {
+        use crate::crypto::SignatureContext;
+        use p256::ecdsa::{Signature, VerifyingKey};
+
+        let object = statement(&self.subject, &self.role, self.not_before, self.not_after);
+        match (VerifyingKey::from_sec1_bytes(&self.issuer), Signature::from_slice(&self.signature)) {
+            (Ok(key), Ok(signature)) => SignatureContext::Endorsement.verify(&key, &object, &signature).unwrap_or(false),
+            _ => false,
+        }
+    }
+}
+
+/// One endorsement of a verified chain.
+#[cfg(feature = "verifier")]
+#[derive(Clone, Debug, Serialize)]
+pub struct Link {
+    /// Hex fingerprint of the endorsed key.
+    pub subject: String,
+    /// Hex fingerprint of the issuer.
+    pub issuer: String,
+    pub role: String,
+    pub not_after: String,
+}
+
+/// The outcome of looking for an endorsement chain from a sealing key to a
+/// root.
+#[cfg(feature = "verifier")]
+#[derive(Clone, Debug, Serialize)]
+pub struct ChainReport {
+    pub valid: bool,
+    /// Hex fingerprint of the root the chain leads to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub root: Option<String>,
+    /// The endorsements followed, starting with the sealing key's. Empty
+    /// when the sealing key is itself a root.
+    pub chain: Vec<Link>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Looks for a chain of `endorsements` leading from `key` (SEC1) to one of
+/// `roots`, each endorsement's signature valid and `at` (Unix seconds)
+/// within its validity period.
+#[cfg(feature = "verifier")]
+pub fn verify_chain(key: &[u8], endorsements: &[Endorsement], roots: &[Vec<u8>], at: i64) -> ChainReport {
+    let mut problem = None;
+    let mut seen = HashSet::new();
+    match search(key, endorsements, roots, at, &mut seen, &mut problem) {
+        Some(mut chain) => {
+            chain.reverse();
+            let root = chain.last().map_or_else(|| key.to_vec(), |e| e.issuer.clone());
+            ChainReport {
+                valid: true,
+                root: Some(Fingerprint::of(&root).to_hex()),
+                chain: chain
+                    .into_iter()
+                    .map(|e| Link {
+                        subject: Fingerprint::of(&e.subject).to_hex(),
+                        issuer: Fingerprint::of(&e.issuer).to_hex(),
+                        role: e.role.clone(),
+                        not_after: unix_rfc3339(e.not_after),
+                    })
+                    .collect(),
+                error: None,
+            }
+        }
+        None => ChainReport {
+            valid: false,
+            root: None,
+            chain: Vec::new(),
+            error: Some(problem.unwrap_or_else(|| {
+                format!("no endorsement of key {} leads to a configured root", Fingerprint::of(key).to_hex())
+            })),
+        },
+    }
+}
+
+// Depth first from `key`; the chain found is returned root end first. The
+// first reason an endorsement was passed over is kept for the report.
+#[cfg(feature = "verifier")]
+fn search<'a>(
+    key: &[u8],
+    endorsements: &'a [Endorsement],
+    roots: &[Vec<u8>],
+    at: i64,
+    seen: &mut HashSet<Vec<u8>>,
+    problem: &mut Option<String>,
+) -> Option<Vec<&'a Endorsement>> {
+    if roots.iter().any(|root| root == key) {
+        return Some(Vec::new());
+    }
+    if seen.len() >= MAX_CHAIN || !seen.insert(key.to_vec()) {
+        return None;
+    }
+    for endorsement in endorsements.iter().filter(|e| e.subject == key) {
+        let fault = if at < endorsement.not_before {
+            Some("is not yet valid")
+        } else if at > endorsement.not_after {
+            Some("has expired")
+        } else if !endorsement.signature_valid() {
+            Some("has an invalid signature")
+        } else {
+            None
+        };
+        if let Some(fault) = fault {
+            problem.get_or_insert_with(|| {
+                format!(
+                    "endorsement of {} by {} {}",
+                    Fingerprint::of(&endorsement.subject).to_hex(),
+                    Fingerprint::of(&endorsement.issuer).to_hex(),
+                    fault
+                )
+            });
+            continue;
+        }
+        if let Some(mut chain) = search(&endorsement.issuer, endorsements, roots, at, seen, problem) {
+            chain.push(endorsement);
+            return Some(chain);
+        }
+    }
+    seen.remove(key);
+    None
+}
+
+/// Checks the sealing key of `ancient`, verified as `report`, for a chain to
+/// one of `roots` through the container's own endorsements and `known`
+/// ones, at the time `crypto::checked_at()` gives.
+#[cfg(feature = "verifier")]
+pub fn verify_container(
+    ancient: &crate::format::AegisAncient,
+    report: &crate::crypto::VerificationReport,
+    known: &[Endorsement],
+    roots: &[Vec<u8>],
+) -> Result<ChainReport, AegisError> {
+    let mut endorsements = ancient.header.endorsements()?;
+    endorsements.extend_from_slice(known);
+    Ok(verify_chain(&ancient.public_key, &endorsements, roots, crate::crypto::checked_at(ancient, report)))
+}
+
+#[cfg(all(test, feature = "sealer", feature = "verifier"))]
+mod tests {
+    use super::*;
+    use crate::test_util::test_signing_key;
+
+    // 2024-06-01.
+    const AT: i64 = 1_717_200_000;
+    const YEAR: i64 = 365 * 86_400;
+
+    fn key(n: u32) -> Vec<u8> {
+        test_signing_key(n).verifying_key().to_sec1_bytes().into_vec()
+    }
+
+    /// Key `issuer` endorsing key `subject` for the year around `AT`.
+    fn endorsement(issuer: u32, subject: u32) -> Endorsement {
+        endorse(&key(subject), "team", AT - YEAR / 2, AT + YEAR / 2, &test_signing_key(issuer)).unwrap()
+    }
+
+    #[test]
+    fn follows_a_two_level_chain_to_a_root() {
+        // Root 0 endorses team key 1, which endorses sealing key 2.
+        let endorsements = [endorsement(1, 2), endorsement(0, 1)];
+        let report = verify_chain(&key(2), &endorsements, &[key(0)], AT);
+        assert!(report.valid, "{:?}", report.error);
+        assert_eq!(report.root, Some(Fingerprint::of(&key(0)).to_hex()));
+        let subjects: Vec<_> = report.chain.iter().map(|link| link.subject.clone()).collect();
+        assert_eq!(subjects, [Fingerprint::of(&key(2)).to_hex(), Fingerprint::of(&key(1)).to_hex()]);
+        assert_eq!(report.chain[1].issuer, Fingerprint::of(&key(0)).to_hex());
+
+        // A root needs no chain; an unrelated root is not reached.
+        let report = verify_chain(&key(0), &endorsements, &[key(0)], AT);
+        assert!(report.valid && report.chain.is_empty());
+        assert!(!verify_chain(&key(2), &endorsements, &[key(9)], AT).valid);
+    }
+
+    #[test]
+    fn rejects_a_chain_with_an_expired_middle_link() {
+        let expired = endorse(&key(1), "team", AT - 2 * YEAR, AT - YEAR, &test_signing_key(0)).unwrap();
+        let leaf = endorse(&key(2), "team", AT - 2 * YEAR, AT + YEAR, &test_signing_key(1)).unwrap();
+        let report = verify_chain(&key(2), &[leaf.clone(), expired.clone()], &[key(0)], AT);
+        assert!(!report.valid);
+        assert!(report.error.unwrap().ends_with("has expired"));
+        // The chain held while the middle link was in force.
+        assert!(verify_chain(&key(2), &[leaf, expired], &[key(0)], AT - YEAR - 1).valid);
+
+        let future = endorse(&key(1), "team", AT + 1, AT + YEAR, &test_signing_key(0)).unwrap();
+        let report = verify_chain(&key(2), &[endorsement(1, 2), future], &[key(0)], AT);
+        assert!(report.error.unwrap().ends_with("is not yet valid"));
+    }
+
+    #[test]
+    fn stops_at_a_cycle() {
+        // Keys 1 and 2 endorse each other; neither leads to the root.
+        let endorsements = [endorsement(1, 2), endorsement(2, 1)];
+        let report = verify_chain(&key(2), &endorsements, &[key(0)], AT);
+        assert!(!report.valid);
+        assert!(report.error.unwrap().contains("leads to a configured root"));
+
+        // A way out of the cycle is still found.
+        let endorsements = [endorsement(1, 2), endorsement(2, 1), endorsement(0, 1)];
+        assert!(verify_chain(&key(2), &endorsements, &[key(0)], AT).valid);
+    }
+
+    #[test]
+    fn rejects_a_forged_signature() {
+        // Key 3 signs, claiming to be root 0.
+        let mut forged = endorsement(3, 1);
+        forged.issuer = key(0);
+        let report = verify_chain(&key(2), &[endorsement(1, 2), forged], &[key(0)], AT);
+        assert!(!report.valid);
+        assert!(report.error.unwrap().ends_with("has an invalid signature"));
+
+        // Nor may a genuine signature be stretched to another role or time.
+        for tamper in [
+            |e: &mut Endorsement| e.role = "admin".into(),
+            |e: &mut Endorsement| e.not_after += YEAR,
+            |e: &mut Endorsement| e.subject = key(3),
+        ] {
+            let mut endorsement = endorsement(0, 1);
+            tamper(&mut endorsement);
+            assert!(!endorsement.signature_valid());
+        }
+    }
+
+    #[test]
+    fn limits_the_chain_length() {
+        // Key n + 1 endorses key n, from the sealing key 1 up to the root.
+        let chain = |links: u32| (1..=links).map(|n| endorsement(n + 1, n)).collect::<Vec<_>>();
+        let links = MAX_CHAIN as u32;
+        let report = verify_chain(&key(1), &chain(links), &[key(links + 1)], AT);
+        assert!(report.valid, "{:?}", report.error);
+        assert_eq!(report.chain.len(), MAX_CHAIN);
+        assert!(!verify_chain(&key(1), &chain(links + 1), &[key(links + 2)], AT).valid);
+    }
+
+    #[test]
+    fn round_trips_json() {
+        let endorsements = vec![endorsement(0, 1), endorsement(1, 2)];
+        let json = serde_json::to_string(&endorsements).unwrap();
+        assert_eq!(from_json(&json).unwrap(), endorsements);
+        let one = serde_json::to_string(&endorsements[0]).unwrap();
+        assert_eq!(from_json(&one).unwrap(), endorsements[..1]);
+        let extra = one.replacen('{', r#"{"extra":1,"#, 1);
+        assert!(from_json(&extra).is_err());
+    }
+}
@@ -0,0 +1,79 @@
+// aegis-core/src/error.rs
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum AegisError {
+    #[error("File I/O error")]
+    Io(#[from] std::io::Error),
+
+    // This is a general-purpose crypto error. While not currently constructed
+    // by the sealer, it's kept for future logic. We allow dead_code to
+    // acknowledge it's unused in the current sealer implementation.
+    #[allow(dead_code)]
+    #[error("Cryptographic error: {0}")]
+    Crypto(String),
+
+    // The InvalidFormat error is only relevant when parsing a file,
+    // so we include it only when the 'verifier' feature is enabled.
+    #[cfg(feature = "verifier")]
+    #[error("Invalid file format")]
+    InvalidFormat,
+
+    #[error("Invalid extension: {0}")]
+    InvalidExtension(String),
+
+    #[error("Invalid co-signature: {0}")]
+    InvalidCosignature(String),
+
+    #[error("Invalid certificate: {0}")]
+    InvalidCertificate(String),
+
+    #[error("Invalid trust hint: {0}")]
+    InvalidTrustHint(String),
+
+    #[error("Invalid endorsement: {0}")]
+    InvalidEndorsement(String),
+
+    #[error("Invalid delegation token: {0}")]
+    InvalidDelegation(String),
+
+    #[error("Invalid image chunks: {0}")]
+    InvalidChunks(String),
+
+    #[error("Invalid compressed block: {0}")]
+    InvalidCompression(String),
+
+    #[error("Invalid payload manifest: {0}")]
+    InvalidManifest(String),
+
+    #[error("Invalid attestation: {0}")]
+    InvalidAttestation(String),
+
+    #[error("Invalid log proof: {0}")]
+    InvalidProof(String),
+
+    #[error("Invalid metadata: {0}")]
+    InvalidMetadata(String),
+
+    #[error("Invalid image: {0}")]
+    InvalidImage(&'static str),
+
+    #[error("Unsupported format version {}", char::from(*.0))]
+    UnsupportedVersion(u8),
+
+    #[error("Format version {} cannot carry header fields", char::from(*.0))]
+    HeaderNotSupported(u8),
+
+    #[cfg(feature = "verifier")]
+    #[error("Unsupported format flags {0:#x}")]
+    UnsupportedFlags(u32),
+
+    #[cfg(feature = "verifier")]
+    #[error("Unsupported signature scheme {0}")]
+    UnsupportedScheme(u16),
+
+    #[cfg(feature = "verifier")]
+    #[error("External metadata document is missing or does not match its reference")]
+    ExternalMetadataMismatch,
+}
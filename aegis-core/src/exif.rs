@@ -0,0 +1,276 @@
+// aegis-core/src/exif.rs
+
+// Reads the EXIF fields worth sealing from an image: when and with what it
+// was captured, and where. The EXIF block is a small TIFF structure, found
+// in a JPEG APP1 segment starting `Exif\0\0`, in a PNG `eXIf` chunk, or as
+// the image itself for TIFF files. Only IFD0, the Exif IFD and the GPS IFD
+// are read.
+//
+// `fill_metadata()` copies what it finds into the fields of a
+// `metadata::Metadata` document the sender left empty, falling back to the
+// image's XMP packet for the creator and capture time, and says where each
+// value came from. Fields the sender filled in are never overwritten.
+
+use crate::image_info::ImageFormat;
+use crate::metadata::{Device, Gps, Metadata};
+use crate::xmp::{self, jpeg_segments, png_chunks};
+use serde_json::{json, Map, Value};
+
+const JPEG_EXIF_ID: &[u8] = b"Exif\0\0";
+
+// IFD0
+const TAG_MAKE: u16 = 0x010F;
+const TAG_MODEL: u16 = 0x0110;
+const TAG_ARTIST: u16 = 0x013B;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_GPS_IFD: u16 = 0x8825;
+// Exif IFD
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+const TAG_OFFSET_TIME_ORIGINAL: u16 = 0x9011;
+const TAG_BODY_SERIAL_NUMBER: u16 = 0xA431;
+// GPS IFD
+const TAG_GPS_LATITUDE_REF: u16 = 1;
+const TAG_GPS_LATITUDE: u16 = 2;
+const TAG_GPS_LONGITUDE_REF: u16 = 3;
+const TAG_GPS_LONGITUDE: u16 = 4;
+const TAG_GPS_ALTITUDE_REF: u16 = 5;
+const TAG_GPS_ALTITUDE: u16 = 6;
+
+/// The EXIF fields read from an image.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Exif {
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    pub artist: Option<String>,
+    /// `DateTimeOriginal` as written, `YYYY:MM:DD HH:MM:SS`.
+    pub date_time_original: Option<String>,
+    /// `OffsetTimeOriginal`, e.g. `+02:00`.
+    pub offset_time_original: Option<String>,
+    pub gps: Option<Gps>,
+}
+
+impl Exif {
+    /// Reads the EXIF block of a JPEG, PNG or TIFF image. `None` if it has
+    /// none, or none that can be read.
+    pub fn read(image: &[u8]) -> Option<Self> {
+        let tiff = match ImageFormat::sniff(image)? {
+            ImageFormat::Jpeg => jpeg_segments(image)
+                .into_iter()
+                .filter(|s| s.marker == 0xE1)
+                .find_map(|s| image[s.payload].strip_prefix(JPEG_EXIF_ID))?,
+            ImageFormat::Png => png_chunks(image)
+                .into_iter()
+                .find(|c| &image[c.kind.clone()] == b"eXIf")
+                .map(|c| &image[c.data])?,
+            ImageFormat::Tiff => image,
+            ImageFormat::Heic | ImageFormat::Webp => return None,
+        };
+        let tiff = Tiff::new(tiff)?;
+        let ifd0 = tiff.ifd(tiff.u32(4)?)?;
+        let mut exif = Exif {
+            make: tiff.ascii(&ifd0, TAG_MAKE),
+            model: tiff.ascii(&ifd0, TAG_MODEL),
+            artist: tiff.ascii(&ifd0, TAG_ARTIST),
+            ..Exif::default()
+        };
+        if let Some(ifd) = tiff.long(&ifd0, TAG_EXIF_IFD).and_then(|at| tiff.ifd(at)) {
+            exif.date_time_original = tiff.ascii(&ifd, TAG_DATE_TIME_ORIGINAL);
+            exif.offset_time_original = tiff.ascii(&ifd, TAG_OFFSET_TIME_ORIGINAL);
+            exif.serial = tiff.ascii(&ifd, TAG_BODY_SERIAL_NUMBER);
+        }
+        if let Some(ifd) = tiff.long(&ifd0, TAG_GPS_IFD).and_then(|at| tiff.ifd(at)) {
+            exif.gps = tiff.gps(&ifd);
+        }
+        Some(exif)
+    }
+
+    /// `DateTimeOriginal` as RFC 3339, and whether its offset was recorded
+    /// (`OffsetTimeOriginal`) or UTC is assumed.
+    pub fn captured_at(&self) -> Option<(String, bool)> {
+        let raw = self.date_time_original.as_deref()?;
+        let (date, time) = raw.split_once(' ')?;
+        let offset = self.offset_time_original.as_deref();
+        let timestamp = format!("{}T{}{}", date.replace(':', "-"), time, offset.unwrap_or("Z"));
+        crate::time::parse_rfc3339(&timestamp)?;
+        Some((timestamp, offset.is_some()))
+    }
+}
+
+/// Fills the empty `creator`, `captured_at`, `device` and `gps` fields of
+/// `metadata` from the image's EXIF, or failing that its XMP, and returns
+/// the source of each value filled in, keyed by field (`device.make`, ...).
+pub fn fill_metadata(metadata: &mut Metadata, image: &[u8]) -> Map<String, Value> {
+    let mut filled = Map::new();
+    let exif = Exif::read(image).unwrap_or_default();
+    let xmp = xmp::Embedded::read(image).to_json();
+    let xmp = |name: &str| -> Option<String> {
+        match xmp.get("xmp")?.get(name)? {
+            Value::Array(values) => values.first()?.as_str().map(str::to_string),
+            value => value.as_str().map(str::to_string),
+        }
+    };
+
+    if metadata.creator.is_none() {
+        if let Some(artist) = exif.artist.clone() {
+            metadata.creator = Some(artist);
+            filled.insert("creator".into(), json!("exif:Artist"));
+        } else if let Some(creator) = xmp("dc:creator") {
+            metadata.creator = Some(creator);
+            filled.insert("creator".into(), json!("xmp:dc:creator"));
+        }
+    }
+    if metadata.captured_at.is_none() {
+        if let Some((captured_at, with_offset)) = exif.captured_at() {
+            metadata.captured_at = Some(captured_at);
+            let source = if with_offset {
+                "exif:DateTimeOriginal"
+            } else {
+                "exif:DateTimeOriginal (UTC assumed)"
+            };
+            filled.insert("captured_at".into(), json!(source));
+        } else if let Some(created) = xmp("xmp:CreateDate").filter(|t| crate::time::parse_rfc3339(t).is_some()) {
+            metadata.captured_at = Some(created);
+            filled.insert("captured_at".into(), json!("xmp:xmp:CreateDate"));
+        }
+    }
+    let device = metadata.device.get_or_insert_with(Device::default);
+    for (field, value, slot, source) in [
+        ("device.make", &exif.make, &mut device.make, "exif:Make"),
+        ("device.model", &exif.model, &mut device.model, "exif:Model"),
+        ("device.serial", &exif.serial, &mut device.serial, "exif:BodySerialNumber"),
+    ] {
+        if slot.is_none()
+            && let Some(value) = value
+        {
+            *slot = Some(value.clone());
+            filled.insert(field.into(), json!(source));
+        }
+    }
+    if metadata.device.as_ref().is_some_and(|d| *d == Device::default()) {
+        metadata.device = None;
+    }
+    if metadata.gps.is_none()
+        && let Some(gps) = exif.gps
+    {
+        metadata.gps = Some(gps);
+        filled.insert("gps".into(), json!("exif:GPS"));
+    }
+    filled
+}
+
+struct Tiff<'a> {
+    data: &'a [u8],
+    little: bool,
+}
+
+struct Entry {
+    tag: u16,
+    kind: u16,
+    count: u32,
+    // Where the value starts: in the entry if it fits in 4 bytes, otherwise
+    // at the offset the entry gives.
+    at: usize,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        match data.get(..4)? {
+            b"II*\0" => Some(Tiff { data, little: true }),
+            b"MM\0*" => Some(Tiff { data, little: false }),
+            _ => None,
+        }
+    }
+
+    fn u16(&self, at: usize) -> Option<u16> {
+        let bytes: [u8; 2] = self.data.get(at..at + 2)?.try_into().ok()?;
+        Some(if self.little { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+    }
+
+    fn u32(&self, at: usize) -> Option<u32> {
+        let bytes: [u8; 4] = self.data.get(at..at + 4)?.try_into().ok()?;
+        Some(if self.little { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+    }
+
+    fn ifd(&self, offset: u32) -> Option<Vec<Entry>> {
+        let offset = offset as usize;
+        let count = self.u16(offset)? as usize;
+        let mut entries = Vec::with_capacity(count);
+        for i in 0..count {
+            let entry = offset + 2 + i * 12;
+            let kind = self.u16(entry + 2)?;
+            let count = self.u32(entry + 4)?;
+            let size = match kind {
+                1 | 2 | 7 => 1,
+                3 => 2,
+                4 => 4,
+                5 => 8,
+                _ => 0,
+            } * count as usize;
+            let at = if size <= 4 { entry + 8 } else { self.u32(entry + 8)? as usize };
+            entries.push(Entry { tag: self.u16(entry)?, kind, count, at });
+        }
+        Some(entries)
+    }
+
+    fn find<'e>(&self, ifd: &'e [Entry], tag: u16) -> Option<&'e Entry> {
+        ifd.iter().find(|e| e.tag == tag)
+    }
+
+    fn ascii(&self, ifd: &[Entry], tag: u16) -> Option<String> {
+        let entry = self.find(ifd, tag).filter(|e| e.kind == 2)?;
+        let bytes = self.data.get(entry.at..entry.at + entry.count as usize)?;
+        let text = String::from_utf8_lossy(bytes);
+        let text = text.trim_end_matches('\0').trim();
+        (!text.is_empty()).then(|| text.to_string())
+    }
+
+    fn long(&self, ifd: &[Entry], tag: u16) -> Option<u32> {
+        let entry = self.find(ifd, tag)?;
+        match entry.kind {
+            3 => self.u16(entry.at).map(u32::from),
+            4 => self.u32(entry.at),
+            _ => None,
+        }
+    }
+
+    fn byte(&self, ifd: &[Entry], tag: u16) -> Option<u8> {
+        let entry = self.find(ifd, tag).filter(|e| e.kind == 1)?;
+        self.data.get(entry.at).copied()
+    }
+
+    fn rationals(&self, ifd: &[Entry], tag: u16) -> Option<Vec<f64>> {
+        let entry = self.find(ifd, tag).filter(|e| e.kind == 5)?;
+        (0..entry.count as usize)
+            .map(|i| {
+                let at = entry.at + i * 8;
+                let (numerator, denominator) = (self.u32(at)?, self.u32(at + 4)?);
+                (denominator != 0).then(|| numerator as f64 / denominator as f64)
+            })
+            .collect()
+    }
+
+    // Degrees, minutes and seconds, negated for the south and west.
+    fn coordinate(&self, ifd: &[Entry], tag: u16, ref_tag: u16, negative: &str) -> Option<f64> {
+        let parts = self.rationals(ifd, tag)?;
+        let [degrees, minutes, seconds] = parts.get(..3)? else {
+            return None;
+        };
+        let value = degrees + minutes / 60.0 + seconds / 3600.0;
+        Some(if self.ascii(ifd, ref_tag).as_deref() == Some(negative) { -value } else { value })
+    }
+
+    fn gps(&self, ifd: &[Entry]) -> Option<Gps> {
+        let latitude = self.coordinate(ifd, TAG_GPS_LATITUDE, TAG_GPS_LATITUDE_REF, "S")?;
+        let longitude = self.coordinate(ifd, TAG_GPS_LONGITUDE, TAG_GPS_LONGITUDE_REF, "W")?;
+        if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+            return None;
+        }
+        // Altitude reference 1 is below sea level.
+        let altitude = self
+            .rationals(ifd, TAG_GPS_ALTITUDE)
+            .and_then(|v| v.first().copied())
+            .map(|a| if self.byte(ifd, TAG_GPS_ALTITUDE_REF) == Some(1) { -a } else { a });
+        Some(Gps { latitude, longitude, altitude })
+    }
+}
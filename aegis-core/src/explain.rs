@@ -0,0 +1,358 @@
+// aegis-core/src/explain.rs
+
+// Answers "which bytes are signed?" for one container: where each part of
+// the file lies and whether the signature covers it, what (if anything) was
+// normalized before hashing, and every hashing step from the file's bytes
+// to the 32-byte message the signature is over, with the inputs in hex so
+// an auditor can recompute each digest with ordinary tools.
+
+use crate::chunked::{self, ChunkTable};
+use crate::crypto::{self, SignatureScheme};
+use crate::error::AegisError;
+use crate::format::{self, AegisAncient};
+use crate::zstd;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+/// Inputs longer than this are given by their byte range only.
+pub const MAX_INLINE_HEX: usize = 4096;
+
+/// A contiguous part of the container file.
+pub struct ByteRange {
+    pub name: String,
+    pub offset: u64,
+    pub length: u64,
+    pub signed: bool,
+    pub note: &'static str,
+}
+
+/// One input to a hashing step: a range of the file, or bytes that do not
+/// appear in it (a constant prefix, an earlier digest).
+pub struct StepInput {
+    pub label: String,
+    pub range: Option<(u64, u64)>,
+    pub bytes: Option<Vec<u8>>,
+}
+
+pub struct Step {
+    pub description: String,
+    pub inputs: Vec<StepInput>,
+    pub output: [u8; 32],
+}
+
+pub struct Explanation {
+    pub version: u8,
+    pub ranges: Vec<ByteRange>,
+    pub canonicalization: Vec<String>,
+    pub steps: Vec<Step>,
+    pub scheme: SignatureScheme,
+    /// The message the signature is over: the last step's output.
+    pub signed_message: [u8; 32],
+    pub signature_valid: bool,
+}
+
+/// Explains how `ancient`'s signature is computed. Fails like
+/// `crypto::verify()` for a container that cannot be verified at all.
+pub fn explain(ancient: &AegisAncient) -> Result<Explanation, AegisError> {
+    let scheme = SignatureScheme::of(&ancient.header)?;
+    let mut ranges = vec![
+        unsigned("magic", 0, format::MAGIC_PREFIX.len() as u64, "Identifies the file type."),
+        unsigned("version", format::MAGIC_PREFIX.len() as u64, 1, "Selects the layout."),
+    ];
+    let mut pos = format::MAGIC_NUMBER.len() as u64;
+    let mut block = |ranges: &mut Vec<ByteRange>, name: &str, length: usize, signed: bool, note: &'static str| {
+        ranges.push(unsigned(
+            &format!("{}.length", name),
+            pos,
+            format::BLOCK_LENGTH_PREFIX as u64,
+            "Length prefixes are not hashed.",
+        ));
+        pos += format::BLOCK_LENGTH_PREFIX as u64;
+        ranges.push(ByteRange {
+            name: name.to_string(),
+            offset: pos,
+            length: length as u64,
+            signed,
+            note,
+        });
+        pos += length as u64;
+        pos - length as u64
+    };
+
+    let mut extensions_range = None;
+    if ancient.version != format::VERSION_1 {
+        let header = ancient.header.to_bytes();
+        let start = block(
+            &mut ranges,
+            "header",
+            header.len(),
+            false,
+            "Not covered, except for the extensions field when header flag 1 is set.",
+        );
+        // Flags, then each field as tag, length and value.
+        let mut field_pos = start + 4;
+        for field in &ancient.header.fields {
+            field_pos += 6;
+            if field.tag == format::FIELD_EXTENSIONS {
+                extensions_range = Some((field_pos, field.value.len() as u64));
+            }
+            field_pos += field.value.len() as u64;
+        }
+    }
+    block(
+        &mut ranges,
+        "public_key",
+        ancient.public_key.len(),
+        false,
+        "Not hashed; the signature is checked against it, and trust in it comes from its fingerprint.",
+    );
+    // A compressed block is measured as `write()` would store it.
+    let compression = ancient.header.compression();
+    let stored = |bytes: &[u8], compressed: bool| match compressed {
+        true => (zstd::compress(bytes).len(), "Stored as a zstd frame; hashed after decompression."),
+        false => (bytes.len(), "Hashed byte for byte."),
+    };
+    let (length, note) = stored(ancient.metadata.as_bytes(), compression.metadata);
+    let metadata = Stored {
+        offset: block(&mut ranges, "metadata", length, true, note),
+        length: length as u64,
+        compressed: compression.metadata,
+    };
+    block(&mut ranges, "signature", ancient.signature.len(), false, "The signature itself.");
+    let (length, note) = stored(&ancient.image_data, compression.image);
+    let image = Stored {
+        offset: block(&mut ranges, "image", length, true, note),
+        length: length as u64,
+        compressed: compression.image,
+    };
+    if let Some((offset, length)) = extensions_range {
+        ranges.push(ByteRange {
+            name: format!("header.field[{}] (extensions)", format::FIELD_EXTENSIONS),
+            offset,
+            length,
+            signed: true,
+            note: "Covered through its SHA-256 when header flag 1 is set.",
+        });
+    }
+
+    let mut steps = match ancient.header.image_chunks()? {
+        None => vec![Step {
+            description: format!(
+                "{} of the metadata block followed by the image block, with nothing between them",
+                crypto::DIGEST_ALGORITHM
+            ),
+            inputs: vec![
+                metadata.input("metadata", ancient.metadata.as_bytes()),
+                image.input("image", &ancient.image_data),
+            ],
+            output: crypto::signing_digest(&ancient.metadata, &ancient.image_data),
+        }],
+        Some(table) => chunked_steps(&table, ancient, &metadata, &image)?,
+    };
+    let contents_digest = steps.last().expect("at least one step").output;
+    let signed_message = crypto::container_digest(&ancient.header, &contents_digest)?;
+    if let Some((offset, _)) = extensions_range {
+        let field = ancient.header.field(format::FIELD_EXTENSIONS).unwrap_or_default();
+        let field_digest: [u8; 32] = Sha256::digest(field).into();
+        steps.push(Step {
+            description: format!("{} of the extensions field", crypto::DIGEST_ALGORITHM),
+            inputs: vec![from_file("extensions field", offset, field)],
+            output: field_digest,
+        });
+        steps.push(Step {
+            description: format!(
+                "{} of the extensions prefix, the contents digest and the extensions digest",
+                crypto::DIGEST_ALGORITHM
+            ),
+            inputs: vec![
+                constant("prefix", crypto::EXTENSIONS_DIGEST_PREFIX),
+                constant("contents digest", &contents_digest),
+                constant("extensions digest", &field_digest),
+            ],
+            output: signed_message,
+        });
+    }
+
+    Ok(Explanation {
+        version: ancient.version,
+        ranges,
+        canonicalization: canonicalization(ancient),
+        steps,
+        scheme,
+        signed_message,
+        signature_valid: scheme.verify_digest(&ancient.public_key, &ancient.signature, &signed_message)?,
+    })
+}
+
+// The contents digest of a chunked image (see `chunked`): the metadata
+// digest, then the chunk table's root over the chunks hashed afresh.
+fn chunked_steps(
+    table: &ChunkTable,
+    ancient: &AegisAncient,
+    metadata: &Stored,
+    image: &Stored,
+) -> Result<Vec<Step>, AegisError> {
+    let rehashed = ChunkTable::of(&ancient.image_data, table.chunk_size)?;
+    let metadata_digest: [u8; 32] = Sha256::digest(ancient.metadata.as_bytes()).into();
+    let root = rehashed.root();
+    Ok(vec![
+        Step {
+            description: format!("{} of the metadata block", crypto::DIGEST_ALGORITHM),
+            inputs: vec![metadata.input("metadata", ancient.metadata.as_bytes())],
+            output: metadata_digest,
+        },
+        Step {
+            description: format!(
+                "Merkle root over the {} of each {}-byte chunk of the image block ({} chunks)",
+                crypto::DIGEST_ALGORITHM,
+                table.chunk_size,
+                rehashed.hashes.len()
+            ),
+            inputs: vec![image.input("image", &ancient.image_data)],
+            output: root,
+        },
+        Step {
+            description: format!(
+                "{} of the chunked image prefix, the metadata digest, the chunk size, the image length and the root",
+                crypto::DIGEST_ALGORITHM
+            ),
+            inputs: vec![
+                constant("prefix", chunked::CHUNKED_DIGEST_PREFIX),
+                constant("metadata digest", &metadata_digest),
+                constant("chunk size", &table.chunk_size.to_be_bytes()),
+                constant("image length", &rehashed.image_len.to_be_bytes()),
+                constant("root", &root),
+            ],
+            output: rehashed.contents_digest(&ancient.metadata),
+        },
+    ])
+}
+
+/// Where a signed block is stored.
+struct Stored {
+    offset: u64,
+    length: u64,
+    compressed: bool,
+}
+
+impl Stored {
+    /// The block as hashed: the bytes a compressed block decompresses to.
+    fn input(&self, label: &str, bytes: &[u8]) -> StepInput {
+        if !self.compressed {
+            return from_file(label, self.offset, bytes);
+        }
+        StepInput {
+            label: format!("{}, decompressed", label),
+            range: Some((self.offset, self.length)),
+            bytes: (bytes.len() <= MAX_INLINE_HEX).then(|| bytes.to_vec()),
+        }
+    }
+}
+
+fn unsigned(name: &str, offset: u64, length: u64, note: &'static str) -> ByteRange {
+    ByteRange {
+        name: name.to_string(),
+        offset,
+        length,
+        signed: false,
+        note,
+    }
+}
+
+fn from_file(label: &str, offset: u64, bytes: &[u8]) -> StepInput {
+    StepInput {
+        label: label.to_string(),
+        range: Some((offset, bytes.len() as u64)),
+        bytes: (bytes.len() <= MAX_INLINE_HEX).then(|| bytes.to_vec()),
+    }
+}
+
+fn constant(label: &str, bytes: &[u8]) -> StepInput {
+    StepInput {
+        label: label.to_string(),
+        range: None,
+        bytes: Some(bytes.to_vec()),
+    }
+}
+
+// What was, and was not, normalized before the bytes were hashed.
+fn canonicalization(ancient: &AegisAncient) -> Vec<String> {
+    let mut notes = vec![
+        "Verification applies no normalization: the metadata and image blocks are hashed exactly as stored, after decompressing any stored compressed.".to_string(),
+        "No length prefixes or separators are hashed, so the digest alone does not fix where the metadata ends and the image begins; the container's block lengths do.".to_string(),
+    ];
+    let metadata = &ancient.metadata;
+    if let Some(digest) = format::external_metadata_digest(metadata) {
+        notes.push(format!(
+            "The metadata block is a reference to an external document with SHA-256 {}. Only the reference is signed; the document (header field {}, not itself covered) is bound through that hash.",
+            hex::encode(digest),
+            format::FIELD_EXTERNAL_METADATA,
+        ));
+    } else {
+        match serde_json::from_str::<Value>(metadata) {
+            Ok(value) if value.is_object() && serde_json::to_string(&value).ok().as_deref() == Some(metadata.as_str()) => notes.push(
+                "The metadata is canonical JSON (no insignificant whitespace, object keys sorted at every level), as sealers write it, so a re-serialized copy of the same document hashes the same.".into(),
+            ),
+            Ok(value) if value.is_object() => notes.push(
+                "The metadata is JSON but not in canonical form; a re-serialized copy of the same document will not hash the same.".into(),
+            ),
+            _ => notes.push("The metadata is free-form text.".into()),
+        }
+    }
+    let compressed = ancient.header.compression().blocks();
+    if !compressed.is_empty() {
+        notes.push(format!(
+            "The {} stored as zstd frames (header flags {} and {}); the signature is over the decompressed bytes, so compressing or decompressing a container does not affect it.",
+            match compressed.as_slice() {
+                [block] => format!("{} block is", block),
+                _ => "metadata and image blocks are".to_string(),
+            },
+            format::FLAG_ZSTD_METADATA,
+            format::FLAG_ZSTD_IMAGE,
+        ));
+    }
+    if let Ok(Some(table)) = ancient.header.image_chunks() {
+        notes.push(format!(
+            "The image is signed in {}-byte chunks through a Merkle root, and the chunk size and image length are hashed with it. The chunk hashes listed in header field {} are not needed for a full verification, which hashes the chunks afresh; they let a byte range be checked alone.",
+            table.chunk_size,
+            format::FIELD_IMAGE_CHUNKS,
+        ));
+    }
+    notes.push(
+        "The image is the bytes the sealer stored, after any sanitization (such as stripping EXIF) it applied at sealing time.".into(),
+    );
+    notes.push(format!(
+        "The signature scheme hashes the signed message once more with {} before the ECDSA operation.",
+        crypto::DIGEST_ALGORITHM
+    ));
+    notes
+}
+
+impl Explanation {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "version": char::from(self.version).to_string(),
+            "ranges": self.ranges.iter().map(|r| json!({
+                "name": r.name,
+                "offset": r.offset,
+                "length": r.length,
+                "signed": r.signed,
+                "note": r.note,
+            })).collect::<Vec<_>>(),
+            "canonicalization": self.canonicalization,
+            "steps": self.steps.iter().map(|s| json!({
+                "description": s.description,
+                "inputs": s.inputs.iter().map(|i| json!({
+                    "label": i.label,
+                    "offset": i.range.map(|r| r.0),
+                    "length": i.range.map_or(i.bytes.as_ref().map_or(0, |b| b.len() as u64), |r| r.1),
+                    "hex": i.bytes.as_ref().map(hex::encode),
+                })).collect::<Vec<_>>(),
+                "output": hex::encode(s.output),
+            })).collect::<Vec<_>>(),
+            "signature_scheme": self.scheme.name(),
+            "signed_message": hex::encode(self.signed_message),
+            "signature_valid": self.signature_valid,
+        })
+    }
+}
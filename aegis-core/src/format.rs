@@ -0,0 +1,1303 @@
+use crate::chunked::ChunkTable;
+use crate::dns_trust::TrustHint;
+use crate::endorsement::Endorsement;
+use crate::error::AegisError;
+use crate::merkle::{self, InclusionProof};
+use base64ct::Encoding;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+// Only import `Read` when the `verifier` feature is enabled.
+#[cfg(feature = "verifier")]
+use std::io::Read;
+use std::io::Write;
+
+/// Every container starts with these five bytes followed by a one-byte
+/// format version.
+pub const MAGIC_PREFIX: &[u8; 5] = b"AEGIS";
+
+/// The original layout: four blocks and nothing else.
+pub const VERSION_1: u8 = b'1';
+/// Adds a header block (flags and tagged fields) in front of the other
+/// blocks.
+pub const VERSION_2: u8 = b'2';
+/// The version new containers are written in.
+pub const CURRENT_VERSION: u8 = VERSION_2;
+pub const SUPPORTED_VERSIONS: [u8; 2] = [VERSION_1, VERSION_2];
+
+/// Magic number of version 1 containers.
+pub const MAGIC_NUMBER: &[u8; 6] = b"AEGIS1";
+
+pub const MAX_BLOCK_SIZE: u64 = 1_000_000_000; // 1GB limit
+
+/// What any compressed block may decompress to, whatever its stored size.
+pub const MIN_INFLATE_LIMIT: usize = 16 * 1024 * 1024;
+/// Past `MIN_INFLATE_LIMIT`, the most a compressed block may grow by when
+/// decompressed. Images that compress better than this are rare, and only
+/// matter above 16 MiB.
+pub const MAX_COMPRESSION_RATIO: usize = 1024;
+
+/// Size of the big-endian length prefix in front of every block.
+pub const BLOCK_LENGTH_PREFIX: usize = 8;
+
+/// Flag set when the signature also covers the `FIELD_EXTENSIONS` field
+/// (see `crypto::container_digest`). Readers that predate it reject such
+/// containers rather than verify them without their extensions.
+pub const FLAG_SIGNED_EXTENSIONS: u32 = 1;
+
+/// Flag set when the image is signed in chunks listed in
+/// `FIELD_IMAGE_CHUNKS` (see `chunked`). Readers that predate it reject such
+/// containers, whose signature they could not check.
+pub const FLAG_CHUNKED_IMAGE: u32 = 2;
+
+/// Flag set when the metadata block is stored as a zstd frame (see `zstd`).
+/// The signature is over the metadata as it was before compression, so the
+/// flag changes only how the block is stored.
+pub const FLAG_ZSTD_METADATA: u32 = 4;
+
+/// Flag set when the image block is stored as a zstd frame, as for
+/// `FLAG_ZSTD_METADATA`. Not combined with `FLAG_CHUNKED_IMAGE`, whose
+/// chunks are ranges of the stored bytes.
+pub const FLAG_ZSTD_IMAGE: u32 = 8;
+
+/// Flag bits this implementation understands. Readers reject containers
+/// with any other bit set, since a flag may change how the image is to be
+/// interpreted.
+pub const KNOWN_FLAGS: u32 = FLAG_SIGNED_EXTENSIONS | FLAG_CHUNKED_IMAGE | FLAG_ZSTD_METADATA | FLAG_ZSTD_IMAGE;
+
+/// Header field holding the UTF-8 ID of the keyring key that sealed the
+/// container (see `keys::Keyring`).
+pub const FIELD_KEY_ID: u16 = 1;
+
+/// Header field holding the big-endian `u16` ID of the signature scheme
+/// (see `crypto::SignatureScheme`). Absent means ECDSA P-256, so that
+/// containers from before the field existed keep verifying.
+pub const FIELD_SIGNATURE_SCHEME: u16 = 2;
+
+/// Header field holding a DER RFC 3161 timestamp token over the SHA-256 of
+/// the signature (see `timestamp`).
+pub const FIELD_TIMESTAMP_TOKEN: u16 = 3;
+
+/// Header field holding a metadata document kept out of the metadata block,
+/// for documents (full IPTC or XMP dumps) too large to want in every read of
+/// the signed blocks. The metadata block then holds only
+/// `EXTERNAL_METADATA_PREFIX` and the document's hex SHA-256, which the
+/// signature covers, so the document is bound all the same.
+pub const FIELD_EXTERNAL_METADATA: u16 = 4;
+
+/// Start of a metadata block that refers to a `FIELD_EXTERNAL_METADATA`
+/// document.
+pub const EXTERNAL_METADATA_PREFIX: &str = "aegis:external-metadata:sha256:";
+
+/// Header field holding integrator-defined extensions, covered by the
+/// signature when `FLAG_SIGNED_EXTENSIONS` is set. Each extension is a
+/// 2-byte big-endian name length, the name, a 1-byte `ExtensionKind`, a
+/// 4-byte big-endian value length and the value, in ascending name order.
+pub const FIELD_EXTENSIONS: u16 = 5;
+
+/// Longest extension name. Names use lowercase ASCII letters, digits and
+/// `.`, `_` or `-`, and should start with the integrator's own prefix
+/// (`acme.claim-id`) so they cannot collide.
+pub const MAX_EXTENSION_NAME: usize = 64;
+
+/// How an extension's value is to be read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExtensionKind {
+    Bytes = 0,
+    /// UTF-8 JSON text.
+    Json = 1,
+}
+
+/// One named extension of a container (see `FIELD_EXTENSIONS`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Extension {
+    pub name: String,
+    pub kind: ExtensionKind,
+    pub value: Vec<u8>,
+}
+
+impl Extension {
+    pub fn bytes(name: &str, value: Vec<u8>) -> Self {
+        Extension { name: name.to_string(), kind: ExtensionKind::Bytes, value }
+    }
+
+    /// A JSON extension; the text must be valid JSON.
+    pub fn json(name: &str, text: &str) -> Result<Self, AegisError> {
+        serde_json::from_str::<serde_json::Value>(text)
+            .map_err(|e| AegisError::InvalidExtension(format!("'{}' is not valid JSON: {}", name, e)))?;
+        Ok(Extension { name: name.to_string(), kind: ExtensionKind::Json, value: text.as_bytes().to_vec() })
+    }
+
+    /// The extension for display: JSON values parsed, bytes as base64.
+    pub fn to_json(&self) -> serde_json::Value {
+        match self.kind {
+            ExtensionKind::Json => serde_json::json!({
+                "name": self.name,
+                "type": "json",
+                "value": serde_json::from_slice::<serde_json::Value>(&self.value).unwrap_or(serde_json::Value::Null),
+            }),
+            ExtensionKind::Bytes => serde_json::json!({
+                "name": self.name,
+                "type": "bytes",
+                "base64": base64ct::Base64::encode_string(&self.value),
+                "length": self.value.len(),
+            }),
+        }
+    }
+}
+
+fn check_extension_name(name: &str) -> Result<(), AegisError> {
+    let valid_char = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '.' | '_' | '-');
+    if name.is_empty() || name.len() > MAX_EXTENSION_NAME || !name.chars().all(valid_char) {
+        return Err(AegisError::InvalidExtension(format!(
+            "name '{}' must be 1 to {} of a-z, 0-9, '.', '_' and '-'",
+            name, MAX_EXTENSION_NAME
+        )));
+    }
+    Ok(())
+}
+
+/// Header field holding co-signatures added after sealing (see
+/// `crypto::countersign()`). Each is the SEC1 public key, the signature and
+/// the UTF-8 role, each behind a 2-byte big-endian length, then the signing
+/// time as an 8-byte big-endian Unix time. The container signature does not
+/// cover the field, so co-signers can be added without re-sealing; each
+/// co-signature covers the container digest itself instead (see
+/// `crypto::cosignature_object()`).
+pub const FIELD_COSIGNATURES: u16 = 6;
+
+/// Longest co-signer role.
+pub const MAX_ROLE: usize = 64;
+
+/// One co-signer's signature over a container (see `FIELD_COSIGNATURES`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cosignature {
+    pub public_key: Vec<u8>,
+    pub signature: Vec<u8>,
+    /// What the co-signer signs as, e.g. `agency`.
+    pub role: String,
+    /// When the co-signer signed, in Unix seconds, as the co-signer states it.
+    pub signed_at: i64,
+}
+
+fn check_role(role: &str) -> Result<(), AegisError> {
+    if role.is_empty() || role.len() > MAX_ROLE || role.chars().any(char::is_control) {
+        return Err(AegisError::InvalidCosignature(format!(
+            "role '{}' must be 1 to {} bytes without control characters",
+            role, MAX_ROLE
+        )));
+    }
+    Ok(())
+}
+
+/// Header field holding the X.509 certificate chain of the sealing key
+/// (see `x509`), leaf first, each DER certificate behind a 4-byte big-endian
+/// length. Not covered by the signature: the leaf must certify the
+/// container's public key, so a substituted chain cannot vouch for a key
+/// other than the one that signed.
+pub const FIELD_CERTIFICATE_CHAIN: u16 = 7;
+
+/// Header field naming where the sealing key is published in DNS (see
+/// `dns_trust`): the domain and the selector, each behind a 1-byte length.
+/// Not covered by the signature, since the record it points to must publish
+/// the container's own key to vouch for anything.
+pub const FIELD_TRUST_HINT: u16 = 8;
+
+/// Header field holding endorsements of the sealing key and of the keys
+/// that endorsed it (see `endorsement`). Each is the issuer's and the
+/// subject's SEC1 public keys, the UTF-8 role and the signature, each
+/// behind a 2-byte big-endian length, then the start and end of its
+/// validity as 8-byte big-endian Unix times. Not covered by the signature:
+/// each endorsement is signed by its issuer, and only a chain leading to a
+/// root the verifier chose vouches for anything.
+pub const FIELD_ENDORSEMENTS: u16 = 9;
+
+/// Header field holding the proof that the seal was entered in the sealing
+/// service's transparency log (see `merkle`): the leaf index and tree size
+/// as 8-byte big-endian integers, then the 32-byte audit path hashes. Not
+/// covered by the signature, since a proof only counts against a tree head
+/// the log signed.
+pub const FIELD_LOG_INCLUSION: u16 = 10;
+
+/// Header field listing the chunks of a chunked image (see `chunked`): the
+/// chunk size as a 4-byte and the image length as an 8-byte big-endian
+/// integer, then the 32-byte SHA-256 of each chunk. Set with
+/// `FLAG_CHUNKED_IMAGE`; the signature covers the Merkle root of the hashes
+/// rather than the field itself.
+pub const FIELD_IMAGE_CHUNKS: u16 = 11;
+
+/// A block of the container, in the order they appear on disk.
+pub struct BlockSpec {
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+pub const BLOCKS: [BlockSpec; 4] = [
+    BlockSpec {
+        name: "public_key",
+        description: "SEC1-encoded P-256 public key of the signer.",
+    },
+    BlockSpec {
+        name: "metadata",
+        description: "UTF-8 metadata string supplied at seal time.",
+    },
+    BlockSpec {
+        name: "signature",
+        description: "ECDSA signature: 32-byte r followed by 32-byte s.",
+    },
+    BlockSpec {
+        name: "image_data",
+        description: "The sealed payload bytes, unmodified.",
+    },
+];
+
+/// The version 2 header block, which precedes the public key.
+pub const HEADER_BLOCK: BlockSpec = BlockSpec {
+    name: "header",
+    description: "Version 2 and later: 4-byte big-endian flags, then tagged fields, each a 2-byte big-endian tag, a 4-byte big-endian length and the value.",
+};
+
+/// One tagged field of the version 2 header. Readers keep fields with tags
+/// they do not know so that rewriting a container preserves them, and reject
+/// a header with two fields of the same tag, which readers could otherwise
+/// resolve to different values.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HeaderField {
+    pub tag: u16,
+    pub value: Vec<u8>,
+}
+
+/// The header block of a version 2 container. Its contents are not covered
+/// by the signature, with the one exception of signed extensions; anything
+/// else that must be authenticated belongs in the metadata.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FormatHeader {
+    pub flags: u32,
+    pub fields: Vec<HeaderField>,
+}
+
+impl FormatHeader {
+    pub fn is_empty(&self) -> bool {
+        self.flags == 0 && self.fields.is_empty()
+    }
+
+    pub fn field(&self, tag: u16) -> Option<&[u8]> {
+        self.fields.iter().find(|f| f.tag == tag).map(|f| f.value.as_slice())
+    }
+
+    /// Sets a field, replacing any existing field with the same tag.
+    pub fn set_field(&mut self, tag: u16, value: Vec<u8>) {
+        self.fields.retain(|f| f.tag != tag);
+        self.fields.push(HeaderField { tag, value });
+    }
+
+    pub fn key_id(&self) -> Option<&str> {
+        self.field(FIELD_KEY_ID).and_then(|v| std::str::from_utf8(v).ok())
+    }
+
+    pub fn set_key_id(&mut self, id: &str) {
+        self.set_field(FIELD_KEY_ID, id.as_bytes().to_vec());
+    }
+
+    /// The raw signature scheme ID, if the field is present and well formed.
+    pub fn scheme_id(&self) -> Option<u16> {
+        self.field(FIELD_SIGNATURE_SCHEME)
+            .and_then(|v| <[u8; 2]>::try_from(v).ok())
+            .map(u16::from_be_bytes)
+    }
+
+    pub fn set_scheme_id(&mut self, id: u16) {
+        self.set_field(FIELD_SIGNATURE_SCHEME, id.to_be_bytes().to_vec());
+    }
+
+    pub fn timestamp_token(&self) -> Option<&[u8]> {
+        self.field(FIELD_TIMESTAMP_TOKEN)
+    }
+
+    pub fn set_timestamp_token(&mut self, token: Vec<u8>) {
+        self.set_field(FIELD_TIMESTAMP_TOKEN, token);
+    }
+
+    pub fn external_metadata(&self) -> Option<&[u8]> {
+        self.field(FIELD_EXTERNAL_METADATA)
+    }
+
+    /// Stores `document` in the header and returns the metadata string to
+    /// sign in its place.
+    pub fn set_external_metadata(&mut self, document: &str) -> String {
+        self.set_field(FIELD_EXTERNAL_METADATA, document.as_bytes().to_vec());
+        format!("{}{}", EXTERNAL_METADATA_PREFIX, hex::encode(Sha256::digest(document)))
+    }
+
+    /// The metadata a container vouches for: `metadata` itself, or, if it
+    /// refers to an external document, that document once its hash has been
+    /// checked against the reference.
+    #[cfg(feature = "verifier")]
+    pub fn resolve_metadata(&self, metadata: &str) -> Result<String, AegisError> {
+        let Some(digest) = external_metadata_digest(metadata) else {
+            return Ok(metadata.to_string());
+        };
+        let document = self
+            .external_metadata()
+            .filter(|document| Sha256::digest(document)[..] == digest[..])
+            .ok_or(AegisError::ExternalMetadataMismatch)?;
+        String::from_utf8(document.to_vec()).map_err(|_| AegisError::InvalidFormat)
+    }
+
+    /// Stores `extensions` and sets `FLAG_SIGNED_EXTENSIONS`, so they must be
+    /// set before the container is signed.
+    pub fn set_extensions(&mut self, extensions: &[Extension]) -> Result<(), AegisError> {
+        let mut sorted: Vec<&Extension> = extensions.iter().collect();
+        sorted.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut value = Vec::new();
+        for (i, extension) in sorted.iter().enumerate() {
+            check_extension_name(&extension.name)?;
+            if i > 0 && sorted[i - 1].name == extension.name {
+                return Err(AegisError::InvalidExtension(format!("'{}' is given twice", extension.name)));
+            }
+            value.extend_from_slice(&(extension.name.len() as u16).to_be_bytes());
+            value.extend_from_slice(extension.name.as_bytes());
+            value.push(extension.kind as u8);
+            value.extend_from_slice(&(extension.value.len() as u32).to_be_bytes());
+            value.extend_from_slice(&extension.value);
+        }
+        self.set_field(FIELD_EXTENSIONS, value);
+        self.flags |= FLAG_SIGNED_EXTENSIONS;
+        Ok(())
+    }
+
+    /// The container's signed extensions. Extensions present without
+    /// `FLAG_SIGNED_EXTENSIONS` were not signed and are an error, as is a
+    /// flag without the field.
+    pub fn extensions(&self) -> Result<Vec<Extension>, AegisError> {
+        let signed = self.flags & FLAG_SIGNED_EXTENSIONS != 0;
+        let mut rest = match (self.field(FIELD_EXTENSIONS), signed) {
+            (None, false) => return Ok(Vec::new()),
+            (Some(value), true) => value,
+            _ => return Err(AegisError::InvalidExtension("extensions field and flag disagree".into())),
+        };
+        let malformed = || AegisError::InvalidExtension("malformed extensions field".into());
+        let mut extensions: Vec<Extension> = Vec::new();
+        while !rest.is_empty() {
+            let name_len = u16::from_be_bytes(rest.get(..2).ok_or_else(malformed)?.try_into().expect("2 bytes")) as usize;
+            let name = rest.get(2..2 + name_len).ok_or_else(malformed)?;
+            let name = String::from_utf8(name.to_vec()).map_err(|_| malformed())?;
+            rest = &rest[2 + name_len..];
+            let kind = match rest.first() {
+                Some(0) => ExtensionKind::Bytes,
+                Some(1) => ExtensionKind::Json,
+                _ => return Err(malformed()),
+            };
+            let len = u32::from_be_bytes(rest.get(1..5).ok_or_else(malformed)?.try_into().expect("4 bytes")) as usize;
+            let value = rest.get(5..5 + len).ok_or_else(malformed)?.to_vec();
+            rest = &rest[5 + len..];
+            check_extension_name(&name)?;
+            if extensions.last().is_some_and(|last| last.name >= name) {
+                return Err(AegisError::InvalidExtension("extensions are not in ascending name order".into()));
+            }
+            extensions.push(Extension { name, kind, value });
+        }
+        Ok(extensions)
+    }
+
+    /// The container's co-signatures, in the order they were added.
+    pub fn cosignatures(&self) -> Result<Vec<Cosignature>, AegisError> {
+        let malformed = || AegisError::InvalidCosignature("malformed co-signatures field".into());
+        let mut rest = self.field(FIELD_COSIGNATURES).unwrap_or_default();
+        let next = |rest: &mut &[u8]| -> Result<Vec<u8>, AegisError> {
+            let len = u16::from_be_bytes(rest.get(..2).ok_or_else(malformed)?.try_into().expect("2 bytes")) as usize;
+            let value = rest.get(2..2 + len).ok_or_else(malformed)?.to_vec();
+            *rest = &rest[2 + len..];
+            Ok(value)
+        };
+        let mut cosignatures = Vec::new();
+        while !rest.is_empty() {
+            let public_key = next(&mut rest)?;
+            let signature = next(&mut rest)?;
+            let role = String::from_utf8(next(&mut rest)?).map_err(|_| malformed())?;
+            let signed_at = i64::from_be_bytes(rest.get(..8).ok_or_else(malformed)?.try_into().expect("8 bytes"));
+            rest = &rest[8..];
+            check_role(&role)?;
+            cosignatures.push(Cosignature { public_key, signature, role, signed_at });
+        }
+        Ok(cosignatures)
+    }
+
+    /// Appends a co-signature after any already present.
+    pub fn add_cosignature(&mut self, cosignature: &Cosignature) -> Result<(), AegisError> {
+        check_role(&cosignature.role)?;
+        let mut value = self.field(FIELD_COSIGNATURES).unwrap_or_default().to_vec();
+        for part in [&cosignature.public_key[..], &cosignature.signature, cosignature.role.as_bytes()] {
+            value.extend_from_slice(&(part.len() as u16).to_be_bytes());
+            value.extend_from_slice(part);
+        }
+        value.extend_from_slice(&cosignature.signed_at.to_be_bytes());
+        self.set_field(FIELD_COSIGNATURES, value);
+        Ok(())
+    }
+
+    /// The DER certificates of `FIELD_CERTIFICATE_CHAIN`, leaf first; empty
+    /// if there is none.
+    pub fn certificate_chain(&self) -> Result<Vec<Vec<u8>>, AegisError> {
+        let malformed = || AegisError::InvalidCertificate("malformed certificate chain field".into());
+        let mut rest = self.field(FIELD_CERTIFICATE_CHAIN).unwrap_or_default();
+        let mut chain = Vec::new();
+        while !rest.is_empty() {
+            let len = u32::from_be_bytes(rest.get(..4).ok_or_else(malformed)?.try_into().expect("4 bytes")) as usize;
+            chain.push(rest.get(4..4 + len).ok_or_else(malformed)?.to_vec());
+            rest = &rest[4 + len..];
+        }
+        Ok(chain)
+    }
+
+    /// Stores `chain`, leaf first. Callers check that the leaf certifies the
+    /// sealing key (see `x509::check_leaf()`).
+    pub fn set_certificate_chain(&mut self, chain: &[crate::x509::Certificate]) {
+        let mut value = Vec::new();
+        for certificate in chain {
+            value.extend_from_slice(&(certificate.to_der().len() as u32).to_be_bytes());
+            value.extend_from_slice(certificate.to_der());
+        }
+        self.set_field(FIELD_CERTIFICATE_CHAIN, value);
+    }
+
+    /// The DNS trust hint, if the container carries one.
+    pub fn trust_hint(&self) -> Result<Option<TrustHint>, AegisError> {
+        let Some(mut rest) = self.field(FIELD_TRUST_HINT) else {
+            return Ok(None);
+        };
+        let malformed = || AegisError::InvalidTrustHint("malformed trust hint field".into());
+        let mut next = || -> Result<String, AegisError> {
+            let len = *rest.first().ok_or_else(malformed)? as usize;
+            let value = rest.get(1..1 + len).ok_or_else(malformed)?;
+            rest = &rest[1 + len..];
+            String::from_utf8(value.to_vec()).map_err(|_| malformed())
+        };
+        let (domain, selector) = (next()?, next()?);
+        TrustHint::new(&domain, &selector).map(Some)
+    }
+
+    pub fn set_trust_hint(&mut self, hint: &TrustHint) {
+        let mut value = Vec::new();
+        for part in [&hint.domain, &hint.selector] {
+            value.push(part.len() as u8);
+            value.extend_from_slice(part.as_bytes());
+        }
+        self.set_field(FIELD_TRUST_HINT, value);
+    }
+
+    /// The endorsements of `FIELD_ENDORSEMENTS`; empty if there are none.
+    pub fn endorsements(&self) -> Result<Vec<Endorsement>, AegisError> {
+        let malformed = || AegisError::InvalidEndorsement("malformed endorsements field".into());
+        let mut rest = self.field(FIELD_ENDORSEMENTS).unwrap_or_default();
+        let mut endorsements = Vec::new();
+        while !rest.is_empty() {
+            let mut parts = Vec::with_capacity(4);
+            for _ in 0..4 {
+                let len = u16::from_be_bytes(rest.get(..2).ok_or_else(malformed)?.try_into().expect("2 bytes")) as usize;
+                parts.push(rest.get(2..2 + len).ok_or_else(malformed)?.to_vec());
+                rest = &rest[2 + len..];
+            }
+            let times = rest.get(..16).ok_or_else(malformed)?;
+            let [issuer, subject, role, signature] = <[Vec<u8>; 4]>::try_from(parts).map_err(|_| malformed())?;
+            endorsements.push(Endorsement {
+                issuer,
+                subject,
+                role: String::from_utf8(role).map_err(|_| malformed())?,
+                not_before: i64::from_be_bytes(times[..8].try_into().expect("8 bytes")),
+                not_after: i64::from_be_bytes(times[8..].try_into().expect("8 bytes")),
+                signature,
+            });
+            rest = &rest[16..];
+        }
+        Ok(endorsements)
+    }
+
+    /// Stores `endorsements`, replacing any already there.
+    pub fn set_endorsements(&mut self, endorsements: &[Endorsement]) {
+        let mut value = Vec::new();
+        for endorsement in endorsements {
+            for part in [&endorsement.issuer[..], &endorsement.subject, endorsement.role.as_bytes(), &endorsement.signature] {
+                value.extend_from_slice(&(part.len() as u16).to_be_bytes());
+                value.extend_from_slice(part);
+            }
+            value.extend_from_slice(&endorsement.not_before.to_be_bytes());
+            value.extend_from_slice(&endorsement.not_after.to_be_bytes());
+        }
+        self.set_field(FIELD_ENDORSEMENTS, value);
+    }
+
+    /// The transparency log inclusion proof, if the container carries one.
+    pub fn log_inclusion(&self) -> Result<Option<InclusionProof>, AegisError> {
+        let Some(value) = self.field(FIELD_LOG_INCLUSION) else {
+            return Ok(None);
+        };
+        let malformed = || AegisError::InvalidProof("malformed log inclusion field".into());
+        if value.len() < 16 || (value.len() - 16) % 32 != 0 || (value.len() - 16) / 32 > merkle::MAX_PROOF_LEN {
+            return Err(malformed());
+        }
+        Ok(Some(InclusionProof {
+            leaf_index: u64::from_be_bytes(value[..8].try_into().expect("8 bytes")),
+            tree_size: u64::from_be_bytes(value[8..16].try_into().expect("8 bytes")),
+            audit_path: value[16..].chunks(32).map(|h| h.try_into().expect("32 bytes")).collect(),
+        }))
+    }
+
+    pub fn set_log_inclusion(&mut self, proof: &InclusionProof) {
+        let mut value = Vec::with_capacity(16 + 32 * proof.audit_path.len());
+        value.extend_from_slice(&proof.leaf_index.to_be_bytes());
+        value.extend_from_slice(&proof.tree_size.to_be_bytes());
+        for hash in &proof.audit_path {
+            value.extend_from_slice(hash);
+        }
+        self.set_field(FIELD_LOG_INCLUSION, value);
+    }
+
+    /// The chunk table of a chunked image, or `None` for a flat one. Fails
+    /// if `FLAG_CHUNKED_IMAGE` and the field disagree.
+    pub fn image_chunks(&self) -> Result<Option<ChunkTable>, AegisError> {
+        match (self.field(FIELD_IMAGE_CHUNKS), self.flags & FLAG_CHUNKED_IMAGE != 0) {
+            (None, false) => Ok(None),
+            (Some(_), true) if self.compression().image => {
+                Err(AegisError::InvalidChunks("a chunked image cannot be compressed".into()))
+            }
+            (Some(value), true) => ChunkTable::parse(value).map(Some),
+            _ => Err(AegisError::InvalidChunks("image chunks field and flag disagree".into())),
+        }
+    }
+
+    /// Stores `table` and sets `FLAG_CHUNKED_IMAGE`. The signature covers
+    /// the table's root, so it must be set along with a signature made over
+    /// `ChunkTable::contents_digest()`.
+    pub fn set_image_chunks(&mut self, table: &ChunkTable) {
+        self.flags |= FLAG_CHUNKED_IMAGE;
+        self.set_field(FIELD_IMAGE_CHUNKS, table.to_bytes());
+    }
+
+    /// The blocks stored compressed.
+    pub fn compression(&self) -> Compression {
+        Compression {
+            metadata: self.flags & FLAG_ZSTD_METADATA != 0,
+            image: self.flags & FLAG_ZSTD_IMAGE != 0,
+        }
+    }
+
+    /// Sets or clears `FLAG_ZSTD_METADATA` and `FLAG_ZSTD_IMAGE`. Neither is
+    /// covered by the signature, so this may be changed on a sealed
+    /// container before it is written.
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.flags &= !(FLAG_ZSTD_METADATA | FLAG_ZSTD_IMAGE);
+        if compression.metadata {
+            self.flags |= FLAG_ZSTD_METADATA;
+        }
+        if compression.image {
+            self.flags |= FLAG_ZSTD_IMAGE;
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.flags.to_be_bytes().to_vec();
+        for field in &self.fields {
+            out.extend_from_slice(&field.tag.to_be_bytes());
+            out.extend_from_slice(&(field.value.len() as u32).to_be_bytes());
+            out.extend_from_slice(&field.value);
+        }
+        out
+    }
+
+    #[cfg(feature = "verifier")]
+    pub fn parse(bytes: &[u8]) -> Result<Self, AegisError> {
+        let flags_bytes = bytes.get(..4).ok_or(AegisError::InvalidFormat)?;
+        let flags = u32::from_be_bytes(flags_bytes.try_into().expect("slice is 4 bytes"));
+        if flags & !KNOWN_FLAGS != 0 {
+            return Err(AegisError::UnsupportedFlags(flags & !KNOWN_FLAGS));
+        }
+        let mut fields = Vec::new();
+        let mut rest = &bytes[4..];
+        while !rest.is_empty() {
+            let prefix = rest.get(..6).ok_or(AegisError::InvalidFormat)?;
+            let tag = u16::from_be_bytes([prefix[0], prefix[1]]);
+            let len = u32::from_be_bytes([prefix[2], prefix[3], prefix[4], prefix[5]]) as usize;
+            let value = rest.get(6..6 + len).ok_or(AegisError::InvalidFormat)?;
+            if fields.iter().any(|f: &HeaderField| f.tag == tag) {
+                return Err(AegisError::InvalidFormat);
+            }
+            fields.push(HeaderField { tag, value: value.to_vec() });
+            rest = &rest[6 + len..];
+        }
+        Ok(FormatHeader { flags, fields })
+    }
+}
+
+/// Which blocks of a container are stored as zstd frames.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Compression {
+    pub metadata: bool,
+    pub image: bool,
+}
+
+impl Compression {
+    /// Parses `none`, `all`, or a comma-separated list of `metadata` and
+    /// `image`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut compression = Compression::default();
+        for block in text.split(',').map(str::trim) {
+            match block {
+                "none" => {}
+                "all" => compression = Compression { metadata: true, image: true },
+                "metadata" => compression.metadata = true,
+                "image" => compression.image = true,
+                _ => return None,
+            }
+        }
+        Some(compression)
+    }
+
+    pub fn is_none(&self) -> bool {
+        !self.metadata && !self.image
+    }
+
+    /// The compressed blocks by name, e.g. `["metadata", "image"]`.
+    pub fn blocks(&self) -> Vec<&'static str> {
+        [("metadata", self.metadata), ("image", self.image)]
+            .into_iter()
+            .filter_map(|(name, on)| on.then_some(name))
+            .collect()
+    }
+}
+
+/// Decompresses a block stored as a zstd frame. Containers are read from
+/// untrusted uploads, so a block may inflate to `MIN_INFLATE_LIMIT`, or to
+/// `MAX_COMPRESSION_RATIO` times its stored size if that is more, but never
+/// past `inflate_limit` (itself capped at `MAX_BLOCK_SIZE`): a few kilobytes
+/// cannot demand a gigabyte, and a server picks what any one block may cost.
+#[cfg(feature = "verifier")]
+pub fn decompress_block(stored: &[u8], inflate_limit: usize) -> Result<Vec<u8>, AegisError> {
+    let limit = stored
+        .len()
+        .saturating_mul(MAX_COMPRESSION_RATIO)
+        .max(MIN_INFLATE_LIMIT)
+        .min(inflate_limit)
+        .min(MAX_BLOCK_SIZE as usize);
+    crate::zstd::decompress(stored, limit)
+}
+
+/// The SHA-256 an external metadata reference names, or `None` if
+/// `metadata` is an ordinary metadata string.
+pub fn external_metadata_digest(metadata: &str) -> Option<[u8; 32]> {
+    let hex_digest = metadata.strip_prefix(EXTERNAL_METADATA_PREFIX)?;
+    hex::decode(hex_digest).ok()?.try_into().ok()
+}
+
+/// Everything in a container up to the first image byte: the magic number
+/// and version, the blocks in front of the image and the image block's
+/// length prefix. Writing this followed by `image_len` image bytes yields a
+/// complete container, which lets large images be streamed from disk rather
+/// than held in memory. Written in `CURRENT_VERSION`. The metadata is
+/// compressed if the header says so; if it says the image is, the image
+/// bytes written after are the compressed ones and `image_len` their length.
+pub fn header_bytes(
+    header: &FormatHeader,
+    public_key: &[u8],
+    metadata: &str,
+    signature: &[u8],
+    image_len: u64,
+) -> Vec<u8> {
+    let metadata = stored_metadata(header, metadata);
+    encode_prefix(CURRENT_VERSION, header, public_key, &metadata, signature, image_len)
+        .expect("the current version can carry any header")
+}
+
+/// The metadata block as stored: compressed if `header` says so.
+fn stored_metadata<'a>(header: &FormatHeader, metadata: &'a str) -> Cow<'a, [u8]> {
+    match header.compression().metadata {
+        true => Cow::Owned(crate::zstd::compress(metadata.as_bytes())),
+        false => Cow::Borrowed(metadata.as_bytes()),
+    }
+}
+
+/// Starts a C2PA manifest for a sealed copy of `image`, a sanitized JPEG or
+/// PNG image whose container starts with `container_prefix` (see `c2pa`).
+/// Sign its `signing_message()`, then `finish()` it to get the image with
+/// the manifest embedded.
+pub fn to_c2pa_manifest(
+    image: &[u8],
+    container_prefix: &[u8],
+    public_key: &[u8],
+    metadata: &str,
+) -> Result<crate::c2pa::UnsignedManifest, AegisError> {
+    crate::c2pa::UnsignedManifest::new(image, container_prefix, public_key, metadata)
+}
+
+/// The blocks in front of the image, with `metadata` as stored.
+fn encode_prefix(
+    version: u8,
+    header: &FormatHeader,
+    public_key: &[u8],
+    metadata: &[u8],
+    signature: &[u8],
+    image_len: u64,
+) -> Result<Vec<u8>, AegisError> {
+    let header_block = match version {
+        VERSION_1 if header.is_empty() => None,
+        VERSION_1 => return Err(AegisError::HeaderNotSupported(version)),
+        VERSION_2 => Some(header.to_bytes()),
+        other => return Err(AegisError::UnsupportedVersion(other)),
+    };
+    let mut out = Vec::with_capacity(
+        MAGIC_PREFIX.len()
+            + 1
+            + 5 * BLOCK_LENGTH_PREFIX
+            + header_block.as_ref().map_or(0, Vec::len)
+            + public_key.len()
+            + metadata.len()
+            + signature.len(),
+    );
+    out.extend_from_slice(MAGIC_PREFIX);
+    out.push(version);
+    for block in header_block.as_deref().into_iter().chain([public_key, metadata, signature]) {
+        out.extend_from_slice(&(block.len() as u64).to_be_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&image_len.to_be_bytes());
+    Ok(out)
+}
+
+/// Checks the magic prefix and returns the version byte.
+#[cfg(feature = "verifier")]
+fn check_magic(magic: &[u8; 6]) -> Result<u8, AegisError> {
+    if magic[..MAGIC_PREFIX.len()] != *MAGIC_PREFIX {
+        return Err(AegisError::InvalidFormat);
+    }
+    match magic[MAGIC_PREFIX.len()] {
+        version if SUPPORTED_VERSIONS.contains(&version) => Ok(version),
+        other => Err(AegisError::UnsupportedVersion(other)),
+    }
+}
+
+/// The blocks in front of the image, parsed from the start of a container.
+/// The metadata is decompressed if it was stored compressed; the image is
+/// left to the caller, who must decompress it if `header.compression()`
+/// says so.
+#[cfg(feature = "verifier")]
+pub struct ContainerHeader {
+    pub version: u8,
+    pub header: FormatHeader,
+    pub public_key: Vec<u8>,
+    pub metadata: String,
+    pub signature: Vec<u8>,
+    /// Length of the image block as stored.
+    pub image_len: u64,
+    /// Offset of the first image byte.
+    pub header_len: u64,
+}
+
+/// Parses the header from a prefix of a container, returning `None` if
+/// `prefix` ends before the image length prefix. Lets callers holding only
+/// part of a container (a ranged read, a partial upload) inspect it.
+#[cfg(feature = "verifier")]
+pub fn parse_header(prefix: &[u8]) -> Result<Option<ContainerHeader>, AegisError> {
+    parse_header_limited(prefix, MAX_BLOCK_SIZE as usize)
+}
+
+/// Like `parse_header()`, with compressed metadata allowed to inflate to
+/// `inflate_limit` bytes at most; see `decompress_block()`.
+#[cfg(feature = "verifier")]
+pub fn parse_header_limited(prefix: &[u8], inflate_limit: usize) -> Result<Option<ContainerHeader>, AegisError> {
+    let Some(magic) = prefix.get(..MAGIC_NUMBER.len()) else {
+        return Ok(None);
+    };
+    let version = check_magic(magic.try_into().expect("slice is 6 bytes"))?;
+    // Blocks in front of the image, then the image length prefix.
+    let block_count = if version == VERSION_1 { 3 } else { 4 };
+    let mut pos = MAGIC_NUMBER.len();
+    let mut blocks: Vec<&[u8]> = Vec::with_capacity(block_count);
+    let image_len = loop {
+        let Some(len_bytes) = prefix.get(pos..pos + BLOCK_LENGTH_PREFIX) else {
+            return Ok(None);
+        };
+        let length = u64::from_be_bytes(len_bytes.try_into().expect("slice is 8 bytes"));
+        if length > MAX_BLOCK_SIZE {
+            return Err(AegisError::InvalidFormat);
+        }
+        pos += BLOCK_LENGTH_PREFIX;
+        if blocks.len() == block_count {
+            break length;
+        }
+        let Some(block) = prefix.get(pos..pos + length as usize) else {
+            return Ok(None);
+        };
+        blocks.push(block);
+        pos += length as usize;
+    };
+    let header = match version {
+        VERSION_1 => FormatHeader::default(),
+        _ => FormatHeader::parse(blocks.remove(0))?,
+    };
+    let metadata = match header.compression().metadata {
+        true => decompress_block(blocks[1], inflate_limit)?,
+        false => blocks[1].to_vec(),
+    };
+    Ok(Some(ContainerHeader {
+        version,
+        header,
+        public_key: blocks[0].to_vec(),
+        metadata: String::from_utf8(metadata).map_err(|_| AegisError::InvalidFormat)?,
+        signature: blocks[2].to_vec(),
+        image_len,
+        header_len: pos as u64,
+    }))
+}
+
+/// A container held in memory. The metadata and image are always as
+/// signed: `write()` compresses the blocks `header.compression()` names and
+/// `read()` decompresses them.
+pub struct AegisAncient {
+    /// Format version the container was read in, or will be written in.
+    pub version: u8,
+    /// Always empty for version 1 containers.
+    pub header: FormatHeader,
+    pub public_key: Vec<u8>,
+    pub metadata: String,
+    pub signature: Vec<u8>,
+    pub image_data: Vec<u8>,
+}
+
+impl AegisAncient {
+    /// The blocks as `write()` stores them, compressing those
+    /// `header.compression()` names. Compression is the costly part of
+    /// writing, so take the length and the bytes from the one `StoredBlocks`
+    /// rather than calling `encoded_len()` and `write()`, which each compress.
+    pub fn stored(&self) -> StoredBlocks<'_> {
+        StoredBlocks {
+            ancient: self,
+            metadata: stored_metadata(&self.header, &self.metadata),
+            image: match self.header.compression().image {
+                true => Cow::Owned(crate::zstd::compress(&self.image_data)),
+                false => Cow::Borrowed(&self.image_data),
+            },
+        }
+    }
+
+    /// The exact number of bytes `write()` will produce. Compressed blocks
+    /// are compressed to measure them; see `stored()`.
+    pub fn encoded_len(&self) -> u64 {
+        self.stored().encoded_len()
+    }
+
+    /// The co-signers' signatures; see `FormatHeader::cosignatures()`.
+    pub fn cosignatures(&self) -> Result<Vec<Cosignature>, AegisError> {
+        self.header.cosignatures()
+    }
+
+    /// The bytes `write()` emits in front of the image data.
+    pub fn prefix_bytes(&self) -> Result<Vec<u8>, AegisError> {
+        self.stored().prefix_bytes()
+    }
+
+    /// Serializes into a buffer preallocated to the exact output size.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, AegisError> {
+        self.stored().to_bytes()
+    }
+
+    /// Splits the container into the byte segments `write()` would emit, in
+    /// order. An uncompressed image is moved rather than copied, so large
+    /// payloads can be handed to a streaming writer without an extra buffer.
+    pub fn into_segments(self) -> Result<Vec<Vec<u8>>, AegisError> {
+        let stored = self.stored();
+        let prefix = stored.prefix_bytes()?;
+        let compressed = match stored.image {
+            Cow::Owned(image) => Some(image),
+            Cow::Borrowed(_) => None,
+        };
+        Ok(vec![prefix, compressed.unwrap_or(self.image_data)])
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), AegisError> {
+        self.stored().write(writer)
+    }
+
+    /// Reads a container of any supported version.
+    #[cfg(feature = "verifier")]
+    pub fn read<R: Read>(reader: &mut R) -> Result<Self, AegisError> {
+        Self::read_limited(reader, MAX_BLOCK_SIZE as usize)
+    }
+
+    /// Like `read()`, with each compressed block allowed to inflate to
+    /// `inflate_limit` bytes at most; see `decompress_block()`.
+    #[cfg(feature = "verifier")]
+    pub fn read_limited<R: Read>(reader: &mut R, inflate_limit: usize) -> Result<Self, AegisError> {
+        let mut magic_buf = [0u8; 6];
+        reader.read_exact(&mut magic_buf)?;
+        let version = check_magic(&magic_buf)?;
+        let read_block = |r: &mut R| -> Result<Vec<u8>, AegisError> {
+            let mut len_buf = [0u8; BLOCK_LENGTH_PREFIX];
+            r.read_exact(&mut len_buf)?;
+            let len = u64::from_be_bytes(len_buf);
+            if len > MAX_BLOCK_SIZE {
+                return Err(AegisError::InvalidFormat);
+            }
+            let mut data_buf = Vec::with_capacity(len as usize);
+            let mut limited_reader = r.take(len);
+            limited_reader.read_to_end(&mut data_buf)?;
+            if data_buf.len() as u64 != len {
+                return Err(AegisError::InvalidFormat);
+            }
+            Ok(data_buf)
+        };
+        let header = match version {
+            VERSION_1 => FormatHeader::default(),
+            _ => FormatHeader::parse(&read_block(reader)?)?,
+        };
+        let compression = header.compression();
+        let public_key = read_block(reader)?;
+        let mut metadata_bytes = read_block(reader)?;
+        if compression.metadata {
+            metadata_bytes = decompress_block(&metadata_bytes, inflate_limit)?;
+        }
+        let metadata = String::from_utf8(metadata_bytes).map_err(|_| AegisError::InvalidFormat)?;
+        let signature = read_block(reader)?;
+        let mut image_data = read_block(reader)?;
+        if compression.image {
+            image_data = decompress_block(&image_data, inflate_limit)?;
+        }
+        Ok(AegisAncient {
+            version,
+            header,
+            public_key,
+            metadata,
+            signature,
+            image_data,
+        })
+    }
+}
+
+/// A container's blocks as stored, each compressed once; see
+/// `AegisAncient::stored()`.
+pub struct StoredBlocks<'a> {
+    ancient: &'a AegisAncient,
+    metadata: Cow<'a, [u8]>,
+    image: Cow<'a, [u8]>,
+}
+
+impl StoredBlocks<'_> {
+    /// The exact number of bytes `write()` will produce.
+    pub fn encoded_len(&self) -> u64 {
+        let header_block = match self.ancient.version {
+            VERSION_1 => 0,
+            _ => (BLOCK_LENGTH_PREFIX + self.ancient.header.to_bytes().len()) as u64,
+        };
+        let blocks = [
+            self.ancient.public_key.len(),
+            self.metadata.len(),
+            self.ancient.signature.len(),
+            self.image.len(),
+        ];
+        MAGIC_NUMBER.len() as u64
+            + header_block
+            + blocks
+                .iter()
+                .map(|len| (BLOCK_LENGTH_PREFIX + *len) as u64)
+                .sum::<u64>()
+    }
+
+    /// The bytes `write()` emits in front of the image data.
+    pub fn prefix_bytes(&self) -> Result<Vec<u8>, AegisError> {
+        let ancient = self.ancient;
+        encode_prefix(
+            ancient.version,
+            &ancient.header,
+            &ancient.public_key,
+            &self.metadata,
+            &ancient.signature,
+            self.image.len() as u64,
+        )
+    }
+
+    /// Serializes into a buffer preallocated to the exact output size.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, AegisError> {
+        let mut buf = self.prefix_bytes()?;
+        buf.reserve_exact(self.image.len());
+        buf.extend_from_slice(&self.image);
+        Ok(buf)
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), AegisError> {
+        writer.write_all(&self.prefix_bytes()?)?;
+        writer.write_all(&self.image)?;
+        Ok(())
+    }
+}
+
+/// Magic number of a detached signature (`.aegis.sig`) file.
+pub const DETACHED_MAGIC: &[u8; 6] = b"AEGSIG";
+pub const DETACHED_VERSION_1: u8 = b'1';
+pub const DETACHED_EXTENSION: &str = "aegis.sig";
+
+/// A signature kept apart from the image it covers. The signature is the
+/// same one an embedded container would carry, over `signing_digest()` of
+/// the metadata and the original image, so a sidecar plus its original can
+/// be turned into a full container without re-signing.
+///
+/// Layout: `AEGSIG`, a version byte, then the public key, metadata and
+/// signature as length-prefixed blocks, followed by the image's 32-byte
+/// SHA-256 and its length as an 8-byte big-endian integer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DetachedSignature {
+    pub public_key: Vec<u8>,
+    pub metadata: String,
+    pub signature: Vec<u8>,
+    pub image_sha256: [u8; 32],
+    pub image_len: u64,
+}
+
+impl DetachedSignature {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = DETACHED_MAGIC.to_vec();
+        out.push(DETACHED_VERSION_1);
+        for block in [&self.public_key[..], self.metadata.as_bytes(), &self.signature] {
+            out.extend_from_slice(&(block.len() as u64).to_be_bytes());
+            out.extend_from_slice(block);
+        }
+        out.extend_from_slice(&self.image_sha256);
+        out.extend_from_slice(&self.image_len.to_be_bytes());
+        out
+    }
+
+    /// The full container for this signature and its original image.
+    pub fn attach(self, image_data: Vec<u8>) -> AegisAncient {
+        AegisAncient {
+            version: CURRENT_VERSION,
+            header: FormatHeader::default(),
+            public_key: self.public_key,
+            metadata: self.metadata,
+            signature: self.signature,
+            image_data,
+        }
+    }
+
+    #[cfg(feature = "verifier")]
+    pub fn parse(bytes: &[u8]) -> Result<Self, AegisError> {
+        if bytes.len() < DETACHED_MAGIC.len() + 1 || bytes[..DETACHED_MAGIC.len()] != *DETACHED_MAGIC {
+            return Err(AegisError::InvalidFormat);
+        }
+        match bytes[DETACHED_MAGIC.len()] {
+            DETACHED_VERSION_1 => {}
+            other => return Err(AegisError::UnsupportedVersion(other)),
+        }
+        let mut rest = &bytes[DETACHED_MAGIC.len() + 1..];
+        let mut take = |n: usize| -> Result<&[u8], AegisError> {
+            let (head, tail) = rest.split_at_checked(n).ok_or(AegisError::InvalidFormat)?;
+            rest = tail;
+            Ok(head)
+        };
+        let mut blocks = Vec::with_capacity(3);
+        for _ in 0..3 {
+            let len = u64::from_be_bytes(take(BLOCK_LENGTH_PREFIX)?.try_into().expect("slice is 8 bytes"));
+            if len > MAX_BLOCK_SIZE {
+                return Err(AegisError::InvalidFormat);
+            }
+            blocks.push(take(len as usize)?.to_vec());
+        }
+        let image_sha256: [u8; 32] = take(32)?.try_into().expect("slice is 32 bytes");
+        let image_len = u64::from_be_bytes(take(8)?.try_into().expect("slice is 8 bytes"));
+        if !rest.is_empty() {
+            return Err(AegisError::InvalidFormat);
+        }
+        let signature = blocks.pop().expect("three blocks were read");
+        let metadata = String::from_utf8(blocks.pop().expect("three blocks were read"))
+            .map_err(|_| AegisError::InvalidFormat)?;
+        let public_key = blocks.pop().expect("three blocks were read");
+        Ok(DetachedSignature {
+            public_key,
+            metadata,
+            signature,
+            image_sha256,
+            image_len,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "sealer", feature = "verifier"))]
+mod tests {
+    use super::*;
+    use crate::crypto;
+    use crate::test_util::{sample_container, test_signing_key, SAMPLE_METADATA, SAMPLE_PAYLOAD};
+
+    fn round_trip(ancient: &AegisAncient) -> AegisAncient {
+        let bytes = ancient.to_bytes().unwrap();
+        assert_eq!(bytes.len() as u64, ancient.encoded_len());
+        AegisAncient::read(&mut &bytes[..]).unwrap()
+    }
+
+    /// A sample container's bytes with its header block replaced by
+    /// `header` (flags and fields as stored).
+    fn with_header_block(header: &[u8]) -> Vec<u8> {
+        let ancient = sample_container();
+        let mut bytes = b"AEGIS2".to_vec();
+        let metadata = ancient.metadata.as_bytes();
+        for block in [header, &ancient.public_key, metadata, &ancient.signature, &ancient.image_data] {
+            bytes.extend_from_slice(&(block.len() as u64).to_be_bytes());
+            bytes.extend_from_slice(block);
+        }
+        bytes
+    }
+
+    fn field(tag: u16, value: &[u8]) -> Vec<u8> {
+        [&tag.to_be_bytes()[..], &(value.len() as u32).to_be_bytes(), value].concat()
+    }
+
+    fn signature_valid(ancient: &AegisAncient) -> bool {
+        crypto::verify(ancient).is_ok_and(|report| report.signature_valid)
+    }
+
+    #[test]
+    fn round_trips_version_1_and_2() {
+        let mut ancient = sample_container();
+        ancient.version = VERSION_1;
+        let bytes = ancient.to_bytes().unwrap();
+        assert_eq!(&bytes[..6], MAGIC_NUMBER);
+        let read = round_trip(&ancient);
+        assert_eq!(read.version, VERSION_1);
+        assert_eq!(read.to_bytes().unwrap(), bytes);
+        assert!(signature_valid(&read));
+
+        let mut ancient = sample_container();
+        ancient.header.set_key_id("2024-06");
+        ancient.header.set_trust_hint(&crate::dns_trust::TrustHint::parse("example.com:photo").unwrap());
+        assert_eq!(ancient.to_bytes().unwrap()[..6], *b"AEGIS2");
+        let read = round_trip(&ancient);
+        assert_eq!(read.header, ancient.header);
+        assert_eq!((read.metadata.as_str(), &read.image_data[..]), (SAMPLE_METADATA, SAMPLE_PAYLOAD));
+        assert_eq!(read.header.key_id(), Some("2024-06"));
+        assert!(signature_valid(&read));
+
+        // Version 1 has no header block to put fields in.
+        ancient.version = VERSION_1;
+        assert!(matches!(ancient.to_bytes(), Err(AegisError::HeaderNotSupported(VERSION_1))));
+    }
+
+    #[test]
+    fn keeps_fields_with_unknown_tags() {
+        let mut ancient = sample_container();
+        ancient.header.set_field(0x7fff, b"from a newer writer".to_vec());
+        let read = round_trip(&ancient);
+        assert_eq!(read.header.field(0x7fff), Some(&b"from a newer writer"[..]));
+        assert_eq!(round_trip(&read).to_bytes().unwrap(), ancient.to_bytes().unwrap());
+        assert!(signature_valid(&read));
+    }
+
+    #[test]
+    fn rejects_unknown_flags() {
+        let bytes = with_header_block(&0x8000_0000u32.to_be_bytes());
+        assert!(matches!(AegisAncient::read(&mut &bytes[..]), Err(AegisError::UnsupportedFlags(0x8000_0000))));
+    }
+
+    #[test]
+    fn rejects_duplicate_tags() {
+        let header = [&0u32.to_be_bytes()[..], &field(FIELD_KEY_ID, b"a"), &field(FIELD_KEY_ID, b"b")].concat();
+        assert!(matches!(FormatHeader::parse(&header), Err(AegisError::InvalidFormat)));
+        let bytes = with_header_block(&header);
+        assert!(matches!(AegisAncient::read(&mut &bytes[..]), Err(AegisError::InvalidFormat)));
+        assert!(parse_header(&bytes).is_err());
+
+        let header = [&0u32.to_be_bytes()[..], &field(FIELD_KEY_ID, b"a"), &field(FIELD_TRUST_HINT, b"b")].concat();
+        assert_eq!(FormatHeader::parse(&header).unwrap().fields.len(), 2);
+    }
+
+    #[test]
+    fn rejects_fields_overrunning_the_header() {
+        let whole = [&0u32.to_be_bytes()[..], &field(FIELD_KEY_ID, b"key-1")].concat();
+        assert!(FormatHeader::parse(&whole).is_ok());
+        // A field whose length runs past the block, a field cut off in its
+        // tag and length, and a block too short for the flags.
+        let mut overrun = whole.clone();
+        overrun[9] += 1;
+        for header in [&overrun[..], &whole[..whole.len() - 1], &whole[..7], &whole[..3]] {
+            assert!(matches!(FormatHeader::parse(header), Err(AegisError::InvalidFormat)));
+            let bytes = with_header_block(header);
+            assert!(AegisAncient::read(&mut &bytes[..]).is_err());
+        }
+    }
+
+    #[test]
+    fn signs_extensions_only_with_their_flag() {
+        let mut header = FormatHeader::default();
+        header.set_extensions(&[Extension::json("acme.claim", r#"{"id":7}"#).unwrap()]).unwrap();
+        assert_ne!(header.flags & FLAG_SIGNED_EXTENSIONS, 0);
+        let sealed = crypto::seal_with_header(
+            header,
+            SAMPLE_METADATA.into(),
+            SAMPLE_PAYLOAD.to_vec(),
+            &test_signing_key(0),
+        )
+        .unwrap();
+        let read = round_trip(&sealed);
+        assert!(signature_valid(&read));
+        assert_eq!(read.header.extensions().unwrap()[0].name, "acme.claim");
+
+        // Another value is not what was signed.
+        let mut altered = round_trip(&read);
+        altered.header.set_extensions(&[Extension::json("acme.claim", r#"{"id":8}"#).unwrap()]).unwrap();
+        assert!(!signature_valid(&altered));
+
+        // Nor is dropping the extensions, flag and all.
+        let mut stripped = round_trip(&read);
+        stripped.header.fields.retain(|f| f.tag != FIELD_EXTENSIONS);
+        stripped.header.flags &= !FLAG_SIGNED_EXTENSIONS;
+        assert!(!signature_valid(&stripped));
+
+        // Clearing only the flag, or setting it without the field, is
+        // refused rather than verified without the extensions.
+        let mut unflagged = round_trip(&read);
+        unflagged.header.flags &= !FLAG_SIGNED_EXTENSIONS;
+        assert!(matches!(crypto::verify(&unflagged), Err(AegisError::InvalidExtension(_))));
+        let mut flagged = sample_container();
+        flagged.header.flags |= FLAG_SIGNED_EXTENSIONS;
+        assert!(matches!(crypto::verify(&flagged), Err(AegisError::InvalidExtension(_))));
+    }
+
+    #[test]
+    fn unsigned_fields_cannot_change_the_verdict() {
+        let change = |ancient: &mut AegisAncient| {
+            ancient.header.set_key_id("someone-else");
+            ancient.header.set_field(0x7fff, b"anything".to_vec());
+            ancient.header.set_trust_hint(&crate::dns_trust::TrustHint::parse("evil.example").unwrap());
+            ancient.header.set_timestamp_token(b"not a token".to_vec());
+            ancient.header.set_compression(Compression { metadata: true, image: true });
+        };
+        let genuine = sample_container();
+        let mut changed = round_trip(&genuine);
+        change(&mut changed);
+        let changed = round_trip(&changed);
+        let (before, after) = (crypto::verify(&genuine).unwrap(), crypto::verify(&changed).unwrap());
+        assert!(before.signature_valid && after.signature_valid);
+        assert_eq!(before.digest, after.digest);
+        assert_eq!(before.metadata, after.metadata);
+        assert_eq!(changed.image_data, genuine.image_data);
+
+        let mut tampered = round_trip(&genuine);
+        tampered.image_data[0] ^= 1;
+        assert!(!signature_valid(&tampered));
+        change(&mut tampered);
+        assert!(!signature_valid(&round_trip(&tampered)));
+    }
+
+    #[test]
+    fn caps_what_a_compressed_block_inflates_to() {
+        // Two megabytes of zeros store in a few hundred bytes, well within
+        // what the compression ratio alone allows.
+        let mut ancient = crate::test_util::sample_container_with("{}", &vec![0; 2 << 20]);
+        ancient.header.set_compression(Compression { metadata: false, image: true });
+        let bytes = ancient.to_bytes().unwrap();
+        assert!(bytes.len() < 4096);
+        assert_eq!(AegisAncient::read(&mut &bytes[..]).unwrap().image_data.len(), 2 << 20);
+        assert_eq!(AegisAncient::read_limited(&mut &bytes[..], 4 << 20).unwrap().image_data, ancient.image_data);
+        assert!(matches!(
+            AegisAncient::read_limited(&mut &bytes[..], 1 << 20),
+            Err(AegisError::InvalidCompression(_))
+        ));
+        let stored = crate::zstd::compress(&ancient.image_data);
+        assert!(decompress_block(&stored, 1 << 20).is_err());
+        assert!(decompress_block(&stored, usize::MAX).is_ok());
+    }
+}
@@ -0,0 +1,247 @@
+// aegis-core/src/http_sig.rs
+
+// HTTP Message Signatures (RFC 9421) on service responses. JSON replies carry
+// a `Content-Digest` (RFC 9530) and a signature labelled `sig1` that covers
+// the status code, the content type and that digest, made with the service
+// key using `ecdsa-p256-sha256`.
+//
+// To check a response, a client:
+//   1. recomputes `sha-256=:<base64 SHA-256 of the body>:` and compares it
+//      with the `Content-Digest` header;
+//   2. takes the `Signature-Input` member `sig1`, confirms it covers exactly
+//      `("@status" "content-type" "content-digest")` and that its `keyid` is
+//      the hex fingerprint of a key it trusts;
+//   3. rebuilds the signature base with [`signature_base`] and verifies the
+//      `sig1` value of the `Signature` header (raw r||s, base64) against it.
+// [`verify_response`] performs all three steps.
+//
+// The signature base is fixed by RFC 9421, so it is signed as is rather than
+// under a `crypto::SignatureContext`. It always starts with `"@status"`,
+// which no context prefix does.
+
+use crate::keys::Fingerprint;
+use base64ct::{Base64, Encoding};
+use p256::ecdsa::VerifyingKey;
+use sha2::{Digest, Sha256};
+#[cfg(feature = "verifier")]
+use {
+    crate::error::AegisError,
+    p256::ecdsa::{signature::Verifier, Signature},
+};
+
+pub const SIGNATURE_LABEL: &str = "sig1";
+pub const ALGORITHM: &str = "ecdsa-p256-sha256";
+pub const COVERED_COMPONENTS: [&str; 3] = ["@status", "content-type", "content-digest"];
+
+/// The `Content-Digest` value for a body.
+pub fn content_digest(body: &[u8]) -> String {
+    format!("sha-256=:{}:", Base64::encode_string(&Sha256::digest(body)))
+}
+
+/// The serialized `@signature-params` inner list for a signature created at
+/// `created` (Unix seconds) by `key`.
+pub fn signature_params(created: u64, key: &VerifyingKey) -> String {
+    let components = COVERED_COMPONENTS
+        .iter()
+        .map(|c| format!("\"{}\"", c))
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!(
+        "({});created={};keyid=\"{}\";alg=\"{}\"",
+        components,
+        created,
+        Fingerprint::of(&key.to_sec1_bytes()).to_hex(),
+        ALGORITHM
+    )
+}
+
+/// The signature base the service signs, per RFC 9421 section 2.5.
+pub fn signature_base(status: u16, content_type: &str, content_digest: &str, params: &str) -> String {
+    let status = status.to_string();
+    let values = [status.as_str(), content_type.trim(), content_digest.trim()];
+    serialize_base(&COVERED_COMPONENTS.iter().copied().zip(values).collect::<Vec<_>>(), params)
+}
+
+// One `"name": value` line per covered component, in order, then the
+// `@signature-params` line.
+fn serialize_base(components: &[(&str, &str)], params: &str) -> String {
+    let mut base = String::new();
+    for (name, value) in components {
+        base.push_str(&format!("\"{}\": {}\n", name, value));
+    }
+    base.push_str(&format!("\"@signature-params\": {}", params));
+    base
+}
+
+/// `Signature-Input` and `Signature` header values for a raw r||s signature.
+pub fn signature_headers(params: &str, signature: &[u8]) -> (String, String) {
+    (
+        format!("{}={}", SIGNATURE_LABEL, params),
+        format!("{}=:{}:", SIGNATURE_LABEL, Base64::encode_string(signature)),
+    )
+}
+
+/// Verifies a signed response against `key`, returning the `created` time.
+#[cfg(feature = "verifier")]
+pub fn verify_response(
+    status: u16,
+    content_type: &str,
+    content_digest: &str,
+    signature_input: &str,
+    signature: &str,
+    body: &[u8],
+    key: &VerifyingKey,
+) -> Result<u64, AegisError> {
+    let fail = |msg: &str| AegisError::Crypto(msg.to_string());
+
+    if content_digest.trim() != self::content_digest(body) {
+        return Err(fail("content digest does not match body"));
+    }
+    let params = member(signature_input, SIGNATURE_LABEL).ok_or_else(|| fail("missing sig1 in Signature-Input"))?;
+    let created = params
+        .split(';')
+        .find_map(|p| p.strip_prefix("created="))
+        .and_then(|v| v.parse::<u64>().ok())
+        .ok_or_else(|| fail("signature parameters lack a created time"))?;
+    if params != signature_params(created, key) {
+        return Err(fail("signature parameters do not match the expected components, key or algorithm"));
+    }
+    let encoded = member(signature, SIGNATURE_LABEL)
+        .and_then(|v| v.strip_prefix(':')?.strip_suffix(':'))
+        .ok_or_else(|| fail("missing sig1 in Signature"))?;
+    let raw = Base64::decode_vec(encoded).map_err(|_| fail("signature is not valid base64"))?;
+    let sig = Signature::from_slice(&raw).map_err(|e| AegisError::Crypto(e.to_string()))?;
+    let base = signature_base(status, content_type, content_digest, params);
+    key.verify(base.as_bytes(), &sig)
+        .map_err(|_| fail("response signature does not verify"))?;
+    Ok(created)
+}
+
+/// Extracts a dictionary member's value from a structured header. Members
+/// are split on commas outside parentheses so inner lists stay intact.
+#[cfg(feature = "verifier")]
+fn member<'a>(header: &'a str, label: &str) -> Option<&'a str> {
+    let mut depth = 0i32;
+    let mut start = 0;
+    let mut members = Vec::new();
+    for (i, c) in header.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                members.push(&header[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    members.push(&header[start..]);
+    members
+        .into_iter()
+        .find_map(|m| m.trim().strip_prefix(label)?.strip_prefix('='))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The signature base and headers of RFC 9421 appendix B.2.4, signing
+    // the test response with ecdsa-p256-sha256.
+    const RFC_BASE: &str = "\"@status\": 200
+\"content-type\": application/json
+\"content-digest\": sha-512=:mEWXIS7MaLRuGgxOBdODa3xqM1XdEvxoYhvlCFJ41QJgJc4GTsPp29l5oGX69wWdXymyU0rjJuahq4l5aGgfLQ==:
+\"content-length\": 23
+\"@signature-params\": (\"@status\" \"content-type\" \"content-digest\" \"content-length\");created=1618884473\
+;keyid=\"test-key-ecc-p256\"";
+    const RFC_SIGNATURE_INPUT: &str = "sig-b24=(\"@status\" \"content-type\" \"content-digest\" \
+                                       \"content-length\");created=1618884473;keyid=\"test-key-ecc-p256\"";
+
+    #[test]
+    fn computes_the_rfc_9530_content_digest() {
+        // RFC 9530 appendix B.1.
+        assert_eq!(content_digest(b"{\"hello\": \"world\"}"), "sha-256=:X48E9qOokqqrvdts8nOJRJN3OWDUoyWxBf7kbu9DBPE=:");
+    }
+
+    #[test]
+    fn serializes_the_rfc_9421_response_example() {
+        let components = [
+            ("@status", "200"),
+            ("content-type", "application/json"),
+            (
+                "content-digest",
+                "sha-512=:mEWXIS7MaLRuGgxOBdODa3xqM1XdEvxoYhvlCFJ41QJgJc4GTsPp29l5oGX69wWdXymyU0rjJuahq4l5aGgfLQ==:",
+            ),
+            ("content-length", "23"),
+        ];
+        let params = RFC_SIGNATURE_INPUT.strip_prefix("sig-b24=").unwrap();
+        assert_eq!(serialize_base(&components, params), RFC_BASE);
+        // The service's own base is the same serialization of its three
+        // components.
+        assert_eq!(
+            signature_base(200, " application/json ", components[2].1, "()"),
+            serialize_base(&components[..3], "()")
+        );
+    }
+
+    #[cfg(feature = "verifier")]
+    #[test]
+    fn verifies_the_rfc_9421_response_signature() {
+        // RFC 9421 appendix B.1.3, test-key-ecc-p256, as an uncompressed point.
+        const RFC_KEY: &str = "04a885586552c2acf6471878cfd7b0935b4ffe0fd2dfc341248ea17bc41e058af0\
+                               31ce2737d2d30ce0617e851e83c61ef5679d151867657649035d90a74cd9e85d";
+        const RFC_SIGNATURE: &str =
+            "sig-b24=:wNmSUAhwb5LxtOtOpNa6W5xj067m5hFrj0XQ4fvpaCLx0NKocgPquLgyahnzDnDAUy5eCdlYUEkLIj+32oiasw==:";
+        let key = VerifyingKey::from_sec1_bytes(&hex::decode(RFC_KEY).unwrap()).unwrap();
+        assert_eq!(member(RFC_SIGNATURE_INPUT, "sig-b24"), RFC_SIGNATURE_INPUT.strip_prefix("sig-b24="));
+        let encoded = member(RFC_SIGNATURE, "sig-b24").unwrap().trim_matches(':');
+        let signature = Signature::from_slice(&Base64::decode_vec(encoded).unwrap()).unwrap();
+        assert!(key.verify(RFC_BASE.as_bytes(), &signature).is_ok());
+        assert!(key.verify(RFC_BASE.replace("200", "201").as_bytes(), &signature).is_err());
+    }
+
+    #[cfg(all(feature = "sealer", feature = "verifier"))]
+    #[test]
+    fn verifies_signed_responses_and_rejects_tampering() {
+        use p256::ecdsa::signature::Signer;
+
+        let signing_key = crate::test_util::test_signing_key(1);
+        let key = *signing_key.verifying_key();
+        let body = br#"{"valid":true}"#;
+        let digest = content_digest(body);
+        let sign = |status: u16, content_type: &str, created: u64, key: &VerifyingKey| {
+            let params = signature_params(created, key);
+            let base = signature_base(status, content_type, &digest, &params);
+            let signature: Signature = signing_key.sign(base.as_bytes());
+            signature_headers(&params, &signature.to_bytes())
+        };
+        let (input, signature) = sign(200, "application/json", 1_700_000_000, &key);
+        let verify = |status: u16, content_type: &str, digest: &str, input: &str, signature: &str, body: &[u8]| {
+            verify_response(status, content_type, digest, input, signature, body, &key)
+        };
+        assert_eq!(verify(200, "application/json", &digest, &input, &signature, body).unwrap(), 1_700_000_000);
+        // Another dictionary member alongside ours is ignored.
+        let with_other = format!("other=(\"@method\");created=1, {}", input);
+        assert!(verify(200, "application/json", &digest, &with_other, &signature, body).is_ok());
+
+        // Each covered component, the body, and the parameters.
+        assert!(verify(500, "application/json", &digest, &input, &signature, body).is_err());
+        assert!(verify(200, "text/plain", &digest, &input, &signature, body).is_err());
+        assert!(verify(200, "application/json", &digest, &input, &signature, br#"{"valid":false}"#).is_err());
+        let other_digest = content_digest(br#"{"valid":false}"#);
+        assert!(verify(200, "application/json", &other_digest, &input, &signature, body).is_err());
+        let later = input.replace("created=1700000000", "created=1700000001");
+        assert!(verify(200, "application/json", &digest, &later, &signature, body).is_err());
+        // A signature naming another key.
+        let other_key = crate::test_util::test_signing_key(2);
+        let (renamed_input, renamed) = sign(200, "application/json", 1_700_000_000, other_key.verifying_key());
+        assert!(verify(200, "application/json", &digest, &renamed_input, &renamed, body).is_err());
+        let fewer = input.replace(" \"content-digest\"", "");
+        assert!(verify(200, "application/json", &digest, &fewer, &signature, body).is_err());
+        let mut raw = Base64::decode_vec(signature.trim_start_matches("sig1=").trim_matches(':')).unwrap();
+        raw[10] ^= 1;
+        let flipped = format!("sig1=:{}:", Base64::encode_string(&raw));
+        assert!(verify(200, "application/json", &digest, &input, &flipped, body).is_err());
+        assert!(verify(200, "application/json", &digest, &input, "sig2=:AAAA:", body).is_err());
+        assert!(verify(200, "application/json", &digest, "", &signature, body).is_err());
+    }
+}
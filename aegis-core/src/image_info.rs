@@ -0,0 +1,217 @@
+// aegis-core/src/image_info.rs
+
+// Recognizes image formats from their leading bytes and reads the pixel
+// dimensions from their headers, without decoding any pixels. Sealers use it
+// to refuse uploads that are not images at all.
+//
+// Formats are told apart by their signatures: JPEG `FF D8 FF`, the PNG
+// signature, TIFF `II*\0`/`MM\0*`, a RIFF `WEBP` file, and an ISO BMFF
+// `ftyp` box with a HEIF brand. Dimensions come from the JPEG start-of-frame
+// segment, the PNG `IHDR` chunk, TIFF IFD0, the WebP `VP8 `/`VP8L`/`VP8X`
+// chunk and the HEIF `ispe` property. They are `None` when the header is
+// beyond the bytes given or malformed; the format is still reported.
+
+use serde::Serialize;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+/// `ftyp` major brands of HEIF images.
+const HEIF_BRANDS: [&[u8; 4]; 8] = [b"heic", b"heix", b"heim", b"heis", b"hevc", b"hevx", b"mif1", b"msf1"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Heic,
+    Tiff,
+    Webp,
+}
+
+impl ImageFormat {
+    pub const ALL: [ImageFormat; 5] =
+        [ImageFormat::Jpeg, ImageFormat::Png, ImageFormat::Heic, ImageFormat::Tiff, ImageFormat::Webp];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpeg",
+            ImageFormat::Png => "png",
+            ImageFormat::Heic => "heic",
+            ImageFormat::Tiff => "tiff",
+            ImageFormat::Webp => "webp",
+        }
+    }
+
+    /// The format called `name`, ignoring case; `jpg` and `tif` also work.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "jpeg" | "jpg" => Some(ImageFormat::Jpeg),
+            "png" => Some(ImageFormat::Png),
+            "heic" | "heif" => Some(ImageFormat::Heic),
+            "tiff" | "tif" => Some(ImageFormat::Tiff),
+            "webp" => Some(ImageFormat::Webp),
+            _ => None,
+        }
+    }
+
+    pub fn media_type(self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Png => "image/png",
+            ImageFormat::Heic => "image/heic",
+            ImageFormat::Tiff => "image/tiff",
+            ImageFormat::Webp => "image/webp",
+        }
+    }
+
+    /// Recognizes an image from its first bytes.
+    pub fn sniff(data: &[u8]) -> Option<Self> {
+        if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(ImageFormat::Jpeg)
+        } else if data.starts_with(PNG_SIGNATURE) {
+            Some(ImageFormat::Png)
+        } else if data.starts_with(b"II*\0") || data.starts_with(b"MM\0*") {
+            Some(ImageFormat::Tiff)
+        } else if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+            Some(ImageFormat::Webp)
+        } else if data.len() >= 12 && &data[4..8] == b"ftyp" && HEIF_BRANDS.iter().any(|b| &data[8..12] == *b) {
+            Some(ImageFormat::Heic)
+        } else {
+            None
+        }
+    }
+}
+
+/// What the header of an image says about it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ImageInfo {
+    pub format: ImageFormat,
+    pub media_type: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+}
+
+impl ImageInfo {
+    /// Reads the format and dimensions from the start of an image. `None`
+    /// if `head` is not the start of a recognized format.
+    pub fn read(head: &[u8]) -> Option<Self> {
+        let format = ImageFormat::sniff(head)?;
+        let dimensions = match format {
+            ImageFormat::Jpeg => jpeg_dimensions(head),
+            ImageFormat::Png => png_dimensions(head),
+            ImageFormat::Heic => heif_dimensions(head),
+            ImageFormat::Tiff => tiff_dimensions(head),
+            ImageFormat::Webp => webp_dimensions(head),
+        };
+        Some(ImageInfo {
+            format,
+            media_type: format.media_type(),
+            width: dimensions.map(|d| d.0),
+            height: dimensions.map(|d| d.1),
+        })
+    }
+}
+
+fn be16(data: &[u8], at: usize) -> Option<u32> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?) as u32)
+}
+
+fn be32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn le16(data: &[u8], at: usize) -> Option<u32> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?) as u32)
+}
+
+fn le24(data: &[u8], at: usize) -> Option<u32> {
+    let b = data.get(at..at + 3)?;
+    Some(b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16)
+}
+
+fn le32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+// Walks the segments to the first start-of-frame marker.
+fn jpeg_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let mut pos = 2;
+    loop {
+        if *data.get(pos)? != 0xFF {
+            return None;
+        }
+        let marker = *data.get(pos + 1)?;
+        match marker {
+            // Fill bytes before a marker.
+            0xFF => pos += 1,
+            // Markers without a length.
+            0x01 | 0xD0..=0xD7 => pos += 2,
+            // SOF0-SOF15, except DHT, JPG and DAC.
+            0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                let height = be16(data, pos + 5)?;
+                let width = be16(data, pos + 7)?;
+                return Some((width, height));
+            }
+            // Start of scan with no frame header before it.
+            0xDA | 0xD9 => return None,
+            _ => pos += 2 + be16(data, pos + 2)? as usize,
+        }
+    }
+}
+
+fn png_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    (data.get(12..16)? == b"IHDR").then_some(())?;
+    Some((be32(data, 16)?, be32(data, 20)?))
+}
+
+fn tiff_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let little = data.starts_with(b"II");
+    let u16_at = |at| if little { le16(data, at) } else { be16(data, at) };
+    let u32_at = |at| if little { le32(data, at) } else { be32(data, at) };
+    let ifd = u32_at(4)? as usize;
+    let (mut width, mut height) = (None, None);
+    for i in 0..u16_at(ifd)? as usize {
+        let entry = ifd + 2 + i * 12;
+        let value = match u16_at(entry + 2)? {
+            // SHORT
+            3 => u16_at(entry + 8)?,
+            // LONG
+            4 => u32_at(entry + 8)?,
+            _ => continue,
+        };
+        match u16_at(entry)? {
+            256 => width = Some(value),
+            257 => height = Some(value),
+            _ => {}
+        }
+    }
+    Some((width?, height?))
+}
+
+fn webp_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let chunk = data.get(12..16)?;
+    let body = 20;
+    match chunk {
+        b"VP8 " => {
+            // Frame tag, then the start code 9D 01 2A.
+            (data.get(body + 3..body + 6)? == [0x9D, 0x01, 0x2A]).then_some(())?;
+            Some((le16(data, body + 6)? & 0x3FFF, le16(data, body + 8)? & 0x3FFF))
+        }
+        b"VP8L" => {
+            (*data.get(body)? == 0x2F).then_some(())?;
+            let bits = le32(data, body + 1)?;
+            Some(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1))
+        }
+        b"VP8X" => Some((le24(data, body + 4)? + 1, le24(data, body + 7)? + 1)),
+        _ => None,
+    }
+}
+
+// The first `ispe` (image spatial extents) property, which for the usual
+// single-image file is the primary image's. Its box is a 4-byte size, the
+// type, 4 bytes of version and flags, then width and height.
+fn heif_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let at = data.windows(4).position(|w| w == b"ispe")?;
+    Some((be32(data, at + 8)?, be32(data, at + 12)?))
+}
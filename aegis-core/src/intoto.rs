@@ -0,0 +1,181 @@
+// aegis-core/src/intoto.rs
+
+// In-toto attestations of sealed payloads, for build pipelines that feed
+// the provenance of release artifacts into in-toto or SLSA tooling. A seal
+// is exported as an in-toto v1 statement whose subject is the payload and
+// whose predicate is its metadata and detached signature:
+//
+//     {"_type": "https://in-toto.io/Statement/v1",
+//      "subject": [{"name": "app.tar.gz", "digest": {"sha256": "9f86d0..."}}],
+//      "predicateType": "urn:aegis:predicate:seal:v1",
+//      "predicate": {"metadata": {"title": ...}, "size": 48213,
+//                    "seal": {"public_key": "04ab...", "key_fingerprint": "2bad0f...",
+//                             "signature": "3045...", "signed_fields": ["metadata", "image_data"]}}}
+//
+// The statement travels in a DSSE envelope signed by the sealing key:
+//
+//     {"payloadType": "application/vnd.in-toto+json", "payload": "<base64>",
+//      "signatures": [{"keyid": "2bad0f...", "sig": "<base64 DER ECDSA>"}]}
+//
+// DSSE signs the pre-authentication encoding `pae()` of the payload type and
+// payload, in `SignatureContext::Dsse`. Its `DSSEv1` preamble is the context,
+// so there is no prefix; verifiers accept nothing but a PAE in that context.
+// The seal in the predicate is the payload's detached signature, so the
+// statement also converts to a `.aegis.sig` sidecar without re-signing.
+// Envelopes are written one per line, as `.intoto.jsonl` files are.
+
+use crate::{
+    crypto::{self, SignatureContext},
+    error::AegisError,
+    format::DetachedSignature,
+    keys::Fingerprint,
+};
+use base64ct::{Base64, Encoding};
+use p256::ecdsa::Signature;
+#[cfg(feature = "sealer")]
+use p256::ecdsa::signature::Signer;
+#[cfg(feature = "verifier")]
+use p256::ecdsa::VerifyingKey;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+pub const STATEMENT_TYPE: &str = "https://in-toto.io/Statement/v1";
+pub const PREDICATE_TYPE: &str = "urn:aegis:predicate:seal:v1";
+pub const PAYLOAD_TYPE: &str = "application/vnd.in-toto+json";
+pub const ATTESTATION_EXTENSION: &str = "intoto.jsonl";
+/// Every pre-authentication encoding starts with this.
+pub(crate) const PAE_PREAMBLE: &[u8] = b"DSSEv1 ";
+
+/// A DSSE envelope.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Envelope {
+    pub payload_type: String,
+    /// Base64 of the statement.
+    pub payload: String,
+    pub signatures: Vec<EnvelopeSignature>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EnvelopeSignature {
+    /// Hex SHA-256 fingerprint of the signing key.
+    #[serde(default)]
+    pub keyid: String,
+    /// Base64 of the DER ECDSA signature.
+    pub sig: String,
+}
+
+/// A statement about a sealed payload, ready to be signed.
+pub struct UnsignedEnvelope {
+    statement: Vec<u8>,
+    fingerprint: Fingerprint,
+}
+
+impl UnsignedEnvelope {
+    /// The statement that the payload of `detached`, named `name`, was
+    /// sealed with its metadata.
+    pub fn new(detached: &DetachedSignature, name: &str) -> Self {
+        let fingerprint = Fingerprint::of(&detached.public_key);
+        let metadata = serde_json::from_str::<Value>(&detached.metadata).unwrap_or_else(|_| json!(detached.metadata));
+        let statement = json!({
+            "_type": STATEMENT_TYPE,
+            "subject": [{"name": name, "digest": {"sha256": hex::encode(detached.image_sha256)}}],
+            "predicateType": PREDICATE_TYPE,
+            "predicate": {
+                "metadata": metadata,
+                "size": detached.image_len,
+                "seal": {
+                    "public_key": hex::encode(&detached.public_key),
+                    "key_fingerprint": fingerprint.to_hex(),
+                    "signature": hex::encode(&detached.signature),
+                    "signed_fields": crypto::SIGNED_FIELDS,
+                },
+            },
+        });
+        UnsignedEnvelope { statement: serde_json::to_vec(&statement).expect("statements serialize"), fingerprint }
+    }
+
+    /// The bytes to sign with ECDSA P-256 / SHA-256: the PAE of the
+    /// statement in `SignatureContext::Dsse`.
+    pub fn signing_message(&self) -> Vec<u8> {
+        SignatureContext::Dsse.message(&pae(PAYLOAD_TYPE, &self.statement))
+    }
+
+    /// The envelope with `signature` in place.
+    pub fn finish(self, signature: &Signature) -> Envelope {
+        Envelope {
+            payload_type: PAYLOAD_TYPE.to_string(),
+            payload: Base64::encode_string(&self.statement),
+            signatures: vec![EnvelopeSignature {
+                keyid: self.fingerprint.to_hex(),
+                sig: Base64::encode_string(signature.to_der().as_bytes()),
+            }],
+        }
+    }
+}
+
+impl Envelope {
+    /// Compact JSON ending in a newline: one line of a `.intoto.jsonl` file.
+    pub fn to_line(&self) -> Vec<u8> {
+        let mut out = serde_json::to_vec(self).expect("envelopes serialize");
+        out.push(b'\n');
+        out
+    }
+
+    #[cfg(feature = "verifier")]
+    pub fn parse(bytes: &[u8]) -> Result<Self, AegisError> {
+        let envelope: Envelope =
+            serde_json::from_slice(bytes).map_err(|e| AegisError::InvalidAttestation(e.to_string()))?;
+        if envelope.payload_type != PAYLOAD_TYPE {
+            return Err(AegisError::InvalidAttestation(format!("unsupported payload type '{}'", envelope.payload_type)));
+        }
+        Ok(envelope)
+    }
+
+    /// The statement the envelope carries.
+    pub fn statement(&self) -> Result<Value, AegisError> {
+        let payload = Base64::decode_vec(&self.payload).map_err(|e| AegisError::InvalidAttestation(e.to_string()))?;
+        let statement: Value =
+            serde_json::from_slice(&payload).map_err(|e| AegisError::InvalidAttestation(e.to_string()))?;
+        if statement["_type"] != STATEMENT_TYPE {
+            return Err(AegisError::InvalidAttestation("not an in-toto v1 statement".into()));
+        }
+        Ok(statement)
+    }
+
+    /// Whether any of the envelope's signatures is by `public_key`.
+    #[cfg(feature = "verifier")]
+    pub fn verify(&self, public_key: &VerifyingKey) -> Result<bool, AegisError> {
+        let payload = Base64::decode_vec(&self.payload).map_err(|e| AegisError::InvalidAttestation(e.to_string()))?;
+        let message = pae(&self.payload_type, &payload);
+        Ok(self.signatures.iter().any(|signature| {
+            Base64::decode_vec(&signature.sig)
+                .ok()
+                .and_then(|der| Signature::from_der(&der).ok())
+                .is_some_and(|signature| SignatureContext::Dsse.verify(public_key, &message, &signature).unwrap_or(false))
+        }))
+    }
+}
+
+/// DSSE's pre-authentication encoding: `DSSEv1`, then the payload type and
+/// payload, each preceded by its length in ASCII decimal, separated by
+/// spaces.
+pub fn pae(payload_type: &str, payload: &[u8]) -> Vec<u8> {
+    let mut out = PAE_PREAMBLE.to_vec();
+    out.extend_from_slice(format!("{} {} {} ", payload_type.len(), payload_type, payload.len()).as_bytes());
+    out.extend_from_slice(payload);
+    out
+}
+
+/// The name of the attestation for the payload named `payload_name`.
+pub fn attestation_name(payload_name: &str) -> String {
+    format!("{}.{}", payload_name, ATTESTATION_EXTENSION)
+}
+
+/// Signs a statement about `detached`'s payload with a local key.
+#[cfg(feature = "sealer")]
+pub fn build<S: Signer<Signature>>(detached: &DetachedSignature, name: &str, signer: &S) -> Result<Envelope, AegisError> {
+    let unsigned = UnsignedEnvelope::new(detached, name);
+    let signature = SignatureContext::Dsse.sign(&pae(PAYLOAD_TYPE, &unsigned.statement), signer)?;
+    Ok(unsigned.finish(&signature))
+}
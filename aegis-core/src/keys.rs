@@ -0,0 +1,360 @@
+// aegis-core/src/keys.rs
+
+use crate::error::AegisError;
+use crate::format::FormatHeader;
+use crate::time;
+use p256::ecdsa::{SigningKey, VerifyingKey};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::path::Path;
+#[cfg(any(feature = "sealer", feature = "verifier"))]
+use crate::{crypto, format::AegisAncient};
+
+const RANDOMART_WIDTH: usize = 17;
+const RANDOMART_HEIGHT: usize = 9;
+const RANDOMART_SYMBOLS: &[u8] = b" .o+=*BOX@%&#/^";
+
+/// How many leading fingerprint bytes are rendered as words.
+const WORD_COUNT: usize = 8;
+
+/// A SHA-256 fingerprint of a SEC1-encoded public key.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Fingerprint([u8; 32]);
+
+impl Fingerprint {
+    /// Computes the fingerprint of the given public key bytes.
+    pub fn of(public_key: &[u8]) -> Self {
+        Fingerprint(Sha256::digest(public_key).into())
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Full lowercase hex, suitable for machine comparison.
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.0)
+    }
+
+    /// Uppercase hex split into colon-separated groups of four characters.
+    pub fn to_hex_groups(&self) -> String {
+        hex::encode_upper(self.0)
+            .as_bytes()
+            .chunks(4)
+            .map(|c| std::str::from_utf8(c).unwrap())
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+
+    /// The first bytes of the fingerprint encoded as a short list of words,
+    /// which is easier to read aloud than hex.
+    pub fn to_words(&self) -> String {
+        self.0[..WORD_COUNT]
+            .iter()
+            .map(|b| WORDS[*b as usize])
+            .collect::<Vec<_>>()
+            .join("-")
+    }
+
+    /// OpenSSH-style "drunken bishop" randomart of the fingerprint.
+    pub fn to_randomart(&self) -> String {
+        let mut field = [[0u8; RANDOMART_WIDTH]; RANDOMART_HEIGHT];
+        let (mut x, mut y) = (RANDOMART_WIDTH / 2, RANDOMART_HEIGHT / 2);
+        let start = (x, y);
+        for byte in self.0 {
+            for step in 0..4 {
+                let bits = byte >> (step * 2);
+                x = if bits & 1 == 1 { (x + 1).min(RANDOMART_WIDTH - 1) } else { x.saturating_sub(1) };
+                y = if bits & 2 == 2 { (y + 1).min(RANDOMART_HEIGHT - 1) } else { y.saturating_sub(1) };
+                let cell = &mut field[y][x];
+                *cell = (*cell + 1).min(RANDOMART_SYMBOLS.len() as u8 - 1);
+            }
+        }
+
+        let mut out = format!("+{:-^width$}+\n", "[P-256]", width = RANDOMART_WIDTH);
+        for (row_idx, row) in field.iter().enumerate() {
+            out.push('|');
+            for (col_idx, count) in row.iter().enumerate() {
+                let symbol = if (col_idx, row_idx) == start {
+                    'S'
+                } else if (col_idx, row_idx) == (x, y) {
+                    'E'
+                } else {
+                    RANDOMART_SYMBOLS[*count as usize] as char
+                };
+                out.push(symbol);
+            }
+            out.push_str("|\n");
+        }
+        out.push_str(&format!("+{:-^width$}+", "[SHA256]", width = RANDOMART_WIDTH));
+        out
+    }
+
+    /// Compares against a fingerprint typed or pasted by a human. Separators,
+    /// whitespace and case are ignored; word-list input is accepted as well.
+    pub fn matches(&self, input: &str) -> bool {
+        let normalized: String = input
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        if normalized == self.to_hex() {
+            return true;
+        }
+        let words: Vec<String> = input
+            .split(|c: char| !c.is_ascii_alphabetic())
+            .filter(|w| !w.is_empty())
+            .map(|w| w.to_ascii_lowercase())
+            .collect();
+        words.len() == WORD_COUNT && words.join("-") == self.to_words()
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SHA256:{}", self.to_hex())
+    }
+}
+
+/// One key of a `Keyring`. Keys without a private part can only verify.
+#[derive(Clone, Debug)]
+pub struct KeyringEntry {
+    pub id: String,
+    pub public_key: VerifyingKey,
+    pub signing_key: Option<SigningKey>,
+    /// Unix seconds from which the key may seal; `None` means always.
+    pub not_before: Option<i64>,
+    /// Unix seconds from which the key is retired from sealing. Retired keys
+    /// still verify what they sealed.
+    pub not_after: Option<i64>,
+}
+
+impl KeyringEntry {
+    pub fn fingerprint(&self) -> Fingerprint {
+        Fingerprint::of(&self.public_key.to_sec1_bytes())
+    }
+
+    /// Whether the key can seal at `now` (Unix seconds).
+    pub fn is_active(&self, now: i64) -> bool {
+        self.signing_key.is_some()
+            && self.not_before.is_none_or(|t| t <= now)
+            && self.not_after.is_none_or(|t| now < t)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EntryConfig {
+    #[serde(default)]
+    id: Option<String>,
+    /// Hex 32-byte P-256 private scalar.
+    #[serde(default)]
+    private_key: Option<String>,
+    /// Hex SEC1 public key, for verification-only entries.
+    #[serde(default)]
+    public_key: Option<String>,
+    #[serde(default)]
+    not_before: Option<String>,
+    #[serde(default)]
+    not_after: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyringConfig {
+    keys: Vec<EntryConfig>,
+}
+
+/// A set of keys with IDs and activation windows, so the sealing key can be
+/// rotated while containers sealed with earlier keys keep verifying.
+///
+/// Loaded from a JSON file of the form
+///
+/// ```json
+/// {"keys": [{"id": "2026-q1", "private_key": "<hex>",
+///            "not_before": "2026-01-01T00:00:00Z", "not_after": "2026-04-01T00:00:00Z"}]}
+/// ```
+///
+/// or from a directory holding one such key object per `*.json` file, where
+/// the file name (without extension) is the default ID. At seal time the
+/// active key with the latest `not_before` is used, and its ID is written
+/// into the container header.
+#[derive(Clone, Debug, Default)]
+pub struct Keyring {
+    entries: Vec<KeyringEntry>,
+}
+
+impl Keyring {
+    pub fn from_json(json: &str) -> Result<Self, AegisError> {
+        let config: KeyringConfig =
+            serde_json::from_str(json).map_err(|e| AegisError::Crypto(format!("invalid keyring: {}", e)))?;
+        let mut keyring = Keyring::default();
+        for entry in config.keys {
+            keyring.add(entry, None)?;
+        }
+        Ok(keyring)
+    }
+
+    /// Loads a keyring file, or a directory of key files.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, AegisError> {
+        let path = path.as_ref();
+        if !path.is_dir() {
+            return Keyring::from_json(&std::fs::read_to_string(path)?);
+        }
+        let mut files: Vec<_> = std::fs::read_dir(path)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        files.sort();
+        let mut keyring = Keyring::default();
+        for file in files {
+            let entry: EntryConfig = serde_json::from_str(&std::fs::read_to_string(&file)?)
+                .map_err(|e| AegisError::Crypto(format!("invalid key file {}: {}", file.display(), e)))?;
+            let stem = file.file_stem().map(|s| s.to_string_lossy().into_owned());
+            keyring.add(entry, stem)?;
+        }
+        Ok(keyring)
+    }
+
+    fn add(&mut self, config: EntryConfig, default_id: Option<String>) -> Result<(), AegisError> {
+        let id = config
+            .id
+            .or(default_id)
+            .ok_or_else(|| AegisError::Crypto("keyring entry has no id".into()))?;
+        let invalid = |what: &str| AegisError::Crypto(format!("key '{}': invalid {}", id, what));
+        let signing_key = match &config.private_key {
+            Some(hex_key) => Some(
+                hex::decode(hex_key.trim())
+                    .ok()
+                    .and_then(|bytes| SigningKey::from_slice(&bytes).ok())
+                    .ok_or_else(|| invalid("private_key"))?,
+            ),
+            None => None,
+        };
+        let public_key = match (&signing_key, &config.public_key) {
+            (Some(key), _) => *key.verifying_key(),
+            (None, Some(hex_key)) => hex::decode(hex_key.trim())
+                .ok()
+                .and_then(|bytes| VerifyingKey::from_sec1_bytes(&bytes).ok())
+                .ok_or_else(|| invalid("public_key"))?,
+            (None, None) => return Err(invalid("entry: it needs a private_key or public_key")),
+        };
+        let parse_time = |value: &Option<String>, what: &str| match value {
+            Some(t) => time::parse_rfc3339(t).map(Some).ok_or_else(|| invalid(what)),
+            None => Ok(None),
+        };
+        let not_before = parse_time(&config.not_before, "not_before")?;
+        let not_after = parse_time(&config.not_after, "not_after")?;
+        if self.get(&id).is_some() {
+            return Err(AegisError::Crypto(format!("duplicate key id '{}'", id)));
+        }
+        self.entries.push(KeyringEntry {
+            id,
+            public_key,
+            signing_key,
+            not_before,
+            not_after,
+        });
+        Ok(())
+    }
+
+    pub fn entries(&self) -> &[KeyringEntry] {
+        &self.entries
+    }
+
+    pub fn get(&self, id: &str) -> Option<&KeyringEntry> {
+        self.entries.iter().find(|e| e.id == id)
+    }
+
+    pub fn by_fingerprint(&self, fingerprint: &Fingerprint) -> Option<&KeyringEntry> {
+        self.entries.iter().find(|e| e.fingerprint() == *fingerprint)
+    }
+
+    /// The key to seal with at `now` (Unix seconds): of the active keys, the
+    /// one activated most recently.
+    pub fn active(&self, now: i64) -> Option<&KeyringEntry> {
+        self.entries
+            .iter()
+            .filter(|e| e.is_active(now))
+            .max_by_key(|e| e.not_before.unwrap_or(i64::MIN))
+    }
+
+    /// The entry a container claims to be sealed with: by the key ID in its
+    /// header, or else by the fingerprint of its embedded key. Either way
+    /// the entry's key must be the embedded one.
+    pub fn resolve(&self, header: &FormatHeader, public_key: &[u8]) -> Result<&KeyringEntry, AegisError> {
+        let fingerprint = Fingerprint::of(public_key);
+        let entry = match header.key_id() {
+            Some(id) => self
+                .get(id)
+                .ok_or_else(|| AegisError::Crypto(format!("unknown key id '{}'", id)))?,
+            None => self
+                .by_fingerprint(&fingerprint)
+                .ok_or_else(|| AegisError::Crypto(format!("signing key {} is not in the keyring", fingerprint)))?,
+        };
+        if entry.fingerprint() != fingerprint {
+            return Err(AegisError::Crypto(format!(
+                "key id '{}' does not match the embedded key {}",
+                entry.id, fingerprint
+            )));
+        }
+        Ok(entry)
+    }
+
+    /// Verifies a container against the keyring, including keys retired
+    /// from sealing, and returns the key that sealed it.
+    #[cfg(feature = "verifier")]
+    pub fn verify(&self, ancient: &AegisAncient) -> Result<&KeyringEntry, AegisError> {
+        let entry = self.resolve(&ancient.header, &ancient.public_key)?;
+        if !crypto::verify(ancient)?.signature_valid {
+            return Err(AegisError::Crypto("signature does not match contents".into()));
+        }
+        Ok(entry)
+    }
+
+    /// Seals with the key active at `now` and records its ID in the header.
+    #[cfg(feature = "sealer")]
+    pub fn seal(&self, metadata: String, image_data: Vec<u8>, now: i64) -> Result<AegisAncient, AegisError> {
+        let entry = self
+            .active(now)
+            .ok_or_else(|| AegisError::Crypto("no active key in the keyring".into()))?;
+        let key = entry.signing_key.as_ref().expect("active keys can sign");
+        let mut ancient = crypto::seal(metadata, image_data, key)?;
+        ancient.header.set_key_id(&entry.id);
+        Ok(ancient)
+    }
+}
+
+// 256 short, phonetically distinct words; one per byte value.
+const WORDS: [&str; 256] = [
+    "acid", "acorn", "actor", "adobe", "agent", "alarm", "album", "alder", "alien", "alley",
+    "alpha", "amber", "angle", "ankle", "apple", "april", "apron", "arena", "armor", "arrow",
+    "aspen", "atlas", "attic", "audio", "autumn", "avenue", "badge", "bagel", "baker",
+    "bamboo", "banjo", "barley", "baron", "basil", "basin", "beach", "beacon", "beaver",
+    "berry", "bison", "blade", "blaze", "bloom", "bonus", "border", "bottle", "bounty",
+    "bracket", "brass", "bread", "brick", "bridge", "bronze", "brook", "bucket", "bugle",
+    "cabin", "cactus", "camel", "canal", "candle", "canoe", "canyon", "carbon", "cargo",
+    "carpet", "castle", "cedar", "cello", "chalk", "charm", "cherry", "chess", "cider",
+    "cinema", "circle", "citrus", "clover", "cobalt", "cocoa", "comet", "copper", "coral",
+    "cotton", "cradle", "crater", "crayon", "credit", "cricket", "crystal", "cupid", "cypress",
+    "dagger", "daisy", "dancer", "delta", "denim", "desert", "diesel", "dinner", "dolphin",
+    "domino", "dragon", "drift", "eagle", "ember", "emerald", "engine", "fable", "falcon",
+    "fender", "ferry", "fiber", "fiddle", "flint", "forest", "fossil", "galaxy", "garden",
+    "garlic", "gecko", "geyser", "ginger", "glacier", "globe", "goblet", "granite", "gravel",
+    "guitar", "hammer", "harbor", "harvest", "hazel", "helmet", "hermit", "hickory", "honey",
+    "hornet", "husky", "igloo", "indigo", "island", "ivory", "jacket", "jaguar", "jasmine",
+    "jelly", "jersey", "jigsaw", "jungle", "kayak", "kernel", "kettle", "kiwi", "koala",
+    "ladder", "lagoon", "lantern", "laser", "lemon", "lilac", "linen", "lizard", "lobster",
+    "locket", "lotus", "lumber", "magnet", "mango", "maple", "marble", "meadow", "melon",
+    "meteor", "mirror", "mocha", "monsoon", "mosaic", "muffin", "napkin", "nectar", "needle",
+    "nickel", "noodle", "nutmeg", "oasis", "ocean", "olive", "onion", "opal", "orbit",
+    "orchid", "otter", "oyster", "paddle", "pagoda", "panda", "papaya", "parrot", "pebble",
+    "pepper", "piano", "pilot", "planet", "plaza", "pocket", "polar", "pony", "poppy", "prism",
+    "pumpkin", "puzzle", "quartz", "quill", "rabbit", "radar", "raven", "reef", "ribbon",
+    "ridge", "river", "robin", "rocket", "saddle", "salmon", "sapphire", "saturn", "scarf",
+    "sequoia", "shadow", "shovel", "silver", "socket", "spruce", "squid", "summit", "sunset",
+    "tablet", "tango", "temple", "thistle", "thunder", "tiger", "timber", "tomato", "topaz",
+    "tractor", "tulip", "tundra", "turtle", "umbrella", "valley", "velvet", "violin", "walnut",
+    "willow",
+];
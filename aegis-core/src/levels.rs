@@ -0,0 +1,166 @@
+// aegis-core/src/levels.rs
+
+// Verification in tiers, for callers that need more or less depth than
+// `crypto::verify()` gives. Each level runs the checks of the one below it
+// and more:
+//
+// - `quick`: the container parses, and the signature verifies under its
+//   embedded key.
+// - `standard`: also that metadata kept in the header matches its signed
+//   reference, and that a certificate chain, if there is one and trust
+//   anchors are given, leads to one and was valid (unexpired) when sealed. Callers with a
+//   trust bundle or revocation list of their own add those checks to the
+//   report with `LevelReport::add()`.
+// - `forensic`: also the timestamp token anchoring the signature in time,
+//   if trusted TSAs are given, each co-signature in turn, and a second, chunked pass over the image
+//   whose digest must match the first.
+//
+// The report lists every check that ran with its outcome; a check that does
+// not apply to the container (no timestamp, no chain) is reported as such
+// rather than left out.
+
+use crate::crypto::{self, SigningHasher, VerificationReport};
+use crate::error::AegisError;
+use crate::format::AegisAncient;
+use crate::timestamp::TsaTrust;
+use crate::x509::TrustAnchors;
+use serde::Serialize;
+
+/// Chunk size of the forensic re-hash, different from any buffer the first
+/// pass is likely to have used.
+const REHASH_CHUNK: usize = 64 * 1024 + 7;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VerificationLevel {
+    Quick,
+    Standard,
+    Forensic,
+}
+
+impl VerificationLevel {
+    pub const ALL: [VerificationLevel; 3] =
+        [VerificationLevel::Quick, VerificationLevel::Standard, VerificationLevel::Forensic];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            VerificationLevel::Quick => "quick",
+            VerificationLevel::Standard => "standard",
+            VerificationLevel::Forensic => "forensic",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|level| level.name() == name.trim())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Pass,
+    Fail,
+    NotApplicable,
+}
+
+/// The outcome of one check.
+#[derive(Clone, Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub outcome: Outcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// The checks run at one level. `passed` is false if any of them failed.
+#[derive(Clone, Debug, Serialize)]
+pub struct LevelReport {
+    pub level: VerificationLevel,
+    pub passed: bool,
+    pub checks: Vec<Check>,
+}
+
+impl LevelReport {
+    fn new(level: VerificationLevel) -> Self {
+        LevelReport { level, passed: true, checks: Vec::new() }
+    }
+
+    /// Records the outcome of a check.
+    pub fn add(&mut self, name: &'static str, outcome: Outcome, detail: Option<String>) {
+        self.passed &= outcome != Outcome::Fail;
+        self.checks.push(Check { name, outcome, detail });
+    }
+
+    fn pass_if(&mut self, name: &'static str, ok: bool, detail: Option<String>) {
+        self.add(name, if ok { Outcome::Pass } else { Outcome::Fail }, detail);
+    }
+}
+
+/// Verifies a container at `level`, returning the `crypto::verify()`
+/// report (`crypto::verify_with_anchors()` from `standard` up) with the
+/// checks that ran.
+pub fn verify(
+    ancient: &AegisAncient,
+    level: VerificationLevel,
+    anchors: &TrustAnchors,
+    tsa: &TsaTrust,
+) -> Result<(VerificationReport, LevelReport), AegisError> {
+    let report = if level >= VerificationLevel::Standard {
+        crypto::verify_with_anchors(ancient, anchors, tsa)?
+    } else {
+        crypto::verify(ancient)?
+    };
+    let mut checks = LevelReport::new(level);
+    checks.add("format", Outcome::Pass, Some(format!("version {}", char::from(ancient.version))));
+    checks.pass_if("signature", report.signature_valid, None);
+    if level == VerificationLevel::Quick {
+        return Ok((report, checks));
+    }
+
+    match report.external_metadata_valid {
+        Some(valid) => checks.pass_if("external_metadata", valid, None),
+        None => checks.add("external_metadata", Outcome::NotApplicable, None),
+    }
+    match &report.certificate_chain {
+        Some(_) if anchors.is_empty() => {
+            checks.add("certificate_chain", Outcome::NotApplicable, Some("no trust anchors given".into()))
+        }
+        Some(chain) => checks.pass_if("certificate_chain", chain.valid, chain.error.clone()),
+        None => checks.add("certificate_chain", Outcome::NotApplicable, None),
+    }
+    if level == VerificationLevel::Standard {
+        return Ok((report, checks));
+    }
+
+    match &report.timestamp {
+        Some(_) if tsa.is_empty() => {
+            checks.add("timestamp", Outcome::NotApplicable, Some("no trusted TSAs given".into()))
+        }
+        Some(timestamp) => checks.pass_if(
+            "timestamp",
+            timestamp.valid,
+            timestamp.error.clone().or_else(|| timestamp.attested_time.clone()),
+        ),
+        None => checks.add("timestamp", Outcome::NotApplicable, None),
+    }
+    if report.cosigners.is_empty() {
+        checks.add("cosignatures", Outcome::NotApplicable, None);
+    } else {
+        let invalid: Vec<&str> =
+            report.cosigners.iter().filter(|c| !c.signature_valid).map(|c| c.role.as_str()).collect();
+        let detail = if invalid.is_empty() {
+            format!("{} valid", report.cosigners.len())
+        } else {
+            format!("invalid: {}", invalid.join(", "))
+        };
+        checks.pass_if("cosignatures", invalid.is_empty(), Some(detail));
+    }
+    let mut hasher = SigningHasher::for_header(&ancient.header, &ancient.metadata)?;
+    for chunk in ancient.image_data.chunks(REHASH_CHUNK) {
+        hasher.update(chunk);
+    }
+    let rehashed = hex::encode(crypto::container_digest(&ancient.header, &hasher.finalize())?);
+    let detail = (rehashed != report.digest).then(|| format!("second pass gave {}", rehashed));
+    checks.pass_if("rehash", detail.is_none(), detail);
+    Ok((report, checks))
+}
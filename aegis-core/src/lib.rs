@@ -0,0 +1,48 @@
+// aegis-core/src/lib.rs
+
+// The container format and its cryptography, usable without the HTTP
+// service: no async runtime or web framework is pulled in. The `sealer`
+// feature (on by default) provides signing; `verifier` provides parsing and
+// verification, including of X.509 certificate chains; `hsm` adds signing
+// with PKCS#11 tokens; `test-util` adds fixtures for downstream tests.
+pub mod accel;
+pub mod bundle;
+pub mod c2pa;
+pub mod chunked;
+pub mod crypto;
+mod der;
+pub mod delegation;
+pub mod dns_trust;
+pub mod endorsement;
+pub mod error;
+pub mod exif;
+#[cfg(feature = "verifier")]
+pub mod explain;
+pub mod format;
+pub mod http_sig;
+pub mod image_info;
+pub mod intoto;
+pub mod keys;
+#[cfg(feature = "verifier")]
+pub mod levels;
+pub mod lint;
+pub mod manifest;
+pub mod media_type;
+pub mod merkle;
+pub mod metadata;
+#[cfg(all(unix, feature = "hsm"))]
+pub mod pkcs11;
+pub mod prelude;
+pub mod rollup;
+pub mod spec;
+#[cfg(all(unix, feature = "sealer"))]
+pub mod ssh_agent;
+pub mod tar;
+#[cfg(any(feature = "test-util", test))]
+pub mod test_util;
+pub mod text;
+pub mod time;
+pub mod timestamp;
+pub mod x509;
+pub mod xmp;
+pub mod zstd;
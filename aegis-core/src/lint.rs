@@ -0,0 +1,144 @@
+// aegis-core/src/lint.rs
+
+// Container linting: flags containers whose parameters are weak, deprecated
+// or incomplete even if their signature verifies. Each rule has a default
+// severity that a JSON policy file can raise, lower or switch off, e.g.
+//
+//   { "severities": { "unanchored": "error", "metadata-not-json": "off" },
+//     "max_metadata_bytes": 16384,
+//     "deprecated_hashes": ["md5", "sha1", "sha-1"] }
+
+use crate::{format::AegisAncient, time::parse_rfc3339};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Off,
+    Info,
+    Warning,
+    Error,
+}
+
+/// Rule identifiers with their default severities.
+pub const RULES: [(&str, Severity, &str); 7] = [
+    ("key-not-p256", Severity::Error, "public key is not a valid P-256 SEC1 key"),
+    ("signature-encoding", Severity::Error, "signature is not a 64-byte raw r||s value"),
+    ("deprecated-hash", Severity::Warning, "metadata declares a deprecated hash algorithm"),
+    ("missing-timestamp", Severity::Warning, "metadata carries no RFC 3339 timestamp"),
+    ("unanchored", Severity::Info, "seal is not anchored in a transparency log or timestamp authority"),
+    ("oversize-metadata", Severity::Warning, "metadata exceeds the policy size limit"),
+    ("metadata-not-json", Severity::Info, "metadata is not a JSON document"),
+];
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct LintPolicy {
+    /// Per-rule severity overrides; rules not listed keep their defaults.
+    pub severities: BTreeMap<String, Severity>,
+    pub max_metadata_bytes: usize,
+    /// Lower-case algorithm names treated as deprecated.
+    pub deprecated_hashes: Vec<String>,
+    /// Metadata keys whose presence counts as an anchor.
+    pub anchor_fields: Vec<String>,
+}
+
+impl Default for LintPolicy {
+    fn default() -> Self {
+        LintPolicy {
+            severities: BTreeMap::new(),
+            max_metadata_bytes: 64 * 1024,
+            deprecated_hashes: ["md5", "sha1", "sha-1", "sha224", "sha-224"].map(String::from).to_vec(),
+            anchor_fields: ["transparency_log", "rfc3161_token", "anchor"].map(String::from).to_vec(),
+        }
+    }
+}
+
+impl LintPolicy {
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    pub fn severity(&self, rule: &str) -> Severity {
+        self.severities.get(rule).copied().unwrap_or_else(|| {
+            RULES
+                .iter()
+                .find(|(id, _, _)| *id == rule)
+                .map(|(_, severity, _)| *severity)
+                .unwrap_or(Severity::Warning)
+        })
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Finding {
+    pub rule: &'static str,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Runs every rule against a container; rules set to `off` are skipped.
+pub fn lint(ancient: &AegisAncient, policy: &LintPolicy) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut flag = |rule: &'static str, message: String| {
+        let severity = policy.severity(rule);
+        if severity != Severity::Off {
+            findings.push(Finding { rule, severity, message });
+        }
+    };
+
+    if p256::PublicKey::from_sec1_bytes(&ancient.public_key).is_err() {
+        flag("key-not-p256", format!("{}-byte public key does not decode as P-256", ancient.public_key.len()));
+    }
+    if ancient.signature.len() != 64 {
+        flag("signature-encoding", format!("signature is {} bytes, expected 64", ancient.signature.len()));
+    }
+    if ancient.metadata.len() > policy.max_metadata_bytes {
+        flag(
+            "oversize-metadata",
+            format!("metadata is {} bytes, limit is {}", ancient.metadata.len(), policy.max_metadata_bytes),
+        );
+    }
+
+    let Ok(metadata) = serde_json::from_str::<serde_json::Value>(&ancient.metadata) else {
+        flag("metadata-not-json", "metadata could not be parsed as JSON".into());
+        flag("missing-timestamp", "no timestamp found in metadata".into());
+        flag("unanchored", "no anchor fields found in metadata".into());
+        return findings;
+    };
+
+    let mut strings = Vec::new();
+    let mut keys = Vec::new();
+    collect(&metadata, &mut keys, &mut strings);
+    for (key, value) in keys.iter().zip(strings.iter()) {
+        let is_hash_field = key.contains("hash") || key.contains("digest") || key.ends_with("alg");
+        if is_hash_field && policy.deprecated_hashes.contains(&value.to_ascii_lowercase()) {
+            flag("deprecated-hash", format!("'{}' declares {}", key, value));
+        }
+    }
+    if !strings.iter().any(|s| parse_rfc3339(s).is_some()) {
+        flag("missing-timestamp", "no RFC 3339 timestamp found in metadata".into());
+    }
+    if !policy.anchor_fields.iter().any(|field| keys.iter().any(|k| k == field)) {
+        flag("unanchored", format!("none of {:?} present in metadata", policy.anchor_fields));
+    }
+    findings
+}
+
+// Flattens a JSON document into parallel lists of keys and their string
+// values (empty for non-string values), at any depth.
+fn collect(value: &serde_json::Value, keys: &mut Vec<String>, strings: &mut Vec<String>) {
+    use serde_json::Value;
+    match value {
+        Value::Object(map) => {
+            for (k, v) in map {
+                keys.push(k.to_ascii_lowercase());
+                strings.push(v.as_str().unwrap_or_default().to_string());
+                collect(v, keys, strings);
+            }
+        }
+        Value::Array(items) => items.iter().for_each(|v| collect(v, keys, strings)),
+        _ => {}
+    }
+}
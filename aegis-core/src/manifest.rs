@@ -0,0 +1,161 @@
+// aegis-core/src/manifest.rs
+
+// Payload manifests: a seal kept as a small JSON file next to the payload,
+// which stays untouched in its original format, for archives that will not
+// store anything else. `photo.jpg` is sealed into `photo.jpg.aegis-manifest`:
+//
+//     {"format": "aegis-payload-manifest/1",
+//      "payload": {"file_name": "photo.jpg", "size": 48213,
+//                  "sha256": "9f86d0...", "media_type": "image/jpeg"},
+//      "metadata": "{\"title\": ...}",
+//      "key": {"public_key": "04ab...", "fingerprint": "2bad0f...",
+//              "key_id": "2025-06"},
+//      "signature": {"scheme": "ecdsa-p256", "value": "3045...",
+//                    "signed_fields": ["metadata", "image_data"]}}
+//
+// The signature is that of a detached signature (see
+// `format::DetachedSignature`), over `signing_digest()` of the metadata and
+// the payload, so a manifest converts to a `.aegis.sig` sidecar, or with its
+// payload to a full container, without re-signing. The payload's size and
+// SHA-256 are checked along with the signature. `file_name`, `media_type`
+// (read from the signed metadata when written), the fingerprint and the key
+// ID are descriptive only and not covered by the signature.
+
+#[cfg(feature = "verifier")]
+use crate::error::AegisError;
+use crate::{crypto, format::DetachedSignature, keys::Fingerprint};
+use serde::{Deserialize, Serialize};
+
+pub const MANIFEST_FORMAT: &str = "aegis-payload-manifest/1";
+pub const MANIFEST_EXTENSION: &str = "aegis-manifest";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PayloadManifest {
+    pub format: String,
+    pub payload: PayloadEntry,
+    pub metadata: String,
+    pub key: KeyEntry,
+    pub signature: SignatureEntry,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PayloadEntry {
+    /// The payload's file name when it was sealed, without directories.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_name: Option<String>,
+    pub size: u64,
+    /// Hex SHA-256 of the payload.
+    pub sha256: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct KeyEntry {
+    /// Hex SEC1 public key.
+    pub public_key: String,
+    /// Hex SHA-256 fingerprint of the public key.
+    pub fingerprint: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SignatureEntry {
+    /// `SignatureScheme::name()`; detached signatures are always ECDSA P-256.
+    pub scheme: String,
+    /// Hex raw signature.
+    pub value: String,
+    pub signed_fields: Vec<String>,
+}
+
+impl PayloadManifest {
+    /// The manifest for a detached signature of the payload named
+    /// `file_name`, made with the key called `key_id`.
+    pub fn new(detached: &DetachedSignature, file_name: Option<&str>, key_id: Option<&str>) -> Self {
+        PayloadManifest {
+            format: MANIFEST_FORMAT.to_string(),
+            payload: PayloadEntry {
+                file_name: file_name.map(base_name).filter(|name| !name.is_empty()).map(str::to_string),
+                size: detached.image_len,
+                sha256: hex::encode(detached.image_sha256),
+                media_type: crate::media_type::from_metadata(&detached.metadata),
+            },
+            metadata: detached.metadata.clone(),
+            key: KeyEntry {
+                public_key: hex::encode(&detached.public_key),
+                fingerprint: Fingerprint::of(&detached.public_key).to_hex(),
+                key_id: key_id.map(str::to_string),
+            },
+            signature: SignatureEntry {
+                scheme: crypto::SignatureScheme::EcdsaP256.name().to_string(),
+                value: hex::encode(&detached.signature),
+                signed_fields: crypto::SIGNED_FIELDS.iter().map(|f| f.to_string()).collect(),
+            },
+        }
+    }
+
+    /// Pretty-printed JSON, ending in a newline.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = serde_json::to_vec_pretty(self).expect("manifests serialize");
+        out.push(b'\n');
+        out
+    }
+
+    #[cfg(feature = "verifier")]
+    pub fn parse(bytes: &[u8]) -> Result<Self, AegisError> {
+        let manifest: PayloadManifest =
+            serde_json::from_slice(bytes).map_err(|e| AegisError::InvalidManifest(e.to_string()))?;
+        if manifest.format != MANIFEST_FORMAT {
+            return Err(AegisError::InvalidManifest(format!("unsupported format '{}'", manifest.format)));
+        }
+        if manifest.signature.scheme != crypto::SignatureScheme::EcdsaP256.name() {
+            return Err(AegisError::InvalidManifest(format!("unsupported scheme '{}'", manifest.signature.scheme)));
+        }
+        let detached = manifest.detached()?;
+        if Fingerprint::of(&detached.public_key).to_hex() != manifest.key.fingerprint.to_ascii_lowercase() {
+            return Err(AegisError::InvalidManifest("key fingerprint does not match the public key".into()));
+        }
+        Ok(manifest)
+    }
+
+    /// The detached signature the manifest carries.
+    #[cfg(feature = "verifier")]
+    pub fn detached(&self) -> Result<DetachedSignature, AegisError> {
+        let decode = |field: &str, value: &str| {
+            hex::decode(value.trim()).map_err(|e| AegisError::InvalidManifest(format!("{}: {}", field, e)))
+        };
+        Ok(DetachedSignature {
+            public_key: decode("key.public_key", &self.key.public_key)?,
+            metadata: self.metadata.clone(),
+            signature: decode("signature.value", &self.signature.value)?,
+            image_sha256: decode("payload.sha256", &self.payload.sha256)?
+                .try_into()
+                .map_err(|_| AegisError::InvalidManifest("payload.sha256 is not 32 bytes".into()))?,
+            image_len: self.payload.size,
+        })
+    }
+}
+
+/// Whether `bytes` may be a manifest rather than a container or sidecar:
+/// manifests are JSON objects, which neither of those can start like.
+pub fn sniff(bytes: &[u8]) -> bool {
+    bytes.trim_ascii_start().starts_with(b"{")
+}
+
+/// The name of the manifest for the payload named `payload_name`.
+pub fn manifest_name(payload_name: &str) -> String {
+    format!("{}.{}", payload_name, MANIFEST_EXTENSION)
+}
+
+/// The name of the payload a manifest named `manifest_name` pairs with.
+pub fn payload_name(manifest_name: &str) -> Option<&str> {
+    manifest_name
+        .strip_suffix(MANIFEST_EXTENSION)?
+        .strip_suffix('.')
+        .filter(|name| !name.is_empty())
+}
+
+fn base_name(path: &str) -> &str {
+    path.rsplit(['/', '\\']).next().unwrap_or("")
+}
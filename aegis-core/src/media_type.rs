@@ -0,0 +1,98 @@
+// aegis-core/src/media_type.rs
+
+// Media types of sealed payloads. A container's payload is usually an image,
+// but may be any file; its declared type is kept in the signed `content`
+// block of the metadata:
+//
+//     "content": {"format": "payload", "media_type": "application/pdf"}
+//
+// Declared types are plain `type/subtype` names (RFC 6838), lowercased and
+// without parameters. `sniff()` recognizes common formats from their leading
+// bytes, so a declaration can be checked against the payload and a payload
+// sealed without one can still be described.
+
+use crate::image_info::ImageFormat;
+use serde_json::Value;
+
+/// `sniff()` results that only name a container format, whose contents may
+/// be declared under a more specific type of another top-level type.
+const CONTAINER_TYPES: [&str; 4] = ["application/zip", "application/gzip", "application/ogg", "video/x-matroska"];
+
+/// `type/subtype` lowercased and without parameters, if that is a valid
+/// media type name.
+pub fn normalize(media_type: &str) -> Option<String> {
+    let name = media_type.split(';').next()?.trim().to_ascii_lowercase();
+    let (kind, subtype) = name.split_once('/')?;
+    (is_restricted_name(kind) && is_restricted_name(subtype)).then_some(name)
+}
+
+/// RFC 6838 `restricted-name`: up to 127 characters, starting with a letter
+/// or digit.
+fn is_restricted_name(name: &str) -> bool {
+    name.len() <= 127
+        && name.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || "!#$&-^_.+".contains(c))
+}
+
+/// Whether `media_type` is matched by `pattern`: the same name, `type/*`
+/// or `*/*`.
+pub fn matches(pattern: &str, media_type: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some("*") => true,
+        Some(kind) => media_type.split_once('/').is_some_and(|(k, _)| k == kind),
+        None => pattern == media_type,
+    }
+}
+
+/// Recognizes a payload from its first bytes.
+pub fn sniff(head: &[u8]) -> Option<&'static str> {
+    if let Some(format) = ImageFormat::sniff(head) {
+        return Some(format.media_type());
+    }
+    let at = |offset: usize, magic: &[u8]| head.get(offset..offset + magic.len()) == Some(magic);
+    if at(0, b"%PDF-") {
+        Some("application/pdf")
+    } else if at(0, b"GIF87a") || at(0, b"GIF89a") {
+        Some("image/gif")
+    } else if at(0, b"PK\x03\x04") {
+        Some("application/zip")
+    } else if at(0, b"\x1f\x8b") {
+        Some("application/gzip")
+    } else if at(0, b"OggS") {
+        Some("application/ogg")
+    } else if at(0, b"fLaC") {
+        Some("audio/flac")
+    } else if at(0, b"ID3") || at(0, b"\xff\xfb") || at(0, b"\xff\xf3") || at(0, b"\xff\xf2") {
+        Some("audio/mpeg")
+    } else if at(0, b"RIFF") && at(8, b"WAVE") {
+        Some("audio/wav")
+    } else if at(0, b"RIFF") && at(8, b"AVI ") {
+        Some("video/x-msvideo")
+    } else if at(0, b"\x1a\x45\xdf\xa3") {
+        let webm = head[..head.len().min(64)].windows(4).any(|w| w == b"webm");
+        Some(if webm { "video/webm" } else { "video/x-matroska" })
+    } else if at(4, b"ftyp") {
+        match head.get(8..12) {
+            Some(b"qt  ") => Some("video/quicktime"),
+            Some(b"M4A ") => Some("audio/mp4"),
+            Some(b"avif") => Some("image/avif"),
+            _ => Some("video/mp4"),
+        }
+    } else {
+        None
+    }
+}
+
+/// Whether a payload declared as `declared` may be one `sniff()` recognized
+/// as `sniffed`: the same top-level type, or a container format that may
+/// hold anything.
+pub fn agrees(declared: &str, sniffed: &str) -> bool {
+    let kind = |name: &str| name.split_once('/').map_or("", |(kind, _)| kind).to_string();
+    declared == sniffed || CONTAINER_TYPES.contains(&sniffed) || kind(declared) == kind(sniffed)
+}
+
+/// The media type declared in the `content` block of `metadata`, if any.
+pub fn from_metadata(metadata: &str) -> Option<String> {
+    let metadata: Value = serde_json::from_str(metadata).ok()?;
+    normalize(metadata.get("content")?.get("media_type")?.as_str()?)
+}
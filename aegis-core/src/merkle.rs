@@ -0,0 +1,784 @@
+// aegis-core/src/merkle.rs
+
+// A transparency log in the style of Certificate Transparency (RFC 6962):
+// an append-only Merkle tree whose leaves are the SHA-256 of each sealed
+// file, in the order they were sealed. The log's operator signs tree heads,
+// the size and root hash of the tree at a time, in
+// `SignatureContext::TreeHead`; an inclusion proof then shows that a file is
+// among the first `tree_size` leaves of that tree, so anyone holding the
+// file, the proof and a signed head can confirm the operator logged the
+// seal, and could not later deny or reorder it without signing a second,
+// conflicting head. A consistency proof shows that the tree of one size is
+// a prefix of the tree of a later size, so anyone holding two signed heads
+// can confirm the log only grew in between (RFC 9162 section 2.1.4).
+//
+// Leaves are hashed as SHA-256(0x00 || file SHA-256) and interior nodes as
+// SHA-256(0x01 || left || right), so that no leaf can pass for a node. The
+// tree over n leaves splits them at the largest power of two below n.
+//
+// Proofs are embedded in containers (`format::FIELD_LOG_INCLUSION`) at the
+// tree size just after the seal was logged, and are also served for any
+// later size by the sealing service.
+
+use crate::error::AegisError;
+use crate::time::{parse_rfc3339, rfc3339};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::time::{Duration, UNIX_EPOCH};
+#[cfg(feature = "verifier")]
+use {crate::crypto::SignatureContext, p256::ecdsa::{Signature, VerifyingKey}};
+
+/// Most hashes an inclusion or consistency proof may hold: enough for 2^64
+/// leaves.
+pub const MAX_PROOF_LEN: usize = 64;
+
+/// The hash of an empty tree.
+pub fn empty_root() -> [u8; 32] {
+    Sha256::digest([]).into()
+}
+
+/// The leaf hash of a sealed file, given the file's SHA-256.
+pub fn leaf_hash(file_sha256: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([0u8]);
+    hasher.update(file_sha256);
+    hasher.finalize().into()
+}
+
+fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([1u8]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// The largest power of two below `n`, for `n > 1`.
+fn split(n: u64) -> u64 {
+    1 << (63 - (n - 1).leading_zeros())
+}
+
+/// An append-only Merkle tree. Besides the leaf hashes it keeps the hash of
+/// every complete subtree, so roots and proofs for any size take
+/// O(log² n) hashing rather than a pass over every leaf.
+#[derive(Default)]
+pub struct MerkleTree {
+    // levels[k][i] is the hash of leaves i·2^k .. (i+1)·2^k.
+    levels: Vec<Vec<[u8; 32]>>,
+}
+
+impl MerkleTree {
+    pub fn new() -> Self {
+        MerkleTree::default()
+    }
+
+    pub fn len(&self) -> u64 {
+        self.levels.first().map_or(0, |leaves| leaves.len() as u64)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Appends a leaf hash (see `leaf_hash()`), returning its index.
+    pub fn push(&mut self, leaf: [u8; 32]) -> u64 {
+        let index = self.len();
+        let mut hash = leaf;
+        let mut level = 0;
+        loop {
+            if self.levels.len() == level {
+                self.levels.push(Vec::new());
+            }
+            self.levels[level].push(hash);
+            let nodes = &self.levels[level];
+            if nodes.len() % 2 == 1 {
+                break;
+            }
+            hash = node_hash(&nodes[nodes.len() - 2], &nodes[nodes.len() - 1]);
+            level += 1;
+        }
+        index
+    }
+
+    /// The leaf hash at `index`.
+    pub fn leaf(&self, index: u64) -> Option<&[u8; 32]> {
+        self.levels.first()?.get(index as usize)
+    }
+
+    // The hash of the `len` leaves from `start`, where the tree's splits
+    // always leave `start` aligned to the largest power of two below `len`.
+    fn subtree(&self, start: u64, len: u64) -> [u8; 32] {
+        if len.is_power_of_two() && start.is_multiple_of(len) {
+            return self.levels[len.trailing_zeros() as usize][(start / len) as usize];
+        }
+        let k = split(len);
+        node_hash(&self.subtree(start, k), &self.subtree(start + k, len - k))
+    }
+
+    /// The root hash of the tree's first `size` leaves.
+    pub fn root_at(&self, size: u64) -> Result<[u8; 32], AegisError> {
+        if size > self.len() {
+            return Err(AegisError::InvalidProof(format!("the log has only {} entries", self.len())));
+        }
+        Ok(if size == 0 { empty_root() } else { self.subtree(0, size) })
+    }
+
+    /// The proof that leaf `index` is in the tree of the first `size`
+    /// leaves.
+    pub fn inclusion_proof(&self, index: u64, size: u64) -> Result<InclusionProof, AegisError> {
+        if size > self.len() {
+            return Err(AegisError::InvalidProof(format!("the log has only {} entries", self.len())));
+        }
+        if index >= size {
+            return Err(AegisError::InvalidProof(format!("entry {} is not in a log of {} entries", index, size)));
+        }
+        let mut audit_path = Vec::new();
+        self.path(index, 0, size, &mut audit_path);
+        Ok(InclusionProof { leaf_index: index, tree_size: size, audit_path })
+    }
+
+    /// The proof that the tree of the first `old_size` leaves is a prefix of
+    /// the tree of the first `new_size`.
+    pub fn consistency_proof(&self, old_size: u64, new_size: u64) -> Result<ConsistencyProof, AegisError> {
+        if new_size > self.len() {
+            return Err(AegisError::InvalidProof(format!("the log has only {} entries", self.len())));
+        }
+        if old_size > new_size {
+            return Err(AegisError::InvalidProof(format!(
+                "a log of {} entries cannot follow one of {}",
+                new_size, old_size
+            )));
+        }
+        let mut path = Vec::new();
+        if old_size > 0 {
+            self.subproof(old_size, 0, new_size, true, &mut path);
+        }
+        Ok(ConsistencyProof { old_size, new_size, path })
+    }
+
+    // RFC 6962 SUBPROOF(m, D[start:start+n], b), deepest node first.
+    fn subproof(&self, m: u64, start: u64, n: u64, whole: bool, out: &mut Vec<[u8; 32]>) {
+        if m == n {
+            if !whole {
+                out.push(self.subtree(start, n));
+            }
+            return;
+        }
+        let k = split(n);
+        if m <= k {
+            self.subproof(m, start, k, whole, out);
+            out.push(self.subtree(start + k, n - k));
+        } else {
+            self.subproof(m - k, start + k, n - k, false, out);
+            out.push(self.subtree(start, k));
+        }
+    }
+
+    // RFC 6962 PATH(m, D[start:start+n]), deepest sibling first.
+    fn path(&self, m: u64, start: u64, n: u64, out: &mut Vec<[u8; 32]>) {
+        if n <= 1 {
+            return;
+        }
+        let k = split(n);
+        if m < k {
+            self.path(m, start, k, out);
+            out.push(self.subtree(start + k, n - k));
+        } else {
+            self.path(m - k, start + k, n - k, out);
+            out.push(self.subtree(start, k));
+        }
+    }
+}
+
+/// Proof that a leaf is among the first `tree_size` leaves of a log: the
+/// sibling hashes from the leaf up to the root.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "InclusionProofJson", into = "InclusionProofJson")]
+pub struct InclusionProof {
+    pub leaf_index: u64,
+    pub tree_size: u64,
+    pub audit_path: Vec<[u8; 32]>,
+}
+
+// The JSON form: hex hashes.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct InclusionProofJson {
+    leaf_index: u64,
+    tree_size: u64,
+    audit_path: Vec<String>,
+}
+
+fn hash_from_hex(name: &str, value: &str) -> Result<[u8; 32], AegisError> {
+    hex::decode(value)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| AegisError::InvalidProof(format!("{} is not a hex SHA-256 hash", name)))
+}
+
+impl TryFrom<InclusionProofJson> for InclusionProof {
+    type Error = AegisError;
+
+    fn try_from(json: InclusionProofJson) -> Result<Self, AegisError> {
+        if json.audit_path.len() > MAX_PROOF_LEN {
+            return Err(AegisError::InvalidProof("audit path is too long".into()));
+        }
+        Ok(InclusionProof {
+            leaf_index: json.leaf_index,
+            tree_size: json.tree_size,
+            audit_path: json
+                .audit_path
+                .iter()
+                .map(|h| hash_from_hex("audit path entry", h))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl From<InclusionProof> for InclusionProofJson {
+    fn from(proof: InclusionProof) -> Self {
+        InclusionProofJson {
+            leaf_index: proof.leaf_index,
+            tree_size: proof.tree_size,
+            audit_path: proof.audit_path.iter().map(hex::encode).collect(),
+        }
+    }
+}
+
+impl InclusionProof {
+    /// The root hash this proof leads to from `leaf`, per RFC 9162 section
+    /// 2.1.3.2; fails if the path has the wrong length for the leaf's
+    /// position.
+    pub fn root_from(&self, leaf: &[u8; 32]) -> Result<[u8; 32], AegisError> {
+        let invalid = || AegisError::InvalidProof("audit path does not fit the leaf index and tree size".into());
+        if self.leaf_index >= self.tree_size {
+            return Err(invalid());
+        }
+        let (mut f, mut s) = (self.leaf_index, self.tree_size - 1);
+        let mut root = *leaf;
+        for sibling in &self.audit_path {
+            if s == 0 {
+                return Err(invalid());
+            }
+            if f % 2 == 1 || f == s {
+                root = node_hash(sibling, &root);
+                while f % 2 == 0 && f != 0 {
+                    f >>= 1;
+                    s >>= 1;
+                }
+            } else {
+                root = node_hash(&root, sibling);
+            }
+            f >>= 1;
+            s >>= 1;
+        }
+        if s != 0 {
+            return Err(invalid());
+        }
+        Ok(root)
+    }
+}
+
+/// Proof that the tree of the first `old_size` leaves of a log is a prefix
+/// of the tree of its first `new_size`: the hashes RFC 9162 section 2.1.4.1
+/// lists, deepest first.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "ConsistencyProofJson", into = "ConsistencyProofJson")]
+pub struct ConsistencyProof {
+    pub old_size: u64,
+    pub new_size: u64,
+    pub path: Vec<[u8; 32]>,
+}
+
+// The JSON form: hex hashes.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConsistencyProofJson {
+    old_size: u64,
+    new_size: u64,
+    path: Vec<String>,
+}
+
+impl TryFrom<ConsistencyProofJson> for ConsistencyProof {
+    type Error = AegisError;
+
+    fn try_from(json: ConsistencyProofJson) -> Result<Self, AegisError> {
+        if json.path.len() > MAX_PROOF_LEN {
+            return Err(AegisError::InvalidProof("consistency path is too long".into()));
+        }
+        Ok(ConsistencyProof {
+            old_size: json.old_size,
+            new_size: json.new_size,
+            path: json.path.iter().map(|h| hash_from_hex("consistency path entry", h)).collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl From<ConsistencyProof> for ConsistencyProofJson {
+    fn from(proof: ConsistencyProof) -> Self {
+        ConsistencyProofJson {
+            old_size: proof.old_size,
+            new_size: proof.new_size,
+            path: proof.path.iter().map(hex::encode).collect(),
+        }
+    }
+}
+
+impl ConsistencyProof {
+    /// Checks that the tree with root `old_root` at `old_size` leaves grew
+    /// into the one with root `new_root` at `new_size`, per RFC 9162
+    /// section 2.1.4.2. Any tree extends the empty one, and a tree only
+    /// itself at the same size.
+    pub fn verify(&self, old_root: &[u8; 32], new_root: &[u8; 32]) -> Result<(), AegisError> {
+        let invalid = |why: &str| Err(AegisError::InvalidProof(format!("consistency proof {}", why)));
+        if self.old_size > self.new_size || self.path.len() > MAX_PROOF_LEN {
+            return invalid("does not fit the tree sizes");
+        }
+        if self.old_size == 0 || self.old_size == self.new_size {
+            if !self.path.is_empty() {
+                return invalid("does not fit the tree sizes");
+            }
+            let matches = match self.old_size {
+                0 => *old_root == empty_root(),
+                _ => old_root == new_root,
+            };
+            return if matches { Ok(()) } else { invalid("does not lead to the tree heads' roots") };
+        }
+        // A complete old tree is a node of the new one, and its root the
+        // proof's implicit first hash.
+        let mut path = self.path.iter();
+        let first = match self.old_size.is_power_of_two() {
+            true => *old_root,
+            false => match path.next() {
+                Some(hash) => *hash,
+                None => return invalid("does not fit the tree sizes"),
+            },
+        };
+        let (mut f, mut s) = (self.old_size - 1, self.new_size - 1);
+        while f % 2 == 1 {
+            f >>= 1;
+            s >>= 1;
+        }
+        let (mut old, mut new) = (first, first);
+        for hash in path {
+            if s == 0 {
+                return invalid("does not fit the tree sizes");
+            }
+            if f % 2 == 1 || f == s {
+                old = node_hash(hash, &old);
+                new = node_hash(hash, &new);
+                while f % 2 == 0 && f != 0 {
+                    f >>= 1;
+                    s >>= 1;
+                }
+            } else {
+                new = node_hash(&new, hash);
+            }
+            f >>= 1;
+            s >>= 1;
+        }
+        if s != 0 {
+            return invalid("does not fit the tree sizes");
+        }
+        if old != *old_root || new != *new_root {
+            return invalid("does not lead to the tree heads' roots");
+        }
+        Ok(())
+    }
+}
+
+/// The size and root hash of a log at a time, in Unix seconds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TreeHead {
+    pub tree_size: u64,
+    pub root_hash: [u8; 32],
+    pub timestamp: i64,
+}
+
+impl TreeHead {
+    /// What the log operator signs, in `SignatureContext::TreeHead`: the
+    /// tree size as an 8-byte big-endian integer, the root hash, and the
+    /// timestamp as an 8-byte big-endian Unix time.
+    pub fn statement(&self) -> Vec<u8> {
+        let mut object = Vec::with_capacity(8 + 32 + 8);
+        object.extend_from_slice(&self.tree_size.to_be_bytes());
+        object.extend_from_slice(&self.root_hash);
+        object.extend_from_slice(&self.timestamp.to_be_bytes());
+        object
+    }
+}
+
+/// A tree head with the operator's signature and public key.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "SignedTreeHeadJson", into = "SignedTreeHeadJson")]
+pub struct SignedTreeHead {
+    pub head: TreeHead,
+    /// SEC1 public key of the signer.
+    pub public_key: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+// The JSON form: hex hashes, key and signature, an RFC 3339 time.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SignedTreeHeadJson {
+    tree_size: u64,
+    root_hash: String,
+    timestamp: String,
+    public_key: String,
+    signature: String,
+}
+
+impl TryFrom<SignedTreeHeadJson> for SignedTreeHead {
+    type Error = AegisError;
+
+    fn try_from(json: SignedTreeHeadJson) -> Result<Self, AegisError> {
+        let bytes = |name: &str, value: &str| {
+            hex::decode(value).map_err(|_| AegisError::InvalidProof(format!("{} is not hex", name)))
+        };
+        Ok(SignedTreeHead {
+            head: TreeHead {
+                tree_size: json.tree_size,
+                root_hash: hash_from_hex("root_hash", &json.root_hash)?,
+                timestamp: parse_rfc3339(&json.timestamp)
+                    .ok_or_else(|| AegisError::InvalidProof("timestamp is not an RFC 3339 time".into()))?,
+            },
+            public_key: bytes("public_key", &json.public_key)?,
+            signature: bytes("signature", &json.signature)?,
+        })
+    }
+}
+
+impl From<SignedTreeHead> for SignedTreeHeadJson {
+    fn from(signed: SignedTreeHead) -> Self {
+        SignedTreeHeadJson {
+            tree_size: signed.head.tree_size,
+            root_hash: hex::encode(signed.head.root_hash),
+            timestamp: rfc3339(UNIX_EPOCH + Duration::from_secs(signed.head.timestamp.max(0) as u64)),
+            public_key: hex::encode(&signed.public_key),
+            signature: hex::encode(&signed.signature),
+        }
+    }
+}
+
+#[cfg(feature = "verifier")]
+impl SignedTreeHead {
+    /// Whether the signature is by `public_key` over the head.
+    pub fn signature_valid(&self) -> bool {
+        let Ok(key) = VerifyingKey::from_sec1_bytes(&self.public_key) else {
+            return false;
+        };
+        let Ok(signature) = Signature::from_slice(&self.signature) else {
+            return false;
+        };
+        SignatureContext::TreeHead
+            .verify(&key, &self.head.statement(), &signature)
+            .unwrap_or(false)
+    }
+}
+
+/// Confirms that the file with SHA-256 `file_sha256` was logged by the
+/// holder of `log_key` (SEC1) among the first `head.tree_size` entries:
+/// the head must be signed by that key, the proof must be for the head's
+/// size, and it must lead from the file's leaf to the head's root.
+#[cfg(feature = "verifier")]
+pub fn verify_inclusion(
+    file_sha256: &[u8; 32],
+    proof: &InclusionProof,
+    head: &SignedTreeHead,
+    log_key: &[u8],
+) -> Result<(), AegisError> {
+    if head.public_key != log_key {
+        return Err(AegisError::InvalidProof("the tree head is signed by a different key".into()));
+    }
+    if !head.signature_valid() {
+        return Err(AegisError::InvalidProof("the tree head signature is invalid".into()));
+    }
+    if proof.tree_size != head.head.tree_size {
+        return Err(AegisError::InvalidProof(format!(
+            "the proof is for a log of {} entries, the tree head for {}",
+            proof.tree_size, head.head.tree_size
+        )));
+    }
+    if proof.root_from(&leaf_hash(file_sha256))? != head.head.root_hash {
+        return Err(AegisError::InvalidProof("the proof does not lead to the tree head's root".into()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The leaves and expected hashes of the Certificate Transparency
+    // reference tests, as RFC 6962 defines the tree; leaf data here is
+    // arbitrary rather than a file's SHA-256, so the leaf hash is taken
+    // directly.
+    const LEAVES: [&str; 8] = [
+        "",
+        "00",
+        "10",
+        "2021",
+        "3031",
+        "40414243",
+        "5051525354555657",
+        "606162636465666768696a6b6c6d6e6f",
+    ];
+    const ROOTS: [&str; 8] = [
+        "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d",
+        "fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125",
+        "aeb6bcfe274b70a14fb067a5e5578264db0fa9b51af5e0ba159158f329e06e77",
+        "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+        "4e3bbb1f7b478dcfe71fb631631519a3bca12c9aefca1612bfce4c13a86264d4",
+        "76e67dadbcdf1e10e1b74ddc608abd2f98dfb16fbce75277b5232a127f2087ef",
+        "ddb89be403809e325750d3d263cd78929c2942b7942a34b77e122c9594a74c8c",
+        "5dc9da79a70659a9ad559cb701ded9a2ab9d823aad2f4960cfe370eff4604328",
+    ];
+    // (leaf index, tree size, audit path)
+    const INCLUSION: [(u64, u64, &[&str]); 4] = [
+        (
+            0,
+            8,
+            &[
+                "96a296d224f285c67bee93c30f8a309157f0daa35dc5b87e410b78630a09cfc7",
+                "5f083f0a1a33ca076a95279832580db3e0ef4584bdff1f54c8a360f50de3031e",
+                "6b47aaf29ee3c2af9af889bc1fb9254dabd31177f16232dd6aab035ca39bf6e4",
+            ],
+        ),
+        (
+            5,
+            8,
+            &[
+                "bc1a0643b12e4d2d7c77918f44e0f4f79a838b6cf9ec5b5c283e1f4d88599e6b",
+                "ca854ea128ed050b41b35ffc1b87b8eb2bde461e9e3b5596ece6b9d5975a0ae0",
+                "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+            ],
+        ),
+        (2, 3, &["fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125"]),
+        (
+            1,
+            5,
+            &[
+                "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d",
+                "5f083f0a1a33ca076a95279832580db3e0ef4584bdff1f54c8a360f50de3031e",
+                "bc1a0643b12e4d2d7c77918f44e0f4f79a838b6cf9ec5b5c283e1f4d88599e6b",
+            ],
+        ),
+    ];
+    // (old size, new size, path)
+    const CONSISTENCY: [(u64, u64, &[&str]); 3] = [
+        (
+            1,
+            8,
+            &[
+                "96a296d224f285c67bee93c30f8a309157f0daa35dc5b87e410b78630a09cfc7",
+                "5f083f0a1a33ca076a95279832580db3e0ef4584bdff1f54c8a360f50de3031e",
+                "6b47aaf29ee3c2af9af889bc1fb9254dabd31177f16232dd6aab035ca39bf6e4",
+            ],
+        ),
+        (
+            6,
+            8,
+            &[
+                "0ebc5d3437fbe2db158b9f126a1d118e308181031d0a949f8dededebc558ef6a",
+                "ca854ea128ed050b41b35ffc1b87b8eb2bde461e9e3b5596ece6b9d5975a0ae0",
+                "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+            ],
+        ),
+        (
+            2,
+            5,
+            &[
+                "5f083f0a1a33ca076a95279832580db3e0ef4584bdff1f54c8a360f50de3031e",
+                "bc1a0643b12e4d2d7c77918f44e0f4f79a838b6cf9ec5b5c283e1f4d88599e6b",
+            ],
+        ),
+    ];
+
+    fn tree() -> MerkleTree {
+        let mut tree = MerkleTree::new();
+        for leaf in LEAVES {
+            let mut hasher = Sha256::new();
+            hasher.update([0u8]);
+            hasher.update(hex::decode(leaf).unwrap());
+            tree.push(hasher.finalize().into());
+        }
+        tree
+    }
+
+    fn hashes(hex_hashes: &[&str]) -> Vec<[u8; 32]> {
+        hex_hashes.iter().map(|h| hash_from_hex("test", h).unwrap()).collect()
+    }
+
+    fn root(tree: &MerkleTree, size: u64) -> [u8; 32] {
+        tree.root_at(size).unwrap()
+    }
+
+    #[test]
+    fn computes_the_reference_roots() {
+        let tree = tree();
+        assert_eq!(root(&tree, 0), empty_root());
+        for (size, expected) in (1..=8).zip(ROOTS) {
+            assert_eq!(hex::encode(root(&tree, size)), expected, "size {}", size);
+        }
+        assert!(tree.root_at(9).is_err());
+    }
+
+    #[test]
+    fn matches_the_reference_inclusion_proofs() {
+        let tree = tree();
+        for (index, size, path) in INCLUSION {
+            let proof = tree.inclusion_proof(index, size).unwrap();
+            assert_eq!(proof.audit_path, hashes(path), "leaf {} of {}", index, size);
+            assert_eq!(proof.root_from(tree.leaf(index).unwrap()).unwrap(), root(&tree, size));
+        }
+    }
+
+    #[test]
+    fn proves_every_leaf_of_every_size() {
+        let tree = tree();
+        for size in 1..=8 {
+            for index in 0..size {
+                let proof = tree.inclusion_proof(index, size).unwrap();
+                assert_eq!(proof.root_from(tree.leaf(index).unwrap()).unwrap(), root(&tree, size));
+            }
+        }
+        assert!(tree.inclusion_proof(3, 3).is_err());
+        assert!(tree.inclusion_proof(0, 9).is_err());
+    }
+
+    #[test]
+    fn rejects_tampered_inclusion_proofs() {
+        let tree = tree();
+        let proof = tree.inclusion_proof(5, 8).unwrap();
+        let leaf = *tree.leaf(5).unwrap();
+        let expected = root(&tree, 8);
+        for i in 0..proof.audit_path.len() {
+            let mut tampered = proof.clone();
+            tampered.audit_path[i][0] ^= 1;
+            assert_ne!(tampered.root_from(&leaf).unwrap(), expected);
+        }
+        // Another leaf, index or size, or a path too short or too long.
+        assert_ne!(proof.root_from(tree.leaf(4).unwrap()).unwrap(), expected);
+        let moved = InclusionProof { leaf_index: 4, ..proof.clone() };
+        assert_ne!(moved.root_from(&leaf).unwrap(), expected);
+        let resized = InclusionProof { tree_size: 6, ..proof.clone() };
+        assert!(resized.root_from(&leaf).is_err());
+        let mut short = proof.clone();
+        short.audit_path.pop();
+        assert!(short.root_from(&leaf).is_err());
+        let mut long = proof.clone();
+        long.audit_path.push(expected);
+        assert!(long.root_from(&leaf).is_err());
+        let outside = InclusionProof { leaf_index: 8, ..proof };
+        assert!(outside.root_from(&leaf).is_err());
+    }
+
+    #[test]
+    fn matches_the_reference_consistency_proofs() {
+        let tree = tree();
+        for (old, new, path) in CONSISTENCY {
+            let proof = tree.consistency_proof(old, new).unwrap();
+            assert_eq!(proof.path, hashes(path), "{} to {}", old, new);
+            proof.verify(&root(&tree, old), &root(&tree, new)).unwrap();
+        }
+    }
+
+    #[test]
+    fn proves_consistency_between_every_pair_of_sizes() {
+        let tree = tree();
+        for new in 0..=8 {
+            for old in 0..=new {
+                let proof = tree.consistency_proof(old, new).unwrap();
+                proof.verify(&root(&tree, old), &root(&tree, new)).unwrap();
+            }
+        }
+        assert!(tree.consistency_proof(5, 4).is_err());
+        assert!(tree.consistency_proof(1, 9).is_err());
+    }
+
+    #[test]
+    fn rejects_tampered_consistency_proofs() {
+        let tree = tree();
+        let (old_root, new_root) = (root(&tree, 6), root(&tree, 8));
+        let proof = tree.consistency_proof(6, 8).unwrap();
+        for i in 0..proof.path.len() {
+            let mut tampered = proof.clone();
+            tampered.path[i][0] ^= 1;
+            assert!(tampered.verify(&old_root, &new_root).is_err());
+        }
+        // Other roots, as a log that rewrote its history would have.
+        assert!(proof.verify(&root(&tree, 5), &new_root).is_err());
+        assert!(proof.verify(&old_root, &root(&tree, 7)).is_err());
+        // Other sizes, or a path too short or too long. (Sizes whose proofs
+        // take the same shape, such as 6 to 7, are told apart only by the
+        // signed heads the roots come from.)
+        for (old_size, new_size) in [(5, 8), (7, 8), (6, 5), (8, 6)] {
+            let resized = ConsistencyProof { old_size, new_size, ..proof.clone() };
+            assert!(resized.verify(&old_root, &new_root).is_err(), "{} to {}", old_size, new_size);
+        }
+        let mut short = proof.clone();
+        short.path.pop();
+        assert!(short.verify(&old_root, &new_root).is_err());
+        let mut long = proof.clone();
+        long.path.push(new_root);
+        assert!(long.verify(&old_root, &new_root).is_err());
+        // Trivial proofs carry no path and need matching roots.
+        let same = tree.consistency_proof(8, 8).unwrap();
+        assert!(same.path.is_empty());
+        assert!(same.verify(&new_root, &new_root).is_ok());
+        assert!(same.verify(&old_root, &new_root).is_err());
+        let empty = tree.consistency_proof(0, 8).unwrap();
+        assert!(empty.verify(&empty_root(), &new_root).is_ok());
+        assert!(empty.verify(&old_root, &new_root).is_err());
+    }
+
+    #[cfg(all(feature = "sealer", feature = "verifier"))]
+    #[test]
+    fn verifies_inclusion_against_a_signed_head() {
+        use crate::crypto::SignatureContext;
+        use crate::test_util::test_signing_key;
+
+        // A log of real files: the leaves are their SHA-256s.
+        let files: Vec<[u8; 32]> =
+            LEAVES.iter().map(|data| Sha256::digest(hex::decode(data).unwrap()).into()).collect();
+        let mut tree = MerkleTree::new();
+        for file in &files {
+            tree.push(leaf_hash(file));
+        }
+        let key = test_signing_key(1);
+        let log_key = key.verifying_key().to_sec1_bytes().to_vec();
+        let sign = |head: TreeHead| {
+            let signature = SignatureContext::TreeHead.sign(&head.statement(), &key).unwrap();
+            SignedTreeHead { head, public_key: log_key.clone(), signature: signature.to_bytes().to_vec() }
+        };
+        let head = sign(TreeHead { tree_size: 8, root_hash: root(&tree, 8), timestamp: 1_700_000_000 });
+        let proof = tree.inclusion_proof(5, 8).unwrap();
+        verify_inclusion(&files[5], &proof, &head, &log_key).unwrap();
+
+        assert!(verify_inclusion(&files[4], &proof, &head, &log_key).is_err());
+        let other_key = test_signing_key(2).verifying_key().to_sec1_bytes().to_vec();
+        assert!(verify_inclusion(&files[5], &proof, &head, &other_key).is_err());
+        let mut forged = head.clone();
+        forged.head.timestamp += 1;
+        assert!(verify_inclusion(&files[5], &proof, &forged, &log_key).is_err());
+        let smaller = sign(TreeHead { tree_size: 7, root_hash: root(&tree, 7), timestamp: 1_700_000_000 });
+        assert!(verify_inclusion(&files[5], &proof, &smaller, &log_key).is_err());
+        let proof_at_7 = tree.inclusion_proof(5, 7).unwrap();
+        verify_inclusion(&files[5], &proof_at_7, &smaller, &log_key).unwrap();
+    }
+
+    #[test]
+    fn round_trips_proofs_through_json() {
+        let tree = tree();
+        let inclusion = tree.inclusion_proof(5, 8).unwrap();
+        let json = serde_json::to_string(&inclusion).unwrap();
+        assert_eq!(serde_json::from_str::<InclusionProof>(&json).unwrap(), inclusion);
+        let consistency = tree.consistency_proof(6, 8).unwrap();
+        let json = serde_json::to_string(&consistency).unwrap();
+        assert_eq!(serde_json::from_str::<ConsistencyProof>(&json).unwrap(), consistency);
+        let path = vec![format!("\"{}\"", ROOTS[0]); MAX_PROOF_LEN + 1].join(",");
+        let long = format!(r#"{{"old_size":1,"new_size":2,"path":[{}]}}"#, path);
+        assert!(serde_json::from_str::<ConsistencyProof>(&long).is_err());
+    }
+}
@@ -0,0 +1,259 @@
+// aegis-core/src/metadata.rs
+
+// The structured metadata schema. The metadata block is still a free-form
+// string, so older containers and clients that send plain text keep working,
+// but a JSON object is read as `Metadata`:
+//
+//     {"creator": "Jane Doe", "captured_at": "2025-01-01T12:00:00Z",
+//      "device": {"make": "Canon", "model": "EOS R5", "serial": "0123"},
+//      "gps": {"latitude": 48.8584, "longitude": 2.2945, "altitude": 35.0},
+//      "project": "...", ...}
+//
+// Every field is optional. Any other top-level key is a custom field, kept
+// as it is (the service adds `submission` and `embedded` this way). Sealers
+// store the canonical form from `to_canonical_json()`: compact, with object
+// keys sorted at every level, so two sealers write the same bytes for the
+// same document.
+//
+// Captions and titles in several languages go under `languages`, keyed by
+// BCP 47 tag, with `default_language` naming the original:
+//
+//     {"default_language": "en",
+//      "languages": {"en": {"caption": "Flooding in the old town"},
+//                    "fr": {"caption": "Inondations dans la vieille ville"}}}
+//
+// Tags are stored in their canonical case (`fr-CA`, `zh-Hant-TW`). All
+// variants sit in the one signed document, so a reader shown a single
+// language (`Metadata::language_view()`) can be told the digest of every
+// variant signed with it.
+
+use crate::error::AegisError;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub creator: Option<String>,
+    /// When the image was captured, as an RFC 3339 timestamp.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub captured_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<Device>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gps: Option<Gps>,
+    /// Labels in each language, keyed by BCP 47 tag.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub languages: BTreeMap<String, Labels>,
+    /// The language the labels were written in, one of `languages`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_language: Option<String>,
+    /// Every other top-level key.
+    #[serde(flatten)]
+    pub custom: Map<String, Value>,
+}
+
+/// The capture device.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Device {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub make: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serial: Option<String>,
+}
+
+/// Where the image was captured, in WGS 84 degrees and metres above sea level.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Gps {
+    pub latitude: f64,
+    pub longitude: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub altitude: Option<f64>,
+}
+
+/// The labels of an image in one language. Any other key is kept as a
+/// custom label.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Labels {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub caption: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keywords: Vec<String>,
+    #[serde(flatten)]
+    pub custom: Map<String, Value>,
+}
+
+impl Labels {
+    /// Hex SHA-256 of the labels' canonical JSON.
+    pub fn digest(&self) -> String {
+        let canonical = serde_json::to_value(self).expect("labels serialize to JSON").to_string();
+        hex::encode(Sha256::digest(canonical))
+    }
+}
+
+/// A BCP 47 language tag in canonical case: the language lowercase, a
+/// script in title case, a region uppercase. `None` if `tag` is not
+/// well-formed.
+pub fn canonical_language_tag(tag: &str) -> Option<String> {
+    let mut subtags = Vec::new();
+    for (i, subtag) in tag.split(['-', '_']).enumerate() {
+        if subtag.is_empty() || subtag.len() > 8 || !subtag.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return None;
+        }
+        let lower = subtag.to_ascii_lowercase();
+        subtags.push(match (i, subtag.len()) {
+            (0, _) if !subtag.chars().all(|c| c.is_ascii_alphabetic()) || subtag.len() < 2 => return None,
+            (0, _) => lower,
+            (_, 4) if subtag.chars().all(|c| c.is_ascii_alphabetic()) => {
+                lower[..1].to_ascii_uppercase() + &lower[1..]
+            }
+            (_, 2) if subtag.chars().all(|c| c.is_ascii_alphabetic()) => lower.to_ascii_uppercase(),
+            _ => lower,
+        });
+    }
+    Some(subtags.join("-"))
+}
+
+impl Metadata {
+    /// Parses and validates a metadata document. Fails for anything but a
+    /// JSON object matching the schema.
+    pub fn parse(metadata: &str) -> Result<Self, AegisError> {
+        let value: Value = serde_json::from_str(metadata)
+            .map_err(|e| AegisError::InvalidMetadata(format!("not JSON: {}", e)))?;
+        if !value.is_object() {
+            return Err(AegisError::InvalidMetadata("not a JSON object".into()));
+        }
+        let mut metadata: Metadata =
+            serde_json::from_value(value).map_err(|e| AegisError::InvalidMetadata(e.to_string()))?;
+        metadata.canonicalize_languages()?;
+        metadata.validate()?;
+        Ok(metadata)
+    }
+
+    /// Like `parse()`, but `None` for metadata that is not a JSON object, so
+    /// free-form metadata can be told apart from a malformed document.
+    pub fn parse_structured(metadata: &str) -> Result<Option<Self>, AegisError> {
+        match serde_json::from_str::<Value>(metadata) {
+            Ok(Value::Object(_)) => Self::parse(metadata).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    // Rewrites language tags in canonical case, rejecting malformed tags
+    // and tags that differ only in case.
+    fn canonicalize_languages(&mut self) -> Result<(), AegisError> {
+        let canonical = |tag: &str| {
+            canonical_language_tag(tag)
+                .ok_or_else(|| AegisError::InvalidMetadata(format!("'{}' is not a BCP 47 language tag", tag)))
+        };
+        let mut languages = BTreeMap::new();
+        for (tag, labels) in std::mem::take(&mut self.languages) {
+            let tag = canonical(&tag)?;
+            if languages.contains_key(&tag) {
+                return Err(AegisError::InvalidMetadata(format!("language '{}' is given twice", tag)));
+            }
+            languages.insert(tag, labels);
+        }
+        self.languages = languages;
+        if let Some(default) = &self.default_language {
+            let default = canonical(default)?;
+            if !self.languages.contains_key(&default) {
+                return Err(AegisError::InvalidMetadata(format!(
+                    "default_language '{}' has no labels in languages",
+                    default
+                )));
+            }
+            self.default_language = Some(default);
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), AegisError> {
+        if let Some(captured_at) = &self.captured_at
+            && crate::time::parse_rfc3339(captured_at).is_none()
+        {
+            return Err(AegisError::InvalidMetadata(format!(
+                "captured_at '{}' is not an RFC 3339 timestamp",
+                captured_at
+            )));
+        }
+        if let Some(gps) = &self.gps {
+            if !(-90.0..=90.0).contains(&gps.latitude) {
+                return Err(AegisError::InvalidMetadata(format!("gps.latitude {} is out of range", gps.latitude)));
+            }
+            if !(-180.0..=180.0).contains(&gps.longitude) {
+                return Err(AegisError::InvalidMetadata(format!("gps.longitude {} is out of range", gps.longitude)));
+            }
+        }
+        Ok(())
+    }
+
+    /// The capture time in Unix seconds.
+    pub fn captured_at_unix(&self) -> Option<i64> {
+        crate::time::parse_rfc3339(self.captured_at.as_deref()?)
+    }
+
+    pub fn custom_field(&self, name: &str) -> Option<&Value> {
+        self.custom.get(name)
+    }
+
+    /// The tag of the variant best matching `requested`: the same tag, then
+    /// the nearest variant sharing its leading subtags (`fr` for `fr-CA`,
+    /// `fr-CA` for `fr`), then `default_language`, then the first tag.
+    pub fn negotiate_language(&self, requested: &str) -> Option<&str> {
+        let requested = canonical_language_tag(requested)?;
+        if let Some((tag, _)) = self.languages.get_key_value(&requested) {
+            return Some(tag);
+        }
+        let mut prefix = requested.as_str();
+        loop {
+            if let Some((tag, _)) = self.languages.get_key_value(prefix) {
+                return Some(tag);
+            }
+            if let Some(tag) = self.languages.keys().find(|tag| tag.starts_with(&format!("{}-", prefix))) {
+                return Some(tag);
+            }
+            match prefix.rsplit_once('-') {
+                Some((shorter, _)) => prefix = shorter,
+                None => break,
+            }
+        }
+        self.default_language
+            .as_deref()
+            .or_else(|| self.languages.keys().next().map(String::as_str))
+    }
+
+    /// The labels in the language best matching `requested` (see
+    /// `negotiate_language()`), along with the digest of every variant, all
+    /// of which the container signature covers. `None` if the document has
+    /// no labels.
+    pub fn language_view(&self, requested: &str) -> Option<Value> {
+        let tag = self.negotiate_language(requested)?;
+        Some(json!({
+            "requested": requested,
+            "language": tag,
+            "default_language": self.default_language,
+            "labels": self.languages[tag],
+            "variants": self
+                .languages
+                .iter()
+                .map(|(tag, labels)| (tag.clone(), json!(labels.digest())))
+                .collect::<Map<String, Value>>(),
+        }))
+    }
+
+    /// The compact, key-sorted JSON that sealers store.
+    pub fn to_canonical_json(&self) -> String {
+        // `Value` objects keep their keys sorted, which also orders the
+        // flattened custom fields among the schema's own.
+        serde_json::to_value(self).expect("metadata serializes to JSON").to_string()
+    }
+}
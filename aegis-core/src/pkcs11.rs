@@ -0,0 +1,428 @@
+// aegis-core/src/pkcs11.rs
+
+// Signing with a P-256 key held on a PKCS#11 token: a YubiKey through
+// Yubico's `libykcs11`, a smart card through OpenSC, SoftHSM, a network
+// HSM's client library. The module is loaded at run time, so nothing here
+// links against a vendor library; `Pkcs11Signer` logs in to one slot and
+// signs with `CKM_ECDSA` over the SHA-256 of the message, exactly like
+// `SigningKey`.
+//
+// Only the handful of Cryptoki functions needed for that are declared. The
+// module is initialized without locking callbacks, so every call goes
+// through one mutex.
+
+use crate::error::AegisError;
+use p256::ecdsa::{
+    signature::{Error as SignatureError, Keypair, Signer},
+    Signature, VerifyingKey,
+};
+use sha2::{Digest, Sha256};
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::c_ulong;
+use std::path::Path;
+use std::ptr;
+use std::sync::Mutex;
+
+type CkUlong = c_ulong;
+type CkRv = CkUlong;
+type CkFn = Option<unsafe extern "C" fn()>;
+
+const CKR_OK: CkRv = 0;
+const CKR_USER_ALREADY_LOGGED_IN: CkRv = 0x100;
+const CKR_CRYPTOKI_ALREADY_INITIALIZED: CkRv = 0x191;
+const CKF_SERIAL_SESSION: CkUlong = 0x4;
+const CKU_USER: CkUlong = 1;
+const CKO_PUBLIC_KEY: CkUlong = 2;
+const CKO_PRIVATE_KEY: CkUlong = 3;
+const CKK_EC: CkUlong = 3;
+const CKA_CLASS: CkUlong = 0x0;
+const CKA_LABEL: CkUlong = 0x3;
+const CKA_KEY_TYPE: CkUlong = 0x100;
+const CKA_ID: CkUlong = 0x102;
+const CKA_EC_POINT: CkUlong = 0x181;
+const CKM_ECDSA: CkUlong = 0x1041;
+
+#[repr(C)]
+struct CkVersion {
+    major: u8,
+    minor: u8,
+}
+
+#[repr(C)]
+struct CkAttribute {
+    kind: CkUlong,
+    value: *mut c_void,
+    len: CkUlong,
+}
+
+#[repr(C)]
+struct CkMechanism {
+    mechanism: CkUlong,
+    parameter: *mut c_void,
+    len: CkUlong,
+}
+
+// The start of CK_FUNCTION_LIST, up to C_Sign. Entries we never call are
+// left untyped; the order is fixed by the standard.
+#[repr(C)]
+struct FunctionList {
+    _version: CkVersion,
+    initialize: unsafe extern "C" fn(*mut c_void) -> CkRv,
+    finalize: unsafe extern "C" fn(*mut c_void) -> CkRv,
+    _get_info: CkFn,
+    _get_function_list: CkFn,
+    get_slot_list: unsafe extern "C" fn(u8, *mut CkUlong, *mut CkUlong) -> CkRv,
+    _get_slot_info: CkFn,
+    _get_token_info: CkFn,
+    _get_mechanism_list: CkFn,
+    _get_mechanism_info: CkFn,
+    _init_token: CkFn,
+    _init_pin: CkFn,
+    _set_pin: CkFn,
+    open_session: unsafe extern "C" fn(CkUlong, CkUlong, *mut c_void, *mut c_void, *mut CkUlong) -> CkRv,
+    close_session: unsafe extern "C" fn(CkUlong) -> CkRv,
+    _close_all_sessions: CkFn,
+    _get_session_info: CkFn,
+    _get_operation_state: CkFn,
+    _set_operation_state: CkFn,
+    login: unsafe extern "C" fn(CkUlong, CkUlong, *const u8, CkUlong) -> CkRv,
+    logout: unsafe extern "C" fn(CkUlong) -> CkRv,
+    _create_object: CkFn,
+    _copy_object: CkFn,
+    _destroy_object: CkFn,
+    _get_object_size: CkFn,
+    get_attribute_value: unsafe extern "C" fn(CkUlong, CkUlong, *mut CkAttribute, CkUlong) -> CkRv,
+    _set_attribute_value: CkFn,
+    find_objects_init: unsafe extern "C" fn(CkUlong, *mut CkAttribute, CkUlong) -> CkRv,
+    find_objects: unsafe extern "C" fn(CkUlong, *mut CkUlong, CkUlong, *mut CkUlong) -> CkRv,
+    find_objects_final: unsafe extern "C" fn(CkUlong) -> CkRv,
+    _encrypt_init: CkFn,
+    _encrypt: CkFn,
+    _encrypt_update: CkFn,
+    _encrypt_final: CkFn,
+    _decrypt_init: CkFn,
+    _decrypt: CkFn,
+    _decrypt_update: CkFn,
+    _decrypt_final: CkFn,
+    _digest_init: CkFn,
+    _digest: CkFn,
+    _digest_update: CkFn,
+    _digest_key: CkFn,
+    _digest_final: CkFn,
+    sign_init: unsafe extern "C" fn(CkUlong, *mut CkMechanism, CkUlong) -> CkRv,
+    sign: unsafe extern "C" fn(CkUlong, *const u8, CkUlong, *mut u8, *mut CkUlong) -> CkRv,
+}
+
+fn pkcs11_error(msg: impl Into<String>) -> AegisError {
+    AegisError::Crypto(format!("pkcs11: {}", msg.into()))
+}
+
+// Turns a return value into an error naming the call, spelling out the
+// codes a user can act on.
+fn check(call: &str, rv: CkRv) -> Result<(), AegisError> {
+    let reason = match rv {
+        CKR_OK => return Ok(()),
+        0x03 => "no such slot",
+        0x30 => "device error",
+        0x32 => "token removed",
+        0x60 => "key handle invalid",
+        0x70 => "token does not support ECDSA",
+        0xA0 => "PIN incorrect",
+        0xA2 => "PIN has the wrong length",
+        0xA4 => "PIN locked",
+        0xB3 => "session closed",
+        0xE0 => "token not present",
+        0x101 => "not logged in",
+        _ => return Err(pkcs11_error(format!("{} failed with CKR 0x{:X}", call, rv))),
+    };
+    Err(pkcs11_error(format!("{}: {}", call, reason)))
+}
+
+/// Which private key on the token to sign with.
+pub enum KeySelector {
+    /// `CKA_LABEL`, e.g. `Private key for Digital Signature` on a YubiKey.
+    Label(String),
+    /// `CKA_ID`, e.g. `[0x02]` for the YubiKey PIV slot 9c.
+    Id(Vec<u8>),
+}
+
+struct Session {
+    functions: *const FunctionList,
+    library: *mut c_void,
+    /// Whether we initialized the module, and so must finalize it.
+    initialized: bool,
+    handle: CkUlong,
+}
+
+// The raw pointers are only dereferenced with the mutex in `Pkcs11Signer`
+// held.
+unsafe impl Send for Session {}
+
+/// A container signer backed by a P-256 key on a PKCS#11 token. Each
+/// signature is made on the token; the private key never leaves it.
+pub struct Pkcs11Signer {
+    session: Mutex<Session>,
+    key: CkUlong,
+    public_key: VerifyingKey,
+}
+
+impl Pkcs11Signer {
+    /// Loads `module` (e.g. `/usr/lib/libykcs11.so`), opens `slot` (the
+    /// first slot with a token present if `None`), logs in with `pin` and
+    /// finds the key. The public key is read from the token's matching
+    /// public key object (same `CKA_ID`).
+    pub fn open(module: &Path, slot: Option<u64>, pin: &str, key: &KeySelector) -> Result<Self, AegisError> {
+        let path = CString::new(module.as_os_str().as_encoded_bytes())
+            .map_err(|_| pkcs11_error("module path contains a NUL byte"))?;
+        // SAFETY: dlopen and dlsym are given NUL-terminated strings, and the
+        // symbol is C_GetFunctionList, whose signature is fixed by PKCS#11.
+        let mut session = unsafe {
+            let library = libc::dlopen(path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL);
+            if library.is_null() {
+                let reason = libc::dlerror();
+                let reason = match reason.is_null() {
+                    true => "unknown error".into(),
+                    false => CStr::from_ptr(reason).to_string_lossy(),
+                };
+                return Err(pkcs11_error(format!("cannot load module {}: {}", module.display(), reason)));
+            }
+            let symbol = libc::dlsym(library, c"C_GetFunctionList".as_ptr());
+            if symbol.is_null() {
+                libc::dlclose(library);
+                return Err(pkcs11_error(format!("{} is not a PKCS#11 module", module.display())));
+            }
+            let get_function_list: unsafe extern "C" fn(*mut *const FunctionList) -> CkRv =
+                std::mem::transmute(symbol);
+            let mut functions: *const FunctionList = ptr::null();
+            let rv = get_function_list(&mut functions);
+            if rv != CKR_OK || functions.is_null() {
+                libc::dlclose(library);
+                return Err(pkcs11_error("C_GetFunctionList failed"));
+            }
+            let rv = ((*functions).initialize)(ptr::null_mut());
+            let initialized = rv == CKR_OK;
+            if !initialized && rv != CKR_CRYPTOKI_ALREADY_INITIALIZED {
+                libc::dlclose(library);
+                return Err(check("C_Initialize", rv).expect_err("rv is not CKR_OK"));
+            }
+            Session {
+                functions,
+                library,
+                initialized,
+                handle: 0,
+            }
+        };
+        // From here on, dropping `session` closes whatever has been opened.
+        let slot = match slot {
+            Some(slot) => slot as CkUlong,
+            None => session.first_slot()?,
+        };
+        session.open(slot)?;
+        session.login(pin)?;
+        let (key, id) = session.find_private_key(key)?;
+        let public_key = session.public_key(&id)?;
+        Ok(Pkcs11Signer {
+            session: Mutex::new(session),
+            key,
+            public_key,
+        })
+    }
+
+    fn sign_digest(&self, digest: &[u8; 32]) -> Result<Signature, AegisError> {
+        let session = self.session.lock().map_err(|_| pkcs11_error("session lock poisoned"))?;
+        let mut mechanism = CkMechanism {
+            mechanism: CKM_ECDSA,
+            parameter: ptr::null_mut(),
+            len: 0,
+        };
+        let mut raw = [0u8; 64];
+        let mut len = raw.len() as CkUlong;
+        // SAFETY: the session and key handles are valid while the session
+        // is open, and `raw` is as long as `len` says.
+        unsafe {
+            let f = &*session.functions;
+            check("C_SignInit", (f.sign_init)(session.handle, &mut mechanism, self.key))?;
+            check(
+                "C_Sign",
+                (f.sign)(session.handle, digest.as_ptr(), digest.len() as CkUlong, raw.as_mut_ptr(), &mut len),
+            )?;
+        }
+        // CKM_ECDSA returns r || s, each as long as the curve order.
+        if len != 64 {
+            return Err(pkcs11_error(format!("token returned a {}-byte signature", len)));
+        }
+        let signature = Signature::from_slice(&raw).map_err(|e| pkcs11_error(e.to_string()))?;
+        Ok(signature.normalize_s().unwrap_or(signature))
+    }
+}
+
+impl Session {
+    fn first_slot(&self) -> Result<CkUlong, AegisError> {
+        let mut slots = [0 as CkUlong; 16];
+        let mut count = slots.len() as CkUlong;
+        // SAFETY: `slots` has room for `count` entries.
+        unsafe {
+            check(
+                "C_GetSlotList",
+                ((*self.functions).get_slot_list)(1, slots.as_mut_ptr(), &mut count),
+            )?;
+        }
+        match count {
+            0 => Err(pkcs11_error("no slot has a token present")),
+            _ => Ok(slots[0]),
+        }
+    }
+
+    fn open(&mut self, slot: CkUlong) -> Result<(), AegisError> {
+        // SAFETY: the output pointer is valid; no notification callback.
+        unsafe {
+            check(
+                "C_OpenSession",
+                ((*self.functions).open_session)(
+                    slot,
+                    CKF_SERIAL_SESSION,
+                    ptr::null_mut(),
+                    ptr::null_mut(),
+                    &mut self.handle,
+                ),
+            )
+        }
+    }
+
+    fn login(&self, pin: &str) -> Result<(), AegisError> {
+        // SAFETY: the PIN pointer and length describe one live buffer.
+        let rv = unsafe { ((*self.functions).login)(self.handle, CKU_USER, pin.as_ptr(), pin.len() as CkUlong) };
+        match rv {
+            CKR_USER_ALREADY_LOGGED_IN => Ok(()),
+            rv => check("C_Login", rv),
+        }
+    }
+
+    // The one object matching `template`.
+    fn find(&self, template: &mut [CkAttribute], what: &str) -> Result<CkUlong, AegisError> {
+        let mut objects = [0 as CkUlong; 2];
+        let mut count: CkUlong = 0;
+        // SAFETY: every template entry points at a live value of its length,
+        // and `objects` has room for the two handles asked for.
+        unsafe {
+            let f = &*self.functions;
+            check(
+                "C_FindObjectsInit",
+                (f.find_objects_init)(self.handle, template.as_mut_ptr(), template.len() as CkUlong),
+            )?;
+            let rv = (f.find_objects)(self.handle, objects.as_mut_ptr(), objects.len() as CkUlong, &mut count);
+            (f.find_objects_final)(self.handle);
+            check("C_FindObjects", rv)?;
+        }
+        match count {
+            0 => Err(pkcs11_error(format!("no {} found on the token", what))),
+            1 => Ok(objects[0]),
+            _ => Err(pkcs11_error(format!("more than one {} matches", what))),
+        }
+    }
+
+    fn find_private_key(&self, selector: &KeySelector) -> Result<(CkUlong, Vec<u8>), AegisError> {
+        let mut class = CKO_PRIVATE_KEY;
+        let mut key_type = CKK_EC;
+        let (kind, mut value) = match selector {
+            KeySelector::Label(label) => (CKA_LABEL, label.as_bytes().to_vec()),
+            KeySelector::Id(id) => (CKA_ID, id.clone()),
+        };
+        let mut template = [
+            ulong_attribute(CKA_CLASS, &mut class),
+            ulong_attribute(CKA_KEY_TYPE, &mut key_type),
+            CkAttribute {
+                kind,
+                value: value.as_mut_ptr().cast(),
+                len: value.len() as CkUlong,
+            },
+        ];
+        let key = self.find(&mut template, "EC private key")?;
+        let id = match selector {
+            KeySelector::Id(id) => id.clone(),
+            KeySelector::Label(_) => self.attribute(key, CKA_ID)?,
+        };
+        Ok((key, id))
+    }
+
+    fn public_key(&self, id: &[u8]) -> Result<VerifyingKey, AegisError> {
+        let mut class = CKO_PUBLIC_KEY;
+        let mut id = id.to_vec();
+        let mut template = [
+            ulong_attribute(CKA_CLASS, &mut class),
+            CkAttribute {
+                kind: CKA_ID,
+                value: id.as_mut_ptr().cast(),
+                len: id.len() as CkUlong,
+            },
+        ];
+        let object = self.find(&mut template, "public key for the private key")?;
+        let point = self.attribute(object, CKA_EC_POINT)?;
+        // Usually a DER OCTET STRING around the SEC1 point; some tokens
+        // return the bare point.
+        let sec1 = match point.as_slice() {
+            [0x04, 0x41, der @ ..] if der.len() == 0x41 => der,
+            bare => bare,
+        };
+        VerifyingKey::from_sec1_bytes(sec1).map_err(|_| pkcs11_error("the key is not a P-256 key"))
+    }
+
+    fn attribute(&self, object: CkUlong, kind: CkUlong) -> Result<Vec<u8>, AegisError> {
+        let mut attribute = CkAttribute {
+            kind,
+            value: ptr::null_mut(),
+            len: 0,
+        };
+        // SAFETY: the first call only reads the length; the second gets a
+        // buffer of that length.
+        unsafe {
+            let f = &*self.functions;
+            check("C_GetAttributeValue", (f.get_attribute_value)(self.handle, object, &mut attribute, 1))?;
+            let mut value = vec![0u8; attribute.len as usize];
+            attribute.value = value.as_mut_ptr().cast();
+            check("C_GetAttributeValue", (f.get_attribute_value)(self.handle, object, &mut attribute, 1))?;
+            value.truncate(attribute.len as usize);
+            Ok(value)
+        }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        // SAFETY: the module stays loaded until dlclose, after the last call.
+        unsafe {
+            let f = &*self.functions;
+            if self.handle != 0 {
+                (f.logout)(self.handle);
+                (f.close_session)(self.handle);
+            }
+            if self.initialized {
+                (f.finalize)(ptr::null_mut());
+            }
+            libc::dlclose(self.library);
+        }
+    }
+}
+
+fn ulong_attribute(kind: CkUlong, value: &mut CkUlong) -> CkAttribute {
+    CkAttribute {
+        kind,
+        value: (value as *mut CkUlong).cast(),
+        len: std::mem::size_of::<CkUlong>() as CkUlong,
+    }
+}
+
+impl Signer<Signature> for Pkcs11Signer {
+    fn try_sign(&self, msg: &[u8]) -> Result<Signature, SignatureError> {
+        let digest: [u8; 32] = Sha256::digest(msg).into();
+        self.sign_digest(&digest).map_err(SignatureError::from_source)
+    }
+}
+
+impl Keypair for Pkcs11Signer {
+    type VerifyingKey = VerifyingKey;
+
+    fn verifying_key(&self) -> VerifyingKey {
+        self.public_key
+    }
+}
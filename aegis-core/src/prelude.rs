@@ -0,0 +1,231 @@
+// aegis-core/src/prelude.rs
+
+// A small facade for embedders: `Sealer` and `Verifier` wrap key handling
+// and container I/O so the common "seal these bytes" / "verify these bytes"
+// flows need no knowledge of the format internals.
+//
+//     use aegis_core::prelude::*;
+//     let sealer = Sealer::from_hex(&std::env::var("AEGIS_PRIVATE_KEY")?)?;
+//     let sealed = sealer.seal_to_vec("{\"title\":\"demo\"}", image_bytes)?;
+
+pub use crate::error::AegisError;
+pub use crate::format::{AegisAncient, DetachedSignature};
+pub use crate::keys::Fingerprint;
+pub use crate::metadata::Metadata;
+pub use p256::ecdsa::SigningKey;
+
+#[cfg(any(feature = "sealer", feature = "verifier"))]
+use {
+    crate::crypto,
+    std::fs::File,
+    std::io::{BufReader, Read},
+    std::path::Path,
+};
+#[cfg(feature = "sealer")]
+use std::io::{BufWriter, Seek, Write};
+
+/// Seals payloads with a single signing key.
+#[cfg(feature = "sealer")]
+pub struct Sealer {
+    key: SigningKey,
+}
+
+#[cfg(feature = "sealer")]
+impl Sealer {
+    pub fn new(key: SigningKey) -> Self {
+        Sealer { key }
+    }
+
+    /// Builds a sealer from a hex-encoded 32-byte P-256 private scalar, the
+    /// same encoding the service reads from `AEGIS_PRIVATE_KEY`.
+    pub fn from_hex(private_key_hex: &str) -> Result<Self, AegisError> {
+        let bytes = hex::decode(private_key_hex.trim())
+            .map_err(|e| AegisError::Crypto(format!("invalid private key hex: {}", e)))?;
+        let key = SigningKey::from_slice(&bytes)
+            .map_err(|e| AegisError::Crypto(format!("invalid private key: {}", e)))?;
+        Ok(Sealer { key })
+    }
+
+    /// Fingerprint of the public key embedded in every container this sealer produces.
+    pub fn fingerprint(&self) -> Fingerprint {
+        Fingerprint::of(&self.key.verifying_key().to_sec1_bytes())
+    }
+
+    pub fn seal(
+        &self,
+        metadata: impl Into<String>,
+        payload: Vec<u8>,
+    ) -> Result<AegisAncient, AegisError> {
+        crypto::seal(metadata.into(), payload, &self.key)
+    }
+
+    /// Adds this key's co-signature to a container sealed by someone else
+    /// (see `crypto::countersign()`).
+    pub fn countersign(&self, ancient: &mut AegisAncient, role: &str, signed_at: i64) -> Result<(), AegisError> {
+        crypto::countersign(ancient, role, signed_at, &self.key)
+    }
+
+    pub fn seal_to_vec(
+        &self,
+        metadata: impl Into<String>,
+        payload: Vec<u8>,
+    ) -> Result<Vec<u8>, AegisError> {
+        self.seal(metadata, payload)?.to_bytes()
+    }
+
+    /// Seals the payload in `input` into `output` without buffering it: the
+    /// input is read once to hash and once to copy, so it must be seekable.
+    pub fn seal_stream<R: Read + Seek, W: Write>(
+        &self,
+        metadata: impl AsRef<str>,
+        input: &mut R,
+        output: &mut W,
+    ) -> Result<(), AegisError> {
+        crypto::seal_stream(metadata.as_ref(), input, output, &self.key)?;
+        output.flush()?;
+        Ok(())
+    }
+
+    /// Like `seal_stream`, for inputs that cannot seek (pipes, sockets). The
+    /// whole payload is read into memory first.
+    pub fn seal_reader<R: Read, W: Write>(
+        &self,
+        metadata: impl Into<String>,
+        input: &mut R,
+        output: &mut W,
+    ) -> Result<(), AegisError> {
+        let mut payload = Vec::new();
+        input.read_to_end(&mut payload)?;
+        self.seal(metadata, payload)?.write(output)?;
+        output.flush()?;
+        Ok(())
+    }
+
+    /// Signs the payload in `input` without embedding it; write the result
+    /// with `DetachedSignature::to_bytes()` next to the original, e.g. as
+    /// `photo.jpg.aegis.sig`.
+    pub fn seal_detached<R: Read>(
+        &self,
+        metadata: impl AsRef<str>,
+        input: &mut R,
+    ) -> Result<DetachedSignature, AegisError> {
+        crypto::seal_detached(metadata.as_ref(), input, &self.key)
+    }
+
+    pub fn seal_file(
+        &self,
+        metadata: impl AsRef<str>,
+        input: impl AsRef<Path>,
+        output: impl AsRef<Path>,
+    ) -> Result<(), AegisError> {
+        let mut reader = BufReader::new(File::open(input)?);
+        let mut writer = BufWriter::new(File::create(output)?);
+        self.seal_stream(metadata, &mut reader, &mut writer)
+    }
+}
+
+/// The contents of a container whose signature checked out.
+#[cfg(feature = "verifier")]
+pub struct Verified {
+    pub metadata: String,
+    pub payload: Vec<u8>,
+    pub fingerprint: Fingerprint,
+}
+
+#[cfg(feature = "verifier")]
+impl Verified {
+    /// The metadata as a `Metadata` document, `None` if it is free-form.
+    pub fn structured_metadata(&self) -> Result<Option<Metadata>, AegisError> {
+        Metadata::parse_structured(&self.metadata)
+    }
+}
+
+/// What a detached signature vouched for once it checked out against the
+/// original.
+#[cfg(feature = "verifier")]
+pub struct VerifiedDetached {
+    pub metadata: String,
+    pub image_len: u64,
+    pub fingerprint: Fingerprint,
+}
+
+#[cfg(feature = "verifier")]
+impl VerifiedDetached {
+    /// The metadata as a `Metadata` document, `None` if it is free-form.
+    pub fn structured_metadata(&self) -> Result<Option<Metadata>, AegisError> {
+        Metadata::parse_structured(&self.metadata)
+    }
+}
+
+/// Verifies containers, optionally only accepting a pinned set of keys.
+#[cfg(feature = "verifier")]
+#[derive(Default)]
+pub struct Verifier {
+    trusted: Vec<Fingerprint>,
+}
+
+#[cfg(feature = "verifier")]
+impl Verifier {
+    /// A verifier that accepts any correctly signed container.
+    pub fn new() -> Self {
+        Verifier::default()
+    }
+
+    /// Restricts verification to containers signed by this key. May be
+    /// called several times to trust more than one key.
+    pub fn trust(mut self, fingerprint: Fingerprint) -> Self {
+        self.trusted.push(fingerprint);
+        self
+    }
+
+    pub fn verify(&self, ancient: AegisAncient) -> Result<Verified, AegisError> {
+        let fingerprint = Fingerprint::of(&ancient.public_key);
+        if !self.trusted.is_empty() && !self.trusted.contains(&fingerprint) {
+            return Err(AegisError::Crypto(format!("untrusted signing key {}", fingerprint)));
+        }
+        let unsealed = crypto::unseal(ancient)?;
+        Ok(Verified {
+            metadata: unsealed.metadata,
+            payload: unsealed.image_data,
+            fingerprint,
+        })
+    }
+
+    pub fn verify_bytes(&self, bytes: &[u8]) -> Result<Verified, AegisError> {
+        self.verify_stream(&mut &bytes[..])
+    }
+
+    pub fn verify_stream<R: Read>(&self, input: &mut R) -> Result<Verified, AegisError> {
+        self.verify(AegisAncient::read(input)?)
+    }
+
+    pub fn verify_file(&self, path: impl AsRef<Path>) -> Result<Verified, AegisError> {
+        self.verify_stream(&mut BufReader::new(File::open(path)?))
+    }
+
+    /// Checks a `.aegis.sig` sidecar against the original it was made for.
+    pub fn verify_detached<R: Read>(&self, sidecar: &[u8], original: &mut R) -> Result<VerifiedDetached, AegisError> {
+        let detached = DetachedSignature::parse(sidecar)?;
+        let fingerprint = Fingerprint::of(&detached.public_key);
+        if !self.trusted.is_empty() && !self.trusted.contains(&fingerprint) {
+            return Err(AegisError::Crypto(format!("untrusted signing key {}", fingerprint)));
+        }
+        if !crypto::verify_detached(&detached, original)?.signature_valid {
+            return Err(AegisError::Crypto("signature does not match the original".into()));
+        }
+        Ok(VerifiedDetached {
+            metadata: detached.metadata,
+            image_len: detached.image_len,
+            fingerprint,
+        })
+    }
+
+    pub fn verify_detached_file(
+        &self,
+        sidecar: impl AsRef<Path>,
+        original: impl AsRef<Path>,
+    ) -> Result<VerifiedDetached, AegisError> {
+        let sidecar = std::fs::read(sidecar)?;
+        self.verify_detached(&sidecar, &mut BufReader::new(File::open(original)?))
+    }
+}
@@ -0,0 +1,194 @@
+// aegis-core/src/rollup.rs
+
+// Daily rollups: one signed summary covering every seal made on a UTC day.
+// The sealing service builds a Merkle tree (see `merkle`) over the SHA-256 of
+// each image sealed that day, in the order they were sealed, and signs its
+// root with the date in `SignatureContext::Rollup`, optionally with an
+// RFC 3161 timestamp over that signature. A per-seal inclusion proof then
+// shows that a file is among the day's seals, so the one rollup stands for
+// all of them; and because the date is signed, a rollup cannot be passed
+// off as another day's, or a transparency log head as a rollup.
+
+use crate::error::AegisError;
+use crate::time::{parse_rfc3339, rfc3339};
+use base64ct::{Base64, Encoding};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, UNIX_EPOCH};
+#[cfg(feature = "verifier")]
+use {
+    crate::crypto::SignatureContext,
+    crate::merkle::{leaf_hash, InclusionProof},
+    crate::timestamp::{self, TimestampReport, TsaTrust},
+    p256::ecdsa::{Signature, VerifyingKey},
+};
+
+/// Length of a rollup date, `YYYY-MM-DD`.
+const DATE_LEN: usize = 10;
+
+/// The start of a UTC day given as `YYYY-MM-DD`, in Unix seconds, or
+/// `None` if `date` is not such a day.
+pub fn day_start(date: &str) -> Option<i64> {
+    if date.len() != DATE_LEN {
+        return None;
+    }
+    let start = parse_rfc3339(&format!("{}T00:00:00Z", date))?;
+    // parse_rfc3339 does not reject days past the end of the month.
+    (date_of(start) == date).then_some(start)
+}
+
+/// The UTC day of a Unix time, as `YYYY-MM-DD`.
+pub fn date_of(unix_secs: i64) -> String {
+    rfc3339(UNIX_EPOCH + Duration::from_secs(unix_secs.max(0) as u64))[..DATE_LEN].to_string()
+}
+
+/// What a rollup commits to: the day, how many seals it covers, the root of
+/// the tree over them, and when it was signed, in Unix seconds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RollupHead {
+    pub date: String,
+    pub seal_count: u64,
+    pub root_hash: [u8; 32],
+    pub signed_at: i64,
+}
+
+impl RollupHead {
+    /// What the service signs, in `SignatureContext::Rollup`: the date as 10
+    /// ASCII bytes, the seal count as an 8-byte big-endian integer, the root
+    /// hash, and the signing time as an 8-byte big-endian Unix time.
+    pub fn statement(&self) -> Vec<u8> {
+        let mut object = Vec::with_capacity(DATE_LEN + 8 + 32 + 8);
+        object.extend_from_slice(self.date.as_bytes());
+        object.extend_from_slice(&self.seal_count.to_be_bytes());
+        object.extend_from_slice(&self.root_hash);
+        object.extend_from_slice(&self.signed_at.to_be_bytes());
+        object
+    }
+}
+
+/// A rollup with the service's signature and public key, and a TSA token
+/// over the signature if one was obtained.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "SignedRollupJson", into = "SignedRollupJson")]
+pub struct SignedRollup {
+    pub head: RollupHead,
+    /// SEC1 public key of the signer.
+    pub public_key: Vec<u8>,
+    pub signature: Vec<u8>,
+    /// DER RFC 3161 token whose imprint is the SHA-256 of `signature`.
+    pub timestamp_token: Option<Vec<u8>>,
+}
+
+// The JSON form: hex hash, key and signature, an RFC 3339 time, a base64
+// token.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SignedRollupJson {
+    date: String,
+    seal_count: u64,
+    root_hash: String,
+    signed_at: String,
+    public_key: String,
+    signature: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timestamp_token: Option<String>,
+}
+
+impl TryFrom<SignedRollupJson> for SignedRollup {
+    type Error = AegisError;
+
+    fn try_from(json: SignedRollupJson) -> Result<Self, AegisError> {
+        let bytes = |name: &str, value: &str| {
+            hex::decode(value).map_err(|_| AegisError::InvalidProof(format!("{} is not hex", name)))
+        };
+        if day_start(&json.date).is_none() {
+            return Err(AegisError::InvalidProof("date is not a YYYY-MM-DD day".into()));
+        }
+        Ok(SignedRollup {
+            head: RollupHead {
+                date: json.date,
+                seal_count: json.seal_count,
+                root_hash: bytes("root_hash", &json.root_hash)?
+                    .try_into()
+                    .map_err(|_| AegisError::InvalidProof("root_hash is not 32 bytes".into()))?,
+                signed_at: parse_rfc3339(&json.signed_at)
+                    .ok_or_else(|| AegisError::InvalidProof("signed_at is not an RFC 3339 time".into()))?,
+            },
+            public_key: bytes("public_key", &json.public_key)?,
+            signature: bytes("signature", &json.signature)?,
+            timestamp_token: json
+                .timestamp_token
+                .map(|token| {
+                    Base64::decode_vec(&token)
+                        .map_err(|_| AegisError::InvalidProof("timestamp_token is not base64".into()))
+                })
+                .transpose()?,
+        })
+    }
+}
+
+impl From<SignedRollup> for SignedRollupJson {
+    fn from(signed: SignedRollup) -> Self {
+        SignedRollupJson {
+            date: signed.head.date,
+            seal_count: signed.head.seal_count,
+            root_hash: hex::encode(signed.head.root_hash),
+            signed_at: rfc3339(UNIX_EPOCH + Duration::from_secs(signed.head.signed_at.max(0) as u64)),
+            public_key: hex::encode(&signed.public_key),
+            signature: hex::encode(&signed.signature),
+            timestamp_token: signed.timestamp_token.map(|token| Base64::encode_string(&token)),
+        }
+    }
+}
+
+#[cfg(feature = "verifier")]
+impl SignedRollup {
+    /// Whether the signature is by `public_key` over the head.
+    pub fn signature_valid(&self) -> bool {
+        let Ok(key) = VerifyingKey::from_sec1_bytes(&self.public_key) else {
+            return false;
+        };
+        let Ok(signature) = Signature::from_slice(&self.signature) else {
+            return false;
+        };
+        SignatureContext::Rollup
+            .verify(&key, &self.head.statement(), &signature)
+            .unwrap_or(false)
+    }
+
+    /// The TSA's account of when the rollup was signed, if it was
+    /// timestamped, checked against the TSAs `trust` accepts.
+    pub fn timestamp(&self, trust: &TsaTrust) -> Option<TimestampReport> {
+        self.timestamp_token
+            .as_deref()
+            .map(|token| timestamp::verify(token, &self.signature, trust))
+    }
+}
+
+/// Confirms that the file with SHA-256 `file_sha256` is among the seals of
+/// the day `rollup` covers, signed by the holder of `service_key` (SEC1):
+/// the rollup must be signed by that key, the proof must be for its seal
+/// count, and it must lead from the file's leaf to its root.
+#[cfg(feature = "verifier")]
+pub fn verify_inclusion(
+    file_sha256: &[u8; 32],
+    proof: &InclusionProof,
+    rollup: &SignedRollup,
+    service_key: &[u8],
+) -> Result<(), AegisError> {
+    if rollup.public_key != service_key {
+        return Err(AegisError::InvalidProof("the rollup is signed by a different key".into()));
+    }
+    if !rollup.signature_valid() {
+        return Err(AegisError::InvalidProof("the rollup signature is invalid".into()));
+    }
+    if proof.tree_size != rollup.head.seal_count {
+        return Err(AegisError::InvalidProof(format!(
+            "the proof is for {} seals, the rollup for {}",
+            proof.tree_size, rollup.head.seal_count
+        )));
+    }
+    if proof.root_from(&leaf_hash(file_sha256))? != rollup.head.root_hash {
+        return Err(AegisError::InvalidProof("the proof does not lead to the rollup's root".into()));
+    }
+    Ok(())
+}
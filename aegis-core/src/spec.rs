@@ -0,0 +1,396 @@
+// aegis-core/src/spec.rs
+
+// Generates a human-readable description of the container format from the
+// same constants the reader and writer use, so the published spec cannot
+// drift from the implementation.
+
+use crate::{chunked, crypto, dns_trust, endorsement, format, merkle, xmp};
+
+struct Section {
+    heading: String,
+    paragraphs: Vec<String>,
+    table: Option<(Vec<&'static str>, Vec<Vec<String>>)>,
+}
+
+fn sections() -> Vec<Section> {
+    let magic = String::from_utf8_lossy(format::MAGIC_PREFIX).into_owned();
+    let versions = format::SUPPORTED_VERSIONS
+        .iter()
+        .map(|v| format!("`{}`", char::from(*v)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut layout_rows = vec![
+        vec![
+            "magic".to_string(),
+            format!("{} bytes", format::MAGIC_PREFIX.len()),
+            format!("ASCII `{}`.", magic),
+        ],
+        vec![
+            "version".to_string(),
+            "1 byte".to_string(),
+            format!("ASCII format version digit: one of {}.", versions),
+        ],
+    ];
+    for block in std::iter::once(&format::HEADER_BLOCK).chain(&format::BLOCKS) {
+        layout_rows.push(vec![
+            format!("{}.length", block.name),
+            format!("{} bytes", format::BLOCK_LENGTH_PREFIX),
+            "Unsigned big-endian length of the block that follows.".to_string(),
+        ]);
+        layout_rows.push(vec![
+            block.name.to_string(),
+            "length bytes".to_string(),
+            block.description.to_string(),
+        ]);
+    }
+
+    vec![
+        Section {
+            heading: "Overview".into(),
+            paragraphs: vec![
+                format!(
+                    "An Aegis container starts with the magic `{}` and a version byte, followed by length-prefixed blocks in a fixed order. There are no block tags; a block's meaning is given by its position.",
+                    magic
+                ),
+                format!(
+                    "Version `{}` containers hold the {} blocks below other than `{}`. Version `{}` adds the `{}` block in front of them, and is what writers produce. Readers accept every supported version.",
+                    char::from(format::VERSION_1),
+                    format::BLOCKS.len(),
+                    format::HEADER_BLOCK.name,
+                    char::from(format::VERSION_2),
+                    format::HEADER_BLOCK.name,
+                ),
+                "Readers reject any header flag bit they do not understand, and skip (but preserve) header fields with unknown tags. The header is not covered by the signature, except for signed extensions (below).".into(),
+            ],
+            table: None,
+        },
+        Section {
+            heading: "Binary layout".into(),
+            paragraphs: vec![],
+            table: Some((vec!["Field", "Size", "Description"], layout_rows)),
+        },
+        Section {
+            heading: "Limits".into(),
+            paragraphs: vec![format!(
+                "Readers reject any block whose declared length exceeds {} bytes, and any container that ends before a declared block is complete.",
+                format::MAX_BLOCK_SIZE
+            )],
+            table: None,
+        },
+        Section {
+            heading: "Signature".into(),
+            paragraphs: vec![
+                format!(
+                    "Header field {} holds the signature scheme as a big-endian 16-bit ID. Without it the scheme is {} (`{}`, ID {}), which is also the only scheme this implementation writes.",
+                    format::FIELD_SIGNATURE_SCHEME,
+                    crypto::SIGNATURE_ALGORITHM,
+                    crypto::SignatureScheme::EcdsaP256.name(),
+                    crypto::SignatureScheme::EcdsaP256.id(),
+                ),
+                format!(
+                    "The signed message is the {} digest of the concatenation of: {}. No length prefixes or separators are included.",
+                    crypto::DIGEST_ALGORITHM,
+                    crypto::SIGNED_FIELDS
+                        .iter()
+                        .map(|f| format!("`{}`", f))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                "The ECDSA signature is then computed over that 32-byte message, which the signature scheme hashes again with SHA-256.".into(),
+            ],
+            table: None,
+        },
+        Section {
+            heading: "Signature contexts".into(),
+            paragraphs: vec![
+                "A key that seals containers may also sign other objects. Each of those is signed as a context prefix followed by the object's bytes, and verifiers check it only under the context they expect, so a signature over one kind of object is never valid as another.".into(),
+                "Containers are signed over the bare 32-byte digest above, with no prefix. Verifiers accept nothing but a 32-byte message in that context.".into(),
+            ],
+            table: Some((
+                vec!["Context", "Prefix"],
+                crypto::SignatureContext::ALL
+                    .iter()
+                    .map(|c| {
+                        let prefix = String::from_utf8_lossy(c.prefix()).replace('\0', "\\0");
+                        vec![
+                            format!("`{}`", c.name()),
+                            if prefix.is_empty() { "(none)".to_string() } else { format!("`{}`", prefix) },
+                        ]
+                    })
+                    .collect(),
+            )),
+        },
+        Section {
+            heading: "Timestamps".into(),
+            paragraphs: vec![format!(
+                "Header field {} may hold a DER RFC 3161 timestamp token whose message imprint is the SHA-256 of the `signature` block. Since it covers the signature, the token can be added after sealing without re-signing. Readers report the attested time and whether the token matches the signature.",
+                format::FIELD_TIMESTAMP_TOKEN,
+            )],
+            table: None,
+        },
+        Section {
+            heading: "Metadata".into(),
+            paragraphs: vec![
+                "The `metadata` block is UTF-8 text. When it is a JSON object it follows a common schema, with every field optional: `creator` (string), `captured_at` (RFC 3339 timestamp), `device` (object with string `make`, `model` and `serial`) and `gps` (object with `latitude` from -90 to 90, `longitude` from -180 to 180 and `altitude` in metres). Any other top-level key is a custom field.".into(),
+                "Labels in several languages go under `languages`, an object keyed by BCP 47 language tag whose values hold an optional `title` and `caption` (strings), `keywords` (array of strings) and any custom labels; `default_language` names one of its keys as the original. Tags are written in canonical case: the language lowercase, a four-letter script in title case, a two-letter region uppercase. All variants are in the one signed document, so any one of them is as authentic as the rest.".into(),
+                "Writers store such a document as canonical JSON: no insignificant whitespace, and the keys of every object in ascending order.".into(),
+            ],
+            table: None,
+        },
+        Section {
+            heading: "External metadata".into(),
+            paragraphs: vec![format!(
+                "A large metadata document may be kept in header field {} instead of the `metadata` block. The block then holds ASCII `{}` followed by the lowercase hex SHA-256 of the document, so the signature binds the document through its hash. Readers that resolve the reference must reject a document whose hash differs.",
+                format::FIELD_EXTERNAL_METADATA,
+                format::EXTERNAL_METADATA_PREFIX,
+            )],
+            table: None,
+        },
+        Section {
+            heading: "Signed extensions".into(),
+            paragraphs: vec![
+                format!(
+                    "Header field {} holds integrator-defined extensions, in ascending name order with no duplicates. Each is a 2-byte big-endian name length, the name (at most {} bytes of lowercase ASCII letters, digits, `.`, `_` or `-`), a 1-byte kind (`{}` for bytes, `{}` for UTF-8 JSON), a 4-byte big-endian value length and the value.",
+                    format::FIELD_EXTENSIONS,
+                    format::MAX_EXTENSION_NAME,
+                    format::ExtensionKind::Bytes as u8,
+                    format::ExtensionKind::Json as u8,
+                ),
+                format!(
+                    "A container with extensions sets header flag `{}`, and its signature is then over the {} digest of ASCII `{}`, the 32-byte digest described under Signature, and the {} digest of the whole field. Readers reject a container with the field but not the flag, or the flag but not the field.",
+                    format::FLAG_SIGNED_EXTENSIONS,
+                    crypto::DIGEST_ALGORITHM,
+                    String::from_utf8_lossy(crypto::EXTENSIONS_DIGEST_PREFIX).replace('\0', "\\0"),
+                    crypto::DIGEST_ALGORITHM,
+                ),
+            ],
+            table: None,
+        },
+        Section {
+            heading: "Chunked images".into(),
+            paragraphs: vec![
+                format!(
+                    "Header field {} lists the image in fixed-size chunks: the chunk size ({} to {} bytes) as a 4-byte and the image length as an 8-byte big-endian integer, then the 32-byte SHA-256 of each chunk in order, the last of which may be short. A container with the field sets header flag `{}`; readers reject one with the field but not the flag, or the flag but not the field.",
+                    format::FIELD_IMAGE_CHUNKS,
+                    chunked::MIN_CHUNK_SIZE,
+                    chunked::MAX_CHUNK_SIZE,
+                    format::FLAG_CHUNKED_IMAGE,
+                ),
+                format!(
+                    "The 32-byte digest described under Signature is then replaced by the {} digest of ASCII `{}`, the {} digest of the metadata block, the chunk size and image length as above, and the root of a Merkle tree over the chunk hashes, built as for the transparency log with each chunk hash as a leaf's input. A full verification hashes every chunk itself and so does not depend on the list; to check a byte range, a reader checks the signature against the root of the listed hashes, then hashes only the chunks the range overlaps and compares them with the list.",
+                    crypto::DIGEST_ALGORITHM,
+                    String::from_utf8_lossy(chunked::CHUNKED_DIGEST_PREFIX).replace('\0', "\\0"),
+                    crypto::DIGEST_ALGORITHM,
+                ),
+            ],
+            table: None,
+        },
+        Section {
+            heading: "Compressed blocks".into(),
+            paragraphs: vec![
+                format!(
+                    "Header flag `{}` marks the metadata block, and flag `{}` the image block, as stored as a single Zstandard frame (RFC 8878) that needs no dictionary; the block's length prefix is the frame's length. Readers decompress such a block before using it, and reject one whose frame is malformed or would decompress to more than {} bytes. The image block of a chunked container is not compressed.",
+                    format::FLAG_ZSTD_METADATA,
+                    format::FLAG_ZSTD_IMAGE,
+                    format::MAX_BLOCK_SIZE,
+                ),
+                "Every digest described above is over the decompressed bytes, and neither flag is covered by the signature, so a container can be compressed or decompressed after sealing without affecting its signature.".into(),
+            ],
+            table: None,
+        },
+        Section {
+            heading: "Co-signatures".into(),
+            paragraphs: vec![
+                format!(
+                    "Header field {} holds signatures added after sealing by co-signers, such as an agency countersigning a photographer's seal. Each entry is the co-signer's SEC1 public key, its signature and its role (UTF-8, 1 to {} bytes), each preceded by a 2-byte big-endian length, then the signing time as an 8-byte big-endian signed Unix time. Entries are appended in the order they were made.",
+                    format::FIELD_COSIGNATURES,
+                    format::MAX_ROLE,
+                ),
+                "The container signature does not cover the field. Each co-signature is made in the `cosignature` context over the 32-byte digest the container signature covers, the SHA-256 of the sealing `public_key`, the role as a 2-byte big-endian length and its bytes, and the signing time as an 8-byte big-endian integer. Readers report each co-signer's validity separately from the container's.".into(),
+            ],
+            table: None,
+        },
+        Section {
+            heading: "Certificate chains".into(),
+            paragraphs: vec![
+                format!(
+                    "Header field {} holds the X.509 certificate chain of the sealing key, leaf first, each DER certificate preceded by its 4-byte big-endian length. The leaf's subject public key must be the container's `public_key`.",
+                    format::FIELD_CERTIFICATE_CHAIN,
+                ),
+                "The container signature does not cover the field; since the leaf must certify the key the signature is checked against, a substituted chain cannot lend trust to another key. Readers check each certificate's ECDSA P-256 SHA-256 signature by the next, require issuers to be CAs, require the last certificate to be, or be signed by, one of their own trust anchors, and check validity periods at the time of a valid timestamp, or at the current time without one. They report the leaf's subject as the identity of the sealer.".into(),
+            ],
+            table: None,
+        },
+        Section {
+            heading: "DNS trust hints".into(),
+            paragraphs: vec![
+                format!(
+                    "Header field {} names where the sealing key is published in DNS: the domain, then a selector, each preceded by a 1-byte length. Both are lowercase ASCII letters, digits and hyphens, the domain in dot-separated labels and the selector a single label.",
+                    format::FIELD_TRUST_HINT,
+                ),
+                format!(
+                    "The key is published as a TXT record at `<selector>.{}.<domain>` reading `v={}; k=p256; p=` followed by the base64 of the SEC1 key. A name may hold several records, one per key. The container signature does not cover the field; a reader that resolves the hint accepts it only if one of the records publishes the container's `public_key`, and reports whether the answer was authenticated with DNSSEC.",
+                    dns_trust::RECORD_LABEL,
+                    dns_trust::RECORD_VERSION,
+                ),
+            ],
+            table: None,
+        },
+        Section {
+            heading: "Key endorsements".into(),
+            paragraphs: vec![
+                format!(
+                    "Header field {} holds endorsements: statements by an issuer key that a subject key may seal in a role during a validity period. Each entry is the issuer's and the subject's SEC1 public keys, the role (UTF-8, 1 to {} bytes) and the signature, each preceded by a 2-byte big-endian length, then the start and end of the validity period as 8-byte big-endian signed Unix times.",
+                    format::FIELD_ENDORSEMENTS,
+                    endorsement::MAX_ROLE,
+                ),
+                format!(
+                    "Each endorsement is signed by its issuer in the `endorsement` context over the SHA-256 of the subject key, the role as a 2-byte big-endian length and its bytes, and the two times as 8-byte big-endian integers. The container signature does not cover the field. A reader with root keys of its own accepts the sealing key if it is a root, or if a chain of at most {} endorsements leads from it to a root, each endorsing the previous one's issuer, each signature valid and the time checked (that of a valid timestamp, or the current time) within each validity period. Endorsements may also be supplied outside the container, for example in a trust bundle.",
+                    endorsement::MAX_CHAIN,
+                ),
+            ],
+            table: None,
+        },
+        Section {
+            heading: "Transparency log".into(),
+            paragraphs: vec![
+                "A sealing service may enter every seal in an append-only Merkle tree as in RFC 6962: each leaf is the SHA-256 of `0x00` and the 32-byte SHA-256 of the sealed image, each interior node the SHA-256 of `0x01` and its two children, and the tree over n leaves splits them at the largest power of two below n. The service signs tree heads in the `tree-head` context over the tree size as an 8-byte big-endian integer, the 32-byte root hash, and the time as an 8-byte big-endian signed Unix time.".into(),
+                format!(
+                    "Header field {} holds the seal's inclusion proof at the tree size just after it was logged: the leaf index and tree size as 8-byte big-endian integers, then at most {} 32-byte sibling hashes, from the leaf up. The container signature does not cover the field. A reader confirms the seal was logged by hashing the image, computing the root from the proof as in RFC 9162 section 2.1.3.2, and comparing it with a tree head of that size signed by the service's key.",
+                    format::FIELD_LOG_INCLUSION,
+                    merkle::MAX_PROOF_LEN,
+                ),
+            ],
+            table: None,
+        },
+        Section {
+            heading: "Rollups".into(),
+            paragraphs: vec![
+                "A sealing service may also sign one rollup per UTC day: a Merkle tree, hashed as above, over the SHA-256 of every image sealed that day in the order they were sealed. It signs the rollup in the `rollup` context over the day as 10 ASCII bytes (`YYYY-MM-DD`), the seal count as an 8-byte big-endian integer, the 32-byte root hash, and the signing time as an 8-byte big-endian signed Unix time, and may add an RFC 3161 token whose message imprint is the SHA-256 of that signature. An inclusion proof for the seal count then shows a file was sealed that day.".into(),
+            ],
+            table: None,
+        },
+        Section {
+            heading: "Detached signatures".into(),
+            paragraphs: vec![format!(
+                "A `.{}` sidecar carries the same signature without the image. It starts with ASCII `{}` and the version byte `{}`, followed by the `public_key`, `metadata` and `signature` blocks as above, then the 32-byte SHA-256 of the image and its length as an 8-byte big-endian integer.",
+                format::DETACHED_EXTENSION,
+                String::from_utf8_lossy(format::DETACHED_MAGIC),
+                char::from(format::DETACHED_VERSION_1),
+            )],
+            table: None,
+        },
+        Section {
+            heading: "Payload manifests".into(),
+            paragraphs: vec![format!(
+                "A `.{}` manifest is the same detached signature as a JSON object, kept next to the untouched payload: `format` is `{}`; `payload` has the payload's `size`, hex `sha256` and, for information, its `file_name` and `media_type`; `metadata` is the signed metadata as a string; `key` has the hex SEC1 `public_key`, its hex `fingerprint` and an optional `key_id`; `signature` has the `scheme`, the hex `value` and the `signed_fields`. Verifiers pair a manifest with the file named as it is without the `.{}` suffix unless given another.",
+                crate::manifest::MANIFEST_EXTENSION,
+                crate::manifest::MANIFEST_FORMAT,
+                crate::manifest::MANIFEST_EXTENSION,
+            )],
+            table: None,
+        },
+        Section {
+            heading: "In-toto attestations".into(),
+            paragraphs: vec![format!(
+                "A seal may be exported as an in-toto v1 statement in a DSSE envelope with payload type `{}`, one envelope per line of a `.{}` file. The statement's subject is the payload's name and SHA-256; its `predicateType` is `{}` and its predicate has the signed `metadata` (as JSON where it parses), the payload's `size`, and under `seal` the hex `public_key`, `key_fingerprint`, `signature` and the `signed_fields` of the detached signature above. The envelope is signed by the sealing key in the `dsse` context over DSSE's pre-authentication encoding, as a DER ECDSA signature whose `keyid` is the key fingerprint.",
+                crate::intoto::PAYLOAD_TYPE,
+                crate::intoto::ATTESTATION_EXTENSION,
+                crate::intoto::PREDICATE_TYPE,
+            )],
+            table: None,
+        },
+        Section {
+            heading: "XMP copies".into(),
+            paragraphs: vec![
+                format!(
+                    "A JPEG or PNG image may carry its own seal in an XMP packet (a JPEG APP1 segment after SOI and any JFIF segment, or a PNG `iTXt` chunk after IHDR). The packet's `rdf:Description` has the properties `KeyFingerprint`, `Signature` and `Container` in namespace `{}`; `Container` is the base64 of the container up to and including the `image.length` field.",
+                    xmp::AEGIS_NS,
+                ),
+                "The image block is the image with that segment or chunk removed, so verifiers rebuild the container by appending those bytes to the decoded `Container` value and verify it as usual.".into(),
+            ],
+            table: None,
+        },
+        Section {
+            heading: "C2PA copies".into(),
+            paragraphs: vec![
+                "A JPEG or PNG image may instead carry its seal in a C2PA manifest store (JPEG APP11 segments after SOI and any JFIF segment, or a PNG `caBX` chunk after IHDR). Its manifest has a `c2pa.hash.data` assertion excluding the manifest bytes, a claim, and a COSE_Sign1 ES256 signature over the claim by the sealing key, which is named by its fingerprint (`kid`) rather than a certificate chain.".into(),
+                format!(
+                    "The `{}` JSON assertion holds `container`, the base64 of the container up to and including the `image.length` field, as in XMP copies; the image block is the image with the manifest removed.",
+                    crate::c2pa::SEAL_ASSERTION,
+                ),
+            ],
+            table: None,
+        },
+    ]
+}
+
+/// Renders the format specification as Markdown.
+pub fn to_markdown() -> String {
+    let mut out = String::from("# Aegis container format\n\n");
+    for section in sections() {
+        out.push_str(&format!("## {}\n\n", section.heading));
+        for p in &section.paragraphs {
+            out.push_str(p);
+            out.push_str("\n\n");
+        }
+        if let Some((header, rows)) = &section.table {
+            out.push_str(&format!("| {} |\n", header.join(" | ")));
+            out.push_str(&format!("|{}\n", "---|".repeat(header.len())));
+            for row in rows {
+                out.push_str(&format!("| {} |\n", row.join(" | ")));
+            }
+            out.push('\n');
+        }
+    }
+    out.trim_end().to_string() + "\n"
+}
+
+/// Renders the format specification as a standalone HTML document.
+pub fn to_html() -> String {
+    let mut out = String::from(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Aegis container format</title></head><body>\n<h1>Aegis container format</h1>\n",
+    );
+    for section in sections() {
+        out.push_str(&format!("<h2>{}</h2>\n", escape(&section.heading)));
+        for p in &section.paragraphs {
+            out.push_str(&format!("<p>{}</p>\n", inline_html(p)));
+        }
+        if let Some((header, rows)) = &section.table {
+            out.push_str("<table>\n<tr>");
+            for h in header {
+                out.push_str(&format!("<th>{}</th>", escape(h)));
+            }
+            out.push_str("</tr>\n");
+            for row in rows {
+                out.push_str("<tr>");
+                for cell in row {
+                    out.push_str(&format!("<td>{}</td>", inline_html(cell)));
+                }
+                out.push_str("</tr>\n");
+            }
+            out.push_str("</table>\n");
+        }
+    }
+    out.push_str("</body></html>\n");
+    out
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+// Escapes text and turns Markdown `code` spans into <code> elements.
+fn inline_html(s: &str) -> String {
+    escape(s)
+        .split('`')
+        .enumerate()
+        .map(|(i, part)| {
+            if i % 2 == 1 {
+                format!("<code>{}</code>", part)
+            } else {
+                part.to_string()
+            }
+        })
+        .collect()
+}
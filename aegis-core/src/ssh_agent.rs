@@ -0,0 +1,275 @@
+// aegis-core/src/ssh_agent.rs
+
+// Signing with keys held by an OpenSSH agent, including FIDO2-backed
+// `sk-ecdsa` keys. Plain `ecdsa-sha2-nistp256` agent keys produce ordinary
+// container signatures through `SshAgentSigner`; any agent key can produce
+// an `ssh-keygen -Y verify`-compatible SSHSIG signature with `sshsig()`.
+
+use crate::error::AegisError;
+use base64ct::{Base64, Base64Unpadded, Encoding};
+use p256::ecdsa::{
+    signature::{Error as SignatureError, Keypair, Signer},
+    Signature, VerifyingKey,
+};
+use sha2::{Digest, Sha256, Sha512};
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+
+const SSH_AGENT_FAILURE: u8 = 5;
+const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+const SSH_AGENTC_SIGN_REQUEST: u8 = 13;
+const SSH_AGENT_SIGN_RESPONSE: u8 = 14;
+const MAX_AGENT_MESSAGE: usize = 256 * 1024;
+
+pub const ECDSA_P256: &str = "ecdsa-sha2-nistp256";
+pub const SK_ECDSA_P256: &str = "sk-ecdsa-sha2-nistp256@openssh.com";
+
+/// Namespace used for SSHSIG signatures over sealed containers.
+pub const SSHSIG_NAMESPACE: &str = "aegis";
+const SSHSIG_MAGIC: &[u8] = b"SSHSIG";
+
+fn agent_error(msg: impl Into<String>) -> AegisError {
+    AegisError::Crypto(format!("ssh-agent: {}", msg.into()))
+}
+
+fn put_string(buf: &mut Vec<u8>, data: &[u8]) {
+    buf.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    buf.extend_from_slice(data);
+}
+
+// Cursor over SSH wire-format data.
+struct Wire<'a>(&'a [u8]);
+
+impl<'a> Wire<'a> {
+    fn u32(&mut self) -> Result<u32, AegisError> {
+        if self.0.len() < 4 {
+            return Err(agent_error("truncated message"));
+        }
+        let (head, rest) = self.0.split_at(4);
+        self.0 = rest;
+        Ok(u32::from_be_bytes(head.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Result<&'a [u8], AegisError> {
+        let len = self.u32()? as usize;
+        if self.0.len() < len {
+            return Err(agent_error("truncated message"));
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(head)
+    }
+}
+
+/// A public key offered by the agent.
+#[derive(Clone, Debug)]
+pub struct AgentKey {
+    pub key_type: String,
+    pub blob: Vec<u8>,
+    pub comment: String,
+}
+
+impl AgentKey {
+    /// OpenSSH-style fingerprint, e.g. `SHA256:uNiVztksCsDhcc0u9e8BujQXVUpKZIDTMczCvj3tD2s`.
+    pub fn fingerprint(&self) -> String {
+        format!("SHA256:{}", Base64Unpadded::encode_string(&Sha256::digest(&self.blob)))
+    }
+
+    /// The P-256 public key for `ecdsa` and `sk-ecdsa` keys.
+    pub fn p256_public_key(&self) -> Option<VerifyingKey> {
+        if self.key_type != ECDSA_P256 && self.key_type != SK_ECDSA_P256 {
+            return None;
+        }
+        let mut wire = Wire(&self.blob);
+        wire.string().ok()?;
+        wire.string().ok()?;
+        VerifyingKey::from_sec1_bytes(wire.string().ok()?).ok()
+    }
+
+    /// Whether `selector` names this key by fingerprint or comment.
+    pub fn matches(&self, selector: &str) -> bool {
+        self.fingerprint() == selector || self.comment == selector
+    }
+}
+
+/// A connection to the agent listening on `SSH_AUTH_SOCK`.
+pub struct SshAgent {
+    socket: PathBuf,
+}
+
+impl SshAgent {
+    pub fn new(socket: impl Into<PathBuf>) -> Self {
+        SshAgent {
+            socket: socket.into(),
+        }
+    }
+
+    pub fn from_env() -> Result<Self, AegisError> {
+        std::env::var_os("SSH_AUTH_SOCK")
+            .map(SshAgent::new)
+            .ok_or_else(|| agent_error("SSH_AUTH_SOCK is not set"))
+    }
+
+    fn call(&self, request: &[u8]) -> Result<Vec<u8>, AegisError> {
+        let mut stream = UnixStream::connect(&self.socket)?;
+        stream.write_all(&(request.len() as u32).to_be_bytes())?;
+        stream.write_all(request)?;
+        let mut len = [0u8; 4];
+        stream.read_exact(&mut len)?;
+        let len = u32::from_be_bytes(len) as usize;
+        if len == 0 || len > MAX_AGENT_MESSAGE {
+            return Err(agent_error("invalid response length"));
+        }
+        let mut response = vec![0u8; len];
+        stream.read_exact(&mut response)?;
+        Ok(response)
+    }
+
+    pub fn list_keys(&self) -> Result<Vec<AgentKey>, AegisError> {
+        let response = self.call(&[SSH_AGENTC_REQUEST_IDENTITIES])?;
+        if response[0] != SSH_AGENT_IDENTITIES_ANSWER {
+            return Err(agent_error("agent refused to list identities"));
+        }
+        let mut wire = Wire(&response[1..]);
+        let count = wire.u32()?;
+        let mut keys = Vec::new();
+        for _ in 0..count {
+            let blob = wire.string()?.to_vec();
+            let comment = String::from_utf8_lossy(wire.string()?).into_owned();
+            let key_type = String::from_utf8_lossy(Wire(&blob).string()?).into_owned();
+            keys.push(AgentKey {
+                key_type,
+                blob,
+                comment,
+            });
+        }
+        Ok(keys)
+    }
+
+    /// Finds a key by fingerprint or comment.
+    pub fn find_key(&self, selector: &str) -> Result<AgentKey, AegisError> {
+        self.list_keys()?
+            .into_iter()
+            .find(|k| k.matches(selector))
+            .ok_or_else(|| agent_error(format!("no key matching '{}'", selector)))
+    }
+
+    /// Asks the agent to sign `data`, returning the SSH signature blob. For
+    /// FIDO2 keys this waits for the user to touch the token.
+    pub fn sign(&self, key: &AgentKey, data: &[u8]) -> Result<Vec<u8>, AegisError> {
+        let mut request = vec![SSH_AGENTC_SIGN_REQUEST];
+        put_string(&mut request, &key.blob);
+        put_string(&mut request, data);
+        request.extend_from_slice(&0u32.to_be_bytes());
+        let response = self.call(&request)?;
+        match response[0] {
+            SSH_AGENT_SIGN_RESPONSE => Ok(Wire(&response[1..]).string()?.to_vec()),
+            SSH_AGENT_FAILURE => Err(agent_error("agent refused to sign")),
+            other => Err(agent_error(format!("unexpected response type {}", other))),
+        }
+    }
+}
+
+/// A container signer backed by an `ecdsa-sha2-nistp256` agent key. The agent
+/// computes ECDSA/SHA-256 over the message, exactly like `SigningKey`.
+pub struct SshAgentSigner {
+    agent: SshAgent,
+    key: AgentKey,
+    public_key: VerifyingKey,
+}
+
+impl SshAgentSigner {
+    pub fn new(agent: SshAgent, key: AgentKey) -> Result<Self, AegisError> {
+        if key.key_type != ECDSA_P256 {
+            return Err(agent_error(format!(
+                "{} keys cannot produce container signatures; only {} keys can (use sshsig() instead)",
+                key.key_type, ECDSA_P256
+            )));
+        }
+        let public_key = key
+            .p256_public_key()
+            .ok_or_else(|| agent_error("malformed P-256 public key"))?;
+        Ok(SshAgentSigner {
+            agent,
+            key,
+            public_key,
+        })
+    }
+
+    pub fn key(&self) -> &AgentKey {
+        &self.key
+    }
+}
+
+impl Signer<Signature> for SshAgentSigner {
+    fn try_sign(&self, msg: &[u8]) -> Result<Signature, SignatureError> {
+        let blob = self.agent.sign(&self.key, msg).map_err(SignatureError::from_source)?;
+        decode_ecdsa_signature(&blob).map_err(SignatureError::from_source)
+    }
+}
+
+impl Keypair for SshAgentSigner {
+    type VerifyingKey = VerifyingKey;
+
+    fn verifying_key(&self) -> VerifyingKey {
+        self.public_key
+    }
+}
+
+// Converts an SSH `ecdsa-sha2-nistp256` signature blob (two mpints) into the
+// fixed-size r || s form.
+fn decode_ecdsa_signature(blob: &[u8]) -> Result<Signature, AegisError> {
+    let mut wire = Wire(blob);
+    if wire.string()? != ECDSA_P256.as_bytes() {
+        return Err(agent_error("unexpected signature type"));
+    }
+    let mut inner = Wire(wire.string()?);
+    let mut raw = [0u8; 64];
+    for half in raw.chunks_mut(32) {
+        let mpint = inner.string()?;
+        let trimmed = &mpint[mpint.iter().take_while(|b| **b == 0).count()..];
+        if trimmed.len() > 32 {
+            return Err(agent_error("signature component too large"));
+        }
+        half[32 - trimmed.len()..].copy_from_slice(trimmed);
+    }
+    Signature::from_slice(&raw).map_err(|e| agent_error(e.to_string()))
+}
+
+/// Produces an armored SSHSIG signature over `message`, verifiable with
+/// `ssh-keygen -Y verify -n <namespace>`. Works with any agent key type.
+pub fn sshsig(
+    agent: &SshAgent,
+    key: &AgentKey,
+    namespace: &str,
+    message: &[u8],
+) -> Result<String, AegisError> {
+    let hash_algorithm = "sha512";
+    let digest = Sha512::digest(message);
+
+    let mut signed_data = SSHSIG_MAGIC.to_vec();
+    put_string(&mut signed_data, namespace.as_bytes());
+    put_string(&mut signed_data, b"");
+    put_string(&mut signed_data, hash_algorithm.as_bytes());
+    put_string(&mut signed_data, &digest);
+    let signature = agent.sign(key, &signed_data)?;
+
+    let mut blob = SSHSIG_MAGIC.to_vec();
+    blob.extend_from_slice(&1u32.to_be_bytes());
+    put_string(&mut blob, &key.blob);
+    put_string(&mut blob, namespace.as_bytes());
+    put_string(&mut blob, b"");
+    put_string(&mut blob, hash_algorithm.as_bytes());
+    put_string(&mut blob, &signature);
+
+    let encoded = Base64::encode_string(&blob);
+    let mut armored = String::from("-----BEGIN SSH SIGNATURE-----\n");
+    for line in encoded.as_bytes().chunks(70) {
+        armored.push_str(std::str::from_utf8(line).unwrap());
+        armored.push('\n');
+    }
+    armored.push_str("-----END SSH SIGNATURE-----\n");
+    Ok(armored)
+}
@@ -0,0 +1,135 @@
+// aegis-core/src/tar.rs
+
+// Minimal ustar writer and reader for regular files, enough to package
+// sealed artifacts into archives every platform's `tar` can open.
+
+use crate::error::AegisError;
+use std::io::{Read, Write};
+
+const BLOCK: usize = 512;
+
+/// Writes regular-file entries to an underlying writer.
+pub struct TarWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> TarWriter<W> {
+    pub fn new(inner: W) -> Self {
+        TarWriter { inner }
+    }
+
+    /// Appends a file. Names longer than 100 bytes are rejected.
+    pub fn append(&mut self, name: &str, data: &[u8], mtime: u64) -> Result<(), AegisError> {
+        self.inner.write_all(&header(name, data.len() as u64, mtime)?)?;
+        self.inner.write_all(data)?;
+        self.pad(data.len())
+    }
+
+    /// Writes the end-of-archive marker and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, AegisError> {
+        self.inner.write_all(&[0u8; BLOCK * 2])?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    fn pad(&mut self, len: usize) -> Result<(), AegisError> {
+        let rem = len % BLOCK;
+        if rem != 0 {
+            self.inner.write_all(&[0u8; BLOCK][..BLOCK - rem])?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds the 512-byte ustar header for a regular file.
+pub fn header(name: &str, size: u64, mtime: u64) -> Result<[u8; BLOCK], AegisError> {
+    if name.len() > 100 {
+        return Err(AegisError::Io(std::io::Error::other(format!(
+            "tar entry name too long: {}",
+            name
+        ))));
+    }
+    let mut h = [0u8; BLOCK];
+    h[..name.len()].copy_from_slice(name.as_bytes());
+    write_octal(&mut h[100..108], 0o644);
+    write_octal(&mut h[108..116], 0);
+    write_octal(&mut h[116..124], 0);
+    write_octal(&mut h[124..136], size);
+    write_octal(&mut h[136..148], mtime);
+    h[148..156].copy_from_slice(b"        ");
+    h[156] = b'0';
+    h[257..263].copy_from_slice(b"ustar\0");
+    h[263..265].copy_from_slice(b"00");
+    let checksum: u64 = h.iter().map(|b| *b as u64).sum();
+    write_octal(&mut h[148..155], checksum);
+    h[155] = b' ';
+    Ok(h)
+}
+
+/// Number of zero bytes that follow `len` bytes of entry data.
+pub fn padding(len: u64) -> usize {
+    (BLOCK - (len as usize % BLOCK)) % BLOCK
+}
+
+/// The end-of-archive marker.
+pub fn trailer() -> [u8; BLOCK * 2] {
+    [0u8; BLOCK * 2]
+}
+
+// Zero-padded octal, NUL-terminated, filling the field.
+fn write_octal(field: &mut [u8], value: u64) {
+    let digits = field.len() - 1;
+    let s = format!("{:0width$o}", value, width = digits);
+    field[..digits].copy_from_slice(&s.as_bytes()[s.len() - digits..]);
+    field[digits] = 0;
+}
+
+fn read_octal(field: &[u8]) -> Result<u64, AegisError> {
+    let s: String = field
+        .iter()
+        .take_while(|b| **b != 0 && **b != b' ')
+        .map(|b| *b as char)
+        .collect();
+    u64::from_str_radix(s.trim(), 8).map_err(|_| invalid("bad octal field"))
+}
+
+fn invalid(msg: &str) -> AegisError {
+    AegisError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_string()))
+}
+
+/// Reads every regular file in an archive, up to `max_total` bytes of content.
+pub fn read_all<R: Read>(reader: &mut R, max_total: u64) -> Result<Vec<(String, Vec<u8>)>, AegisError> {
+    let mut entries = Vec::new();
+    let mut total = 0u64;
+    loop {
+        let mut h = [0u8; BLOCK];
+        reader.read_exact(&mut h)?;
+        if h.iter().all(|b| *b == 0) {
+            return Ok(entries);
+        }
+        let expected = read_octal(&h[148..156])?;
+        let actual: u64 = h
+            .iter()
+            .enumerate()
+            .map(|(i, b)| if (148..156).contains(&i) { b' ' as u64 } else { *b as u64 })
+            .sum();
+        if expected != actual {
+            return Err(invalid("tar header checksum mismatch"));
+        }
+        let name_len = h[..100].iter().position(|b| *b == 0).unwrap_or(100);
+        let name = String::from_utf8(h[..name_len].to_vec()).map_err(|_| invalid("non-UTF-8 name"))?;
+        let size = read_octal(&h[124..136])?;
+        total += size;
+        if total > max_total {
+            return Err(invalid("archive content exceeds limit"));
+        }
+        let mut data = vec![0u8; size as usize];
+        reader.read_exact(&mut data)?;
+        let mut pad = vec![0u8; padding(size)];
+        reader.read_exact(&mut pad)?;
+        // Only regular files are meaningful to us; skip anything else.
+        if h[156] == b'0' || h[156] == 0 {
+            entries.push((name, data));
+        }
+    }
+}
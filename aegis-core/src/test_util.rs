@@ -0,0 +1,411 @@
+// aegis-core/src/test_util.rs
+
+// Helpers for downstream tests: deterministic keys, a recording mock signer,
+// ready-made valid or deliberately broken containers, and X.509 certificates
+// and RFC 3161 timestamp tokens made from test keys. Only compiled with the
+// `test-util` feature and for this crate's own tests; never use these keys
+// for real seals.
+
+use crate::der::{
+    encode, encode_unsigned, TAG_BIT_STRING, TAG_BOOLEAN, TAG_CONTEXT_0, TAG_GENERALIZED_TIME, TAG_INTEGER, TAG_NULL,
+    TAG_OCTET_STRING, TAG_OID, TAG_SEQUENCE, TAG_SET,
+};
+#[cfg(feature = "sealer")]
+use crate::{crypto, format::AegisAncient};
+use p256::ecdsa::{
+    signature::{Error as SignatureError, Keypair, Signer},
+    Signature, SigningKey, VerifyingKey,
+};
+use sha2::{Digest, Sha256};
+use std::sync::Mutex;
+
+pub const SAMPLE_METADATA: &str = r#"{"title":"aegis test sample"}"#;
+pub const SAMPLE_PAYLOAD: &[u8] = b"aegis test payload";
+
+/// Returns the `n`th deterministic test key. The same `n` always yields the
+/// same key, and different `n` yield different keys.
+pub fn test_signing_key(n: u32) -> SigningKey {
+    let mut counter = 0u32;
+    loop {
+        let seed = Sha256::new()
+            .chain_update(b"aegis-test-key")
+            .chain_update(n.to_be_bytes())
+            .chain_update(counter.to_be_bytes())
+            .finalize();
+        // A handful of 32-byte strings are not valid scalars; skip them.
+        if let Ok(key) = SigningKey::from_slice(&seed) {
+            return key;
+        }
+        counter += 1;
+    }
+}
+
+/// Hex encoding of `test_signing_key(n)`, for code that reads keys from config.
+pub fn test_signing_key_hex(n: u32) -> String {
+    hex::encode(test_signing_key(n).to_bytes())
+}
+
+/// A signer that wraps a deterministic test key and records every message it
+/// is asked to sign. It can also be configured to fail.
+pub struct MockSigner {
+    key: SigningKey,
+    fail: bool,
+    calls: Mutex<Vec<Vec<u8>>>,
+}
+
+impl MockSigner {
+    pub fn new() -> Self {
+        Self::with_key(test_signing_key(0))
+    }
+
+    pub fn with_key(key: SigningKey) -> Self {
+        MockSigner {
+            key,
+            fail: false,
+            calls: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// A signer whose every signing attempt returns an error.
+    pub fn failing() -> Self {
+        MockSigner {
+            fail: true,
+            ..Self::new()
+        }
+    }
+
+    /// The messages passed to the signer so far, oldest first.
+    pub fn calls(&self) -> Vec<Vec<u8>> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    pub fn call_count(&self) -> usize {
+        self.calls.lock().unwrap().len()
+    }
+}
+
+impl Default for MockSigner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Signer<Signature> for MockSigner {
+    fn try_sign(&self, msg: &[u8]) -> Result<Signature, SignatureError> {
+        self.calls.lock().unwrap().push(msg.to_vec());
+        if self.fail {
+            return Err(SignatureError::new());
+        }
+        self.key.try_sign(msg)
+    }
+}
+
+impl Keypair for MockSigner {
+    type VerifyingKey = VerifyingKey;
+
+    fn verifying_key(&self) -> VerifyingKey {
+        *self.key.verifying_key()
+    }
+}
+
+/// Ways a sample container can be broken.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Corruption {
+    /// One bit of the signature is flipped.
+    Signature,
+    /// The metadata is altered after signing.
+    Metadata,
+    /// One bit of the payload is flipped.
+    Payload,
+    /// The embedded public key is replaced by a different valid key.
+    PublicKey,
+    /// The magic number is wrong.
+    BadMagic,
+    /// The serialized container is cut off mid-payload.
+    Truncated,
+}
+
+/// A correctly signed container built from the sample metadata and payload.
+#[cfg(feature = "sealer")]
+pub fn sample_container() -> AegisAncient {
+    sample_container_with(SAMPLE_METADATA, SAMPLE_PAYLOAD)
+}
+
+#[cfg(feature = "sealer")]
+pub fn sample_container_with(metadata: &str, payload: &[u8]) -> AegisAncient {
+    crypto::seal(metadata.to_string(), payload.to_vec(), &test_signing_key(0))
+        .expect("sealing with a test key cannot fail")
+}
+
+/// The serialized bytes of `sample_container()`.
+#[cfg(feature = "sealer")]
+pub fn sample_bytes() -> Vec<u8> {
+    sample_container().to_bytes().expect("writing to a Vec cannot fail")
+}
+
+/// Serialized sample container with the given defect applied.
+#[cfg(feature = "sealer")]
+pub fn corrupted_bytes(corruption: Corruption) -> Vec<u8> {
+    let mut ancient = sample_container();
+    match corruption {
+        Corruption::Signature => ancient.signature[0] ^= 0x01,
+        Corruption::Metadata => ancient.metadata.push(' '),
+        Corruption::Payload => ancient.image_data[0] ^= 0x01,
+        Corruption::PublicKey => {
+            ancient.public_key = test_signing_key(1)
+                .verifying_key()
+                .to_sec1_bytes()
+                .into_vec()
+        }
+        Corruption::BadMagic | Corruption::Truncated => {}
+    }
+    let mut bytes = ancient.to_bytes().expect("writing to a Vec cannot fail");
+    match corruption {
+        Corruption::BadMagic => bytes[0] = b'X',
+        Corruption::Truncated => bytes.truncate(bytes.len() - SAMPLE_PAYLOAD.len() / 2),
+        _ => {}
+    }
+    bytes
+}
+
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const OID_P256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const OID_ECDSA_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+const OID_SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+const OID_BASIC_CONSTRAINTS: &[u8] = &[0x55, 0x1d, 0x13];
+const OID_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x0f];
+const OID_EXTENDED_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x25];
+const OID_SIGNED_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x02];
+const OID_TST_INFO: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x10, 0x01, 0x04];
+const OID_CONTENT_TYPE: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x03];
+const OID_MESSAGE_DIGEST: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x04];
+
+/// Object identifier of the id-kp-timeStamping key purpose.
+pub const OID_TIME_STAMPING: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x08];
+/// Key usage bits, as the first byte of the extension's bit string.
+pub const DIGITAL_SIGNATURE: u8 = 0x80;
+pub const KEY_CERT_SIGN: u8 = 0x04;
+
+/// 2020-01-01 and 2040-01-01, the default validity period of test
+/// certificates, in Unix seconds.
+pub const NOT_BEFORE: i64 = 1_577_836_800;
+pub const NOT_AFTER: i64 = 2_208_988_800;
+
+fn sequence(parts: &[Vec<u8>]) -> Vec<u8> {
+    encode(TAG_SEQUENCE, &parts.concat())
+}
+
+fn bit_string(bytes: &[u8]) -> Vec<u8> {
+    encode(TAG_BIT_STRING, &[&[0][..], bytes].concat())
+}
+
+fn generalized_time(unix_secs: i64) -> Vec<u8> {
+    let text = crate::time::rfc3339(std::time::UNIX_EPOCH + std::time::Duration::from_secs(unix_secs as u64));
+    let digits: String = text.chars().filter(char::is_ascii_digit).collect();
+    encode(TAG_GENERALIZED_TIME, format!("{}Z", digits).as_bytes())
+}
+
+/// A DER `Name` holding a single common name.
+pub fn test_name(common_name: &str) -> Vec<u8> {
+    let attribute = sequence(&[encode(TAG_OID, OID_COMMON_NAME), encode(0x0c, common_name.as_bytes())]);
+    sequence(&[encode(TAG_SET, &attribute)])
+}
+
+/// An X.509 certificate for `test_signing_key(key)`, described field by
+/// field so tests can get any of them wrong. Build it with `issued_by()` or
+/// `self_signed()`.
+#[derive(Clone, Debug)]
+pub struct TestCertificate {
+    pub subject: String,
+    pub key: u32,
+    pub serial: u32,
+    pub not_before: i64,
+    pub not_after: i64,
+    /// Basic constraints: `Some(path_len)` for a CA, `None` for a leaf.
+    pub ca: Option<Option<u8>>,
+    /// Key usage bits, or `None` to leave the extension out.
+    pub key_usage: Option<u8>,
+    /// Extended key usage purposes and whether the extension is critical.
+    pub extended_key_usage: Option<(Vec<&'static [u8]>, bool)>,
+    /// Further extensions: object identifier, criticality and value.
+    pub extensions: Vec<(Vec<u8>, bool, Vec<u8>)>,
+    /// The signature algorithm named inside the signed part, if not the
+    /// one the certificate is signed with.
+    pub tbs_signature_algorithm: Option<Vec<u8>>,
+}
+
+impl TestCertificate {
+    /// An end-entity certificate for signing.
+    pub fn leaf(subject: &str, key: u32) -> Self {
+        TestCertificate {
+            subject: subject.to_string(),
+            key,
+            serial: key + 1,
+            not_before: NOT_BEFORE,
+            not_after: NOT_AFTER,
+            ca: None,
+            key_usage: Some(DIGITAL_SIGNATURE),
+            extended_key_usage: None,
+            extensions: Vec::new(),
+            tbs_signature_algorithm: None,
+        }
+    }
+
+    /// A CA certificate without a path length constraint.
+    pub fn ca(subject: &str, key: u32) -> Self {
+        TestCertificate {
+            ca: Some(None),
+            key_usage: Some(KEY_CERT_SIGN),
+            ..Self::leaf(subject, key)
+        }
+    }
+
+    /// A time-stamping authority's certificate, as RFC 3161 describes it.
+    pub fn tsa(subject: &str, key: u32) -> Self {
+        TestCertificate {
+            extended_key_usage: Some((vec![OID_TIME_STAMPING], true)),
+            ..Self::leaf(subject, key)
+        }
+    }
+
+    pub fn name(&self) -> Vec<u8> {
+        test_name(&self.subject)
+    }
+
+    pub fn signing_key(&self) -> SigningKey {
+        test_signing_key(self.key)
+    }
+
+    /// SEC1 encoding of the certified key.
+    pub fn public_key(&self) -> Vec<u8> {
+        self.signing_key().verifying_key().to_sec1_bytes().into_vec()
+    }
+
+    /// The certificate as DER, signed by `issuer`.
+    pub fn issued_by(&self, issuer: &TestCertificate) -> Vec<u8> {
+        self.build(&issuer.name(), &issuer.signing_key())
+    }
+
+    pub fn self_signed(&self) -> Vec<u8> {
+        self.build(&self.name(), &self.signing_key())
+    }
+
+    fn build(&self, issuer: &[u8], issuer_key: &SigningKey) -> Vec<u8> {
+        let algorithm = sequence(&[encode(TAG_OID, OID_ECDSA_SHA256)]);
+        let spki = sequence(&[
+            sequence(&[encode(TAG_OID, OID_EC_PUBLIC_KEY), encode(TAG_OID, OID_P256)]),
+            bit_string(&self.public_key()),
+        ]);
+        let extension = |oid: &[u8], critical: bool, value: Vec<u8>| {
+            let critical = if critical { encode(TAG_BOOLEAN, &[0xff]) } else { Vec::new() };
+            sequence(&[encode(TAG_OID, oid), critical, encode(TAG_OCTET_STRING, &value)])
+        };
+        let mut extensions = Vec::new();
+        if let Some(path_len) = self.ca {
+            let path_len = path_len.map(|n| encode_unsigned(&[n])).unwrap_or_default();
+            let constraints = sequence(&[encode(TAG_BOOLEAN, &[0xff]), path_len]);
+            extensions.push(extension(OID_BASIC_CONSTRAINTS, true, constraints));
+        }
+        if let Some(bits) = self.key_usage {
+            extensions.push(extension(OID_KEY_USAGE, true, bit_string(&[bits])));
+        }
+        if let Some((purposes, critical)) = &self.extended_key_usage {
+            let purposes: Vec<_> = purposes.iter().map(|oid| encode(TAG_OID, oid)).collect();
+            extensions.push(extension(OID_EXTENDED_KEY_USAGE, *critical, sequence(&purposes)));
+        }
+        for (oid, critical, value) in &self.extensions {
+            extensions.push(extension(oid, *critical, value.clone()));
+        }
+        let tbs_algorithm = match &self.tbs_signature_algorithm {
+            Some(oid) => sequence(&[encode(TAG_OID, oid)]),
+            None => algorithm.clone(),
+        };
+        let mut tbs = vec![
+            encode(TAG_CONTEXT_0, &encode(TAG_INTEGER, &[2])),
+            encode_unsigned(&self.serial.to_be_bytes()),
+            tbs_algorithm,
+            issuer.to_vec(),
+            sequence(&[generalized_time(self.not_before), generalized_time(self.not_after)]),
+            self.name(),
+            spki,
+        ];
+        if !extensions.is_empty() {
+            tbs.push(encode(0xa3, &sequence(&extensions)));
+        }
+        let tbs = sequence(&tbs);
+        let signature: Signature = issuer_key.sign(&tbs);
+        sequence(&[tbs, algorithm, bit_string(signature.to_der().as_bytes())])
+    }
+}
+
+/// An RFC 3161 timestamp token signed with `test_signing_key(key)`, whose
+/// signer info names the certificate `issuer` gave `serial`.
+#[derive(Clone, Debug)]
+pub struct TestTimestamp {
+    pub key: u32,
+    pub issuer: String,
+    pub serial: u32,
+    /// The attested time, in Unix seconds.
+    pub time: i64,
+    /// Certificates carried in the token, DER, usually the TSA's first.
+    pub certificates: Vec<Vec<u8>>,
+    /// Whether the signed attributes include the content type.
+    pub content_type: bool,
+}
+
+impl TestTimestamp {
+    /// A token from the TSA `tsa`, certified by `issuer`, carrying both
+    /// certificates.
+    pub fn new(tsa: &TestCertificate, issuer: &TestCertificate, time: i64) -> Self {
+        TestTimestamp {
+            key: tsa.key,
+            issuer: issuer.subject.clone(),
+            serial: tsa.serial,
+            time,
+            certificates: vec![tsa.issued_by(issuer), issuer.self_signed()],
+            content_type: true,
+        }
+    }
+
+    /// The DER token over the SHA-256 of `signature`.
+    pub fn token(&self, signature: &[u8]) -> Vec<u8> {
+        let sha256 = sequence(&[encode(TAG_OID, OID_SHA256), encode(TAG_NULL, &[])]);
+        let tst_info = sequence(&[
+            encode(TAG_INTEGER, &[1]),
+            encode(TAG_OID, &[0x2a, 0x03, 0x04]),
+            sequence(&[sha256.clone(), encode(TAG_OCTET_STRING, &Sha256::digest(signature))]),
+            encode(TAG_INTEGER, &[0x2a]),
+            generalized_time(self.time),
+        ]);
+        let mut attributes = Vec::new();
+        if self.content_type {
+            attributes.push(sequence(&[
+                encode(TAG_OID, OID_CONTENT_TYPE),
+                encode(TAG_SET, &encode(TAG_OID, OID_TST_INFO)),
+            ]));
+        }
+        attributes.push(sequence(&[
+            encode(TAG_OID, OID_MESSAGE_DIGEST),
+            encode(TAG_SET, &encode(TAG_OCTET_STRING, &Sha256::digest(&tst_info))),
+        ]));
+        let attributes = attributes.concat();
+        let signature: Signature = test_signing_key(self.key).sign(&encode(TAG_SET, &attributes));
+        let signer_info = sequence(&[
+            encode(TAG_INTEGER, &[1]),
+            sequence(&[test_name(&self.issuer), encode_unsigned(&self.serial.to_be_bytes())]),
+            sha256.clone(),
+            encode(TAG_CONTEXT_0, &attributes),
+            sequence(&[encode(TAG_OID, OID_ECDSA_SHA256)]),
+            encode(TAG_OCTET_STRING, signature.to_der().as_bytes()),
+        ]);
+        let signed_data = sequence(&[
+            encode(TAG_INTEGER, &[3]),
+            encode(TAG_SET, &sha256),
+            sequence(&[encode(TAG_OID, OID_TST_INFO), encode(TAG_CONTEXT_0, &encode(TAG_OCTET_STRING, &tst_info))]),
+            encode(TAG_CONTEXT_0, &self.certificates.concat()),
+            encode(TAG_SET, &signer_info),
+        ]);
+        sequence(&[encode(TAG_OID, OID_SIGNED_DATA), encode(TAG_CONTEXT_0, &signed_data)])
+    }
+}
@@ -0,0 +1,263 @@
+// aegis-core/src/text.rs
+
+// Text documents as payloads. A `.txt` or `.csv` file that passes between
+// Windows and Unix often has its line endings rewritten on the way, which
+// changes every hash of it while leaving the text as a reader sees it
+// unchanged. Sealing a document in text mode records, in the `content`
+// block of its metadata (covered by the signature), that the payload is
+// UTF-8 text, the line endings it arrived with, and the normalization
+// applied before signing, if any:
+//
+//     "content": {"format": "text", "media_type": "text/plain",
+//                 "encoding": "utf-8", "line_endings": "crlf",
+//                 "normalization": "lf"}
+//
+// `normalization` is `none` when the document was signed exactly as it
+// arrived. A verifier given a copy that does not match can then tell
+// whether the copy differs only in its line endings (`LineEndingHasher`),
+// and say so instead of just reporting a mismatch.
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::io::{self, Write};
+
+/// The line endings found in a document.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEndings {
+    /// No line breaks at all.
+    None,
+    Lf,
+    Crlf,
+    Cr,
+    Mixed,
+}
+
+impl LineEndings {
+    pub fn of(text: &[u8]) -> Self {
+        let (mut lf, mut crlf, mut cr) = (false, false, false);
+        let mut bytes = text.iter().peekable();
+        while let Some(&b) = bytes.next() {
+            match b {
+                b'\r' if bytes.peek() == Some(&&b'\n') => {
+                    bytes.next();
+                    crlf = true;
+                }
+                b'\r' => cr = true,
+                b'\n' => lf = true,
+                _ => {}
+            }
+        }
+        match (lf, crlf, cr) {
+            (false, false, false) => LineEndings::None,
+            (true, false, false) => LineEndings::Lf,
+            (false, true, false) => LineEndings::Crlf,
+            (false, false, true) => LineEndings::Cr,
+            _ => LineEndings::Mixed,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LineEndings::None => "none",
+            LineEndings::Lf => "lf",
+            LineEndings::Crlf => "crlf",
+            LineEndings::Cr => "cr",
+            LineEndings::Mixed => "mixed",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        [LineEndings::None, LineEndings::Lf, LineEndings::Crlf, LineEndings::Cr, LineEndings::Mixed]
+            .into_iter()
+            .find(|l| l.name() == name)
+    }
+}
+
+/// What a document's line endings are rewritten to before signing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Normalization {
+    /// Signed as it arrived.
+    None,
+    Lf,
+    Crlf,
+}
+
+impl Normalization {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "none" => Some(Normalization::None),
+            "lf" => Some(Normalization::Lf),
+            "crlf" => Some(Normalization::Crlf),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Normalization::None => "none",
+            Normalization::Lf => "lf",
+            Normalization::Crlf => "crlf",
+        }
+    }
+
+    /// `text` with every CRLF, CR and LF written as this line ending.
+    pub fn apply(self, text: &[u8]) -> Vec<u8> {
+        let ending: &[u8] = match self {
+            Normalization::None => return text.to_vec(),
+            Normalization::Lf => b"\n",
+            Normalization::Crlf => b"\r\n",
+        };
+        let mut out = Vec::with_capacity(text.len() + text.len() / 32);
+        let mut writer = Normalizer { ending, pending_cr: false };
+        writer.feed(text, |bytes| out.extend_from_slice(bytes));
+        writer.end(|bytes| out.extend_from_slice(bytes));
+        out
+    }
+}
+
+/// Rewrites line endings in a stream; a CR at the end of one piece may
+/// start a CRLF completed by the next.
+struct Normalizer {
+    ending: &'static [u8],
+    pending_cr: bool,
+}
+
+impl Normalizer {
+    fn feed(&mut self, mut data: &[u8], mut out: impl FnMut(&[u8])) {
+        if self.pending_cr && !data.is_empty() {
+            self.pending_cr = false;
+            out(self.ending);
+            if data[0] == b'\n' {
+                data = &data[1..];
+            }
+        }
+        while let Some(at) = data.iter().position(|&b| b == b'\r' || b == b'\n') {
+            out(&data[..at]);
+            if data[at] == b'\r' {
+                match data.get(at + 1) {
+                    Some(b'\n') => {
+                        out(self.ending);
+                        data = &data[at + 2..];
+                    }
+                    Some(_) => {
+                        out(self.ending);
+                        data = &data[at + 1..];
+                    }
+                    None => {
+                        self.pending_cr = true;
+                        return;
+                    }
+                }
+            } else {
+                out(self.ending);
+                data = &data[at + 1..];
+            }
+        }
+        out(data);
+    }
+
+    fn end(&mut self, mut out: impl FnMut(&[u8])) {
+        if std::mem::take(&mut self.pending_cr) {
+            out(self.ending);
+        }
+    }
+}
+
+/// What the `content` block of a text-mode seal records.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct TextRecord {
+    /// Line endings of the document as it was submitted.
+    pub line_endings: LineEndings,
+    pub normalization: Normalization,
+}
+
+impl TextRecord {
+    /// The `content` block for a seal of `submitted` under `normalization`.
+    pub fn new(submitted: &[u8], normalization: Normalization) -> Self {
+        TextRecord { line_endings: LineEndings::of(submitted), normalization }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "format": "text",
+            "media_type": "text/plain",
+            "encoding": "utf-8",
+            "line_endings": self.line_endings.name(),
+            "normalization": self.normalization.name(),
+        })
+    }
+
+    /// The record in signed metadata, if the seal was made in text mode.
+    pub fn from_metadata(metadata: &str) -> Option<Self> {
+        let metadata: Value = serde_json::from_str(metadata).ok()?;
+        let content = metadata.get("content").filter(|c| c["format"] == "text")?;
+        Some(TextRecord {
+            line_endings: LineEndings::parse(content["line_endings"].as_str()?)?,
+            normalization: Normalization::parse(content["normalization"].as_str()?)?,
+        })
+    }
+}
+
+/// What verification found out about a text-mode seal.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TextReport {
+    #[serde(flatten)]
+    pub record: TextRecord,
+    /// For a copy checked against a detached signature it does not match:
+    /// whether it matches once its line endings are rewritten.
+    pub differs_only_in_line_endings: bool,
+}
+
+/// Whether a document is text that text mode accepts: UTF-8 without NUL
+/// bytes.
+pub fn is_text(data: &[u8]) -> bool {
+    !data.contains(&0) && std::str::from_utf8(data).is_ok()
+}
+
+/// Hashes a document with its line endings rewritten as LF and as CRLF, so
+/// that a copy can be compared with a sealed document's SHA-256 regardless
+/// of the line endings either has. Implements `Write`.
+pub struct LineEndingHasher {
+    lf: (Normalizer, Sha256),
+    crlf: (Normalizer, Sha256),
+}
+
+impl Default for LineEndingHasher {
+    fn default() -> Self {
+        LineEndingHasher {
+            lf: (Normalizer { ending: b"\n", pending_cr: false }, Sha256::new()),
+            crlf: (Normalizer { ending: b"\r\n", pending_cr: false }, Sha256::new()),
+        }
+    }
+}
+
+impl LineEndingHasher {
+    pub fn update(&mut self, data: &[u8]) {
+        for (normalizer, hasher) in [&mut self.lf, &mut self.crlf] {
+            normalizer.feed(data, |bytes| hasher.update(bytes));
+        }
+    }
+
+    /// Whether the document, with one line ending or the other throughout,
+    /// has SHA-256 `sealed_sha256`.
+    pub fn matches(self, sealed_sha256: &[u8; 32]) -> bool {
+        [self.lf, self.crlf].into_iter().any(|(mut normalizer, mut hasher)| {
+            normalizer.end(|bytes| hasher.update(bytes));
+            <[u8; 32]>::from(hasher.finalize()) == *sealed_sha256
+        })
+    }
+}
+
+impl Write for LineEndingHasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,258 @@
+// aegis-core/src/time.rs
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Formats a point in time as an RFC 3339 UTC timestamp, e.g. `2024-05-01T12:00:00Z`.
+pub fn rfc3339(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    let (year, month, day) = civil_from_days(days as i64);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+// Howard Hinnant's days-to-civil algorithm, valid for any date after the epoch.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// Inverse of `civil_from_days`.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 } as i64;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Parses an RFC 3339 timestamp (`Z` or `±HH:MM` offset, optional fractional
+/// seconds) into Unix seconds.
+pub fn parse_rfc3339(s: &str) -> Option<i64> {
+    let b = s.as_bytes();
+    if b.len() < 20 || b[4] != b'-' || b[7] != b'-' || !matches!(b[10], b'T' | b't' | b' ') || b[13] != b':' || b[16] != b':' {
+        return None;
+    }
+    let num = |range: std::ops::Range<usize>| s.get(range)?.parse::<u32>().ok();
+    let (year, month, day) = (num(0..4)?, num(5..7)?, num(8..10)?);
+    let (hour, minute, second) = (num(11..13)?, num(14..16)?, num(17..19)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    let mut rest = &s[19..];
+    if let Some(frac) = rest.strip_prefix('.') {
+        let digits = frac.bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0 {
+            return None;
+        }
+        rest = &frac[digits..];
+    }
+    let offset = match rest {
+        "Z" | "z" => 0,
+        _ => parse_offset(rest)?,
+    };
+    let days = days_from_civil(year as i64, month, day);
+    Some(days * 86_400 + (hour * 3600 + minute * 60 + second) as i64 - offset as i64 * 60)
+}
+
+/// Formats Unix seconds as an HTTP date (RFC 9110 IMF-fixdate), e.g.
+/// `Sun, 06 Nov 1994 08:49:37 GMT`.
+pub fn http_date(unix_secs: i64) -> String {
+    let (days, rem) = (unix_secs.div_euclid(86_400), unix_secs.rem_euclid(86_400));
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS_EN[days.rem_euclid(7) as usize],
+        day,
+        MONTHS_EN[month as usize - 1],
+        year,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+/// Parses an IMF-fixdate HTTP date into Unix seconds. The obsolete RFC 850
+/// and asctime forms are not accepted.
+pub fn parse_http_date(s: &str) -> Option<i64> {
+    let (_weekday, rest) = s.trim().split_once(", ")?;
+    let mut parts = rest.split(' ');
+    let day = parts.next()?.parse::<u32>().ok()?;
+    let month = parts.next()?;
+    let month = MONTHS_EN.iter().position(|m| *m == month)? as u32 + 1;
+    let year = parts.next()?.parse::<i64>().ok()?;
+    let time = parts.next()?;
+    if parts.next() != Some("GMT") || parts.next().is_some() || !(1..=31).contains(&day) {
+        return None;
+    }
+    let mut hms = time.split(':').map(|n| n.parse::<i64>().ok());
+    let (hour, minute, second) = (hms.next()??, hms.next()??, hms.next()??);
+    if hms.next().is_some() || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    Some(days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second)
+}
+
+/// Parses a fixed UTC offset such as `+05:30`, `-0800` or `UTC`, in minutes.
+pub fn parse_offset(s: &str) -> Option<i32> {
+    let s = s.trim();
+    if s.eq_ignore_ascii_case("utc") || s == "Z" {
+        return Some(0);
+    }
+    let s = s.strip_prefix("UTC").unwrap_or(s);
+    let (sign, digits) = match s.as_bytes().first()? {
+        b'+' => (1, &s[1..]),
+        b'-' => (-1, &s[1..]),
+        _ => return None,
+    };
+    let digits = digits.replace(':', "");
+    if digits.len() != 4 || !digits.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let (hours, minutes) = (digits[..2].parse::<i32>().ok()?, digits[2..].parse::<i32>().ok()?);
+    (hours <= 23 && minutes <= 59).then_some(sign * (hours * 60 + minutes))
+}
+
+/// Date and time layouts for human-readable output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Locale {
+    /// Canonical RFC 3339, shifted to the display offset.
+    Rfc3339,
+    EnUs,
+    EnGb,
+    De,
+    Fr,
+    Ja,
+}
+
+impl Locale {
+    /// Accepts BCP 47-style tags (`en-US`, `de`, `ja-JP`, ...) or `rfc3339`.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let tag = tag.to_ascii_lowercase().replace('_', "-");
+        Some(match tag.as_str() {
+            "rfc3339" | "iso" => Locale::Rfc3339,
+            "en-us" | "en" => Locale::EnUs,
+            "en-gb" | "en-ie" | "en-au" => Locale::EnGb,
+            t if t == "de" || t.starts_with("de-") => Locale::De,
+            t if t == "fr" || t.starts_with("fr-") => Locale::Fr,
+            t if t == "ja" || t.starts_with("ja-") => Locale::Ja,
+            _ => return None,
+        })
+    }
+}
+
+const MONTHS_EN: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+// Starting from Thursday, the weekday of the Unix epoch.
+const WEEKDAYS_EN: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+
+/// How timestamps are presented in reports and inspectors. This only affects
+/// display: signed data always keeps its canonical UTC timestamps. Offsets are
+/// fixed; there is no time zone database, so DST is not applied.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeDisplay {
+    pub offset_minutes: i32,
+    pub locale: Locale,
+}
+
+impl Default for TimeDisplay {
+    fn default() -> Self {
+        TimeDisplay { offset_minutes: 0, locale: Locale::Rfc3339 }
+    }
+}
+
+impl TimeDisplay {
+    /// Reads `AEGIS_DISPLAY_TZ` (e.g. `+02:00`) and `AEGIS_DISPLAY_LOCALE`
+    /// (e.g. `de-DE`), ignoring values that do not parse.
+    pub fn from_env() -> Self {
+        let mut display = TimeDisplay::default();
+        if let Some(offset) = std::env::var("AEGIS_DISPLAY_TZ").ok().and_then(|v| parse_offset(&v)) {
+            display.offset_minutes = offset;
+        }
+        if let Some(locale) = std::env::var("AEGIS_DISPLAY_LOCALE").ok().and_then(|v| Locale::from_tag(&v)) {
+            display.locale = locale;
+        }
+        display
+    }
+
+    fn offset_label(&self, separator: &str) -> String {
+        let sign = if self.offset_minutes < 0 { '-' } else { '+' };
+        let abs = self.offset_minutes.unsigned_abs();
+        format!("{}{:02}{}{:02}", sign, abs / 60, separator, abs % 60)
+    }
+
+    /// Renders Unix seconds in this display's offset and locale.
+    pub fn render(&self, unix_secs: i64) -> String {
+        let local = unix_secs + self.offset_minutes as i64 * 60;
+        let (days, rem) = (local.div_euclid(86_400), local.rem_euclid(86_400));
+        let (year, month, day) = civil_from_days(days);
+        let (h, m, s) = (rem / 3600, (rem % 3600) / 60, rem % 60);
+        let zone = if self.offset_minutes == 0 { "UTC".to_string() } else { format!("UTC{}", self.offset_label(":")) };
+        match self.locale {
+            Locale::Rfc3339 if self.offset_minutes == 0 => {
+                format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, h, m, s)
+            }
+            Locale::Rfc3339 => format!(
+                "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}",
+                year, month, day, h, m, s, self.offset_label(":")
+            ),
+            Locale::EnUs => {
+                let (h12, meridiem) = match h {
+                    0 => (12, "AM"),
+                    1..=11 => (h, "AM"),
+                    12 => (12, "PM"),
+                    _ => (h - 12, "PM"),
+                };
+                format!(
+                    "{} {}, {}, {}:{:02}:{:02} {} {}",
+                    MONTHS_EN[month as usize - 1], day, year, h12, m, s, meridiem, zone
+                )
+            }
+            Locale::EnGb => format!(
+                "{} {} {}, {:02}:{:02}:{:02} {}",
+                day, MONTHS_EN[month as usize - 1], year, h, m, s, zone
+            ),
+            Locale::De => format!("{:02}.{:02}.{}, {:02}:{:02}:{:02} {}", day, month, year, h, m, s, zone),
+            Locale::Fr => format!("{:02}/{:02}/{} {:02}:{:02}:{:02} {}", day, month, year, h, m, s, zone),
+            Locale::Ja => format!("{}/{:02}/{:02} {}:{:02}:{:02} {}", year, month, day, h, m, s, zone),
+        }
+    }
+
+    /// Renders an RFC 3339 string, returning it unchanged if it does not parse.
+    pub fn render_str(&self, timestamp: &str) -> String {
+        match parse_rfc3339(timestamp) {
+            Some(secs) => self.render(secs),
+            None => timestamp.to_string(),
+        }
+    }
+
+    /// Returns a copy of a JSON document with every RFC 3339 string value
+    /// rendered for display. Intended for reports, never for signed data.
+    pub fn localize_json(&self, value: &serde_json::Value) -> serde_json::Value {
+        use serde_json::Value;
+        match value {
+            Value::String(s) if parse_rfc3339(s).is_some() => Value::String(self.render_str(s)),
+            Value::Array(items) => Value::Array(items.iter().map(|v| self.localize_json(v)).collect()),
+            Value::Object(map) => Value::Object(
+                map.iter().map(|(k, v)| (k.clone(), self.localize_json(v))).collect(),
+            ),
+            other => other.clone(),
+        }
+    }
+}
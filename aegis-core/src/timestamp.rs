@@ -0,0 +1,513 @@
+// aegis-core/src/timestamp.rs
+
+// RFC 3161 trusted timestamps. A time-stamping authority (TSA) signs the
+// SHA-256 of a container's signature together with the time it saw it, which
+// proves the seal existed at that time. The token is kept in the container
+// header (`format::FIELD_TIMESTAMP_TOKEN`); the header is unsigned, and the
+// token needs no signature of ours since it already covers the signature.
+//
+// Only the DER this needs is handled here: building a `TimeStampReq`, reading
+// a `TimeStampResp`, and walking the CMS `SignedData` of a token down to its
+// `TSTInfo`. The TSA's own signature is checked when it is ECDSA P-256 with
+// SHA-256; other algorithms are reported as unchecked. The TSA's certificate
+// comes from the token itself, so anyone can mint a token with a good
+// signature for any time: a timestamp is only valid when its signature
+// checks out and the TSA is one the verifier trusts (`TsaTrust`), by a
+// pinned key fingerprint or a certificate chain to a TSA trust anchor. Either
+// way the signer's certificate, found by issuer and serial number, must be a
+// TSA certificate (critical extended key usage of id-kp-timeStamping alone),
+// so an ordinary certificate under a trusted CA cannot attest to times.
+
+use crate::der::{
+    encode, encode_unsigned, generalized_time, oid_string, Der, TAG_BOOLEAN, TAG_CONTEXT_0, TAG_GENERALIZED_TIME,
+    TAG_INTEGER, TAG_NULL, TAG_OCTET_STRING, TAG_OID, TAG_SEQUENCE, TAG_SET,
+};
+use crate::error::AegisError;
+use sha2::{Digest, Sha256};
+use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(feature = "verifier")]
+use {
+    crate::der::TAG_BIT_STRING,
+    crate::keys::Fingerprint,
+    crate::x509::{self, TrustAnchors},
+    p256::ecdsa::{signature::Verifier, Signature, VerifyingKey},
+    serde::Serialize,
+};
+
+/// Media types of the request and response bodies when talking to a TSA
+/// over HTTP.
+pub const REQUEST_CONTENT_TYPE: &str = "application/timestamp-query";
+pub const RESPONSE_CONTENT_TYPE: &str = "application/timestamp-reply";
+
+const OID_SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+const OID_SIGNED_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x02];
+const OID_TST_INFO: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x10, 0x01, 0x04];
+#[cfg(feature = "verifier")]
+const OID_CONTENT_TYPE: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x03];
+#[cfg(feature = "verifier")]
+const OID_MESSAGE_DIGEST: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x04];
+#[cfg(feature = "verifier")]
+const OID_ECDSA_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+
+fn der_error(msg: &str) -> AegisError {
+    AegisError::Crypto(format!("timestamp: {}", msg))
+}
+
+/// A `TimeStampReq` for a container signature, along with the nonce it
+/// carries so the reply can be matched to it.
+pub struct TimestampRequest {
+    der: Vec<u8>,
+    nonce: [u8; 8],
+    imprint: [u8; 32],
+}
+
+impl TimestampRequest {
+    /// Asks for a timestamp over the SHA-256 of `signature`, with the TSA's
+    /// certificate included in the token.
+    pub fn new(signature: &[u8]) -> Self {
+        let imprint: [u8; 32] = Sha256::digest(signature).into();
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+        let mut nonce_hasher = Sha256::new();
+        nonce_hasher.update(imprint);
+        nonce_hasher.update(now.to_be_bytes());
+        let nonce: [u8; 8] = nonce_hasher.finalize()[..8].try_into().expect("digest is 32 bytes");
+
+        let algorithm = encode(TAG_SEQUENCE, &[encode(TAG_OID, OID_SHA256), encode(TAG_NULL, &[])].concat());
+        let message_imprint = encode(TAG_SEQUENCE, &[algorithm, encode(TAG_OCTET_STRING, &imprint)].concat());
+        let der = encode(
+            TAG_SEQUENCE,
+            &[
+                encode(TAG_INTEGER, &[1]),
+                message_imprint,
+                encode_unsigned(&nonce),
+                encode(TAG_BOOLEAN, &[0xff]),
+            ]
+            .concat(),
+        );
+        TimestampRequest { der, nonce, imprint }
+    }
+
+    /// The DER body to POST to the TSA.
+    pub fn to_der(&self) -> &[u8] {
+        &self.der
+    }
+
+    /// Extracts the token from the TSA's `TimeStampResp`, checking that it
+    /// was granted and answers this request.
+    pub fn accept(&self, response: &[u8]) -> Result<Vec<u8>, AegisError> {
+        let mut resp = Der(response).sequence()?;
+        let mut status = resp.sequence()?;
+        let code = status.expect(TAG_INTEGER)?;
+        // 0 is granted, 1 granted with modifications.
+        if !matches!(code, [0] | [1]) {
+            let detail = Der(status.0)
+                .sequence()
+                .and_then(|mut texts| texts.element())
+                .map(|(_, text, _)| String::from_utf8_lossy(text).into_owned())
+                .unwrap_or_default();
+            return Err(der_error(&format!("TSA refused the request (status {:?}) {}", code, detail)));
+        }
+        let (_, _, token) = resp.element().map_err(|_| der_error("TSA response carries no token"))?;
+        let parsed = TimestampToken::parse(token)?;
+        if parsed.message_imprint != self.imprint {
+            return Err(der_error("token does not cover the requested signature"));
+        }
+        if parsed.nonce.as_deref().map(strip_leading_zeros) != Some(strip_leading_zeros(&self.nonce)) {
+            return Err(der_error("token nonce does not match the request"));
+        }
+        Ok(token.to_vec())
+    }
+}
+
+fn strip_leading_zeros(value: &[u8]) -> &[u8] {
+    &value[value.iter().take_while(|b| **b == 0).count()..]
+}
+
+/// Splits a CMS `ContentInfo` holding `SignedData` into its encapsulated
+/// content and the elements that follow it (certificates, CRLs and signer
+/// infos).
+fn signed_data(token: &[u8]) -> Result<(&[u8], Der<'_>), AegisError> {
+    let mut content_info = Der(token).sequence()?;
+    if content_info.expect(TAG_OID)? != OID_SIGNED_DATA {
+        return Err(der_error("token is not CMS SignedData"));
+    }
+    let mut signed = Der(content_info.expect(TAG_CONTEXT_0)?).sequence()?;
+    signed.expect(TAG_INTEGER)?;
+    signed.expect(TAG_SET)?;
+    let mut encapsulated = signed.sequence()?;
+    if encapsulated.expect(TAG_OID)? != OID_TST_INFO {
+        return Err(der_error("token does not hold a TSTInfo"));
+    }
+    let e_content = Der(encapsulated.expect(TAG_CONTEXT_0)?).expect(TAG_OCTET_STRING)?;
+    Ok((e_content, signed))
+}
+
+/// The fields of a token's `TSTInfo`.
+#[derive(Debug, Clone)]
+pub struct TimestampToken {
+    /// Dotted OID of the TSA policy the token was issued under.
+    pub policy: String,
+    pub hash_algorithm: String,
+    pub message_imprint: Vec<u8>,
+    /// Hex serial number the TSA gave the token.
+    pub serial: String,
+    /// The attested time, in Unix seconds.
+    pub time: i64,
+    pub nonce: Option<Vec<u8>>,
+}
+
+impl TimestampToken {
+    pub fn parse(token: &[u8]) -> Result<Self, AegisError> {
+        let (tst_info, _) = signed_data(token)?;
+        let mut info = Der(tst_info).sequence()?;
+        info.expect(TAG_INTEGER)?;
+        let policy = oid_string(info.expect(TAG_OID)?);
+        let mut imprint = info.sequence()?;
+        let hash_algorithm = oid_string(imprint.sequence()?.expect(TAG_OID)?);
+        let message_imprint = imprint.expect(TAG_OCTET_STRING)?.to_vec();
+        let serial = hex::encode(info.expect(TAG_INTEGER)?);
+        let time = generalized_time(info.expect(TAG_GENERALIZED_TIME)?)
+            .ok_or_else(|| der_error("invalid genTime"))?;
+        // accuracy and ordering may precede the nonce.
+        info.optional(TAG_SEQUENCE)?;
+        info.optional(TAG_BOOLEAN)?;
+        let nonce = info.optional(TAG_INTEGER)?.map(<[u8]>::to_vec);
+        Ok(TimestampToken {
+            policy,
+            hash_algorithm,
+            message_imprint,
+            serial,
+            time,
+            nonce,
+        })
+    }
+
+    /// Whether the token's imprint is the SHA-256 of `signature`.
+    pub fn covers(&self, signature: &[u8]) -> bool {
+        self.hash_algorithm == oid_string(OID_SHA256) && self.message_imprint[..] == Sha256::digest(signature)[..]
+    }
+}
+
+/// The TSAs whose timestamps a verifier accepts: keys pinned by hex
+/// fingerprint, and CA certificates that TSA certificates chain to.
+#[cfg(feature = "verifier")]
+#[derive(Clone, Debug, Default)]
+pub struct TsaTrust {
+    pub fingerprints: Vec<String>,
+    pub anchors: TrustAnchors,
+}
+
+#[cfg(feature = "verifier")]
+impl TsaTrust {
+    pub fn is_empty(&self) -> bool {
+        self.fingerprints.is_empty() && self.anchors.is_empty()
+    }
+
+    /// Whether the TSA with `fingerprint` and certificate chain `chain`
+    /// (DER, leaf first) is trusted, its certificates checked at `at`.
+    fn trusts(&self, fingerprint: &Fingerprint, key: &[u8], chain: &[Vec<u8>], at: i64) -> bool {
+        self.fingerprints.iter().any(|pinned| pinned.eq_ignore_ascii_case(&fingerprint.to_hex()))
+            || (!self.anchors.is_empty() && x509::verify_chain(chain, key, &self.anchors, at).valid)
+    }
+}
+
+/// What verification found out about a container's timestamp.
+#[cfg(feature = "verifier")]
+#[derive(Debug, Clone, Serialize)]
+pub struct TimestampReport {
+    /// The token covers the container's signature, its TSA signature
+    /// verifies, and the TSA is trusted. Anything else is unverified, and
+    /// the attested time is not to be relied on.
+    pub valid: bool,
+    /// The time the TSA attests to, as RFC 3339.
+    pub attested_time: Option<String>,
+    pub imprint_matches: bool,
+    /// `None` when the TSA signed with an algorithm other than ECDSA P-256.
+    pub tsa_signature_valid: Option<bool>,
+    /// Hex fingerprint of the TSA's P-256 key, for pinning trusted TSAs.
+    pub tsa_key_fingerprint: Option<String>,
+    /// The TSA is pinned or chains to a TSA trust anchor.
+    pub tsa_trusted: bool,
+    pub serial: Option<String>,
+    pub policy: Option<String>,
+    /// Why the token could not be read, if it could not.
+    pub error: Option<String>,
+}
+
+/// Checks a timestamp token against the container signature it should
+/// cover and the TSAs `trust` accepts. Never fails; a token that cannot be
+/// read is reported as invalid.
+#[cfg(feature = "verifier")]
+pub fn verify(token: &[u8], signature: &[u8], trust: &TsaTrust) -> TimestampReport {
+    let parsed = match TimestampToken::parse(token) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            return TimestampReport {
+                valid: false,
+                attested_time: None,
+                imprint_matches: false,
+                tsa_signature_valid: None,
+                tsa_key_fingerprint: None,
+                tsa_trusted: false,
+                serial: None,
+                policy: None,
+                error: Some(e.to_string()),
+            };
+        }
+    };
+    let imprint_matches = parsed.covers(signature);
+    let (tsa_signature_valid, tsa_key_fingerprint, tsa_trusted, error) = match verify_tsa_signature(token) {
+        Ok(Some(signer)) => {
+            let fingerprint = Fingerprint::of(&signer.key);
+            let trusted = signer.valid
+                && signer.time_stamping
+                && trust.trusts(&fingerprint, &signer.key, &signer.chain, parsed.time);
+            let error = (signer.valid && !signer.time_stamping)
+                .then(|| "the signer's certificate is not for time stamping".into());
+            (Some(signer.valid), Some(fingerprint.to_hex()), trusted, error)
+        }
+        Ok(None) => (None, None, false, Some("the TSA signature algorithm is not supported".into())),
+        Err(e) => (Some(false), None, false, Some(e.to_string())),
+    };
+    let error = error.or_else(|| (!tsa_trusted && tsa_signature_valid == Some(true)).then(|| "the TSA is not trusted".into()));
+    TimestampReport {
+        valid: imprint_matches && tsa_signature_valid == Some(true) && tsa_trusted,
+        attested_time: u64::try_from(parsed.time)
+            .ok()
+            .map(|secs| crate::time::rfc3339(UNIX_EPOCH + std::time::Duration::from_secs(secs))),
+        imprint_matches,
+        tsa_signature_valid,
+        tsa_key_fingerprint,
+        tsa_trusted,
+        serial: Some(parsed.serial),
+        policy: Some(parsed.policy),
+        error,
+    }
+}
+
+/// The TSA that signed a token, as `verify_tsa_signature()` found it.
+#[cfg(feature = "verifier")]
+struct TsaSigner {
+    /// The signature verifies and covers the `TSTInfo`.
+    valid: bool,
+    /// The signer's certificate is a TSA certificate.
+    time_stamping: bool,
+    /// SEC1 key of the signer.
+    key: Vec<u8>,
+    /// The token's certificates, DER, the signer's first.
+    chain: Vec<Vec<u8>>,
+}
+
+/// Checks the first signer of the token against the certificate in the
+/// token with the matching issuer and serial number. `None` if that signer
+/// did not use ECDSA P-256 with SHA-256.
+#[cfg(feature = "verifier")]
+fn verify_tsa_signature(token: &[u8]) -> Result<Option<TsaSigner>, AegisError> {
+    let (tst_info, mut rest) = signed_data(token)?;
+    let certificates = rest.optional(TAG_CONTEXT_0)?.ok_or_else(|| der_error("token carries no TSA certificate"))?;
+    rest.optional(0xa1)?;
+    let mut signer = Der(rest.expect(TAG_SET)?).sequence()?;
+    signer.expect(TAG_INTEGER)?;
+    let mut sid = signer.sequence().map_err(|_| der_error("signer is not identified by issuer and serial"))?;
+    let (tag, _, signer_issuer) = sid.element()?;
+    if tag != TAG_SEQUENCE {
+        return Err(der_error("signer is not identified by issuer and serial"));
+    }
+    let signer_serial = sid.expect(TAG_INTEGER)?;
+    signer.sequence()?;
+    let (tag, attributes, attributes_element) = signer.element()?;
+    if tag != TAG_CONTEXT_0 {
+        return Err(der_error("signer has no signed attributes"));
+    }
+    if signer.sequence()?.expect(TAG_OID)? != OID_ECDSA_SHA256 {
+        return Ok(None);
+    }
+    let signature = signer.expect(TAG_OCTET_STRING)?;
+
+    // Both attributes are required (RFC 5652, section 5.3): the content type
+    // ties the signature to a TSTInfo, the digest to this one.
+    let (mut digest_matches, mut content_type_matches) = (false, false);
+    let mut attributes = Der(attributes);
+    while !attributes.is_empty() {
+        let mut attribute = attributes.sequence()?;
+        match attribute.expect(TAG_OID)? {
+            OID_MESSAGE_DIGEST => {
+                let digest = Der(attribute.expect(TAG_SET)?).expect(TAG_OCTET_STRING)?;
+                digest_matches = digest[..] == Sha256::digest(tst_info)[..];
+            }
+            OID_CONTENT_TYPE => {
+                content_type_matches = Der(attribute.expect(TAG_SET)?).expect(TAG_OID)? == OID_TST_INFO;
+            }
+            _ => {}
+        }
+    }
+
+    let (key, chain) = tsa_key(certificates, signer_issuer, signer_serial)?;
+    let time_stamping = x509::Certificate::from_der(&chain[0]).is_ok_and(|c| c.is_time_stamping());
+    // Signed attributes are signed as a SET, not with their implicit tag.
+    let mut signed = attributes_element.to_vec();
+    signed[0] = TAG_SET;
+    let signature_valid = Signature::from_der(signature)
+        .map(|signature| key.verify(&signed, &signature).is_ok())
+        .unwrap_or(false);
+    Ok(Some(TsaSigner {
+        valid: digest_matches && content_type_matches && signature_valid,
+        time_stamping,
+        key: key.to_sec1_bytes().to_vec(),
+        chain,
+    }))
+}
+
+/// The P-256 key of the certificate `issuer` (a DER `Name`) gave `serial`,
+/// and the token's certificates with that one first.
+#[cfg(feature = "verifier")]
+fn tsa_key(certificates: &[u8], issuer: &[u8], serial: &[u8]) -> Result<(VerifyingKey, Vec<Vec<u8>>), AegisError> {
+    let mut all = Vec::new();
+    let mut reader = Der(certificates);
+    while !reader.is_empty() {
+        all.push(reader.element()?.2.to_vec());
+    }
+    for (index, certificate) in all.iter().enumerate() {
+        let mut tbs = Der(certificate).sequence()?.sequence()?;
+        tbs.optional(TAG_CONTEXT_0)?;
+        if tbs.expect(TAG_INTEGER)? != serial {
+            continue;
+        }
+        tbs.sequence()?;
+        if tbs.element()?.2 != issuer {
+            continue;
+        }
+        // validity, subject
+        for _ in 0..2 {
+            tbs.element()?;
+        }
+        let mut spki = tbs.sequence()?;
+        spki.sequence()?;
+        let bits = spki.expect(TAG_BIT_STRING)?;
+        let sec1 = bits.get(1..).ok_or_else(|| der_error("empty TSA public key"))?;
+        let key = VerifyingKey::from_sec1_bytes(sec1).map_err(|_| der_error("TSA key is not a P-256 key"))?;
+        let mut chain = vec![certificate.clone()];
+        chain.extend(all.iter().enumerate().filter(|(i, _)| *i != index).map(|(_, c)| c.clone()));
+        return Ok((key, chain));
+    }
+    Err(der_error("token does not include the TSA's certificate"))
+}
+
+#[cfg(all(test, feature = "verifier"))]
+mod tests {
+    use super::*;
+    use crate::test_util::{TestCertificate, TestTimestamp};
+    use crate::x509::Certificate;
+
+    const SIGNATURE: &[u8] = b"container signature";
+    // 2024-06-01.
+    const TIME: i64 = 1_717_200_000;
+
+    fn root() -> TestCertificate {
+        TestCertificate::ca("Test TSA Root", 10)
+    }
+
+    fn anchored() -> TsaTrust {
+        TsaTrust {
+            fingerprints: Vec::new(),
+            anchors: TrustAnchors::new(vec![Certificate::from_der(&root().self_signed()).unwrap()]),
+        }
+    }
+
+    #[test]
+    fn trusts_a_tsa_chaining_to_an_anchor() {
+        let token = TestTimestamp::new(&TestCertificate::tsa("Test TSA", 11), &root(), TIME).token(SIGNATURE);
+        let report = verify(&token, SIGNATURE, &anchored());
+        assert!(report.valid, "{:?}", report.error);
+        assert!(report.imprint_matches && report.tsa_trusted);
+        assert_eq!(report.attested_time.as_deref(), Some("2024-06-01T00:00:00Z"));
+    }
+
+    #[test]
+    fn trusts_a_pinned_tsa() {
+        let tsa = TestCertificate::tsa("Test TSA", 11);
+        let token = TestTimestamp::new(&tsa, &root(), TIME).token(SIGNATURE);
+        let trust = TsaTrust {
+            fingerprints: vec![Fingerprint::of(&tsa.public_key()).to_hex()],
+            anchors: TrustAnchors::default(),
+        };
+        assert!(verify(&token, SIGNATURE, &trust).valid);
+    }
+
+    #[test]
+    fn trusts_no_tsa_by_default() {
+        let token = TestTimestamp::new(&TestCertificate::tsa("Test TSA", 11), &root(), TIME).token(SIGNATURE);
+        let report = verify(&token, SIGNATURE, &TsaTrust::default());
+        assert_eq!(report.tsa_signature_valid, Some(true));
+        assert!(!report.valid && !report.tsa_trusted);
+    }
+
+    #[test]
+    fn rejects_a_token_for_another_signature() {
+        let token = TestTimestamp::new(&TestCertificate::tsa("Test TSA", 11), &root(), TIME).token(b"other");
+        let report = verify(&token, SIGNATURE, &anchored());
+        assert!(!report.valid && !report.imprint_matches);
+    }
+
+    #[test]
+    fn rejects_a_backdated_token_from_a_leaf_that_is_not_a_tsa() {
+        // An ordinary signing certificate under the trusted CA, minting a
+        // token for a time of its choosing.
+        let leaf = TestCertificate::leaf("Photo Desk", 12);
+        let token = TestTimestamp::new(&leaf, &root(), 1_600_000_000).token(SIGNATURE);
+        let report = verify(&token, SIGNATURE, &anchored());
+        assert_eq!(report.tsa_signature_valid, Some(true));
+        assert!(!report.valid && !report.tsa_trusted);
+        assert_eq!(report.error.as_deref(), Some("the signer's certificate is not for time stamping"));
+    }
+
+    #[test]
+    fn requires_a_critical_time_stamping_purpose() {
+        let mut tsa = TestCertificate::tsa("Test TSA", 11);
+        tsa.extended_key_usage = Some((vec![crate::test_util::OID_TIME_STAMPING], false));
+        let token = TestTimestamp::new(&tsa, &root(), TIME).token(SIGNATURE);
+        assert!(!verify(&token, SIGNATURE, &anchored()).tsa_trusted);
+        // Nor will a pinned key do without it.
+        let trust = TsaTrust {
+            fingerprints: vec![Fingerprint::of(&tsa.public_key()).to_hex()],
+            anchors: TrustAnchors::default(),
+        };
+        assert!(!verify(&token, SIGNATURE, &trust).valid);
+    }
+
+    #[test]
+    fn rejects_a_token_attested_before_the_tsa_certificate() {
+        let mut tsa = TestCertificate::tsa("Test TSA", 11);
+        tsa.not_before = TIME;
+        let token = TestTimestamp::new(&tsa, &root(), TIME - 86_400).token(SIGNATURE);
+        let report = verify(&token, SIGNATURE, &anchored());
+        assert_eq!(report.tsa_signature_valid, Some(true));
+        assert!(!report.valid && !report.tsa_trusted);
+    }
+
+    #[test]
+    fn matches_the_signer_by_issuer_and_serial() {
+        let tsa = TestCertificate::tsa("Test TSA", 11);
+        let mut timestamp = TestTimestamp::new(&tsa, &root(), TIME);
+        timestamp.issuer = "Another CA".into();
+        let report = verify(&timestamp.token(SIGNATURE), SIGNATURE, &anchored());
+        assert_eq!(report.tsa_signature_valid, Some(false));
+        assert!(!report.valid);
+        assert!(report.error.unwrap().contains("does not include the TSA's certificate"));
+    }
+
+    #[test]
+    fn requires_the_content_type_attribute() {
+        let mut timestamp = TestTimestamp::new(&TestCertificate::tsa("Test TSA", 11), &root(), TIME);
+        timestamp.content_type = false;
+        let report = verify(&timestamp.token(SIGNATURE), SIGNATURE, &anchored());
+        assert_eq!(report.tsa_signature_valid, Some(false));
+        assert!(!report.valid);
+    }
+
+    #[test]
+    fn reports_unreadable_tokens() {
+        let report = verify(b"not a token", SIGNATURE, &anchored());
+        assert!(!report.valid && report.error.is_some());
+    }
+}
@@ -0,0 +1,696 @@
+// aegis-core/src/x509.rs
+
+// X.509 certificate chains, so that a seal can be tied to the organization
+// that holds the key rather than to a bare public key. A container carries
+// the chain in its header (`format::FIELD_CERTIFICATE_CHAIN`), leaf first,
+// and verifiers check it against trust anchors of their own choosing.
+//
+// A chain is valid when its leaf certifies the container's public key, each
+// certificate is signed by the one after it, every issuer is a CA, every
+// certificate is within its validity period at the time of checking, and
+// the last certificate is a trust anchor or is signed by one. Only ECDSA
+// P-256 with SHA-256 signatures are checked, so a chain through an RSA or
+// P-384 CA is reported as not verifiable.
+//
+// As in RFC 5280 path validation, an issuer's path length constraint bounds
+// the CAs below it, and an issuer with a key usage extension must have
+// keyCertSign. The leaf must not be a CA, and with a key usage extension
+// must have digitalSignature. Name constraints, policies and revocation are
+// not checked, so a certificate in the chain (or an anchor) with a critical
+// extension other than basic constraints, key usage, extended key usage or
+// subject alternative name makes the chain invalid rather than have its
+// constraints ignored. The extended key usage is read only to tell
+// time-stamping authorities (`timestamp`) apart from other certificates.
+
+use crate::der::{
+    generalized_time, oid_string, utc_time, Der, TAG_BIT_STRING, TAG_BOOLEAN, TAG_CONTEXT_0, TAG_GENERALIZED_TIME,
+    TAG_INTEGER, TAG_OCTET_STRING, TAG_OID, TAG_SEQUENCE, TAG_SET, TAG_UTC_TIME,
+};
+use crate::error::AegisError;
+use base64ct::{Base64, Encoding};
+use sha2::{Digest, Sha256};
+#[cfg(feature = "verifier")]
+use {
+    p256::ecdsa::{signature::Verifier, Signature, VerifyingKey},
+    serde::Serialize,
+    std::time::{Duration, UNIX_EPOCH},
+};
+
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const OID_P256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+#[cfg(feature = "verifier")]
+const OID_ECDSA_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+const OID_BASIC_CONSTRAINTS: &[u8] = &[0x55, 0x1d, 0x13];
+const OID_EXTENDED_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x25];
+const OID_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x0f];
+const OID_SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+const OID_TIME_STAMPING: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x08];
+
+const TAG_EXTENSIONS: u8 = 0xa3;
+
+// Key usage bits, numbered as in RFC 5280.
+#[cfg(feature = "verifier")]
+const DIGITAL_SIGNATURE: u32 = 0;
+#[cfg(feature = "verifier")]
+const KEY_CERT_SIGN: u32 = 5;
+
+// Short names for the attributes of distinguished names.
+const ATTRIBUTE_NAMES: &[(&str, &str)] = &[
+    ("2.5.4.3", "CN"),
+    ("2.5.4.6", "C"),
+    ("2.5.4.7", "L"),
+    ("2.5.4.8", "ST"),
+    ("2.5.4.10", "O"),
+    ("2.5.4.11", "OU"),
+    ("1.2.840.113549.1.9.1", "emailAddress"),
+];
+
+fn cert_error(msg: &str) -> AegisError {
+    AegisError::InvalidCertificate(msg.to_string())
+}
+
+/// One parsed certificate. Only the parts chain validation needs are read.
+#[derive(Clone, Debug)]
+pub struct Certificate {
+    der: Vec<u8>,
+    // Read only to verify the certificate's signature.
+    #[cfg_attr(not(feature = "verifier"), allow(dead_code))]
+    tbs: Vec<u8>,
+    #[cfg_attr(not(feature = "verifier"), allow(dead_code))]
+    signature_algorithm: Vec<u8>,
+    #[cfg_attr(not(feature = "verifier"), allow(dead_code))]
+    signature: Vec<u8>,
+    issuer: Vec<u8>,
+    subject: Vec<u8>,
+    /// Start of the validity period, in Unix seconds.
+    pub not_before: i64,
+    /// End of the validity period, in Unix seconds.
+    pub not_after: i64,
+    /// SEC1 key, if the subject key is a P-256 key.
+    public_key: Option<Vec<u8>>,
+    is_ca: bool,
+    /// Most CAs that may follow this one in a chain, from its basic
+    /// constraints.
+    #[cfg_attr(not(feature = "verifier"), allow(dead_code))]
+    path_len: Option<u64>,
+    /// The key usage bits, bit 0 (digitalSignature) first, if the extension
+    /// is present.
+    #[cfg_attr(not(feature = "verifier"), allow(dead_code))]
+    key_usage: Option<Vec<bool>>,
+    /// Dotted OID of the first critical extension this module does not
+    /// process.
+    #[cfg_attr(not(feature = "verifier"), allow(dead_code))]
+    unknown_critical: Option<String>,
+    /// Purposes named by the extended key usage extension, and whether it
+    /// is critical.
+    extended_key_usage: Option<(bool, Vec<Vec<u8>>)>,
+}
+
+impl Certificate {
+    pub fn from_der(der: &[u8]) -> Result<Self, AegisError> {
+        let mut outer = Der(der);
+        let mut certificate = outer.sequence()?;
+        if !outer.is_empty() {
+            return Err(cert_error("trailing data after certificate"));
+        }
+        let (tag, tbs_contents, tbs) = certificate.element()?;
+        if tag != TAG_SEQUENCE {
+            return Err(cert_error("missing TBSCertificate"));
+        }
+        let (tag, algorithm, outer_algorithm) = certificate.element()?;
+        if tag != TAG_SEQUENCE {
+            return Err(cert_error("missing signature algorithm"));
+        }
+        let signature_algorithm = Der(algorithm).expect(TAG_OID)?.to_vec();
+        let signature = certificate
+            .expect(TAG_BIT_STRING)?
+            .get(1..)
+            .ok_or_else(|| cert_error("empty signature"))?
+            .to_vec();
+
+        let mut tbs_fields = Der(tbs_contents);
+        tbs_fields.optional(TAG_CONTEXT_0)?;
+        tbs_fields.expect(TAG_INTEGER)?;
+        // RFC 5280 requires the signed copy of the algorithm to match, so
+        // that it cannot be swapped out after signing.
+        if tbs_fields.element()?.2 != outer_algorithm {
+            return Err(cert_error("signature algorithm differs from the one in the signed certificate"));
+        }
+        let (_, _, issuer) = tbs_fields.element()?;
+        let mut validity = tbs_fields.sequence()?;
+        let not_before = time(&mut validity)?;
+        let not_after = time(&mut validity)?;
+        let (_, _, subject) = tbs_fields.element()?;
+        let mut spki = tbs_fields.sequence()?;
+        let mut algorithm = spki.sequence()?;
+        let key_type = algorithm.expect(TAG_OID)?;
+        let curve = algorithm.optional(TAG_OID)?;
+        let key_bits = spki.expect(TAG_BIT_STRING)?;
+        let public_key = (key_type == OID_EC_PUBLIC_KEY && curve == Some(OID_P256))
+            .then(|| key_bits.get(1..).map(<[u8]>::to_vec))
+            .flatten();
+        // Unique identifiers may precede the extensions.
+        tbs_fields.optional(0x81)?;
+        tbs_fields.optional(0x82)?;
+        let (mut is_ca, mut path_len, mut key_usage, mut extended_key_usage) = (false, None, None, None);
+        let mut unknown_critical = None;
+        if let Some(extensions) = tbs_fields.optional(TAG_EXTENSIONS)? {
+            let mut extensions = Der(extensions).sequence()?;
+            while !extensions.is_empty() {
+                let mut extension = extensions.sequence()?;
+                let id = extension.expect(TAG_OID)?;
+                let critical = extension.optional(TAG_BOOLEAN)?.is_some_and(|v| v != [0]);
+                let value = extension.expect(TAG_OCTET_STRING)?;
+                if id == OID_BASIC_CONSTRAINTS {
+                    let mut constraints = Der(value).sequence()?;
+                    is_ca = constraints.optional(TAG_BOOLEAN)?.is_some_and(|v| v != [0]);
+                    path_len = match constraints.optional(TAG_INTEGER)? {
+                        Some(n) if n.len() <= 8 && n.first().is_none_or(|b| b & 0x80 == 0) => {
+                            Some(n.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64))
+                        }
+                        Some(_) => return Err(cert_error("invalid path length constraint")),
+                        None => None,
+                    };
+                } else if id == OID_KEY_USAGE {
+                    let bits = Der(value).expect(TAG_BIT_STRING)?;
+                    let bytes = bits.get(1..).ok_or_else(|| cert_error("empty key usage"))?;
+                    key_usage = Some(bytes.iter().flat_map(|b| (0..8).map(move |i| b & (0x80 >> i) != 0)).collect());
+                } else if id == OID_EXTENDED_KEY_USAGE {
+                    let mut purposes = Der(value).sequence()?;
+                    let mut ids = Vec::new();
+                    while !purposes.is_empty() {
+                        ids.push(purposes.expect(TAG_OID)?.to_vec());
+                    }
+                    extended_key_usage = Some((critical, ids));
+                } else if critical && id != OID_SUBJECT_ALT_NAME && unknown_critical.is_none() {
+                    unknown_critical = Some(oid_string(id));
+                }
+            }
+        }
+
+        Ok(Certificate {
+            der: der.to_vec(),
+            tbs: tbs.to_vec(),
+            signature_algorithm,
+            signature,
+            issuer: issuer.to_vec(),
+            subject: subject.to_vec(),
+            not_before,
+            not_after,
+            public_key,
+            is_ca,
+            path_len,
+            key_usage,
+            unknown_critical,
+            extended_key_usage,
+        })
+    }
+
+    pub fn to_der(&self) -> &[u8] {
+        &self.der
+    }
+
+    /// The subject's distinguished name, e.g. `CN=Photo Desk, O=Acme News`.
+    pub fn subject(&self) -> String {
+        name_string(&self.subject)
+    }
+
+    pub fn issuer(&self) -> String {
+        name_string(&self.issuer)
+    }
+
+    /// The subject's SEC1 public key, if it is a P-256 key.
+    pub fn public_key(&self) -> Option<&[u8]> {
+        self.public_key.as_deref()
+    }
+
+    /// Whether the basic constraints extension marks this as a CA.
+    pub fn is_ca(&self) -> bool {
+        self.is_ca
+    }
+
+    /// Whether this certificate is for a time-stamping authority: as RFC 3161
+    /// requires, its extended key usage is critical and names
+    /// id-kp-timeStamping alone.
+    pub fn is_time_stamping(&self) -> bool {
+        matches!(&self.extended_key_usage, Some((true, ids)) if ids.len() == 1 && ids[0] == OID_TIME_STAMPING)
+    }
+
+    /// Hex SHA-256 of the DER certificate.
+    pub fn fingerprint(&self) -> String {
+        hex::encode(Sha256::digest(&self.der))
+    }
+
+    /// Whether the key usage extension, if there is one, grants usage `bit`.
+    #[cfg(feature = "verifier")]
+    fn allows(&self, bit: u32) -> bool {
+        self.key_usage.as_ref().is_none_or(|bits| bits.get(bit as usize).copied().unwrap_or(false))
+    }
+
+    /// Fails if this certificate has a critical extension that is not
+    /// processed here.
+    #[cfg(feature = "verifier")]
+    fn check_extensions(&self) -> Result<(), AegisError> {
+        match &self.unknown_critical {
+            Some(oid) => Err(cert_error(&format!(
+                "'{}' has a critical extension {} that is not supported",
+                self.subject(),
+                oid
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Fails unless this certificate may issue others with `below` CAs
+    /// between it and the leaf.
+    #[cfg(feature = "verifier")]
+    fn check_issuer(&self, below: usize) -> Result<(), AegisError> {
+        if !self.is_ca() {
+            return Err(cert_error(&format!("'{}' is not a CA", self.subject())));
+        }
+        if !self.allows(KEY_CERT_SIGN) {
+            return Err(cert_error(&format!("'{}' is not allowed to sign certificates", self.subject())));
+        }
+        if let Some(max) = self.path_len
+            && below as u64 > max
+        {
+            return Err(cert_error(&format!("'{}' allows no more than {} CAs below it", self.subject(), max)));
+        }
+        Ok(())
+    }
+
+    /// Whether `issuer`'s key made this certificate's signature.
+    #[cfg(feature = "verifier")]
+    fn signed_by(&self, issuer: &Certificate) -> Result<bool, AegisError> {
+        if self.signature_algorithm != OID_ECDSA_SHA256 {
+            return Err(cert_error(&format!(
+                "'{}' is signed with {}, not ECDSA P-256 with SHA-256",
+                self.subject(),
+                oid_string(&self.signature_algorithm)
+            )));
+        }
+        let key = issuer
+            .public_key()
+            .and_then(|key| VerifyingKey::from_sec1_bytes(key).ok())
+            .ok_or_else(|| cert_error(&format!("'{}' does not have a P-256 key", issuer.subject())))?;
+        Ok(Signature::from_der(&self.signature).is_ok_and(|signature| key.verify(&self.tbs, &signature).is_ok()))
+    }
+}
+
+fn time(validity: &mut Der) -> Result<i64, AegisError> {
+    match validity.element()? {
+        (TAG_UTC_TIME, value, _) => utc_time(value),
+        (TAG_GENERALIZED_TIME, value, _) => generalized_time(value),
+        _ => None,
+    }
+    .ok_or_else(|| cert_error("invalid validity time"))
+}
+
+/// A DER `Name` as comma-separated `TYPE=value` pairs, in encoded order.
+fn name_string(name: &[u8]) -> String {
+    let mut parts = Vec::new();
+    let mut rdns = Der(name).sequence().unwrap_or(Der(&[]));
+    while let Ok(set) = rdns.expect(TAG_SET) {
+        let mut attributes = Der(set);
+        while let Ok(mut attribute) = attributes.sequence() {
+            let (Ok(oid), Ok((_, value, _))) = (attribute.expect(TAG_OID), attribute.element()) else {
+                break;
+            };
+            let oid = oid_string(oid);
+            let label = ATTRIBUTE_NAMES
+                .iter()
+                .find(|(id, _)| *id == oid)
+                .map_or(oid.as_str(), |(_, label)| label);
+            parts.push(format!("{}={}", label, String::from_utf8_lossy(value)));
+        }
+    }
+    parts.join(", ")
+}
+
+/// The `CERTIFICATE` blocks of a PEM file, in order.
+pub fn certificates_from_pem(text: &str) -> Result<Vec<Certificate>, AegisError> {
+    let mut certificates = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("-----BEGIN CERTIFICATE-----") {
+        let body = &rest[start + "-----BEGIN CERTIFICATE-----".len()..];
+        let end = body
+            .find("-----END CERTIFICATE-----")
+            .ok_or_else(|| cert_error("unterminated PEM certificate"))?;
+        let encoded: String = body[..end].chars().filter(|c| !c.is_ascii_whitespace()).collect();
+        let der = Base64::decode_vec(&encoded).map_err(|_| cert_error("invalid base64 in PEM certificate"))?;
+        certificates.push(Certificate::from_der(&der)?);
+        rest = &body[end..];
+    }
+    if certificates.is_empty() {
+        return Err(cert_error("no PEM certificates found"));
+    }
+    Ok(certificates)
+}
+
+/// Checks that `chain` is fit to embed in containers sealed with
+/// `public_key`: it has a leaf, and the leaf certifies that key.
+pub fn check_leaf(chain: &[Certificate], public_key: &[u8]) -> Result<(), AegisError> {
+    let leaf = chain.first().ok_or_else(|| cert_error("certificate chain is empty"))?;
+    if leaf.public_key() != Some(public_key) {
+        return Err(cert_error(&format!(
+            "leaf certificate '{}' does not certify the signing key",
+            leaf.subject()
+        )));
+    }
+    Ok(())
+}
+
+/// The certificates a verifier trusts to vouch for sealing keys, typically
+/// an organization's root CA.
+#[cfg(feature = "verifier")]
+#[derive(Clone, Debug, Default)]
+pub struct TrustAnchors {
+    certificates: Vec<Certificate>,
+}
+
+#[cfg(feature = "verifier")]
+impl TrustAnchors {
+    pub fn new(certificates: Vec<Certificate>) -> Self {
+        TrustAnchors { certificates }
+    }
+
+    /// Every certificate in a PEM bundle.
+    pub fn from_pem(text: &str) -> Result<Self, AegisError> {
+        certificates_from_pem(text).map(Self::new)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.certificates.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.certificates.len()
+    }
+
+    /// The anchor that is `certificate`, or that signed it.
+    fn anchor_for(&self, certificate: &Certificate) -> Option<&Certificate> {
+        self.certificates.iter().find(|anchor| anchor.der == certificate.der).or_else(|| {
+            self.certificates.iter().find(|anchor| {
+                anchor.subject == certificate.issuer && certificate.signed_by(anchor).unwrap_or(false)
+            })
+        })
+    }
+}
+
+/// What verification found out about a container's certificate chain.
+#[cfg(feature = "verifier")]
+#[derive(Debug, Clone, Serialize)]
+pub struct ChainReport {
+    /// The chain certifies the sealing key and leads to a trust anchor.
+    pub valid: bool,
+    /// Subject of the leaf certificate: who the seal identifies as.
+    pub subject: Option<String>,
+    pub issuer: Option<String>,
+    /// Validity period of the leaf certificate, as RFC 3339.
+    pub not_before: Option<String>,
+    pub not_after: Option<String>,
+    /// Subject of the trust anchor the chain leads to.
+    pub anchor: Option<String>,
+    /// Number of certificates the container carries.
+    pub length: usize,
+    /// Why the chain is not valid, if it is not.
+    pub error: Option<String>,
+}
+
+/// Checks `chain` (DER, leaf first) for a container sealed with
+/// `public_key` against `anchors`, with validity periods checked at `at`
+/// (Unix seconds). Never fails; a chain that cannot be read is reported as
+/// invalid.
+#[cfg(feature = "verifier")]
+pub fn verify_chain(chain: &[Vec<u8>], public_key: &[u8], anchors: &TrustAnchors, at: i64) -> ChainReport {
+    let rfc3339 = |secs: i64| crate::time::rfc3339(UNIX_EPOCH + Duration::from_secs(secs.max(0) as u64));
+    let mut report = ChainReport {
+        valid: false,
+        subject: None,
+        issuer: None,
+        not_before: None,
+        not_after: None,
+        anchor: None,
+        length: chain.len(),
+        error: None,
+    };
+    let certificates = match chain.iter().map(|der| Certificate::from_der(der)).collect::<Result<Vec<_>, _>>() {
+        Ok(certificates) => certificates,
+        Err(e) => {
+            report.error = Some(e.to_string());
+            return report;
+        }
+    };
+    if let Some(leaf) = certificates.first() {
+        report.subject = Some(leaf.subject());
+        report.issuer = Some(leaf.issuer());
+        report.not_before = Some(rfc3339(leaf.not_before));
+        report.not_after = Some(rfc3339(leaf.not_after));
+    }
+    match check_chain(&certificates, public_key, anchors, at) {
+        Ok(anchor) => {
+            report.valid = true;
+            report.anchor = Some(anchor.subject());
+        }
+        Err(e) => report.error = Some(e.to_string()),
+    }
+    report
+}
+
+#[cfg(feature = "verifier")]
+fn check_chain<'a>(
+    chain: &[Certificate],
+    public_key: &[u8],
+    anchors: &'a TrustAnchors,
+    at: i64,
+) -> Result<&'a Certificate, AegisError> {
+    check_leaf(chain, public_key)?;
+    let leaf = &chain[0];
+    if leaf.is_ca() {
+        return Err(cert_error(&format!("leaf certificate '{}' is a CA", leaf.subject())));
+    }
+    if !leaf.allows(DIGITAL_SIGNATURE) {
+        return Err(cert_error(&format!("leaf certificate '{}' is not for digital signatures", leaf.subject())));
+    }
+    for certificate in chain {
+        if at < certificate.not_before || at > certificate.not_after {
+            return Err(cert_error(&format!("'{}' is not valid at the time checked", certificate.subject())));
+        }
+        certificate.check_extensions()?;
+    }
+    for (below, pair) in chain.windows(2).enumerate() {
+        let (certificate, issuer) = (&pair[0], &pair[1]);
+        if certificate.issuer != issuer.subject {
+            return Err(cert_error(&format!(
+                "'{}' is not issued by the next certificate, '{}'",
+                certificate.subject(),
+                issuer.subject()
+            )));
+        }
+        issuer.check_issuer(below)?;
+        if !certificate.signed_by(issuer)? {
+            return Err(cert_error(&format!("signature on '{}' does not verify", certificate.subject())));
+        }
+    }
+    if anchors.is_empty() {
+        return Err(cert_error("no trust anchors configured"));
+    }
+    let last = chain.last().expect("chain has a leaf");
+    let anchor = anchors
+        .anchor_for(last)
+        .ok_or_else(|| cert_error(&format!("chain ends at '{}', which is not a trust anchor", last.issuer())))?;
+    if anchor.der != last.der {
+        if at < anchor.not_before || at > anchor.not_after {
+            return Err(cert_error(&format!("trust anchor '{}' is not a valid CA", anchor.subject())));
+        }
+        anchor.check_extensions()?;
+        anchor.check_issuer(chain.len() - 1)?;
+    }
+    Ok(anchor)
+}
+
+#[cfg(all(test, feature = "verifier"))]
+mod tests {
+    use super::*;
+    use crate::test_util::{TestCertificate, DIGITAL_SIGNATURE, KEY_CERT_SIGN, NOT_AFTER};
+
+    const AT: i64 = 1_700_000_000;
+    const OID_NAME_CONSTRAINTS: &[u8] = &[0x55, 0x1d, 0x1e];
+    const OID_ECDSA_SHA384: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x03];
+
+    struct Pki {
+        root: TestCertificate,
+        intermediate: TestCertificate,
+        leaf: TestCertificate,
+    }
+
+    impl Pki {
+        fn new() -> Self {
+            Pki {
+                root: TestCertificate::ca("Test Root", 10),
+                intermediate: TestCertificate::ca("Test Intermediate", 11),
+                leaf: TestCertificate::leaf("Test Sealer", 12),
+            }
+        }
+
+        /// Leaf and intermediate, checked against the root as the only
+        /// anchor.
+        fn verify(&self) -> ChainReport {
+            let chain = vec![self.leaf.issued_by(&self.intermediate), self.intermediate.issued_by(&self.root)];
+            self.verify_chain(&chain)
+        }
+
+        fn verify_chain(&self, chain: &[Vec<u8>]) -> ChainReport {
+            let anchors = TrustAnchors::new(vec![Certificate::from_der(&self.root.self_signed()).unwrap()]);
+            verify_chain(chain, &self.leaf.public_key(), &anchors, AT)
+        }
+    }
+
+    fn rejected(report: ChainReport, reason: &str) {
+        assert!(!report.valid);
+        let error = report.error.unwrap();
+        assert!(error.contains(reason), "{}", error);
+    }
+
+    #[test]
+    fn accepts_a_chain_to_an_anchor() {
+        let report = Pki::new().verify();
+        assert!(report.valid, "{:?}", report.error);
+        assert_eq!(report.subject.as_deref(), Some("CN=Test Sealer"));
+        assert_eq!(report.anchor.as_deref(), Some("CN=Test Root"));
+        assert_eq!(report.length, 2);
+    }
+
+    #[test]
+    fn accepts_a_chain_that_includes_the_anchor() {
+        let pki = Pki::new();
+        let chain = vec![
+            pki.leaf.issued_by(&pki.intermediate),
+            pki.intermediate.issued_by(&pki.root),
+            pki.root.self_signed(),
+        ];
+        assert!(pki.verify_chain(&chain).valid);
+    }
+
+    #[test]
+    fn rejects_an_expired_certificate() {
+        let mut pki = Pki::new();
+        pki.leaf.not_after = AT - 1;
+        rejected(pki.verify(), "'CN=Test Sealer' is not valid at the time checked");
+
+        let mut pki = Pki::new();
+        pki.intermediate.not_before = AT + 1;
+        pki.intermediate.not_after = NOT_AFTER;
+        rejected(pki.verify(), "'CN=Test Intermediate' is not valid");
+
+        let mut pki = Pki::new();
+        pki.root.not_after = AT - 1;
+        rejected(pki.verify(), "trust anchor 'CN=Test Root' is not a valid CA");
+    }
+
+    #[test]
+    fn rejects_a_certificate_from_another_issuer() {
+        let pki = Pki::new();
+        let other = TestCertificate::ca("Other CA", 13);
+        let chain = vec![pki.leaf.issued_by(&other), pki.intermediate.issued_by(&pki.root)];
+        rejected(pki.verify_chain(&chain), "'CN=Test Sealer' is not issued by the next certificate");
+
+        // Same issuer name, different key.
+        let impostor = TestCertificate::ca("Test Intermediate", 13);
+        let chain = vec![pki.leaf.issued_by(&impostor), pki.intermediate.issued_by(&pki.root)];
+        rejected(pki.verify_chain(&chain), "signature on 'CN=Test Sealer' does not verify");
+
+        let chain = vec![pki.leaf.issued_by(&pki.intermediate), pki.intermediate.issued_by(&other)];
+        rejected(pki.verify_chain(&chain), "not a trust anchor");
+    }
+
+    #[test]
+    fn rejects_a_non_ca_intermediate() {
+        let mut pki = Pki::new();
+        pki.intermediate = TestCertificate::leaf("Test Intermediate", 11);
+        rejected(pki.verify(), "'CN=Test Intermediate' is not a CA");
+    }
+
+    #[test]
+    fn requires_key_cert_sign_on_issuers() {
+        let mut pki = Pki::new();
+        pki.intermediate.key_usage = Some(DIGITAL_SIGNATURE);
+        rejected(pki.verify(), "'CN=Test Intermediate' is not allowed to sign certificates");
+
+        let mut pki = Pki::new();
+        pki.root.key_usage = Some(DIGITAL_SIGNATURE);
+        rejected(pki.verify(), "'CN=Test Root' is not allowed to sign certificates");
+
+        // Without the extension, any use is allowed.
+        let mut pki = Pki::new();
+        pki.intermediate.key_usage = None;
+        assert!(pki.verify().valid);
+    }
+
+    #[test]
+    fn enforces_path_length_constraints() {
+        let mut pki = Pki::new();
+        pki.intermediate.ca = Some(Some(0));
+        pki.root.ca = Some(Some(1));
+        assert!(pki.verify().valid);
+
+        pki.root.ca = Some(Some(0));
+        rejected(pki.verify(), "'CN=Test Root' allows no more than 0 CAs below it");
+
+        let mut pki = Pki::new();
+        let second = TestCertificate::ca("Second Intermediate", 13);
+        pki.intermediate.ca = Some(Some(0));
+        let chain = vec![
+            pki.leaf.issued_by(&second),
+            second.issued_by(&pki.intermediate),
+            pki.intermediate.issued_by(&pki.root),
+        ];
+        rejected(pki.verify_chain(&chain), "'CN=Test Intermediate' allows no more than 0 CAs below it");
+    }
+
+    #[test]
+    fn requires_a_leaf_for_digital_signatures() {
+        let mut pki = Pki::new();
+        pki.leaf.key_usage = Some(KEY_CERT_SIGN);
+        rejected(pki.verify(), "leaf certificate 'CN=Test Sealer' is not for digital signatures");
+
+        let mut pki = Pki::new();
+        pki.leaf.ca = Some(None);
+        pki.leaf.key_usage = Some(DIGITAL_SIGNATURE | KEY_CERT_SIGN);
+        rejected(pki.verify(), "leaf certificate 'CN=Test Sealer' is a CA");
+    }
+
+    #[test]
+    fn rejects_an_unknown_critical_extension() {
+        let mut pki = Pki::new();
+        pki.intermediate.extensions.push((OID_NAME_CONSTRAINTS.to_vec(), true, vec![0x30, 0x00]));
+        rejected(pki.verify(), "'CN=Test Intermediate' has a critical extension 2.5.29.30 that is not supported");
+
+        let mut pki = Pki::new();
+        pki.root.extensions.push((OID_NAME_CONSTRAINTS.to_vec(), true, vec![0x30, 0x00]));
+        rejected(pki.verify(), "'CN=Test Root' has a critical extension 2.5.29.30");
+
+        // Not critical, so it may be ignored.
+        let mut pki = Pki::new();
+        pki.intermediate.extensions.push((OID_NAME_CONSTRAINTS.to_vec(), false, vec![0x30, 0x00]));
+        assert!(pki.verify().valid);
+    }
+
+    #[test]
+    fn rejects_mismatched_signature_algorithms() {
+        let mut pki = Pki::new();
+        pki.leaf.tbs_signature_algorithm = Some(OID_ECDSA_SHA384.to_vec());
+        let error = Certificate::from_der(&pki.leaf.issued_by(&pki.intermediate)).unwrap_err();
+        assert!(error.to_string().contains("signature algorithm differs"));
+        rejected(pki.verify(), "signature algorithm differs");
+    }
+
+    #[test]
+    fn rejects_a_leaf_for_another_key() {
+        let pki = Pki::new();
+        let chain = vec![pki.leaf.issued_by(&pki.intermediate), pki.intermediate.issued_by(&pki.root)];
+        let anchors = TrustAnchors::new(vec![Certificate::from_der(&pki.root.self_signed()).unwrap()]);
+        let other = TestCertificate::leaf("Other", 13).public_key();
+        assert!(!verify_chain(&chain, &other, &anchors, AT).valid);
+    }
+}
@@ -0,0 +1,778 @@
+// aegis-core/src/xmp.rs
+
+// XMP and IPTC interchange for JPEG and PNG images.
+//
+// Reading pulls the XMP packet (JPEG APP1, PNG `iTXt` chunk with keyword
+// `XML:com.adobe.xmp`) and the IPTC IIM record (JPEG APP13 Photoshop
+// resource 0x0404) out of an image and flattens the well-known properties in
+// `PROPERTIES` and `IPTC_DATASETS` into JSON. Anything else, including
+// compressed PNG text, is ignored.
+//
+// Writing goes the other way for sealed copies: `sanitize()` drops every
+// embedded metadata segment but those needed to render the image, and
+// `embed()` inserts a single XMP packet from `seal_packet()`, in the `aegis`
+// namespace, that carries the seal. The packet holds the container up to its image block,
+// so `extract_sealed()` can cut the packet out again and rebuild the
+// container around the remaining, sanitized bytes. Tools that only read XMP
+// see the signer's fingerprint, the signature and the sealed metadata, and
+// any properties passed to `seal_packet()`.
+
+use crate::error::AegisError;
+use base64ct::{Base64, Encoding};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+/// Namespace URI of the properties describing a seal.
+pub const AEGIS_NS: &str = "urn:aegis:seal:1.0/";
+
+const RDF_NS: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+const JPEG_XMP_ID: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const JPEG_IPTC_ID: &[u8] = b"Photoshop 3.0\0";
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+const PNG_XMP_KEYWORD: &[u8] = b"XML:com.adobe.xmp";
+/// Largest XMP packet a single JPEG APP1 segment can hold.
+const MAX_JPEG_PACKET: usize = 65533 - JPEG_XMP_ID.len();
+
+/// The namespaces properties are read and written in, with the prefix used
+/// for their JSON names whatever prefix the packet declared.
+const NAMESPACES: [(&str, &str); 7] = [
+    ("dc", "http://purl.org/dc/elements/1.1/"),
+    ("xmp", "http://ns.adobe.com/xap/1.0/"),
+    ("xmpRights", "http://ns.adobe.com/xap/1.0/rights/"),
+    ("photoshop", "http://ns.adobe.com/photoshop/1.0/"),
+    ("Iptc4xmpCore", "http://iptc.org/std/Iptc4xmpCore/1.0/xmlns/"),
+    ("rdf", RDF_NS),
+    ("aegis", AEGIS_NS),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// A single value.
+    Text,
+    /// A language alternative. Only the first value is read, which is the
+    /// `x-default` one by convention.
+    Alt,
+    /// An ordered array.
+    Seq,
+    /// An unordered array.
+    Bag,
+}
+
+/// The XMP properties imported and written back.
+const PROPERTIES: [(&str, Kind); 17] = [
+    ("dc:title", Kind::Alt),
+    ("dc:description", Kind::Alt),
+    ("dc:rights", Kind::Alt),
+    ("dc:creator", Kind::Seq),
+    ("dc:subject", Kind::Bag),
+    ("xmp:CreateDate", Kind::Text),
+    ("xmp:ModifyDate", Kind::Text),
+    ("xmp:CreatorTool", Kind::Text),
+    ("xmp:Rating", Kind::Text),
+    ("xmpRights:WebStatement", Kind::Text),
+    ("xmpRights:UsageTerms", Kind::Alt),
+    ("photoshop:Headline", Kind::Text),
+    ("photoshop:Credit", Kind::Text),
+    ("photoshop:Source", Kind::Text),
+    ("photoshop:City", Kind::Text),
+    ("photoshop:Country", Kind::Text),
+    ("Iptc4xmpCore:Location", Kind::Text),
+];
+
+/// IPTC IIM record 2 datasets imported, with whether they may repeat.
+const IPTC_DATASETS: [(u8, &str, bool); 12] = [
+    (5, "ObjectName", false),
+    (25, "Keywords", true),
+    (40, "SpecialInstructions", false),
+    (55, "DateCreated", false),
+    (80, "By-line", true),
+    (90, "City", false),
+    (101, "Country-PrimaryLocationName", false),
+    (105, "Headline", false),
+    (110, "Credit", false),
+    (115, "Source", false),
+    (116, "CopyrightNotice", false),
+    (120, "Caption-Abstract", false),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageKind {
+    Jpeg,
+    Png,
+}
+
+impl ImageKind {
+    /// Recognizes an image from its first bytes.
+    pub fn sniff(data: &[u8]) -> Option<Self> {
+        if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(ImageKind::Jpeg)
+        } else if data.starts_with(PNG_SIGNATURE) {
+            Some(ImageKind::Png)
+        } else {
+            None
+        }
+    }
+
+    pub fn media_type(self) -> &'static str {
+        match self {
+            ImageKind::Jpeg => "image/jpeg",
+            ImageKind::Png => "image/png",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageKind::Jpeg => "jpg",
+            ImageKind::Png => "png",
+        }
+    }
+}
+
+/// Metadata found embedded in an image.
+#[derive(Default)]
+pub struct Embedded {
+    /// The XMP packet.
+    pub xmp: Option<String>,
+    /// The raw IPTC IIM datasets.
+    pub iptc: Option<Vec<u8>>,
+}
+
+impl Embedded {
+    /// Reads the metadata embedded in a JPEG or PNG image. The image may be
+    /// truncated, so the start of a large file is enough as long as its
+    /// metadata comes before the cut; other formats have none.
+    pub fn read(image: &[u8]) -> Self {
+        let mut embedded = Embedded::default();
+        match ImageKind::sniff(image) {
+            Some(ImageKind::Jpeg) => {
+                for segment in jpeg_segments(image) {
+                    let payload = &image[segment.payload.clone()];
+                    if segment.marker == 0xE1 && embedded.xmp.is_none() {
+                        if let Some(packet) = payload.strip_prefix(JPEG_XMP_ID) {
+                            embedded.xmp = Some(String::from_utf8_lossy(packet).into_owned());
+                        }
+                    } else if segment.marker == 0xED && embedded.iptc.is_none() {
+                        embedded.iptc = payload.strip_prefix(JPEG_IPTC_ID).and_then(photoshop_iptc);
+                    }
+                }
+            }
+            Some(ImageKind::Png) => {
+                for chunk in png_chunks(image) {
+                    if &image[chunk.kind.clone()] == b"iTXt"
+                        && embedded.xmp.is_none()
+                        && let Some(text) = png_xmp_text(&image[chunk.data.clone()])
+                    {
+                        embedded.xmp = Some(text);
+                    }
+                }
+            }
+            None => {}
+        }
+        embedded
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.xmp.is_none() && self.iptc.is_none()
+    }
+
+    /// The recognized properties as `{"xmp": {...}, "iptc": {...}}`, leaving
+    /// out a source with none. Seal properties in the `aegis` namespace are
+    /// not imported.
+    pub fn to_json(&self) -> Value {
+        let mut out = Map::new();
+        if let Some(packet) = &self.xmp {
+            let properties: Map<String, Value> = read_properties(packet)
+                .into_iter()
+                .filter(|(name, _)| !name.starts_with("aegis:"))
+                .collect();
+            if !properties.is_empty() {
+                out.insert("xmp".to_string(), Value::Object(properties));
+            }
+        }
+        if let Some(iptc) = &self.iptc {
+            let datasets = read_iptc(iptc);
+            if !datasets.is_empty() {
+                out.insert("iptc".to_string(), Value::Object(datasets));
+            }
+        }
+        Value::Object(out)
+    }
+}
+
+/// Removes the embedded metadata from a JPEG or PNG image: for JPEG every
+/// APPn segment but JFIF, ICC profiles and Adobe colour information, and
+/// comments; for PNG the text, EXIF, time and C2PA (`caBX`) chunks.
+pub fn sanitize(image: &[u8]) -> Result<Vec<u8>, AegisError> {
+    match ImageKind::sniff(image) {
+        Some(ImageKind::Jpeg) => {
+            let segments = jpeg_segments(image);
+            let scan = segments.last().filter(|s| s.marker == 0xDA).ok_or(AegisError::InvalidImage(
+                "JPEG image ends before its image data",
+            ))?;
+            let mut out = image[..2].to_vec();
+            for segment in &segments {
+                let payload = &image[segment.payload.clone()];
+                let keep = match segment.marker {
+                    0xE0 => payload.starts_with(b"JFIF\0"),
+                    0xE2 => payload.starts_with(b"ICC_PROFILE\0"),
+                    0xEE => payload.starts_with(b"Adobe"),
+                    0xE1..=0xEF | 0xFE => false,
+                    _ => true,
+                };
+                if keep {
+                    out.extend_from_slice(&image[segment.start..segment.payload.end]);
+                }
+            }
+            out.extend_from_slice(&image[scan.payload.end..]);
+            Ok(out)
+        }
+        Some(ImageKind::Png) => {
+            let chunks = png_chunks(image);
+            if chunks.last().is_none_or(|c| &image[c.kind.clone()] != b"IEND") {
+                return Err(AegisError::InvalidImage("PNG image has no IEND chunk"));
+            }
+            let mut out = PNG_SIGNATURE.to_vec();
+            for chunk in &chunks {
+                if !matches!(&image[chunk.kind.clone()], b"tEXt" | b"zTXt" | b"iTXt" | b"eXIf" | b"tIME" | b"caBX") {
+                    out.extend_from_slice(&image[chunk.start..chunk.end]);
+                }
+            }
+            Ok(out)
+        }
+        None => Err(AegisError::InvalidImage("only JPEG and PNG images can carry XMP")),
+    }
+}
+
+/// The XMP packet describing a seal. `container_prefix` is the container up
+/// to its image block (`format::header_bytes()`); `properties` holds values
+/// in the shape `Embedded::to_json()` gives for `xmp`, and those in
+/// `PROPERTIES` are written too.
+pub fn seal_packet(
+    container_prefix: &[u8],
+    public_key: &[u8],
+    signature: &[u8],
+    metadata: &str,
+    properties: &Map<String, Value>,
+) -> String {
+    let mut body = String::new();
+    for (name, kind) in PROPERTIES {
+        let Some(value) = properties.get(name) else {
+            continue;
+        };
+        let items: Vec<&str> = match value {
+            Value::String(s) => vec![s.as_str()],
+            Value::Array(values) => values.iter().filter_map(Value::as_str).collect(),
+            _ => continue,
+        };
+        if items.is_empty() {
+            continue;
+        }
+        body.push_str(&format!("   <{}>", name));
+        match kind {
+            Kind::Text => body.push_str(&escape(items[0])),
+            Kind::Alt => body.push_str(&format!(
+                "<rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt>",
+                escape(items[0])
+            )),
+            Kind::Seq | Kind::Bag => {
+                let tag = if kind == Kind::Seq { "rdf:Seq" } else { "rdf:Bag" };
+                body.push_str(&format!("<{}>", tag));
+                for item in items {
+                    body.push_str(&format!("<rdf:li>{}</rdf:li>", escape(item)));
+                }
+                body.push_str(&format!("</{}>", tag));
+            }
+        }
+        body.push_str(&format!("</{}>\n", name));
+    }
+    let declarations: String = NAMESPACES
+        .iter()
+        .filter(|(prefix, _)| *prefix != "rdf")
+        .map(|(prefix, uri)| format!("\n    xmlns:{}=\"{}\"", prefix, uri))
+        .collect();
+    format!(
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+         <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n \
+         <rdf:RDF xmlns:rdf=\"{rdf}\">\n  \
+         <rdf:Description rdf:about=\"\"{declarations}\n    \
+         aegis:KeyFingerprint=\"{fingerprint}\"\n    \
+         aegis:Signature=\"{signature}\"\n    \
+         aegis:Container=\"{container}\">\n   \
+         <aegis:Metadata>{metadata}</aegis:Metadata>\n\
+         {body}  </rdf:Description>\n \
+         </rdf:RDF>\n\
+         </x:xmpmeta>\n\
+         <?xpacket end=\"r\"?>",
+        rdf = RDF_NS,
+        declarations = declarations,
+        fingerprint = hex::encode(Sha256::digest(public_key)),
+        signature = hex::encode(signature),
+        container = Base64::encode_string(container_prefix),
+        metadata = escape(metadata),
+        body = body,
+    )
+}
+
+/// Inserts `packet` into a sanitized image: for JPEG as an APP1 segment
+/// after SOI and any JFIF segment, for PNG as an `iTXt` chunk after IHDR.
+pub fn embed(image: &[u8], packet: &str) -> Result<Vec<u8>, AegisError> {
+    let at = packet_position(image)?;
+    let mut out = Vec::with_capacity(image.len() + packet.len() + 64);
+    out.extend_from_slice(&image[..at]);
+    match ImageKind::sniff(image) {
+        Some(ImageKind::Jpeg) => {
+            if packet.len() > MAX_JPEG_PACKET {
+                return Err(AegisError::InvalidImage("XMP packet too large for a JPEG APP1 segment"));
+            }
+            out.extend_from_slice(&[0xFF, 0xE1]);
+            out.extend_from_slice(&((2 + JPEG_XMP_ID.len() + packet.len()) as u16).to_be_bytes());
+            out.extend_from_slice(JPEG_XMP_ID);
+            out.extend_from_slice(packet.as_bytes());
+        }
+        _ => {
+            let mut data = PNG_XMP_KEYWORD.to_vec();
+            // Null separator, uncompressed, then empty language and translated keyword.
+            data.extend_from_slice(&[0, 0, 0, 0, 0]);
+            data.extend_from_slice(packet.as_bytes());
+            out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            let crc_start = out.len();
+            out.extend_from_slice(b"iTXt");
+            out.extend_from_slice(&data);
+            let crc = crc32(&out[crc_start..]);
+            out.extend_from_slice(&crc.to_be_bytes());
+        }
+    }
+    out.extend_from_slice(&image[at..]);
+    Ok(out)
+}
+
+/// Rebuilds the container from a copy written with `seal_packet()` and
+/// `embed()`: the image block is the copy without the packet. Copies with
+/// no aegis XMP packet are read as C2PA copies (`c2pa::extract_sealed()`).
+/// Compressed blocks may inflate to `inflate_limit` bytes at most, as with
+/// `AegisAncient::read_limited()`.
+#[cfg(feature = "verifier")]
+pub fn extract_sealed(image: &[u8], inflate_limit: usize) -> Result<crate::format::AegisAncient, AegisError> {
+    match extract_xmp_sealed(image, inflate_limit) {
+        Err(AegisError::InvalidImage(_)) => crate::c2pa::extract_sealed(image, inflate_limit)
+            .map_err(|_| AegisError::InvalidImage("image carries no aegis XMP packet or C2PA manifest")),
+        other => other,
+    }
+}
+
+#[cfg(feature = "verifier")]
+fn extract_xmp_sealed(image: &[u8], inflate_limit: usize) -> Result<crate::format::AegisAncient, AegisError> {
+    let not_sealed = || AegisError::InvalidImage("image carries no aegis XMP packet");
+    let at = packet_position(image).map_err(|_| not_sealed())?;
+    let (packet, end) = match ImageKind::sniff(image) {
+        Some(ImageKind::Jpeg) => {
+            let segment = jpeg_segments(image).into_iter().find(|s| s.start == at).ok_or_else(not_sealed)?;
+            let packet = image[segment.payload.clone()].strip_prefix(JPEG_XMP_ID);
+            (packet.map(|p| String::from_utf8_lossy(p).into_owned()), segment.payload.end)
+        }
+        _ => {
+            let chunk = png_chunks(image).into_iter().find(|c| c.start == at).ok_or_else(not_sealed)?;
+            let packet = (&image[chunk.kind.clone()] == b"iTXt").then(|| png_xmp_text(&image[chunk.data.clone()]));
+            (packet.flatten(), chunk.end)
+        }
+    };
+    let container = packet
+        .as_deref()
+        .and_then(|p| read_properties(p).remove("aegis:Container"))
+        .and_then(|v| v.as_str().and_then(|s| Base64::decode_vec(s).ok()))
+        .ok_or_else(not_sealed)?;
+    let mut bytes = container;
+    bytes.extend_from_slice(&image[..at]);
+    bytes.extend_from_slice(&image[end..]);
+    crate::format::AegisAncient::read_limited(&mut &bytes[..], inflate_limit)
+}
+
+/// Where `embed()` puts the packet.
+pub(crate) fn packet_position(image: &[u8]) -> Result<usize, AegisError> {
+    match ImageKind::sniff(image) {
+        Some(ImageKind::Jpeg) => Ok(jpeg_segments(image)
+            .first()
+            .filter(|s| s.marker == 0xE0 && image[s.payload.clone()].starts_with(b"JFIF\0"))
+            .map_or(2, |s| s.payload.end)),
+        Some(ImageKind::Png) => png_chunks(image)
+            .first()
+            .filter(|c| &image[c.kind.clone()] == b"IHDR")
+            .map(|c| c.end)
+            .ok_or(AegisError::InvalidImage("PNG image does not start with IHDR")),
+        None => Err(AegisError::InvalidImage("only JPEG and PNG images can carry XMP")),
+    }
+}
+
+pub(crate) struct Segment {
+    pub(crate) marker: u8,
+    /// Offset of the 0xFF marker byte.
+    pub(crate) start: usize,
+    pub(crate) payload: std::ops::Range<usize>,
+}
+
+/// The JPEG marker segments up to and including start of scan, stopping
+/// early at anything malformed or truncated.
+pub(crate) fn jpeg_segments(image: &[u8]) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut pos = 2;
+    while pos + 4 <= image.len() && image[pos] == 0xFF {
+        let marker = image[pos + 1];
+        let len = u16::from_be_bytes([image[pos + 2], image[pos + 3]]) as usize;
+        if len < 2 || pos + 2 + len > image.len() {
+            break;
+        }
+        segments.push(Segment { marker, start: pos, payload: pos + 4..pos + 2 + len });
+        if marker == 0xDA {
+            break;
+        }
+        pos += 2 + len;
+    }
+    segments
+}
+
+pub(crate) struct Chunk {
+    pub(crate) start: usize,
+    pub(crate) kind: std::ops::Range<usize>,
+    pub(crate) data: std::ops::Range<usize>,
+    pub(crate) end: usize,
+}
+
+/// The PNG chunks, stopping early at anything truncated.
+pub(crate) fn png_chunks(image: &[u8]) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    let mut pos = PNG_SIGNATURE.len();
+    while pos + 12 <= image.len() {
+        let len = u32::from_be_bytes(image[pos..pos + 4].try_into().expect("4 bytes")) as usize;
+        let end = pos + 12 + len;
+        if end > image.len() {
+            break;
+        }
+        let chunk = Chunk { start: pos, kind: pos + 4..pos + 8, data: pos + 8..pos + 8 + len, end };
+        let last = &image[chunk.kind.clone()] == b"IEND";
+        chunks.push(chunk);
+        if last {
+            break;
+        }
+        pos = end;
+    }
+    chunks
+}
+
+/// The text of an uncompressed `iTXt` chunk holding XMP.
+fn png_xmp_text(data: &[u8]) -> Option<String> {
+    let rest = data.strip_prefix(PNG_XMP_KEYWORD)?.strip_prefix(&[0])?;
+    // Only uncompressed text: PNG compresses with zlib, which we do not read.
+    let [0, _method, rest @ ..] = rest else {
+        return None;
+    };
+    // Skip the language tag and translated keyword.
+    let mut parts = rest.splitn(3, |b| *b == 0);
+    let (_, _, text) = (parts.next()?, parts.next()?, parts.next()?);
+    Some(String::from_utf8_lossy(text).into_owned())
+}
+
+/// The IPTC IIM data in a Photoshop image resource block.
+fn photoshop_iptc(mut resources: &[u8]) -> Option<Vec<u8>> {
+    while resources.len() >= 12 && resources.starts_with(b"8BIM") {
+        let id = u16::from_be_bytes([resources[4], resources[5]]);
+        // Pascal string name, padded to an even length with its length byte.
+        let name_len = resources[6] as usize;
+        let mut pos = 6 + (name_len + 1).next_multiple_of(2);
+        let size = u32::from_be_bytes(resources.get(pos..pos + 4)?.try_into().ok()?) as usize;
+        pos += 4;
+        let data = resources.get(pos..pos + size)?;
+        if id == 0x0404 {
+            return Some(data.to_vec());
+        }
+        resources = resources.get((pos + size).next_multiple_of(2)..).unwrap_or(&[]);
+    }
+    None
+}
+
+fn read_iptc(mut data: &[u8]) -> Map<String, Value> {
+    let mut out = Map::new();
+    while data.len() >= 5 && data[0] == 0x1C {
+        let (record, dataset) = (data[1], data[2]);
+        let len = u16::from_be_bytes([data[3], data[4]]) as usize;
+        // Extended-length datasets are never text; stop rather than guess.
+        if len & 0x8000 != 0 || data.len() < 5 + len {
+            break;
+        }
+        let value = &data[5..5 + len];
+        data = &data[5 + len..];
+        let Some((_, name, repeats)) = IPTC_DATASETS.iter().find(|(id, _, _)| record == 2 && *id == dataset) else {
+            continue;
+        };
+        // IIM text is UTF-8 when declared so, and commonly Latin-1 otherwise.
+        let text = match std::str::from_utf8(value) {
+            Ok(text) => text.to_string(),
+            Err(_) => value.iter().map(|b| char::from(*b)).collect(),
+        };
+        if *repeats {
+            let entry = out.entry(name.to_string()).or_insert_with(|| Value::Array(Vec::new()));
+            if let Value::Array(items) = entry {
+                items.push(Value::String(text));
+            }
+        } else {
+            out.entry(name.to_string()).or_insert(Value::String(text));
+        }
+    }
+    out
+}
+
+enum Token<'a> {
+    Start { name: &'a str, attrs: Vec<(&'a str, String)>, empty: bool },
+    End,
+    Text(String),
+}
+
+/// Splits XML into start tags, end tags and text. Declarations, processing
+/// instructions and comments are skipped; this is enough for XMP packets,
+/// which never use a DTD.
+fn tokens(xml: &str) -> Vec<Token<'_>> {
+    let mut out = Vec::new();
+    let mut rest = xml;
+    while !rest.is_empty() {
+        let Some(open) = rest.find('<') else {
+            out.push(Token::Text(unescape(rest)));
+            break;
+        };
+        if open > 0 {
+            out.push(Token::Text(unescape(&rest[..open])));
+        }
+        rest = &rest[open..];
+        let skip_to = |end: &str| rest.find(end).map(|i| i + end.len());
+        if rest.starts_with("<!--") {
+            rest = &rest[skip_to("-->").unwrap_or(rest.len())..];
+            continue;
+        }
+        if rest.starts_with("<?") || rest.starts_with("<!") {
+            rest = &rest[skip_to(">").unwrap_or(rest.len())..];
+            continue;
+        }
+        let Some(close) = tag_end(rest) else {
+            break;
+        };
+        let tag = &rest[1..close];
+        rest = &rest[close + 1..];
+        if tag.starts_with('/') {
+            out.push(Token::End);
+            continue;
+        }
+        let empty = tag.ends_with('/');
+        let tag = tag.trim_end_matches('/');
+        let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+        out.push(Token::Start { name: &tag[..name_end], attrs: attributes(&tag[name_end..]), empty });
+    }
+    out
+}
+
+/// The index of the `>` closing the tag at the start of `s`, skipping over
+/// quoted attribute values.
+fn tag_end(s: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn attributes(mut s: &str) -> Vec<(&str, String)> {
+    let mut attrs = Vec::new();
+    loop {
+        s = s.trim_start();
+        let Some(eq) = s.find('=') else {
+            return attrs;
+        };
+        let name = s[..eq].trim();
+        s = s[eq + 1..].trim_start();
+        let Some(quote) = s.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            return attrs;
+        };
+        let Some(end) = s[1..].find(quote) else {
+            return attrs;
+        };
+        attrs.push((name, unescape(&s[1..1 + end])));
+        s = &s[end + 2..];
+    }
+}
+
+fn unescape(s: &str) -> String {
+    if !s.contains('&') {
+        return s.to_string();
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let Some(semi) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..semi];
+        let decoded = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Resolves a qualified name against the in-scope declarations to its name
+/// under the `NAMESPACES` prefix, or `None` for other namespaces.
+fn canonical(qname: &str, scopes: &[(String, String)]) -> Option<String> {
+    let (prefix, local) = qname.split_once(':')?;
+    let uri = &scopes.iter().rev().find(|(p, _)| p == prefix)?.1;
+    let (canonical, _) = NAMESPACES.iter().find(|(_, u)| u == uri)?;
+    Some(format!("{}:{}", canonical, local))
+}
+
+fn property_kind(name: &str) -> Option<Kind> {
+    if name.starts_with("aegis:") {
+        return Some(Kind::Text);
+    }
+    PROPERTIES.iter().find(|(p, _)| *p == name).map(|(_, kind)| *kind)
+}
+
+/// The values of the `PROPERTIES` and `aegis` properties in an XMP packet,
+/// in either the element or the attribute form.
+fn read_properties(packet: &str) -> Map<String, Value> {
+    struct Open {
+        name: String,
+        kind: Kind,
+        depth: usize,
+        text: String,
+        items: Vec<String>,
+        item: Option<String>,
+    }
+
+    let mut out = Map::new();
+    // In-scope namespace declarations, with the element depth declaring them.
+    let mut scopes: Vec<(String, String)> = Vec::new();
+    let mut scope_depths: Vec<usize> = Vec::new();
+    let mut stack: Vec<Option<String>> = Vec::new();
+    let mut open: Option<Open> = None;
+    for token in tokens(packet) {
+        match token {
+            Token::Start { name, attrs, empty } => {
+                let depth = stack.len();
+                for (attr, value) in &attrs {
+                    if let Some(prefix) = attr.strip_prefix("xmlns:") {
+                        scopes.push((prefix.to_string(), value.clone()));
+                        scope_depths.push(depth);
+                    }
+                }
+                let resolved = canonical(name, &scopes);
+                let parent = stack.last().cloned().flatten();
+                if resolved.as_deref() == Some("rdf:Description") {
+                    for (attr, value) in &attrs {
+                        if let Some(attr) = canonical(attr, &scopes)
+                            && property_kind(&attr).is_some()
+                        {
+                            out.entry(attr).or_insert(Value::String(value.clone()));
+                        }
+                    }
+                } else if let Some(open) = open.as_mut() {
+                    if resolved.as_deref() == Some("rdf:li") {
+                        open.item = Some(String::new());
+                    }
+                } else if parent.as_deref() == Some("rdf:Description")
+                    && let Some(kind) = resolved.as_deref().and_then(property_kind)
+                {
+                    let name = resolved.clone().expect("resolved above");
+                    if empty {
+                        let resource = attrs.iter().find(|(a, _)| canonical(a, &scopes).as_deref() == Some("rdf:resource"));
+                        if let Some((_, value)) = resource {
+                            out.entry(name).or_insert(Value::String(value.clone()));
+                        }
+                    } else {
+                        open = Some(Open { name, kind, depth, text: String::new(), items: Vec::new(), item: None });
+                    }
+                }
+                if empty {
+                    while scope_depths.last() == Some(&depth) {
+                        scope_depths.pop();
+                        scopes.pop();
+                    }
+                } else {
+                    stack.push(resolved);
+                }
+            }
+            Token::Text(text) => {
+                if let Some(open) = open.as_mut() {
+                    match open.item.as_mut() {
+                        Some(item) => item.push_str(&text),
+                        None => open.text.push_str(&text),
+                    }
+                }
+            }
+            Token::End => {
+                let Some(resolved) = stack.pop() else {
+                    break;
+                };
+                let depth = stack.len();
+                if let Some(current) = open.as_mut() {
+                    if resolved.as_deref() == Some("rdf:li") && let Some(item) = current.item.take() {
+                        current.items.push(item.trim().to_string());
+                    } else if depth == current.depth {
+                        let current = open.take().expect("open property");
+                        let value = match current.kind {
+                            _ if current.items.is_empty() => Value::String(current.text.trim().to_string()),
+                            Kind::Text | Kind::Alt => Value::String(current.items[0].clone()),
+                            Kind::Seq | Kind::Bag => {
+                                Value::Array(current.items.into_iter().map(Value::String).collect())
+                            }
+                        };
+                        out.entry(current.name).or_insert(value);
+                    }
+                }
+                while scope_depths.last() == Some(&depth) {
+                    scope_depths.pop();
+                    scopes.pop();
+                }
+            }
+        }
+    }
+    out
+}
+
+/// CRC-32 (ISO-HDLC) as used by PNG chunks.
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
@@ -0,0 +1,1281 @@
+// aegis-core/src/zstd.rs
+
+// Zstandard (RFC 8878) compression of container blocks, written out here as
+// `tar`, `der` and the CBOR in `c2pa` are, to keep the core free of native
+// dependencies. `decompress()` reads any frame the reference implementation
+// writes, short of ones needing a dictionary. `compress()` writes frames
+// that any decoder reads: a greedy LZ77 match finder, literals Huffman-coded
+// where that helps, and sequences coded with the predefined FSE tables. Its
+// ratio is below the reference encoder's at level 1, which is enough for
+// JSON metadata and uncompressed images; payloads that are compressed
+// already gain little either way.
+
+use crate::error::AegisError;
+
+const MAGIC: u32 = 0xFD2F_B528;
+const SKIPPABLE_MAGIC: u32 = 0x184D_2A50;
+const MAX_BLOCK: usize = 128 * 1024;
+/// Farthest back a match is looked for when compressing.
+const MAX_OFFSET: usize = 1 << 22;
+const MIN_MATCH: usize = 4;
+const HASH_LOG: u32 = 16;
+const MAX_HUFFMAN_BITS: u32 = 11;
+/// Largest window a frame may ask for, the reference decoder's default
+/// limit of 128 MiB.
+const MAX_WINDOW_LOG: u32 = 27;
+
+fn corrupt(what: &str) -> AegisError {
+    AegisError::InvalidCompression(what.to_string())
+}
+
+// --- Bit streams ---
+
+/// Bits `start..start + n` of `data`, least significant first, as the
+/// low bits of the result. Bits before the start or past the end read as
+/// zero. `n` is at most 56.
+fn bits_at(data: &[u8], start: isize, n: u32) -> u64 {
+    if n == 0 {
+        return 0;
+    }
+    if start < 0 {
+        let shift = start.unsigned_abs() as u32;
+        return if shift >= n { 0 } else { bits_at(data, 0, n - shift) << shift };
+    }
+    let byte = start as usize / 8;
+    let mut word = [0u8; 8];
+    if byte < data.len() {
+        let avail = (data.len() - byte).min(8);
+        word[..avail].copy_from_slice(&data[byte..byte + avail]);
+    }
+    (u64::from_le_bytes(word) >> (start % 8)) & ((1u64 << n) - 1)
+}
+
+/// Reads a bit stream from its end towards its start, as Huffman-coded
+/// literals and FSE-coded sequences are. The highest set bit of the last
+/// byte marks where the stream begins.
+struct BackwardBits<'a> {
+    data: &'a [u8],
+    /// Bits not yet read; negative once the stream has been read past.
+    pos: isize,
+}
+
+impl<'a> BackwardBits<'a> {
+    fn new(data: &'a [u8]) -> Result<Self, AegisError> {
+        let last = *data.last().ok_or_else(|| corrupt("empty bit stream"))?;
+        if last == 0 {
+            return Err(corrupt("bit stream has no end mark"));
+        }
+        let pos = (data.len() - 1) * 8 + (7 - last.leading_zeros() as usize);
+        Ok(BackwardBits { data, pos: pos as isize })
+    }
+
+    fn peek(&self, n: u32) -> u64 {
+        bits_at(self.data, self.pos - n as isize, n)
+    }
+
+    fn read(&mut self, n: u32) -> u64 {
+        let value = self.peek(n);
+        self.pos -= n as isize;
+        value
+    }
+
+    fn finish(&self) -> Result<(), AegisError> {
+        match self.pos {
+            0 => Ok(()),
+            _ => Err(corrupt("bit stream length does not match its contents")),
+        }
+    }
+}
+
+/// Writes a bit stream that `BackwardBits` reads back last value first.
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u64,
+    filled: u32,
+}
+
+impl BitWriter {
+    fn add(&mut self, value: u64, n: u32) {
+        debug_assert!(n <= 32 && (n == 32 || value < 1 << n));
+        self.acc |= value << self.filled;
+        self.filled += n;
+        while self.filled >= 8 {
+            self.out.push(self.acc as u8);
+            self.acc >>= 8;
+            self.filled -= 8;
+        }
+    }
+
+    /// Adds the end mark and pads to a whole byte.
+    fn finish(mut self) -> Vec<u8> {
+        self.add(1, 1);
+        if self.filled > 0 {
+            self.out.push(self.acc as u8);
+        }
+        self.out
+    }
+}
+
+fn highest_bit(value: u32) -> u32 {
+    31 - value.leading_zeros()
+}
+
+// --- FSE ---
+
+#[derive(Clone, Copy, Default)]
+struct FseEntry {
+    symbol: u8,
+    bits: u8,
+    baseline: u16,
+}
+
+#[derive(Clone)]
+struct FseTable {
+    accuracy: u32,
+    entries: Vec<FseEntry>,
+}
+
+impl FseTable {
+    /// The decoding table for a normalized distribution, in which -1 is a
+    /// symbol whose probability is below 1 / 2^accuracy.
+    fn new(counts: &[i16], accuracy: u32) -> Result<Self, AegisError> {
+        let size = 1usize << accuracy;
+        let mut entries = vec![FseEntry::default(); size];
+        let mut next = vec![0u32; counts.len()];
+        let mut high = size;
+        for (symbol, &count) in counts.iter().enumerate() {
+            if count == -1 {
+                high = high.checked_sub(1).ok_or_else(|| corrupt("FSE distribution is too large"))?;
+                entries[high].symbol = symbol as u8;
+                next[symbol] = 1;
+            } else {
+                next[symbol] = count.max(0) as u32;
+            }
+        }
+        let step = (size >> 1) + (size >> 3) + 3;
+        let mut position = 0;
+        for (symbol, &count) in counts.iter().enumerate() {
+            for _ in 0..count.max(0) {
+                if position >= high {
+                    return Err(corrupt("FSE distribution does not fill its table"));
+                }
+                entries[position].symbol = symbol as u8;
+                position = (position + step) & (size - 1);
+                while position >= high {
+                    position = (position + step) & (size - 1);
+                }
+            }
+        }
+        if position != 0 {
+            return Err(corrupt("FSE distribution does not fill its table"));
+        }
+        for entry in &mut entries {
+            let state = next[entry.symbol as usize];
+            next[entry.symbol as usize] += 1;
+            entry.bits = (accuracy - highest_bit(state)) as u8;
+            entry.baseline = ((state << entry.bits) as usize - size) as u16;
+        }
+        Ok(FseTable { accuracy, entries })
+    }
+
+    /// A table that always decodes `symbol` and reads no bits.
+    fn rle(symbol: u8) -> Self {
+        FseTable { accuracy: 0, entries: vec![FseEntry { symbol, bits: 0, baseline: 0 }] }
+    }
+
+    /// Reads a distribution description from the start of `data`,
+    /// returning the table and the number of bytes it took.
+    fn read(data: &[u8], max_symbol: usize, max_accuracy: u32) -> Result<(Self, usize), AegisError> {
+        let accuracy = bits_at(data, 0, 4) as u32 + 5;
+        if accuracy > max_accuracy {
+            return Err(corrupt("FSE accuracy is too high"));
+        }
+        let mut pos: isize = 4;
+        let mut remaining = (1i32 << accuracy) + 1;
+        let mut threshold = 1i32 << accuracy;
+        let mut bits = accuracy + 1;
+        let mut counts: Vec<i16> = Vec::new();
+        while remaining > 1 {
+            if counts.len() > max_symbol {
+                return Err(corrupt("FSE distribution has too many symbols"));
+            }
+            let max = 2 * threshold - 1 - remaining;
+            let low = bits_at(data, pos, bits - 1) as i32;
+            let value = if low < max {
+                pos += bits as isize - 1;
+                low
+            } else {
+                let mut value = bits_at(data, pos, bits) as i32;
+                if value >= threshold {
+                    value -= max;
+                }
+                pos += bits as isize;
+                value
+            };
+            let count = value - 1;
+            remaining -= count.abs();
+            counts.push(count as i16);
+            if count == 0 {
+                loop {
+                    let repeat = bits_at(data, pos, 2) as usize;
+                    pos += 2;
+                    counts.extend(std::iter::repeat_n(0, repeat));
+                    if repeat < 3 {
+                        break;
+                    }
+                }
+            }
+            while remaining < threshold && threshold > 1 {
+                bits -= 1;
+                threshold >>= 1;
+            }
+        }
+        let used = (pos as usize).div_ceil(8);
+        if remaining != 1 || used > data.len() || counts.len() > max_symbol + 1 {
+            return Err(corrupt("malformed FSE distribution"));
+        }
+        Ok((FseTable::new(&counts, accuracy)?, used))
+    }
+}
+
+struct FseState<'t> {
+    table: &'t FseTable,
+    state: usize,
+}
+
+impl<'t> FseState<'t> {
+    fn new(table: &'t FseTable, bits: &mut BackwardBits) -> Self {
+        FseState { table, state: bits.read(table.accuracy) as usize }
+    }
+
+    fn symbol(&self) -> u8 {
+        self.table.entries[self.state].symbol
+    }
+
+    fn update(&mut self, bits: &mut BackwardBits) {
+        let entry = self.table.entries[self.state];
+        self.state = entry.baseline as usize + bits.read(entry.bits as u32) as usize;
+    }
+}
+
+/// Encodes with the table `FseTable::new()` builds: for each symbol, the
+/// state that decodes it and leads to a given next state.
+struct FseEncoder {
+    table: FseTable,
+    /// `previous[symbol][next state]`.
+    previous: Vec<Vec<u16>>,
+}
+
+impl FseEncoder {
+    fn new(counts: &[i16], accuracy: u32) -> Self {
+        let table = FseTable::new(counts, accuracy).expect("predefined distributions are valid");
+        let mut previous = vec![vec![0u16; table.entries.len()]; counts.len()];
+        for (state, entry) in table.entries.iter().enumerate() {
+            let start = entry.baseline as usize;
+            for next in &mut previous[entry.symbol as usize][start..start + (1 << entry.bits)] {
+                *next = state as u16;
+            }
+        }
+        FseEncoder { table, previous }
+    }
+
+    fn first_state(&self, symbol: u8) -> usize {
+        self.table.entries.iter().position(|e| e.symbol == symbol).expect("every symbol has a state")
+    }
+
+    /// The state that emits `symbol` then moves to `next`, and the bits
+    /// that move it there.
+    fn step(&self, symbol: u8, next: usize) -> (usize, u64, u32) {
+        let state = self.previous[symbol as usize][next] as usize;
+        let entry = self.table.entries[state];
+        (state, (next - entry.baseline as usize) as u64, entry.bits as u32)
+    }
+}
+
+const LL_DEFAULT: [i16; 36] = [
+    4, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 3, 2, 1, 1, 1, 1, 1, -1, -1, -1, -1,
+];
+const ML_DEFAULT: [i16; 53] = [
+    1, 4, 3, 2, 2, 2, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, -1, -1, -1, -1, -1, -1, -1,
+];
+const OF_DEFAULT: [i16; 29] = [
+    1, 1, 1, 1, 1, 1, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, -1, -1, -1, -1, -1,
+];
+
+/// Baseline and extra bits of literal length codes 16 and up.
+const LL_CODES: [(u32, u32); 20] = [
+    (16, 1), (18, 1), (20, 1), (22, 1), (24, 2), (28, 2), (32, 3), (40, 3), (48, 4), (64, 6),
+    (128, 7), (256, 8), (512, 9), (1024, 10), (2048, 11), (4096, 12), (8192, 13), (16384, 14),
+    (32768, 15), (65536, 16),
+];
+/// Baseline and extra bits of match length codes 32 and up.
+const ML_CODES: [(u32, u32); 21] = [
+    (35, 1), (37, 1), (39, 1), (41, 1), (43, 2), (47, 2), (51, 3), (59, 3), (67, 4), (83, 4),
+    (99, 5), (131, 7), (259, 8), (515, 9), (1027, 10), (2051, 11), (4099, 12), (8195, 13),
+    (16387, 14), (32771, 15), (65539, 16),
+];
+
+fn literal_length_code(code: u8) -> (u32, u32) {
+    match code {
+        0..=15 => (code as u32, 0),
+        _ => LL_CODES[code as usize - 16],
+    }
+}
+
+fn match_length_code(code: u8) -> (u32, u32) {
+    match code {
+        0..=31 => (code as u32 + 3, 0),
+        _ => ML_CODES[code as usize - 32],
+    }
+}
+
+/// The code for `value` among `codes` (baselines from `first`), and its
+/// extra bits.
+fn length_code(value: u32, direct: u32, first: u32, codes: &[(u32, u32)]) -> (u8, u64, u32) {
+    if value < first + direct {
+        return ((value - first) as u8, 0, 0);
+    }
+    let index = codes.iter().rposition(|&(baseline, _)| baseline <= value).expect("value is in range");
+    let (baseline, bits) = codes[index];
+    ((direct + index as u32) as u8, (value - baseline) as u64, bits)
+}
+
+// --- Huffman ---
+
+#[derive(Clone)]
+struct HuffmanTable {
+    max_bits: u32,
+    /// Symbol and code length, indexed by the next `max_bits` bits.
+    entries: Vec<(u8, u8)>,
+}
+
+impl HuffmanTable {
+    /// The table for weights of symbols 0 up to the last, whose weight is
+    /// implied by the others.
+    fn from_weights(weights: &[u8]) -> Result<Self, AegisError> {
+        if weights.len() > 255 || weights.iter().any(|&w| w > MAX_HUFFMAN_BITS as u8) {
+            return Err(corrupt("malformed Huffman weights"));
+        }
+        let sum: u32 = weights.iter().filter(|&&w| w > 0).map(|&w| 1 << (w - 1)).sum();
+        if sum == 0 {
+            return Err(corrupt("malformed Huffman weights"));
+        }
+        let max_bits = highest_bit(sum) + 1;
+        let rest = (1 << max_bits) - sum;
+        if max_bits > MAX_HUFFMAN_BITS || !rest.is_power_of_two() {
+            return Err(corrupt("Huffman weights do not form a tree"));
+        }
+        let mut weights = weights.to_vec();
+        weights.push((highest_bit(rest) + 1) as u8);
+        let mut entries = Vec::with_capacity(1 << max_bits);
+        for weight in 1..=max_bits as u8 {
+            for (symbol, _) in weights.iter().enumerate().filter(|(_, w)| **w == weight) {
+                let length = (max_bits + 1 - weight as u32) as u8;
+                entries.extend(std::iter::repeat_n((symbol as u8, length), 1 << (weight - 1)));
+            }
+        }
+        Ok(HuffmanTable { max_bits, entries })
+    }
+
+    /// Reads a tree description, returning the table and the bytes it took.
+    fn read(data: &[u8]) -> Result<(Self, usize), AegisError> {
+        let header = *data.first().ok_or_else(|| corrupt("missing Huffman tree"))? as usize;
+        if header >= 128 {
+            let count = header - 127;
+            let bytes = data.get(1..1 + count.div_ceil(2)).ok_or_else(|| corrupt("truncated Huffman tree"))?;
+            let weights: Vec<u8> = (0..count).map(|i| (bytes[i / 2] >> if i % 2 == 0 { 4 } else { 0 }) & 15).collect();
+            return Ok((HuffmanTable::from_weights(&weights)?, 1 + bytes.len()));
+        }
+        let data = data.get(1..1 + header).ok_or_else(|| corrupt("truncated Huffman tree"))?;
+        let (table, used) = FseTable::read(data, 255, 6)?;
+        let mut bits = BackwardBits::new(&data[used..])?;
+        let mut states = [FseState::new(&table, &mut bits), FseState::new(&table, &mut bits)];
+        let mut weights = Vec::new();
+        let mut turn = 0;
+        loop {
+            weights.push(states[turn].symbol());
+            states[turn].update(&mut bits);
+            if bits.pos < 0 {
+                weights.push(states[1 - turn].symbol());
+                break;
+            }
+            if weights.len() > 255 {
+                return Err(corrupt("too many Huffman weights"));
+            }
+            turn = 1 - turn;
+        }
+        Ok((HuffmanTable::from_weights(&weights)?, 1 + header))
+    }
+
+    fn decode_stream(&self, data: &[u8], count: usize, out: &mut Vec<u8>) -> Result<(), AegisError> {
+        let mut bits = BackwardBits::new(data)?;
+        for _ in 0..count {
+            let (symbol, length) = self.entries[bits.peek(self.max_bits) as usize];
+            bits.pos -= length as isize;
+            out.push(symbol);
+        }
+        bits.finish()
+    }
+}
+
+/// Code lengths of a Huffman code for `counts`, none longer than
+/// `MAX_HUFFMAN_BITS`; zero for symbols that do not occur. Needs two or more
+/// symbols that do.
+fn huffman_lengths(counts: &[u64; 256]) -> [u32; 256] {
+    use std::cmp::Reverse;
+    let mut counts = *counts;
+    loop {
+        // Leaves are numbered by symbol; the tree's inner nodes follow.
+        let mut parents: Vec<Option<usize>> = vec![None; 256];
+        let mut heap: std::collections::BinaryHeap<_> =
+            counts.iter().enumerate().filter(|(_, c)| **c > 0).map(|(symbol, &c)| Reverse((c, symbol))).collect();
+        while heap.len() > 1 {
+            let Reverse((a, i)) = heap.pop().expect("two nodes");
+            let Reverse((b, j)) = heap.pop().expect("two nodes");
+            parents.push(None);
+            parents[i] = Some(parents.len() - 1);
+            parents[j] = Some(parents.len() - 1);
+            heap.push(Reverse((a + b, parents.len() - 1)));
+        }
+        let mut lengths = [0u32; 256];
+        for (symbol, length) in lengths.iter_mut().enumerate().filter(|(s, _)| counts[*s] > 0) {
+            let mut node = symbol;
+            while let Some(parent) = parents[node] {
+                *length += 1;
+                node = parent;
+            }
+        }
+        if lengths.iter().all(|&l| l <= MAX_HUFFMAN_BITS) {
+            return lengths;
+        }
+        for count in counts.iter_mut().filter(|c| **c > 0) {
+            *count = count.div_ceil(2);
+        }
+    }
+}
+
+/// Huffman-codes `literals`, returning the literals section, or `None` if
+/// that would not be smaller than storing them as they are.
+fn compress_literals(literals: &[u8]) -> Option<Vec<u8>> {
+    let mut counts = [0u64; 256];
+    for &b in literals {
+        counts[b as usize] += 1;
+    }
+    let last = counts.iter().rposition(|&c| c > 0)?;
+    if literals.len() < 64 || counts.iter().filter(|&&c| c > 0).count() < 2 || last > 128 {
+        return None;
+    }
+    let lengths = huffman_lengths(&counts);
+    let max_bits = *lengths.iter().max().expect("256 lengths");
+    let weights: Vec<u8> =
+        lengths[..=last].iter().map(|&l| if l == 0 { 0 } else { (max_bits + 1 - l) as u8 }).collect();
+    // Canonical codes, assigned in the order `HuffmanTable::from_weights`
+    // lays out its entries.
+    let mut codes = [(0u64, 0u32); 256];
+    let mut next = 0u64;
+    for weight in 1..=max_bits as u8 {
+        for (symbol, _) in weights.iter().enumerate().filter(|(_, w)| **w == weight) {
+            codes[symbol] = (next >> (weight - 1), max_bits + 1 - weight as u32);
+            next += 1 << (weight - 1);
+        }
+    }
+
+    let mut tree = vec![(127 + last) as u8];
+    tree.extend(weights[..last].chunks(2).map(|pair| pair[0] << 4 | pair.get(1).copied().unwrap_or(0)));
+    let encode = |segment: &[u8]| {
+        let mut writer = BitWriter::default();
+        for &b in segment.iter().rev() {
+            let (code, length) = codes[b as usize];
+            writer.add(code, length);
+        }
+        writer.finish()
+    };
+    let streams: Vec<Vec<u8>> = if literals.len() < 256 {
+        vec![encode(literals)]
+    } else {
+        let segment = literals.len().div_ceil(4);
+        literals.chunks(segment).map(encode).collect()
+    };
+    if streams.len() != 1 && streams.len() != 4 {
+        return None;
+    }
+    let mut body = tree;
+    if streams.len() == 4 {
+        for stream in &streams[..3] {
+            body.extend_from_slice(&u16::try_from(stream.len()).ok()?.to_le_bytes());
+        }
+    }
+    for stream in &streams {
+        body.extend_from_slice(stream);
+    }
+
+    let (regenerated, compressed) = (literals.len() as u64, body.len() as u64);
+    let (size_format, bits, header_len) = match (streams.len(), regenerated.max(compressed)) {
+        (1, n) if n < 1 << 10 => (0, 10, 3),
+        (1, _) => return None,
+        (_, n) if n < 1 << 10 => (1, 10, 3),
+        (_, n) if n < 1 << 14 => (2, 14, 4),
+        (_, n) if n < 1 << 18 => (3, 18, 5),
+        _ => return None,
+    };
+    let header = 2 | size_format << 2 | regenerated << 4 | compressed << (4 + bits);
+    let mut out = header.to_le_bytes()[..header_len].to_vec();
+    out.extend_from_slice(&body);
+    (out.len() < literals.len() + 3).then_some(out)
+}
+
+/// The literals section storing `literals` as they are, or as one repeated
+/// byte.
+fn raw_literals(literals: &[u8]) -> Vec<u8> {
+    let rle = literals.len() > 1 && literals.iter().all(|&b| b == literals[0]);
+    let kind = if rle { 1 } else { 0 };
+    let size = literals.len() as u32;
+    let mut out = match size {
+        0..32 => vec![(kind | size << 3) as u8],
+        32..4096 => (kind | 1 << 2 | size << 4).to_le_bytes()[..2].to_vec(),
+        _ => (kind | 3 << 2 | size << 4).to_le_bytes()[..3].to_vec(),
+    };
+    match rle {
+        true => out.push(literals[0]),
+        false => out.extend_from_slice(literals),
+    }
+    out
+}
+
+// --- Decompression ---
+
+/// What carries over from one compressed block to the next in a frame.
+#[derive(Default)]
+struct FrameState {
+    huffman: Option<HuffmanTable>,
+    tables: [Option<FseTable>; 3],
+    offsets: [usize; 3],
+}
+
+/// Decompresses one or more frames, failing if the result would exceed
+/// `limit` bytes.
+pub fn decompress(mut input: &[u8], limit: usize) -> Result<Vec<u8>, AegisError> {
+    let mut out = Vec::new();
+    if input.is_empty() {
+        return Err(corrupt("no zstd frame"));
+    }
+    while !input.is_empty() {
+        let magic = u32::from_le_bytes(input.get(..4).ok_or_else(|| corrupt("truncated frame"))?.try_into().expect("4 bytes"));
+        if magic & 0xFFFF_FFF0 == SKIPPABLE_MAGIC {
+            let size = input.get(4..8).ok_or_else(|| corrupt("truncated frame"))?;
+            let size = u32::from_le_bytes(size.try_into().expect("4 bytes")) as usize;
+            input = input.get(8 + size..).ok_or_else(|| corrupt("truncated skippable frame"))?;
+            continue;
+        }
+        if magic != MAGIC {
+            return Err(corrupt("not a zstd frame"));
+        }
+        let used = decompress_frame(&input[4..], limit, &mut out)?;
+        input = &input[4 + used..];
+    }
+    Ok(out)
+}
+
+fn decompress_frame(input: &[u8], limit: usize, out: &mut Vec<u8>) -> Result<usize, AegisError> {
+    let truncated = || corrupt("truncated frame");
+    let descriptor = *input.first().ok_or_else(truncated)?;
+    if descriptor & 0x08 != 0 {
+        return Err(corrupt("reserved frame header bit is set"));
+    }
+    let single_segment = descriptor & 0x20 != 0;
+    let checksum = descriptor & 0x04 != 0;
+    let dictionary_len = [0, 1, 2, 4][(descriptor & 3) as usize];
+    let size_len = match descriptor >> 6 {
+        0 if single_segment => 1,
+        0 => 0,
+        1 => 2,
+        2 => 4,
+        _ => 8,
+    };
+    if !single_segment {
+        let window = *input.get(1).ok_or_else(truncated)?;
+        if 10 + u32::from(window >> 3) > MAX_WINDOW_LOG {
+            return Err(corrupt("window is too large"));
+        }
+    }
+    let mut pos = 1 + usize::from(!single_segment);
+    let dictionary = input.get(pos..pos + dictionary_len).ok_or_else(truncated)?;
+    if dictionary.iter().any(|&b| b != 0) {
+        return Err(corrupt("frames that need a dictionary are not supported"));
+    }
+    pos += dictionary_len;
+    let size_bytes = input.get(pos..pos + size_len).ok_or_else(truncated)?;
+    let mut content_size = size_bytes.iter().rev().fold(0u64, |acc, &b| acc << 8 | b as u64);
+    if size_len == 2 {
+        content_size += 256;
+    }
+    pos += size_len;
+    if size_len > 0 && content_size > (limit - out.len().min(limit)) as u64 {
+        return Err(corrupt("decompressed size is over the limit"));
+    }
+
+    let start = out.len();
+    let mut state = FrameState { offsets: [1, 4, 8], ..Default::default() };
+    loop {
+        let header = input.get(pos..pos + 3).ok_or_else(truncated)?;
+        let header = u32::from_le_bytes([header[0], header[1], header[2], 0]);
+        let (last, kind, size) = (header & 1 != 0, (header >> 1) & 3, (header >> 3) as usize);
+        pos += 3;
+        if size > MAX_BLOCK {
+            return Err(corrupt("block is too large"));
+        }
+        match kind {
+            0 => out.extend_from_slice(input.get(pos..pos + size).ok_or_else(truncated)?),
+            1 => {
+                let byte = *input.get(pos).ok_or_else(truncated)?;
+                out.extend(std::iter::repeat_n(byte, size));
+            }
+            2 => decompress_block(input.get(pos..pos + size).ok_or_else(truncated)?, &mut state, start, out)?,
+            _ => return Err(corrupt("reserved block type")),
+        }
+        pos += if kind == 1 { 1 } else { size };
+        if out.len() > limit {
+            return Err(corrupt("decompressed size is over the limit"));
+        }
+        if last {
+            break;
+        }
+    }
+    if size_len > 0 && (out.len() - start) as u64 != content_size {
+        return Err(corrupt("decompressed size does not match the frame header"));
+    }
+    if checksum {
+        let expected = input.get(pos..pos + 4).ok_or_else(truncated)?;
+        if (xxh64(&out[start..], 0) as u32).to_le_bytes() != expected {
+            return Err(corrupt("content checksum does not match"));
+        }
+        pos += 4;
+    }
+    Ok(pos)
+}
+
+fn decompress_block(block: &[u8], state: &mut FrameState, frame_start: usize, out: &mut Vec<u8>) -> Result<(), AegisError> {
+    let truncated = || corrupt("truncated block");
+    let block_start = out.len();
+    let (literals, used) = read_literals(block, state)?;
+    let mut pos = used;
+
+    let first = *block.get(pos).ok_or_else(truncated)? as usize;
+    let (count, len) = match first {
+        0..128 => (first, 1),
+        128..255 => (((first - 128) << 8) + *block.get(pos + 1).ok_or_else(truncated)? as usize, 2),
+        _ => {
+            let bytes = block.get(pos + 1..pos + 3).ok_or_else(truncated)?;
+            (bytes[0] as usize + ((bytes[1] as usize) << 8) + 0x7F00, 3)
+        }
+    };
+    pos += len;
+    if count == 0 {
+        out.extend_from_slice(&literals);
+        return Ok(());
+    }
+
+    let modes = *block.get(pos).ok_or_else(truncated)?;
+    pos += 1;
+    if modes & 3 != 0 {
+        return Err(corrupt("reserved sequence mode bits are set"));
+    }
+    // Literal lengths, offsets, match lengths.
+    let kinds: [(&[i16], u32, usize, u32); 3] = [(&LL_DEFAULT, 6, 35, 9), (&OF_DEFAULT, 5, 31, 8), (&ML_DEFAULT, 6, 52, 9)];
+    for (i, (default, accuracy, max_symbol, max_accuracy)) in kinds.into_iter().enumerate() {
+        let mode = (modes >> (6 - 2 * i)) & 3;
+        state.tables[i] = Some(match mode {
+            0 => FseTable::new(default, accuracy)?,
+            1 => {
+                let symbol = *block.get(pos).ok_or_else(truncated)?;
+                pos += 1;
+                if symbol as usize > max_symbol {
+                    return Err(corrupt("sequence code out of range"));
+                }
+                FseTable::rle(symbol)
+            }
+            2 => {
+                let (table, used) = FseTable::read(block.get(pos..).ok_or_else(truncated)?, max_symbol, max_accuracy)?;
+                pos += used;
+                table
+            }
+            _ => state.tables[i].take().ok_or_else(|| corrupt("repeated sequence table with none before"))?,
+        });
+    }
+
+    let [Some(ll_table), Some(of_table), Some(ml_table)] = &state.tables else {
+        unreachable!("all three tables were just set");
+    };
+    let mut bits = BackwardBits::new(block.get(pos..).ok_or_else(truncated)?)?;
+    let mut ll = FseState::new(ll_table, &mut bits);
+    let mut of = FseState::new(of_table, &mut bits);
+    let mut ml = FseState::new(ml_table, &mut bits);
+    let mut literal_pos = 0;
+    for n in 0..count {
+        let (of_code, ml_code, ll_code) = (of.symbol(), ml.symbol(), ll.symbol());
+        if of_code > 31 || ml_code > 52 || ll_code > 35 {
+            return Err(corrupt("sequence code out of range"));
+        }
+        let offset_value = (1usize << of_code) + bits.read(of_code as u32) as usize;
+        let (ml_base, ml_bits) = match_length_code(ml_code);
+        let match_len = ml_base as usize + bits.read(ml_bits) as usize;
+        let (ll_base, ll_bits) = literal_length_code(ll_code);
+        let literal_len = ll_base as usize + bits.read(ll_bits) as usize;
+        if n + 1 < count {
+            ll.update(&mut bits);
+            ml.update(&mut bits);
+            of.update(&mut bits);
+        }
+
+        let offsets = &mut state.offsets;
+        let offset = if offset_value > 3 {
+            *offsets = [offset_value - 3, offsets[0], offsets[1]];
+            offsets[0]
+        } else {
+            let index = offset_value - 1 + usize::from(literal_len == 0);
+            if index == 0 {
+                offsets[0]
+            } else {
+                let offset = if index == 3 { offsets[0].wrapping_sub(1) } else { offsets[index] };
+                if index > 1 {
+                    offsets[2] = offsets[1];
+                }
+                offsets[1] = offsets[0];
+                offsets[0] = offset;
+                offset
+            }
+        };
+
+        let literals_end = literal_pos + literal_len;
+        out.extend_from_slice(literals.get(literal_pos..literals_end).ok_or_else(|| corrupt("sequence overruns its literals"))?);
+        literal_pos = literals_end;
+        if offset == 0 || offset > out.len() - frame_start {
+            return Err(corrupt("match offset is out of range"));
+        }
+        // No block decompresses to more than MAX_BLOCK, however many
+        // sequences it has.
+        if out.len() - block_start + match_len > MAX_BLOCK {
+            return Err(corrupt("block decompresses to more than 128 KiB"));
+        }
+        let from = out.len() - offset;
+        if offset >= match_len {
+            out.extend_from_within(from..from + match_len);
+        } else {
+            for i in 0..match_len {
+                out.push(out[from + i]);
+            }
+        }
+    }
+    bits.finish()?;
+    out.extend_from_slice(&literals[literal_pos..]);
+    if out.len() - block_start > MAX_BLOCK {
+        return Err(corrupt("block decompresses to more than 128 KiB"));
+    }
+    Ok(())
+}
+
+fn read_literals(block: &[u8], state: &mut FrameState) -> Result<(Vec<u8>, usize), AegisError> {
+    let truncated = || corrupt("truncated literals");
+    let first = *block.first().ok_or_else(truncated)? as u64;
+    let (kind, size_format) = (first & 3, (first >> 2) & 3);
+    let header = |len: usize| -> Result<u64, AegisError> {
+        let bytes = block.get(..len).ok_or_else(truncated)?;
+        Ok(bytes.iter().rev().fold(0u64, |acc, &b| acc << 8 | b as u64))
+    };
+    if kind < 2 {
+        let (size, len) = match size_format {
+            0 | 2 => (first >> 3, 1),
+            1 => (header(2)? >> 4, 2),
+            _ => (header(3)? >> 4, 3),
+        };
+        let size = size as usize;
+        if size > MAX_BLOCK {
+            return Err(corrupt("too many literals"));
+        }
+        return Ok(match kind {
+            0 => (block.get(len..len + size).ok_or_else(truncated)?.to_vec(), len + size),
+            _ => (vec![*block.get(len).ok_or_else(truncated)?; size], len + 1),
+        });
+    }
+
+    let (streams, bits, len) = match size_format {
+        0 => (1, 10, 3),
+        1 => (4, 10, 3),
+        2 => (4, 14, 4),
+        _ => (4, 18, 5),
+    };
+    let fields = header(len)?;
+    let regenerated = ((fields >> 4) & ((1 << bits) - 1)) as usize;
+    let compressed = ((fields >> (4 + bits)) & ((1 << bits) - 1)) as usize;
+    if regenerated > MAX_BLOCK {
+        return Err(corrupt("too many literals"));
+    }
+    let mut data = block.get(len..len + compressed).ok_or_else(truncated)?;
+    if kind == 2 {
+        let (table, used) = HuffmanTable::read(data)?;
+        state.huffman = Some(table);
+        data = &data[used..];
+    }
+    let table = state.huffman.as_ref().ok_or_else(|| corrupt("literals reuse a Huffman table with none before"))?;
+    let mut literals = Vec::with_capacity(regenerated);
+    if streams == 1 {
+        table.decode_stream(data, regenerated, &mut literals)?;
+    } else {
+        let jump = data.get(..6).ok_or_else(truncated)?;
+        let sizes = [0, 2, 4].map(|i| u16::from_le_bytes([jump[i], jump[i + 1]]) as usize);
+        let segment = regenerated.div_ceil(4);
+        let mut rest = &data[6..];
+        let last = regenerated.checked_sub(3 * segment).ok_or_else(|| corrupt("too few literals for four streams"))?;
+        for size in sizes {
+            table.decode_stream(rest.get(..size).ok_or_else(truncated)?, segment, &mut literals)?;
+            rest = &rest[size..];
+        }
+        table.decode_stream(rest, last, &mut literals)?;
+    }
+    Ok((literals, len + compressed))
+}
+
+// --- Compression ---
+
+struct Sequence {
+    literal_len: u32,
+    match_len: u32,
+    offset: u32,
+}
+
+/// Compresses `data` as one frame, with its size and checksum.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = MAGIC.to_le_bytes().to_vec();
+    let size = data.len() as u64;
+    let (flag, size_bytes) = match size {
+        0..256 => (0, vec![size as u8]),
+        256..65792 => (1, ((size - 256) as u16).to_le_bytes().to_vec()),
+        65792..0x1_0000_0000 => (2, (size as u32).to_le_bytes().to_vec()),
+        _ => (3, size.to_le_bytes().to_vec()),
+    };
+    // Single segment, with a checksum.
+    out.push(flag << 6 | 0x20 | 0x04);
+    out.extend_from_slice(&size_bytes);
+
+    let ll_encoder = FseEncoder::new(&LL_DEFAULT, 6);
+    let of_encoder = FseEncoder::new(&OF_DEFAULT, 5);
+    let ml_encoder = FseEncoder::new(&ML_DEFAULT, 6);
+    let encoders = [&ll_encoder, &of_encoder, &ml_encoder];
+    let mut hashes = vec![0u32; 1 << HASH_LOG];
+    let mut start = 0;
+    loop {
+        let end = (start + MAX_BLOCK).min(data.len());
+        let last = end == data.len();
+        let block = &data[start..end];
+        let compressed = compress_block(data, start..end, &mut hashes, encoders);
+        let (kind, body): (u32, &[u8]) = match compressed {
+            Some(ref body) if body.len() < block.len() => (2, body),
+            _ if block.len() > 1 && block.iter().all(|&b| b == block[0]) => (1, &block[..1]),
+            _ => (0, block),
+        };
+        let size = if kind == 1 { block.len() } else { body.len() } as u32;
+        out.extend_from_slice(&(u32::from(last) | kind << 1 | size << 3).to_le_bytes()[..3]);
+        out.extend_from_slice(body);
+        if last {
+            break;
+        }
+        start = end;
+    }
+    out.extend_from_slice(&(xxh64(data, 0) as u32).to_le_bytes());
+    out
+}
+
+fn read_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(data[at..at + 4].try_into().expect("4 bytes"))
+}
+
+fn hash(value: u32) -> usize {
+    (value.wrapping_mul(2_654_435_761) >> (32 - HASH_LOG)) as usize
+}
+
+/// The compressed form of block `range` of `data`, finding matches in it
+/// and in the bytes before it; `None` if it has nothing worth compressing.
+fn compress_block(
+    data: &[u8],
+    range: std::ops::Range<usize>,
+    hashes: &mut [u32],
+    [ll_encoder, of_encoder, ml_encoder]: [&FseEncoder; 3],
+) -> Option<Vec<u8>> {
+    // `hashes` holds positions plus one, so that zero means none.
+    let mut sequences = Vec::new();
+    let mut literals = Vec::new();
+    let (mut anchor, mut i) = (range.start, range.start);
+    while i + MIN_MATCH <= range.end {
+        let slot = hash(read_u32(data, i));
+        let candidate = hashes[slot] as usize;
+        hashes[slot] = i as u32 + 1;
+        let Some(mut candidate) = candidate.checked_sub(1) else {
+            i += 1;
+            continue;
+        };
+        if i - candidate > MAX_OFFSET || read_u32(data, candidate) != read_u32(data, i) {
+            i += 1;
+            continue;
+        }
+        let mut len = MIN_MATCH;
+        while i + len < range.end && data[candidate + len] == data[i + len] {
+            len += 1;
+        }
+        while i > anchor && candidate > 0 && data[i - 1] == data[candidate - 1] {
+            i -= 1;
+            candidate -= 1;
+            len += 1;
+        }
+        literals.extend_from_slice(&data[anchor..i]);
+        sequences.push(Sequence { literal_len: (i - anchor) as u32, match_len: len as u32, offset: (i - candidate) as u32 });
+        for at in i + 1..(i + len).min(range.end.saturating_sub(MIN_MATCH - 1)) {
+            hashes[hash(read_u32(data, at))] = at as u32 + 1;
+        }
+        i += len;
+        anchor = i;
+    }
+    if sequences.is_empty() && range.len() < 64 {
+        return None;
+    }
+    literals.extend_from_slice(&data[anchor..range.end]);
+
+    let mut out = compress_literals(&literals).unwrap_or_else(|| raw_literals(&literals));
+    let count = sequences.len();
+    match count {
+        0..128 => out.push(count as u8),
+        128..0x7F00 => out.extend_from_slice(&[(count >> 8) as u8 + 128, count as u8]),
+        _ => {
+            out.push(255);
+            out.extend_from_slice(&((count - 0x7F00) as u16).to_le_bytes());
+        }
+    }
+    if count == 0 {
+        return Some(out);
+    }
+    // Predefined tables for all three.
+    out.push(0);
+
+    let codes: Vec<[(u8, u64, u32); 3]> = sequences
+        .iter()
+        .map(|s| {
+            let offset_value = s.offset + 3;
+            let of_bits = highest_bit(offset_value);
+            [
+                length_code(s.literal_len, 16, 0, &LL_CODES),
+                (of_bits as u8, (offset_value - (1 << of_bits)) as u64, of_bits),
+                length_code(s.match_len, 32, 3, &ML_CODES),
+            ]
+        })
+        .collect();
+    // Written in the reverse of the order the decoder reads: last sequence
+    // first, each one's state updates (offsets, match lengths, literal
+    // lengths) before its extra bits (literal lengths, match lengths,
+    // offsets), and the initial states at the end.
+    let mut writer = BitWriter::default();
+    let [ll, of, ml] = &codes[count - 1];
+    let mut states = [ll_encoder.first_state(ll.0), of_encoder.first_state(of.0), ml_encoder.first_state(ml.0)];
+    for (n, [ll, of, ml]) in codes.iter().enumerate().rev() {
+        if n + 1 < count {
+            let (of_state, of_update, of_update_bits) = of_encoder.step(of.0, states[1]);
+            let (ml_state, ml_update, ml_update_bits) = ml_encoder.step(ml.0, states[2]);
+            let (ll_state, ll_update, ll_update_bits) = ll_encoder.step(ll.0, states[0]);
+            writer.add(of_update, of_update_bits);
+            writer.add(ml_update, ml_update_bits);
+            writer.add(ll_update, ll_update_bits);
+            states = [ll_state, of_state, ml_state];
+        }
+        writer.add(ll.1, ll.2);
+        writer.add(ml.1, ml.2);
+        writer.add(of.1, of.2);
+    }
+    writer.add(states[2] as u64, ml_encoder.table.accuracy);
+    writer.add(states[1] as u64, of_encoder.table.accuracy);
+    writer.add(states[0] as u64, ll_encoder.table.accuracy);
+    out.extend_from_slice(&writer.finish());
+    Some(out)
+}
+
+// --- XXH64, for frame checksums ---
+
+const P1: u64 = 0x9E37_79B1_85EB_CA87;
+const P2: u64 = 0xC2B2_AE3D_27D4_EB4F;
+const P3: u64 = 0x1656_67B1_9E37_79F9;
+const P4: u64 = 0x85EB_CA77_C2B2_AE63;
+const P5: u64 = 0x27D4_EB2F_1656_67C5;
+
+fn xxh64_round(acc: u64, input: u64) -> u64 {
+    acc.wrapping_add(input.wrapping_mul(P2)).rotate_left(31).wrapping_mul(P1)
+}
+
+fn xxh64(data: &[u8], seed: u64) -> u64 {
+    let u64_at = |at: usize| u64::from_le_bytes(data[at..at + 8].try_into().expect("8 bytes"));
+    let mut pos = 0;
+    let mut hash = if data.len() >= 32 {
+        let mut v = [seed.wrapping_add(P1).wrapping_add(P2), seed.wrapping_add(P2), seed, seed.wrapping_sub(P1)];
+        while pos + 32 <= data.len() {
+            for (i, lane) in v.iter_mut().enumerate() {
+                *lane = xxh64_round(*lane, u64_at(pos + 8 * i));
+            }
+            pos += 32;
+        }
+        let mut hash = v[0]
+            .rotate_left(1)
+            .wrapping_add(v[1].rotate_left(7))
+            .wrapping_add(v[2].rotate_left(12))
+            .wrapping_add(v[3].rotate_left(18));
+        for lane in v {
+            hash = (hash ^ xxh64_round(0, lane)).wrapping_mul(P1).wrapping_add(P4);
+        }
+        hash
+    } else {
+        seed.wrapping_add(P5)
+    };
+    hash = hash.wrapping_add(data.len() as u64);
+    while pos + 8 <= data.len() {
+        hash = (hash ^ xxh64_round(0, u64_at(pos))).rotate_left(27).wrapping_mul(P1).wrapping_add(P4);
+        pos += 8;
+    }
+    if pos + 4 <= data.len() {
+        let word = u32::from_le_bytes(data[pos..pos + 4].try_into().expect("4 bytes")) as u64;
+        hash = (hash ^ word.wrapping_mul(P1)).rotate_left(23).wrapping_mul(P2).wrapping_add(P3);
+        pos += 4;
+    }
+    for &b in &data[pos..] {
+        hash = (hash ^ (b as u64).wrapping_mul(P5)).rotate_left(11).wrapping_mul(P1);
+    }
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(P2);
+    hash ^= hash >> 29;
+    hash = hash.wrapping_mul(P3);
+    hash ^ (hash >> 32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic bytes that do not compress.
+    fn noise(len: usize, mut seed: u64) -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                seed as u8
+            })
+            .collect()
+    }
+
+    /// Words picked by `noise`: text with plenty of matches and a skewed
+    /// byte distribution, so literals are Huffman-coded.
+    fn text(words: usize) -> Vec<u8> {
+        const WORDS: [&str; 8] = ["sealed ", "image ", "the ", "of ", "metadata ", "signature ", "aegis ", "key\n"];
+        noise(words, 7).iter().flat_map(|&n| WORDS[n as usize % WORDS.len()].bytes()).collect()
+    }
+
+    fn frame_header(descriptor: u8, rest: &[u8]) -> Vec<u8> {
+        let mut frame = MAGIC.to_le_bytes().to_vec();
+        frame.push(descriptor);
+        frame.extend_from_slice(rest);
+        frame
+    }
+
+    /// A single-segment frame without a checksum holding one last
+    /// compressed block.
+    fn compressed_block_frame(content_size: u8, block: &[u8]) -> Vec<u8> {
+        let mut frame = frame_header(0x20, &[content_size]);
+        frame.extend_from_slice(&(1 | 2 << 1 | (block.len() as u32) << 3).to_le_bytes()[..3]);
+        frame.extend_from_slice(block);
+        frame
+    }
+
+    fn assert_corrupt(input: &[u8]) {
+        match decompress(input, 1 << 20) {
+            Err(AegisError::InvalidCompression(_)) => {}
+            other => panic!("expected a compression error, got {:?}", other.map(|out| out.len())),
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let inputs = [
+            Vec::new(),
+            b"a".to_vec(),
+            br#"{"title":"Sunset","tags":["sunset","sunset","sunset"]}"#.to_vec(),
+            text(20_000),
+            noise(300_000, 1),
+            vec![0; 3 * MAX_BLOCK + 17],
+            [text(5_000), noise(70_000, 2), text(5_000)].concat(),
+        ];
+        for input in inputs {
+            let compressed = compress(&input);
+            assert_eq!(decompress(&compressed, input.len()).unwrap(), input, "{} bytes", input.len());
+        }
+    }
+
+    #[test]
+    fn compresses_repetitive_input() {
+        let input = text(20_000);
+        assert!(compress(&input).len() < input.len() / 3);
+    }
+
+    #[test]
+    fn reads_frames_of_the_reference_encoder() {
+        // `zstd -19` of a pangram paragraph, and `zstd -3 --no-check` of
+        // JSON metadata.
+        let frames: [(&str, &[u8]); 2] = [
+            (
+                "28b52ffd2498c50200b2051316806dc6002274e29516a16897d807ac2608eeaeb066f440e018f1f86d6c3479c96989f3d162\
+                 07153a5ab6a48cb425ace06a2b6389a56925a783bf7c768751e271b4c50bb48f8d348dcde9860200ccc2571a964a0f2645f565",
+                b"The quick brown fox jumps over the lazy dog. The quick brown fox jumps over the lazy cat. \
+                  Pack my box with five dozen liquor jugs. The quick brown fox!\n",
+            ),
+            (
+                "28b52ffd6054007503005206151980396d7aadedb92d1465e68b8802f5862d5899f50593a209694d7300c16920480e91ea3e\
+                 c7440b244c9d31bae9293bdcc7602082b7dc5acb62bd60f465d8dee7aa64a176f88eb5de92de5ba8a41bfd9245f64c08004\
+                 31b4accbae2c8145960e6a6d5a02c6c0680732928",
+                br#"{"title": "Sunset over the harbour", "tags": ["sunset", "harbour", "boats", "evening", "sunset", "harbour", "boats", "evening", "sunset", "harbour", "boats", "evening", "sunset", "harbour", "boats", "evening", "sunset", "harbour", "boats", "evening", "sunset", "harbour", "boats", "evening"], "camera": {"make": "Canon", "model": "EOS R5"}}"#,
+            ),
+        ];
+        for (frame, expected) in frames {
+            assert_eq!(decompress(&hex::decode(frame).unwrap(), 1 << 20).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn skips_skippable_frames() {
+        let mut input = SKIPPABLE_MAGIC.to_le_bytes().to_vec();
+        input.extend_from_slice(&3u32.to_le_bytes());
+        input.extend_from_slice(b"xyz");
+        input.extend_from_slice(&compress(b"payload"));
+        assert_eq!(decompress(&input, 100).unwrap(), b"payload");
+    }
+
+    #[test]
+    fn enforces_the_limit() {
+        let input = vec![7; 200_000];
+        let compressed = compress(&input);
+        assert!(decompress(&compressed, input.len()).is_ok());
+        assert_corrupt_with_limit(&compressed, input.len() - 1);
+        // Without a declared size the limit is enforced as blocks arrive.
+        let mut frame = frame_header(0x00, &[0x58]);
+        for last in [0, 0, 1] {
+            frame.extend_from_slice(&(last | 1 << 1 | (MAX_BLOCK as u32) << 3).to_le_bytes()[..3]);
+            frame.push(0);
+        }
+        assert!(decompress(&frame, 3 * MAX_BLOCK).is_ok());
+        assert_corrupt_with_limit(&frame, 2 * MAX_BLOCK);
+    }
+
+    fn assert_corrupt_with_limit(input: &[u8], limit: usize) {
+        assert!(matches!(decompress(input, limit), Err(AegisError::InvalidCompression(_))));
+    }
+
+    #[test]
+    fn rejects_truncated_frames() {
+        let compressed = compress(&text(2_000));
+        for len in 0..compressed.len() {
+            assert_corrupt(&compressed[..len]);
+        }
+    }
+
+    #[test]
+    fn rejects_trailing_garbage() {
+        let mut compressed = compress(b"payload");
+        compressed.extend_from_slice(b"junk");
+        assert_corrupt(&compressed);
+    }
+
+    #[test]
+    fn survives_corrupted_frames() {
+        // Any byte of a frame may be damaged; the result may be an error or
+        // other bytes, but never a panic.
+        for input in [text(3_000), noise(1_000, 3)] {
+            let compressed = compress(&input);
+            for at in 0..compressed.len() {
+                for flip in [0x01, 0x10, 0x80, 0xFF] {
+                    let mut damaged = compressed.clone();
+                    damaged[at] ^= flip;
+                    let _ = decompress(&damaged, 1 << 20);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_windows() {
+        // Not single-segment, window exponent 31: a 2 TiB window.
+        let mut frame = frame_header(0x00, &[0xF8]);
+        frame.extend_from_slice(&[0x01, 0x00, 0x00]);
+        assert_corrupt(&frame);
+    }
+
+    #[test]
+    fn rejects_frames_needing_a_dictionary() {
+        let mut frame = frame_header(0x21, &[0x01, 0x05]);
+        frame.extend_from_slice(&[0x01, 0x00, 0x00]);
+        assert_corrupt(&frame);
+    }
+
+    #[test]
+    fn rejects_bad_huffman_tables() {
+        for weights in [
+            // No symbol has a weight.
+            vec![0x82, 0x00],
+            // A weight over the maximum code length.
+            vec![0x82, 0xC1],
+            // Weights that leave a gap no single symbol fills.
+            vec![0x83, 0x11, 0x10],
+        ] {
+            // Compressed literals, one stream: 3 header bytes holding the
+            // regenerated and compressed sizes.
+            let compressed = weights.len() + 1;
+            let header = 2 | 4 << 4 | (compressed as u32) << 14;
+            let mut block = header.to_le_bytes()[..3].to_vec();
+            block.extend_from_slice(&weights);
+            block.push(0x01);
+            block.push(0x00);
+            assert_corrupt(&compressed_block_frame(4, &block));
+        }
+        // Literals reusing a table when there is none.
+        assert_corrupt(&compressed_block_frame(4, &[0x03 | 4 << 4, 0x40, 0x00, 0x01, 0x00]));
+    }
+
+    #[test]
+    fn rejects_bad_fse_tables() {
+        // No literals, one sequence, then a literal length table with
+        // accuracy 20.
+        assert_corrupt(&compressed_block_frame(4, &[0x00, 0x01, 0x80, 0x0F, 0x01]));
+        // A distribution that never adds up to its table.
+        assert_corrupt(&compressed_block_frame(4, &[0x00, 0x01, 0x80, 0x00, 0x00, 0x00, 0x01]));
+        // Repeating tables when there are none.
+        assert_corrupt(&compressed_block_frame(4, &[0x00, 0x01, 0xFC, 0x01]));
+        // A sequence code out of range in an RLE table.
+        assert_corrupt(&compressed_block_frame(4, &[0x00, 0x01, 0x40, 36, 0x01]));
+    }
+
+    #[test]
+    fn rejects_blocks_that_expand_past_the_block_size() {
+        // Eight raw bytes, then a block of three sequences copying 65539
+        // bytes each from offset 4: RLE tables for literal length 0, the
+        // repeat offset and match length code 52, whose 16 extra bits are
+        // zero in the bit stream.
+        let mut frame = frame_header(0x00, &[0x58]);
+        frame.extend_from_slice(&(8u32 << 3).to_le_bytes()[..3]);
+        frame.extend_from_slice(b"abcdefgh");
+        let block = [&[0x00, 0x03, 0x54, 0x00, 0x00, 52][..], &[0; 6], &[0x01]].concat();
+        frame.extend_from_slice(&(1 | 2 << 1 | (block.len() as u32) << 3).to_le_bytes()[..3]);
+        frame.extend_from_slice(&block);
+        assert_corrupt(&frame);
+    }
+
+    #[test]
+    fn rejects_bad_checksums() {
+        let mut compressed = compress(b"payload");
+        let last = compressed.len() - 1;
+        compressed[last] ^= 1;
+        assert_corrupt(&compressed);
+    }
+}
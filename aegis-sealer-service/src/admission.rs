@@ -0,0 +1,122 @@
+// aegis-sealer-service/src/admission.rs
+
+// Admission control for uploads. Requests are sorted into size classes by
+// their declared Content-Length, and each class has its own concurrency
+// limit and wait queue, so a burst of large uploads cannot starve small ones.
+
+use crate::AppError;
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::env;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tracing::{debug, warn};
+
+const RETRY_AFTER_SECS: &str = "5";
+
+pub struct SizeClass {
+    name: String,
+    max_bytes: u64,
+    semaphore: Semaphore,
+    queue_limit: usize,
+    waiting: AtomicUsize,
+}
+
+pub struct Admission {
+    // Ordered by ascending `max_bytes`; the last class also takes requests
+    // without a Content-Length.
+    classes: Vec<SizeClass>,
+}
+
+impl Admission {
+    /// Builds the size classes from `AEGIS_SIZE_CLASSES`, a comma-separated list
+    /// of `name=max_bytes/concurrency/queue_limit` entries, e.g.
+    /// `small=2097152/32/128,large=104857600/2/8`.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let spec = env::var("AEGIS_SIZE_CLASSES").unwrap_or_else(|_| {
+            "small=2097152/32/128,medium=20971520/8/32,large=104857600/2/8".to_string()
+        });
+        let mut classes = spec
+            .split(',')
+            .map(|entry| parse_class(entry.trim()))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if classes.is_empty() {
+            anyhow::bail!("AEGIS_SIZE_CLASSES must define at least one class");
+        }
+        classes.sort_by_key(|c| c.max_bytes);
+        Ok(Admission { classes })
+    }
+
+    /// The largest upload any size class accepts.
+    pub fn max_upload_bytes(&self) -> u64 {
+        self.classes.last().map(|c| c.max_bytes).unwrap_or(0)
+    }
+
+    fn classify(&self, content_length: Option<u64>) -> &SizeClass {
+        let last = self.classes.last().expect("at least one size class");
+        match content_length {
+            Some(len) => self.classes.iter().find(|c| len <= c.max_bytes).unwrap_or(last),
+            None => last,
+        }
+    }
+}
+
+pub(crate) fn parse_class(entry: &str) -> anyhow::Result<SizeClass> {
+    let invalid = || anyhow::anyhow!("invalid size class '{}'", entry);
+    let (name, limits) = entry.split_once('=').ok_or_else(invalid)?;
+    let parts: Vec<&str> = limits.split('/').collect();
+    let [max_bytes, permits, queue_limit] = parts.as_slice() else {
+        return Err(invalid());
+    };
+    Ok(SizeClass {
+        name: name.to_string(),
+        max_bytes: max_bytes.parse().map_err(|_| invalid())?,
+        semaphore: Semaphore::new(permits.parse().map_err(|_| invalid())?),
+        queue_limit: queue_limit.parse().map_err(|_| invalid())?,
+        waiting: AtomicUsize::new(0),
+    })
+}
+
+/// Middleware that holds a permit from the request's size class for the
+/// duration of the handler, rejecting with 503 when that class's queue is full.
+pub async fn limit(
+    State(admission): State<Arc<Admission>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let content_length = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    let class = admission.classify(content_length);
+
+    let permit = match class.semaphore.try_acquire() {
+        Ok(permit) => permit,
+        Err(_) => {
+            if class.waiting.fetch_add(1, Ordering::SeqCst) >= class.queue_limit {
+                class.waiting.fetch_sub(1, Ordering::SeqCst);
+                warn!(size_class = %class.name, "Upload queue full; rejecting request.");
+                return Ok((
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    [(header::RETRY_AFTER, RETRY_AFTER_SECS)],
+                    format!("Too many '{}' uploads in progress. Try again later.", class.name),
+                )
+                    .into_response());
+            }
+            debug!(size_class = %class.name, "Waiting for upload slot.");
+            let permit = class.semaphore.acquire().await;
+            class.waiting.fetch_sub(1, Ordering::SeqCst);
+            permit.expect("size class semaphores are never closed")
+        }
+    };
+
+    let response = next.run(request).await;
+    drop(permit);
+    Ok(response)
+}
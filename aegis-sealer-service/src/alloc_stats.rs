@@ -0,0 +1,104 @@
+// aegis-sealer-service/src/alloc_stats.rs
+
+// A thin wrapper around the system allocator that keeps running totals, so
+// memory growth can be attributed to heap usage rather than guessed at from
+// RSS. Only compiled in with the `alloc-stats` feature.
+
+use axum::Json;
+use serde::Serialize;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+static IN_USE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static DEALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+pub struct TrackingAllocator;
+
+impl TrackingAllocator {
+    fn added(size: usize) {
+        let now = IN_USE.fetch_add(size, Ordering::Relaxed) + size;
+        PEAK.fetch_max(now, Ordering::Relaxed);
+    }
+
+    fn removed(size: usize) {
+        IN_USE.fetch_sub(size, Ordering::Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            Self::added(layout.size());
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc_zeroed(layout) };
+        if !ptr.is_null() {
+            Self::added(layout.size());
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        Self::removed(layout.size());
+        DEALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = unsafe { System.realloc(ptr, layout, new_size) };
+        if !new_ptr.is_null() {
+            Self::removed(layout.size());
+            Self::added(new_size);
+        }
+        new_ptr
+    }
+}
+
+#[derive(Serialize)]
+pub struct MemorySnapshot {
+    pub heap_in_use_bytes: usize,
+    pub heap_peak_bytes: usize,
+    pub allocations: u64,
+    pub deallocations: u64,
+    pub rss_bytes: Option<u64>,
+}
+
+/// Bytes currently allocated through the global allocator.
+pub fn in_use() -> usize {
+    IN_USE.load(Ordering::Relaxed)
+}
+
+/// Highest value `in_use()` has reached since startup.
+pub fn peak() -> usize {
+    PEAK.load(Ordering::Relaxed)
+}
+
+pub fn snapshot() -> MemorySnapshot {
+    MemorySnapshot {
+        heap_in_use_bytes: in_use(),
+        heap_peak_bytes: peak(),
+        allocations: ALLOCATIONS.load(Ordering::Relaxed),
+        deallocations: DEALLOCATIONS.load(Ordering::Relaxed),
+        rss_bytes: resident_set_size(),
+    }
+}
+
+// Reads VmRSS from procfs; unavailable on non-Linux platforms.
+fn resident_set_size() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+pub async fn memory_handler() -> Json<MemorySnapshot> {
+    Json(snapshot())
+}
@@ -0,0 +1,149 @@
+// aegis-sealer-service/src/audit.rs
+
+use aegis_core::{format::AegisAncient, keys::Fingerprint};
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// A record of a single seal operation. Only hashes and the metadata string
+/// are kept; the image itself is never stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuditAction {
+    Seal,
+    /// A countersignature over an existing container. Resealing has to
+    /// parse containers, so it only exists with the 'verifier' feature.
+    #[cfg(feature = "verifier")]
+    Reseal,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Seal => "seal",
+            #[cfg(feature = "verifier")]
+            AuditAction::Reseal => "reseal",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "seal" => Some(AuditAction::Seal),
+            #[cfg(feature = "verifier")]
+            "reseal" => Some(AuditAction::Reseal),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AuditRecord {
+    pub id: u64,
+    pub action: AuditAction,
+    pub sealed_at: SystemTime,
+    pub image_hash: String,
+    pub image_size: usize,
+    pub metadata_hash: String,
+    pub metadata: String,
+    pub key_fingerprint: Fingerprint,
+}
+
+/// In-memory store of the most recent seal operations.
+pub struct AuditStore {
+    capacity: usize,
+    inner: Mutex<AuditInner>,
+}
+
+struct AuditInner {
+    next_id: u64,
+    records: VecDeque<AuditRecord>,
+}
+
+impl AuditStore {
+    pub fn new(capacity: usize) -> Self {
+        AuditStore {
+            capacity,
+            inner: Mutex::new(AuditInner {
+                next_id: 1,
+                records: VecDeque::with_capacity(capacity),
+            }),
+        }
+    }
+
+    /// Records a freshly sealed container and returns the stored record.
+    pub fn record(&self, ancient: &AegisAncient) -> AuditRecord {
+        self.record_action(AuditAction::Seal, ancient)
+    }
+
+    pub fn record_action(&self, action: AuditAction, ancient: &AegisAncient) -> AuditRecord {
+        self.record_streamed(
+            action,
+            &ancient.public_key,
+            &ancient.metadata,
+            hex::encode(Sha256::digest(&ancient.image_data)),
+            ancient.image_data.len(),
+        )
+    }
+
+    /// Records a seal whose image was streamed rather than held in memory;
+    /// the caller supplies the image's hex SHA-256 and size.
+    pub fn record_streamed(
+        &self,
+        action: AuditAction,
+        public_key: &[u8],
+        metadata: &str,
+        image_hash: String,
+        image_size: usize,
+    ) -> AuditRecord {
+        self.record_at(action, public_key, metadata, image_hash, image_size, SystemTime::now())
+    }
+
+    /// Records a seal that happened at `sealed_at`, as when restoring
+    /// records from the write-ahead log.
+    pub fn record_at(
+        &self,
+        action: AuditAction,
+        public_key: &[u8],
+        metadata: &str,
+        image_hash: String,
+        image_size: usize,
+        sealed_at: SystemTime,
+    ) -> AuditRecord {
+        let mut inner = self.inner.lock().unwrap();
+        let record = AuditRecord {
+            id: inner.next_id,
+            action,
+            sealed_at,
+            image_hash,
+            image_size,
+            metadata_hash: hex::encode(Sha256::digest(metadata.as_bytes())),
+            metadata: metadata.to_string(),
+            key_fingerprint: Fingerprint::of(public_key),
+        };
+        inner.next_id += 1;
+        if inner.records.len() == self.capacity {
+            inner.records.pop_front();
+        }
+        inner.records.push_back(record.clone());
+        record
+    }
+
+    /// Total records written since startup and the newest record, if any.
+    pub fn checkpoint(&self) -> (u64, Option<AuditRecord>) {
+        let inner = self.inner.lock().unwrap();
+        (inner.next_id - 1, inner.records.back().cloned())
+    }
+
+    /// Returns up to `limit` records, newest first, with IDs strictly below `before`.
+    pub fn page(&self, before: Option<u64>, limit: usize) -> Vec<AuditRecord> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .records
+            .iter()
+            .rev()
+            .filter(|r| before.is_none_or(|b| r.id < b))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
@@ -0,0 +1,367 @@
+// aegis-sealer-service/src/audit_log.rs
+
+// Persistent, tamper-evident record of every seal, for compliance. Unlike
+// the in-memory audit store, which keeps recent seals for feeds and bundles,
+// this log is never trimmed and says who asked for each seal.
+//
+// Each seal appends one JSON line: a sequence number, the time, the action,
+// the request ID, the caller (`tenant:<name>`, `key:<hash prefix>`,
+// `delegation:<token ID>`, `dam`, or none for anonymous requests), the ID
+// of the delegation token it was made under, if any, the image and metadata
+// SHA-256, the key ID and fingerprint, and the audit store ID. Each line also holds `prev`,
+// the `hash` of the line before it (64 zeros for the first), and its own
+// `hash`, the SHA-256 of the line's JSON without `hash`. Editing, removing
+// or reordering lines breaks the chain from that point on; the log is
+// checked on startup, which fails if it is broken, and on every query.
+//
+// The log lives at `AEGIS_AUDIT_LOG` (default `aegis-audit.log`); set it to
+// an empty string to disable it. Lines are synced before the sealed
+// container is returned, and a seal whose line cannot be written fails.
+//
+// GET /audit searches the log, oldest first:
+//
+//     GET /audit?caller=tenant:acme&since=2025-01-01T00:00:00Z&limit=50
+//
+// filtering on `request_id`, `caller`, `tenant`, `delegation`,
+// `image_sha256`, `key_id`, `action`, and RFC 3339 `since`/`until`; `after` resumes from the
+// `next_after` of the previous page. The response reports whether the chain
+// is intact. Every response carries an `x-request-id`, the client's own if
+// it sent a usable one.
+
+use crate::{
+    auth::{Delegated, Principal, Tenant},
+    hooks::SealEvent,
+    AppError, AppState,
+};
+use aegis_core::{delegation::Delegation, time};
+use axum::{
+    extract::{FromRequestParts, Query, Request, State},
+    http::{request::Parts, HeaderValue, StatusCode},
+    middleware::Next,
+    response::Response,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 1000;
+/// Longest client-supplied request ID that is kept rather than replaced.
+const MAX_REQUEST_ID: usize = 128;
+
+/// The ID of a request, set by `request_id`.
+#[derive(Clone, Debug)]
+pub struct RequestId(pub String);
+
+/// Who made a request, for the audit log and seal hooks.
+#[derive(Clone, Debug, Default)]
+pub struct Caller {
+    pub request_id: Option<String>,
+    /// See `auth::Principal`.
+    pub principal: Option<String>,
+    pub tenant: Option<String>,
+    /// The delegation token the request was authorized by, if any.
+    pub delegation: Option<Delegation>,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Caller {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Caller {
+            request_id: parts.extensions.get::<RequestId>().map(|id| id.0.clone()),
+            principal: parts.extensions.get::<Principal>().map(|p| p.0.clone()),
+            tenant: parts.extensions.get::<Tenant>().map(|t| t.0.clone()),
+            delegation: parts.extensions.get::<Delegated>().map(|d| d.0.clone()),
+        })
+    }
+}
+
+/// One line of the log.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Entry {
+    pub seq: u64,
+    pub at: String,
+    pub action: String,
+    pub request_id: Option<String>,
+    pub caller: Option<String>,
+    pub tenant: Option<String>,
+    // Left out when absent, so lines written before tokens existed keep
+    // their hashes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delegation: Option<String>,
+    pub image_sha256: String,
+    pub image_size: u64,
+    pub metadata_sha256: String,
+    pub key_id: Option<String>,
+    pub key_fingerprint: Option<String>,
+    pub audit_id: Option<u64>,
+    pub prev: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Line {
+    #[serde(flatten)]
+    entry: Entry,
+    hash: String,
+}
+
+impl Entry {
+    fn hash(&self) -> String {
+        hex::encode(Sha256::digest(serde_json::to_vec(self).expect("entries serialize")))
+    }
+}
+
+// The tail of the chain, which the next line extends.
+struct Head {
+    file: File,
+    seq: u64,
+    hash: String,
+}
+
+pub struct AuditLog {
+    path: Option<PathBuf>,
+    head: Option<tokio::sync::Mutex<Head>>,
+}
+
+/// The result of walking the chain.
+struct Chain {
+    lines: Vec<Line>,
+    /// The sequence number of the first line that does not follow from the
+    /// one before it.
+    broken_at: Option<u64>,
+}
+
+fn walk(contents: &str) -> Result<Chain, String> {
+    let mut chain = Chain { lines: Vec::new(), broken_at: None };
+    let mut prev = GENESIS.to_string();
+    let lines: Vec<&str> = contents.lines().collect();
+    for (n, text) in lines.iter().enumerate() {
+        let line: Line = match serde_json::from_str(text) {
+            Ok(line) => line,
+            // A torn final line from a crash mid-append, or an append in
+            // progress.
+            Err(_) if n + 1 == lines.len() && !contents.ends_with('\n') => break,
+            Err(e) => return Err(format!("line {}: {}", n + 1, e)),
+        };
+        let expected_seq = chain.lines.last().map_or(1, |l| l.entry.seq + 1);
+        if chain.broken_at.is_none()
+            && (line.entry.prev != prev || line.entry.hash() != line.hash || line.entry.seq != expected_seq)
+        {
+            chain.broken_at = Some(line.entry.seq);
+        }
+        prev = line.hash.clone();
+        chain.lines.push(line);
+    }
+    Ok(chain)
+}
+
+impl AuditLog {
+    /// Opens the log from `AEGIS_AUDIT_LOG`, failing if its chain is broken.
+    pub async fn open() -> anyhow::Result<Self> {
+        let path = env::var("AEGIS_AUDIT_LOG").unwrap_or_else(|_| "aegis-audit.log".into());
+        if path.is_empty() {
+            warn!("AEGIS_AUDIT_LOG is empty; seals are not recorded in a persistent audit log.");
+            return Ok(AuditLog { path: None, head: None });
+        }
+        let path = PathBuf::from(path);
+        let contents = match tokio::fs::read_to_string(&path).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let chain = walk(&contents).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+        if let Some(seq) = chain.broken_at {
+            anyhow::bail!(
+                "{}: the hash chain is broken at entry {}; the log has been altered since it was written",
+                path.display(),
+                seq
+            );
+        }
+        let (seq, hash) = chain
+            .lines
+            .last()
+            .map_or((0, GENESIS.to_string()), |l| (l.entry.seq, l.hash.clone()));
+        info!(path = %path.display(), entries = seq, "Audit log chain verified.");
+
+        let mut file = OpenOptions::new().create(true).append(true).open(&path).await?;
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            warn!("Terminating a partially written final audit log line.");
+            file.write_all(b"\n").await?;
+        }
+        Ok(AuditLog {
+            path: Some(path),
+            head: Some(tokio::sync::Mutex::new(Head { file, seq, hash })),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.head.is_some()
+    }
+
+    /// Appends a completed seal, as given to `after_seal` hooks, and syncs
+    /// the line to disk.
+    pub async fn append(&self, event: &SealEvent) -> Result<(), AppError> {
+        let Some(head) = &self.head else {
+            return Ok(());
+        };
+        let mut head = head.lock().await;
+        let entry = Entry {
+            seq: head.seq + 1,
+            at: time::rfc3339(SystemTime::now()),
+            action: event.action.to_string(),
+            request_id: event.request_id.clone(),
+            caller: event.caller.clone(),
+            tenant: event.tenant.clone(),
+            delegation: event.delegation.clone(),
+            image_sha256: event.image_sha256.clone(),
+            image_size: event.image_size,
+            metadata_sha256: hex::encode(Sha256::digest(event.metadata.as_bytes())),
+            key_id: event.key_id.clone(),
+            key_fingerprint: event.key_fingerprint.clone(),
+            audit_id: event.audit_id,
+            prev: head.hash.clone(),
+        };
+        let line = Line { hash: entry.hash(), entry };
+        let mut bytes = serde_json::to_vec(&line)?;
+        bytes.push(b'\n');
+        let written = async {
+            head.file.write_all(&bytes).await?;
+            head.file.sync_data().await
+        };
+        if let Err(e) = written.await {
+            warn!(error = %e, "Failed to append to the audit log.");
+            return Err(AppError(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "The seal could not be recorded in the audit log.".into(),
+            ));
+        }
+        head.seq = line.entry.seq;
+        head.hash = line.hash;
+        Ok(())
+    }
+
+    /// Every entry, oldest first, or `None` if the log is disabled. Fails if
+    /// the chain is broken, so that nothing is built on an altered log.
+    pub(crate) async fn entries(&self) -> Result<Option<Vec<Entry>>, AppError> {
+        let Some(path) = &self.path else {
+            return Ok(None);
+        };
+        let contents = tokio::fs::read_to_string(path).await?;
+        let chain = walk(&contents).map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, format!("Audit log: {}", e)))?;
+        if let Some(seq) = chain.broken_at {
+            return Err(AppError(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Audit log: the hash chain is broken at entry {}.", seq),
+            ));
+        }
+        Ok(Some(chain.lines.into_iter().map(|l| l.entry).collect()))
+    }
+}
+
+#[derive(Deserialize, Default)]
+pub struct AuditQuery {
+    request_id: Option<String>,
+    caller: Option<String>,
+    tenant: Option<String>,
+    delegation: Option<String>,
+    image_sha256: Option<String>,
+    key_id: Option<String>,
+    action: Option<String>,
+    since: Option<String>,
+    until: Option<String>,
+    after: Option<u64>,
+    limit: Option<usize>,
+}
+
+/// GET /audit
+pub async fn audit_handler(
+    State(state): State<AppState>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Value>, AppError> {
+    let Some(path) = &state.audit_log.path else {
+        return Err(AppError(StatusCode::NOT_FOUND, "The audit log is disabled (AEGIS_AUDIT_LOG).".into()));
+    };
+    let bound = |name: &str, value: &Option<String>| -> Result<Option<i64>, AppError> {
+        value
+            .as_deref()
+            .map(|v| {
+                time::parse_rfc3339(v)
+                    .ok_or_else(|| AppError(StatusCode::BAD_REQUEST, format!("'{}' must be an RFC 3339 time.", name)))
+            })
+            .transpose()
+    };
+    let (since, until) = (bound("since", &query.since)?, bound("until", &query.until)?);
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    let contents = tokio::fs::read_to_string(path).await?;
+    let chain = walk(&contents).map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, format!("Audit log: {}", e)))?;
+    let matches = |e: &Entry| {
+        let equals = |filter: &Option<String>, value: Option<&str>| filter.as_deref().is_none_or(|f| Some(f) == value);
+        let at = time::parse_rfc3339(&e.at).unwrap_or(0);
+        query.after.is_none_or(|after| e.seq > after)
+            && equals(&query.request_id, e.request_id.as_deref())
+            && equals(&query.caller, e.caller.as_deref())
+            && equals(&query.tenant, e.tenant.as_deref())
+            && equals(&query.delegation, e.delegation.as_deref())
+            && equals(&query.image_sha256, Some(&e.image_sha256))
+            && equals(&query.key_id, e.key_id.as_deref())
+            && equals(&query.action, Some(&e.action))
+            && since.is_none_or(|since| at >= since)
+            && until.is_none_or(|until| at < until)
+    };
+    let mut found = chain.lines.iter().filter(|l| matches(&l.entry));
+    let entries: Vec<&Line> = found.by_ref().take(limit).collect();
+    let next_after = match found.next() {
+        Some(_) => entries.last().map(|l| l.entry.seq),
+        None => None,
+    };
+    Ok(Json(json!({
+        "entries": entries,
+        "next_after": next_after,
+        "chain": {
+            "valid": chain.broken_at.is_none(),
+            "broken_at": chain.broken_at,
+            "length": chain.lines.len(),
+            "head": chain.lines.last().map(|l| l.hash.as_str()),
+        },
+    })))
+}
+
+static NEXT_REQUEST: AtomicU64 = AtomicU64::new(0);
+
+/// Middleware giving every request an ID: the client's `x-request-id` if it
+/// is short and printable, otherwise a new one. The ID is echoed on the
+/// response.
+pub async fn request_id(mut request: Request, next: Next) -> Response {
+    let given = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty() && v.len() <= MAX_REQUEST_ID && v.chars().all(|c| c.is_ascii_graphic()))
+        .map(str::to_string);
+    let id = given.unwrap_or_else(|| {
+        let nanos = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_nanos();
+        let mut hasher = Sha256::new();
+        hasher.update(nanos.to_be_bytes());
+        hasher.update(std::process::id().to_be_bytes());
+        hasher.update(NEXT_REQUEST.fetch_add(1, Ordering::Relaxed).to_be_bytes());
+        hex::encode(&hasher.finalize()[..16])
+    });
+    request.extensions_mut().insert(RequestId(id.clone()));
+    let mut response = next.run(request).await;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
@@ -0,0 +1,678 @@
+// aegis-sealer-service/src/auth.rs
+
+// Per-route access policy. Each route is either `public` (anonymous access
+// allowed, subject to per-IP rate limits and a smaller body cap) or
+// `authenticated` (requires an API key). Callers presenting a valid key are
+// never subject to the anonymous limits. Without `AEGIS_API_KEYS` every
+// route is public, and every caller is held to those limits.
+//
+// The sealing endpoints also accept a delegation token (see
+// `aegis_core::delegation`) signed by one of `AEGIS_DELEGATION_KEYS`, in
+// place of an API key:
+//
+//     Authorization: Delegation eyJpZCI6...
+//
+// A token whose signature checks out authorizes the request as
+// `delegation:<token ID>`; its expiry, size limit and metadata constraints
+// are checked before the seal is signed. Tokens are checked even when API
+// keys are not configured, so their constraints always hold.
+
+use crate::AppError;
+use aegis_core::delegation::{self, Delegation};
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http_body_util::Limited;
+use p256::ecdsa::VerifyingKey;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Anonymous clients that hit the rate limit this many times within one
+/// refill period are blocked for `BAN_DURATION`.
+const STRIKES_BEFORE_BAN: u32 = 5;
+const BAN_DURATION: Duration = Duration::from_secs(600);
+/// How often the limiter drops the buckets of clients that have gone quiet.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+/// Bucket count above which the limiter prunes before `PRUNE_INTERVAL` is
+/// up; after each such prune the threshold doubles from what is left, so a
+/// flood of distinct addresses cannot make every request scan the table.
+const PRUNE_ABOVE: usize = 100_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Public,
+    Authenticated,
+}
+
+/// The tenant an authenticated request acts for, added to the request's
+/// extensions by `enforce` when the presented API key belongs to one.
+#[derive(Clone, Debug)]
+pub struct Tenant(pub String);
+
+/// Who an authenticated request was made by: `tenant:<name>` for a tenant's
+/// key, otherwise `key:` and the first 8 hex digits of the key's SHA-256.
+/// Added to the request's extensions by `enforce`.
+#[derive(Clone, Debug)]
+pub struct Principal(pub String);
+
+/// The most any compressed block in an anonymous request's container may
+/// inflate to, in bytes. Added to the request's extensions by `enforce`.
+#[cfg(feature = "verifier")]
+#[derive(Clone, Copy, Debug)]
+pub struct InflateLimit(pub usize);
+
+/// The delegation token a request to a sealing endpoint presented, added
+/// to the request's extensions by `enforce` once its signature is checked.
+#[derive(Clone, Debug)]
+pub struct Delegated(pub Delegation);
+
+/// The routes a delegation token may be presented to.
+const DELEGABLE: [&str; 2] = ["/seal", "/seal/batch"];
+
+struct ApiKey {
+    // SHA-256 of the key, so comparisons don't leak key bytes.
+    hash: [u8; 32],
+    tenant: Option<String>,
+}
+
+pub struct AuthPolicy {
+    keys: Vec<ApiKey>,
+    routes: HashMap<String, Access>,
+    trust_proxy: bool,
+    anonymous: AnonymousLimiter,
+    anonymous_max_body: u64,
+    #[cfg(feature = "verifier")]
+    anonymous_max_inflate: usize,
+    delegation_issuers: Vec<VerifyingKey>,
+}
+
+impl AuthPolicy {
+    /// Builds the policy from the environment:
+    ///
+    /// - `AEGIS_API_KEYS`: comma-separated API keys, each optionally prefixed
+    ///   with `tenant:` to tie it to a tenant. When unset, authentication is
+    ///   disabled and every route behaves as public.
+    /// - `AEGIS_ROUTE_ACCESS`: comma-separated `path=public|authenticated`
+    ///   overrides of the defaults below.
+    /// - `AEGIS_ANON_RATE_PER_MIN` / `AEGIS_ANON_BURST`: anonymous per-IP rate limit.
+    /// - `AEGIS_ANON_MAX_BODY`: largest request body accepted from anonymous callers.
+    /// - `AEGIS_ANON_MAX_INFLATE`: most a compressed block sent by an anonymous
+    ///   caller may decompress to.
+    /// - `AEGIS_TRUST_PROXY`: use the last `X-Forwarded-For` address as the client IP.
+    /// - `AEGIS_DELEGATION_KEYS`: comma-separated hex SEC1 public keys whose
+    ///   delegation tokens are accepted.
+    pub fn from_env(defaults: &[(&str, Access)]) -> anyhow::Result<Self> {
+        let keys = parse_keys(&env::var("AEGIS_API_KEYS").unwrap_or_default());
+        let routes = parse_routes(defaults, &env::var("AEGIS_ROUTE_ACCESS").unwrap_or_default())?;
+
+        if keys.is_empty() {
+            warn!("AEGIS_API_KEYS is not set; all routes are open to anonymous callers, within the anonymous limits.");
+        } else {
+            info!(keys = keys.len(), "API key authentication enabled.");
+        }
+
+        let delegation_issuers = env::var("AEGIS_DELEGATION_KEYS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|k| !k.is_empty())
+            .map(|k| {
+                hex::decode(k)
+                    .ok()
+                    .and_then(|sec1| VerifyingKey::from_sec1_bytes(&sec1).ok())
+                    .ok_or_else(|| anyhow::anyhow!("AEGIS_DELEGATION_KEYS: '{}' is not a hex SEC1 public key", k))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if !delegation_issuers.is_empty() {
+            info!(issuers = delegation_issuers.len(), "Delegation tokens accepted for sealing.");
+        }
+
+        let per_minute: f64 = env_number("AEGIS_ANON_RATE_PER_MIN", 30.0)?;
+        let burst: f64 = env_number("AEGIS_ANON_BURST", 10.0)?;
+        Ok(AuthPolicy {
+            keys,
+            routes,
+            trust_proxy: matches!(env::var("AEGIS_TRUST_PROXY").as_deref(), Ok("true" | "1")),
+            anonymous: AnonymousLimiter::new(per_minute / 60.0, burst),
+            anonymous_max_body: env_number("AEGIS_ANON_MAX_BODY", 10.0 * 1024.0 * 1024.0)? as u64,
+            #[cfg(feature = "verifier")]
+            anonymous_max_inflate: env_number("AEGIS_ANON_MAX_INFLATE", 16.0 * 1024.0 * 1024.0)? as usize,
+            delegation_issuers,
+        })
+    }
+
+    pub fn access_for(&self, path: &str) -> Access {
+        if self.keys.is_empty() {
+            return Access::Public;
+        }
+        // Unlisted routes require authentication.
+        self.routes.get(path).copied().unwrap_or(Access::Authenticated)
+    }
+
+    /// The API key presented in the request headers, if it is a valid one.
+    fn authenticate(&self, headers: &HeaderMap) -> Option<&ApiKey> {
+        let presented = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .or_else(|| headers.get("x-api-key").and_then(|v| v.to_str().ok()))?;
+        let hash: [u8; 32] = Sha256::digest(presented.trim().as_bytes()).into();
+        self.keys.iter().find(|k| k.hash == hash)
+    }
+
+    pub fn accepts_delegation(&self) -> bool {
+        !self.delegation_issuers.is_empty()
+    }
+
+    /// The delegation token presented in the request headers, checked
+    /// against the configured issuers; `None` if there is none.
+    fn delegation(&self, headers: &HeaderMap) -> Option<Result<Delegation, String>> {
+        let token = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Delegation "))?;
+        if self.delegation_issuers.is_empty() {
+            return Some(Err("delegation tokens are not accepted (AEGIS_DELEGATION_KEYS)".into()));
+        }
+        Some(delegation::verify(token, &self.delegation_issuers).map_err(|e| e.to_string()))
+    }
+
+    fn client_ip(&self, request: &Request) -> Option<IpAddr> {
+        client_ip(request, self.trust_proxy)
+    }
+}
+
+/// `AEGIS_API_KEYS`: comma-separated keys, each optionally `tenant:key`.
+fn parse_keys(spec: &str) -> Vec<ApiKey> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|k| !k.is_empty())
+        .map(|entry| {
+            let (tenant, key) = match entry.split_once(':') {
+                Some((tenant, key)) => (Some(tenant.trim().to_string()), key.trim()),
+                None => (None, entry),
+            };
+            ApiKey {
+                hash: Sha256::digest(key.as_bytes()).into(),
+                tenant,
+            }
+        })
+        .collect()
+}
+
+/// `defaults` with the `path=public|authenticated` overrides of
+/// `AEGIS_ROUTE_ACCESS` applied.
+fn parse_routes(defaults: &[(&str, Access)], spec: &str) -> anyhow::Result<HashMap<String, Access>> {
+    let mut routes: HashMap<String, Access> = defaults
+        .iter()
+        .map(|(path, access)| (path.to_string(), *access))
+        .collect();
+    for entry in spec.split(',') {
+        let entry = entry.trim();
+        if entry.is_empty() {
+            continue;
+        }
+        let (path, access) = entry
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("invalid AEGIS_ROUTE_ACCESS entry '{}'", entry))?;
+        let access = match access.trim() {
+            "public" => Access::Public,
+            "authenticated" => Access::Authenticated,
+            other => anyhow::bail!("unknown access level '{}' for {}", other, path),
+        };
+        routes.insert(path.trim().to_string(), access);
+    }
+    Ok(routes)
+}
+
+/// The client's address: the last `X-Forwarded-For` entry when behind a
+/// trusted proxy, otherwise the connection's peer. The proxy appends the
+/// address it saw; everything before that came from the client and could
+/// be anything.
+pub(crate) fn client_ip(request: &Request, trust_proxy: bool) -> Option<IpAddr> {
+    if trust_proxy
+        && let Some(ip) = request
+            .headers()
+            .get_all("x-forwarded-for")
+            .iter()
+            .next_back()
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.rsplit(',').next())
+            .and_then(|v| v.trim().parse().ok())
+    {
+        return Some(ip);
+    }
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ci| ci.0.ip())
+}
+
+fn env_number(name: &str, default: f64) -> anyhow::Result<f64> {
+    match env::var(name) {
+        Ok(v) => v
+            .parse()
+            .map_err(|_| anyhow::anyhow!("{} must be a number, got '{}'", name, v)),
+        Err(_) => Ok(default),
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    strikes: u32,
+    banned_until: Option<Instant>,
+}
+
+struct Buckets {
+    by_ip: HashMap<Option<IpAddr>, Bucket>,
+    next_prune: Instant,
+    prune_above: usize,
+}
+
+/// Token-bucket limiter keyed by client IP, with temporary bans for clients
+/// that keep hammering after being limited. Clients whose address is
+/// unknown share the `None` bucket. A client that is not banned and whose
+/// bucket has refilled is no different from one never seen, so its bucket
+/// is dropped at the next prune.
+struct AnonymousLimiter {
+    refill_per_sec: f64,
+    burst: f64,
+    buckets: Mutex<Buckets>,
+}
+
+enum Verdict {
+    Allowed,
+    Limited { retry_after: u64 },
+    Banned { retry_after: u64 },
+}
+
+impl AnonymousLimiter {
+    fn new(refill_per_sec: f64, burst: f64) -> Self {
+        AnonymousLimiter {
+            refill_per_sec,
+            burst,
+            buckets: Mutex::new(Buckets {
+                by_ip: HashMap::new(),
+                next_prune: Instant::now() + PRUNE_INTERVAL,
+                prune_above: PRUNE_ABOVE,
+            }),
+        }
+    }
+
+    fn check(&self, ip: Option<IpAddr>) -> Verdict {
+        self.check_at(ip, Instant::now())
+    }
+
+    fn check_at(&self, ip: Option<IpAddr>, now: Instant) -> Verdict {
+        let mut buckets = self.buckets.lock().unwrap();
+        if now >= buckets.next_prune || buckets.by_ip.len() > buckets.prune_above {
+            let (refill_per_sec, burst) = (self.refill_per_sec, self.burst);
+            buckets.by_ip.retain(|_, b| {
+                let refilled = b.tokens + now.duration_since(b.updated).as_secs_f64() * refill_per_sec;
+                b.banned_until.is_some_and(|until| until > now) || refilled < burst
+            });
+            buckets.next_prune = now + PRUNE_INTERVAL;
+            buckets.prune_above = PRUNE_ABOVE.max(buckets.by_ip.len() * 2);
+        }
+        let bucket = buckets.by_ip.entry(ip).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+            strikes: 0,
+            banned_until: None,
+        });
+
+        if let Some(until) = bucket.banned_until {
+            if until > now {
+                return Verdict::Banned {
+                    retry_after: (until - now).as_secs().max(1),
+                };
+            }
+            bucket.banned_until = None;
+            bucket.strikes = 0;
+        }
+
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.refill_per_sec).min(self.burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            bucket.strikes = 0;
+            return Verdict::Allowed;
+        }
+
+        bucket.strikes += 1;
+        if bucket.strikes >= STRIKES_BEFORE_BAN {
+            bucket.banned_until = Some(now + BAN_DURATION);
+            return Verdict::Banned {
+                retry_after: BAN_DURATION.as_secs(),
+            };
+        }
+        let retry_after = ((1.0 - bucket.tokens) / self.refill_per_sec).ceil() as u64;
+        Verdict::Limited {
+            retry_after: retry_after.max(1),
+        }
+    }
+}
+
+/// Middleware enforcing the access policy for the matched route.
+pub async fn enforce(
+    State(policy): State<Arc<AuthPolicy>>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+    let access = policy.access_for(&path);
+    if let Some(delegation) = policy.delegation(request.headers()) {
+        if !DELEGABLE.contains(&path.as_str()) {
+            return Err(AppError(
+                StatusCode::FORBIDDEN,
+                "Delegation tokens only authorize sealing.".into(),
+            ));
+        }
+        let delegation = delegation.map_err(|e| AppError(StatusCode::UNAUTHORIZED, format!("{}.", e)))?;
+        request.extensions_mut().insert(Principal(format!("delegation:{}", delegation.id)));
+        request.extensions_mut().insert(Delegated(delegation));
+        return Ok(next.run(request).await);
+    }
+    // Without API keys nobody authenticates and every route is public, so
+    // every caller falls through to the anonymous limits below.
+    if let Some(key) = policy.authenticate(request.headers()) {
+        let principal = match &key.tenant {
+            Some(tenant) => format!("tenant:{}", tenant),
+            None => format!("key:{}", hex::encode(&key.hash[..4])),
+        };
+        request.extensions_mut().insert(Principal(principal));
+        if let Some(tenant) = &key.tenant {
+            request.extensions_mut().insert(Tenant(tenant.clone()));
+        }
+        return Ok(next.run(request).await);
+    }
+    if access == Access::Authenticated {
+        return Err(AppError(
+            StatusCode::UNAUTHORIZED,
+            "A valid API key is required for this endpoint.".into(),
+        ));
+    }
+
+    // Anonymous access to a public route.
+    let declared_len = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if declared_len.is_some_and(|len| len > policy.anonymous_max_body) {
+        return Err(AppError(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "Request body exceeds the limit for anonymous access.".into(),
+        ));
+    }
+    // Content-Length is only a claim, and a chunked body has none, so the
+    // body itself is capped too: reading past the limit fails with 413.
+    let limit = usize::try_from(policy.anonymous_max_body).unwrap_or(usize::MAX);
+    #[cfg(feature = "verifier")]
+    request.extensions_mut().insert(InflateLimit(policy.anonymous_max_inflate));
+    let request = request.map(|body| Body::new(Limited::new(body, limit)));
+    // Requests without a resolvable address are limited together, not
+    // waved through.
+    let ip = policy.client_ip(&request);
+    let (status, retry_after) = match policy.anonymous.check(ip) {
+        Verdict::Allowed => return Ok(next.run(request).await),
+        Verdict::Limited { retry_after } => (StatusCode::TOO_MANY_REQUESTS, retry_after),
+        Verdict::Banned { retry_after } => {
+            let client_ip = ip.map_or_else(|| "unknown".to_string(), |ip| ip.to_string());
+            warn!(client_ip, "Anonymous client temporarily banned for abuse.");
+            (StatusCode::TOO_MANY_REQUESTS, retry_after)
+        }
+    };
+    Ok((
+        status,
+        [(header::RETRY_AFTER, retry_after.to_string())],
+        "Anonymous rate limit exceeded. Authenticate or retry later.",
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aegis_core::test_util::test_signing_key;
+    use axum::{middleware, routing::{get, post}, Router};
+    use tower::ServiceExt;
+
+    const PEER: &str = "203.0.113.7:4000";
+
+    fn policy(keys: &str, issuers: Vec<VerifyingKey>) -> AuthPolicy {
+        AuthPolicy {
+            keys: parse_keys(keys),
+            routes: parse_routes(&crate::router::PUBLIC_ROUTES, "").unwrap(),
+            trust_proxy: false,
+            anonymous: AnonymousLimiter::new(1.0, 100.0),
+            anonymous_max_body: 1024,
+            #[cfg(feature = "verifier")]
+            anonymous_max_inflate: 1024,
+            delegation_issuers: issuers,
+        }
+    }
+
+    // Answers with who the request was authorized as.
+    async fn whoami(request: Request) -> String {
+        request.extensions().get::<Principal>().map_or("anonymous".into(), |p| p.0.clone())
+    }
+
+    fn app(policy: AuthPolicy) -> Router {
+        Router::new()
+            .route("/healthz", get(whoami))
+            .route("/log/proof/{hash}", get(whoami))
+            .route("/audit", get(whoami))
+            .route("/seal", post(whoami))
+            .route_layer(middleware::from_fn_with_state(Arc::new(policy), enforce))
+    }
+
+    fn request(method: &str, uri: &str, headers: &[(&str, &str)]) -> Request {
+        let mut builder = Request::builder().method(method).uri(uri);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        let mut request = builder.body(Body::empty()).unwrap();
+        request.extensions_mut().insert(ConnectInfo(PEER.parse::<SocketAddr>().unwrap()));
+        request
+    }
+
+    async fn call(app: &Router, request: Request) -> (StatusCode, String) {
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    fn headers(pairs: &[(&str, &str)]) -> HeaderMap {
+        request("GET", "/", pairs).headers().clone()
+    }
+
+    #[test]
+    fn matches_api_keys() {
+        let policy = policy("alpha, acme:beta,,", vec![]);
+        assert_eq!(policy.keys.len(), 2);
+        let tenant = |pairs: &[(&str, &str)]| policy.authenticate(&headers(pairs)).map(|k| k.tenant.clone());
+        assert_eq!(tenant(&[("authorization", "Bearer alpha")]), Some(None));
+        assert_eq!(tenant(&[("x-api-key", " alpha ")]), Some(None));
+        assert_eq!(tenant(&[("x-api-key", "beta")]), Some(Some("acme".into())));
+        // The tenant prefix is not part of the key.
+        assert_eq!(tenant(&[("x-api-key", "acme:beta")]), None);
+        assert_eq!(tenant(&[("authorization", "Bearer alph")]), None);
+        assert_eq!(tenant(&[("authorization", "Basic alpha")]), None);
+        assert_eq!(tenant(&[]), None);
+    }
+
+    #[test]
+    fn parses_route_overrides() {
+        let routes = parse_routes(&[("/a", Access::Public)], " /a=authenticated, /b = public ,").unwrap();
+        assert_eq!(routes["/a"], Access::Authenticated);
+        assert_eq!(routes["/b"], Access::Public);
+        assert!(parse_routes(&[], "/a").is_err());
+        assert!(parse_routes(&[], "/a=open").is_err());
+    }
+
+    #[test]
+    fn trusts_forwarded_addresses_only_behind_a_proxy() {
+        let forwarded = request("GET", "/", &[("x-forwarded-for", "198.51.100.1, 192.0.2.9")]);
+        assert_eq!(client_ip(&forwarded, false), Some("203.0.113.7".parse().unwrap()));
+        // The proxy appends the address it saw; earlier entries are the
+        // client's to choose.
+        assert_eq!(client_ip(&forwarded, true), Some("192.0.2.9".parse().unwrap()));
+        let repeated = request(
+            "GET",
+            "/",
+            &[("x-forwarded-for", "192.0.2.9"), ("x-forwarded-for", "198.51.100.1")],
+        );
+        assert_eq!(client_ip(&repeated, true), Some("198.51.100.1".parse().unwrap()));
+        let garbled = request("GET", "/", &[("x-forwarded-for", "192.0.2.9, not-an-ip")]);
+        assert_eq!(client_ip(&garbled, true), Some("203.0.113.7".parse().unwrap()));
+        let plain = request("GET", "/", &[]);
+        assert_eq!(client_ip(&plain, true), Some("203.0.113.7".parse().unwrap()));
+        let mut unknown = plain;
+        unknown.extensions_mut().remove::<ConnectInfo<SocketAddr>>();
+        assert_eq!(client_ip(&unknown, true), None);
+    }
+
+    #[tokio::test]
+    async fn keeps_public_routes_open_and_protected_routes_closed() {
+        let app = app(policy("alpha, acme:beta", vec![]));
+        assert_eq!(call(&app, request("GET", "/healthz", &[])).await, (StatusCode::OK, "anonymous".into()));
+        assert_eq!(call(&app, request("GET", "/log/proof/00", &[])).await.0, StatusCode::OK);
+        assert_eq!(call(&app, request("GET", "/audit", &[])).await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(call(&app, request("POST", "/seal", &[])).await.0, StatusCode::UNAUTHORIZED);
+        let wrong = [("x-api-key", "gamma")];
+        assert_eq!(call(&app, request("GET", "/audit", &wrong)).await.0, StatusCode::UNAUTHORIZED);
+
+        let (status, principal) = call(&app, request("GET", "/audit", &[("authorization", "Bearer alpha")])).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(principal, format!("key:{}", &hex::encode(Sha256::digest("alpha"))[..8]));
+        let tenant = [("x-api-key", "beta")];
+        assert_eq!(call(&app, request("POST", "/seal", &tenant)).await, (StatusCode::OK, "tenant:acme".into()));
+
+        // Without keys every route is public.
+        let open = self::app(policy("", vec![]));
+        assert_eq!(call(&open, request("GET", "/audit", &[])).await.0, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn lets_delegation_tokens_seal_without_a_key() {
+        let issuer = test_signing_key(1);
+        let claims = Delegation {
+            id: "a1b2c3d4".into(),
+            subject: "field-app".into(),
+            issuer: String::new(),
+            not_before: 0,
+            not_after: i64::from(u32::MAX),
+            max_payload: None,
+            metadata: vec![],
+        };
+        let token = format!("Delegation {}", delegation::issue(claims.clone(), &issuer).unwrap());
+        let app = app(policy("alpha", vec![*issuer.verifying_key()]));
+        let delegated = [("authorization", token.as_str())];
+        let (status, principal) = call(&app, request("POST", "/seal", &delegated)).await;
+        assert_eq!((status, principal.as_str()), (StatusCode::OK, "delegation:a1b2c3d4"));
+        // Only sealing is delegable, even to a public route.
+        assert_eq!(call(&app, request("GET", "/audit", &delegated)).await.0, StatusCode::FORBIDDEN);
+        assert_eq!(call(&app, request("GET", "/healthz", &delegated)).await.0, StatusCode::FORBIDDEN);
+
+        let untrusted = format!("Delegation {}", delegation::issue(claims, &test_signing_key(2)).unwrap());
+        let untrusted = [("authorization", untrusted.as_str())];
+        assert_eq!(call(&app, request("POST", "/seal", &untrusted)).await.0, StatusCode::UNAUTHORIZED);
+        let garbled = [("authorization", "Delegation not-a-token")];
+        assert_eq!(call(&app, request("POST", "/seal", &garbled)).await.0, StatusCode::UNAUTHORIZED);
+        // Tokens are refused outright when no issuer is configured.
+        let closed = self::app(policy("", vec![]));
+        assert_eq!(call(&closed, request("POST", "/seal", &delegated)).await.0, StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn limits_and_bans_anonymous_clients() {
+        let limiter = AnonymousLimiter::new(1.0, 2.0);
+        let ip = Some("192.0.2.1".parse().unwrap());
+        let start = Instant::now();
+        assert!(matches!(limiter.check_at(ip, start), Verdict::Allowed));
+        assert!(matches!(limiter.check_at(ip, start), Verdict::Allowed));
+        assert!(matches!(limiter.check_at(ip, start), Verdict::Limited { retry_after: 1 }));
+        // Other clients, and those of unknown address, have their own buckets.
+        assert!(matches!(limiter.check_at(Some("192.0.2.2".parse().unwrap()), start), Verdict::Allowed));
+        assert!(matches!(limiter.check_at(None, start), Verdict::Allowed));
+        // The bucket refills over time.
+        assert!(matches!(limiter.check_at(ip, start + Duration::from_secs(1)), Verdict::Allowed));
+
+        let later = start + Duration::from_secs(1);
+        for _ in 1..STRIKES_BEFORE_BAN {
+            assert!(matches!(limiter.check_at(ip, later), Verdict::Limited { .. }));
+        }
+        assert!(matches!(limiter.check_at(ip, later), Verdict::Banned { retry_after: 600 }));
+        // A ban outlasts the refill, and ends.
+        assert!(matches!(limiter.check_at(ip, later + Duration::from_secs(10)), Verdict::Banned { .. }));
+        assert!(matches!(limiter.check_at(ip, later + BAN_DURATION), Verdict::Allowed));
+    }
+
+    #[test]
+    fn prunes_idle_clients_on_a_timer() {
+        let limiter = AnonymousLimiter::new(1.0, 2.0);
+        let ip = |n: u8| Some(IpAddr::from([192, 0, 2, n]));
+        let start = Instant::now();
+        limiter.check_at(ip(1), start);
+        for _ in 0..=STRIKES_BEFORE_BAN + 1 {
+            limiter.check_at(ip(2), start);
+        }
+        let len = || limiter.buckets.lock().unwrap().by_ip.len();
+        assert_eq!(len(), 2);
+
+        // Before the interval nothing is dropped; at it the refilled bucket
+        // goes and the banned one stays, along with the one just used.
+        limiter.check_at(ip(3), start + PRUNE_INTERVAL - Duration::from_secs(1));
+        assert_eq!(len(), 3);
+        limiter.check_at(ip(4), start + PRUNE_INTERVAL);
+        let buckets = limiter.buckets.lock().unwrap();
+        assert!(!buckets.by_ip.contains_key(&ip(1)));
+        assert!(buckets.by_ip.contains_key(&ip(2)) && buckets.by_ip.contains_key(&ip(4)));
+        assert_eq!(buckets.next_prune, start + PRUNE_INTERVAL + PRUNE_INTERVAL);
+        drop(buckets);
+        // A pruned client starts over with a full bucket.
+        assert!(matches!(limiter.check_at(ip(1), start + PRUNE_INTERVAL), Verdict::Allowed));
+        assert!(matches!(limiter.check_at(ip(2), start + PRUNE_INTERVAL), Verdict::Banned { .. }));
+    }
+
+    #[test]
+    fn prunes_early_when_the_table_grows() {
+        let limiter = AnonymousLimiter::new(1.0, 2.0);
+        let start = Instant::now();
+        {
+            let mut buckets = limiter.buckets.lock().unwrap();
+            for n in 0..=PRUNE_ABOVE as u32 {
+                let bucket = Bucket { tokens: 0.0, updated: start, strikes: 0, banned_until: None };
+                buckets.by_ip.insert(Some(IpAddr::from(n.to_be_bytes())), bucket);
+            }
+        }
+        // Everyone is still draining, so nothing can go; the threshold
+        // moves up rather than the table being scanned on every request.
+        let soon = start + Duration::from_millis(500);
+        limiter.check_at(None, soon);
+        let buckets = limiter.buckets.lock().unwrap();
+        assert_eq!(buckets.by_ip.len(), PRUNE_ABOVE + 2);
+        assert_eq!(buckets.prune_above, (PRUNE_ABOVE + 1) * 2);
+        drop(buckets);
+        // Once they refill, the next timed prune clears them.
+        limiter.check_at(None, soon + PRUNE_INTERVAL);
+        assert_eq!(limiter.buckets.lock().unwrap().by_ip.len(), 1);
+    }
+}
@@ -0,0 +1,222 @@
+// aegis-sealer-service/src/aws_kms.rs
+
+// AWS KMS signing backend. The key is an asymmetric `ECC_NIST_P256` KMS key
+// with usage `SIGN_VERIFY`; the service sends it SHA-256 digests and never
+// sees the private key. Requests are signed with SigV4, using the standard
+// `AWS_ACCESS_KEY_ID` / `AWS_SECRET_ACCESS_KEY` / `AWS_SESSION_TOKEN` when
+// set, and otherwise the EC2 instance role's credentials from IMDSv2.
+//
+// KMS is HTTPS-only, while our outbound client speaks plain HTTP, so
+// `AEGIS_AWS_KMS_ENDPOINT` is expected to point at a TLS-terminating egress
+// sidecar (or a VPC endpoint behind one).
+
+use crate::http_client;
+use crate::sigv4::{self, Credentials};
+use aegis_core::time::parse_rfc3339;
+use anyhow::{anyhow, bail, Context};
+use base64ct::{Base64, Encoding};
+use p256::ecdsa::{Signature, VerifyingKey};
+use p256::pkcs8::DecodePublicKey;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+use tracing::info;
+
+const IMDS_URL: &str = "http://169.254.169.254/latest";
+const MAX_RESPONSE: usize = 1024 * 1024;
+// Refresh instance credentials this long before they expire.
+const REFRESH_MARGIN: Duration = Duration::from_secs(300);
+
+enum CredentialSource {
+    Static(Arc<Credentials>),
+    InstanceRole(Mutex<Option<(Arc<Credentials>, SystemTime)>>),
+}
+
+struct KmsClient {
+    endpoint: String,
+    host: String,
+    region: String,
+    credentials: CredentialSource,
+}
+
+pub struct AwsKmsKey {
+    client: KmsClient,
+    key_id: String,
+    public_key: VerifyingKey,
+}
+
+impl AwsKmsKey {
+    /// Configured with `AEGIS_AWS_KMS_KEY_ID` (a key ID, ARN or alias),
+    /// `AEGIS_AWS_KMS_ENDPOINT` and `AEGIS_AWS_KMS_REGION` (default
+    /// `us-east-1`). The public key is fetched once and embedded in every
+    /// container.
+    pub async fn from_env() -> anyhow::Result<Self> {
+        let key_id = env::var("AEGIS_AWS_KMS_KEY_ID").context("AEGIS_AWS_KMS_KEY_ID must be set")?;
+        let endpoint = env::var("AEGIS_AWS_KMS_ENDPOINT").context("AEGIS_AWS_KMS_ENDPOINT must be set")?;
+        let endpoint = endpoint.trim_end_matches('/').to_string();
+        let client = KmsClient {
+            host: http_client::authority(&endpoint)?,
+            endpoint,
+            region: env::var("AEGIS_AWS_KMS_REGION").unwrap_or_else(|_| "us-east-1".into()),
+            credentials: match Credentials::from_env() {
+                Some(credentials) => CredentialSource::Static(Arc::new(credentials)),
+                None => CredentialSource::InstanceRole(Mutex::new(None)),
+            },
+        };
+        let public_key = client.fetch_public_key(&key_id).await?;
+        info!(key = %key_id, "Loaded AWS KMS signing key.");
+        Ok(AwsKmsKey {
+            client,
+            key_id,
+            public_key,
+        })
+    }
+
+    pub fn public_key(&self) -> &VerifyingKey {
+        &self.public_key
+    }
+
+    /// Confirms KMS is reachable and the key still has the public key we embed.
+    pub async fn check(&self) -> anyhow::Result<()> {
+        if self.client.fetch_public_key(&self.key_id).await? != self.public_key {
+            bail!("AWS KMS key '{}' has a different public key", self.key_id);
+        }
+        Ok(())
+    }
+
+    /// Signs `message` with ECDSA/SHA-256. KMS takes the digest and returns a
+    /// DER signature, which is converted to the r || s encoding we store.
+    pub async fn sign(&self, message: &[u8]) -> anyhow::Result<Signature> {
+        let digest = Sha256::digest(message);
+        let resp = self
+            .client
+            .call(
+                "Sign",
+                json!({
+                    "KeyId": self.key_id,
+                    "Message": Base64::encode_string(&digest),
+                    "MessageType": "DIGEST",
+                    "SigningAlgorithm": "ECDSA_SHA_256",
+                }),
+            )
+            .await?;
+        let der = decode(&resp, "Signature")?;
+        let signature = Signature::from_der(&der).map_err(|e| anyhow!("invalid AWS KMS signature: {}", e))?;
+        Ok(signature.normalize_s().unwrap_or(signature))
+    }
+}
+
+impl KmsClient {
+    async fn fetch_public_key(&self, key_id: &str) -> anyhow::Result<VerifyingKey> {
+        let resp = self.call("GetPublicKey", json!({ "KeyId": key_id })).await?;
+        if resp["KeySpec"].as_str() != Some("ECC_NIST_P256") || resp["KeyUsage"].as_str() != Some("SIGN_VERIFY") {
+            bail!("AWS KMS key '{}' must be an ECC_NIST_P256 key for SIGN_VERIFY", key_id);
+        }
+        VerifyingKey::from_public_key_der(&decode(&resp, "PublicKey")?)
+            .map_err(|e| anyhow!("invalid AWS KMS public key: {}", e))
+    }
+
+    async fn call(&self, action: &str, body: Value) -> anyhow::Result<Value> {
+        let body = body.to_string();
+        let target = format!("TrentService.{}", action);
+        let own_headers = [("content-type", "application/x-amz-json-1.1"), ("x-amz-target", target.as_str())];
+        let credentials = self.credentials().await?;
+        let request = sigv4::Request {
+            service: "kms",
+            region: &self.region,
+            method: "POST",
+            path: "/",
+            query: "",
+            host: &self.host,
+            headers: &own_headers,
+            payload: body.as_bytes(),
+        };
+        let signed = sigv4::sign(&credentials, &request, SystemTime::now());
+        let mut headers: Vec<(&str, &str)> = own_headers.to_vec();
+        headers.extend(signed.iter().map(|(n, v)| (n.as_str(), v.as_str())));
+        let url = format!("{}/", self.endpoint);
+        let resp = http_client::request("POST", &url, &headers, body.as_bytes(), MAX_RESPONSE).await?;
+        if !resp.is_success() {
+            bail!(
+                "AWS KMS {} returned HTTP {}: {}",
+                action,
+                resp.status,
+                String::from_utf8_lossy(&resp.body)
+            );
+        }
+        Ok(serde_json::from_slice(&resp.body)?)
+    }
+
+    async fn credentials(&self) -> anyhow::Result<Arc<Credentials>> {
+        let cached = match &self.credentials {
+            CredentialSource::Static(credentials) => return Ok(credentials.clone()),
+            CredentialSource::InstanceRole(cached) => cached,
+        };
+        let mut cached = cached.lock().await;
+        if let Some((credentials, _)) = cached
+            .as_ref()
+            .filter(|(_, expires_at)| *expires_at > SystemTime::now() + REFRESH_MARGIN)
+        {
+            return Ok(credentials.clone());
+        }
+        let (credentials, expires_at) = instance_credentials().await?;
+        let credentials = Arc::new(credentials);
+        *cached = Some((credentials.clone(), expires_at));
+        Ok(credentials)
+    }
+}
+
+/// The EC2 instance role's temporary credentials and their expiry, via IMDSv2.
+async fn instance_credentials() -> anyhow::Result<(Credentials, SystemTime)> {
+    let resp = http_client::request(
+        "PUT",
+        &format!("{}/api/token", IMDS_URL),
+        &[("X-aws-ec2-metadata-token-ttl-seconds", "60")],
+        &[],
+        MAX_RESPONSE,
+    )
+    .await
+    .context("no AWS credentials in the environment and no instance metadata service")?;
+    if !resp.is_success() {
+        bail!("IMDS token request returned HTTP {}", resp.status);
+    }
+    let token = String::from_utf8(resp.body)?;
+    let roles = imds_get(&token, "").await?;
+    let role = roles
+        .lines()
+        .next()
+        .filter(|r| !r.is_empty())
+        .ok_or_else(|| anyhow!("the instance has no IAM role"))?;
+    let body: Value = serde_json::from_str(&imds_get(&token, role).await?)?;
+    let field = |name: &str| -> anyhow::Result<String> {
+        Ok(body[name].as_str().ok_or_else(|| anyhow!("instance credentials have no {}", name))?.to_string())
+    };
+    let expires = parse_rfc3339(&field("Expiration")?)
+        .map(|secs| UNIX_EPOCH + Duration::from_secs(secs.max(0) as u64))
+        .ok_or_else(|| anyhow!("instance credentials have an invalid Expiration"))?;
+    Ok((
+        Credentials {
+            access_key: field("AccessKeyId")?,
+            secret_key: field("SecretAccessKey")?,
+            session_token: Some(field("Token")?),
+        },
+        expires,
+    ))
+}
+
+async fn imds_get(token: &str, role: &str) -> anyhow::Result<String> {
+    let url = format!("{}/meta-data/iam/security-credentials/{}", IMDS_URL, role);
+    let resp = http_client::request("GET", &url, &[("X-aws-ec2-metadata-token", token)], &[], MAX_RESPONSE).await?;
+    if !resp.is_success() {
+        bail!("IMDS credentials request returned HTTP {}", resp.status);
+    }
+    Ok(String::from_utf8(resp.body)?)
+}
+
+fn decode(resp: &Value, field: &str) -> anyhow::Result<Vec<u8>> {
+    let encoded = resp[field].as_str().ok_or_else(|| anyhow!("AWS KMS response has no {}", field))?;
+    Base64::decode_vec(encoded).map_err(|e| anyhow!("invalid AWS KMS {} encoding: {}", field, e))
+}
@@ -0,0 +1,245 @@
+// aegis-sealer-service/src/azure.rs
+
+// Azure Key Vault signing backend. Tokens come from either the instance
+// metadata service (managed identity) or the client-credentials flow, and
+// the key version is pinned at startup so every signature matches the
+// public key we embed.
+//
+// Key Vault and Entra ID are HTTPS-only, while our outbound client speaks
+// plain HTTP, so `AZURE_KEYVAULT_URL` and `AZURE_AUTHORITY_URL` are expected
+// to point at a TLS-terminating egress sidecar in production.
+
+use crate::http_client;
+use anyhow::{anyhow, bail, Context};
+use base64ct::{Base64UrlUnpadded, Encoding};
+use p256::ecdsa::{Signature, VerifyingKey};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::info;
+
+const API_VERSION: &str = "7.4";
+const RESOURCE: &str = "https://vault.azure.net";
+const IMDS_TOKEN_URL: &str = "http://169.254.169.254/metadata/identity/oauth2/token";
+const MAX_RESPONSE: usize = 1024 * 1024;
+// Refresh tokens this long before they expire.
+const REFRESH_MARGIN: Duration = Duration::from_secs(300);
+
+enum Credential {
+    ManagedIdentity {
+        client_id: Option<String>,
+    },
+    ClientSecret {
+        authority: String,
+        tenant_id: String,
+        client_id: String,
+        client_secret: String,
+    },
+}
+
+struct CachedToken {
+    value: String,
+    expires_at: Instant,
+}
+
+struct KeyVaultClient {
+    vault_url: String,
+    credential: Credential,
+    token: Mutex<Option<CachedToken>>,
+}
+
+pub struct AzureKeyVaultKey {
+    client: KeyVaultClient,
+    name: String,
+    version: String,
+    public_key: VerifyingKey,
+}
+
+impl AzureKeyVaultKey {
+    /// Configured with `AZURE_KEYVAULT_URL`, `AZURE_KEYVAULT_KEY` and optionally
+    /// `AZURE_KEYVAULT_KEY_VERSION` (defaults to the current version, which is
+    /// then pinned). Client-credential auth is used when `AZURE_TENANT_ID`,
+    /// `AZURE_CLIENT_ID` and `AZURE_CLIENT_SECRET` are all set; otherwise the
+    /// managed identity (optionally `AZURE_CLIENT_ID` for a user-assigned one).
+    pub async fn from_env() -> anyhow::Result<Self> {
+        let vault_url = env::var("AZURE_KEYVAULT_URL").context("AZURE_KEYVAULT_URL must be set")?;
+        let name = env::var("AZURE_KEYVAULT_KEY").context("AZURE_KEYVAULT_KEY must be set")?;
+        let credential = match (
+            env::var("AZURE_TENANT_ID"),
+            env::var("AZURE_CLIENT_ID"),
+            env::var("AZURE_CLIENT_SECRET"),
+        ) {
+            (Ok(tenant_id), Ok(client_id), Ok(client_secret)) => Credential::ClientSecret {
+                authority: env::var("AZURE_AUTHORITY_URL")
+                    .unwrap_or_else(|_| "https://login.microsoftonline.com".into()),
+                tenant_id,
+                client_id,
+                client_secret,
+            },
+            (_, client_id, _) => Credential::ManagedIdentity {
+                client_id: client_id.ok(),
+            },
+        };
+
+        let client = KeyVaultClient {
+            vault_url: vault_url.trim_end_matches('/').to_string(),
+            credential,
+            token: Mutex::new(None),
+        };
+        let requested_version = env::var("AZURE_KEYVAULT_KEY_VERSION").unwrap_or_default();
+        let (version, public_key) = client.fetch_key(&name, &requested_version).await?;
+        info!(key = %name, version = %version, "Loaded Azure Key Vault signing key.");
+        Ok(AzureKeyVaultKey {
+            client,
+            name,
+            version,
+            public_key,
+        })
+    }
+
+    pub fn public_key(&self) -> &VerifyingKey {
+        &self.public_key
+    }
+
+    /// Confirms Key Vault is reachable and the pinned key version is unchanged.
+    pub async fn check(&self) -> anyhow::Result<()> {
+        let (_, public_key) = self.client.fetch_key(&self.name, &self.version).await?;
+        if public_key != self.public_key {
+            bail!("Key Vault key '{}' version {} has a different public key", self.name, self.version);
+        }
+        Ok(())
+    }
+
+    /// Signs `message` with ECDSA/SHA-256. Key Vault's ES256 operation takes the
+    /// digest and returns the raw r || s encoding the container stores.
+    pub async fn sign(&self, message: &[u8]) -> anyhow::Result<Signature> {
+        let digest = Sha256::digest(message);
+        let resp = self
+            .client
+            .call(
+                "POST",
+                &format!("{}/{}/sign", self.name, self.version),
+                Some(json!({ "alg": "ES256", "value": Base64UrlUnpadded::encode_string(&digest) })),
+            )
+            .await?;
+        let encoded = resp["value"]
+            .as_str()
+            .ok_or_else(|| anyhow!("Key Vault sign returned no value"))?;
+        let raw = Base64UrlUnpadded::decode_vec(encoded)
+            .map_err(|e| anyhow!("invalid Key Vault signature encoding: {}", e))?;
+        Signature::from_slice(&raw).map_err(|e| anyhow!("invalid Key Vault signature: {}", e))
+    }
+}
+
+impl KeyVaultClient {
+    async fn access_token(&self) -> anyhow::Result<String> {
+        let mut cached = self.token.lock().await;
+        if let Some(token) = cached.as_ref().filter(|t| t.expires_at > Instant::now() + REFRESH_MARGIN) {
+            return Ok(token.value.clone());
+        }
+        let resp = match &self.credential {
+            Credential::ManagedIdentity { client_id } => {
+                let mut url = format!("{}?api-version=2018-02-01&resource={}", IMDS_TOKEN_URL, RESOURCE);
+                if let Some(id) = client_id {
+                    url.push_str(&format!("&client_id={}", id));
+                }
+                http_client::request("GET", &url, &[("Metadata", "true")], &[], MAX_RESPONSE).await?
+            }
+            Credential::ClientSecret {
+                authority,
+                tenant_id,
+                client_id,
+                client_secret,
+            } => {
+                let form = form_urlencoded::Serializer::new(String::new())
+                    .append_pair("grant_type", "client_credentials")
+                    .append_pair("client_id", client_id)
+                    .append_pair("client_secret", client_secret)
+                    .append_pair("scope", &format!("{}/.default", RESOURCE))
+                    .finish();
+                let url = format!("{}/{}/oauth2/v2.0/token", authority.trim_end_matches('/'), tenant_id);
+                http_client::post(
+                    &url,
+                    &[("Content-Type", "application/x-www-form-urlencoded")],
+                    form.as_bytes(),
+                    MAX_RESPONSE,
+                )
+                .await?
+            }
+        };
+        if !resp.is_success() {
+            bail!("Azure token request returned HTTP {}", resp.status);
+        }
+        let body: Value = serde_json::from_slice(&resp.body)?;
+        let value = body["access_token"]
+            .as_str()
+            .ok_or_else(|| anyhow!("Azure token response has no access_token"))?
+            .to_string();
+        // IMDS returns expires_in as a string, Entra ID as a number.
+        let expires_in = body["expires_in"]
+            .as_u64()
+            .or_else(|| body["expires_in"].as_str().and_then(|s| s.parse().ok()))
+            .unwrap_or(3600);
+        *cached = Some(CachedToken {
+            value: value.clone(),
+            expires_at: Instant::now() + Duration::from_secs(expires_in),
+        });
+        Ok(value)
+    }
+
+    async fn call(&self, method: &str, path: &str, body: Option<Value>) -> anyhow::Result<Value> {
+        let token = self.access_token().await?;
+        let auth = format!("Bearer {}", token);
+        let url = format!("{}/keys/{}?api-version={}", self.vault_url, path, API_VERSION);
+        let body = body.map(|b| b.to_string()).unwrap_or_default();
+        let resp = http_client::request(
+            method,
+            &url,
+            &[("Authorization", &auth), ("Content-Type", "application/json")],
+            body.as_bytes(),
+            MAX_RESPONSE,
+        )
+        .await?;
+        if !resp.is_success() {
+            bail!(
+                "Key Vault {} {} returned HTTP {}: {}",
+                method,
+                path,
+                resp.status,
+                String::from_utf8_lossy(&resp.body)
+            );
+        }
+        Ok(serde_json::from_slice(&resp.body)?)
+    }
+
+    async fn fetch_key(&self, name: &str, version: &str) -> anyhow::Result<(String, VerifyingKey)> {
+        let path = if version.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", name, version)
+        };
+        let resp = self.call("GET", &path, None).await?;
+        let jwk = &resp["key"];
+        if jwk["crv"].as_str() != Some("P-256") {
+            bail!("Key Vault key '{}' must be an EC P-256 key", name);
+        }
+        let coordinate = |name: &str| -> anyhow::Result<Vec<u8>> {
+            let encoded = jwk[name].as_str().ok_or_else(|| anyhow!("JWK has no '{}'", name))?;
+            Base64UrlUnpadded::decode_vec(encoded).map_err(|e| anyhow!("invalid JWK '{}': {}", name, e))
+        };
+        let mut point = vec![0x04];
+        point.extend(coordinate("x")?);
+        point.extend(coordinate("y")?);
+        let public_key = VerifyingKey::from_sec1_bytes(&point)
+            .map_err(|e| anyhow!("invalid Key Vault public key: {}", e))?;
+        // The kid ends in the concrete version: .../keys/<name>/<version>.
+        let version = jwk["kid"]
+            .as_str()
+            .and_then(|kid| kid.rsplit('/').next())
+            .ok_or_else(|| anyhow!("Key Vault key has no kid"))?
+            .to_string();
+        Ok((version, public_key))
+    }
+}
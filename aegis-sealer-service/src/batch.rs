@@ -0,0 +1,291 @@
+// aegis-sealer-service/src/batch.rs
+
+// Batch sealing: `POST /seal/batch` seals many images in one request and
+// returns a tar archive with one `.aegis` container per image, streamed from
+// the spool files. The multipart form takes:
+//
+// - `image` or `payload` parts, any number; the part's file name names the
+//   container, and a `payload` part's Content-Type declares its media type
+//   (see `intake`);
+// - `archive` parts, tar archives whose regular files are each an image;
+// - `metadata`, metadata for every image without metadata of its own;
+// - `metadata:<file name>`, metadata for the image with that file name.
+//
+// Archives are tar rather than zip, matching the offline bundles. Every image
+// must have metadata and pass the content checks before the response starts;
+// then the images are sealed in the order received, each container streamed
+// as soon as it is sealed. A seal that fails (a hook rejects it, say) leaves
+// its container out rather than failing the request, and the archive ends
+// with a `manifest.json` entry listing, for every image, the container it
+// went into or why it was not sealed. As the archive is streamed while it
+// is made, the response has no Content-Length.
+
+use crate::{audit_log::Caller, check_metadata, provenance::Submission, seal_spooled, spool::Spool, AppError, AppState, SpooledSeal};
+use aegis_core::{format, tar};
+use axum::{
+    body::{Body, Bytes},
+    extract::{Multipart, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures_util::stream;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+/// Most images one batch may hold.
+const MAX_BATCH_IMAGES: usize = 1000;
+/// Upper bound on the contents of an `archive` part, matching the body limit.
+const MAX_ARCHIVE_BYTES: u64 = 100 * 1024 * 1024;
+
+struct Upload {
+    file_name: String,
+    spool: Spool,
+    image_hash: String,
+    /// Declared by the Content-Type of a `payload` part.
+    media_type: Option<String>,
+}
+
+/// What became of one image, as `manifest.json` lists it.
+#[derive(Serialize)]
+struct ItemResult {
+    file: String,
+    image_hash: String,
+    /// The archive entry holding its container, if it was sealed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    container: Option<String>,
+    /// Why it was not sealed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Manifest {
+    sealed: usize,
+    failed: usize,
+    images: Vec<ItemResult>,
+}
+
+pub async fn batch_seal_handler(
+    State(state): State<AppState>,
+    caller: Caller,
+    submission: Submission,
+    mut multipart: Multipart,
+) -> Result<Response, AppError> {
+    let mut uploads: Vec<Upload> = Vec::new();
+    let mut shared_metadata: Option<String> = None;
+    let mut file_metadata: HashMap<String, String> = HashMap::new();
+
+    while let Some(mut field) = multipart.next_field().await? {
+        let name = field.name().unwrap_or("").to_string();
+        if name == "image" || name == "payload" {
+            let media_type =
+                field.content_type().filter(|_| name == "payload").and_then(aegis_core::media_type::normalize);
+            let file_name = base_name(field.file_name().unwrap_or(""));
+            let mut spool = Spool::create(&state.config.spool_dir).await?;
+            let mut hasher = Sha256::new();
+            while let Some(chunk) = field.chunk().await? {
+                hasher.update(&chunk);
+                spool.write_all(&chunk).await?;
+            }
+            push(&mut uploads, Upload { file_name, spool, image_hash: hex::encode(hasher.finalize()), media_type })?;
+        } else if name == "archive" {
+            let data = field.bytes().await?;
+            let files = tar::read_all(&mut &data[..], MAX_ARCHIVE_BYTES)
+                .map_err(|e| AppError(StatusCode::BAD_REQUEST, format!("Invalid tar archive: {}", e)))?;
+            for (path, contents) in files {
+                let mut spool = Spool::create(&state.config.spool_dir).await?;
+                spool.write_all(&contents).await?;
+                let image_hash = hex::encode(Sha256::digest(&contents));
+                push(&mut uploads, Upload { file_name: base_name(&path), spool, image_hash, media_type: None })?;
+            }
+        } else if name == "metadata" {
+            shared_metadata = Some(field.text().await?);
+        } else if let Some(file_name) = name.strip_prefix("metadata:") {
+            file_metadata.insert(file_name.to_string(), field.text().await?);
+        }
+    }
+
+    if uploads.is_empty() {
+        return Err(AppError(
+            StatusCode::BAD_REQUEST,
+            "Request contains no 'image', 'payload' or 'archive' parts.".into(),
+        ));
+    }
+    // Every image must have metadata and pass the content checks before
+    // any of them is signed.
+    let mut metadata = Vec::with_capacity(uploads.len());
+    for upload in &mut uploads {
+        let checked = file_metadata
+            .get(&upload.file_name)
+            .or(shared_metadata.as_ref())
+            .ok_or_else(|| {
+                AppError(
+                    StatusCode::BAD_REQUEST,
+                    format!("No metadata for '{}' and no shared 'metadata' field.", upload.file_name),
+                )
+            })
+            .and_then(|metadata| check_metadata(&state.config, metadata.clone()))
+            .map(|metadata| submission.attach(metadata))?;
+        let checked = state.config.intake.check_spool(&mut upload.spool, checked, upload.media_type.as_deref()).await.map_err(|e| {
+            AppError(e.0, format!("'{}': {}", upload.file_name, e.1))
+        })?;
+        metadata.push(checked);
+    }
+
+    let mtime = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let batch = Batch {
+        state,
+        caller,
+        items: uploads.into_iter().zip(metadata).enumerate().collect(),
+        names: HashSet::new(),
+        results: Vec::new(),
+        mtime,
+        pending: VecDeque::new(),
+        streaming: None,
+        finished: false,
+    };
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "application/x-tar"),
+            (header::CONTENT_DISPOSITION, "attachment; filename=\"sealed.tar\""),
+        ],
+        Body::from_stream(stream::unfold(batch, Batch::next_chunk)),
+    )
+        .into_response())
+}
+
+fn push(uploads: &mut Vec<Upload>, upload: Upload) -> Result<(), AppError> {
+    if uploads.len() == MAX_BATCH_IMAGES {
+        return Err(AppError(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("A batch may hold at most {} images.", MAX_BATCH_IMAGES),
+        ));
+    }
+    uploads.push(upload);
+    Ok(())
+}
+
+/// The last path component of an uploaded file name.
+fn base_name(path: &str) -> String {
+    path.rsplit(['/', '\\']).next().unwrap_or("").to_string()
+}
+
+/// A unique archive entry name for the container of the `index`th image.
+/// Names that are empty, clash, or do not fit a tar header fall back to the
+/// image's position.
+fn container_name(file_name: &str, index: usize, taken: &mut HashSet<String>) -> String {
+    let name = format!("{}.aegis", file_name);
+    let name = if file_name.is_empty() || name.len() > 100 || taken.contains(&name) {
+        format!("image-{}.aegis", index + 1)
+    } else {
+        name
+    };
+    taken.insert(name.clone());
+    name
+}
+
+/// A batch being sealed and streamed as a tar archive: for each image, the
+/// tar header and the container header, then the spooled image and the tar
+/// padding; at the end the manifest and the tar trailer.
+struct Batch {
+    state: AppState,
+    caller: Caller,
+    items: VecDeque<(usize, (Upload, String))>,
+    names: HashSet<String>,
+    results: Vec<ItemResult>,
+    mtime: u64,
+    /// Bytes to send before anything else.
+    pending: VecDeque<Bytes>,
+    /// The image being sent and the padding that follows it.
+    streaming: Option<(Spool, usize)>,
+    finished: bool,
+}
+
+impl Batch {
+    async fn next_chunk(mut self) -> Option<(Result<Bytes, std::io::Error>, Self)> {
+        loop {
+            if let Some(bytes) = self.pending.pop_front() {
+                return Some((Ok(bytes), self));
+            }
+            if let Some((spool, padding)) = &mut self.streaming {
+                match spool.read_chunk().await {
+                    Ok(Some(chunk)) => return Some((Ok(Bytes::from(chunk)), self)),
+                    Ok(None) => {
+                        self.pending.push_back(Bytes::from(vec![0u8; *padding]));
+                        self.streaming = None;
+                        continue;
+                    }
+                    Err(e) => {
+                        // The archive cannot be completed; end it here.
+                        self.streaming = None;
+                        self.items.clear();
+                        self.finished = true;
+                        return Some((Err(e), self));
+                    }
+                }
+            }
+            match self.items.pop_front() {
+                Some((index, (upload, metadata))) => self.seal(index, upload, metadata).await,
+                None if !self.finished => self.finish(),
+                None => return None,
+            }
+        }
+    }
+
+    /// Seals one image and queues its entry, or records why it failed.
+    async fn seal(&mut self, index: usize, mut upload: Upload, metadata: String) {
+        let sealed = seal_spooled(
+            &self.state,
+            &self.caller,
+            &metadata,
+            format::FormatHeader::default(),
+            &mut upload.spool,
+            &upload.image_hash,
+            true,
+        )
+        .await;
+        let name = container_name(&upload.file_name, index, &mut self.names);
+        let entry = sealed.and_then(|SpooledSeal { public_key, signature, header, .. }| {
+            let container_header =
+                format::header_bytes(&header, &public_key, &metadata, &signature.to_bytes(), upload.spool.len());
+            let size = container_header.len() as u64 + upload.spool.len();
+            let mut head = tar::header(&name, size, self.mtime)?.to_vec();
+            head.extend_from_slice(&container_header);
+            Ok((head, tar::padding(size)))
+        });
+        let (container, error) = match entry {
+            Ok((head, padding)) => {
+                self.pending.push_back(Bytes::from(head));
+                self.streaming = Some((upload.spool, padding));
+                (Some(name), None)
+            }
+            Err(AppError(status, message)) => {
+                warn!(file = %upload.file_name, %status, "Batch image not sealed: {}", message);
+                (None, Some(message))
+            }
+        };
+        self.results.push(ItemResult { file: upload.file_name, image_hash: upload.image_hash, container, error });
+    }
+
+    /// Queues the manifest entry and the tar trailer.
+    fn finish(&mut self) {
+        self.finished = true;
+        let failed = self.results.iter().filter(|result| result.error.is_some()).count();
+        info!(images = self.results.len(), failed, "Batch sealed.");
+        let images = std::mem::take(&mut self.results);
+        let manifest = Manifest { sealed: images.len() - failed, failed, images };
+        let json = serde_json::to_vec_pretty(&manifest).expect("the manifest serializes");
+        let head = tar::header("manifest.json", json.len() as u64, self.mtime).expect("the name fits a tar header");
+        let padding = tar::padding(json.len() as u64);
+        self.pending.extend([
+            Bytes::from(head.to_vec()),
+            Bytes::from(json),
+            Bytes::from(vec![0u8; padding]),
+            Bytes::from(tar::trailer().to_vec()),
+        ]);
+    }
+}
@@ -0,0 +1,297 @@
+// aegis-sealer-service/src/bench.rs
+
+// A load generator for capacity planning, driven by `aegis bench-remote`.
+// Workers seal synthetic images against a running service, and unless told
+// otherwise verify each container they get back, until the time or request
+// budget runs out; the report gives latency percentiles, throughput and
+// error counts for each operation.
+//
+// The images are PNGs of the requested size: a real signature and IHDR, so
+// the service's intake checks accept them, followed by an ancillary chunk
+// of pseudo-random filler. Each request changes the first bytes of the
+// filler, so every seal is of a distinct image. Every request opens its own
+// connection, as `http_client` does for all outbound calls.
+
+use crate::http_client;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// PNG signature, then an IHDR chunk for a 1024x1024 8-bit RGB image.
+const PNG_HEAD: &[u8] = b"\x89PNG\r\n\x1a\n\
+    \x00\x00\x00\x0dIHDR\x00\x00\x04\x00\x00\x00\x04\x00\x08\x02\x00\x00\x00\xf0\x7f\xbc\xd4";
+const PNG_TAIL: &[u8] = b"\x00\x00\x00\x00IEND\xae\x42\x60\x82";
+/// Bytes of the filler chunk rewritten for each request.
+const VARIED: usize = 16;
+const BOUNDARY: &str = "aegis-bench-7f3c9a1e5b2d4f60";
+
+pub struct BenchOptions {
+    /// Base URL of the service, e.g. `http://localhost:10000`.
+    pub url: String,
+    /// Size of each synthetic image, in bytes.
+    pub image_size: usize,
+    pub concurrency: usize,
+    /// How long to run.
+    pub duration: Duration,
+    /// Stop after this many seals, if sooner.
+    pub max_requests: Option<u64>,
+    /// Whether to verify each sealed container.
+    pub verify: bool,
+    /// Sent as `Authorization: Bearer KEY`.
+    pub api_key: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Latency {
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct OperationReport {
+    pub requests: u64,
+    pub succeeded: u64,
+    pub failed: u64,
+    /// Fraction of requests that failed.
+    pub error_rate: f64,
+    /// Failures by HTTP status, or `connection` for those with no response.
+    pub errors: BTreeMap<String, u64>,
+    pub requests_per_sec: f64,
+    /// Bodies of successful requests sent per second, in MB (10^6 bytes).
+    pub upload_mb_per_sec: f64,
+    /// Of successful requests.
+    pub latency: Option<Latency>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BenchReport {
+    pub url: String,
+    pub image_size: usize,
+    pub concurrency: usize,
+    pub elapsed_secs: f64,
+    pub seal: OperationReport,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verify: Option<OperationReport>,
+}
+
+impl BenchReport {
+    /// Whether any seal succeeded.
+    pub fn any_succeeded(&self) -> bool {
+        self.seal.succeeded > 0
+    }
+}
+
+/// Parses a size such as `512KB`, `10MB` or `1GiB`; a bare number is bytes.
+/// KB, MB and GB are powers of 1000, KiB, MiB and GiB powers of 1024.
+pub fn parse_size(text: &str) -> anyhow::Result<usize> {
+    let text = text.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: usize = number.parse().map_err(|_| anyhow::anyhow!("'{}' is not a size", text))?;
+    let multiplier = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" | "k" => 1000,
+        "mb" | "m" => 1000 * 1000,
+        "gb" | "g" => 1000 * 1000 * 1000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        _ => anyhow::bail!("'{}' has an unknown unit; use B, KB, MB, GB, KiB, MiB or GiB", text),
+    };
+    number.checked_mul(multiplier).ok_or_else(|| anyhow::anyhow!("'{}' is too large", text))
+}
+
+#[derive(Default)]
+struct Samples {
+    latencies: Vec<Duration>,
+    failures: BTreeMap<String, u64>,
+    bytes_sent: u64,
+}
+
+impl Samples {
+    fn record(&mut self, started: Instant, sent: usize, outcome: Result<(), String>) {
+        match outcome {
+            Ok(()) => {
+                self.latencies.push(started.elapsed());
+                self.bytes_sent += sent as u64;
+            }
+            Err(kind) => *self.failures.entry(kind).or_default() += 1,
+        }
+    }
+
+    fn merge(&mut self, other: Samples) {
+        self.latencies.extend(other.latencies);
+        for (kind, count) in other.failures {
+            *self.failures.entry(kind).or_default() += count;
+        }
+        self.bytes_sent += other.bytes_sent;
+    }
+
+    fn report(mut self, elapsed: Duration) -> OperationReport {
+        let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+        let succeeded = self.latencies.len() as u64;
+        let failed: u64 = self.failures.values().sum();
+        let requests = succeeded + failed;
+        self.latencies.sort();
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let percentile = |p: f64| {
+            let rank = ((p / 100.0) * self.latencies.len() as f64).ceil() as usize;
+            ms(self.latencies[rank.clamp(1, self.latencies.len()) - 1])
+        };
+        let latency = (!self.latencies.is_empty()).then(|| Latency {
+            mean_ms: self.latencies.iter().copied().map(ms).sum::<f64>() / self.latencies.len() as f64,
+            p50_ms: percentile(50.0),
+            p90_ms: percentile(90.0),
+            p99_ms: percentile(99.0),
+            max_ms: ms(*self.latencies.last().expect("not empty")),
+        });
+        OperationReport {
+            requests,
+            succeeded,
+            failed,
+            error_rate: if requests == 0 { 0.0 } else { failed as f64 / requests as f64 },
+            errors: self.failures,
+            requests_per_sec: requests as f64 / secs,
+            upload_mb_per_sec: self.bytes_sent as f64 / 1e6 / secs,
+            latency,
+        }
+    }
+}
+
+/// A multipart /seal body whose image filler can be varied in place.
+struct SealBody {
+    bytes: Vec<u8>,
+    /// Offset of the varied filler bytes.
+    varied_at: usize,
+}
+
+impl SealBody {
+    fn new(image_size: usize, seed: u64) -> Self {
+        let filler_len = image_size.saturating_sub(PNG_HEAD.len() + PNG_TAIL.len() + 12).max(VARIED);
+        let mut bytes = format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"metadata\"\r\nContent-Type: application/json\r\n\r\n{m}\r\n\
+             --{b}\r\nContent-Disposition: form-data; name=\"image\"; filename=\"bench.png\"\r\nContent-Type: image/png\r\n\r\n",
+            b = BOUNDARY,
+            m = json!({"title": "aegis bench-remote", "description": "Synthetic load test image."}),
+        )
+        .into_bytes();
+        bytes.extend_from_slice(PNG_HEAD);
+        bytes.extend_from_slice(&(filler_len as u32).to_be_bytes());
+        bytes.extend_from_slice(b"bnCh");
+        let varied_at = bytes.len();
+        // xorshift64*, which is plenty for bytes that only need to differ.
+        let mut state = seed | 1;
+        bytes.extend((0..filler_len).map(|_| {
+            state ^= state >> 12;
+            state ^= state << 25;
+            state ^= state >> 27;
+            (state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+        }));
+        // The chunk CRC is left zero; the service only reads the header.
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(PNG_TAIL);
+        bytes.extend_from_slice(format!("\r\n--{}--\r\n", BOUNDARY).as_bytes());
+        SealBody { bytes, varied_at }
+    }
+
+    /// Makes the image distinct for request `n` of `worker`.
+    fn vary(&mut self, worker: usize, n: u64) {
+        let mut varied = [0u8; VARIED];
+        varied[..8].copy_from_slice(&(worker as u64).to_be_bytes());
+        varied[8..].copy_from_slice(&n.to_be_bytes());
+        self.bytes[self.varied_at..self.varied_at + VARIED].copy_from_slice(&varied);
+    }
+}
+
+fn failure(result: &anyhow::Result<http_client::HttpResponse>) -> Option<String> {
+    match result {
+        Ok(resp) if resp.is_success() => None,
+        Ok(resp) => Some(resp.status.to_string()),
+        Err(_) => Some("connection".into()),
+    }
+}
+
+async fn worker(
+    options: Arc<BenchOptions>,
+    id: usize,
+    deadline: Instant,
+    issued: Arc<AtomicU64>,
+) -> (Samples, Samples) {
+    let (mut seals, mut verifies) = (Samples::default(), Samples::default());
+    let seed = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0) ^ id as u64;
+    let mut body = SealBody::new(options.image_size, seed);
+    let seal_url = format!("{}/seal", options.url);
+    let verify_url = format!("{}/verify", options.url);
+    let content_type = format!("multipart/form-data; boundary={}", BOUNDARY);
+    let authorization = options.api_key.as_ref().map(|key| format!("Bearer {}", key));
+    let mut headers = vec![("Content-Type", content_type.as_str())];
+    if let Some(authorization) = &authorization {
+        headers.push(("Authorization", authorization));
+    }
+    let max_body = options.image_size + 1024 * 1024;
+    let mut n = 0;
+    while Instant::now() < deadline {
+        if options.max_requests.is_some_and(|max| issued.fetch_add(1, Ordering::Relaxed) >= max) {
+            break;
+        }
+        n += 1;
+        body.vary(id, n);
+        let started = Instant::now();
+        let result = http_client::post(&seal_url, &headers, &body.bytes, max_body).await;
+        seals.record(started, body.bytes.len(), failure(&result).map_or(Ok(()), Err));
+        let (Some(container), true) = (result.ok().filter(|resp| resp.is_success()), options.verify) else {
+            continue;
+        };
+        let mut verify_headers = vec![("Content-Type", "application/octet-stream")];
+        if let Some(authorization) = &authorization {
+            verify_headers.push(("Authorization", authorization));
+        }
+        let started = Instant::now();
+        let result = http_client::post(&verify_url, &verify_headers, &container.body, 1024 * 1024).await;
+        let outcome = match failure(&result) {
+            Some(kind) => Err(kind),
+            None => match serde_json::from_slice::<serde_json::Value>(&result.expect("succeeded").body) {
+                Ok(verdict) if verdict["signature_valid"] == json!(true) => Ok(()),
+                _ => Err("invalid".into()),
+            },
+        };
+        verifies.record(started, container.body.len(), outcome);
+    }
+    (seals, verifies)
+}
+
+/// Runs the load test and reports on it.
+pub async fn run(options: BenchOptions) -> anyhow::Result<BenchReport> {
+    if options.concurrency == 0 {
+        anyhow::bail!("concurrency must be at least 1");
+    }
+    http_client::authority(&options.url)?;
+    let options = Arc::new(BenchOptions { url: options.url.trim_end_matches('/').to_string(), ..options });
+    let issued = Arc::new(AtomicU64::new(0));
+    let started = Instant::now();
+    let deadline = started + options.duration;
+    let workers: Vec<_> = (0..options.concurrency)
+        .map(|id| tokio::spawn(worker(options.clone(), id, deadline, issued.clone())))
+        .collect();
+    let (mut seals, mut verifies) = (Samples::default(), Samples::default());
+    for handle in workers {
+        let (worker_seals, worker_verifies) = handle.await?;
+        seals.merge(worker_seals);
+        verifies.merge(worker_verifies);
+    }
+    let elapsed = started.elapsed();
+    Ok(BenchReport {
+        url: options.url.clone(),
+        image_size: options.image_size,
+        concurrency: options.concurrency,
+        elapsed_secs: elapsed.as_secs_f64(),
+        seal: seals.report(elapsed),
+        verify: options.verify.then(|| verifies.report(elapsed)),
+    })
+}
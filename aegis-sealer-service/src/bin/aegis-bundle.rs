@@ -0,0 +1,77 @@
+// aegis-sealer-service/src/bin/aegis-bundle.rs
+
+// Verifies an offline bundle produced by POST /export/bundle without any
+// network access. Usage:
+//   cargo run -p aegis-sealer-service --features verifier --bin aegis-bundle -- \
+//       verify --trust KEY [--trust KEY]... aegis-bundle.tar
+//
+// A bundle carries its own trust list, so it is only trusted if its
+// manifest is signed by a key given with `--trust` (a hex fingerprint, or a
+// file holding the public key as PEM) or endorsed by one. A bundle signed
+// by any other key is reported as signed, signer not trusted.
+//
+// Timestamps in the report follow `AEGIS_DISPLAY_TZ` and `AEGIS_DISPLAY_LOCALE`
+// (canonical RFC 3339 UTC by default).
+
+use aegis_core::{bundle, keys::Fingerprint, time::TimeDisplay};
+use anyhow::{anyhow, Context};
+use p256::{ecdsa::VerifyingKey, pkcs8::DecodePublicKey};
+use std::path::Path;
+
+const USAGE: &str = "usage: aegis-bundle verify --trust <fingerprint|pem>... <bundle.tar>";
+
+/// A `--trust` argument: a file holding a PEM public key, or a fingerprint.
+/// Returned as fingerprint text.
+fn load_trusted(arg: &str) -> anyhow::Result<String> {
+    if !Path::new(arg).exists() {
+        return Ok(arg.to_string());
+    }
+    let text = std::fs::read_to_string(arg).with_context(|| format!("reading key {}", arg))?;
+    let key = VerifyingKey::from_public_key_pem(text.trim()).map_err(|e| anyhow!("{}: {}", arg, e))?;
+    Ok(Fingerprint::of(&key.to_sec1_bytes()).to_hex())
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    if args.next().as_deref() != Some("verify") {
+        anyhow::bail!(USAGE);
+    }
+    let (mut trusted, mut path) = (Vec::new(), None);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--trust" => {
+                let value = args.next().ok_or_else(|| anyhow!("--trust needs a value"))?;
+                trusted.push(load_trusted(&value)?);
+            }
+            _ if arg.starts_with('-') => anyhow::bail!("unknown option '{}'\n{}", arg, USAGE),
+            _ if path.is_none() => path = Some(arg),
+            _ => anyhow::bail!(USAGE),
+        }
+    }
+    let Some(path) = path else {
+        anyhow::bail!(USAGE);
+    };
+    if trusted.is_empty() {
+        anyhow::bail!("--trust is required: a bundle cannot vouch for itself\n{}", USAGE);
+    }
+    match bundle::verify(&std::fs::read(path)?, &trusted) {
+        Ok(report) => {
+            if report.manifest_signer_trusted {
+                println!("VALID");
+            } else {
+                println!("SIGNED, SIGNER NOT TRUSTED: manifest signed by {}", report.manifest_signer);
+            }
+            let trusted = report.manifest_signer_trusted;
+            let report = TimeDisplay::from_env().localize_json(&serde_json::to_value(&report)?);
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            if !trusted {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Err(e) => {
+            println!("INVALID: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,47 @@
+// aegis-sealer-service/src/bin/aegis-lint.rs
+
+// Flags weak, deprecated or incomplete parameters in .aegis files. Usage:
+//   cargo run -p aegis-sealer-service --features verifier --bin aegis-lint -- [--policy policy.json] file.aegis...
+//
+// Exits with status 1 if any finding has `error` severity.
+
+use aegis_core::{
+    format::AegisAncient,
+    lint::{lint, LintPolicy, Severity},
+};
+use std::fs::File;
+use std::io::BufReader;
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1).peekable();
+    let policy = if args.peek().map(String::as_str) == Some("--policy") {
+        args.next();
+        let path = args.next().ok_or_else(|| anyhow::anyhow!("--policy needs a file"))?;
+        LintPolicy::from_json(&std::fs::read_to_string(&path)?)
+            .map_err(|e| anyhow::anyhow!("{}: {}", path, e))?
+    } else {
+        LintPolicy::default()
+    };
+    let paths: Vec<String> = args.collect();
+    if paths.is_empty() {
+        anyhow::bail!("usage: aegis-lint [--policy policy.json] <file.aegis>...");
+    }
+
+    let mut failed = false;
+    for path in &paths {
+        let ancient = AegisAncient::read(&mut BufReader::new(File::open(path)?))
+            .map_err(|e| anyhow::anyhow!("{}: {}", path, e))?;
+        let findings = lint(&ancient, &policy);
+        if findings.is_empty() {
+            println!("{}: ok", path);
+        }
+        for f in findings {
+            failed |= f.severity == Severity::Error;
+            println!("{}: {:?}: {} ({})", path, f.severity, f.message, f.rule);
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
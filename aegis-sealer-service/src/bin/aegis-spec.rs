@@ -0,0 +1,15 @@
+// aegis-sealer-service/src/bin/aegis-spec.rs
+
+// Prints the container format specification. Usage:
+//   cargo run -p aegis-sealer-service --bin aegis-spec [-- --html] > SPEC.md
+
+use aegis_core::spec;
+
+fn main() {
+    let html = std::env::args().skip(1).any(|arg| arg == "--html");
+    if html {
+        print!("{}", spec::to_html());
+    } else {
+        print!("{}", spec::to_markdown());
+    }
+}
@@ -0,0 +1,167 @@
+// aegis-sealer-service/src/bin/aegis-tui.rs
+
+// Interactive terminal inspector for .aegis files. Usage:
+//   cargo run -p aegis-sealer-service --features verifier --bin aegis-tui -- file.aegis
+//
+// Commands are read line by line from stdin; type `help` for the list.
+// Timestamps in metadata are shown per `AEGIS_DISPLAY_TZ` and
+// `AEGIS_DISPLAY_LOCALE`, adjustable with the `tz` and `locale` commands.
+
+use aegis_core::{
+    format::AegisAncient,
+    keys::Fingerprint,
+    lint::{lint, LintPolicy},
+    time::{parse_offset, Locale, TimeDisplay},
+};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+
+const HEX_ROWS: usize = 16;
+const HEX_WIDTH: usize = 16;
+
+fn main() -> anyhow::Result<()> {
+    let path = std::env::args()
+        .nth(1)
+        .ok_or_else(|| anyhow::anyhow!("usage: aegis-tui <file.aegis>"))?;
+    let ancient = AegisAncient::read(&mut BufReader::new(File::open(&path)?))
+        .map_err(|e| anyhow::anyhow!("{}: {}", path, e))?;
+
+    println!("Aegis inspector — {}", path);
+    print_tree(&ancient);
+    let mut display = TimeDisplay::from_env();
+    let stdin = io::stdin();
+    loop {
+        print!("aegis> ");
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            break;
+        }
+        let args: Vec<&str> = line.split_whitespace().collect();
+        match args.as_slice() {
+            [] => {}
+            ["tree"] | ["t"] => print_tree(&ancient),
+            ["meta"] | ["m"] => print_metadata(&ancient, &display),
+            ["meta", "raw"] | ["m", "raw"] => println!("{}", ancient.metadata),
+            ["tz", offset] => match parse_offset(offset) {
+                Some(minutes) => display.offset_minutes = minutes,
+                None => println!("offset must look like UTC, +02:00 or -0530"),
+            },
+            ["locale", tag] => match Locale::from_tag(tag) {
+                Some(locale) => display.locale = locale,
+                None => println!("supported locales: rfc3339, en-US, en-GB, de, fr, ja"),
+            },
+            ["key"] | ["k"] => print_key(&ancient),
+            ["hex", block] | ["x", block] => hex_view(&ancient, block, 0),
+            ["hex", block, offset] | ["x", block, offset] => match offset.parse() {
+                Ok(offset) => hex_view(&ancient, block, offset),
+                Err(_) => println!("offset must be a number"),
+            },
+            ["extract", block, out] | ["e", block, out] => match block_bytes(&ancient, block) {
+                Some(bytes) => match std::fs::write(out, bytes) {
+                    Ok(()) => println!("wrote {} bytes to {}", bytes.len(), out),
+                    Err(e) => println!("write failed: {}", e),
+                },
+                None => println!("unknown block '{}'", block),
+            },
+            ["lint"] | ["l"] => {
+                let findings = lint(&ancient, &LintPolicy::default());
+                if findings.is_empty() {
+                    println!("no findings");
+                }
+                for f in findings {
+                    println!("{:<8} {:<20} {}", format!("{:?}", f.severity).to_lowercase(), f.rule, f.message);
+                }
+            }
+            ["help"] | ["h"] | ["?"] => print_help(),
+            ["quit"] | ["q"] | ["exit"] => break,
+            _ => println!("unknown command; type `help`"),
+        }
+    }
+    Ok(())
+}
+
+fn print_help() {
+    println!("  tree (t)                   show the block tree");
+    println!("  meta (m) [raw]             print the metadata block");
+    println!("  tz <offset>                show timestamps at a UTC offset");
+    println!("  locale <tag>               timestamp layout (rfc3339, en-US, de, ...)");
+    println!("  key (k)                    show the signer key fingerprint");
+    println!("  lint (l)                   check for weak or deprecated parameters");
+    println!("  hex (x) <block> [offset]   hex preview of a block");
+    println!("  extract (e) <block> <file> write a block's bytes to a file");
+    println!("  quit (q)                   exit");
+}
+
+fn block_bytes<'a>(ancient: &'a AegisAncient, name: &str) -> Option<&'a [u8]> {
+    match name {
+        "public_key" | "key" => Some(&ancient.public_key),
+        "metadata" | "meta" => Some(ancient.metadata.as_bytes()),
+        "signature" | "sig" => Some(&ancient.signature),
+        "image_data" | "image" | "payload" => Some(&ancient.image_data),
+        _ => None,
+    }
+}
+
+/// Pretty-prints JSON metadata with timestamps rendered for display; the
+/// signed bytes are untouched (`meta raw` shows them verbatim).
+fn print_metadata(ancient: &AegisAncient, display: &TimeDisplay) {
+    match serde_json::from_str::<serde_json::Value>(&ancient.metadata) {
+        Ok(value) => match serde_json::to_string_pretty(&display.localize_json(&value)) {
+            Ok(pretty) => println!("{}", pretty),
+            Err(_) => println!("{}", ancient.metadata),
+        },
+        Err(_) => println!("{}", ancient.metadata),
+    }
+}
+
+fn print_tree(ancient: &AegisAncient) {
+    println!("container ({} bytes)", ancient.encoded_len());
+    let blocks = [
+        ("public_key", ancient.public_key.len()),
+        ("metadata", ancient.metadata.len()),
+        ("signature", ancient.signature.len()),
+        ("image_data", ancient.image_data.len()),
+    ];
+    for (i, (name, len)) in blocks.iter().enumerate() {
+        let branch = if i + 1 == blocks.len() { "└──" } else { "├──" };
+        println!("{} {:<12} {:>12} bytes", branch, name, len);
+    }
+}
+
+fn print_key(ancient: &AegisAncient) {
+    let fingerprint = Fingerprint::of(&ancient.public_key);
+    println!("{}", fingerprint);
+    println!("{}", fingerprint.to_hex_groups());
+    println!("{}", fingerprint.to_words());
+    println!("{}", fingerprint.to_randomart());
+}
+
+fn hex_view(ancient: &AegisAncient, name: &str, offset: usize) {
+    let Some(bytes) = block_bytes(ancient, name) else {
+        println!("unknown block '{}'", name);
+        return;
+    };
+    if offset >= bytes.len() && !bytes.is_empty() {
+        println!("offset beyond end of block ({} bytes)", bytes.len());
+        return;
+    }
+    let end = (offset + HEX_ROWS * HEX_WIDTH).min(bytes.len());
+    for (row, chunk) in bytes[offset..end].chunks(HEX_WIDTH).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+        let ascii: String = chunk
+            .iter()
+            .map(|b| if b.is_ascii_graphic() { *b as char } else { '.' })
+            .collect();
+        println!(
+            "{:08x}  {:<width$}  {}",
+            offset + row * HEX_WIDTH,
+            hex.join(" "),
+            ascii,
+            width = HEX_WIDTH * 3 - 1
+        );
+    }
+    if end < bytes.len() {
+        println!("... {} more bytes (hex {} {})", bytes.len() - end, name, end);
+    }
+}
@@ -0,0 +1,1683 @@
+// aegis-sealer-service/src/bin/aegis.rs
+
+// Offline command-line sealing, verification and inspection of local files,
+// for when the HTTP service is not available. Usage:
+//   cargo run -p aegis-sealer-service --features verifier --bin aegis -- <command> ...
+//
+//   aegis seal --key key.pem [--metadata JSON | --metadata-file FILE] [--external-metadata | --detached | --manifest | --intoto]
+//              [--extension NAME=FILE]... [--extension-json NAME=JSON]... [--cert-chain PEM]
+//              [--trust-hint DOMAIN[:SELECTOR]] [--endorsements FILE]... [--chunk-size SIZE]
+//              [--text none|lf|crlf | --media-type TYPE] [--compress BLOCKS] [-o OUT] [--json] FILE
+//   aegis verify [--trust KEY]... [--trust-anchors PEM] [--original FILE] [--lang TAG]
+//                [--level quick|standard|forensic] [--dns-resolver URL [--require-dnssec]]
+//                [--endorsement-root KEY]... [--endorsements FILE]... [--log-proof FILE [--log-key KEY]]
+//                [--trusted-tsa FINGERPRINT]... [--tsa-anchors PEM] [--json] FILE
+//   aegis verify --range OFFSET:LENGTH [--trust KEY]... [-o OUT] [--json] FILE
+//   aegis inspect [--metadata | --explain] [--lang TAG] [--json] FILE
+//   aegis countersign --key KEY --role ROLE [-o OUT] [--json] FILE
+//   aegis dns-record --key KEY --trust-hint DOMAIN[:SELECTOR]
+//   aegis endorse --key KEY --subject KEY --role ROLE [--not-before TIME] --not-after TIME [-o OUT] [--json]
+//   aegis delegate --key KEY --subject NAME [--not-before TIME] --not-after TIME [--id ID] [--max-size BYTES]
+//                  [--require FIELD=VALUE]... [--require-prefix FIELD=PREFIX]... [--json]
+//   aegis bench-remote --url URL [--size SIZE] [--concurrency N] [--duration SECS] [--requests N]
+//                      [--seal-only] [--api-key KEY] [--json]
+//   aegis config schema
+//   aegis config check [--env-file FILE] [--config FILE] [--json]
+//
+// Keys are PEM (PKCS#8 or SEC1 private keys, SPKI public keys) or hex (a
+// private scalar or a SEC1 public key). `--trust` also accepts a key
+// fingerprint. `verify` checks a `.aegis.sig` sidecar when given `--original`,
+// and a JPEG or PNG copy sealed into XMP or a C2PA manifest by the service's
+// `output=xmp` or `output=c2pa`.
+// It exits with status 1 if the signature is invalid or the key is not
+// trusted. `--external-metadata` keeps the metadata document in the header
+// and signs only its hash; `inspect` then shows just the reference unless
+// given `--metadata`, which checks the document against it and parses it.
+// `inspect --explain` reads the whole container and shows which of its bytes
+// the signature covers and every digest computed from them, in hex (see
+// `aegis_core::explain`).
+// `--lang` picks one language of multi-language metadata (see
+// `Metadata::language_view()`) and lists the digest of every variant the
+// signature covers along with it.
+// `verify --level` runs the checks of a verification level (see
+// `aegis_core::levels`) and lists each with its outcome; from `standard` up
+// the keys given with `--trust` are one of them, and any failed check makes
+// the container invalid.
+// `--extension` and `--extension-json` add signed extensions (see
+// `format::FIELD_EXTENSIONS`), which `verify` and `inspect` list.
+// `--cert-chain` embeds an X.509 chain for the key, leaf first (see
+// `aegis_core::x509`); `verify --trust-anchors` checks it against the CA
+// certificates in a PEM bundle, reports the leaf's subject, and fails if
+// the chain does not lead to one of them.
+// A timestamp is reported verified only when its TSA is trusted: pinned by
+// key fingerprint with `--trusted-tsa` or chaining to a CA in the PEM
+// bundle `--tsa-anchors`. Any other timestamp is reported unverified.
+// `--trust-hint` names where the key is published in DNS (see
+// `aegis_core::dns_trust`), and `dns-record` prints the TXT record to
+// publish there for a private or public key. `verify --dns-resolver`
+// resolves the container's hint with a DNS over HTTPS resolver (see
+// `aegis_sealer_service::dns`) and fails unless a record there publishes the
+// sealing key, or with `--require-dnssec`, unless the answer was also
+// authenticated.
+// `endorse` has one key (an organization's root, say) endorse another (a
+// team's sealing key) in a role until `--not-after`, an RFC 3339 time, and
+// writes the endorsement as JSON (see `aegis_core::endorsement`), adding it
+// to the array in `-o` if that file exists. `seal --endorsements` embeds
+// those of the given endorsements that chain from the sealing key.
+// `verify --endorsement-root` fails unless the sealing key is one of the
+// roots or a chain of endorsements, from the container or the files given
+// with `--endorsements`, leads from it to one, valid at the time of a valid
+// trusted timestamp or else now.
+// `verify --log-proof` takes the response of the service's
+// GET /log/proof/{hash} and fails unless its tree head is signed by the
+// sealing key (or `--log-key`) and its proof puts the sealed image in the
+// log at that size (see `aegis_core::merkle`); `inspect` shows the proof a
+// container carries from when it was sealed.
+// `delegate` signs a delegation token (see `aegis_core::delegation`) with
+// an admin key the service accepts in `AEGIS_DELEGATION_KEYS`, letting its
+// holder seal until `--not-after` payloads up to `--max-size` bytes whose
+// metadata has each `--require` field equal to the value (JSON, or else a
+// string) and each `--require-prefix` field start with the prefix. The
+// token is printed for `Authorization: Delegation TOKEN`.
+// `bench-remote` puts a running service under load for capacity planning
+// (see `aegis_sealer_service::bench`): `--concurrency` workers (default 8)
+// seal synthetic `--size` images (default 1MB; KB, MB, GB or KiB, MiB, GiB)
+// for `--duration` seconds (default 30) or `--requests` seals, verifying
+// each sealed container unless given `--seal-only`, and it reports latency
+// percentiles, throughput and error rates per operation. It exits with
+// status 1 if no seal succeeded.
+// `seal --chunk-size` signs the image in chunks of that size (see
+// `aegis_core::chunked`; default unit bytes, or KiB, MiB as for
+// `bench-remote`), and `verify --range` then checks just the image bytes
+// from OFFSET for LENGTH against the signature, reading only the header and
+// the chunks they fall in, and writes them to `-o` if given.
+// `seal --text` seals a UTF-8 text document with its line endings rewritten
+// to LF or CRLF, or as it is with `none`, recording which in the metadata
+// (see `aegis_core::text`); `verify --original` then reports a copy that
+// differs only in its line endings as such.
+// `seal --manifest` leaves the file as it is and writes its seal to
+// `FILE.aegis-manifest`, a JSON payload manifest with the same signature a
+// sidecar would carry (see `aegis_core::manifest`). `verify` given a
+// manifest pairs it with the file of the same name without the suffix, or
+// the file name recorded in it, unless given `--original`.
+// `seal --intoto` also leaves the file as it is, and writes an in-toto
+// statement about it in a DSSE envelope signed by the key to
+// `FILE.intoto.jsonl`, for in-toto and SLSA tooling (see
+// `aegis_core::intoto`).
+// `seal --media-type` declares the file's media type (a PDF, a video: any
+// file can be sealed) in the metadata's `content` block, after checking it
+// against the file's leading bytes where those are recognized (see
+// `aegis_core::media_type`); `verify` and `inspect` report it, or the type
+// recognized from the payload when none was declared.
+// `seal --compress` stores the `metadata` or `image` block, or `all` of
+// them, as zstd frames (see `aegis_core::zstd`); the signature is over the
+// uncompressed bytes, and every command reads such containers as it does
+// any other. An image to compress is read whole, so it is sealed in memory
+// rather than streamed.
+// `countersign` adds a co-signature to a container after checking its
+// signature, rewriting it in place unless given `-o`; `verify` reports each
+// co-signer and fails if any co-signature is invalid.
+// `config schema` prints a JSON Schema of the service's settings, and
+// `config check` checks the environment, `.env` (or `--env-file`) and the
+// config file `AEGIS_CONFIG` names (or `--config`) the way the service does
+// at startup, exiting with status 1 on an invalid value.
+
+use aegis_sealer_service::{
+    bench, config_file,
+    settings::{self, EnvFile, Severity},
+};
+use aegis_core::{
+    chunked, crypto,
+    delegation::{self, Constraint, Delegation},
+    dns_trust::{self, TrustHint},
+    endorsement::{self, Endorsement},
+    format::{self, AegisAncient, DetachedSignature},
+    intoto,
+    keys::Fingerprint,
+    levels::{self, Outcome, VerificationLevel},
+    manifest::{self, PayloadManifest},
+    media_type,
+    merkle::{self, InclusionProof, SignedTreeHead},
+    metadata::Metadata,
+    prelude::Sealer,
+    text::{self, Normalization, TextRecord},
+    time::{self, TimeDisplay},
+    timestamp,
+    x509, xmp,
+};
+use anyhow::{anyhow, bail, Context};
+use p256::ecdsa::{SigningKey, VerifyingKey};
+use p256::pkcs8::{DecodePrivateKey, DecodePublicKey};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "usage:
+  aegis seal --key KEY [--metadata JSON | --metadata-file FILE] [--external-metadata | --detached | --manifest | --intoto]
+             [--extension NAME=FILE]... [--extension-json NAME=JSON]... [--cert-chain PEM]
+             [--trust-hint DOMAIN[:SELECTOR]] [--endorsements FILE]... [--chunk-size SIZE]
+             [--text none|lf|crlf | --media-type TYPE] [--compress BLOCKS] [-o OUT] [--json] FILE
+  aegis verify [--trust KEY]... [--trust-anchors PEM] [--original FILE] [--lang TAG]
+               [--level quick|standard|forensic] [--dns-resolver URL [--require-dnssec]]
+               [--endorsement-root KEY]... [--endorsements FILE]... [--log-proof FILE [--log-key KEY]]
+               [--trusted-tsa FINGERPRINT]... [--tsa-anchors PEM] [--json] FILE
+  aegis verify --range OFFSET:LENGTH [--trust KEY]... [-o OUT] [--json] FILE
+  aegis inspect [--metadata | --explain] [--lang TAG] [--json] FILE
+  aegis countersign --key KEY --role ROLE [-o OUT] [--json] FILE
+  aegis dns-record --key KEY --trust-hint DOMAIN[:SELECTOR]
+  aegis endorse --key KEY --subject KEY --role ROLE [--not-before TIME] --not-after TIME [-o OUT] [--json]
+  aegis delegate --key KEY --subject NAME [--not-before TIME] --not-after TIME [--id ID] [--max-size BYTES]
+                 [--require FIELD=VALUE]... [--require-prefix FIELD=PREFIX]... [--json]
+  aegis bench-remote --url URL [--size SIZE] [--concurrency N] [--duration SECS] [--requests N]
+                     [--seal-only] [--api-key KEY] [--json]
+  aegis config schema
+  aegis config check [--env-file FILE] [--config FILE] [--json]";
+
+/// A payload to seal: a file, or a text document normalized in memory.
+trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// Header bytes read first by `inspect`; doubled until the header fits.
+const INITIAL_PREFIX: usize = 64 * 1024;
+
+struct Args {
+    flags: Vec<(String, Option<String>)>,
+    positional: Vec<String>,
+}
+
+impl Args {
+    /// Splits arguments into flags and positionals. Flags named in
+    /// `with_value` take the next argument as their value.
+    fn parse(args: impl Iterator<Item = String>, with_value: &[&str]) -> anyhow::Result<Self> {
+        let mut args = args.peekable();
+        let mut parsed = Args { flags: Vec::new(), positional: Vec::new() };
+        while let Some(arg) = args.next() {
+            if arg.starts_with('-') && arg.len() > 1 {
+                let value = if with_value.contains(&arg.as_str()) {
+                    Some(args.next().ok_or_else(|| anyhow!("{} needs a value", arg))?)
+                } else {
+                    None
+                };
+                parsed.flags.push((arg, value));
+            } else {
+                parsed.positional.push(arg);
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn has(&self, flag: &str) -> bool {
+        self.flags.iter().any(|(f, _)| f == flag)
+    }
+
+    fn value(&self, flag: &str) -> Option<&str> {
+        self.flags.iter().find(|(f, _)| f == flag).and_then(|(_, v)| v.as_deref())
+    }
+
+    fn values<'a>(&'a self, flag: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.flags.iter().filter(move |(f, _)| f == flag).filter_map(|(_, v)| v.as_deref())
+    }
+
+    fn check(&self, known: &[&str]) -> anyhow::Result<()> {
+        match self.flags.iter().find(|(f, _)| !known.contains(&f.as_str())) {
+            Some((flag, _)) => bail!("unknown option {}\n{}", flag, USAGE),
+            None => Ok(()),
+        }
+    }
+
+    fn file(&self) -> anyhow::Result<&str> {
+        match self.positional.as_slice() {
+            [file] => Ok(file),
+            _ => bail!(USAGE),
+        }
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let command = args.next().ok_or_else(|| anyhow!(USAGE))?;
+    let ok = match command.as_str() {
+        "seal" => seal(Args::parse(
+            args,
+            &[
+                "--key",
+                "--metadata",
+                "--metadata-file",
+                "--extension",
+                "--extension-json",
+                "--cert-chain",
+                "--trust-hint",
+                "--endorsements",
+                "--chunk-size",
+                "--text",
+                "--media-type",
+                "--compress",
+                "-o",
+            ],
+        )?)?,
+        "verify" => verify(Args::parse(
+            args,
+            &[
+                "--trust",
+                "--trust-anchors",
+                "--original",
+                "--lang",
+                "--level",
+                "--dns-resolver",
+                "--endorsement-root",
+                "--endorsements",
+                "--log-proof",
+                "--log-key",
+                "--trusted-tsa",
+                "--tsa-anchors",
+                "--range",
+                "-o",
+            ],
+        )?)?,
+        "inspect" => inspect(Args::parse(args, &["--lang"])?)?,
+        "countersign" => countersign(Args::parse(args, &["--key", "--role", "-o"])?)?,
+        "dns-record" => dns_record(Args::parse(args, &["--key", "--trust-hint"])?)?,
+        "endorse" => endorse(Args::parse(args, &["--key", "--subject", "--role", "--not-before", "--not-after", "-o"])?)?,
+        "delegate" => delegate(Args::parse(
+            args,
+            &[
+                "--key",
+                "--subject",
+                "--not-before",
+                "--not-after",
+                "--id",
+                "--max-size",
+                "--require",
+                "--require-prefix",
+            ],
+        )?)?,
+        "bench-remote" => bench_remote(Args::parse(
+            args,
+            &["--url", "--size", "--concurrency", "--duration", "--requests", "--api-key"],
+        )?)?,
+        "config" => config(Args::parse(args, &["--env-file", "--config"])?)?,
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            true
+        }
+        other => bail!("unknown command '{}'\n{}", other, USAGE),
+    };
+    if !ok {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn print(json_output: bool, value: &Value, human: impl FnOnce()) -> anyhow::Result<()> {
+    if json_output {
+        println!("{}", serde_json::to_string_pretty(value)?);
+    } else {
+        human();
+    }
+    Ok(())
+}
+
+fn load_signing_key(path: &str) -> anyhow::Result<SigningKey> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("reading key {}", path))?;
+    let text = text.trim();
+    if text.starts_with("-----BEGIN") {
+        if let Ok(key) = SigningKey::from_pkcs8_pem(text) {
+            return Ok(key);
+        }
+        let secret = p256::SecretKey::from_sec1_pem(text)
+            .map_err(|_| anyhow!("{}: not a P-256 private key in PKCS#8 or SEC1 PEM", path))?;
+        return Ok(secret.into());
+    }
+    let bytes = hex::decode(text).with_context(|| format!("{}: not PEM or hex", path))?;
+    SigningKey::from_slice(&bytes).map_err(|e| anyhow!("{}: invalid private key: {}", path, e))
+}
+
+/// A `--trust` argument: a file holding a public key, or a fingerprint in
+/// any form `Fingerprint::matches` accepts. Returned as fingerprint text.
+fn load_trusted(arg: &str) -> anyhow::Result<String> {
+    if !Path::new(arg).exists() {
+        return Ok(arg.to_string());
+    }
+    Ok(Fingerprint::of(&load_public_key(arg)?.to_sec1_bytes()).to_hex())
+}
+
+fn load_public_key(path: &str) -> anyhow::Result<VerifyingKey> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("reading key {}", path))?;
+    let text = text.trim();
+    if text.starts_with("-----BEGIN") {
+        VerifyingKey::from_public_key_pem(text).map_err(|e| anyhow!("{}: {}", path, e))
+    } else {
+        let sec1 = hex::decode(text).with_context(|| format!("{}: not PEM or hex", path))?;
+        VerifyingKey::from_sec1_bytes(&sec1).map_err(|e| anyhow!("{}: {}", path, e))
+    }
+}
+
+fn load_trust_hint(args: &Args) -> anyhow::Result<Option<TrustHint>> {
+    args.value("--trust-hint")
+        .map(|spec| TrustHint::parse(spec).map_err(|e| anyhow!("--trust-hint: {}", e)))
+        .transpose()
+}
+
+/// The proof and tree head in a response of the service's
+/// GET /log/proof/{hash}.
+fn load_log_proof(path: &str) -> anyhow::Result<(InclusionProof, SignedTreeHead)> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("reading {}", path))?;
+    let mut value: Value = serde_json::from_str(&text).map_err(|e| anyhow!("{}: {}", path, e))?;
+    let proof = serde_json::from_value(value["proof"].take()).map_err(|e| anyhow!("{}: proof: {}", path, e))?;
+    let head = serde_json::from_value(value["tree_head"].take()).map_err(|e| anyhow!("{}: tree_head: {}", path, e))?;
+    Ok((proof, head))
+}
+
+/// The payload a manifest at `path` pairs with: the file named as it is
+/// without its suffix, or else the file name recorded in it, in the same
+/// directory.
+fn manifest_payload(path: &Path, manifest: &PayloadManifest) -> anyhow::Result<PathBuf> {
+    let by_suffix = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(manifest::payload_name)
+        .map(|name| path.with_file_name(name));
+    let by_record = manifest.payload.file_name.as_ref().map(|name| path.with_file_name(name));
+    by_suffix
+        .into_iter()
+        .chain(by_record)
+        .find(|candidate| candidate.is_file())
+        .ok_or_else(|| anyhow!("{}: no payload found next to the manifest; give it with --original", path.display()))
+}
+
+/// The endorsements in the files given with `--endorsements`.
+fn load_endorsements(args: &Args) -> anyhow::Result<Vec<Endorsement>> {
+    let mut endorsements = Vec::new();
+    for path in args.values("--endorsements") {
+        let text = std::fs::read_to_string(path).with_context(|| format!("reading {}", path))?;
+        endorsements.extend(endorsement::from_json(&text).map_err(|e| anyhow!("{}: {}", path, e))?);
+    }
+    Ok(endorsements)
+}
+
+/// A public key given as a key file, or a private key file for its public
+/// key.
+fn load_any_public_key(path: &str) -> anyhow::Result<VerifyingKey> {
+    match load_public_key(path) {
+        Ok(key) => Ok(key),
+        Err(_) => Ok(*load_signing_key(path)?.verifying_key()),
+    }
+}
+
+fn seal(args: Args) -> anyhow::Result<bool> {
+    args.check(&[
+        "--key",
+        "--metadata",
+        "--metadata-file",
+        "--external-metadata",
+        "--detached",
+        "--manifest",
+        "--intoto",
+        "--extension",
+        "--extension-json",
+        "--cert-chain",
+        "--trust-hint",
+        "--endorsements",
+        "--chunk-size",
+        "--text",
+        "--media-type",
+        "--compress",
+        "-o",
+        "--json",
+    ])?;
+    let input = args.file()?;
+    let key = load_signing_key(args.value("--key").ok_or_else(|| anyhow!("seal needs --key"))?)?;
+    let metadata = match (args.value("--metadata"), args.value("--metadata-file")) {
+        (Some(_), Some(_)) => bail!("give --metadata or --metadata-file, not both"),
+        (Some(metadata), None) => metadata.to_string(),
+        (None, Some(path)) => std::fs::read_to_string(path)?,
+        (None, None) => "{}".to_string(),
+    };
+    // A text document is read whole, normalized, and described in the
+    // metadata's `content` block, as the service does.
+    let text_mode = args
+        .value("--text")
+        .map(|mode| Normalization::parse(mode).ok_or_else(|| anyhow!("--text takes none, lf or crlf")))
+        .transpose()?;
+    let (metadata, document) = match text_mode {
+        Some(normalization) => {
+            let document = std::fs::read(input).with_context(|| format!("reading {}", input))?;
+            if !text::is_text(&document) {
+                bail!("{}: --text needs a UTF-8 document without NUL bytes", input);
+            }
+            let mut object = match serde_json::from_str(&metadata) {
+                Ok(Value::Object(object)) => object,
+                _ => bail!("--text needs metadata that is a JSON object"),
+            };
+            object.insert("content".into(), TextRecord::new(&document, normalization).to_json());
+            (Value::Object(object).to_string(), Some(normalization.apply(&document)))
+        }
+        None => (metadata, None),
+    };
+    // A declared media type is checked against the file's leading bytes and
+    // recorded in the `content` block, as the service does.
+    let declared = match args.value("--media-type") {
+        Some(_) if text_mode.is_some() => bail!("--text seals text/plain documents; give it or --media-type, not both"),
+        Some(value) => {
+            Some(media_type::normalize(value).ok_or_else(|| anyhow!("--media-type takes a media type like application/pdf"))?)
+        }
+        None => None,
+    };
+    let metadata = match &declared {
+        Some(declared) => {
+            let mut head = Vec::new();
+            File::open(input).with_context(|| format!("reading {}", input))?.take(64).read_to_end(&mut head)?;
+            if let Some(sniffed) = media_type::sniff(&head).filter(|sniffed| !media_type::agrees(declared, sniffed)) {
+                bail!("{}: declared as {} but its content is {}", input, declared, sniffed);
+            }
+            let mut object = match serde_json::from_str(&metadata) {
+                Ok(Value::Object(object)) => object,
+                _ => bail!("--media-type needs metadata that is a JSON object"),
+            };
+            object.insert("content".into(), json!({ "format": "payload", "media_type": declared }));
+            Value::Object(object).to_string()
+        }
+        None => metadata,
+    };
+    // JSON object metadata is checked against the schema and stored in
+    // canonical form, as the service does.
+    let metadata = match Metadata::parse_structured(&metadata)? {
+        Some(structured) => structured.to_canonical_json(),
+        None => metadata,
+    };
+    let open_payload = || -> anyhow::Result<Box<dyn ReadSeek>> {
+        Ok(match &document {
+            Some(document) => Box::new(io::Cursor::new(document.clone())),
+            None => Box::new(BufReader::new(File::open(input)?)),
+        })
+    };
+    // Manifests and attestations are detached signatures in another form.
+    let manifest = args.has("--manifest");
+    let attestation = args.has("--intoto");
+    if [args.has("--detached"), manifest, attestation].into_iter().filter(|&given| given).count() > 1 {
+        bail!("give one of --detached, --manifest or --intoto");
+    }
+    let detached = args.has("--detached") || manifest || attestation;
+    let kind = match (manifest, attestation) {
+        (true, _) => "payload manifests",
+        (false, true) => "in-toto attestations",
+        (false, false) => "detached signatures",
+    };
+    if attestation && text_mode.is_some() {
+        bail!("in-toto attestations are about the file as it is; they cannot be combined with --text");
+    }
+    let external_metadata = args.has("--external-metadata");
+    if detached && external_metadata {
+        bail!("{} cannot carry external metadata", kind);
+    }
+    let mut extensions = Vec::new();
+    for arg in args.values("--extension") {
+        let (name, path) = arg.split_once('=').ok_or_else(|| anyhow!("--extension takes NAME=FILE"))?;
+        extensions.push(format::Extension::bytes(name, std::fs::read(path).with_context(|| format!("reading {}", path))?));
+    }
+    for arg in args.values("--extension-json") {
+        let (name, json) = arg.split_once('=').ok_or_else(|| anyhow!("--extension-json takes NAME=JSON"))?;
+        extensions.push(format::Extension::json(name, json)?);
+    }
+    if detached && !extensions.is_empty() {
+        bail!("{} cannot carry extensions", kind);
+    }
+    let chain = match args.value("--cert-chain") {
+        Some(path) => {
+            let text = std::fs::read_to_string(path).with_context(|| format!("reading {}", path))?;
+            let chain = x509::certificates_from_pem(&text).map_err(|e| anyhow!("{}: {}", path, e))?;
+            x509::check_leaf(&chain, &key.verifying_key().to_sec1_bytes()).map_err(|e| anyhow!("{}: {}", path, e))?;
+            chain
+        }
+        None => Vec::new(),
+    };
+    if detached && !chain.is_empty() {
+        bail!("{} cannot carry a certificate chain", kind);
+    }
+    let trust_hint = load_trust_hint(&args)?;
+    if detached && trust_hint.is_some() {
+        bail!("{} cannot carry a trust hint", kind);
+    }
+    let given = load_endorsements(&args)?;
+    let endorsements = endorsement::chain_of(&key.verifying_key().to_sec1_bytes(), &given);
+    if !given.is_empty() && endorsements.is_empty() {
+        bail!("none of the --endorsements endorses the sealing key");
+    }
+    if detached && !endorsements.is_empty() {
+        bail!("{} cannot carry endorsements", kind);
+    }
+    let chunk_size = match args.value("--chunk-size") {
+        Some(size) => {
+            let size = u32::try_from(bench::parse_size(size)?).map_err(|_| anyhow!("--chunk-size is too large"))?;
+            chunked::check_chunk_size(size)?;
+            Some(size)
+        }
+        None => None,
+    };
+    if detached && chunk_size.is_some() {
+        bail!("{} cannot be chunked", kind);
+    }
+    let compression = match args.value("--compress") {
+        Some(blocks) => format::Compression::parse(blocks)
+            .ok_or_else(|| anyhow!("--compress takes metadata, image, all or none"))?,
+        None => format::Compression::default(),
+    };
+    if detached && !compression.is_none() {
+        bail!("{} cannot be compressed", kind);
+    }
+    if compression.image && chunk_size.is_some() {
+        bail!("a chunked image cannot be compressed");
+    }
+    let extension = match (manifest, attestation, detached) {
+        (true, _, _) => manifest::MANIFEST_EXTENSION,
+        (false, true, _) => intoto::ATTESTATION_EXTENSION,
+        (false, false, true) => format::DETACHED_EXTENSION,
+        (false, false, false) => "aegis",
+    };
+    let output = args
+        .value("-o")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(format!("{}.{}", input, extension)));
+
+    let sealer = Sealer::new(key.clone());
+    if detached {
+        let signature = sealer.seal_detached(&metadata, &mut open_payload()?)?;
+        if manifest {
+            std::fs::write(&output, PayloadManifest::new(&signature, Some(input), None).to_bytes())?;
+        } else if attestation {
+            let name = Path::new(input).file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+            std::fs::write(&output, intoto::build(&signature, &name, &key)?.to_line())?;
+        } else {
+            std::fs::write(&output, signature.to_bytes())?;
+        }
+    } else if external_metadata
+        || !extensions.is_empty()
+        || !chain.is_empty()
+        || trust_hint.is_some()
+        || !endorsements.is_empty()
+        || chunk_size.is_some()
+        || document.is_some()
+        || !compression.is_none()
+    {
+        let mut header = format::FormatHeader::default();
+        let metadata = if external_metadata { header.set_external_metadata(&metadata) } else { metadata.clone() };
+        if !extensions.is_empty() {
+            header.set_extensions(&extensions)?;
+        }
+        if !chain.is_empty() {
+            header.set_certificate_chain(&chain);
+        }
+        if let Some(hint) = &trust_hint {
+            header.set_trust_hint(hint);
+        }
+        if !endorsements.is_empty() {
+            header.set_endorsements(&endorsements);
+        }
+        header.set_compression(compression);
+        let mut writer = BufWriter::new(File::create(&output)?);
+        let mut reader = open_payload()?;
+        if compression.image {
+            let mut image = Vec::new();
+            reader.read_to_end(&mut image)?;
+            crypto::seal_with_header(header, metadata, image, &key)?.write(&mut writer)?;
+        } else {
+            match chunk_size {
+                Some(size) => crypto::seal_stream_chunked(&header, &metadata, size, &mut reader, &mut writer, &key)?,
+                None => crypto::seal_stream_with_header(&header, &metadata, &mut reader, &mut writer, &key)?,
+            };
+        }
+        writer.flush()?;
+    } else {
+        sealer.seal_file(&metadata, input, &output)?;
+    }
+    let fingerprint = sealer.fingerprint();
+    let report = json!({
+        "input": input,
+        "output": output.display().to_string(),
+        "detached": detached,
+        "manifest": manifest,
+        "intoto": attestation,
+        "external_metadata": external_metadata,
+        "extensions": extensions.iter().map(|e| e.name.as_str()).collect::<Vec<_>>(),
+        "certificate_subject": chain.first().map(x509::Certificate::subject),
+        "trust_hint": trust_hint,
+        "endorsements": endorsements.len(),
+        "chunk_size": chunk_size,
+        "text": text_mode.map(Normalization::name),
+        "media_type": declared,
+        "compressed": compression.blocks(),
+        "key_fingerprint": fingerprint.to_hex(),
+    });
+    print(args.has("--json"), &report, || {
+        println!("Sealed {} -> {}", input, output.display());
+        println!("Key: {} ({})", fingerprint.to_hex_groups(), fingerprint.to_words());
+    })?;
+    Ok(true)
+}
+
+fn verify(args: Args) -> anyhow::Result<bool> {
+    args.check(&[
+        "--trust",
+        "--trust-anchors",
+        "--original",
+        "--lang",
+        "--level",
+        "--dns-resolver",
+        "--require-dnssec",
+        "--endorsement-root",
+        "--endorsements",
+        "--log-proof",
+        "--log-key",
+        "--trusted-tsa",
+        "--tsa-anchors",
+        "--range",
+        "-o",
+        "--json",
+    ])?;
+    let path = args.file()?;
+    if let Some(range) = args.value("--range") {
+        return verify_range(&args, path, range);
+    }
+    if args.has("-o") {
+        bail!("-o needs --range");
+    }
+    let level = args
+        .value("--level")
+        .map(|name| {
+            VerificationLevel::parse(name).ok_or_else(|| anyhow!("unknown level '{}'; expected quick, standard or forensic", name))
+        })
+        .transpose()?;
+    let trusted = args.values("--trust").map(load_trusted).collect::<anyhow::Result<Vec<_>>>()?;
+    let anchors = match args.value("--trust-anchors") {
+        Some(path) => {
+            let text = std::fs::read_to_string(path).with_context(|| format!("reading {}", path))?;
+            x509::TrustAnchors::from_pem(&text).map_err(|e| anyhow!("{}: {}", path, e))?
+        }
+        None => x509::TrustAnchors::default(),
+    };
+    let tsa_trust = timestamp::TsaTrust {
+        fingerprints: args
+            .values("--trusted-tsa")
+            .map(|fingerprint| {
+                if fingerprint.len() == 64 && fingerprint.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    Ok(fingerprint.to_ascii_lowercase())
+                } else {
+                    Err(anyhow!("--trusted-tsa takes a hex key fingerprint, not '{}'", fingerprint))
+                }
+            })
+            .collect::<anyhow::Result<_>>()?,
+        anchors: match args.value("--tsa-anchors") {
+            Some(path) => {
+                let text = std::fs::read_to_string(path).with_context(|| format!("reading {}", path))?;
+                x509::TrustAnchors::from_pem(&text).map_err(|e| anyhow!("{}: {}", path, e))?
+            }
+            None => x509::TrustAnchors::default(),
+        },
+    };
+
+    let resolver = args.value("--dns-resolver");
+    if args.has("--require-dnssec") && resolver.is_none() {
+        bail!("--require-dnssec needs --dns-resolver");
+    }
+    let roots: Vec<Vec<u8>> = args
+        .values("--endorsement-root")
+        .map(|path| load_any_public_key(path).map(|key| key.to_sec1_bytes().into_vec()))
+        .collect::<anyhow::Result<_>>()?;
+    let known = load_endorsements(&args)?;
+    if args.has("--log-key") && !args.has("--log-proof") {
+        bail!("--log-key needs --log-proof");
+    }
+    let log_proof = args.value("--log-proof").map(load_log_proof).transpose()?;
+
+    // A payload manifest is checked as the sidecar it stands for, against
+    // the file it pairs with.
+    let mut head = Vec::new();
+    File::open(path)?.take(64).read_to_end(&mut head)?;
+    let detached = if manifest::sniff(&head) {
+        let manifest = PayloadManifest::parse(&std::fs::read(path)?).map_err(|e| anyhow!("{}: {}", path, e))?;
+        let original = match args.value("--original") {
+            Some(original) => PathBuf::from(original),
+            None => manifest_payload(Path::new(path), &manifest)?,
+        };
+        Some((manifest.detached()?, original))
+    } else {
+        match args.value("--original") {
+            Some(original) => Some((
+                DetachedSignature::parse(&std::fs::read(path)?).map_err(|e| anyhow!("{}: {}", path, e))?,
+                PathBuf::from(original),
+            )),
+            None => None,
+        }
+    };
+    let original = detached.as_ref().map(|(_, original)| original.display().to_string());
+    let (report, mut checks, public_key, trust_hint, endorsed, image_sha256) = match detached {
+        Some(_) if level.is_some() => bail!("--level needs a container, not a detached signature"),
+        Some(_) if resolver.is_some() => bail!("--dns-resolver needs a container, not a detached signature"),
+        Some((sidecar, original)) => {
+            let report = crypto::verify_detached(
+                &sidecar,
+                &mut BufReader::new(File::open(&original).with_context(|| format!("reading {}", original.display()))?),
+            )?;
+            let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_secs() as i64;
+            let endorsed =
+                (!roots.is_empty()).then(|| endorsement::verify_chain(&sidecar.public_key, &known, &roots, now));
+            (report, None, sidecar.public_key, None, endorsed, sidecar.image_sha256)
+        }
+        None => {
+            let mut file = BufReader::new(File::open(path)?);
+            let ancient = if xmp::ImageKind::sniff(file.fill_buf()?).is_some() {
+                let mut image = Vec::new();
+                file.read_to_end(&mut image)?;
+                xmp::extract_sealed(&image, format::MAX_BLOCK_SIZE as usize)
+            } else {
+                AegisAncient::read(&mut file)
+            }
+            .map_err(|e| anyhow!("{}: {}", path, e))?;
+            let trust_hint = ancient.header.trust_hint().map_err(|e| anyhow!("{}: {}", path, e))?;
+            let (report, checks) = match level {
+                Some(level) => {
+                    let (report, checks) = levels::verify(&ancient, level, &anchors, &tsa_trust)?;
+                    (report, Some(checks))
+                }
+                None => (crypto::verify_with_anchors(&ancient, &anchors, &tsa_trust)?, None),
+            };
+            let endorsed = (!roots.is_empty())
+                .then(|| endorsement::verify_container(&ancient, &report, &known, &roots))
+                .transpose()
+                .map_err(|e| anyhow!("{}: {}", path, e))?;
+            let image_sha256 = Sha256::digest(&ancient.image_data).into();
+            (report, checks, ancient.public_key, trust_hint, endorsed, image_sha256)
+        }
+    };
+    let fingerprint = Fingerprint::of(&public_key);
+    let key_trusted = (!trusted.is_empty()).then(|| trusted.iter().any(|t| fingerprint.matches(t)));
+    let endorsement_valid = endorsed.as_ref().map(|chain| chain.valid);
+    let logged = match (&log_proof, args.value("--log-key")) {
+        (None, _) => None,
+        (Some((proof, head)), log_key) => {
+            let log_key = match log_key {
+                Some(path) => load_any_public_key(path)?.to_sec1_bytes().into_vec(),
+                None => public_key.clone(),
+            };
+            Some(merkle::verify_inclusion(&image_sha256, proof, head, &log_key).map_err(|e| e.to_string()))
+        }
+    };
+    let log_valid = logged.as_ref().map(Result::is_ok);
+    if let Some(checks) = checks.as_mut().filter(|c| c.level >= VerificationLevel::Standard) {
+        match key_trusted {
+            Some(trusted) => checks.add("key_trust", if trusted { Outcome::Pass } else { Outcome::Fail }, None),
+            None => checks.add("key_trust", Outcome::NotApplicable, Some("no --trust keys given".into())),
+        }
+        match &endorsed {
+            Some(chain) => checks.add(
+                "endorsement",
+                if chain.valid { Outcome::Pass } else { Outcome::Fail },
+                chain.error.clone().or_else(|| chain.root.clone()),
+            ),
+            None => checks.add("endorsement", Outcome::NotApplicable, Some("no --endorsement-root given".into())),
+        }
+        match &logged {
+            Some(Ok(())) => checks.add("log_inclusion", Outcome::Pass, None),
+            Some(Err(e)) => checks.add("log_inclusion", Outcome::Fail, Some(e.clone())),
+            None => checks.add("log_inclusion", Outcome::NotApplicable, Some("no --log-proof given".into())),
+        }
+    }
+    // Given a resolver, the seal must carry a hint whose records publish its
+    // key.
+    let dns = match (resolver, &trust_hint) {
+        (Some(resolver), Some(hint)) => Some(
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?
+                .block_on(aegis_sealer_service::dns::check(resolver, hint, &public_key)),
+        ),
+        _ => None,
+    };
+    let dns_valid = resolver.map(|_| {
+        dns.as_ref()
+            .is_some_and(|d| d.key_published && (d.authenticated || !args.has("--require-dnssec")))
+    });
+    let dns_problem = match (&dns, dns_valid) {
+        (_, Some(true) | None) => None,
+        (None, _) => Some("carries no DNS trust hint".to_string()),
+        (Some(dns), _) => Some(match &dns.error {
+            Some(error) => error.clone(),
+            None => format!("{} is not authenticated with DNSSEC", dns.record_name),
+        }),
+    };
+    if let (Some(checks), Some(dns_valid)) = (checks.as_mut(), dns_valid) {
+        checks.add(
+            "dns_trust",
+            if dns_valid { Outcome::Pass } else { Outcome::Fail },
+            dns_problem.clone().or_else(|| dns.as_ref().map(|d| d.record_name.clone())),
+        );
+    }
+    let checks_passed = checks.as_ref().is_none_or(|c| c.passed);
+    let cosigners_valid = report.cosigners.iter().all(|c| c.signature_valid);
+    // Given anchors, the seal must carry a chain leading to one of them.
+    let chain_valid = (!anchors.is_empty()).then(|| report.certificate_chain.as_ref().is_some_and(|c| c.valid));
+    let valid = report.signature_valid
+        && key_trusted != Some(false)
+        && endorsement_valid != Some(false)
+        && log_valid != Some(false)
+        && chain_valid != Some(false)
+        && dns_valid != Some(false)
+        && report.external_metadata_valid != Some(false)
+        && cosigners_valid
+        && checks_passed;
+
+    let mut value = serde_json::to_value(&report)?;
+    value["file"] = json!(path);
+    value["key_trusted"] = json!(key_trusted);
+    value["valid"] = json!(valid);
+    value["trust_hint"] = json!(trust_hint);
+    if let Some(dns) = &dns {
+        value["dns_trust"] = serde_json::to_value(dns)?;
+    }
+    if let Some(chain) = &endorsed {
+        value["endorsement"] = serde_json::to_value(chain)?;
+    }
+    if let (Some((proof, head)), Some(logged)) = (&log_proof, &logged) {
+        value["log_inclusion"] = json!({
+            "valid": logged.is_ok(),
+            "error": logged.as_ref().err(),
+            "leaf_index": proof.leaf_index,
+            "tree_size": head.head.tree_size,
+            "root_hash": hex::encode(head.head.root_hash),
+        });
+    }
+    if let Some(checks) = &checks {
+        value["verification"] = serde_json::to_value(checks)?;
+    }
+    let view = args.value("--lang").and_then(|lang| language_view(&report.metadata, lang));
+    if let Some(view) = &view {
+        value["language_view"] = view.clone();
+    }
+    print(args.has("--json"), &value, || {
+        let display = TimeDisplay::from_env();
+        match (report.signature_valid, valid) {
+            (true, true) => println!("VALID: {}", path),
+            (true, false) if key_trusted == Some(false) => {
+                println!("UNTRUSTED: {} is signed by a key not given with --trust", path)
+            }
+            (true, false) if endorsement_valid == Some(false) => println!(
+                "UNTRUSTED: {} {}",
+                path,
+                endorsed.as_ref().and_then(|c| c.error.as_deref()).unwrap_or_default()
+            ),
+            (true, false) if log_valid == Some(false) => println!(
+                "UNLOGGED: {} {}",
+                path,
+                logged.as_ref().and_then(|l| l.as_ref().err()).map(String::as_str).unwrap_or_default()
+            ),
+            (true, false) if chain_valid == Some(false) => println!(
+                "UNTRUSTED: {} {}",
+                path,
+                report
+                    .certificate_chain
+                    .as_ref()
+                    .and_then(|c| c.error.as_deref())
+                    .unwrap_or("carries no certificate chain")
+            ),
+            (true, false) if dns_valid == Some(false) => {
+                println!("UNTRUSTED: {} {}", path, dns_problem.as_deref().unwrap_or_default())
+            }
+            (true, false) if !cosigners_valid => println!("INVALID: {} has a co-signature that does not verify", path),
+            (true, false) if !checks_passed => println!(
+                "INVALID: {} failed {} verification",
+                path,
+                checks.as_ref().map_or("", |c| c.level.name())
+            ),
+            (true, false) => println!("INVALID: {} does not carry the metadata it signed", path),
+            (false, _) if report.text.as_ref().is_some_and(|t| t.differs_only_in_line_endings) => println!(
+                "INVALID: {} differs from the signed document only in its line endings",
+                original.as_deref().unwrap_or(path)
+            ),
+            (false, _) => println!("INVALID: {} does not match its signature", path),
+        }
+        println!("Key: {}", report.key_fingerprint);
+        if let Some(id) = &report.key_id {
+            println!("Key ID: {}", id);
+        }
+        if let Some(chain) = &report.certificate_chain {
+            match (&chain.subject, &chain.anchor) {
+                (Some(subject), Some(anchor)) => println!("Certificate: {} (issued under {})", subject, anchor),
+                (Some(subject), None) => println!("Certificate: {} (not verified)", subject),
+                (None, _) => println!("Certificate: unreadable"),
+            }
+        }
+        if let Some(chain) = endorsed.as_ref().filter(|c| c.valid) {
+            let roles: Vec<&str> = chain.chain.iter().map(|link| link.role.as_str()).collect();
+            match roles.as_slice() {
+                [] => println!("Endorsement: the key is a root"),
+                roles => println!(
+                    "Endorsement: {} under root {}",
+                    roles.join(" < "),
+                    chain.root.as_deref().unwrap_or_default()
+                ),
+            }
+        }
+        if let (Some((proof, head)), Some(Ok(()))) = (&log_proof, &logged) {
+            println!("Log: entry {} of {}", proof.leaf_index + 1, head.head.tree_size);
+        }
+        if let Some(dns) = &dns {
+            match &dns.error {
+                Some(error) => println!("DNS: {}", error),
+                None => println!(
+                    "DNS: {} publishes the key{}",
+                    dns.record_name,
+                    if dns.authenticated { " (DNSSEC)" } else { "" }
+                ),
+            }
+        } else if let Some(hint) = &trust_hint {
+            println!("DNS hint: {} (not resolved)", hint.record_name());
+        }
+        if let Some(timestamp) = &report.timestamp {
+            let reason = timestamp.error.as_deref().map(|e| format!(" ({})", e)).unwrap_or_default();
+            match (&timestamp.attested_time, timestamp.valid) {
+                (Some(time), true) => println!("Timestamp: {}", display.render_str(time)),
+                (Some(time), false) if timestamp.imprint_matches && timestamp.tsa_signature_valid != Some(false) => {
+                    println!("Timestamp: unverified{}, claims {}", reason, display.render_str(time))
+                }
+                _ => println!("Timestamp: invalid{}", reason),
+            }
+        }
+        match &report.media_type {
+            Some(media_type) => println!("Payload: {} bytes ({})", report.payload_size, media_type),
+            None => println!("Payload: {} bytes", report.payload_size),
+        }
+        if let Some(text) = &report.text {
+            match text.record.normalization {
+                Normalization::None => {
+                    println!("Text: UTF-8, signed as submitted ({} line endings)", text.record.line_endings.name().to_ascii_uppercase())
+                }
+                normalization => println!(
+                    "Text: UTF-8, line endings normalized to {} (submitted with {})",
+                    normalization.name().to_ascii_uppercase(),
+                    text.record.line_endings.name().to_ascii_uppercase()
+                ),
+            }
+        }
+        if report.external_metadata_valid == Some(false) {
+            println!("Metadata: external document missing or altered; signed reference {}", report.metadata);
+        } else {
+            println!("Metadata: {}", report.metadata);
+        }
+        if let Some(view) = &view {
+            println!(
+                "Labels ({} of {} signed languages): {}",
+                scalar(&view["language"]),
+                view["variants"].as_object().map_or(0, |v| v.len()),
+                view["labels"]
+            );
+        }
+        for cosigner in &report.cosigners {
+            println!(
+                "Co-signer {}: {} at {} ({})",
+                cosigner.role,
+                cosigner.key_fingerprint,
+                display.render(cosigner.signed_at),
+                if cosigner.signature_valid { "valid" } else { "INVALID" }
+            );
+        }
+        for extension in &report.extensions {
+            match extension.get("value") {
+                Some(value) => println!("Extension {}: {}", scalar(&extension["name"]), value),
+                None => println!(
+                    "Extension {}: {} bytes ({})",
+                    scalar(&extension["name"]),
+                    extension["length"],
+                    scalar(&extension["base64"])
+                ),
+            }
+        }
+        if let Some(checks) = &checks {
+            println!("Checks ({}):", checks.level.name());
+            for check in &checks.checks {
+                let outcome = match check.outcome {
+                    Outcome::Pass => "pass",
+                    Outcome::Fail => "FAIL",
+                    Outcome::NotApplicable => "n/a",
+                };
+                match &check.detail {
+                    Some(detail) => println!("  {}: {} ({})", check.name, outcome, detail),
+                    None => println!("  {}: {}", check.name, outcome),
+                }
+            }
+        }
+    })?;
+    Ok(valid)
+}
+
+/// `verify --range`: checks part of a chunked container's image.
+fn verify_range(args: &Args, path: &str, range: &str) -> anyhow::Result<bool> {
+    let trusted = args.values("--trust").map(load_trusted).collect::<anyhow::Result<Vec<_>>>()?;
+    for other in [
+        "--trust-anchors",
+        "--original",
+        "--lang",
+        "--level",
+        "--dns-resolver",
+        "--endorsement-root",
+        "--log-proof",
+        "--trusted-tsa",
+        "--tsa-anchors",
+    ] {
+        if args.has(other) {
+            bail!("{} cannot be combined with --range", other);
+        }
+    }
+    let (offset, length) = range
+        .split_once(':')
+        .and_then(|(offset, length)| Some((offset.parse::<u64>().ok()?, length.parse::<u64>().ok()?)))
+        .ok_or_else(|| anyhow!("--range takes OFFSET:LENGTH in bytes"))?;
+    let end = offset.checked_add(length).ok_or_else(|| anyhow!("--range is too large"))?;
+    let mut file = BufReader::new(File::open(path)?);
+    let report = chunked::verify_range(&mut file, offset..end).map_err(|e| anyhow!("{}: {}", path, e))?;
+    let fingerprint = Fingerprint::of(&report.public_key);
+    let key_trusted = (!trusted.is_empty()).then(|| trusted.iter().any(|t| fingerprint.matches(t)));
+    let valid = report.signature_valid
+        && report.range_valid
+        && report.external_metadata_valid != Some(false)
+        && key_trusted != Some(false);
+    if let (true, Some(out)) = (valid, args.value("-o")) {
+        std::fs::write(out, &report.data).with_context(|| format!("writing {}", out))?;
+    }
+
+    let mut value = serde_json::to_value(&report)?;
+    value["file"] = json!(path);
+    value["key_trusted"] = json!(key_trusted);
+    value["valid"] = json!(valid);
+    print(args.has("--json"), &value, || {
+        let range = format!("bytes {}..{} of {}", report.start, report.end, path);
+        match (report.signature_valid, report.range_valid) {
+            (false, _) => println!("INVALID: {} does not match its signature", path),
+            (true, false) => println!("INVALID: {} do not match their signed chunk hashes", range),
+            (true, true) if report.external_metadata_valid == Some(false) => {
+                println!("INVALID: {}'s external metadata does not match its signed reference", path)
+            }
+            (true, true) if key_trusted == Some(false) => {
+                println!("UNTRUSTED: {} is signed by a key not given with --trust", path)
+            }
+            (true, true) => println!("VALID: {}", range),
+        }
+        println!("Key: {}", report.key_fingerprint);
+        if let Some(id) = &report.key_id {
+            println!("Key ID: {}", id);
+        }
+        println!(
+            "Chunks: {}..{} of {} ({} bytes each), {} of {} image bytes read",
+            report.chunks.start, report.chunks.end, report.chunk_count, report.chunk_size, report.bytes_read, report.image_len
+        );
+        if report.external_metadata_valid == Some(false) {
+            println!("Metadata: external document missing or altered; signed reference {}", report.metadata);
+        } else {
+            println!("Metadata: {}", report.metadata);
+        }
+    })?;
+    Ok(valid)
+}
+
+fn countersign(args: Args) -> anyhow::Result<bool> {
+    args.check(&["--key", "--role", "-o", "--json"])?;
+    let input = args.file()?;
+    let key = load_signing_key(args.value("--key").ok_or_else(|| anyhow!("countersign needs --key"))?)?;
+    let role = args.value("--role").ok_or_else(|| anyhow!("countersign needs --role"))?;
+    let output = args.value("-o").map(PathBuf::from).unwrap_or_else(|| PathBuf::from(input));
+
+    let mut file = File::open(input)?;
+    let size = file.metadata()?.len();
+    let mut prefix = Vec::new();
+    let mut want = INITIAL_PREFIX;
+    let header = loop {
+        (&mut file).take((want - prefix.len()) as u64).read_to_end(&mut prefix)?;
+        match format::parse_header(&prefix).map_err(|e| anyhow!("{}: {}", input, e))? {
+            Some(header) => break header,
+            None if (prefix.len() as u64) < size => want *= 2,
+            None => bail!("{}: truncated container", input),
+        }
+    };
+
+    // A co-signer vouches for the contents, so check them first.
+    file.seek(SeekFrom::Start(header.header_len))?;
+    let mut hasher = crypto::SigningHasher::for_header(&header.header, &header.metadata)?;
+    if header.header.compression().image {
+        let mut stored = Vec::new();
+        (&mut file).take(header.image_len).read_to_end(&mut stored)?;
+        if stored.len() as u64 != header.image_len {
+            bail!("{}: truncated container", input);
+        }
+        hasher.update(&format::decompress_block(&stored, format::MAX_BLOCK_SIZE as usize)?);
+    } else if io::copy(&mut (&mut file).take(header.image_len), &mut hasher)? != header.image_len {
+        bail!("{}: truncated container", input);
+    }
+    let digest = crypto::container_digest(&header.header, &hasher.finalize())?;
+    let scheme = crypto::SignatureScheme::of(&header.header)?;
+    if !scheme.verify_digest(&header.public_key, &header.signature, &digest)? {
+        bail!("{}: does not match its signature; not countersigning", input);
+    }
+
+    let signed_at = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_secs() as i64;
+    let cosignature = crypto::cosign(&digest, &header.public_key, role, signed_at, &key)?;
+    let mut format_header = header.header.clone();
+    format_header.add_cosignature(&cosignature)?;
+    let cosigners = format_header.cosignatures()?.len();
+
+    // Written beside the output and renamed over it, since the output may
+    // be the input.
+    let temp = PathBuf::from(format!("{}.tmp", output.display()));
+    let mut writer = BufWriter::new(File::create(&temp)?);
+    writer.write_all(&format::header_bytes(
+        &format_header,
+        &header.public_key,
+        &header.metadata,
+        &header.signature,
+        header.image_len,
+    ))?;
+    file.seek(SeekFrom::Start(header.header_len))?;
+    io::copy(&mut (&mut file).take(header.image_len), &mut writer)?;
+    writer.flush()?;
+    drop(writer);
+    std::fs::rename(&temp, &output)?;
+
+    let fingerprint = Fingerprint::of(&cosignature.public_key);
+    let report = json!({
+        "input": input,
+        "output": output.display().to_string(),
+        "role": role,
+        "signed_at": signed_at,
+        "key_fingerprint": fingerprint.to_hex(),
+        "cosigners": cosigners,
+    });
+    print(args.has("--json"), &report, || {
+        println!("Countersigned {} -> {} as {}", input, output.display(), role);
+        println!("Key: {} ({})", fingerprint.to_hex_groups(), fingerprint.to_words());
+    })?;
+    Ok(true)
+}
+
+fn dns_record(args: Args) -> anyhow::Result<bool> {
+    args.check(&["--key", "--trust-hint"])?;
+    let path = args.value("--key").ok_or_else(|| anyhow!("dns-record needs --key"))?;
+    let hint = load_trust_hint(&args)?.ok_or_else(|| anyhow!("dns-record needs --trust-hint"))?;
+    // A private key is accepted so the record can be made from the key that
+    // seals.
+    let public_key = load_any_public_key(path)?;
+    println!("{}", dns_trust::zone_line(&hint, &public_key.to_sec1_bytes()));
+    Ok(true)
+}
+
+fn endorse(args: Args) -> anyhow::Result<bool> {
+    args.check(&["--key", "--subject", "--role", "--not-before", "--not-after", "-o", "--json"])?;
+    let key = load_signing_key(args.value("--key").ok_or_else(|| anyhow!("endorse needs --key"))?)?;
+    let subject = load_any_public_key(args.value("--subject").ok_or_else(|| anyhow!("endorse needs --subject"))?)?;
+    let role = args.value("--role").ok_or_else(|| anyhow!("endorse needs --role"))?;
+    let time = |flag: &str| -> anyhow::Result<Option<i64>> {
+        args.value(flag)
+            .map(|value| time::parse_rfc3339(value).ok_or_else(|| anyhow!("{} takes an RFC 3339 time", flag)))
+            .transpose()
+    };
+    let not_before = match time("--not-before")? {
+        Some(at) => at,
+        None => std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_secs() as i64,
+    };
+    let not_after = time("--not-after")?.ok_or_else(|| anyhow!("endorse needs --not-after"))?;
+    let endorsement = endorsement::endorse(&subject.to_sec1_bytes(), role, not_before, not_after, &key)?;
+
+    let output = match args.value("-o") {
+        Some(path) => {
+            // Endorsements collect in one file, so a chain can be kept together.
+            let mut endorsements = match std::fs::read_to_string(path) {
+                Ok(text) => endorsement::from_json(&text).map_err(|e| anyhow!("{}: {}", path, e))?,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+                Err(e) => return Err(anyhow!("reading {}: {}", path, e)),
+            };
+            endorsements.retain(|e| e.issuer != endorsement.issuer || e.subject != endorsement.subject);
+            endorsements.push(endorsement.clone());
+            std::fs::write(path, serde_json::to_string_pretty(&endorsements)? + "\n")?;
+            Some(path)
+        }
+        None => None,
+    };
+    let issuer = Fingerprint::of(&endorsement.issuer);
+    let endorsed = Fingerprint::of(&endorsement.subject);
+    let value = serde_json::to_value(&endorsement)?;
+    match output {
+        Some(path) => print(args.has("--json"), &value, || {
+            println!("Endorsed {} as {} -> {}", endorsed.to_hex(), role, path);
+            println!("Issuer: {} ({})", issuer.to_hex_groups(), issuer.to_words());
+        })?,
+        None => println!("{}", serde_json::to_string_pretty(&value)?),
+    }
+    Ok(true)
+}
+
+fn delegate(args: Args) -> anyhow::Result<bool> {
+    args.check(&[
+        "--key",
+        "--subject",
+        "--not-before",
+        "--not-after",
+        "--id",
+        "--max-size",
+        "--require",
+        "--require-prefix",
+        "--json",
+    ])?;
+    let key = load_signing_key(args.value("--key").ok_or_else(|| anyhow!("delegate needs --key"))?)?;
+    let subject = args.value("--subject").ok_or_else(|| anyhow!("delegate needs --subject"))?;
+    let time = |flag: &str| -> anyhow::Result<Option<i64>> {
+        args.value(flag)
+            .map(|value| time::parse_rfc3339(value).ok_or_else(|| anyhow!("{} takes an RFC 3339 time", flag)))
+            .transpose()
+    };
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?;
+    let id = match args.value("--id") {
+        Some(id) => id.to_string(),
+        None => {
+            let mut hasher = Sha256::new();
+            hasher.update(subject.as_bytes());
+            hasher.update(now.as_nanos().to_be_bytes());
+            hasher.update(std::process::id().to_be_bytes());
+            hex::encode(&hasher.finalize()[..8])
+        }
+    };
+    let max_payload = args
+        .value("--max-size")
+        .map(|v| v.parse().map_err(|_| anyhow!("--max-size takes a number of bytes")))
+        .transpose()?;
+    let metadata = args
+        .values("--require")
+        .map(Constraint::equals)
+        .chain(args.values("--require-prefix").map(Constraint::prefix))
+        .collect::<Result<Vec<_>, _>>()?;
+    let claims = Delegation {
+        id,
+        subject: subject.to_string(),
+        issuer: String::new(),
+        not_before: time("--not-before")?.unwrap_or(now.as_secs() as i64),
+        not_after: time("--not-after")?.ok_or_else(|| anyhow!("delegate needs --not-after"))?,
+        max_payload,
+        metadata,
+    };
+    let token = delegation::issue(claims, &key)?;
+    let claims = delegation::decode(&token)?;
+    let value = json!({ "token": token, "claims": claims });
+    print(args.has("--json"), &value, || {
+        println!("{}", token);
+        eprintln!(
+            "Delegation {} for {} until {}",
+            claims.id,
+            claims.subject,
+            value["claims"]["not_after"].as_str().unwrap_or_default()
+        );
+    })?;
+    Ok(true)
+}
+
+fn bench_remote(args: Args) -> anyhow::Result<bool> {
+    args.check(&[
+        "--url",
+        "--size",
+        "--concurrency",
+        "--duration",
+        "--requests",
+        "--seal-only",
+        "--api-key",
+        "--json",
+    ])?;
+    let number = |flag: &str, default: u64| -> anyhow::Result<u64> {
+        args.value(flag)
+            .map(|v| v.parse().map_err(|_| anyhow!("{} takes a number", flag)))
+            .unwrap_or(Ok(default))
+    };
+    let options = bench::BenchOptions {
+        url: args.value("--url").ok_or_else(|| anyhow!("bench-remote needs --url"))?.to_string(),
+        image_size: bench::parse_size(args.value("--size").unwrap_or("1MB"))?,
+        concurrency: number("--concurrency", 8)? as usize,
+        duration: std::time::Duration::from_secs(number("--duration", 30)?),
+        max_requests: args.value("--requests").map(|_| number("--requests", 0)).transpose()?,
+        verify: !args.has("--seal-only"),
+        api_key: args.value("--api-key").map(str::to_string),
+    };
+    if !args.has("--json") {
+        eprintln!(
+            "Sealing {}-byte images at {} with {} workers...",
+            options.image_size, options.url, options.concurrency
+        );
+    }
+    let report = tokio::runtime::Runtime::new()?.block_on(bench::run(options))?;
+    print(args.has("--json"), &serde_json::to_value(&report)?, || {
+        println!(
+            "{} workers, {}-byte images, {:.1}s",
+            report.concurrency, report.image_size, report.elapsed_secs
+        );
+        for (name, op) in [("seal", Some(&report.seal)), ("verify", report.verify.as_ref())] {
+            let Some(op) = op else { continue };
+            println!(
+                "{:<7} {} requests, {} failed ({:.2}%), {:.1} req/s, {:.1} MB/s up",
+                name,
+                op.requests,
+                op.failed,
+                op.error_rate * 100.0,
+                op.requests_per_sec,
+                op.upload_mb_per_sec
+            );
+            if let Some(l) = &op.latency {
+                println!(
+                    "        latency ms: mean {:.1}  p50 {:.1}  p90 {:.1}  p99 {:.1}  max {:.1}",
+                    l.mean_ms, l.p50_ms, l.p90_ms, l.p99_ms, l.max_ms
+                );
+            }
+            for (kind, count) in &op.errors {
+                println!("        errors {}: {}", kind, count);
+            }
+        }
+    })?;
+    Ok(report.any_succeeded())
+}
+
+fn inspect(args: Args) -> anyhow::Result<bool> {
+    args.check(&["--metadata", "--explain", "--lang", "--json"])?;
+    let path = args.file()?;
+    if args.has("--explain") {
+        return explain(path, args.has("--json"));
+    }
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+
+    // Only the header blocks are read, so inspecting a large container is
+    // cheap.
+    let mut prefix = Vec::new();
+    let mut want = INITIAL_PREFIX;
+    let mut value = loop {
+        (&mut file).take((want - prefix.len()) as u64).read_to_end(&mut prefix)?;
+        if manifest::sniff(&prefix) {
+            let manifest = PayloadManifest::parse(&std::fs::read(path)?).map_err(|e| anyhow!("{}: {}", path, e))?;
+            break manifest_summary(&manifest);
+        }
+        if prefix.starts_with(format::DETACHED_MAGIC) {
+            let sidecar = DetachedSignature::parse(&std::fs::read(path)?).map_err(|e| anyhow!("{}: {}", path, e))?;
+            break detached_summary(&sidecar);
+        }
+        match format::parse_header(&prefix).map_err(|e| anyhow!("{}: {}", path, e))? {
+            Some(header) => break container_summary(&header, size, args.has("--metadata") || args.has("--lang")),
+            None if (prefix.len() as u64) < size => want *= 2,
+            None => bail!("{}: truncated container", path),
+        }
+    };
+    if let Some(lang) = args.value("--lang") {
+        value["language_view"] = language_view(&value["metadata"].to_string(), lang).unwrap_or(Value::Null);
+    }
+    print(args.has("--json"), &value, || {
+        println!("{}", path);
+        print_tree(&value, 1);
+    })?;
+    Ok(true)
+}
+
+fn explain(path: &str, as_json: bool) -> anyhow::Result<bool> {
+    let ancient = AegisAncient::read(&mut BufReader::new(File::open(path)?)).map_err(|e| anyhow!("{}: {}", path, e))?;
+    let explanation = aegis_core::explain::explain(&ancient).map_err(|e| anyhow!("{}: {}", path, e))?;
+    print(as_json, &explanation.to_json(), || {
+        println!("{}", path);
+        println!(
+            "Signature: {} over {} ({})",
+            explanation.scheme.name(),
+            hex::encode(explanation.signed_message),
+            if explanation.signature_valid { "valid" } else { "INVALID" }
+        );
+        println!("Byte ranges:");
+        for range in &explanation.ranges {
+            println!(
+                "  {:>10} +{:<10} {:<30} {:<10} {}",
+                range.offset,
+                range.length,
+                range.name,
+                if range.signed { "signed" } else { "not signed" },
+                range.note
+            );
+        }
+        println!("Canonicalization:");
+        for note in &explanation.canonicalization {
+            println!("  - {}", note);
+        }
+        println!("Digests:");
+        for (i, step) in explanation.steps.iter().enumerate() {
+            println!("  {}. {}", i + 1, step.description);
+            for input in &step.inputs {
+                let place = match input.range {
+                    Some((offset, length)) => format!("bytes {}..{}", offset, offset + length),
+                    None => "constant".to_string(),
+                };
+                match &input.bytes {
+                    Some(bytes) => println!("     {} ({}): {}", input.label, place, hex::encode(bytes)),
+                    None => println!("     {} ({}, too long to show)", input.label, place),
+                }
+            }
+            println!("     = {}", hex::encode(step.output));
+        }
+    })?;
+    Ok(true)
+}
+
+fn config(args: Args) -> anyhow::Result<bool> {
+    match args.positional.as_slice() {
+        [command] if command == "schema" => {
+            args.check(&[])?;
+            println!("{}", serde_json::to_string_pretty(&settings::schema())?);
+            Ok(true)
+        }
+        [command] if command == "check" => {
+            args.check(&["--env-file", "--config", "--json"])?;
+            let env_file = match args.value("--env-file") {
+                Some(path) => Some(EnvFile::read(Path::new(path)).with_context(|| format!("reading {}", path))?),
+                None if Path::new(".env").is_file() => Some(EnvFile::read(Path::new(".env"))?),
+                None => None,
+            };
+            let config_path = args
+                .value("--config")
+                .map(str::to_string)
+                .or_else(|| std::env::var("AEGIS_CONFIG").ok())
+                .or_else(|| env_file.as_ref()?.get("AEGIS_CONFIG").map(str::to_string));
+            let config_file = config_path.map(|path| config_file::read(Path::new(&path))).transpose()?;
+            let files: Vec<&EnvFile> = env_file.iter().chain(&config_file).collect();
+            let problems = settings::check(&files);
+            let value: Value = problems
+                .iter()
+                .map(|p| {
+                    json!({
+                        "severity": match p.severity {
+                            Severity::Error => "error",
+                            Severity::Warning => "warning",
+                        },
+                        "name": p.name,
+                        "location": p.location,
+                        "message": p.message,
+                    })
+                })
+                .collect();
+            print(args.has("--json"), &value, || {
+                for problem in &problems {
+                    let label = if problem.severity == Severity::Error { "error" } else { "warning" };
+                    println!("{}: {}", label, problem);
+                }
+                if problems.is_empty() {
+                    println!("configuration OK");
+                }
+            })?;
+            Ok(!problems.iter().any(|p| p.severity == Severity::Error))
+        }
+        _ => bail!(USAGE),
+    }
+}
+
+fn container_summary(header: &format::ContainerHeader, size: u64, resolve_metadata: bool) -> Value {
+    let fingerprint = Fingerprint::of(&header.public_key);
+    let fields: Vec<Value> = header
+        .header
+        .fields
+        .iter()
+        .map(|f| json!({ "tag": f.tag, "length": f.value.len() }))
+        .collect();
+    json!({
+        "kind": "container",
+        "version": char::from(header.version).to_string(),
+        "flags": header.header.flags,
+        "compressed": header.header.compression().blocks(),
+        "header_fields": fields,
+        "key_id": header.header.key_id(),
+        "signature_scheme": header.header.scheme_id().unwrap_or(crypto::SignatureScheme::EcdsaP256.id()),
+        "timestamped": header.header.timestamp_token().is_some(),
+        "key_fingerprint": fingerprint.to_hex(),
+        "key_words": fingerprint.to_words(),
+        "metadata": container_metadata(header, resolve_metadata),
+        "cosigners": match header.header.cosignatures() {
+            Ok(cosignatures) => json!(cosignatures
+                .iter()
+                .map(|c| json!({
+                    "role": c.role,
+                    "key_fingerprint": Fingerprint::of(&c.public_key).to_hex(),
+                    "signed_at": c.signed_at,
+                }))
+                .collect::<Vec<_>>()),
+            Err(e) => json!({ "error": e.to_string() }),
+        },
+        "extensions": match header.header.extensions() {
+            Ok(extensions) => json!(extensions.iter().map(format::Extension::to_json).collect::<Vec<_>>()),
+            Err(e) => json!({ "error": e.to_string() }),
+        },
+        "certificate_chain": match header.header.certificate_chain() {
+            Ok(chain) => json!(chain
+                .iter()
+                .map(|der| match x509::Certificate::from_der(der) {
+                    Ok(c) => json!({ "subject": c.subject(), "issuer": c.issuer(), "fingerprint": c.fingerprint() }),
+                    Err(e) => json!({ "error": e.to_string() }),
+                })
+                .collect::<Vec<_>>()),
+            Err(e) => json!({ "error": e.to_string() }),
+        },
+        "endorsements": match header.header.endorsements() {
+            Ok(endorsements) => json!(endorsements
+                .iter()
+                .map(|e| json!({
+                    "role": e.role,
+                    "subject": Fingerprint::of(&e.subject).to_hex(),
+                    "issuer": Fingerprint::of(&e.issuer).to_hex(),
+                }))
+                .collect::<Vec<_>>()),
+            Err(e) => json!({ "error": e.to_string() }),
+        },
+        "log_inclusion": match header.header.log_inclusion() {
+            Ok(proof) => json!(proof.map(|p| json!({ "leaf_index": p.leaf_index, "tree_size": p.tree_size, "path_length": p.audit_path.len() }))),
+            Err(e) => json!({ "error": e.to_string() }),
+        },
+        "image_chunks": match header.header.image_chunks() {
+            Ok(table) => json!(table.map(|t| json!({ "chunk_size": t.chunk_size, "chunks": t.hashes.len(), "root": hex::encode(t.root()) }))),
+            Err(e) => json!({ "error": e.to_string() }),
+        },
+        "trust_hint": match header.header.trust_hint() {
+            Ok(hint) => json!(hint.map(|h| json!({ "domain": h.domain, "selector": h.selector, "record_name": h.record_name() }))),
+            Err(e) => json!({ "error": e.to_string() }),
+        },
+        "signature_length": header.signature.len(),
+        "payload_size": header.image_len,
+        "media_type": media_type::from_metadata(&header.metadata),
+        "file_size": size,
+        "size_consistent": header.header_len + header.image_len == size,
+    })
+}
+
+/// The metadata parsed as JSON where it is JSON. A reference to external
+/// metadata is shown as such unless `resolve` asks for the document.
+fn container_metadata(header: &format::ContainerHeader, resolve: bool) -> Value {
+    let parse = |metadata: &str| serde_json::from_str::<Value>(metadata).unwrap_or_else(|_| json!(metadata));
+    let Some(digest) = format::external_metadata_digest(&header.metadata) else {
+        return parse(&header.metadata);
+    };
+    if !resolve {
+        return json!({
+            "external": true,
+            "sha256": hex::encode(digest),
+            "size": header.header.external_metadata().map(<[u8]>::len),
+        });
+    }
+    match header.header.resolve_metadata(&header.metadata) {
+        Ok(document) => parse(&document),
+        Err(e) => json!({ "external": true, "sha256": hex::encode(digest), "error": e.to_string() }),
+    }
+}
+
+/// The `lang` view of structured metadata with labels in several
+/// languages; `None` for other metadata.
+fn language_view(metadata: &str, lang: &str) -> Option<Value> {
+    Metadata::parse_structured(metadata).ok().flatten()?.language_view(lang)
+}
+
+fn detached_summary(sidecar: &DetachedSignature) -> Value {
+    let fingerprint = Fingerprint::of(&sidecar.public_key);
+    json!({
+        "kind": "detached_signature",
+        "key_fingerprint": fingerprint.to_hex(),
+        "key_words": fingerprint.to_words(),
+        "metadata": serde_json::from_str::<Value>(&sidecar.metadata).unwrap_or_else(|_| json!(sidecar.metadata)),
+        "signature_length": sidecar.signature.len(),
+        "original_sha256": hex::encode(sidecar.image_sha256),
+        "original_size": sidecar.image_len,
+        "media_type": media_type::from_metadata(&sidecar.metadata),
+    })
+}
+
+fn manifest_summary(manifest: &PayloadManifest) -> Value {
+    json!({
+        "kind": "payload_manifest",
+        "file_name": manifest.payload.file_name,
+        "key_fingerprint": manifest.key.fingerprint,
+        "key_id": manifest.key.key_id,
+        "metadata": serde_json::from_str::<Value>(&manifest.metadata).unwrap_or_else(|_| json!(manifest.metadata)),
+        "signature_scheme": manifest.signature.scheme,
+        "original_sha256": manifest.payload.sha256,
+        "original_size": manifest.payload.size,
+        "media_type": manifest.payload.media_type,
+    })
+}
+
+fn print_tree(value: &Value, depth: usize) {
+    let indent = "  ".repeat(depth);
+    match value {
+        Value::Object(map) => {
+            for (name, v) in map {
+                match v {
+                    Value::Object(_) | Value::Array(_) => {
+                        println!("{}{}:", indent, name);
+                        print_tree(v, depth + 1);
+                    }
+                    _ => println!("{}{}: {}", indent, name, scalar(v)),
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                match item {
+                    Value::Object(_) | Value::Array(_) => {
+                        println!("{}-", indent);
+                        print_tree(item, depth + 1);
+                    }
+                    _ => println!("{}- {}", indent, scalar(item)),
+                }
+            }
+        }
+        other => println!("{}{}", indent, scalar(other)),
+    }
+}
+
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => "-".into(),
+        other => other.to_string(),
+    }
+}
@@ -0,0 +1,160 @@
+// aegis-sealer-service/src/capabilities.rs
+
+// Capability discovery for SDKs. The document is built once at startup from
+// the compiled-in features and the runtime configuration, and served as-is
+// from GET /capabilities.
+
+use crate::{admission::Admission, auth::{Access, AuthPolicy}, events, quota::Quotas, AppState};
+use aegis_core::{crypto, format, http_sig};
+use serde_json::{json, Value};
+
+/// Endpoints this build serves, as (method, path).
+pub fn endpoints() -> Vec<(&'static str, &'static str)> {
+    let mut endpoints = vec![
+        ("GET", "/capabilities"),
+        ("GET", "/openapi.json"),
+        ("GET", "/docs"),
+        ("GET", "/keys"),
+        ("GET", "/keys/dns"),
+        ("GET", "/healthz"),
+        ("GET", "/readyz"),
+        ("POST", "/seal"),
+        ("POST", "/seal/batch"),
+        ("GET", "/feed/json"),
+        ("GET", "/feed/atom"),
+        ("POST", "/ingest/dam"),
+        ("POST", "/export/bundle"),
+        ("GET", "/sealed/{name}"),
+        ("GET", "/sealed/{name}/notifications"),
+        ("PUT", "/sealed/{name}/notifications"),
+        ("DELETE", "/sealed/{name}/notifications"),
+        ("GET", "/metrics"),
+        ("GET", "/admin/wal"),
+        ("GET", "/audit"),
+        ("GET", "/log/proof/{hash}"),
+        ("GET", "/log/consistency"),
+        ("GET", "/rollups/{date}"),
+        ("GET", "/rollups/{date}/proof/{hash}"),
+        ("GET", "/events/schema"),
+        ("GET", "/events/schema/{version}"),
+        ("GET", "/admin/captures"),
+        ("GET", "/admin/captures/{request_id}"),
+        ("POST", "/admin/wal/{id}/resolve"),
+        ("POST", "/admin/rollups"),
+    ];
+    if cfg!(feature = "verifier") {
+        endpoints.extend([("POST", "/verify"), ("POST", "/reseal")]);
+    }
+    if cfg!(feature = "alloc-stats") {
+        endpoints.push(("GET", "/debug/memory"));
+    }
+    endpoints
+}
+
+pub fn document(state: &AppState, admission: &Admission, quotas: &Quotas, auth: &AuthPolicy) -> Value {
+    let endpoints: Vec<Value> = endpoints()
+        .into_iter()
+        .map(|(method, path)| {
+            json!({
+                "method": method,
+                "path": path,
+                "requires_api_key": auth.access_for(path) == Access::Authenticated,
+            })
+        })
+        .collect();
+    #[cfg(feature = "verifier")]
+    let remote_verify = !state.config.verify_url_allow.is_empty() && state.config.verify_sla.is_none();
+    #[cfg(feature = "verifier")]
+    let verify_sla = state.config.verify_sla.as_ref().map(|sla| {
+        json!({
+            "budget_ms": sla.budget.as_millis() as u64,
+            "max_bytes": sla.max_bytes,
+        })
+    });
+    #[cfg(not(feature = "verifier"))]
+    let (remote_verify, verify_sla) = (false, None::<serde_json::Value>);
+    // Built apart from the rest of the document, which would otherwise take
+    // `json!` past its recursion limit.
+    let features = json!({
+        "verify": cfg!(feature = "verifier"),
+        "reseal": cfg!(feature = "verifier"),
+        "verify_explain": cfg!(feature = "verifier"),
+        "verification_levels": cfg!(feature = "verifier").then_some(["quick", "standard", "forensic"]),
+        "remote_verify": remote_verify,
+        "offline_bundles": true,
+        "detached_signatures": true,
+        "payload_manifests": aegis_core::manifest::MANIFEST_FORMAT,
+        "intoto_attestations": aegis_core::intoto::PREDICATE_TYPE,
+        "xmp_copies": true,
+        "c2pa_copies": true,
+        "extensions": true,
+        "cosignatures": true,
+        "certificate_chains": !state.config.cert_chain.is_empty(),
+        "endorsements": !state.config.endorsements.is_empty(),
+        "structured_metadata": true,
+        "multilingual_metadata": true,
+        "embedded_metadata_import": true,
+        "exif_extraction": true,
+        "text_documents": ["none", "lf", "crlf"],
+        "block_compression": ["metadata", "image"],
+        "signed_feeds": true,
+        "audit_log": state.audit_log.is_enabled(),
+        "transparency_log": state.transparency.is_enabled(),
+        "daily_rollups": state.rollups.is_enabled(),
+        "failure_capture": state.captures.is_enabled(),
+        "delegation_tokens": auth.accepts_delegation(),
+        "response_signatures": state.config.sign_responses.then_some(http_sig::ALGORITHM),
+        "dam_ingest": state.config.dam.is_enabled(),
+        "verification_notifications": state.notifications.is_enabled(),
+        "webhooks": state.config.webhooks.as_ref().map(|webhooks| json!({
+            "urls": webhooks.url_count(),
+            "signed": webhooks.is_signed(),
+            "format": webhooks.format().content_type(),
+        })),
+        "event_schemas": {
+            "current": events::CURRENT_SCHEMA,
+            "versions": events::SCHEMA_VERSIONS,
+            "encodings": [events::Encoding::Json.name(), events::Encoding::Protobuf.name()],
+        },
+        "async_jobs": false,
+        "batch": true,
+        "encryption": false,
+        "c2pa_export": false,
+    });
+    json!({
+        "service": "aegis-sealer",
+        "version": env!("CARGO_PKG_VERSION"),
+        "format": {
+            "magic": String::from_utf8_lossy(format::MAGIC_PREFIX),
+            "version": char::from(format::CURRENT_VERSION).to_string(),
+            "readable_versions": format::SUPPORTED_VERSIONS.iter().map(|v| char::from(*v).to_string()).collect::<Vec<_>>(),
+            "max_block_size": format::MAX_BLOCK_SIZE,
+            "signature_algorithm": crypto::SIGNATURE_ALGORITHM,
+            "signature_schemes": crypto::SignatureScheme::ALL.iter().map(|s| s.name()).collect::<Vec<_>>(),
+            "signature_contexts": crypto::SignatureContext::ALL
+                .iter()
+                .map(|c| (c.name().to_string(), json!(String::from_utf8_lossy(c.prefix()))))
+                .collect::<serde_json::Map<_, _>>(),
+            "digest_algorithm": crypto::DIGEST_ALGORITHM,
+        },
+        "signer": state.signer.kind(),
+        "storage": state.storage.kind(),
+        "timestamping": state.tsa.as_ref().map(|tsa| json!({
+            "tsa_url": tsa.url(),
+            "required": tsa.required(),
+        })),
+        "submission_provenance": state.config.provenance.enabled(),
+        "verify_sla": verify_sla,
+        "max_upload_bytes": admission.max_upload_bytes(),
+        "seal_quotas": quotas.describe(),
+        "image_formats": state.config.intake.allowed().collect::<Vec<_>>(),
+        "payload_types": state.config.intake.payload_types(),
+        "trust_hint": state.config.trust_hint.as_ref().map(|hint| json!({
+            "domain": hint.domain,
+            "selector": hint.selector,
+            "record_name": hint.record_name(),
+        })),
+        "endpoints": endpoints,
+        "features": features,
+    })
+}
@@ -0,0 +1,425 @@
+// aegis-sealer-service/src/capture.rs
+
+// Capture of failed sealing requests, so support can reproduce "my seal
+// failed" reports. With `AEGIS_CAPTURE_FAILURES=true`, every request to a
+// sealing endpoint that ends in a 4xx or 5xx leaves a bundle behind, keyed
+// by its request ID (the `x-request-id` the client got back; see
+// `audit_log`):
+//
+// - the method, path and query, and the headers, with credentials and
+//   anything named like a key, token or secret redacted;
+// - the body's size and, for multipart uploads, each part's name, file name,
+//   content type and size, with a sample of its first bytes (text up to 256
+//   bytes, anything else as hex of its first 32);
+// - the status, the error message, and the chain of causes behind it;
+// - `replay`, a curl command rebuilding the request from the samples and
+//   placeholder files of the recorded sizes.
+//
+// Bodies are streamed through a recorder rather than buffered, so capture
+// costs a few kilobytes per request whatever the upload size; only what the
+// service read is recorded, so a request refused before its body was read
+// has a body of size 0 (its `content-length` header still says how big it
+// was). The newest `AEGIS_CAPTURE_LIMIT` bundles (default 100) are kept in
+// memory; GET /admin/captures lists them and GET
+// /admin/captures/{request_id} returns one.
+
+use crate::{audit_log::RequestId, AppError, AppState};
+use aegis_core::time;
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::{Path, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::Response,
+    Json,
+};
+use futures_util::StreamExt;
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tracing::info;
+
+/// Bytes kept from the start of each part: its headers and a sample.
+const PART_HEAD: usize = 2048;
+const TEXT_SAMPLE: usize = 256;
+const BINARY_SAMPLE: usize = 32;
+/// Longest error message kept.
+const MAX_ERROR: usize = 4096;
+/// Headers whose values are never captured, besides any whose name
+/// contains one of `REDACTED_WORDS`.
+const REDACTED_HEADERS: [&str; 4] = ["authorization", "proxy-authorization", "cookie", "set-cookie"];
+const REDACTED_WORDS: [&str; 5] = ["key", "token", "secret", "signature", "password"];
+
+tokio::task_local! {
+    static ERRORS: RefCell<Vec<String>>;
+}
+
+/// Notes the causes of an error turned into a response, for the capture of
+/// the request being handled, if it is captured.
+pub fn note_error(error: &anyhow::Error) {
+    let _ = ERRORS.try_with(|errors| errors.borrow_mut().extend(error.chain().map(|cause| cause.to_string())));
+}
+
+pub struct Captures {
+    enabled: bool,
+    limit: usize,
+    bundles: Mutex<VecDeque<(String, Value)>>,
+}
+
+impl Captures {
+    /// Reads `AEGIS_CAPTURE_FAILURES` and `AEGIS_CAPTURE_LIMIT`.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let enabled = matches!(env::var("AEGIS_CAPTURE_FAILURES").as_deref(), Ok("true" | "1"));
+        let limit = match env::var("AEGIS_CAPTURE_LIMIT") {
+            Ok(v) => v
+                .trim()
+                .parse::<usize>()
+                .ok()
+                .filter(|n| *n > 0)
+                .ok_or_else(|| anyhow::anyhow!("AEGIS_CAPTURE_LIMIT must be a positive number, got '{}'", v))?,
+            Err(_) => 100,
+        };
+        if enabled {
+            info!(limit, "Capturing failed sealing requests.");
+        }
+        Ok(Captures { enabled, limit, bundles: Mutex::new(VecDeque::new()) })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn store(&self, request_id: String, bundle: Value) {
+        let mut bundles = self.bundles.lock().unwrap();
+        bundles.retain(|(id, _)| *id != request_id);
+        if bundles.len() == self.limit {
+            bundles.pop_front();
+        }
+        bundles.push_back((request_id, bundle));
+    }
+}
+
+// One part of a multipart body as it streams past: where it starts in the
+// body, where it ends once the next delimiter is seen, and its first bytes.
+struct RawPart {
+    start: u64,
+    end: Option<u64>,
+    head: Vec<u8>,
+}
+
+/// Watches a body stream go by, keeping its size and the start of each
+/// multipart part. Offsets are into the body with `\r\n` put in front, so
+/// that every part, the first included, follows `\r\n--<boundary>`.
+struct Recorder {
+    delimiter: Option<Vec<u8>>,
+    // Bytes of the (prefixed) body seen so far.
+    seen: u64,
+    // The last bytes seen, in case a delimiter straddles two chunks.
+    carry: Vec<u8>,
+    // The start of a body that is not multipart.
+    head: Vec<u8>,
+    parts: Vec<RawPart>,
+}
+
+impl Recorder {
+    fn new(headers: &HeaderMap) -> Self {
+        let boundary = headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .filter(|v| v.trim_start().to_ascii_lowercase().starts_with("multipart/"))
+            .and_then(|v| v.split(';').find_map(|p| p.trim().strip_prefix("boundary=")))
+            .map(|b| b.trim_matches('"'));
+        Recorder {
+            delimiter: boundary.map(|b| format!("\r\n--{}", b).into_bytes()),
+            seen: 0,
+            carry: Vec::new(),
+            head: Vec::new(),
+            parts: Vec::new(),
+        }
+    }
+
+    fn feed(&mut self, chunk: &[u8]) {
+        let Some(delimiter) = &self.delimiter else {
+            let room = TEXT_SAMPLE.saturating_sub(self.head.len());
+            self.head.extend_from_slice(&chunk[..room.min(chunk.len())]);
+            self.seen += chunk.len() as u64;
+            return;
+        };
+        let mut new = Vec::with_capacity(chunk.len() + 2);
+        if self.seen == 0 {
+            new.extend_from_slice(b"\r\n");
+        }
+        new.extend_from_slice(chunk);
+        let base = self.seen - self.carry.len() as u64;
+        let mut buf = std::mem::take(&mut self.carry);
+        buf.extend_from_slice(&new);
+        let end = self.seen + new.len() as u64;
+
+        let mut cursor = self.seen;
+        let len = delimiter.len();
+        let matches: Vec<u64> = buf
+            .windows(len)
+            .enumerate()
+            .filter(|(_, w)| w == &delimiter.as_slice())
+            .map(|(i, _)| base + i as u64)
+            .collect();
+        for at in matches {
+            if at >= cursor {
+                self.keep(&buf, base, cursor, at);
+            }
+            if let Some(open) = self.parts.last_mut().filter(|p| p.end.is_none()) {
+                // Bytes of a delimiter that began in the previous chunk may
+                // have been kept as content.
+                open.head.truncate(at.saturating_sub(open.start) as usize);
+                open.end = Some(at);
+            }
+            self.parts.push(RawPart { start: at + len as u64, end: None, head: Vec::new() });
+            cursor = cursor.max(at + len as u64);
+        }
+        self.keep(&buf, base, cursor, end);
+        self.seen = end;
+        self.carry = buf[buf.len().saturating_sub(len - 1)..].to_vec();
+    }
+
+    // Adds the bytes from `from` to `to` to the open part's head.
+    fn keep(&mut self, buf: &[u8], base: u64, from: u64, to: u64) {
+        let Some(open) = self.parts.last_mut().filter(|p| p.end.is_none()) else {
+            return;
+        };
+        let room = PART_HEAD.saturating_sub(open.head.len());
+        let bytes = &buf[(from - base) as usize..(to - base) as usize];
+        open.head.extend_from_slice(&bytes[..room.min(bytes.len())]);
+    }
+
+    fn size(&self) -> u64 {
+        match self.delimiter {
+            Some(_) => self.seen.saturating_sub(2),
+            None => self.seen,
+        }
+    }
+
+    fn summary(&self) -> Vec<Value> {
+        if self.delimiter.is_none() {
+            return if self.seen == 0 {
+                Vec::new()
+            } else {
+                vec![json!({ "name": null, "size": self.seen, "sample": sample(&self.head, self.seen, true) })]
+            };
+        }
+        self.parts
+            .iter()
+            // The part after the closing delimiter `--` is the epilogue.
+            .filter(|p| !p.head.starts_with(b"--"))
+            .map(|part| {
+                let Some(split) = part.head.windows(4).position(|w| w == b"\r\n\r\n") else {
+                    return json!({ "error": "part headers not captured" });
+                };
+                let headers = String::from_utf8_lossy(&part.head[..split]);
+                let disposition = header_value(&headers, "content-disposition").unwrap_or_default();
+                let content = &part.head[split + 4..];
+                let size = part.end.map(|end| (end - part.start).saturating_sub(split as u64 + 4));
+                let filename = param(&disposition, "filename");
+                json!({
+                    "name": param(&disposition, "name"),
+                    "filename": filename,
+                    "content_type": header_value(&headers, "content-type"),
+                    "size": size,
+                    "sample": sample(content, size.unwrap_or(u64::MAX), filename.is_none()),
+                })
+            })
+            .collect()
+    }
+}
+
+fn header_value(headers: &str, name: &str) -> Option<String> {
+    headers
+        .split("\r\n")
+        .filter_map(|line| line.split_once(':'))
+        .find(|(n, _)| n.trim().eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.trim().to_string())
+}
+
+fn param(disposition: &str, name: &str) -> Option<String> {
+    disposition
+        .split(';')
+        .filter_map(|p| p.trim().split_once('='))
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.trim_matches('"').to_string())
+}
+
+// The start of a part's content: text if it reads as such and may be text,
+// else hex. `truncated` says whether it is shorter than the part.
+fn sample(content: &[u8], size: u64, may_be_text: bool) -> Value {
+    match std::str::from_utf8(&content[..content.len().min(TEXT_SAMPLE)]) {
+        Ok(text) if may_be_text => json!({
+            "text": text,
+            "truncated": (text.len() as u64) < size,
+        }),
+        _ => {
+            let bytes = &content[..content.len().min(BINARY_SAMPLE)];
+            json!({ "hex": hex::encode(bytes), "truncated": (bytes.len() as u64) < size })
+        }
+    }
+}
+
+fn redacted_headers(headers: &HeaderMap) -> Value {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let secret = REDACTED_HEADERS.contains(&name.as_str()) || REDACTED_WORDS.iter().any(|w| name.as_str().contains(w));
+            let value = if secret {
+                json!("[redacted]")
+            } else {
+                json!(String::from_utf8_lossy(value.as_bytes()))
+            };
+            (name.to_string(), value)
+        })
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+/// A curl command sending a request like the captured one: text parts
+/// captured whole are inlined, others read from placeholder files named
+/// after their size.
+fn replay(method: &str, path_and_query: &str, headers: &Value, parts: &[Value], multipart: bool) -> String {
+    let quote = |s: &str| format!("'{}'", s.replace('\'', "'\\''"));
+    let mut command = format!("curl -X {} \"$AEGIS_URL\"{}", method, quote(path_and_query));
+    for (name, value) in headers.as_object().into_iter().flatten() {
+        if matches!(name.as_str(), "host" | "content-length" | "content-type" | "x-request-id") && multipart {
+            continue;
+        }
+        match value.as_str() {
+            Some("[redacted]") if name == "x-api-key" => command.push_str(" -H \"x-api-key: $AEGIS_API_KEY\""),
+            Some("[redacted]") if name == "authorization" => {
+                command.push_str(" -H \"Authorization: Bearer $AEGIS_API_KEY\"")
+            }
+            Some("[redacted]") | None => {}
+            Some(value) if !matches!(name.as_str(), "host" | "content-length" | "x-request-id") => {
+                command.push_str(&format!(" -H {}", quote(&format!("{}: {}", name, value))))
+            }
+            Some(_) => {}
+        }
+    }
+    for part in parts {
+        let size = part["size"].as_u64().unwrap_or(0);
+        let inline = part["sample"]["text"].as_str().filter(|_| part["sample"]["truncated"] == json!(false));
+        match (multipart, part["name"].as_str(), inline) {
+            (true, Some(name), Some(text)) if part["filename"].is_null() => {
+                command.push_str(&format!(" -F {}", quote(&format!("{}={}", name, text))))
+            }
+            (true, Some(name), _) => {
+                let file = part["filename"].as_str().map_or_else(|| format!("{}-{}.bin", name, size), str::to_string);
+                let kind = part["content_type"].as_str().map(|t| format!(";type={}", t)).unwrap_or_default();
+                command.push_str(&format!(" -F {}", quote(&format!("{}=@{}{}", name, file, kind))));
+            }
+            (false, _, Some(text)) => command.push_str(&format!(" --data-binary {}", quote(text))),
+            (false, _, None) => command.push_str(&format!(" --data-binary @body-{}.bin", size)),
+            _ => {}
+        }
+    }
+    command
+}
+
+/// Middleware recording sealing requests that fail.
+pub async fn record(State(captures): State<Arc<Captures>>, request: Request, next: Next) -> Response {
+    if !captures.enabled {
+        return next.run(request).await;
+    }
+    let request_id = request.extensions().get::<RequestId>().map(|id| id.0.clone());
+    let Some(request_id) = request_id else {
+        return next.run(request).await;
+    };
+    let method = request.method().to_string();
+    let path_and_query = request.uri().path_and_query().map_or("/", |p| p.as_str()).to_string();
+    let headers = request.headers().clone();
+
+    let recorder = Arc::new(Mutex::new(Recorder::new(&headers)));
+    let (parts, body) = request.into_parts();
+    let tap = recorder.clone();
+    let body = Body::from_stream(body.into_data_stream().map(move |chunk| {
+        if let Ok(bytes) = &chunk {
+            tap.lock().unwrap().feed(bytes);
+        }
+        chunk
+    }));
+    let request = Request::from_parts(parts, body);
+    let (response, errors) = ERRORS
+        .scope(RefCell::new(Vec::new()), async {
+            let response = next.run(request).await;
+            (response, ERRORS.with(|errors| errors.take()))
+        })
+        .await;
+    let status = response.status();
+    if !status.is_client_error() && !status.is_server_error() {
+        return response;
+    }
+
+    // Error responses are short plain text.
+    let (response_parts, response_body) = response.into_parts();
+    let message = to_bytes(response_body, MAX_ERROR * 4).await.unwrap_or_else(|_| Bytes::new());
+    let response = Response::from_parts(response_parts, Body::from(message.clone()));
+    let mut message = String::from_utf8_lossy(&message).into_owned();
+    message.truncate(message.floor_char_boundary(MAX_ERROR));
+
+    let recorder = recorder.lock().unwrap();
+    let body_parts = recorder.summary();
+    let multipart = recorder.delimiter.is_some();
+    let headers = redacted_headers(&headers);
+    let mut chain = errors;
+    if chain.is_empty() {
+        chain.push(message.clone());
+    }
+    let bundle = json!({
+        "request_id": request_id,
+        "captured_at": time::rfc3339(SystemTime::now()),
+        "method": method,
+        "path": path_and_query,
+        "headers": headers,
+        "body": {
+            "size": recorder.size(),
+            "multipart": multipart,
+            "parts": body_parts,
+        },
+        "response": {
+            "status": status.as_u16(),
+            "message": message,
+        },
+        "error_chain": chain,
+        "replay": replay(&method, &path_and_query, &headers, &body_parts, multipart),
+    });
+    info!(request_id = %request_id, status = status.as_u16(), "Captured failed request.");
+    captures.store(request_id, bundle);
+    response
+}
+
+/// GET /admin/captures
+pub async fn list_handler(State(state): State<AppState>) -> Json<Value> {
+    let bundles = state.captures.bundles.lock().unwrap();
+    let captures: Vec<Value> = bundles
+        .iter()
+        .rev()
+        .map(|(_, bundle)| {
+            json!({
+                "request_id": bundle["request_id"],
+                "captured_at": bundle["captured_at"],
+                "method": bundle["method"],
+                "path": bundle["path"],
+                "status": bundle["response"]["status"],
+            })
+        })
+        .collect();
+    Json(json!({ "enabled": state.captures.enabled, "captures": captures }))
+}
+
+/// GET /admin/captures/{request_id}
+pub async fn get_handler(State(state): State<AppState>, Path(request_id): Path<String>) -> Result<Json<Value>, AppError> {
+    let bundles = state.captures.bundles.lock().unwrap();
+    bundles
+        .iter()
+        .find(|(id, _)| *id == request_id)
+        .map(|(_, bundle)| Json(bundle.clone()))
+        .ok_or_else(|| AppError(StatusCode::NOT_FOUND, format!("No capture for request '{}'.", request_id)))
+}
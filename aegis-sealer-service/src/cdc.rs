@@ -0,0 +1,77 @@
+// aegis-sealer-service/src/cdc.rs
+
+// FastCDC content-defined chunking (Xia et al., USENIX ATC 2016) with
+// normalized chunking. Boundaries depend only on nearby content, so an edit
+// to one region of an asset only changes the chunks around it and the rest
+// deduplicate against earlier versions.
+
+pub const MIN_CHUNK: usize = 16 * 1024;
+pub const AVG_CHUNK: usize = 64 * 1024;
+pub const MAX_CHUNK: usize = 256 * 1024;
+
+const AVG_BITS: u32 = AVG_CHUNK.trailing_zeros();
+// Normalization level 2: a harder mask before the average size and an easier
+// one after it pull chunk sizes towards `AVG_CHUNK`. Masks select the high
+// bits because those depend on the widest window of the gear hash.
+const MASK_S: u64 = mask(AVG_BITS + 2);
+const MASK_L: u64 = mask(AVG_BITS - 2);
+
+const fn mask(bits: u32) -> u64 {
+    ((1u64 << bits) - 1) << (64 - bits)
+}
+
+// The gear table is derived from a fixed seed with SplitMix64. Changing it
+// moves every chunk boundary, so stored chunks would no longer deduplicate.
+const GEAR: [u64; 256] = {
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0x6165_6769_735f_6364; // "aegis_cd"
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+};
+
+/// Length of the first chunk of `data`.
+fn cut_point(data: &[u8]) -> usize {
+    let len = data.len().min(MAX_CHUNK);
+    if len <= MIN_CHUNK {
+        return len;
+    }
+    let normal = AVG_CHUNK.min(len);
+    let mut hash = 0u64;
+    let mut i = MIN_CHUNK;
+    while i < normal {
+        hash = (hash << 1).wrapping_add(GEAR[data[i] as usize]);
+        if hash & MASK_S == 0 {
+            return i;
+        }
+        i += 1;
+    }
+    while i < len {
+        hash = (hash << 1).wrapping_add(GEAR[data[i] as usize]);
+        if hash & MASK_L == 0 {
+            return i;
+        }
+        i += 1;
+    }
+    len
+}
+
+/// Splits `data` into content-defined chunks.
+pub fn chunks(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut rest = data;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let (chunk, tail) = rest.split_at(cut_point(rest));
+        rest = tail;
+        Some(chunk)
+    })
+}
@@ -0,0 +1,255 @@
+// aegis-sealer-service/src/config.rs
+
+// Settings the handlers need, read from the environment once at startup and
+// shared through `AppState`. Handlers take them from the state rather than
+// the environment, so a request never pays for an env lookup and an embedder
+// can build the state with settings of its own.
+
+use crate::{
+    feed::Redaction, health::OnFailure, ingest::DamConfig, intake::IntakePolicy, provenance::ProvenanceConfig,
+    webhooks::Webhooks,
+};
+use aegis_core::{
+    dns_trust::TrustHint,
+    endorsement::{self, Endorsement},
+    format::FormatHeader,
+    x509,
+};
+use anyhow::Context;
+use axum::http::HeaderValue;
+#[cfg(feature = "verifier")]
+use {aegis_core::timestamp, crate::s3::S3Config, crate::sla::Sla};
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// `AEGIS_MAX_BODY` unless set: 100 MiB.
+pub const DEFAULT_MAX_BODY: usize = 100 * 1024 * 1024;
+#[cfg(feature = "verifier")]
+pub const DEFAULT_MAX_INFLATE: usize = 64 * 1024 * 1024;
+pub const DEFAULT_ROOT_REDIRECT: &str = "https://www.google.com";
+
+pub struct Config {
+    /// Origins allowed by CORS, from `AEGIS_CORS_ORIGINS`; `None` for any
+    /// (`*`, the default).
+    pub cors_origins: Option<Vec<HeaderValue>>,
+    /// Largest request body, from `AEGIS_MAX_BODY`.
+    pub max_body: usize,
+    /// Where GET / redirects, from `AEGIS_ROOT_REDIRECT`.
+    pub root_redirect: String,
+    /// `AEGIS_PUBLIC_URL` without a trailing slash; prefixes links in feeds.
+    pub public_url: String,
+    pub feed_redaction: Redaction,
+    pub dam: DamConfig,
+    /// Where seal events are posted, if `AEGIS_WEBHOOK_URLS` is set (see
+    /// `webhooks`).
+    pub webhooks: Option<Arc<Webhooks>>,
+    /// Where uploads are spooled: `AEGIS_SPOOL_DIR`, default the system
+    /// temporary directory.
+    pub spool_dir: PathBuf,
+    /// `AEGIS_SIGN_RESPONSES`; on unless set to `false` or `0`.
+    pub sign_responses: bool,
+    pub provenance: ProvenanceConfig,
+    /// Which uploads are sealed, by sniffed format (see `intake`).
+    pub intake: IntakePolicy,
+    /// `AEGIS_REQUIRE_STRUCTURED_METADATA`: reject free-form metadata, so
+    /// every seal carries a `metadata::Metadata` document.
+    pub require_structured_metadata: bool,
+    /// `Cache-Control` for documents such as /capabilities, from
+    /// `AEGIS_CACHE_CONTROL` (see `static_docs`).
+    pub cache_control: String,
+    /// X.509 chain for the signing key, leaf first, from the PEM file
+    /// `AEGIS_CERT_CHAIN` (see `aegis_core::x509`).
+    pub cert_chain: Vec<x509::Certificate>,
+    /// Where the signing keys are published in DNS, from `AEGIS_TRUST_HINT`
+    /// (`DOMAIN` or `DOMAIN:SELECTOR`); named in every container sealed.
+    pub trust_hint: Option<TrustHint>,
+    /// Endorsements of the signing keys, and of the keys that endorsed them,
+    /// from the JSON file `AEGIS_ENDORSEMENTS` (see `aegis_core::endorsement`).
+    /// They go into exported trust bundles, and /verify uses them to find
+    /// chains for the keys they endorse.
+    pub endorsements: Vec<Endorsement>,
+    /// `AEGIS_EMBED_ENDORSEMENTS`: also put the signing key's endorsements
+    /// in every container sealed.
+    pub embed_endorsements: bool,
+    /// Per-dependency failure policy overrides from `AEGIS_DEPENDENCY_POLICY`
+    /// (see `health`).
+    pub dependency_policy: HashMap<String, OnFailure>,
+    /// `AEGIS_STARTUP_CHECKS`; on unless set to `false` or `0`.
+    pub startup_checks: bool,
+    /// CA certificates /verify checks certificate chains against, from the
+    /// PEM bundle `AEGIS_TRUST_ANCHORS`.
+    #[cfg(feature = "verifier")]
+    pub trust_anchors: x509::TrustAnchors,
+    /// TSAs whose timestamps /verify accepts: key fingerprints pinned in
+    /// `AEGIS_TRUSTED_TSAS` (comma-separated hex) and CAs in the PEM bundle
+    /// `AEGIS_TSA_ANCHORS`. Other timestamps are reported unverified.
+    #[cfg(feature = "verifier")]
+    pub tsa_trust: timestamp::TsaTrust,
+    /// SEC1 root keys from `AEGIS_ENDORSEMENT_ROOTS` (comma-separated hex).
+    /// When set, /verify requires the sealing key to be a root or endorsed
+    /// by one, and judges other keys untrusted.
+    #[cfg(feature = "verifier")]
+    pub endorsement_roots: Vec<Vec<u8>>,
+    /// URL prefixes /verify may fetch from, from the comma-separated
+    /// `AEGIS_VERIFY_URL_ALLOW`.
+    #[cfg(feature = "verifier")]
+    pub verify_url_allow: Vec<String>,
+    /// The S3 endpoint for `s3://` URLs at /verify, if `AEGIS_S3_ENDPOINT`
+    /// is set.
+    #[cfg(feature = "verifier")]
+    pub s3: Option<Arc<S3Config>>,
+    /// Verification SLA mode, if `AEGIS_VERIFY_SLA` is set (see `sla`).
+    #[cfg(feature = "verifier")]
+    pub verify_sla: Option<Sla>,
+    /// Most a compressed block of a container being verified may
+    /// decompress to, from `AEGIS_MAX_INFLATE`. Anonymous callers are held
+    /// to the smaller of this and `AEGIS_ANON_MAX_INFLATE`.
+    #[cfg(feature = "verifier")]
+    pub max_inflate: usize,
+}
+
+impl Config {
+    pub fn from_env() -> anyhow::Result<Self> {
+        Ok(Config {
+            cors_origins: match env::var("AEGIS_CORS_ORIGINS").as_deref().map(str::trim) {
+                Err(_) | Ok("*") => None,
+                Ok(origins) => Some(
+                    origins
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|origin| !origin.is_empty())
+                        .map(|origin| {
+                            HeaderValue::try_from(origin)
+                                .with_context(|| format!("AEGIS_CORS_ORIGINS: '{}' is not a valid origin", origin))
+                        })
+                        .collect::<anyhow::Result<_>>()?,
+                ),
+            },
+            max_body: match env::var("AEGIS_MAX_BODY") {
+                Ok(value) => value.trim().parse().context("AEGIS_MAX_BODY")?,
+                Err(_) => DEFAULT_MAX_BODY,
+            },
+            root_redirect: env::var("AEGIS_ROOT_REDIRECT").unwrap_or_else(|_| DEFAULT_ROOT_REDIRECT.to_string()),
+            public_url: env::var("AEGIS_PUBLIC_URL").unwrap_or_default().trim_end_matches('/').to_string(),
+            feed_redaction: Redaction::from_env(),
+            dam: DamConfig::from_env(),
+            webhooks: Webhooks::from_env()?.map(Arc::new),
+            spool_dir: env::var("AEGIS_SPOOL_DIR").map(PathBuf::from).unwrap_or_else(|_| env::temp_dir()),
+            sign_responses: !matches!(env::var("AEGIS_SIGN_RESPONSES").as_deref(), Ok("false" | "0")),
+            provenance: ProvenanceConfig::from_env()?,
+            intake: IntakePolicy::from_env()?,
+            require_structured_metadata: matches!(
+                env::var("AEGIS_REQUIRE_STRUCTURED_METADATA").as_deref(),
+                Ok("true" | "1")
+            ),
+            cache_control: env::var("AEGIS_CACHE_CONTROL")
+                .unwrap_or_else(|_| crate::static_docs::DEFAULT_CACHE_CONTROL.to_string()),
+            cert_chain: match pem_file("AEGIS_CERT_CHAIN")? {
+                Some(text) => x509::certificates_from_pem(&text).context("AEGIS_CERT_CHAIN")?,
+                None => Vec::new(),
+            },
+            trust_hint: match env::var("AEGIS_TRUST_HINT") {
+                Ok(spec) => Some(TrustHint::parse(&spec).context("AEGIS_TRUST_HINT")?),
+                Err(_) => None,
+            },
+            endorsements: match env::var("AEGIS_ENDORSEMENTS") {
+                Ok(path) => {
+                    let text = std::fs::read_to_string(&path)
+                        .with_context(|| format!("AEGIS_ENDORSEMENTS: reading {}", path))?;
+                    endorsement::from_json(&text).context("AEGIS_ENDORSEMENTS")?
+                }
+                Err(_) => Vec::new(),
+            },
+            embed_endorsements: matches!(env::var("AEGIS_EMBED_ENDORSEMENTS").as_deref(), Ok("true" | "1")),
+            dependency_policy: crate::health::parse_policy(&env::var("AEGIS_DEPENDENCY_POLICY").unwrap_or_default())
+                .context("AEGIS_DEPENDENCY_POLICY")?,
+            startup_checks: !matches!(env::var("AEGIS_STARTUP_CHECKS").as_deref(), Ok("false" | "0")),
+            #[cfg(feature = "verifier")]
+            trust_anchors: match pem_file("AEGIS_TRUST_ANCHORS")? {
+                Some(text) => x509::TrustAnchors::from_pem(&text).context("AEGIS_TRUST_ANCHORS")?,
+                None => x509::TrustAnchors::default(),
+            },
+            #[cfg(feature = "verifier")]
+            tsa_trust: timestamp::TsaTrust {
+                fingerprints: env::var("AEGIS_TRUSTED_TSAS")
+                    .unwrap_or_default()
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|fingerprint| !fingerprint.is_empty())
+                    .map(|fingerprint| {
+                        if fingerprint.len() == 64 && fingerprint.bytes().all(|b| b.is_ascii_hexdigit()) {
+                            Ok(fingerprint.to_ascii_lowercase())
+                        } else {
+                            anyhow::bail!("AEGIS_TRUSTED_TSAS: '{}' is not a hex key fingerprint", fingerprint)
+                        }
+                    })
+                    .collect::<anyhow::Result<_>>()?,
+                anchors: match pem_file("AEGIS_TSA_ANCHORS")? {
+                    Some(text) => x509::TrustAnchors::from_pem(&text).context("AEGIS_TSA_ANCHORS")?,
+                    None => x509::TrustAnchors::default(),
+                },
+            },
+            #[cfg(feature = "verifier")]
+            endorsement_roots: env::var("AEGIS_ENDORSEMENT_ROOTS")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|key| !key.is_empty())
+                .map(|key| {
+                    hex::decode(key)
+                        .ok()
+                        .filter(|sec1| p256::ecdsa::VerifyingKey::from_sec1_bytes(sec1).is_ok())
+                        .with_context(|| format!("AEGIS_ENDORSEMENT_ROOTS: '{}' is not a hex SEC1 P-256 key", key))
+                })
+                .collect::<anyhow::Result<_>>()?,
+            #[cfg(feature = "verifier")]
+            verify_url_allow: env::var("AEGIS_VERIFY_URL_ALLOW")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|prefix| !prefix.is_empty())
+                .map(str::to_string)
+                .collect(),
+            #[cfg(feature = "verifier")]
+            s3: S3Config::from_env().ok().map(Arc::new),
+            #[cfg(feature = "verifier")]
+            verify_sla: Sla::from_env()?,
+            #[cfg(feature = "verifier")]
+            max_inflate: match env::var("AEGIS_MAX_INFLATE") {
+                Ok(value) => value.trim().parse().context("AEGIS_MAX_INFLATE")?,
+                Err(_) => DEFAULT_MAX_INFLATE,
+            },
+        })
+    }
+
+    /// Adds `cert_chain` to a container sealed with `public_key`, if its
+    /// leaf certifies that key; after a keyring rotation it no longer does.
+    /// Also adds the trust hint, if there is one, and with
+    /// `embed_endorsements` the key's endorsements, if it has any.
+    pub fn certify(&self, header: &mut FormatHeader, public_key: &[u8]) {
+        if !self.cert_chain.is_empty() && x509::check_leaf(&self.cert_chain, public_key).is_ok() {
+            header.set_certificate_chain(&self.cert_chain);
+        }
+        if self.embed_endorsements {
+            let endorsements = endorsement::chain_of(public_key, &self.endorsements);
+            if !endorsements.is_empty() {
+                header.set_endorsements(&endorsements);
+            }
+        }
+        if let Some(hint) = &self.trust_hint {
+            header.set_trust_hint(hint);
+        }
+    }
+}
+
+/// The contents of the PEM file named by `var`, if it is set.
+fn pem_file(var: &str) -> anyhow::Result<Option<String>> {
+    match env::var(var) {
+        Ok(path) => std::fs::read_to_string(&path)
+            .with_context(|| format!("{}: reading {}", var, path))
+            .map(Some),
+        Err(_) => Ok(None),
+    }
+}
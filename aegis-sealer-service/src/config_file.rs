@@ -0,0 +1,314 @@
+// aegis-sealer-service/src/config_file.rs
+
+// The service's settings as a TOML file, for deployments that would rather
+// keep them in one reviewed file than in scattered environment variables.
+// `AEGIS_CONFIG` names the file. Each key is a setting's name (see
+// `settings`) in lowercase without `AEGIS_`, and a table's name prefixes
+// the keys in it, so these set `AEGIS_LISTEN`, `AEGIS_CORS_ORIGINS`,
+// `AEGIS_MAX_BODY`, `AEGIS_SIGNER`, `VAULT_ADDR`, `AEGIS_API_KEYS`,
+// `AEGIS_STORAGE`, `AEGIS_STORAGE_BUCKET` and `AEGIS_TSA_URL`:
+//
+//     listen = "0.0.0.0:8443"
+//     cors_origins = ["https://app.example", "https://admin.example"]
+//     max_body = 52_428_800
+//     signer = "vault-transit"
+//     vault_addr = "https://vault.internal:8200"
+//     api_keys = ["newsroom:k3y..."]
+//     storage = "s3"
+//     storage_bucket = "sealed"
+//
+//     [tsa]
+//     url = "https://tsa.example/tsr"
+//
+// Names without the `AEGIS_` prefix (`port`, `vault_addr`) are used as they
+// are. Arrays are joined into the comma-separated lists the settings take.
+// The environment and `.env` override the file: its values are only set for
+// variables that are unset, and then checked with the rest at startup, with
+// the file and line of any bad value. An unknown key is an error, with the
+// nearest known one suggested.
+//
+// Only the part of TOML settings need is read: bare and dotted keys, tables,
+// strings, integers, floats, booleans and arrays of those. Inline tables,
+// arrays of tables and multi-line strings are refused.
+
+use crate::settings::{self, EnvFile};
+use anyhow::anyhow;
+use std::path::Path;
+
+/// Reads the file named by `AEGIS_CONFIG`, if it is set, and sets its
+/// values in the environment where the variables are unset.
+pub fn load() -> anyhow::Result<Option<EnvFile>> {
+    let Some(path) = std::env::var_os("AEGIS_CONFIG") else {
+        return Ok(None);
+    };
+    let file = read(Path::new(&path))?;
+    file.apply();
+    Ok(Some(file))
+}
+
+/// Reads a config file without changing the environment. Errors name the
+/// file and line.
+pub fn read(path: &Path) -> anyhow::Result<EnvFile> {
+    let text = std::fs::read_to_string(path).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+    let values = parse(&text).map_err(|(line, message)| anyhow!("{}:{}: {}", path.display(), line, message))?;
+    Ok(EnvFile::from_values(path, values))
+}
+
+/// A setting's name, its value and the line it was set on.
+type Entry = (String, String, usize);
+
+/// The settings in `text`, or the line of the first error and the error.
+fn parse(text: &str) -> Result<Vec<Entry>, (usize, String)> {
+    let mut parser = Parser { rest: text, line: 1 };
+    let mut table = Vec::new();
+    let mut values: Vec<Entry> = Vec::new();
+    loop {
+        parser.skip_blank();
+        let Some(c) = parser.peek() else { break };
+        let line = parser.line;
+        if c == '[' {
+            parser.bump();
+            if parser.peek() == Some('[') {
+                return Err((line, "arrays of tables are not supported".into()));
+            }
+            parser.skip_spaces();
+            table = parser.key().map_err(|e| (line, e))?;
+            parser.skip_spaces();
+            parser.expect(']').map_err(|e| (line, e))?;
+            parser.end_of_line().map_err(|e| (line, e))?;
+            continue;
+        }
+        let key = parser.key().map_err(|e| (line, e))?;
+        parser.skip_spaces();
+        parser.expect('=').map_err(|e| (line, e))?;
+        parser.skip_spaces();
+        let value = parser.value().map_err(|e| (parser.line, e))?;
+        parser.end_of_line().map_err(|e| (parser.line, e))?;
+
+        let shown = table.iter().chain(&key).cloned().collect::<Vec<_>>().join(".");
+        let name = setting_name(&table, &key).ok_or_else(|| {
+            let flat = shown.replace(['.', '-'], "_").to_ascii_uppercase();
+            let message = match settings::closest(&format!("AEGIS_{}", flat)).or_else(|| settings::closest(&flat)) {
+                Some(known) => format!(
+                    "'{}' is not a known setting; did you mean {}?",
+                    shown,
+                    known.strip_prefix("AEGIS_").unwrap_or(known).to_ascii_lowercase()
+                ),
+                None => format!("'{}' is not a known setting", shown),
+            };
+            (line, message)
+        })?;
+        if name == "AEGIS_CONFIG" {
+            return Err((line, "a config file cannot name another config file".into()));
+        }
+        if let Some((_, _, first)) = values.iter().find(|(n, _, _)| *n == name) {
+            return Err((line, format!("'{}' is already set on line {}", shown, first)));
+        }
+        values.push((name, value, line));
+    }
+    Ok(values)
+}
+
+/// The setting a key names in a table, if there is one.
+fn setting_name(table: &[String], key: &[String]) -> Option<String> {
+    let flat = table.iter().chain(key).cloned().collect::<Vec<_>>().join("_").replace('-', "_").to_ascii_uppercase();
+    [format!("AEGIS_{}", flat), flat].into_iter().find(|name| settings::find(name).is_some())
+}
+
+struct Parser<'a> {
+    rest: &'a str,
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.rest.chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.rest = &self.rest[c.len_utf8()..];
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn expect(&mut self, wanted: char) -> Result<(), String> {
+        match self.bump() {
+            Some(c) if c == wanted => Ok(()),
+            Some(c) => Err(format!("expected '{}', found '{}'", wanted, c)),
+            None => Err(format!("expected '{}' at the end of the file", wanted)),
+        }
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.bump();
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), None | Some('\n')) {
+                self.bump();
+            }
+        }
+    }
+
+    /// Skips whitespace, line breaks and comments.
+    fn skip_blank(&mut self) {
+        loop {
+            match self.peek() {
+                Some(' ' | '\t' | '\r' | '\n') => {
+                    self.bump();
+                }
+                Some('#') => self.skip_comment(),
+                _ => return,
+            }
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<(), String> {
+        self.skip_spaces();
+        self.skip_comment();
+        if self.peek() == Some('\r') {
+            self.bump();
+        }
+        match self.bump() {
+            None | Some('\n') => Ok(()),
+            Some(c) => Err(format!("unexpected '{}' at the end of the line", c)),
+        }
+    }
+
+    /// A bare or dotted key.
+    fn key(&mut self) -> Result<Vec<String>, String> {
+        let mut parts = Vec::new();
+        loop {
+            let part: String = self.rest.chars().take_while(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-').collect();
+            if part.is_empty() {
+                return Err(match self.peek() {
+                    Some('"' | '\'') => "quoted keys are not supported".into(),
+                    Some(c) => format!("expected a key, found '{}'", c),
+                    None => "expected a key".into(),
+                });
+            }
+            self.rest = &self.rest[part.len()..];
+            parts.push(part.to_ascii_lowercase());
+            self.skip_spaces();
+            if self.peek() != Some('.') {
+                return Ok(parts);
+            }
+            self.bump();
+            self.skip_spaces();
+        }
+    }
+
+    /// A value as the text of an environment variable.
+    fn value(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some('"') if self.rest.starts_with("\"\"\"") => Err("multi-line strings are not supported".into()),
+            Some('\'') if self.rest.starts_with("'''") => Err("multi-line strings are not supported".into()),
+            Some('"') => self.basic_string(),
+            Some('\'') => self.literal_string(),
+            Some('[') => self.array(),
+            Some('{') => Err("inline tables are not supported".into()),
+            _ => self.scalar(),
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String, String> {
+        self.bump();
+        let mut out = String::new();
+        loop {
+            match self.bump() {
+                None | Some('\n') => return Err("unterminated string".into()),
+                Some('"') => return Ok(out),
+                Some('\\') => match self.bump() {
+                    Some('"') => out.push('"'),
+                    Some('\\') => out.push('\\'),
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some('r') => out.push('\r'),
+                    Some(c @ ('u' | 'U')) => {
+                        let digits = if c == 'u' { 4 } else { 8 };
+                        let hex: String = self.rest.chars().take(digits).collect();
+                        let c = u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .filter(|_| hex.len() == digits)
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| format!("invalid escape \\{}{}", c, hex))?;
+                        self.rest = &self.rest[hex.len()..];
+                        out.push(c);
+                    }
+                    Some(c) => return Err(format!("invalid escape \\{}", c)),
+                    None => return Err("unterminated string".into()),
+                },
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String, String> {
+        self.bump();
+        let mut out = String::new();
+        loop {
+            match self.bump() {
+                None | Some('\n') => return Err("unterminated string".into()),
+                Some('\'') => return Ok(out),
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    /// An array, as a comma-separated list.
+    fn array(&mut self) -> Result<String, String> {
+        self.bump();
+        let mut items = Vec::new();
+        loop {
+            self.skip_blank();
+            if self.peek() == Some(']') {
+                self.bump();
+                return Ok(items.join(","));
+            }
+            if self.peek() == Some('[') {
+                return Err("nested arrays are not supported".into());
+            }
+            let item = self.value()?;
+            if item.contains(',') {
+                return Err(format!("array item '{}' cannot contain a comma", item));
+            }
+            items.push(item);
+            self.skip_blank();
+            match self.bump() {
+                Some(',') => {}
+                Some(']') => return Ok(items.join(",")),
+                Some(c) => return Err(format!("expected ',' or ']' in an array, found '{}'", c)),
+                None => return Err("unterminated array".into()),
+            }
+        }
+    }
+
+    /// A boolean, integer or float.
+    fn scalar(&mut self) -> Result<String, String> {
+        let token: String = self
+            .rest
+            .chars()
+            .take_while(|c| !matches!(c, ' ' | '\t' | '\r' | '\n' | ',' | ']' | '#'))
+            .collect();
+        let digits = token.strip_prefix(['+', '-']).unwrap_or(&token);
+        let number = !digits.is_empty()
+            && digits.starts_with(|c: char| c.is_ascii_digit())
+            && !digits.contains("__")
+            && !digits.ends_with('_')
+            && digits.replace('_', "").parse::<f64>().is_ok();
+        let value = match token.as_str() {
+            "true" | "false" => token.clone(),
+            _ if number => token.replace('_', "").trim_start_matches('+').to_string(),
+            "" => return Err("expected a value".into()),
+            _ => return Err(format!("'{}' is not a string, number, boolean or array; quote strings", token)),
+        };
+        self.rest = &self.rest[token.len()..];
+        Ok(value)
+    }
+}
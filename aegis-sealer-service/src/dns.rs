@@ -0,0 +1,89 @@
+// aegis-sealer-service/src/dns.rs
+
+// Resolves DNS trust hints (see `aegis_core::dns_trust`) over DNS over
+// HTTPS, using the JSON API that public resolvers such as Google's
+// (`/resolve`) and Cloudflare's (`/dns-query`) offer:
+//
+//     GET <resolver>?name=<selector>._aegis.<domain>&type=TXT
+//     Accept: application/dns-json
+//
+// The answer's `AD` flag says whether the resolver validated it with DNSSEC.
+// Requests go through `http_client` like every other outbound call, so an
+// `https://` resolver is reached as `http://` through the TLS-terminating
+// egress proxy, or through a local resolver serving the same API.
+
+use crate::http_client;
+use aegis_core::dns_trust::{self, DnsTrustReport, TrustHint};
+use anyhow::{anyhow, bail};
+use serde_json::Value;
+
+const MAX_RESPONSE: usize = 64 * 1024;
+/// DNS type code of TXT records.
+const TYPE_TXT: u64 = 16;
+/// RCODE of a name that does not exist.
+const NXDOMAIN: u64 = 3;
+
+/// The TXT records at `name`, and whether the answer was authenticated.
+pub async fn resolve_txt(resolver: &str, name: &str) -> anyhow::Result<(Vec<String>, bool)> {
+    let query: String = form_urlencoded::Serializer::new(String::new())
+        .append_pair("name", name)
+        .append_pair("type", "TXT")
+        .finish();
+    let separator = if resolver.contains('?') { '&' } else { '?' };
+    let url = format!("{}{}{}", resolver, separator, query);
+    let response =
+        http_client::request("GET", &url, &[("Accept", "application/dns-json")], &[], MAX_RESPONSE).await?;
+    if !response.is_success() {
+        bail!("resolver answered HTTP {}", response.status);
+    }
+    let answer: Value =
+        serde_json::from_slice(&response.body).map_err(|e| anyhow!("resolver answer is not DNS JSON: {}", e))?;
+    match answer["Status"].as_u64() {
+        Some(0) => {}
+        Some(NXDOMAIN) => return Ok((Vec::new(), answer["AD"].as_bool().unwrap_or(false))),
+        Some(rcode) => bail!("resolver answered RCODE {}", rcode),
+        None => bail!("resolver answer has no Status"),
+    }
+    let records = answer["Answer"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .filter(|record| record["type"].as_u64() == Some(TYPE_TXT))
+        .filter_map(|record| record["data"].as_str())
+        .map(txt_text)
+        .collect();
+    Ok((records, answer["AD"].as_bool().unwrap_or(false)))
+}
+
+/// Resolves `hint` and checks that it publishes `public_key`.
+pub async fn check(resolver: &str, hint: &TrustHint, public_key: &[u8]) -> DnsTrustReport {
+    match resolve_txt(resolver, &hint.record_name()).await {
+        Ok((records, authenticated)) => dns_trust::check(hint, public_key, &records, authenticated),
+        Err(e) => dns_trust::unresolved(hint, e.to_string()),
+    }
+}
+
+// The text of TXT record data, which resolvers give as one or more quoted
+// strings (records over 255 bytes are split) or, for some, unquoted.
+fn txt_text(data: &str) -> String {
+    let data = data.trim();
+    if !data.starts_with('"') {
+        return data.to_string();
+    }
+    let mut text = String::new();
+    let (mut quoted, mut escaped) = (false, false);
+    for c in data.chars() {
+        match c {
+            _ if escaped => {
+                text.push(c);
+                escaped = false;
+            }
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            _ if quoted => text.push(c),
+            _ => {}
+        }
+    }
+    text
+}
@@ -0,0 +1,516 @@
+// aegis-sealer-service/src/events.rs
+
+// Versioned seal events, for consumers downstream of the service (webhook
+// receivers today) that need a payload they can rely on. Each schema
+// version is fixed once released; a change makes a new version, and the
+// service keeps producing the old ones for consumers that ask for them.
+//
+//   version 1  the original webhook payload: flat, as in `webhooks`.
+//   version 2  adds the schema version, the event ID, the caller and the
+//              SHA-256 of the metadata, and groups the image and key fields:
+//
+//     {"schema_version": 2, "id": "seal-41", "event": "seal",
+//      "sealed_at": "2025-06-01T12:00:00Z", "audit_id": 41,
+//      "request_id": "...", "caller": "tenant:acme", "tenant": "acme",
+//      "delegation": null,
+//      "image": {"sha256": "9f86d0...", "size": 48213},
+//      "metadata": "...", "metadata_sha256": "2c26b4...",
+//      "key": {"id": "2025-06", "fingerprint": "..."}}
+//
+// Every version can be encoded as JSON or as protobuf (proto3, the message
+// given by GET /events/schema/{version}?format=proto); absent optional
+// fields are null in JSON and left out of protobuf. The content type names the encoding and the
+// version, e.g. `application/vnd.aegis.event+protobuf; version=2`.
+//
+// Consumers choose what they receive: a format is negotiated from an
+// `Accept` list of those media types (with `q` weights; `application/json`
+// alone means version 1, and a media type without a version means the
+// newest). A webhook receiver that cannot read what it was sent answers 415
+// with such an `Accept` header, and the service switches that URL to the
+// best format it lists and sends the event again (see `webhooks`).
+
+use crate::hooks::SealEvent;
+use crate::AppError;
+use axum::{
+    extract::{Path, Query},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+/// Schema versions the service produces, oldest first.
+pub const SCHEMA_VERSIONS: [u32; 2] = [1, 2];
+pub const CURRENT_SCHEMA: u32 = 2;
+
+const JSON_MEDIA_TYPE: &str = "application/vnd.aegis.event+json";
+const PROTOBUF_MEDIA_TYPE: &str = "application/vnd.aegis.event+protobuf";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Json,
+    Protobuf,
+}
+
+impl Encoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Json => "json",
+            Encoding::Protobuf => "protobuf",
+        }
+    }
+
+    pub fn media_type(self) -> &'static str {
+        match self {
+            Encoding::Json => JSON_MEDIA_TYPE,
+            Encoding::Protobuf => PROTOBUF_MEDIA_TYPE,
+        }
+    }
+
+    /// The encoding a media type names, and the version it implies when it
+    /// carries none.
+    fn from_media_type(media_type: &str) -> Option<(Encoding, u32)> {
+        match media_type.to_ascii_lowercase().as_str() {
+            JSON_MEDIA_TYPE => Some((Encoding::Json, CURRENT_SCHEMA)),
+            PROTOBUF_MEDIA_TYPE | "application/x-protobuf" | "application/protobuf" => {
+                Some((Encoding::Protobuf, CURRENT_SCHEMA))
+            }
+            // What receivers of the original payload expect.
+            "application/json" | "application/*" | "*/*" => Some((Encoding::Json, 1)),
+            _ => None,
+        }
+    }
+}
+
+/// An encoding and a schema version.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EventFormat {
+    pub version: u32,
+    pub encoding: Encoding,
+}
+
+impl Default for EventFormat {
+    /// Version 1 JSON, which receivers configured before versioning expect.
+    fn default() -> Self {
+        EventFormat { version: 1, encoding: Encoding::Json }
+    }
+}
+
+impl EventFormat {
+    /// Parses a configured format: `json` or `protobuf`, optionally with
+    /// `;version=N` (default the newest), or a media type as in `Accept`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut parts = text.split(';').map(str::trim);
+        let name = parts.next()?;
+        let (encoding, default_version) = match name.to_ascii_lowercase().as_str() {
+            "json" => (Encoding::Json, CURRENT_SCHEMA),
+            "protobuf" | "proto" => (Encoding::Protobuf, CURRENT_SCHEMA),
+            _ => Encoding::from_media_type(name)?,
+        };
+        let mut version = default_version;
+        for param in parts {
+            let (key, value) = param.split_once('=')?;
+            if key.trim().eq_ignore_ascii_case("version") {
+                version = value.trim().trim_matches('"').parse().ok()?;
+            }
+        }
+        SCHEMA_VERSIONS.contains(&version).then_some(EventFormat { version, encoding })
+    }
+
+    /// The best format in an `Accept` list: the one with the highest `q`,
+    /// the first listed among equals. `None` if none is one the service
+    /// produces.
+    pub fn negotiate(accept: &str) -> Option<Self> {
+        let mut best: Option<(f32, EventFormat)> = None;
+        for range in accept.split(',').map(str::trim).filter(|r| !r.is_empty()) {
+            let mut q = 1.0;
+            let mut spec = Vec::new();
+            for (i, part) in range.split(';').map(str::trim).enumerate() {
+                match part.split_once('=') {
+                    Some((key, value)) if i > 0 && key.trim().eq_ignore_ascii_case("q") => {
+                        q = value.trim().parse().unwrap_or(0.0);
+                    }
+                    _ => spec.push(part),
+                }
+            }
+            let Some(format) = EventFormat::parse(&spec.join(";")) else {
+                continue;
+            };
+            if q > 0.0 && best.is_none_or(|(best_q, _)| q > best_q) {
+                best = Some((q, format));
+            }
+        }
+        best.map(|(_, format)| format)
+    }
+
+    /// The `Content-Type` of events in this format.
+    pub fn content_type(&self) -> String {
+        format!("{}; version={}", self.encoding.media_type(), self.version)
+    }
+}
+
+/// A seal event as delivered, independent of schema version.
+#[derive(Clone, Debug)]
+pub struct Event {
+    pub id: String,
+    pub seal: SealEvent,
+    /// RFC 3339.
+    pub sealed_at: String,
+}
+
+impl Event {
+    pub fn new(seal: &SealEvent, sealed_at: String) -> Self {
+        Event {
+            id: format!("{}-{}", seal.action, seal.audit_id.unwrap_or_default()),
+            seal: seal.clone(),
+            sealed_at,
+        }
+    }
+
+    pub fn encode(&self, format: EventFormat) -> Vec<u8> {
+        match (format.version, format.encoding) {
+            (1, Encoding::Json) => serde_json::to_vec(&V1::from(self)),
+            (_, Encoding::Json) => serde_json::to_vec(&V2::from(self)),
+            (1, Encoding::Protobuf) => return V1::from(self).to_protobuf(),
+            (_, Encoding::Protobuf) => return V2::from(self).to_protobuf(),
+        }
+        .expect("events serialize")
+    }
+}
+
+/// Schema version 1.
+#[derive(Serialize)]
+pub struct V1<'a> {
+    pub event: &'a str,
+    pub audit_id: Option<u64>,
+    pub request_id: Option<&'a str>,
+    pub image_sha256: &'a str,
+    pub image_size: u64,
+    pub metadata: &'a str,
+    pub key_id: Option<&'a str>,
+    pub key_fingerprint: Option<&'a str>,
+    pub tenant: Option<&'a str>,
+    pub delegation: Option<&'a str>,
+    pub sealed_at: &'a str,
+}
+
+impl<'a> From<&'a Event> for V1<'a> {
+    fn from(event: &'a Event) -> Self {
+        let seal = &event.seal;
+        V1 {
+            event: seal.action,
+            audit_id: seal.audit_id,
+            request_id: seal.request_id.as_deref(),
+            image_sha256: &seal.image_sha256,
+            image_size: seal.image_size,
+            metadata: &seal.metadata,
+            key_id: seal.key_id.as_deref(),
+            key_fingerprint: seal.key_fingerprint.as_deref(),
+            tenant: seal.tenant.as_deref(),
+            delegation: seal.delegation.as_deref(),
+            sealed_at: &event.sealed_at,
+        }
+    }
+}
+
+impl V1<'_> {
+    fn to_protobuf(&self) -> Vec<u8> {
+        let mut out = Proto::default();
+        out.string(1, self.event);
+        out.optional_uint(2, self.audit_id);
+        out.optional_string(3, self.request_id);
+        out.string(4, self.image_sha256);
+        out.uint(5, self.image_size);
+        out.string(6, self.metadata);
+        out.optional_string(7, self.key_id);
+        out.optional_string(8, self.key_fingerprint);
+        out.optional_string(9, self.tenant);
+        out.optional_string(10, self.delegation);
+        out.string(11, self.sealed_at);
+        out.0
+    }
+}
+
+/// Schema version 2.
+#[derive(Serialize)]
+pub struct V2<'a> {
+    pub schema_version: u32,
+    pub id: &'a str,
+    pub event: &'a str,
+    pub sealed_at: &'a str,
+    pub audit_id: Option<u64>,
+    pub request_id: Option<&'a str>,
+    pub caller: Option<&'a str>,
+    pub tenant: Option<&'a str>,
+    pub delegation: Option<&'a str>,
+    pub image: ImageV2<'a>,
+    pub metadata: &'a str,
+    pub metadata_sha256: String,
+    pub key: KeyV2<'a>,
+}
+
+#[derive(Serialize)]
+pub struct ImageV2<'a> {
+    pub sha256: &'a str,
+    pub size: u64,
+}
+
+#[derive(Serialize)]
+pub struct KeyV2<'a> {
+    pub id: Option<&'a str>,
+    pub fingerprint: Option<&'a str>,
+}
+
+impl<'a> From<&'a Event> for V2<'a> {
+    fn from(event: &'a Event) -> Self {
+        let seal = &event.seal;
+        V2 {
+            schema_version: 2,
+            id: &event.id,
+            event: seal.action,
+            sealed_at: &event.sealed_at,
+            audit_id: seal.audit_id,
+            request_id: seal.request_id.as_deref(),
+            caller: seal.caller.as_deref(),
+            tenant: seal.tenant.as_deref(),
+            delegation: seal.delegation.as_deref(),
+            image: ImageV2 { sha256: &seal.image_sha256, size: seal.image_size },
+            metadata: &seal.metadata,
+            metadata_sha256: hex::encode(Sha256::digest(seal.metadata.as_bytes())),
+            key: KeyV2 { id: seal.key_id.as_deref(), fingerprint: seal.key_fingerprint.as_deref() },
+        }
+    }
+}
+
+impl V2<'_> {
+    fn to_protobuf(&self) -> Vec<u8> {
+        let mut out = Proto::default();
+        out.uint(1, self.schema_version as u64);
+        out.string(2, self.id);
+        out.string(3, self.event);
+        out.string(4, self.sealed_at);
+        out.optional_uint(5, self.audit_id);
+        out.optional_string(6, self.request_id);
+        out.optional_string(7, self.caller);
+        out.optional_string(8, self.tenant);
+        out.optional_string(9, self.delegation);
+        let mut image = Proto::default();
+        image.string(1, self.image.sha256);
+        image.uint(2, self.image.size);
+        out.bytes(10, &image.0);
+        out.string(11, self.metadata);
+        out.string(12, &self.metadata_sha256);
+        let mut key = Proto::default();
+        key.optional_string(1, self.key.id);
+        key.optional_string(2, self.key.fingerprint);
+        out.bytes(13, &key.0);
+        out.0
+    }
+}
+
+/// Protobuf wire encoding of the few field types events use. Singular
+/// fields at their default value are omitted, as proto3 does; `optional`
+/// fields are written whenever present.
+#[derive(Default)]
+struct Proto(Vec<u8>);
+
+impl Proto {
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.0.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.0.push(value as u8);
+    }
+
+    fn uint(&mut self, field: u32, value: u64) {
+        if value != 0 {
+            self.optional_uint(field, Some(value));
+        }
+    }
+
+    fn optional_uint(&mut self, field: u32, value: Option<u64>) {
+        if let Some(value) = value {
+            self.varint((field as u64) << 3);
+            self.varint(value);
+        }
+    }
+
+    fn bytes(&mut self, field: u32, value: &[u8]) {
+        self.varint((field as u64) << 3 | 2);
+        self.varint(value.len() as u64);
+        self.0.extend_from_slice(value);
+    }
+
+    fn string(&mut self, field: u32, value: &str) {
+        if !value.is_empty() {
+            self.bytes(field, value.as_bytes());
+        }
+    }
+
+    fn optional_string(&mut self, field: u32, value: Option<&str>) {
+        if let Some(value) = value {
+            self.bytes(field, value.as_bytes());
+        }
+    }
+}
+
+const PROTO_V1: &str = r#"syntax = "proto3";
+
+package aegis.events.v1;
+
+// Content type: application/vnd.aegis.event+protobuf; version=1
+message SealEvent {
+  string event = 1;
+  optional uint64 audit_id = 2;
+  optional string request_id = 3;
+  string image_sha256 = 4;
+  uint64 image_size = 5;
+  string metadata = 6;
+  optional string key_id = 7;
+  optional string key_fingerprint = 8;
+  optional string tenant = 9;
+  optional string delegation = 10;
+  // RFC 3339.
+  string sealed_at = 11;
+}
+"#;
+
+const PROTO_V2: &str = r#"syntax = "proto3";
+
+package aegis.events.v2;
+
+// Content type: application/vnd.aegis.event+protobuf; version=2
+message SealEvent {
+  uint32 schema_version = 1;
+  string id = 2;
+  string event = 3;
+  // RFC 3339.
+  string sealed_at = 4;
+  optional uint64 audit_id = 5;
+  optional string request_id = 6;
+  optional string caller = 7;
+  optional string tenant = 8;
+  optional string delegation = 9;
+  Image image = 10;
+  string metadata = 11;
+  string metadata_sha256 = 12;
+  Key key = 13;
+
+  message Image {
+    string sha256 = 1;
+    uint64 size = 2;
+  }
+
+  message Key {
+    optional string id = 1;
+    optional string fingerprint = 2;
+  }
+}
+"#;
+
+/// The `.proto` definition of a schema version.
+pub fn proto_schema(version: u32) -> Option<&'static str> {
+    match version {
+        1 => Some(PROTO_V1),
+        2 => Some(PROTO_V2),
+        _ => None,
+    }
+}
+
+/// The JSON Schema of a schema version.
+pub fn json_schema(version: u32) -> Option<Value> {
+    let optional_string = json!({"type": ["string", "null"]});
+    let id = |version: u32| format!("urn:aegis:events:seal:v{}", version);
+    match version {
+        1 => Some(json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "$id": id(1),
+            "title": "Aegis seal event, version 1",
+            "type": "object",
+            "required": ["event", "image_sha256", "image_size", "metadata", "sealed_at"],
+            "properties": {
+                "event": {"enum": ["seal", "reseal"]},
+                "audit_id": {"type": ["integer", "null"], "minimum": 0},
+                "request_id": optional_string,
+                "image_sha256": {"type": "string", "pattern": "^[0-9a-f]{64}$"},
+                "image_size": {"type": "integer", "minimum": 0},
+                "metadata": {"type": "string"},
+                "key_id": optional_string,
+                "key_fingerprint": optional_string,
+                "tenant": optional_string,
+                "delegation": optional_string,
+                "sealed_at": {"type": "string", "format": "date-time"},
+            },
+        })),
+        2 => Some(json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "$id": id(2),
+            "title": "Aegis seal event, version 2",
+            "type": "object",
+            "required": ["schema_version", "id", "event", "sealed_at", "image", "metadata", "metadata_sha256", "key"],
+            "properties": {
+                "schema_version": {"const": 2},
+                "id": {"type": "string"},
+                "event": {"enum": ["seal", "reseal"]},
+                "sealed_at": {"type": "string", "format": "date-time"},
+                "audit_id": {"type": ["integer", "null"], "minimum": 0},
+                "request_id": optional_string,
+                "caller": optional_string,
+                "tenant": optional_string,
+                "delegation": optional_string,
+                "image": {
+                    "type": "object",
+                    "required": ["sha256", "size"],
+                    "properties": {
+                        "sha256": {"type": "string", "pattern": "^[0-9a-f]{64}$"},
+                        "size": {"type": "integer", "minimum": 0},
+                    },
+                },
+                "metadata": {"type": "string"},
+                "metadata_sha256": {"type": "string", "pattern": "^[0-9a-f]{64}$"},
+                "key": {
+                    "type": "object",
+                    "properties": {"id": optional_string, "fingerprint": optional_string},
+                },
+            },
+        })),
+        _ => None,
+    }
+}
+
+/// GET /events/schema: the versions and encodings events come in.
+pub async fn schemas_handler() -> Json<Value> {
+    let encodings: Vec<Value> = [Encoding::Json, Encoding::Protobuf]
+        .iter()
+        .map(|e| json!({"name": e.name(), "media_type": e.media_type()}))
+        .collect();
+    Json(json!({
+        "current": CURRENT_SCHEMA,
+        "versions": SCHEMA_VERSIONS,
+        "encodings": encodings,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct SchemaQuery {
+    /// `json` (JSON Schema, the default) or `proto`.
+    format: Option<String>,
+}
+
+/// GET /events/schema/{version}: the JSON Schema of a version, or with
+/// `?format=proto` its `.proto` definition.
+pub async fn schema_handler(Path(version): Path<u32>, Query(query): Query<SchemaQuery>) -> Result<Response, AppError> {
+    let unknown = || AppError(StatusCode::NOT_FOUND, format!("No event schema version {}.", version));
+    match query.format.as_deref().unwrap_or("json") {
+        "json" => Ok(Json(json_schema(version).ok_or_else(unknown)?).into_response()),
+        "proto" => Ok(([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], proto_schema(version).ok_or_else(unknown)?)
+            .into_response()),
+        other => Err(AppError(
+            StatusCode::BAD_REQUEST,
+            format!("Unknown schema format '{}'; use json or proto.", other),
+        )),
+    }
+}
@@ -0,0 +1,68 @@
+// aegis-sealer-service/src/export.rs
+
+use crate::{auth::Tenant, AppError, AppState};
+use aegis_core::{
+    bundle::{BundleContents, TrustBundle, UnsignedBundle},
+    time::rfc3339,
+};
+use axum::{
+    body::Bytes,
+    extract::{Extension, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+use std::time::SystemTime;
+use tracing::info;
+
+/// Packages a sealed container into an offline verification bundle (a tar
+/// archive) with the keys and revocations of the caller's tenant (see
+/// `tenants`), the configured endorsements, and an audit checkpoint.
+pub async fn bundle_handler(
+    State(state): State<AppState>,
+    tenant: Option<Extension<Tenant>>,
+    body: Bytes,
+) -> Result<Response, AppError> {
+    if body.is_empty() {
+        return Err(AppError(
+            StatusCode::BAD_REQUEST,
+            "Request body must be a sealed container.".into(),
+        ));
+    }
+    let signer = state.signer.pin()?;
+    let public_key = signer.public_key()?;
+    let tenant = tenant.map(|Extension(Tenant(name))| name);
+    let (audit_size, latest) = state.audit.checkpoint();
+    let contents = BundleContents {
+        container: body.to_vec(),
+        trust: TrustBundle {
+            keys: state.tenants.trusted_keys(tenant.as_deref()),
+            endorsements: state.config.endorsements.clone(),
+        },
+        revoked: state.tenants.revoked_keys(tenant.as_deref()),
+        checkpoint: json!({
+            "kind": "audit",
+            "records": audit_size,
+            "latest_record_id": latest.as_ref().map(|r| r.id),
+            "latest_record_at": latest.as_ref().map(|r| rfc3339(r.sealed_at)),
+            "generated_at": rfc3339(SystemTime::now()),
+        }),
+    };
+
+    let unsigned = UnsignedBundle::new(&contents, &public_key)?;
+    let signature = signer.sign(&unsigned.signing_message()).await?;
+    let archive = unsigned.finish(&signature)?;
+    info!(bytes = archive.len(), "Offline verification bundle exported.");
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "application/x-tar"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"aegis-bundle.tar\"",
+            ),
+        ],
+        archive,
+    )
+        .into_response())
+}
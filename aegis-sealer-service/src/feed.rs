@@ -0,0 +1,247 @@
+// aegis-sealer-service/src/feed.rs
+
+use crate::{audit::AuditRecord, AppError, AppState};
+use aegis_core::{crypto::SignatureContext, time::rfc3339};
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderName, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::env;
+
+const DEFAULT_PAGE_SIZE: usize = 20;
+const MAX_PAGE_SIZE: usize = 100;
+
+#[derive(Deserialize)]
+pub struct FeedQuery {
+    limit: Option<usize>,
+    before: Option<u64>,
+}
+
+/// Which record fields are withheld from the public feed. Controlled by the
+/// comma-separated `AEGIS_FEED_REDACT` variable; by default only the metadata
+/// string itself is withheld and its hash is published instead.
+pub struct Redaction {
+    metadata: bool,
+    image_size: bool,
+    key_fingerprint: bool,
+}
+
+impl Redaction {
+    pub fn from_env() -> Self {
+        let value = env::var("AEGIS_FEED_REDACT").unwrap_or_else(|_| "metadata".to_string());
+        let fields: Vec<&str> = value.split(',').map(str::trim).collect();
+        Redaction {
+            metadata: fields.contains(&"metadata"),
+            image_size: fields.contains(&"image_size"),
+            key_fingerprint: fields.contains(&"key_fingerprint"),
+        }
+    }
+}
+
+struct Page {
+    records: Vec<AuditRecord>,
+    next_before: Option<u64>,
+}
+
+fn load_page(state: &AppState, query: &FeedQuery) -> Page {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let records = state.audit.page(query.before, limit);
+    let next_before = if records.len() == limit {
+        records.last().map(|r| r.id)
+    } else {
+        None
+    };
+    Page { records, next_before }
+}
+
+fn page_url(base_url: &str, path: &str, page: &Page) -> Option<String> {
+    page.next_before
+        .map(|before| format!("{}{}?before={}", base_url, path, before))
+}
+
+/// Signs the serialized feed with the service key, in
+/// `SignatureContext::Feed`, so that mirrors and readers can check it was
+/// published by this instance.
+async fn signed_response(
+    state: &AppState,
+    content_type: &'static str,
+    body: String,
+) -> Result<Response, AppError> {
+    let signature = state.signer.sign(&SignatureContext::Feed.message(body.as_bytes())).await?;
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                HeaderName::from_static("x-aegis-feed-signature"),
+                hex::encode(signature.to_bytes()),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+pub async fn json_feed_handler(
+    State(state): State<AppState>,
+    Query(query): Query<FeedQuery>,
+) -> Result<Response, AppError> {
+    let redaction = &state.config.feed_redaction;
+    let base = &state.config.public_url;
+    let page = load_page(&state, &query);
+    let items: Vec<_> = page
+        .records
+        .iter()
+        .map(|r| {
+            let mut aegis = json!({
+                "action": r.action.as_str(),
+                "image_sha256": r.image_hash,
+                "metadata_sha256": r.metadata_hash,
+            });
+            if !redaction.metadata {
+                aegis["metadata"] = json!(r.metadata);
+            }
+            if !redaction.image_size {
+                aegis["image_size"] = json!(r.image_size);
+            }
+            if !redaction.key_fingerprint {
+                aegis["key_fingerprint"] = json!(r.key_fingerprint.to_hex());
+            }
+            json!({
+                "id": r.id.to_string(),
+                "title": format!("{} #{}", title_case(r.action.as_str()), r.id),
+                "content_text": format!("Image SHA-256 {}", r.image_hash),
+                "date_published": rfc3339(r.sealed_at),
+                "_aegis": aegis,
+            })
+        })
+        .collect();
+    let mut feed = json!({
+        "version": "https://jsonfeed.org/version/1.1",
+        "title": "Aegis public seal bulletin",
+        "feed_url": format!("{}/feed/json", base),
+        "items": items,
+    });
+    if let Some(next) = page_url(base, "/feed/json", &page) {
+        feed["next_url"] = json!(next);
+    }
+    signed_response(&state, "application/feed+json", feed.to_string()).await
+}
+
+pub async fn atom_feed_handler(
+    State(state): State<AppState>,
+    Query(query): Query<FeedQuery>,
+) -> Result<Response, AppError> {
+    let redaction = &state.config.feed_redaction;
+    let page = load_page(&state, &query);
+    let base = &state.config.public_url;
+    let updated = page
+        .records
+        .first()
+        .map(|r| rfc3339(r.sealed_at))
+        .unwrap_or_else(|| rfc3339(std::time::UNIX_EPOCH));
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str("  <title>Aegis public seal bulletin</title>\n");
+    xml.push_str(&format!("  <id>{}/feed/atom</id>\n", escape_xml(base)));
+    xml.push_str(&format!("  <updated>{}</updated>\n", updated));
+    xml.push_str(&format!(
+        "  <link rel=\"self\" href=\"{}/feed/atom\"/>\n",
+        escape_xml(base)
+    ));
+    if let Some(next) = page_url(base, "/feed/atom", &page) {
+        xml.push_str(&format!("  <link rel=\"next\" href=\"{}\"/>\n", escape_xml(&next)));
+    }
+    for r in &page.records {
+        let mut content = format!(
+            "Image SHA-256: {}\nMetadata SHA-256: {}",
+            r.image_hash, r.metadata_hash
+        );
+        if !redaction.image_size {
+            content.push_str(&format!("\nImage size: {} bytes", r.image_size));
+        }
+        if !redaction.key_fingerprint {
+            content.push_str(&format!("\nKey: {}", r.key_fingerprint));
+        }
+        if !redaction.metadata {
+            content.push_str(&format!("\nMetadata: {}", r.metadata));
+        }
+        xml.push_str("  <entry>\n");
+        xml.push_str(&format!(
+            "    <title>{} #{}</title>\n",
+            title_case(r.action.as_str()),
+            r.id
+        ));
+        xml.push_str(&format!("    <id>urn:aegis:seal:{}</id>\n", r.image_hash));
+        xml.push_str(&format!("    <updated>{}</updated>\n", rfc3339(r.sealed_at)));
+        xml.push_str(&format!(
+            "    <content type=\"text\">{}</content>\n",
+            escape_xml(&content)
+        ));
+        xml.push_str("  </entry>\n");
+    }
+    xml.push_str("</feed>\n");
+    signed_response(&state, "application/atom+xml", xml).await
+}
+
+fn title_case(s: &str) -> String {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .map(|c| c.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+/// Escapes text for element content and attribute values. Characters XML
+/// 1.0 does not allow at all (control characters other than tab, LF and CR,
+/// and U+FFFE/U+FFFF) are replaced with U+FFFD so the feed stays well-formed.
+fn escape_xml(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            '\t' | '\n' | '\r' => out.push(c),
+            '\u{0}'..='\u{1F}' | '\u{FFFE}' | '\u{FFFF}' => out.push(char::REPLACEMENT_CHARACTER),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_markup_characters() {
+        assert_eq!(
+            escape_xml(r#"<a href="x">Tom & Jerry's</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&apos;s&lt;/a&gt;"
+        );
+        assert_eq!(escape_xml("&amp;"), "&amp;amp;");
+    }
+
+    #[test]
+    fn replaces_characters_xml_forbids() {
+        assert_eq!(escape_xml("a\u{1}b"), "a\u{FFFD}b");
+        assert_eq!(escape_xml("\u{0}\u{8}\u{B}\u{C}\u{1F}"), "\u{FFFD}".repeat(5));
+        assert_eq!(escape_xml("\u{FFFE}\u{FFFF}"), "\u{FFFD}\u{FFFD}");
+    }
+
+    #[test]
+    fn keeps_whitespace_and_other_text() {
+        assert_eq!(escape_xml("a\tb\nc\r\nd"), "a\tb\nc\r\nd");
+        // DEL and the C1 controls are allowed in XML 1.0.
+        assert_eq!(escape_xml("\u{7F}\u{85}"), "\u{7F}\u{85}");
+        assert_eq!(escape_xml("café 📷 \u{FFFD}"), "café 📷 \u{FFFD}");
+        assert_eq!(escape_xml(""), "");
+    }
+}
@@ -0,0 +1,168 @@
+// aegis-sealer-service/src/gcp_kms.rs
+
+// Google Cloud KMS signing backend. The key is an `EC_SIGN_P256_SHA256`
+// crypto key version; the service sends it SHA-256 digests and never sees
+// the private key. Access tokens come from the metadata server of the
+// instance, GKE pod or Cloud Run service the sealer runs on (its attached
+// service account), and are cached until shortly before they expire.
+//
+// Cloud KMS is HTTPS-only, while our outbound client speaks plain HTTP, so
+// `AEGIS_GCP_KMS_ENDPOINT` is expected to point at a TLS-terminating egress
+// sidecar.
+
+use crate::http_client;
+use anyhow::{anyhow, bail, Context};
+use base64ct::{Base64, Encoding};
+use p256::ecdsa::{Signature, VerifyingKey};
+use p256::pkcs8::DecodePublicKey;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::info;
+
+const DEFAULT_METADATA_HOST: &str = "metadata.google.internal";
+const MAX_RESPONSE: usize = 1024 * 1024;
+// Refresh tokens this long before they expire.
+const REFRESH_MARGIN: Duration = Duration::from_secs(300);
+
+struct CachedToken {
+    value: String,
+    expires_at: Instant,
+}
+
+struct CloudKmsClient {
+    endpoint: String,
+    metadata_host: String,
+    token: Mutex<Option<CachedToken>>,
+}
+
+pub struct GcpKmsKey {
+    client: CloudKmsClient,
+    name: String,
+    public_key: VerifyingKey,
+}
+
+impl GcpKmsKey {
+    /// Configured with `AEGIS_GCP_KMS_KEY`, the full resource name
+    /// `projects/P/locations/L/keyRings/R/cryptoKeys/K/cryptoKeyVersions/V`,
+    /// and `AEGIS_GCP_KMS_ENDPOINT`. The metadata server is
+    /// `GCE_METADATA_HOST` (default `metadata.google.internal`).
+    pub async fn from_env() -> anyhow::Result<Self> {
+        let name = env::var("AEGIS_GCP_KMS_KEY").context("AEGIS_GCP_KMS_KEY must be set")?;
+        if !name.contains("/cryptoKeyVersions/") {
+            bail!("AEGIS_GCP_KMS_KEY must name a crypto key version, not '{}'", name);
+        }
+        let endpoint = env::var("AEGIS_GCP_KMS_ENDPOINT").context("AEGIS_GCP_KMS_ENDPOINT must be set")?;
+        let client = CloudKmsClient {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            metadata_host: env::var("GCE_METADATA_HOST").unwrap_or_else(|_| DEFAULT_METADATA_HOST.into()),
+            token: Mutex::new(None),
+        };
+        let public_key = client.fetch_public_key(&name).await?;
+        info!(key = %name, "Loaded Cloud KMS signing key.");
+        Ok(GcpKmsKey {
+            client,
+            name,
+            public_key,
+        })
+    }
+
+    pub fn public_key(&self) -> &VerifyingKey {
+        &self.public_key
+    }
+
+    /// Confirms Cloud KMS is reachable and the key version still has the
+    /// public key we embed.
+    pub async fn check(&self) -> anyhow::Result<()> {
+        if self.client.fetch_public_key(&self.name).await? != self.public_key {
+            bail!("Cloud KMS key '{}' has a different public key", self.name);
+        }
+        Ok(())
+    }
+
+    /// Signs `message` with ECDSA/SHA-256. Cloud KMS takes the digest and
+    /// returns a DER signature, which is converted to the r || s encoding we
+    /// store.
+    pub async fn sign(&self, message: &[u8]) -> anyhow::Result<Signature> {
+        let digest = Sha256::digest(message);
+        let resp = self
+            .client
+            .call(
+                "POST",
+                &format!("{}:asymmetricSign", self.name),
+                Some(json!({ "digest": { "sha256": Base64::encode_string(&digest) } })),
+            )
+            .await?;
+        let encoded = resp["signature"]
+            .as_str()
+            .ok_or_else(|| anyhow!("Cloud KMS asymmetricSign returned no signature"))?;
+        let der = Base64::decode_vec(encoded).map_err(|e| anyhow!("invalid Cloud KMS signature encoding: {}", e))?;
+        let signature = Signature::from_der(&der).map_err(|e| anyhow!("invalid Cloud KMS signature: {}", e))?;
+        Ok(signature.normalize_s().unwrap_or(signature))
+    }
+}
+
+impl CloudKmsClient {
+    async fn access_token(&self) -> anyhow::Result<String> {
+        let mut cached = self.token.lock().await;
+        if let Some(token) = cached.as_ref().filter(|t| t.expires_at > Instant::now() + REFRESH_MARGIN) {
+            return Ok(token.value.clone());
+        }
+        let url = format!(
+            "http://{}/computeMetadata/v1/instance/service-accounts/default/token",
+            self.metadata_host
+        );
+        let resp = http_client::request("GET", &url, &[("Metadata-Flavor", "Google")], &[], MAX_RESPONSE).await?;
+        if !resp.is_success() {
+            bail!("GCP metadata token request returned HTTP {}", resp.status);
+        }
+        let body: Value = serde_json::from_slice(&resp.body)?;
+        let value = body["access_token"]
+            .as_str()
+            .ok_or_else(|| anyhow!("GCP token response has no access_token"))?
+            .to_string();
+        *cached = Some(CachedToken {
+            value: value.clone(),
+            expires_at: Instant::now() + Duration::from_secs(body["expires_in"].as_u64().unwrap_or(3600)),
+        });
+        Ok(value)
+    }
+
+    async fn call(&self, method: &str, path: &str, body: Option<Value>) -> anyhow::Result<Value> {
+        let token = self.access_token().await?;
+        let auth = format!("Bearer {}", token);
+        let url = format!("{}/v1/{}", self.endpoint, path);
+        let body = body.map(|b| b.to_string()).unwrap_or_default();
+        let resp = http_client::request(
+            method,
+            &url,
+            &[("Authorization", &auth), ("Content-Type", "application/json")],
+            body.as_bytes(),
+            MAX_RESPONSE,
+        )
+        .await?;
+        if !resp.is_success() {
+            bail!(
+                "Cloud KMS {} {} returned HTTP {}: {}",
+                method,
+                path,
+                resp.status,
+                String::from_utf8_lossy(&resp.body)
+            );
+        }
+        Ok(serde_json::from_slice(&resp.body)?)
+    }
+
+    async fn fetch_public_key(&self, name: &str) -> anyhow::Result<VerifyingKey> {
+        let resp = self.call("GET", &format!("{}/publicKey", name), None).await?;
+        if resp["algorithm"].as_str() != Some("EC_SIGN_P256_SHA256") {
+            bail!("Cloud KMS key '{}' must use EC_SIGN_P256_SHA256", name);
+        }
+        let pem = resp["pem"]
+            .as_str()
+            .ok_or_else(|| anyhow!("Cloud KMS key '{}' has no public key", name))?;
+        VerifyingKey::from_public_key_pem(pem).map_err(|e| anyhow!("invalid Cloud KMS public key: {}", e))
+    }
+}
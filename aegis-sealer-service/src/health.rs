@@ -0,0 +1,282 @@
+// aegis-sealer-service/src/health.rs
+
+// Liveness and readiness probes. Both answer with a JSON body listing each
+// component's status, and 503 when the service should not get traffic:
+//
+// - `GET /healthz` makes no network calls. It checks that the signing key
+//   can sign (for a keyring, that a key is active now), so an orchestrator
+//   restarts a process whose keys have all expired.
+// - `GET /readyz` also checks the service's dependencies: it asks a remote
+//   signing backend (Vault transit, Azure Key Vault, ...) for its key,
+//   writes a probe file to storage, and checks that the TSA and the S3
+//   endpoint for /verify answer.
+//
+// A failing dependency either fails the service or only degrades it, per
+// `AEGIS_DEPENDENCY_POLICY` (`kms=degrade,tsa=fail`, ...). By default every
+// dependency fails it, except a TSA that `AEGIS_TSA_REQUIRED` does not
+// require, since seals still succeed without a timestamp. The same checks
+// run at startup (`check_dependencies`), so a typo'd endpoint or an
+// unwritable storage directory stops the service before it takes traffic
+// rather than at its first seal.
+//
+// Remote checks are bounded by `PROBE_TIMEOUT` so a hung dependency cannot
+// hang the probe. Both routes are public.
+
+use crate::{http_client, openapi::ApiSchema, signer::ServiceSigner, AppState};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_PROBE_RESPONSE: usize = 64 * 1024;
+
+/// The dependencies `AEGIS_DEPENDENCY_POLICY` can name.
+pub const DEPENDENCIES: &[&str] = &["kms", "storage", "tsa", "s3"];
+
+/// What a failing dependency does to the service.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OnFailure {
+    /// Keep the service from starting, and answer /readyz with 503.
+    Fail,
+    /// Start anyway, reporting the dependency as `degraded`.
+    Degrade,
+}
+
+impl OnFailure {
+    fn as_str(self) -> &'static str {
+        match self {
+            OnFailure::Fail => "fail",
+            OnFailure::Degrade => "degrade",
+        }
+    }
+}
+
+/// Parses a comma-separated list of `dependency=fail|degrade` entries.
+pub fn parse_policy(spec: &str) -> anyhow::Result<HashMap<String, OnFailure>> {
+    let mut policy = HashMap::new();
+    for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (name, on_failure) = entry
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("invalid dependency policy entry '{}'", entry))?;
+        let name = name.trim();
+        if !DEPENDENCIES.contains(&name) {
+            anyhow::bail!("unknown dependency '{}'; expected one of {}", name, DEPENDENCIES.join(", "));
+        }
+        let on_failure = match on_failure.trim() {
+            "fail" => OnFailure::Fail,
+            "degrade" => OnFailure::Degrade,
+            other => anyhow::bail!("unknown failure policy '{}' for {}", other, name),
+        };
+        policy.insert(name.to_string(), on_failure);
+    }
+    Ok(policy)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Status {
+    Ok,
+    Degraded,
+    Fail,
+}
+
+impl Status {
+    fn as_str(self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::Degraded => "degraded",
+            Status::Fail => "fail",
+        }
+    }
+}
+
+#[derive(Default)]
+struct Report {
+    status: Option<Status>,
+    components: Map<String, Value>,
+}
+
+impl Report {
+    fn add(&mut self, name: &str, status: Status, mut detail: Value) {
+        self.status = self.status.max(Some(status));
+        detail["status"] = json!(status.as_str());
+        self.components.insert(name.to_string(), detail);
+    }
+
+    fn dependency(&mut self, name: &str, on_failure: OnFailure, result: anyhow::Result<()>, mut detail: Value) {
+        detail["on_failure"] = json!(on_failure.as_str());
+        match result {
+            Ok(()) => self.add(name, Status::Ok, detail),
+            Err(e) => {
+                detail["error"] = json!(e.to_string());
+                let status = match on_failure {
+                    OnFailure::Fail => Status::Fail,
+                    OnFailure::Degrade => Status::Degraded,
+                };
+                self.add(name, status, detail);
+            }
+        }
+    }
+
+    fn into_response(self) -> Response {
+        let status = self.status.unwrap_or(Status::Ok);
+        let code = if status == Status::Fail { StatusCode::SERVICE_UNAVAILABLE } else { StatusCode::OK };
+        (code, Json(HealthResponse { status: status.as_str(), components: self.components })).into_response()
+    }
+}
+
+/// The body of /healthz and /readyz.
+#[derive(Serialize)]
+pub struct HealthResponse {
+    status: &'static str,
+    /// Each component's details, with its own `status`.
+    components: Map<String, Value>,
+}
+
+impl ApiSchema for HealthResponse {
+    const NAME: &'static str = "HealthResponse";
+
+    fn schema() -> Value {
+        let status = json!({ "type": "string", "enum": ["ok", "degraded", "fail"] });
+        json!({
+            "type": "object",
+            "required": ["status", "components"],
+            "properties": {
+                "status": status,
+                "components": {
+                    "type": "object",
+                    "additionalProperties": {
+                        "type": "object",
+                        "required": ["status"],
+                        "properties": { "status": status, "error": { "type": "string" } },
+                    },
+                },
+            },
+        })
+    }
+}
+
+/// The checks that need no network: the signing key and, for a keyring
+/// signer, the keyring.
+fn local_checks(state: &AppState) -> Report {
+    let mut report = Report::default();
+    match state.signer.check_local() {
+        Ok(fingerprint) => report.add(
+            "signing_key",
+            Status::Ok,
+            json!({ "signer": state.signer.kind(), "fingerprint": fingerprint.to_hex() }),
+        ),
+        Err(e) => report.add(
+            "signing_key",
+            Status::Fail,
+            json!({ "signer": state.signer.kind(), "error": e.to_string() }),
+        ),
+    }
+    if let ServiceSigner::Keyring(keyring) = &state.signer {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+        let active = keyring.active(now).map(|entry| entry.id.clone());
+        report.add(
+            "keyring",
+            if active.is_some() { Status::Ok } else { Status::Fail },
+            json!({ "keys": keyring.entries().len(), "active_key_id": active }),
+        );
+    }
+    report
+}
+
+fn timed_out() -> anyhow::Error {
+    anyhow::anyhow!("no answer within {}s", PROBE_TIMEOUT.as_secs())
+}
+
+async fn probe(check: impl Future<Output = anyhow::Result<()>>) -> anyhow::Result<()> {
+    tokio::time::timeout(PROBE_TIMEOUT, check).await.unwrap_or_else(|_| Err(timed_out()))
+}
+
+fn on_failure(state: &AppState, name: &str, default: OnFailure) -> OnFailure {
+    state.config.dependency_policy.get(name).copied().unwrap_or(default)
+}
+
+/// Checks every dependency the service is configured with.
+async fn dependency_checks(state: &AppState, report: &mut Report) {
+    let kms = async {
+        match tokio::time::timeout(PROBE_TIMEOUT, state.signer.check_remote()).await {
+            Ok(result) => result,
+            Err(_) => Some(Err(timed_out())),
+        }
+    };
+    let storage = probe(state.storage.check());
+    let tsa = async {
+        let tsa = state.tsa.as_ref()?;
+        // Any HTTP answer will do: TSAs only accept POSTed requests, and
+        // asking for a real token on every probe would cost a signature.
+        Some(probe(async { http_client::get(tsa.url(), MAX_PROBE_RESPONSE).await.map(|_| ()) }).await)
+    };
+    let s3 = async {
+        #[cfg(feature = "verifier")]
+        if let Some(s3) = &state.config.s3 {
+            return Some((s3.endpoint(), probe(s3.check()).await));
+        }
+        None::<(&str, anyhow::Result<()>)>
+    };
+    let (kms, storage, tsa, s3) = tokio::join!(kms, storage, tsa, s3);
+
+    let signer = json!({ "signer": state.signer.kind() });
+    if let Some(result) = kms {
+        report.dependency("kms", on_failure(state, "kms", OnFailure::Fail), result, signer);
+    }
+    report.dependency(
+        "storage",
+        on_failure(state, "storage", OnFailure::Fail),
+        storage,
+        json!({ "kind": state.storage.kind() }),
+    );
+    if let (Some(result), Some(config)) = (tsa, state.tsa.as_ref()) {
+        let default = if config.required() { OnFailure::Fail } else { OnFailure::Degrade };
+        let detail = json!({ "url": config.url(), "required": config.required() });
+        report.dependency("tsa", on_failure(state, "tsa", default), result, detail);
+    }
+    if let Some((endpoint, result)) = s3 {
+        report.dependency("s3", on_failure(state, "s3", OnFailure::Fail), result, json!({ "endpoint": endpoint }));
+    }
+}
+
+/// Runs the /readyz dependency checks once, failing if any dependency
+/// whose policy is `fail` is unavailable. Degraded ones are logged.
+pub async fn check_dependencies(state: &AppState) -> anyhow::Result<()> {
+    let mut report = Report::default();
+    dependency_checks(state, &mut report).await;
+    let mut failed = Vec::new();
+    for (name, detail) in &report.components {
+        let error = detail["error"].as_str().unwrap_or_default();
+        match detail["status"].as_str() {
+            Some("fail") => failed.push(format!("{}: {}", name, error)),
+            Some("degraded") => warn!(dependency = %name, error, "Dependency unavailable; starting degraded."),
+            _ => {}
+        }
+    }
+    if !failed.is_empty() {
+        anyhow::bail!(
+            "unavailable dependencies (see AEGIS_DEPENDENCY_POLICY):\n  {}",
+            failed.join("\n  ")
+        );
+    }
+    Ok(())
+}
+
+pub async fn healthz_handler(State(state): State<AppState>) -> Response {
+    local_checks(&state).into_response()
+}
+
+pub async fn readyz_handler(State(state): State<AppState>) -> Response {
+    let mut report = local_checks(&state);
+    dependency_checks(&state, &mut report).await;
+    report.into_response()
+}
@@ -0,0 +1,83 @@
+// aegis-sealer-service/src/hooks.rs
+
+// Hooks that run around every seal, whichever endpoint it came through
+// (/seal, /seal/batch, /reseal, /ingest/dam). Register them with
+// `AppState::add_seal_hook`; they run in registration order.
+//
+// `before_seal` runs once the upload has been read and hashed, before the
+// write-ahead log entry and the signature; returning an error rejects the
+// seal with that status and message, so it is the place for quota or billing
+// checks. `after_seal` runs once the seal is signed and recorded in the audit
+// store and audit log, and cannot fail the request.
+
+use crate::AppError;
+use futures_util::future::BoxFuture;
+use std::sync::{Arc, RwLock};
+
+/// A seal as seen by hooks.
+#[derive(Clone, Debug)]
+pub struct SealEvent {
+    /// `seal` or `reseal`.
+    pub action: &'static str,
+    /// Tenant of the API key the request used, if any.
+    pub tenant: Option<String>,
+    /// ID of the request that asked for the seal (see `audit_log`).
+    pub request_id: Option<String>,
+    /// Who asked for the seal: the `auth::Principal`, `dam` for DAM
+    /// ingestion, or `None` for an anonymous request.
+    pub caller: Option<String>,
+    pub metadata: String,
+    /// Hex SHA-256 of the image (for a reseal, of the original container).
+    pub image_sha256: String,
+    pub image_size: u64,
+    /// Set for `after_seal`.
+    pub audit_id: Option<u64>,
+    /// Hex fingerprint of the key that signed, set for `after_seal`.
+    pub key_fingerprint: Option<String>,
+    /// Keyring ID of the key that signed, if it has one; set for
+    /// `after_seal`.
+    pub key_id: Option<String>,
+    /// ID of the delegation token the seal was requested under, if any
+    /// (see `auth`).
+    pub delegation: Option<String>,
+}
+
+pub trait SealHook: Send + Sync + 'static {
+    fn before_seal<'a>(&'a self, event: &'a SealEvent) -> BoxFuture<'a, Result<(), AppError>> {
+        let _ = event;
+        Box::pin(async { Ok(()) })
+    }
+
+    fn after_seal<'a>(&'a self, event: &'a SealEvent) -> BoxFuture<'a, ()> {
+        let _ = event;
+        Box::pin(async {})
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Hooks {
+    hooks: RwLock<Vec<Arc<dyn SealHook>>>,
+}
+
+impl Hooks {
+    pub fn add(&self, hook: Arc<dyn SealHook>) {
+        self.hooks.write().unwrap().push(hook);
+    }
+
+    fn snapshot(&self) -> Vec<Arc<dyn SealHook>> {
+        self.hooks.read().unwrap().clone()
+    }
+
+    pub async fn before(&self, event: &SealEvent) -> Result<(), AppError> {
+        for hook in self.snapshot() {
+            hook.before_seal(event).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn after(&self, event: &SealEvent) {
+        for hook in self.snapshot() {
+            hook.after_seal(event).await;
+        }
+    }
+}
@@ -0,0 +1,349 @@
+// aegis-sealer-service/src/http_client.rs
+
+// A deliberately small HTTP/1.1 client used for outbound integrations
+// (fetching assets, delivering callbacks). It speaks plain HTTP only; TLS
+// destinations must be reached through a TLS-terminating egress proxy.
+//
+// Every integration (KMS backends, Vault, TSA, URL fetches, mirrors and
+// callbacks) goes through here, so egress settings apply to all of them.
+// `HTTP_PROXY` (or `http_proxy`) names a forward proxy, optionally with
+// `user:password@` for basic authentication, that requests are sent through
+// in absolute form; `NO_PROXY` (or `no_proxy`) lists hosts reached directly,
+// as `*`, exact names, domain suffixes (`.corp.example` or `corp.example`),
+// optionally with `:port`. Instance metadata endpoints such as
+// `169.254.169.254` are usually wanted in `NO_PROXY`. As the client never
+// speaks TLS itself, `HTTPS_PROXY`, extra root certificates and other TLS
+// options belong to the egress proxy's configuration, not the service's.
+
+use anyhow::{anyhow, bail, Context};
+use base64ct::{Base64, Encoding};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use std::net::IpAddr;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_HEADERS: usize = 64;
+
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+struct Target {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+fn parse_url(url: &str) -> anyhow::Result<Target> {
+    let rest = match url.split_once("://") {
+        Some(("http", rest)) => rest,
+        Some((scheme, _)) => bail!("unsupported URL scheme '{}'; only http is supported", scheme),
+        None => bail!("URL '{}' has no scheme", url),
+    };
+    let (authority, path) = match rest.find(['/', '?', '#']) {
+        Some(idx) => (&rest[..idx], &rest[idx..]),
+        None => (rest, "/"),
+    };
+    // The fragment is not sent, and a query needs a path in front of it.
+    let path = path.split('#').next().unwrap_or_default();
+    let path = if path.starts_with('/') { path.to_string() } else { format!("/{}", path) };
+    // `http://expected.host@other.host/` goes to `other.host`; this client
+    // sends no credentials, so such URLs are refused rather than misread.
+    if authority.contains('@') {
+        bail!("URL '{}' has credentials in it, which are not supported", url);
+    }
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse().context("invalid port in URL")?),
+        None => (authority, 80),
+    };
+    if host.is_empty() {
+        bail!("URL '{}' has no host", url);
+    }
+    Ok(Target {
+        host: host.to_string(),
+        port,
+        path,
+    })
+}
+
+/// The `Host` header value for a destination; the port is omitted when it
+/// is the HTTP default.
+pub fn host_header(host: &str, port: u16) -> String {
+    if port == 80 {
+        host.to_string()
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+/// The forward proxy a request goes through.
+struct Proxy {
+    host: String,
+    port: u16,
+    /// `Proxy-Authorization` value, from credentials in the proxy URL.
+    authorization: Option<String>,
+}
+
+fn env_either(upper: &str, lower: &str) -> Option<String> {
+    std::env::var(upper)
+        .or_else(|_| std::env::var(lower))
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+}
+
+/// The proxy for requests to `host:port`, if `HTTP_PROXY` is set and
+/// `NO_PROXY` does not exempt the destination.
+fn proxy_for(host: &str, port: u16) -> anyhow::Result<Option<Proxy>> {
+    let Some(url) = env_either("HTTP_PROXY", "http_proxy") else {
+        return Ok(None);
+    };
+    if env_either("NO_PROXY", "no_proxy").is_some_and(|list| bypasses(&list, host, port)) {
+        return Ok(None);
+    }
+    let url = if url.contains("://") { url } else { format!("http://{}", url) };
+    let (credentials, url) = match url.split_once("://").and_then(|(scheme, rest)| Some((scheme, rest.rsplit_once('@')?))) {
+        Some((scheme, (credentials, rest))) => (Some(credentials.to_string()), format!("{}://{}", scheme, rest)),
+        None => (None, url),
+    };
+    let target = parse_url(&url).context("invalid HTTP_PROXY")?;
+    Ok(Some(Proxy {
+        host: target.host,
+        port: target.port,
+        authorization: credentials.map(|c| format!("Basic {}", Base64::encode_string(c.as_bytes()))),
+    }))
+}
+
+/// Whether the `NO_PROXY` list exempts `host:port`.
+fn bypasses(list: &str, host: &str, port: u16) -> bool {
+    let host = host.to_ascii_lowercase();
+    list.split(',').map(str::trim).filter(|e| !e.is_empty()).any(|entry| {
+        if entry == "*" {
+            return true;
+        }
+        let entry = entry.to_ascii_lowercase();
+        let (name, entry_port) = match entry.rsplit_once(':') {
+            Some((name, p)) if !name.contains(':') => (name, p.parse::<u16>().ok()),
+            _ => (entry.as_str(), None),
+        };
+        let name = name.trim_start_matches('.');
+        entry_port.is_none_or(|p| p == port) && (host == name || host.ends_with(&format!(".{}", name)))
+    })
+}
+
+/// The `Host` header this client will send for `url`.
+pub fn authority(url: &str) -> anyhow::Result<String> {
+    let target = parse_url(url)?;
+    Ok(host_header(&target.host, target.port))
+}
+
+/// The host this client will connect to for `url`, without the port.
+pub fn host(url: &str) -> anyhow::Result<String> {
+    Ok(parse_url(url)?.host)
+}
+
+/// The host, port and path (with any query) this client will request for
+/// `url`.
+#[cfg(feature = "verifier")]
+pub fn components(url: &str) -> anyhow::Result<(String, u16, String)> {
+    let target = parse_url(url)?;
+    Ok((target.host, target.port, target.path))
+}
+
+/// Whether `url` names one of `hosts`, compared without case.
+pub fn names_host(url: &str, hosts: &[String]) -> anyhow::Result<bool> {
+    let host = host(url)?;
+    Ok(hosts.iter().any(|allowed| allowed.eq_ignore_ascii_case(&host)))
+}
+
+/// Whether `ip` is an address of this host or its networks rather than of
+/// the internet: loopback, private, link-local, carrier-grade NAT, unique
+/// local or unspecified. IPv4-mapped IPv6 addresses are judged as IPv4.
+pub fn is_internal(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || (v4.octets()[0] == 100 && v4.octets()[1] & 0xc0 == 64)
+        }
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_internal(IpAddr::V4(v4)),
+            None => {
+                v6.is_loopback()
+                    || v6.is_unspecified()
+                    || v6.is_unique_local()
+                    || v6.is_unicast_link_local()
+            }
+        },
+    }
+}
+
+/// Checks a URL a caller asked the service to send requests to. Its host
+/// must be one of `allowed`; with none listed, any host will do whose
+/// addresses are all public (see `is_internal()`), so a caller cannot point
+/// the service at itself or its network. Listing a host is the only way to
+/// allow an internal one.
+pub async fn check_destination(url: &str, allowed: &[String]) -> anyhow::Result<()> {
+    if names_host(url, allowed)? {
+        return Ok(());
+    }
+    if !allowed.is_empty() {
+        bail!("host is not one of the allowed hosts");
+    }
+    let target = parse_url(url)?;
+    let host = target.host.trim_start_matches('[').trim_end_matches(']');
+    let addresses: Vec<_> = tokio::net::lookup_host((host, target.port))
+        .await
+        .with_context(|| format!("cannot resolve '{}'", host))?
+        .collect();
+    if addresses.is_empty() || addresses.iter().any(|address| is_internal(address.ip())) {
+        bail!("'{}' is an internal address", host);
+    }
+    Ok(())
+}
+
+pub async fn get(url: &str, max_body: usize) -> anyhow::Result<HttpResponse> {
+    request("GET", url, &[], &[], max_body).await
+}
+
+pub async fn post(
+    url: &str,
+    headers: &[(&str, &str)],
+    body: &[u8],
+    max_body: usize,
+) -> anyhow::Result<HttpResponse> {
+    request("POST", url, headers, body, max_body).await
+}
+
+pub async fn request(
+    method: &str,
+    url: &str,
+    headers: &[(&str, &str)],
+    body: &[u8],
+    max_body: usize,
+) -> anyhow::Result<HttpResponse> {
+    tokio::time::timeout(TIMEOUT, request_inner(method, url, headers, body, max_body))
+        .await
+        .map_err(|_| anyhow!("request to {} timed out", url))?
+}
+
+async fn request_inner(
+    method: &str,
+    url: &str,
+    headers: &[(&str, &str)],
+    body: &[u8],
+    max_body: usize,
+) -> anyhow::Result<HttpResponse> {
+    let target = parse_url(url)?;
+    let proxy = proxy_for(&target.host, target.port)?;
+    let (connect_host, connect_port) = match &proxy {
+        Some(proxy) => (proxy.host.as_str(), proxy.port),
+        None => (target.host.as_str(), target.port),
+    };
+    let mut stream = TcpStream::connect((connect_host, connect_port))
+        .await
+        .with_context(|| format!("failed to connect to {}:{}", connect_host, connect_port))?;
+
+    let host = host_header(&target.host, target.port);
+    // Proxies take the request target in absolute form.
+    let request_target = match proxy {
+        Some(_) => format!("http://{}{}", host, target.path),
+        None => target.path.clone(),
+    };
+    let mut head = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nUser-Agent: aegis-sealer\r\nContent-Length: {}\r\n",
+        method,
+        request_target,
+        host,
+        body.len()
+    );
+    if let Some(authorization) = proxy.as_ref().and_then(|p| p.authorization.as_deref()) {
+        head.push_str(&format!("Proxy-Authorization: {}\r\n", authorization));
+    }
+    for (name, value) in headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body).await?;
+
+    // Connection: close means the server ends the response by closing.
+    let mut raw = Vec::new();
+    let limit = (max_body + 64 * 1024) as u64;
+    (&mut stream).take(limit + 1).read_to_end(&mut raw).await?;
+    if raw.len() as u64 > limit {
+        bail!("response from {} exceeds {} bytes", url, max_body);
+    }
+
+    let mut header_buf = [httparse::EMPTY_HEADER; MAX_HEADERS];
+    let mut parsed = httparse::Response::new(&mut header_buf);
+    let header_len = match parsed.parse(&raw)? {
+        httparse::Status::Complete(len) => len,
+        httparse::Status::Partial => bail!("incomplete HTTP response from {}", url),
+    };
+    let status = parsed.code.unwrap_or(0);
+    let headers: Vec<(String, String)> = parsed
+        .headers
+        .iter()
+        .map(|h| (h.name.to_string(), String::from_utf8_lossy(h.value).into_owned()))
+        .collect();
+
+    let mut response = HttpResponse {
+        status,
+        headers,
+        body: Vec::new(),
+    };
+    let rest = &raw[header_len..];
+    response.body = if response
+        .header("transfer-encoding")
+        .is_some_and(|v| v.eq_ignore_ascii_case("chunked"))
+    {
+        decode_chunked(rest)?
+    } else {
+        rest.to_vec()
+    };
+    if response.body.len() > max_body {
+        bail!("response from {} exceeds {} bytes", url, max_body);
+    }
+    Ok(response)
+}
+
+fn decode_chunked(mut data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    loop {
+        let line_end = data
+            .windows(2)
+            .position(|w| w == b"\r\n")
+            .ok_or_else(|| anyhow!("malformed chunked body"))?;
+        let size_str = std::str::from_utf8(&data[..line_end])?;
+        let size_str = size_str.split(';').next().unwrap_or("").trim();
+        let size = usize::from_str_radix(size_str, 16).context("invalid chunk size")?;
+        data = &data[line_end + 2..];
+        if size == 0 {
+            return Ok(out);
+        }
+        if data.len() < size + 2 {
+            bail!("truncated chunked body");
+        }
+        out.extend_from_slice(&data[..size]);
+        data = &data[size + 2..];
+    }
+}
@@ -0,0 +1,263 @@
+// aegis-sealer-service/src/ingest.rs
+
+// Webhook ingestion for third-party DAM systems. The DAM pushes a JSON
+// payload describing a new asset; we fetch the asset, seal it with fields
+// mapped from the payload as metadata, store the result and report back to
+// the DAM's callback URL.
+//
+// Nothing reaches sealing unless the payload is signed with
+// `AEGIS_DAM_WEBHOOK_SECRET`, and the service only fetches from and calls
+// back to the hosts in `AEGIS_DAM_ALLOWED_HOSTS`, so a webhook cannot point
+// it at internal addresses. Without both, the endpoint answers 503.
+
+use crate::{audit::AuditAction, audit_log::Caller, hooks::SealEvent, http_client, provenance::Submission, tsa, AppError, AppState};
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use hmac::{Hmac, Mac};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::env;
+use tracing::{error, info, warn};
+
+const MAX_ASSET_SIZE: usize = 100 * 1024 * 1024;
+const MAX_CALLBACK_RESPONSE: usize = 64 * 1024;
+
+/// Ingestion settings, read from the environment:
+///
+/// - `AEGIS_DAM_WEBHOOK_SECRET`: HMAC-SHA256 key used to authenticate payloads
+///   (sent by the DAM as hex in `x-dam-signature`, optionally `sha256=`-prefixed).
+/// - `AEGIS_DAM_URL_FIELD`: JSON pointer to the asset URL (default `/asset_url`).
+/// - `AEGIS_DAM_CALLBACK_FIELD`: JSON pointer to the callback URL (default `/callback_url`).
+/// - `AEGIS_DAM_METADATA_MAP`: comma-separated `name=/json/pointer` pairs copied into metadata.
+/// - `AEGIS_DAM_ALLOWED_HOSTS`: comma-separated hosts asset and callback URLs may name.
+///
+/// Sealed files are written to the service's `SealedStore`.
+pub struct DamConfig {
+    secret: Option<String>,
+    url_field: String,
+    callback_field: String,
+    metadata_map: Vec<(String, String)>,
+    allowed_hosts: Vec<String>,
+}
+
+impl DamConfig {
+    /// Whether webhook payloads must carry a valid signature.
+    pub fn is_authenticated(&self) -> bool {
+        self.secret.is_some()
+    }
+
+    /// Whether webhooks are accepted: payloads are signed and there are
+    /// hosts to fetch from.
+    pub fn is_enabled(&self) -> bool {
+        self.is_authenticated() && !self.allowed_hosts.is_empty()
+    }
+
+    /// Fails unless `url` names one of the allowed hosts.
+    fn check_host(&self, field: &str, url: &str) -> Result<(), AppError> {
+        let listed = http_client::names_host(url, &self.allowed_hosts)
+            .map_err(|e| AppError(StatusCode::BAD_REQUEST, format!("Invalid URL at '{}': {}", field, e)))?;
+        if !listed {
+            return Err(AppError(
+                StatusCode::FORBIDDEN,
+                format!("The URL at '{}' names a host not in AEGIS_DAM_ALLOWED_HOSTS.", field),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn from_env() -> Self {
+        let metadata_map = env::var("AEGIS_DAM_METADATA_MAP")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(name, pointer)| (name.trim().to_string(), pointer.trim().to_string()))
+            .collect();
+        DamConfig {
+            secret: env::var("AEGIS_DAM_WEBHOOK_SECRET").ok(),
+            url_field: env::var("AEGIS_DAM_URL_FIELD").unwrap_or_else(|_| "/asset_url".into()),
+            callback_field: env::var("AEGIS_DAM_CALLBACK_FIELD")
+                .unwrap_or_else(|_| "/callback_url".into()),
+            metadata_map,
+            allowed_hosts: env::var("AEGIS_DAM_ALLOWED_HOSTS")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|host| !host.is_empty())
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+}
+
+fn verify_signature(secret: &str, headers: &HeaderMap, body: &[u8]) -> Result<(), AppError> {
+    let unauthorized = || AppError(StatusCode::UNAUTHORIZED, "Invalid webhook signature.".into());
+    let provided = headers
+        .get("x-dam-signature")
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(unauthorized)?;
+    let provided = hex::decode(provided.trim_start_matches("sha256=")).map_err(|_| unauthorized())?;
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(body);
+    mac.verify_slice(&provided).map_err(|_| unauthorized())
+}
+
+pub async fn dam_webhook_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    caller: Caller,
+    submission: Submission,
+    body: Bytes,
+) -> Result<Response, AppError> {
+    let config = &state.config.dam;
+    let Some(secret) = &config.secret else {
+        warn!("AEGIS_DAM_WEBHOOK_SECRET is not set; refusing DAM webhook.");
+        return Err(AppError(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "DAM ingestion is not configured (AEGIS_DAM_WEBHOOK_SECRET).".into(),
+        ));
+    };
+    verify_signature(secret, &headers, &body)?;
+    if config.allowed_hosts.is_empty() {
+        return Err(AppError(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "DAM ingestion is not configured (AEGIS_DAM_ALLOWED_HOSTS).".into(),
+        ));
+    }
+
+    let payload: Value = serde_json::from_slice(&body)
+        .map_err(|e| AppError(StatusCode::BAD_REQUEST, format!("Invalid JSON payload: {}", e)))?;
+    let asset_url = payload
+        .pointer(&config.url_field)
+        .and_then(Value::as_str)
+        .ok_or_else(|| {
+            AppError(
+                StatusCode::BAD_REQUEST,
+                format!("Payload is missing the asset URL at '{}'.", config.url_field),
+            )
+        })?
+        .to_string();
+    let callback_url = payload
+        .pointer(&config.callback_field)
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    config.check_host(&config.url_field, &asset_url)?;
+    if let Some(callback_url) = &callback_url {
+        config.check_host(&config.callback_field, callback_url)?;
+    }
+
+    let mut metadata = Map::new();
+    metadata.insert("source".into(), json!("dam"));
+    metadata.insert("asset_url".into(), json!(asset_url));
+    for (name, pointer) in &config.metadata_map {
+        if let Some(value) = payload.pointer(pointer) {
+            metadata.insert(name.clone(), value.clone());
+        }
+    }
+    let metadata = submission.attach(Value::Object(metadata).to_string());
+
+    info!(asset_url = %asset_url, "Accepted DAM ingestion webhook.");
+    tokio::spawn(async move {
+        let result = match ingest(&state, caller.request_id, &asset_url, metadata).await {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                error!(error = %e, asset_url = %asset_url, "DAM ingestion failed.");
+                json!({ "status": "failed", "asset_url": asset_url, "error": e.to_string() })
+            }
+        };
+        if let Some(callback_url) = callback_url {
+            let body = result.to_string();
+            match http_client::post(
+                &callback_url,
+                &[("Content-Type", "application/json")],
+                body.as_bytes(),
+                MAX_CALLBACK_RESPONSE,
+            )
+            .await
+            {
+                Ok(resp) if resp.is_success() => info!(callback_url = %callback_url, "DAM callback delivered."),
+                Ok(resp) => warn!(status = resp.status, callback_url = %callback_url, "DAM callback rejected."),
+                Err(e) => warn!(error = %e, callback_url = %callback_url, "DAM callback failed."),
+            }
+        }
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(json!({ "status": "accepted" }))).into_response())
+}
+
+async fn ingest(
+    state: &AppState,
+    request_id: Option<String>,
+    asset_url: &str,
+    metadata: String,
+) -> anyhow::Result<Value> {
+    let response = http_client::get(asset_url, MAX_ASSET_SIZE).await?;
+    if !response.is_success() {
+        anyhow::bail!("asset fetch returned HTTP {}", response.status);
+    }
+    let metadata = state.config.intake.check(&response.body, metadata, None).map_err(|e| anyhow::anyhow!(e.1))?;
+    let image_hash = hex::encode(Sha256::digest(&response.body));
+    let mut event = SealEvent {
+        action: AuditAction::Seal.as_str(),
+        tenant: None,
+        request_id,
+        caller: Some("dam".into()),
+        metadata: metadata.clone(),
+        image_sha256: image_hash.clone(),
+        image_size: response.body.len() as u64,
+        audit_id: None,
+        key_fingerprint: None,
+        key_id: None,
+        delegation: None,
+    };
+    state.hooks.before(&event).await.map_err(|e| anyhow::anyhow!(e.1))?;
+    let signer = state.signer.pin().map_err(|e| anyhow::anyhow!(e.1))?;
+    let public_key = signer.public_key().map_err(|e| anyhow::anyhow!(e.1))?.to_sec1_bytes();
+    let wal_id = state
+        .wal
+        .begin(AuditAction::Seal, &public_key, &metadata, &image_hash, response.body.len())
+        .await?;
+    let mut ancient = match signer.seal(metadata, response.body).await {
+        Ok(ancient) => ancient,
+        Err(e) => {
+            state.wal.abort(wal_id, &e.1).await;
+            anyhow::bail!(e.1);
+        }
+    };
+    state.config.certify(&mut ancient.header, &public_key);
+    if let Err(e) = tsa::stamp(state.tsa.as_deref(), &mut ancient.header, &ancient.signature).await {
+        state.wal.abort(wal_id, &e.1).await;
+        anyhow::bail!(e.1);
+    }
+    let record = state.audit.record(&ancient);
+    state.wal.complete(wal_id, record.id).await;
+    state.metrics.record_seal(ancient.image_data.len() as u64);
+    event.audit_id = Some(record.id);
+    event.key_fingerprint = Some(record.key_fingerprint.to_hex());
+    event.key_id = ancient.header.key_id().map(str::to_string);
+    state.audit_log.append(&event).await.map_err(|e| anyhow::anyhow!(e.1))?;
+    if let Some(proof) = state.transparency.append(&image_hash).await.map_err(|e| anyhow::anyhow!(e.1))? {
+        ancient.header.set_log_inclusion(&proof);
+    }
+    state.hooks.after(&event).await;
+
+    let sealed_bytes = ancient.to_bytes()?;
+    let location = state.storage.put(&record.image_hash, &sealed_bytes).await?;
+    info!(location = %location, audit_id = record.id, "DAM asset sealed and stored.");
+
+    Ok(json!({
+        "status": "sealed",
+        "asset_url": asset_url,
+        "audit_id": record.id,
+        "image_sha256": record.image_hash,
+        "sealed_sha256": hex::encode(Sha256::digest(&sealed_bytes)),
+        "stored_path": location,
+        "retrieval_path": format!("/sealed/{}", record.image_hash),
+        "key_fingerprint": record.key_fingerprint.to_hex(),
+    }))
+}
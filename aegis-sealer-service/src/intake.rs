@@ -0,0 +1,260 @@
+// aegis-sealer-service/src/intake.rs
+
+// Content checks on uploads before they are sealed. The leading bytes of
+// every image are sniffed (see `aegis_core::image_info`), and only formats
+// in `AEGIS_IMAGE_FORMATS` (default: jpeg, png, heic, tiff, webp) are
+// sealed. Anything else is refused with 415, or, with
+// `AEGIS_UNRECOGNIZED_IMAGES=flag`, sealed with a flag in its metadata:
+//
+//     "content": {"flagged": "not a recognized image format"}
+//
+// With `AEGIS_RECORD_IMAGE_INFO=true` accepted images get the format and
+// dimensions read from their headers instead:
+//
+//     "content": {"format": "jpeg", "media_type": "image/jpeg",
+//                 "width": 4000, "height": 3000}
+//
+// A payload declared as something other than an accepted image format (by
+// a `media_type` part, or the Content-Type of a `payload` part) skips the
+// image checks. Its type must match `AEGIS_PAYLOAD_TYPES` (default:
+// application/pdf, audio/*, video/*) and agree with its leading bytes when
+// those are recognized (see `aegis_core::media_type`), and it is recorded:
+//
+//     "content": {"format": "payload", "media_type": "application/pdf"}
+//
+// A `text` part of `none`, `lf` or `crlf` seals the upload as a text
+// document instead (see `aegis_core::text`): it must be UTF-8, at most
+// `MAX_TEXT_SIZE` bytes, its line endings are rewritten to LF or CRLF unless
+// `none`, and the block records what was done:
+//
+//     "content": {"format": "text", "media_type": "text/plain",
+//                 "encoding": "utf-8", "line_endings": "crlf",
+//                 "normalization": "lf"}
+//
+// As with `submission`, the block replaces any `content` key sent by the
+// client, is covered by the signature, and is left out of metadata that is
+// not a JSON object.
+
+use crate::{spool::Spool, AppError};
+use aegis_core::image_info::{ImageFormat, ImageInfo};
+use aegis_core::media_type;
+use aegis_core::text::{self, Normalization, TextRecord};
+use axum::http::StatusCode;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::path::Path;
+use tracing::{info, warn};
+
+/// How much of an upload is read for its headers. JPEGs keep their
+/// dimensions after any EXIF and XMP segments, each up to 64 KiB.
+const HEAD_BYTES: usize = 1024 * 1024;
+
+/// Payload types sealed when `AEGIS_PAYLOAD_TYPES` is unset.
+pub const DEFAULT_PAYLOAD_TYPES: &str = "application/pdf,audio/*,video/*";
+
+/// Largest document sealed in text mode, which reads it whole.
+pub const MAX_TEXT_SIZE: u64 = 64 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Unrecognized {
+    Reject,
+    Flag,
+}
+
+pub struct IntakePolicy {
+    allowed: Vec<ImageFormat>,
+    unrecognized: Unrecognized,
+    record_info: bool,
+    /// Media types, or `type/*` patterns, of payloads that are not images.
+    payload_types: Vec<String>,
+}
+
+impl IntakePolicy {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let allowed = match env::var("AEGIS_IMAGE_FORMATS") {
+            Ok(list) => parse_formats(&list)?,
+            Err(_) => ImageFormat::ALL.to_vec(),
+        };
+        let unrecognized = match env::var("AEGIS_UNRECOGNIZED_IMAGES").as_deref() {
+            Ok("reject") | Err(_) => Unrecognized::Reject,
+            Ok("flag") => Unrecognized::Flag,
+            Ok(other) => anyhow::bail!("AEGIS_UNRECOGNIZED_IMAGES must be reject or flag, got '{}'", other),
+        };
+        Ok(IntakePolicy {
+            allowed,
+            unrecognized,
+            record_info: matches!(env::var("AEGIS_RECORD_IMAGE_INFO").as_deref(), Ok("true" | "1")),
+            payload_types: parse_media_types(
+                &env::var("AEGIS_PAYLOAD_TYPES").unwrap_or_else(|_| DEFAULT_PAYLOAD_TYPES.to_string()),
+            )?,
+        })
+    }
+
+    /// The formats sealed without a flag.
+    pub fn allowed(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.allowed.iter().map(|f| f.name())
+    }
+
+    /// The media types of payloads sealed besides images.
+    pub fn payload_types(&self) -> &[String] {
+        &self.payload_types
+    }
+
+    /// Checks an upload whose first bytes are `head` and that was declared
+    /// as `declared`, returning `metadata` with the `content` block added if
+    /// there is one to add.
+    pub fn check(&self, head: &[u8], metadata: String, declared: Option<&str>) -> Result<String, AppError> {
+        if let Some(declared) = declared.filter(|d| !self.is_image_type(d)) {
+            return self.check_payload(head, metadata, declared);
+        }
+        let info = ImageInfo::read(head);
+        let problem = match &info {
+            None => "not a recognized image format".to_string(),
+            Some(info) if !self.allowed.contains(&info.format) => {
+                format!("{} images are not accepted", info.format.name())
+            }
+            Some(info) if self.record_info => return Ok(attach(metadata, json!(info))),
+            Some(_) => return Ok(metadata),
+        };
+        match self.unrecognized {
+            Unrecognized::Reject => Err(AppError(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                format!(
+                    "Upload refused: {}. Accepted formats: {}.",
+                    problem,
+                    self.allowed().collect::<Vec<_>>().join(", ")
+                ),
+            )),
+            Unrecognized::Flag => {
+                warn!(problem = %problem, "Sealing an upload flagged by content checks.");
+                Ok(attach(metadata, json!({ "flagged": problem })))
+            }
+        }
+    }
+
+    /// Whether uploads declared as `declared` get the image checks:
+    /// undeclared ones and those of an accepted image format.
+    fn is_image_type(&self, declared: &str) -> bool {
+        declared == "application/octet-stream" || self.allowed.iter().any(|f| f.media_type() == declared)
+    }
+
+    fn check_payload(&self, head: &[u8], metadata: String, declared: &str) -> Result<String, AppError> {
+        if !self.payload_types.iter().any(|pattern| media_type::matches(pattern, declared)) {
+            return Err(AppError(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                format!(
+                    "Upload refused: {} payloads are not accepted. Accepted types: {}, {}.",
+                    declared,
+                    self.allowed.iter().map(|f| f.media_type()).collect::<Vec<_>>().join(", "),
+                    self.payload_types.join(", ")
+                ),
+            ));
+        }
+        if let Some(sniffed) = media_type::sniff(head).filter(|sniffed| !media_type::agrees(declared, sniffed)) {
+            return Err(AppError(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                format!("Upload refused: declared as {} but its content is {}.", declared, sniffed),
+            ));
+        }
+        Ok(attach(metadata, json!({ "format": "payload", "media_type": declared })))
+    }
+
+    /// `check()` on a spooled upload. The spool is left at its start.
+    pub async fn check_spool(&self, spool: &mut Spool, metadata: String, declared: Option<&str>) -> Result<String, AppError> {
+        let mut head = Vec::new();
+        spool.rewind().await?;
+        while head.len() < HEAD_BYTES
+            && let Some(chunk) = spool.read_chunk().await?
+        {
+            head.extend_from_slice(&chunk);
+        }
+        spool.rewind().await?;
+        self.check(&head, metadata, declared)
+    }
+}
+
+/// Text mode: checks that a spooled upload is text, normalizes its line
+/// endings, and records both in `metadata`. Returns the spool to seal, its
+/// hex SHA-256, and the metadata.
+pub async fn check_text(
+    mut spool: Spool,
+    image_hash: String,
+    metadata: String,
+    normalization: Normalization,
+    spool_dir: &Path,
+) -> Result<(Spool, String, String), AppError> {
+    if spool.len() > MAX_TEXT_SIZE {
+        return Err(AppError(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Text mode seals documents of at most {} bytes.", MAX_TEXT_SIZE),
+        ));
+    }
+    let mut document = Vec::with_capacity(spool.len() as usize);
+    spool.rewind().await?;
+    while let Some(chunk) = spool.read_chunk().await? {
+        document.extend_from_slice(&chunk);
+    }
+    if !text::is_text(&document) {
+        return Err(AppError(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Upload refused: text mode needs a UTF-8 document without NUL bytes.".into(),
+        ));
+    }
+    let record = TextRecord::new(&document, normalization);
+    let metadata = attach(metadata, record.to_json());
+    let normalized = normalization.apply(&document);
+    if normalized == document {
+        spool.rewind().await?;
+        return Ok((spool, image_hash, metadata));
+    }
+    info!(
+        line_endings = record.line_endings.name(),
+        normalization = normalization.name(),
+        before = document.len(),
+        after = normalized.len(),
+        "Normalized line endings of a text document."
+    );
+    let mut out = Spool::create(spool_dir).await?;
+    out.write_all(&normalized).await?;
+    out.rewind().await?;
+    Ok((out, hex::encode(Sha256::digest(&normalized)), metadata))
+}
+
+/// Parses a comma-separated list of format names.
+pub fn parse_formats(list: &str) -> anyhow::Result<Vec<ImageFormat>> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| ImageFormat::parse(name).ok_or_else(|| anyhow::anyhow!("unknown image format '{}'", name)))
+        .collect()
+}
+
+/// Parses a comma-separated list of media types and `type/*` patterns.
+pub fn parse_media_types(list: &str) -> anyhow::Result<Vec<String>> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            let normalized = match name.strip_suffix("/*") {
+                Some("*") => Some(name.to_string()),
+                Some(kind) => media_type::normalize(&format!("{}/x", kind)).map(|_| format!("{}/*", kind.to_ascii_lowercase())),
+                None => media_type::normalize(name),
+            };
+            normalized.ok_or_else(|| anyhow::anyhow!("invalid media type '{}'", name))
+        })
+        .collect()
+}
+
+fn attach(metadata: String, block: Value) -> String {
+    match serde_json::from_str::<Value>(&metadata) {
+        Ok(Value::Object(mut object)) => {
+            object.insert("content".to_string(), block);
+            Value::Object(object).to_string()
+        }
+        _ => {
+            warn!("Metadata is not a JSON object; sealing without the content block.");
+            metadata
+        }
+    }
+}
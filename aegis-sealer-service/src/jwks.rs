@@ -0,0 +1,114 @@
+// aegis-sealer-service/src/jwks.rs
+
+// Public key distribution. GET /keys serves every key this service's
+// signatures may carry as a JWK Set (RFC 7517), so verifiers can pin them
+// ahead of time instead of trusting the key embedded in each container.
+//
+// Each key is a P-256 JWK with `use: sig` and `alg: ES256`. Its `kid` is the
+// keyring ID, or the key's fingerprint for signers without a keyring. Extra
+// members give the `fingerprint` (the SHA-256 of the SEC1 key, as shown by
+// `aegis verify`), the `status` (`active`, `pending`, `retired`, or
+// `verify_only` for a key held without its private half) and, for keyring
+// keys, the validity window as RFC 3339 `not_before` and `not_after`.
+// Retired keys stay listed: they still verify what they sealed.
+//
+// With `AEGIS_TRUST_HINT` set, GET /keys/dns serves the same keys as zone
+// file lines, the TXT records to publish under the hint's name so that
+// verifiers resolving it find them (see `aegis_core::dns_trust`).
+
+use crate::{AppError, AppState};
+use aegis_core::{
+    dns_trust,
+    keys::{Fingerprint, KeyringEntry},
+    time::rfc3339,
+};
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use base64ct::{Base64UrlUnpadded, Encoding};
+use p256::ecdsa::VerifyingKey;
+use serde_json::{json, Value};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const CONTENT_TYPE: &str = "application/jwk-set+json";
+
+/// GET /keys
+pub async fn keys_handler(State(state): State<AppState>) -> Result<Response, AppError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let keys: Vec<Value> = match state.signer.keyring() {
+        Some(keyring) => keyring.entries().iter().map(|entry| keyring_jwk(entry, now)).collect(),
+        None => vec![jwk(&state.signer.public_key()?, None, "active")],
+    };
+    Ok((
+        [
+            (header::CONTENT_TYPE, CONTENT_TYPE.to_string()),
+            (header::CACHE_CONTROL, state.config.cache_control.clone()),
+        ],
+        json!({ "keys": keys }).to_string(),
+    )
+        .into_response())
+}
+
+/// GET /keys/dns
+pub async fn dns_records_handler(State(state): State<AppState>) -> Result<Response, AppError> {
+    let hint = state.config.trust_hint.as_ref().ok_or_else(|| {
+        AppError(StatusCode::NOT_FOUND, "No DNS trust hint is configured (AEGIS_TRUST_HINT).".into())
+    })?;
+    let keys: Vec<VerifyingKey> = match state.signer.keyring() {
+        Some(keyring) => keyring.entries().iter().map(|entry| entry.public_key).collect(),
+        None => vec![state.signer.public_key()?],
+    };
+    let zone: String = keys
+        .iter()
+        .map(|key| format!("{}\n", dns_trust::zone_line(hint, &key.to_sec1_bytes())))
+        .collect();
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/plain; charset=utf-8".to_string()),
+            (header::CACHE_CONTROL, state.config.cache_control.clone()),
+        ],
+        zone,
+    )
+        .into_response())
+}
+
+fn keyring_jwk(entry: &KeyringEntry, now: i64) -> Value {
+    let status = if entry.signing_key.is_none() {
+        "verify_only"
+    } else if entry.not_before.is_some_and(|t| now < t) {
+        "pending"
+    } else if entry.is_active(now) {
+        "active"
+    } else {
+        "retired"
+    };
+    let mut key = jwk(&entry.public_key, Some(&entry.id), status);
+    key["not_before"] = json!(entry.not_before.map(timestamp));
+    key["not_after"] = json!(entry.not_after.map(timestamp));
+    key
+}
+
+fn jwk(public_key: &VerifyingKey, id: Option<&str>, status: &str) -> Value {
+    let point = public_key.to_encoded_point(false);
+    let fingerprint = Fingerprint::of(&public_key.to_sec1_bytes()).to_hex();
+    json!({
+        "kty": "EC",
+        "crv": "P-256",
+        "x": Base64UrlUnpadded::encode_string(point.x().expect("uncompressed point")),
+        "y": Base64UrlUnpadded::encode_string(point.y().expect("uncompressed point")),
+        "use": "sig",
+        "alg": "ES256",
+        "kid": id.unwrap_or(&fingerprint),
+        "fingerprint": fingerprint,
+        "status": status,
+    })
+}
+
+fn timestamp(unix_secs: i64) -> String {
+    rfc3339(UNIX_EPOCH + Duration::from_secs(unix_secs.max(0) as u64))
+}
@@ -0,0 +1,1104 @@
+// aegis-sealer-service/src/lib.rs
+
+// The sealing service as a library, so it can be embedded: build the state
+// with `AppState::from_env()`, register `hooks::SealHook`s on it, and get the
+// routes from `router()` or, to wrap the sealing endpoints in your own tower
+// layers or add routes behind the service's auth, from `RouterBuilder`.
+//
+//     let state = AppState::from_env().await?;
+//     state.add_seal_hook(Arc::new(Billing::new()));
+//     let app = RouterBuilder::new(&state)
+//         .seal_layer(TimeoutLayer::new(Duration::from_secs(30)))
+//         .build()?
+//         .with_state(state.clone());
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{Multipart, Path, Query, State},
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+};
+use futures_util::{stream, StreamExt};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{error, info, instrument, warn};
+
+// Import our core Aegis logic
+use aegis_core::{
+    crypto::{self, SigningHasher},
+    format,
+    intoto,
+    keys::Fingerprint,
+    manifest::PayloadManifest,
+    metadata::Metadata,
+};
+use sha2::{Digest, Sha256};
+#[cfg(feature = "verifier")]
+use aegis_core::{format::AegisAncient, levels::VerificationLevel};
+
+mod admission;
+mod cdc;
+mod config;
+pub mod config_file;
+#[cfg(feature = "alloc-stats")]
+pub mod alloc_stats;
+mod audit;
+mod audit_log;
+mod auth;
+mod aws_kms;
+mod azure;
+mod batch;
+pub mod bench;
+mod capabilities;
+mod capture;
+#[cfg(feature = "verifier")]
+pub mod dns;
+mod events;
+mod export;
+mod feed;
+mod gcp_kms;
+mod health;
+pub mod hooks;
+mod http_client;
+mod ingest;
+mod intake;
+mod jwks;
+mod metrics;
+mod mirror;
+mod openapi;
+mod provenance;
+mod quota;
+#[cfg(feature = "verifier")]
+mod reseal;
+#[cfg(feature = "verifier")]
+mod remote_verify;
+mod response_sig;
+mod rollup;
+mod router;
+mod s3;
+pub mod settings;
+mod signer;
+mod sigv4;
+#[cfg(feature = "verifier")]
+mod sla;
+mod spool;
+mod static_docs;
+mod storage;
+mod telemetry;
+mod tenants;
+mod transparency;
+mod tsa;
+mod vault;
+mod verify_notify;
+mod wal;
+mod webhooks;
+mod xmp;
+
+use crate::audit::{AuditAction, AuditStore};
+use crate::audit_log::{AuditLog, Caller};
+use crate::capture::Captures;
+use crate::config::Config;
+use crate::metrics::Metrics;
+use crate::rollup::Rollups;
+use crate::signer::ServiceSigner;
+use crate::spool::Spool;
+use crate::storage::SealedStore;
+use crate::tenants::Tenants;
+use crate::transparency::TransparencyLog;
+use crate::tsa::Tsa;
+use crate::wal::Wal;
+use crate::hooks::{Hooks, SealEvent, SealHook};
+
+pub use crate::router::{router, RouterBuilder};
+
+/// How many recent seal operations are kept in the in-memory audit store.
+const AUDIT_CAPACITY: usize = 10_000;
+
+/// Shared state of the service's handlers, built once at startup. Handlers
+/// read their settings from `config` rather than from the environment.
+#[derive(Clone)]
+pub struct AppState {
+    config: Arc<Config>,
+    metrics: Arc<Metrics>,
+    audit: Arc<AuditStore>,
+    audit_log: Arc<AuditLog>,
+    captures: Arc<Captures>,
+    signer: ServiceSigner,
+    storage: Arc<SealedStore>,
+    tenants: Arc<Tenants>,
+    transparency: Arc<TransparencyLog>,
+    rollups: Arc<Rollups>,
+    wal: Arc<Wal>,
+    hooks: Arc<Hooks>,
+    tsa: Option<Arc<Tsa>>,
+    notifications: Arc<verify_notify::VerifyNotifications>,
+}
+
+impl AppState {
+    /// Builds the service state from the environment: settings, the signer,
+    /// tenant trust, audit store (replayed from the write-ahead log), audit
+    /// and transparency logs, rollups, and storage.
+    pub async fn from_env() -> anyhow::Result<Self> {
+        let signer = ServiceSigner::from_env().await?;
+        let service_keys = signer
+            .known_keys()
+            .map_err(|e| anyhow::anyhow!("signing key unavailable: {}", e.1))?;
+        let tenants = Arc::new(Tenants::from_env(&service_keys)?);
+        let audit = Arc::new(AuditStore::new(AUDIT_CAPACITY));
+        let wal = Arc::new(Wal::open(&audit).await?);
+        let audit_log = Arc::new(AuditLog::open().await?);
+        let transparency = Arc::new(TransparencyLog::open().await?);
+        let rollups = Arc::new(Rollups::open(audit_log.is_enabled()).await?);
+        let config = Config::from_env()?;
+        if !config.cert_chain.is_empty() {
+            let public_key = signer
+                .public_key()
+                .map_err(|e| anyhow::anyhow!("signing key unavailable: {}", e.1))?;
+            aegis_core::x509::check_leaf(&config.cert_chain, &public_key.to_sec1_bytes())
+                .map_err(|e| anyhow::anyhow!("AEGIS_CERT_CHAIN: {}", e))?;
+        }
+        let state = AppState {
+            config: Arc::new(config),
+            metrics: Arc::new(Metrics::default()),
+            audit,
+            audit_log,
+            captures: Arc::new(Captures::from_env()?),
+            signer,
+            storage: Arc::new(SealedStore::from_env()?),
+            tenants,
+            transparency,
+            rollups,
+            wal,
+            hooks: Arc::new(Hooks::default()),
+            tsa: Tsa::from_env().map(Arc::new),
+            notifications: Arc::new(verify_notify::VerifyNotifications::open().await?),
+        };
+        if let Some(webhooks) = &state.config.webhooks {
+            state.add_seal_hook(Arc::new(webhooks::WebhookHook(webhooks.clone())));
+        }
+        #[cfg(feature = "verifier")]
+        if state.config.verify_sla.is_some() {
+            sla::warm_up(&state)?;
+        }
+        if state.config.startup_checks {
+            health::check_dependencies(&state).await?;
+        }
+        Ok(state)
+    }
+
+    /// Registers a hook that runs around every seal (see `hooks`).
+    pub fn add_seal_hook(&self, hook: Arc<dyn SealHook>) {
+        self.hooks.add(hook);
+    }
+
+    /// Starts the background tasks that report on this state.
+    pub fn spawn_background_tasks(&self) {
+        telemetry::spawn(self.clone());
+        rollup::spawn(self.clone());
+    }
+}
+
+async fn root_redirect_handler(State(state): State<AppState>) -> Redirect {
+    Redirect::to(&state.config.root_redirect)
+}
+
+// A simple handler for the cron job endpoint.
+async fn cron_job_handler() -> &'static str {
+    "cron-job successful"
+}
+
+/// Validates metadata sent for sealing. A JSON object must match the
+/// `metadata::Metadata` schema and is rewritten to its canonical form; any
+/// other string is sealed as sent unless structured metadata is required.
+fn check_metadata(config: &config::Config, metadata: String) -> Result<String, AppError> {
+    match Metadata::parse_structured(&metadata) {
+        Ok(Some(structured)) => Ok(structured.to_canonical_json()),
+        Ok(None) if config.require_structured_metadata => Err(AppError(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "Metadata must be a JSON object.".into(),
+        )),
+        Ok(None) => Ok(metadata),
+        Err(e) => Err(AppError(StatusCode::UNPROCESSABLE_ENTITY, e.to_string())),
+    }
+}
+
+#[instrument(skip_all, fields(image_size, metadata_size, heap_in_use, heap_peak))]
+async fn seal_handler(
+    State(state): State<AppState>,
+    caller: Caller,
+    submission: provenance::Submission,
+    Query(query): Query<SealQuery>,
+    mut multipart: Multipart,
+) -> Result<Response, AppError> {
+    info!("Received new request for /seal endpoint.");
+
+    // The payload (the `payload` part, or `image` as before) is streamed to
+    // a spool file as it arrives, so memory use does not grow with upload
+    // size.
+    let mut image: Option<(Spool, String)> = None;
+    // The payload part's file name, recorded in payload manifests and
+    // in-toto subjects.
+    let mut payload_name: Option<String> = None;
+    // The payload's media type, from a `media_type` part or the Content-Type
+    // of a `payload` part; anything but an image skips the image checks
+    // (see `intake`).
+    let mut media_type: Option<String> = None;
+    let mut part_media_type: Option<String> = None;
+    let mut metadata_str: Option<String> = None;
+    // `detached=true` returns a `.aegis.sig` sidecar instead of a container.
+    let mut detached = false;
+    // `external_metadata=true` keeps the metadata in the container header
+    // and signs a reference to it (see `format::FIELD_EXTERNAL_METADATA`).
+    let mut external_metadata = false;
+    // `import_embedded=true` adds the image's XMP/IPTC to the metadata, and
+    // `output=xmp` or `output=c2pa` (also `?format=`) returns a sanitized
+    // image with the seal in XMP or a C2PA manifest (see `xmp`).
+    // `output=manifest` returns a payload manifest for the payload as sent
+    // (see `aegis_core::manifest`), and `output=intoto` an in-toto statement
+    // about it in a DSSE envelope (see `aegis_core::intoto`).
+    let mut import_embedded = false;
+    // `extract_exif=true` fills empty capture fields from the image's EXIF.
+    let mut extract_exif = false;
+    let mut output = query.format.as_deref().map(xmp::Output::parse).transpose()?.unwrap_or(xmp::Output::Container);
+    // `extension:<name>` parts become signed extensions: JSON when the part
+    // is sent as application/json, bytes otherwise.
+    let mut extensions: Vec<format::Extension> = Vec::new();
+    // `text=none|lf|crlf` seals a text document, with its line endings
+    // normalized as given (see `intake::check_text`).
+    let mut text_mode = None;
+    // `compress=metadata|image|all` stores those blocks of the container
+    // as zstd frames (see `format::Compression`).
+    let mut compression = format::Compression::default();
+
+    info!("Processing multipart form data...");
+    while let Some(mut field) = multipart.next_field().await? {
+        let name = field.name().unwrap_or("").to_string();
+
+        if name == "image" || name == "payload" {
+            if name == "payload" {
+                part_media_type = field.content_type().and_then(aegis_core::media_type::normalize);
+            }
+            payload_name = field.file_name().map(str::to_string);
+            let mut spool = Spool::create(&state.config.spool_dir).await?;
+            let mut image_hasher = Sha256::new();
+            while let Some(chunk) = field.chunk().await? {
+                image_hasher.update(&chunk);
+                spool.write_all(&chunk).await?;
+            }
+            let size = spool.len();
+            tracing::Span::current().record("image_size", size);
+            info!(size, field = %name, "Found payload field.");
+            image = Some((spool, hex::encode(image_hasher.finalize())));
+        } else if name == "metadata" {
+            let data = field.bytes().await?;
+            let size = data.len();
+            tracing::Span::current().record("metadata_size", size);
+            info!(size, "Found 'metadata' field.");
+            metadata_str = Some(String::from_utf8(data.to_vec())?);
+        } else if name == "detached" {
+            let value = field.text().await?;
+            detached = matches!(value.trim(), "true" | "1");
+        } else if name == "external_metadata" {
+            let value = field.text().await?;
+            external_metadata = matches!(value.trim(), "true" | "1");
+        } else if let Some(extension) = name.strip_prefix("extension:") {
+            let json = field.content_type().is_some_and(|t| t.starts_with("application/json"));
+            let extension = extension.to_string();
+            let value = field.bytes().await?;
+            extensions.push(if json {
+                let text = std::str::from_utf8(&value)
+                    .map_err(|_| AppError(StatusCode::BAD_REQUEST, format!("Extension '{}' is not UTF-8.", extension)))?;
+                format::Extension::json(&extension, text).map_err(|e| AppError(StatusCode::BAD_REQUEST, e.to_string()))?
+            } else {
+                format::Extension::bytes(&extension, value.to_vec())
+            });
+        } else if name == "import_embedded" {
+            let value = field.text().await?;
+            import_embedded = matches!(value.trim(), "true" | "1");
+        } else if name == "extract_exif" {
+            let value = field.text().await?;
+            extract_exif = matches!(value.trim(), "true" | "1");
+        } else if name == "output" {
+            output = xmp::Output::parse(&field.text().await?)?;
+        } else if name == "text" {
+            let value = field.text().await?;
+            text_mode = Some(aegis_core::text::Normalization::parse(&value).ok_or_else(|| {
+                AppError(StatusCode::BAD_REQUEST, format!("Unknown text mode '{}'; use none, lf or crlf.", value.trim()))
+            })?);
+        } else if name == "media_type" {
+            let value = field.text().await?;
+            media_type = Some(aegis_core::media_type::normalize(&value).ok_or_else(|| {
+                AppError(StatusCode::BAD_REQUEST, format!("Invalid media type '{}'.", value.trim()))
+            })?);
+        } else if name == "compress" {
+            let value = field.text().await?;
+            compression = format::Compression::parse(&value).ok_or_else(|| {
+                AppError(
+                    StatusCode::BAD_REQUEST,
+                    format!("Unknown blocks to compress '{}'; use metadata, image, all or none.", value.trim()),
+                )
+            })?;
+        }
+    }
+
+    let (mut spool, mut image_hash) = image.ok_or_else(|| {
+        AppError(StatusCode::BAD_REQUEST, "Request is missing required 'payload' (or 'image') field.".into())
+    })?;
+    if text_mode.is_some() && media_type.as_deref().is_some_and(|t| t != "text/plain") {
+        return Err(AppError(
+            StatusCode::BAD_REQUEST,
+            "Text mode seals text/plain documents; 'media_type' cannot declare another type.".into(),
+        ));
+    }
+    let media_type = media_type.or(part_media_type);
+    let metadata_str = metadata_str.ok_or_else(|| AppError(StatusCode::BAD_REQUEST, "Request is missing required 'metadata' field.".into()))?;
+    let metadata_str = submission.attach(check_metadata(&state.config, metadata_str)?);
+    let metadata_str = match text_mode {
+        Some(normalization) => {
+            let (text_spool, text_hash, metadata) =
+                intake::check_text(spool, image_hash, metadata_str, normalization, &state.config.spool_dir).await?;
+            (spool, image_hash) = (text_spool, text_hash);
+            metadata
+        }
+        None => state.config.intake.check_spool(&mut spool, metadata_str, media_type.as_deref()).await?,
+    };
+    let metadata_str = if import_embedded { xmp::import(&mut spool, metadata_str).await? } else { metadata_str };
+    let metadata_str = if extract_exif { xmp::extract_exif(&mut spool, metadata_str).await? } else { metadata_str };
+    if output != xmp::Output::Container && detached {
+        return Err(AppError(
+            StatusCode::BAD_REQUEST,
+            "Detached signatures cannot be returned as XMP or C2PA copies, payload manifests or attestations.".into(),
+        ));
+    }
+    if output == xmp::Output::Intoto && text_mode.is_some() {
+        return Err(AppError(
+            StatusCode::BAD_REQUEST,
+            "In-toto attestations are about the payload as sent; they cannot be combined with text mode.".into(),
+        ));
+    }
+    // Payload manifests and attestations are detached signatures in
+    // another form.
+    let detached = detached || output.is_detached();
+    if !compression.is_none() && (detached || output != xmp::Output::Container) {
+        return Err(AppError(
+            StatusCode::BAD_REQUEST,
+            "Only containers can be compressed; 'compress' cannot be combined with detached or copy output.".into(),
+        ));
+    }
+    if compression.image && spool.len() > MAX_COMPRESSED_IMAGE {
+        return Err(AppError(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Images of at most {} bytes can be compressed.", MAX_COMPRESSED_IMAGE),
+        ));
+    }
+    if query.store && (detached || output != xmp::Output::Container) {
+        return Err(AppError(
+            StatusCode::BAD_REQUEST,
+            "Only containers can be stored; 'store' cannot be combined with detached or copy output.".into(),
+        ));
+    }
+    let copy_kind = if output.is_copy() {
+        let (sanitized, sanitized_hash, kind) = xmp::sanitize(&mut spool, &state.config.spool_dir).await?;
+        (spool, image_hash) = (sanitized, sanitized_hash);
+        Some(kind)
+    } else {
+        None
+    };
+    let mut container_header = format::FormatHeader::default();
+    if !extensions.is_empty() {
+        if detached {
+            return Err(AppError(
+                StatusCode::BAD_REQUEST,
+                "Detached signatures cannot carry extensions.".into(),
+            ));
+        }
+        container_header
+            .set_extensions(&extensions)
+            .map_err(|e| AppError(StatusCode::BAD_REQUEST, e.to_string()))?;
+    }
+    let metadata_str = if external_metadata {
+        if detached {
+            return Err(AppError(
+                StatusCode::BAD_REQUEST,
+                "Detached signatures cannot carry external metadata.".into(),
+            ));
+        }
+        container_header.set_external_metadata(&metadata_str)
+    } else {
+        metadata_str
+    };
+
+    // Detached signatures have no header to carry a timestamp.
+    let SpooledSeal { public_key, signature, header: mut container_header, signer } = seal_spooled(
+        &state,
+        &caller,
+        &metadata_str,
+        container_header,
+        &mut spool,
+        &image_hash,
+        !detached,
+    )
+    .await?;
+    #[cfg(feature = "alloc-stats")]
+    {
+        let span = tracing::Span::current();
+        span.record("heap_in_use", alloc_stats::in_use());
+        span.record("heap_peak", alloc_stats::peak());
+    }
+
+    if detached {
+        let fingerprint = Fingerprint::of(&public_key);
+        let sidecar = format::DetachedSignature {
+            public_key: public_key.into_vec(),
+            metadata: metadata_str,
+            signature: signature.to_bytes().to_vec(),
+            image_sha256: hex::decode(&image_hash)?.try_into().expect("SHA-256 is 32 bytes"),
+            image_len: spool.len(),
+        };
+        let name = document_name(payload_name.as_deref());
+        let document = match output {
+            xmp::Output::Manifest => {
+                let document = PayloadManifest::new(&sidecar, payload_name.as_deref(), container_header.key_id()).to_bytes();
+                info!(manifest_size = document.len(), "Payload manifest produced.");
+                Some((document, aegis_core::manifest::manifest_name(name.as_deref().unwrap_or("sealed"))))
+            }
+            xmp::Output::Intoto => {
+                // The envelope is signed by the key that sealed the payload.
+                let unsigned = intoto::UnsignedEnvelope::new(&sidecar, name.as_deref().unwrap_or("payload"));
+                let envelope_signature = signer.sign(&unsigned.signing_message()).await?;
+                let document = unsigned.finish(&envelope_signature).to_line();
+                info!(attestation_size = document.len(), "In-toto attestation produced.");
+                Some((document, intoto::attestation_name(name.as_deref().unwrap_or("sealed"))))
+            }
+            _ => None,
+        };
+        if let Some((document, filename)) = document {
+            let content_length = document.len() as u64;
+            let mut response = container_response(Body::from(document), content_length, &fingerprint, &filename);
+            response.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            return Ok(response);
+        }
+        let sidecar = sidecar.to_bytes();
+        info!(sidecar_size = sidecar.len(), "Detached signature produced.");
+        let content_length = sidecar.len() as u64;
+        let filename = format!("sealed.{}", format::DETACHED_EXTENSION);
+        return Ok(container_response(Body::from(sidecar), content_length, &fingerprint, &filename));
+    }
+
+    // Compression leaves the signature as it is.
+    container_header.set_compression(compression);
+    if compression.image {
+        spool = compress_spooled(spool, &state.config.spool_dir).await?;
+    }
+    let header = format::header_bytes(
+        &container_header,
+        &public_key,
+        &metadata_str,
+        &signature.to_bytes(),
+        spool.len(),
+    );
+    match (output, copy_kind) {
+        (xmp::Output::Xmp, Some(kind)) => {
+            return xmp::sealed_copy_response(spool, kind, &header, &public_key, &signature.to_bytes(), &metadata_str).await;
+        }
+        (xmp::Output::C2pa, Some(kind)) => {
+            return xmp::c2pa_copy_response(spool, kind, &signer, &header, &public_key, &metadata_str).await;
+        }
+        _ => {}
+    }
+    if query.store {
+        return store_spooled(&state, &caller, header, spool, &image_hash, &public_key).await;
+    }
+    Ok(spooled_response(header, spool, &Fingerprint::of(&public_key), "sealed.aegis"))
+}
+
+/// A payload's file name without directories, made safe for a
+/// Content-Disposition header.
+fn document_name(file_name: Option<&str>) -> Option<String> {
+    let name: String = file_name?
+        .rsplit(['/', '\\'])
+        .next()?
+        .chars()
+        .filter(|c| !c.is_control() && *c != '"' && *c != '\\')
+        .collect();
+    (!name.is_empty()).then_some(name)
+}
+
+/// Largest image `compress=image` accepts; it is compressed in memory.
+const MAX_COMPRESSED_IMAGE: u64 = 256 * 1024 * 1024;
+
+/// The spooled image compressed as a zstd frame, in a new spool.
+async fn compress_spooled(mut spool: Spool, spool_dir: &std::path::Path) -> Result<Spool, AppError> {
+    let mut image = Vec::with_capacity(spool.len() as usize);
+    spool.rewind().await?;
+    while let Some(chunk) = spool.read_chunk().await? {
+        image.extend_from_slice(&chunk);
+    }
+    let compressed = aegis_core::zstd::compress(&image);
+    info!(before = image.len(), after = compressed.len(), "Compressed the image block.");
+    let mut out = Spool::create(spool_dir).await?;
+    out.write_all(&compressed).await?;
+    out.rewind().await?;
+    Ok(out)
+}
+
+/// Writes a container whose image is still spooled to the sealed store,
+/// named by the image hash, and returns a receipt saying where it went.
+/// The caller is recorded as its owner (see `verify_notify`).
+async fn store_spooled(
+    state: &AppState,
+    caller: &Caller,
+    header: Vec<u8>,
+    mut spool: Spool,
+    image_hash: &str,
+    public_key: &[u8],
+) -> Result<Response, AppError> {
+    let mut container = header;
+    container.reserve(spool.len() as usize);
+    while let Some(chunk) = spool.read_chunk().await? {
+        container.extend_from_slice(&chunk);
+    }
+    let location = state.storage.put(image_hash, &container).await.map_err(|e| {
+        error!(error = %e, "Failed to store sealed container.");
+        AppError(StatusCode::BAD_GATEWAY, format!("The sealed container could not be stored: {}", e))
+    })?;
+    info!(location = %location, size = container.len(), "Sealed container stored.");
+    if let Err(e) = state.notifications.stored(image_hash, caller).await {
+        warn!(error = %e, "Failed to record the owner of a stored container.");
+    }
+    Ok(axum::Json(SealReceipt {
+        status: "stored",
+        storage: state.storage.kind(),
+        location,
+        object: image_hash.to_string(),
+        retrieval_path: format!("/sealed/{}", image_hash),
+        image_sha256: image_hash.to_string(),
+        sealed_sha256: hex::encode(Sha256::digest(&container)),
+        sealed_size: container.len() as u64,
+        key_fingerprint: Fingerprint::of(public_key).to_hex(),
+    })
+    .into_response())
+}
+
+/// What `?store=true` returns in place of the container.
+#[derive(serde::Serialize)]
+struct SealReceipt {
+    status: &'static str,
+    /// `SealedStore::kind()`.
+    storage: &'static str,
+    location: String,
+    /// The object name in the store: the hex SHA-256 of the image.
+    object: String,
+    retrieval_path: String,
+    image_sha256: String,
+    sealed_sha256: String,
+    sealed_size: u64,
+    key_fingerprint: String,
+}
+
+impl openapi::ApiSchema for SealReceipt {
+    const NAME: &'static str = "SealReceipt";
+
+    fn schema() -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "required": ["status", "storage", "location", "object", "retrieval_path", "image_sha256", "sealed_sha256", "sealed_size", "key_fingerprint"],
+            "properties": {
+                "status": { "const": "stored" },
+                "storage": { "type": "string" },
+                "location": { "type": "string" },
+                "object": { "type": "string" },
+                "retrieval_path": { "type": "string", "description": "Where GET returns the container." },
+                "image_sha256": { "type": "string" },
+                "sealed_sha256": { "type": "string" },
+                "sealed_size": { "type": "integer" },
+                "key_fingerprint": { "type": "string" },
+            },
+        })
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct SealQuery {
+    /// Same as the `output` form field, which overrides it.
+    format: Option<String>,
+    /// `store=true` writes the container to the sealed store and returns a
+    /// receipt instead of the container.
+    #[serde(default)]
+    store: bool,
+}
+
+/// A spooled image signed by `seal_spooled()`.
+struct SpooledSeal {
+    public_key: Box<[u8]>,
+    signature: p256::ecdsa::Signature,
+    header: format::FormatHeader,
+    /// The pinned signer, for anything else that must be signed by the same key.
+    signer: ServiceSigner,
+}
+
+/// Signs a spooled image with `metadata`: runs the seal hooks, logs the seal
+/// ahead in the write-ahead log, signs, optionally timestamps, and records
+/// it in the audit store and audit log. Signed extensions already in `header` are covered
+/// by the signature; the key ID and timestamp are added to it afterwards.
+/// The spool is left positioned at its start.
+async fn seal_spooled(
+    state: &AppState,
+    caller: &Caller,
+    metadata: &str,
+    mut header: format::FormatHeader,
+    spool: &mut Spool,
+    image_hash: &str,
+    timestamp: bool,
+) -> Result<SpooledSeal, AppError> {
+    // The metadata may arrive after the image, so the signing digest is
+    // computed in a second pass over the spooled bytes.
+    info!("Hashing spooled image for signing...");
+    let mut hasher = SigningHasher::new(metadata);
+    spool.rewind().await?;
+    while let Some(chunk) = spool.read_chunk().await? {
+        hasher.update(&chunk);
+    }
+    let digest = crypto::container_digest(&header, &hasher.finalize())?;
+    let mut event = SealEvent {
+        action: AuditAction::Seal.as_str(),
+        tenant: caller.tenant.clone(),
+        request_id: caller.request_id.clone(),
+        caller: caller.principal.clone(),
+        metadata: metadata.to_string(),
+        image_sha256: image_hash.to_string(),
+        image_size: spool.len(),
+        audit_id: None,
+        key_fingerprint: None,
+        key_id: None,
+        delegation: caller.delegation.as_ref().map(|d| d.id.clone()),
+    };
+    // Checked before the hooks so that they only see seals the token allows.
+    if let Some(delegation) = &caller.delegation {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+        if let Err(e) = delegation.permits(metadata, spool.len(), now) {
+            warn!(delegation = %delegation.id, reason = %e, "Seal refused under delegation token.");
+            return Err(AppError(StatusCode::FORBIDDEN, format!("Delegation token {}: {}.", delegation.id, e)));
+        }
+    }
+    state.hooks.before(&event).await?;
+    let signer = state.signer.pin()?;
+    let public_key = signer.public_key()?.to_sec1_bytes();
+    let size = spool.len() as usize;
+    let wal_id = state.wal.begin(AuditAction::Seal, &public_key, metadata, image_hash, size).await?;
+    let signature = match signer.sign(&digest).await {
+        Ok(signature) => signature,
+        Err(e) => {
+            state.wal.abort(wal_id, &e.1).await;
+            return Err(e);
+        }
+    };
+    if let Some(id) = signer.key_id() {
+        header.set_key_id(id);
+    }
+    state.config.certify(&mut header, &public_key);
+    if timestamp && let Err(e) = tsa::stamp(state.tsa.as_deref(), &mut header, &signature.to_bytes()).await {
+        state.wal.abort(wal_id, &e.1).await;
+        return Err(e);
+    }
+    let record = state.audit.record_streamed(
+        AuditAction::Seal,
+        &public_key,
+        metadata,
+        image_hash.to_string(),
+        size,
+    );
+    state.wal.complete(wal_id, record.id).await;
+    state.metrics.record_seal(spool.len());
+    info!(audit_id = record.id, "Seal recorded in audit store.");
+    event.audit_id = Some(record.id);
+    event.key_fingerprint = Some(Fingerprint::of(&public_key).to_hex());
+    event.key_id = signer.key_id().map(str::to_string);
+    state.audit_log.append(&event).await?;
+    if let Some(proof) = state.transparency.append(image_hash).await? {
+        header.set_log_inclusion(&proof);
+    }
+    state.hooks.after(&event).await;
+    spool.rewind().await?;
+    Ok(SpooledSeal { public_key, signature, header, signer })
+}
+
+/// Streams a container whose image is still on disk: the header block
+/// bytes, then the spooled image read in chunks.
+fn spooled_response(header: Vec<u8>, spool: Spool, fingerprint: &Fingerprint, filename: &str) -> Response {
+    info!(key_fingerprint = %fingerprint.to_hex_groups(), key_words = %fingerprint.to_words(), "Sealed with key.");
+    let content_length = header.len() as u64 + spool.len();
+    let body = stream::once(async move { Ok::<_, std::io::Error>(Bytes::from(header)) }).chain(
+        stream::try_unfold(spool, |mut spool| async move {
+            Ok(spool.read_chunk().await?.map(|chunk| (Bytes::from(chunk), spool)))
+        }),
+    );
+    info!(content_length, "Data successfully sealed; streaming response.");
+    container_response(Body::from_stream(body), content_length, fingerprint, filename)
+}
+
+fn container_response(body: Body, content_length: u64, fingerprint: &Fingerprint, filename: &str) -> Response {
+    (
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "application/octet-stream"),
+            (
+                header::CONTENT_DISPOSITION,
+                &format!("attachment; filename=\"{}\"", filename),
+            ),
+            (
+                header::HeaderName::from_static("x-aegis-key-fingerprint"),
+                &fingerprint.to_string(),
+            ),
+            (header::CONTENT_LENGTH, &content_length.to_string()),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+/// Checks a sealed container sent either as the raw request body or as the
+/// first file part of a multipart form, and reports what it found. A
+/// multipart form with `signature` and `original` parts checks a detached
+/// `.aegis.sig` sidecar against the original instead, and `manifest` and
+/// `original` parts a payload manifest (see `aegis_core::manifest`). A JSON
+/// body `{"url": ..., "mode": "quick" | "full"}` verifies a remote container
+/// with ranged reads instead. A JPEG or PNG copy returned by `output=xmp` is
+/// checked like the container it was made from. The verdict is judged
+/// against the trust of the tenant the API key belongs to. In SLA mode
+/// (see `sla`) remote verification and oversized bodies are refused and the
+/// verdict carries a latency breakdown. `?explain=true` adds an account of
+/// exactly which bytes of a `.aegis` container the signature covers, and
+/// `?lang=TAG` the labels of multi-language metadata in that language (see
+/// `Metadata::language_view()`). `?level=quick|standard|forensic` runs that
+/// level's checks (see `aegis_core::levels`) on a container and lists their
+/// outcomes under `verification`; from `standard` up the tenant's key trust
+/// and revocation list are among them.
+#[cfg(feature = "verifier")]
+#[instrument(skip_all, fields(container_size))]
+async fn verify_handler(
+    State(state): State<AppState>,
+    Query(query): Query<VerifyQuery>,
+    request: axum::extract::Request,
+) -> Result<Response, AppError> {
+    use axum::extract::FromRequest;
+
+    info!("Received new request for /verify endpoint.");
+    let level = query
+        .level
+        .as_deref()
+        .map(|name| {
+            VerificationLevel::parse(name).ok_or_else(|| {
+                AppError(
+                    StatusCode::BAD_REQUEST,
+                    format!("Unknown level '{}'; expected quick, standard or forensic.", name),
+                )
+            })
+        })
+        .transpose()?;
+    let mut watch = sla::Stopwatch::start();
+    let tenant = request.extensions().get::<auth::Tenant>().map(|t| t.0.clone());
+    let inflate_limit = request
+        .extensions()
+        .get::<auth::InflateLimit>()
+        .map_or(state.config.max_inflate, |limit| limit.0.min(state.config.max_inflate));
+    // For the verification notifications of stored seals, whose opt-out and
+    // geo headers are read once the verdict is known.
+    let headers = request.headers().clone();
+    let content_type = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_string();
+    if let Some(sla) = &state.config.verify_sla {
+        if content_type.starts_with("application/json") {
+            return Err(AppError(
+                StatusCode::BAD_REQUEST,
+                "Remote verification is not available in SLA mode.".into(),
+            ));
+        }
+        let length = request
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok())
+            .ok_or_else(|| AppError(StatusCode::LENGTH_REQUIRED, "SLA mode needs a Content-Length.".into()))?;
+        if length > sla.max_bytes {
+            return Err(AppError(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("SLA mode verifies at most {} bytes.", sla.max_bytes),
+            ));
+        }
+    }
+    let no_levels = || AppError(StatusCode::BAD_REQUEST, "Verification levels need a container.".into());
+    if content_type.starts_with("application/json") {
+        if level.is_some() {
+            return Err(no_levels());
+        }
+        let axum::Json(remote) = axum::Json::<remote_verify::RemoteRequest>::from_request(request, &())
+            .await
+            .map_err(|e| AppError(StatusCode::BAD_REQUEST, e.body_text()))?;
+        let default_mode = state.tenants.remote_mode(tenant.as_deref()).unwrap_or_default();
+        let report = remote_verify::verify(&state.config, remote, default_mode, inflate_limit).await?;
+        let judgement = state.tenants.judge(tenant.as_deref(), &report.key_fingerprint, report.signature_valid);
+        return Ok(axum::Json(tenants::Judged { report, judgement }).into_response());
+    }
+    let container = if content_type.starts_with("multipart/form-data") {
+        let mut multipart = Multipart::from_request(request, &()).await?;
+        let mut parts = Vec::new();
+        while let Some(field) = multipart.next_field().await? {
+            let name = field.name().unwrap_or("").to_string();
+            parts.push((name, field.bytes().await?));
+        }
+        let part = |wanted: &str| parts.iter().find(|(name, _)| name == wanted).map(|(_, bytes)| bytes);
+        if let Some(sidecar) = part("signature").or_else(|| part("manifest")) {
+            if level.is_some() {
+                return Err(no_levels());
+            }
+            let original = part("original").ok_or_else(|| {
+                AppError(StatusCode::BAD_REQUEST, "Detached verification needs an 'original' part.".into())
+            })?;
+            watch.lap("read");
+            return verify_detached(&state, tenant, sidecar, original, watch);
+        }
+        parts.into_iter().next().map(|(_, bytes)| bytes).ok_or_else(|| {
+            AppError(StatusCode::BAD_REQUEST, "Multipart request contains no file part.".into())
+        })?
+    } else {
+        Bytes::from_request(request, &()).await?
+    };
+    tracing::Span::current().record("container_size", container.len());
+    watch.lap("read");
+
+    let embedded = aegis_core::xmp::ImageKind::sniff(&container).is_some();
+    if query.explain && embedded {
+        return Err(AppError(
+            StatusCode::BAD_REQUEST,
+            "Explain mode needs a .aegis container, not an image carrying one.".into(),
+        ));
+    }
+    let ancient = if embedded {
+        aegis_core::xmp::extract_sealed(&container, inflate_limit).map_err(|e| {
+            warn!(error = %e, "Submitted image carries no readable seal.");
+            AppError(StatusCode::UNPROCESSABLE_ENTITY, format!("Not a sealed XMP or C2PA copy: {}", e))
+        })?
+    } else {
+        AegisAncient::read_limited(&mut &container[..], inflate_limit).map_err(|e| {
+            warn!(error = %e, "Submitted container could not be parsed.");
+            AppError(StatusCode::UNPROCESSABLE_ENTITY, format!("Not a valid .aegis container: {}", e))
+        })?
+    };
+    watch.lap("parse");
+    let (anchors, tsa) = (&state.config.trust_anchors, &state.config.tsa_trust);
+    let (report, mut checks) = match level {
+        Some(level) => aegis_core::levels::verify(&ancient, level, anchors, tsa)
+            .map(|(report, checks)| (report, Some(checks))),
+        None => aegis_core::crypto::verify_with_anchors(&ancient, anchors, tsa).map(|report| (report, None)),
+    }
+    .map_err(|e| AppError(StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))?;
+    watch.lap("verify");
+    // External metadata that does not match its signed reference fails the
+    // container as a bad signature would.
+    let contents_valid = report.signature_valid && report.external_metadata_valid != Some(false);
+    let mut judgement = state.tenants.judge(tenant.as_deref(), &report.key_fingerprint, Some(contents_valid));
+    if !state.config.endorsement_roots.is_empty() {
+        let chain = aegis_core::endorsement::verify_container(
+            &ancient,
+            &report,
+            &state.config.endorsements,
+            &state.config.endorsement_roots,
+        )
+        .map_err(|e| AppError(StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))?;
+        judgement.require_endorsement(chain);
+    }
+    if let Some(checks) = checks.as_mut().filter(|c| c.level >= VerificationLevel::Standard) {
+        tenants::add_checks(checks, &judgement);
+    }
+    watch.lap("judge");
+    info!(
+        signature_valid = report.signature_valid,
+        key_fingerprint = %report.key_fingerprint,
+        verdict = ?judgement.verdict,
+        tenant = tenant.as_deref().unwrap_or("-"),
+        "Container verified."
+    );
+    if state.notifications.any_registered().await {
+        let object = hex::encode(Sha256::digest(&ancient.image_data));
+        let verdict = serde_json::to_value(judgement.verdict).unwrap_or_default();
+        state.notifications.verified(&object, verdict.as_str().unwrap_or_default(), &headers).await;
+    }
+    let language_view = query
+        .lang
+        .as_deref()
+        .and_then(|lang| Metadata::parse_structured(&report.metadata).ok().flatten()?.language_view(lang));
+    if query.explain || language_view.is_some() || checks.is_some() {
+        let mut value = serde_json::to_value(tenants::Judged { report, judgement })
+            .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if let Some(checks) = checks {
+            value["verification"] = serde_json::json!(checks);
+        }
+        if query.explain {
+            let explanation = aegis_core::explain::explain(&ancient)
+                .map_err(|e| AppError(StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))?;
+            value["explanation"] = explanation.to_json();
+        }
+        if let Some(view) = language_view {
+            value["language_view"] = view;
+        }
+        return Ok(axum::Json(value).into_response());
+    }
+    verdict_response(&state, tenants::Judged { report, judgement }, &watch)
+}
+
+#[cfg(feature = "verifier")]
+#[derive(serde::Deserialize)]
+struct VerifyQuery {
+    /// Adds an `explanation` of which bytes the signature covers (see
+    /// `aegis_core::explain`). Only for `.aegis` containers.
+    #[serde(default)]
+    explain: bool,
+    /// Adds a `language_view` of multi-language metadata in the language
+    /// best matching this BCP 47 tag.
+    lang: Option<String>,
+    /// Adds the outcome of each check of this verification level.
+    level: Option<String>,
+}
+
+#[cfg(feature = "verifier")]
+fn verify_detached(
+    state: &AppState,
+    tenant: Option<String>,
+    sidecar: &[u8],
+    original: &[u8],
+    mut watch: sla::Stopwatch,
+) -> Result<Response, AppError> {
+    // A payload manifest carries a detached signature as JSON.
+    let detached = if aegis_core::manifest::sniff(sidecar) {
+        PayloadManifest::parse(sidecar).and_then(|manifest| manifest.detached()).map_err(|e| {
+            AppError(StatusCode::UNPROCESSABLE_ENTITY, format!("Not a valid payload manifest: {}", e))
+        })?
+    } else {
+        format::DetachedSignature::parse(sidecar).map_err(|e| {
+            AppError(StatusCode::UNPROCESSABLE_ENTITY, format!("Not a valid .aegis.sig file: {}", e))
+        })?
+    };
+    watch.lap("parse");
+    let report = aegis_core::crypto::verify_detached(&detached, &mut &original[..])
+        .map_err(|e| AppError(StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))?;
+    watch.lap("verify");
+    let mut judgement = state.tenants.judge(tenant.as_deref(), &report.key_fingerprint, Some(report.signature_valid));
+    if !state.config.endorsement_roots.is_empty() {
+        // A sidecar carries no endorsements or timestamp of its own.
+        judgement.require_endorsement(aegis_core::endorsement::verify_chain(
+            &detached.public_key,
+            &state.config.endorsements,
+            &state.config.endorsement_roots,
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or(0),
+        ));
+    }
+    if report.text.as_ref().is_some_and(|text| text.differs_only_in_line_endings) {
+        judgement
+            .warnings
+            .push("The document differs from the one signed only in its line endings.".to_string());
+    }
+    watch.lap("judge");
+    info!(
+        signature_valid = report.signature_valid,
+        key_fingerprint = %report.key_fingerprint,
+        verdict = ?judgement.verdict,
+        "Detached signature verified."
+    );
+    verdict_response(state, tenants::Judged { report, judgement }, &watch)
+}
+
+/// The verdict as JSON, with its latency breakdown in SLA mode.
+#[cfg(feature = "verifier")]
+fn verdict_response<T: serde::Serialize>(
+    state: &AppState,
+    judged: tenants::Judged<T>,
+    watch: &sla::Stopwatch,
+) -> Result<Response, AppError> {
+    let Some(sla) = &state.config.verify_sla else {
+        return Ok(axum::Json(judged).into_response());
+    };
+    let mut value = serde_json::to_value(&judged)
+        .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    value["latency"] = watch.report(sla);
+    Ok(([("server-timing", watch.server_timing())], axum::Json(value)).into_response())
+}
+
+/// Returns a container previously written to the sealed store.
+async fn sealed_download_handler(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Response, AppError> {
+    let bytes = state
+        .storage
+        .get(&name)
+        .await
+        .map_err(|e| AppError(StatusCode::BAD_REQUEST, e.to_string()))?
+        .ok_or_else(|| AppError(StatusCode::NOT_FOUND, format!("No sealed container named '{}'.", name)))?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}.aegis\"", name)),
+        ],
+        bytes,
+    )
+        .into_response())
+}
+
+/// Streams a sealed container back to the client as a file download.
+#[cfg(feature = "verifier")]
+fn sealed_response(ancient: AegisAncient, filename: &str) -> Result<Response, AppError> {
+    let fingerprint = Fingerprint::of(&ancient.public_key);
+    info!(key_fingerprint = %fingerprint.to_hex_groups(), key_words = %fingerprint.to_words(), "Sealed with key.");
+
+    // The container is streamed straight from its blocks with a known
+    // Content-Length, so the image is never copied into a second buffer.
+    let segments = ancient.into_segments()?;
+    let content_length = segments.iter().map(|segment| segment.len() as u64).sum();
+    let segments = segments.into_iter().map(|segment| Ok::<_, std::convert::Infallible>(Bytes::from(segment)));
+    info!(content_length, "Data successfully sealed; streaming response.");
+    Ok(container_response(Body::from_stream(stream::iter(segments)), content_length, &fingerprint, filename))
+}
+
+/// An error response: the status code and a plain-text message.
+#[derive(Debug)]
+pub struct AppError(pub StatusCode, pub String);
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        (self.0, self.1).into_response()
+    }
+}
+
+impl<E> From<E> for AppError
+where
+    E: Into<anyhow::Error>,
+{
+    fn from(err: E) -> Self {
+        let anyhow_err = err.into();
+        // Reading a body fails with the caller's status, 413 for a body cut
+        // off at its limit (see `auth::enforce`), wherever it was read.
+        if anyhow_err.chain().any(|e| e.is::<http_body_util::LengthLimitError>()) {
+            return Self(StatusCode::PAYLOAD_TOO_LARGE, "Request body exceeds the size limit.".into());
+        }
+        if let Some(rejection) = anyhow_err.downcast_ref::<axum::extract::rejection::BytesRejection>() {
+            return Self(rejection.status(), rejection.body_text());
+        }
+        if let Some(error) = anyhow_err.downcast_ref::<axum::extract::multipart::MultipartError>() {
+            return Self(error.status(), error.body_text());
+        }
+        error!(error = %anyhow_err, "An internal application error occurred.");
+        capture::note_error(&anyhow_err);
+        Self(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Something went wrong: {}", anyhow_err),
+        )
+    }
+}
@@ -0,0 +1,66 @@
+// aegis-sealer-service/src/main.rs
+
+use aegis_core::accel;
+use aegis_sealer_service::settings::{self, EnvFile, Severity};
+use aegis_sealer_service::{router, AppState};
+use std::env;
+use std::net::SocketAddr;
+use tracing::{info, instrument, warn};
+
+#[cfg(feature = "alloc-stats")]
+#[global_allocator]
+static GLOBAL: aegis_sealer_service::alloc_stats::TrackingAllocator =
+    aegis_sealer_service::alloc_stats::TrackingAllocator;
+
+#[tokio::main]
+#[instrument]
+async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+
+    info!("Attempting to load .env file...");
+    let env_file = EnvFile::load()?;
+    match &env_file {
+        Some(file) => info!(path = %file.path.display(), ".env file loaded successfully."),
+        None => warn!(".env file not found. Service will rely on system environment variables."),
+    };
+    // The config file fills in what the environment and .env leave unset.
+    let config_file = aegis_sealer_service::config_file::load()?;
+    if let Some(file) = &config_file {
+        info!(path = %file.path.display(), "Config file loaded.");
+    }
+
+    // Reject settings the service would misread before anything reads them.
+    let mut invalid = Vec::new();
+    let files: Vec<&EnvFile> = env_file.iter().chain(&config_file).collect();
+    for problem in settings::check(&files) {
+        match problem.severity {
+            Severity::Error => invalid.push(problem.to_string()),
+            Severity::Warning => warn!("{}", problem),
+        }
+    }
+    if !invalid.is_empty() {
+        anyhow::bail!("invalid configuration:\n  {}", invalid.join("\n  "));
+    }
+
+    info!(backend = accel::sha256_backend(), "SHA-256 hardware acceleration probe complete.");
+
+    let state = AppState::from_env().await?;
+    let app = router(&state)?.with_state(state.clone());
+    state.spawn_background_tasks();
+
+    let address = match env::var("AEGIS_LISTEN") {
+        Ok(address) => address,
+        Err(_) => format!("0.0.0.0:{}", env::var("PORT").unwrap_or_else(|_| "10000".to_string())),
+    };
+    let listener = tokio::net::TcpListener::bind(&address).await?;
+    info!("✅ Aegis Sealer listening on {}", listener.local_addr()?);
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,203 @@
+// aegis-sealer-service/src/metrics.rs
+
+// Counters kept in `AppState` for the lifetime of the process. They are
+// aggregates only, never payloads or metadata, and are reported by
+// telemetry and, in the Prometheus text format, at `GET /metrics`:
+//
+// - `aegis_http_responses_total{class}`: every response, by status class;
+// - `aegis_seal_requests_total{endpoint}`: requests to the sealing endpoints;
+// - `aegis_seal_failures_total{endpoint,class}`: those that failed, by
+//   `failure_class()`;
+// - `aegis_seal_duration_seconds{endpoint}`: histogram of the time until
+//   the response started (a streamed container body is not included);
+// - `aegis_seals_total` and `aegis_sealed_bytes_total`: containers signed
+//   and the payload bytes in them, counted by the handlers.
+//
+// Like other routes, /metrics needs an API key once keys are configured;
+// list it in `AEGIS_ROUTE_ACCESS` as `/metrics=public` to scrape without one.
+
+use crate::AppState;
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Upper bounds, in seconds, of the seal latency histogram buckets.
+const LATENCY_BUCKETS: [f64; 12] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+#[derive(Default)]
+struct Histogram {
+    /// Observations at or below each bound, not cumulative.
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        if let Some(i) = LATENCY_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.buckets[i] += 1;
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+}
+
+#[derive(Default)]
+struct EndpointStats {
+    requests: u64,
+    failures: BTreeMap<&'static str, u64>,
+    latency: Histogram,
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    responses_2xx: AtomicU64,
+    responses_4xx: AtomicU64,
+    responses_5xx: AtomicU64,
+    seals: AtomicU64,
+    sealed_bytes: AtomicU64,
+    endpoints: Mutex<BTreeMap<String, EndpointStats>>,
+}
+
+impl Metrics {
+    /// Response counts by status class.
+    pub fn responses(&self) -> Value {
+        json!({
+            "2xx": self.responses_2xx.load(Ordering::Relaxed),
+            "4xx": self.responses_4xx.load(Ordering::Relaxed),
+            "5xx": self.responses_5xx.load(Ordering::Relaxed),
+        })
+    }
+
+    /// Counts a signed container holding `payload_bytes` of payload.
+    pub fn record_seal(&self, payload_bytes: u64) {
+        self.seals.fetch_add(1, Ordering::Relaxed);
+        self.sealed_bytes.fetch_add(payload_bytes, Ordering::Relaxed);
+    }
+
+    fn observe(&self, endpoint: &str, status: StatusCode, elapsed: Duration) {
+        let mut endpoints = self.endpoints.lock().unwrap();
+        let stats = endpoints.entry(endpoint.to_string()).or_default();
+        stats.requests += 1;
+        if let Some(class) = failure_class(status) {
+            *stats.failures.entry(class).or_default() += 1;
+        }
+        stats.latency.observe(elapsed.as_secs_f64());
+    }
+
+    /// Renders every metric in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        family(&mut out, "aegis_http_responses_total", "counter", "HTTP responses by status class.");
+        for (class, counter) in [
+            ("2xx", &self.responses_2xx),
+            ("4xx", &self.responses_4xx),
+            ("5xx", &self.responses_5xx),
+        ] {
+            let _ = writeln!(out, "aegis_http_responses_total{{class=\"{}\"}} {}", class, counter.load(Ordering::Relaxed));
+        }
+        family(&mut out, "aegis_seals_total", "counter", "Containers signed.");
+        let _ = writeln!(out, "aegis_seals_total {}", self.seals.load(Ordering::Relaxed));
+        family(&mut out, "aegis_sealed_bytes_total", "counter", "Payload bytes in signed containers.");
+        let _ = writeln!(out, "aegis_sealed_bytes_total {}", self.sealed_bytes.load(Ordering::Relaxed));
+
+        let endpoints = self.endpoints.lock().unwrap();
+        family(&mut out, "aegis_seal_requests_total", "counter", "Requests to the sealing endpoints.");
+        for (endpoint, stats) in endpoints.iter() {
+            let _ = writeln!(out, "aegis_seal_requests_total{{endpoint=\"{}\"}} {}", endpoint, stats.requests);
+        }
+        family(&mut out, "aegis_seal_failures_total", "counter", "Failed sealing requests by error class.");
+        for (endpoint, stats) in endpoints.iter() {
+            for (class, count) in &stats.failures {
+                let _ = writeln!(
+                    out,
+                    "aegis_seal_failures_total{{endpoint=\"{}\",class=\"{}\"}} {}",
+                    endpoint, class, count
+                );
+            }
+        }
+        family(&mut out, "aegis_seal_duration_seconds", "histogram", "Time until a sealing response starts.");
+        for (endpoint, stats) in endpoints.iter() {
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(stats.latency.buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    out,
+                    "aegis_seal_duration_seconds_bucket{{endpoint=\"{}\",le=\"{}\"}} {}",
+                    endpoint, bound, cumulative
+                );
+            }
+            let histogram = &stats.latency;
+            let _ = writeln!(
+                out,
+                "aegis_seal_duration_seconds_bucket{{endpoint=\"{}\",le=\"+Inf\"}} {}",
+                endpoint, histogram.count
+            );
+            let _ = writeln!(out, "aegis_seal_duration_seconds_sum{{endpoint=\"{}\"}} {}", endpoint, histogram.sum);
+            let _ = writeln!(out, "aegis_seal_duration_seconds_count{{endpoint=\"{}\"}} {}", endpoint, histogram.count);
+        }
+        out
+    }
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}\n# TYPE {} {}", name, help, name, kind);
+}
+
+/// Classifies a sealing response that failed, or `None` for a success.
+fn failure_class(status: StatusCode) -> Option<&'static str> {
+    Some(match status.as_u16() {
+        100..=399 => return None,
+        400 | 415 | 422 => "bad_request",
+        401 | 403 => "unauthorized",
+        413 => "too_large",
+        429 | 503 => "overloaded",
+        502 | 504 => "upstream",
+        400..=499 => "rejected",
+        _ => "internal",
+    })
+}
+
+/// Middleware counting responses by status class.
+pub async fn count_responses(State(metrics): State<Arc<Metrics>>, request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    let counter = match response.status().as_u16() {
+        200..=299 => &metrics.responses_2xx,
+        400..=499 => &metrics.responses_4xx,
+        500..=599 => &metrics.responses_5xx,
+        _ => return response,
+    };
+    counter.fetch_add(1, Ordering::Relaxed);
+    response
+}
+
+/// Middleware for the sealing endpoints: counts requests and failures and
+/// times them, labelled with the matched route.
+pub async fn instrument_seals(State(metrics): State<Arc<Metrics>>, request: Request, next: Next) -> Response {
+    let endpoint = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+    let started = Instant::now();
+    let response = next.run(request).await;
+    metrics.observe(&endpoint, response.status(), started.elapsed());
+    response
+}
+
+pub async fn metrics_handler(State(state): State<AppState>) -> Response {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(),
+    )
+        .into_response()
+}
@@ -0,0 +1,153 @@
+// aegis-sealer-service/src/mirror.rs
+
+// Request mirroring for safe rollouts. When `AEGIS_SHADOW_URL` is set, each
+// request through this layer is replayed asynchronously against the shadow
+// deployment with the same method, path, headers and body. The shadow's
+// status and response-body hash are compared with the primary's, and the
+// outcome is counted; the client only ever sees the primary response.
+
+use crate::http_client;
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::header,
+    middleware::Next,
+    response::Response,
+};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+
+/// Upper bound on bodies buffered for mirroring; matches the service body limit.
+const MAX_MIRRORED_BODY: usize = 100 * 1024 * 1024;
+
+static SEEN: AtomicU64 = AtomicU64::new(0);
+static MIRRORED: AtomicU64 = AtomicU64::new(0);
+static MATCHED: AtomicU64 = AtomicU64::new(0);
+static DIVERGED: AtomicU64 = AtomicU64::new(0);
+static SHADOW_FAILED: AtomicU64 = AtomicU64::new(0);
+
+// Hop-by-hop and framing headers are recomputed by the shadow request.
+const SKIPPED_HEADERS: [header::HeaderName; 4] = [
+    header::HOST,
+    header::CONTENT_LENGTH,
+    header::CONNECTION,
+    header::TRANSFER_ENCODING,
+];
+
+pub struct Mirror {
+    shadow_url: Option<String>,
+    sample_percent: u64,
+}
+
+impl Mirror {
+    /// Reads `AEGIS_SHADOW_URL` (base URL of the shadow deployment) and
+    /// `AEGIS_SHADOW_SAMPLE_PERCENT` (default 100).
+    pub fn from_env() -> anyhow::Result<Self> {
+        let shadow_url = env::var("AEGIS_SHADOW_URL")
+            .ok()
+            .map(|url| url.trim_end_matches('/').to_string());
+        let sample_percent = match env::var("AEGIS_SHADOW_SAMPLE_PERCENT") {
+            Ok(v) => v
+                .parse::<u64>()
+                .ok()
+                .filter(|p| *p <= 100)
+                .ok_or_else(|| anyhow::anyhow!("AEGIS_SHADOW_SAMPLE_PERCENT must be 0-100, got {:?}", v))?,
+            Err(_) => 100,
+        };
+        if let Some(url) = &shadow_url {
+            info!(shadow_url = %url, sample_percent, "Request mirroring enabled.");
+        }
+        Ok(Mirror { shadow_url, sample_percent })
+    }
+
+    fn sampled(&self) -> bool {
+        SEEN.fetch_add(1, Ordering::Relaxed) % 100 < self.sample_percent
+    }
+}
+
+/// Divergence counters, reported in telemetry heartbeats.
+pub fn stats() -> Value {
+    json!({
+        "mirrored": MIRRORED.load(Ordering::Relaxed),
+        "matched": MATCHED.load(Ordering::Relaxed),
+        "diverged": DIVERGED.load(Ordering::Relaxed),
+        "shadow_failed": SHADOW_FAILED.load(Ordering::Relaxed),
+    })
+}
+
+/// Middleware that duplicates requests to the shadow deployment.
+pub async fn shadow(State(mirror): State<Arc<Mirror>>, request: Request, next: Next) -> Response {
+    let Some(shadow_url) = mirror.shadow_url.clone() else {
+        return next.run(request).await;
+    };
+    if !mirror.sampled() {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let body = match to_bytes(body, MAX_MIRRORED_BODY).await {
+        Ok(body) => body,
+        // Let the handler produce its usual error for oversized bodies.
+        Err(_) => return next.run(Request::from_parts(parts, Body::empty())).await,
+    };
+    let method = parts.method.to_string();
+    let url = format!(
+        "{}{}",
+        shadow_url,
+        parts.uri.path_and_query().map(|p| p.as_str()).unwrap_or("/")
+    );
+    let headers: Vec<(String, String)> = parts
+        .headers
+        .iter()
+        .filter(|(name, _)| !SKIPPED_HEADERS.contains(name))
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+
+    let response = next.run(Request::from_parts(parts, Body::from(body.clone()))).await;
+
+    // The primary response is buffered so its hash can be compared.
+    let (parts, primary_body) = response.into_parts();
+    let primary_body = match to_bytes(primary_body, MAX_MIRRORED_BODY).await {
+        Ok(body) => body,
+        Err(e) => {
+            warn!(error = %e, "Failed to buffer primary response for mirroring.");
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+    let primary_status = parts.status.as_u16();
+    let primary_hash = Sha256::digest(&primary_body);
+
+    tokio::spawn(async move {
+        MIRRORED.fetch_add(1, Ordering::Relaxed);
+        let header_refs: Vec<(&str, &str)> = headers.iter().map(|(n, v)| (n.as_str(), v.as_str())).collect();
+        match http_client::request(&method, &url, &header_refs, &body, MAX_MIRRORED_BODY).await {
+            Ok(shadow) => {
+                let shadow_hash = Sha256::digest(&shadow.body);
+                if shadow.status == primary_status && shadow_hash == primary_hash {
+                    MATCHED.fetch_add(1, Ordering::Relaxed);
+                    debug!(url = %url, "Shadow response matched.");
+                } else {
+                    DIVERGED.fetch_add(1, Ordering::Relaxed);
+                    warn!(
+                        url = %url,
+                        primary_status,
+                        shadow_status = shadow.status,
+                        primary_sha256 = %hex::encode(primary_hash),
+                        shadow_sha256 = %hex::encode(shadow_hash),
+                        "Shadow response diverged."
+                    );
+                }
+            }
+            Err(e) => {
+                SHADOW_FAILED.fetch_add(1, Ordering::Relaxed);
+                warn!(url = %url, error = %e, "Shadow request failed.");
+            }
+        }
+    });
+
+    Response::from_parts(parts, Body::from(primary_body))
+}
@@ -0,0 +1,329 @@
+// aegis-sealer-service/src/openapi.rs
+
+// The OpenAPI 3.1 description of the HTTP API, so clients can generate
+// their requests rather than hand-roll multipart forms and guess at
+// response shapes. Like /capabilities it is built once when the router is
+// built, from the same endpoint list and access policy, and served from
+// GET /openapi.json; GET /docs serves Swagger UI pointed at it, with its
+// script and stylesheet from `SWAGGER_UI_CDN`.
+//
+// JSON response bodies are typed structs implementing `ApiSchema`, each
+// with its schema beside it so the two change together. Endpoints without
+// one are described by their summary and status codes only. Errors are
+// plain-text messages (see `AppError`).
+
+use crate::{
+    auth::{Access, AuthPolicy},
+    capabilities,
+};
+use serde_json::{json, Map, Value};
+
+pub const SWAGGER_UI_CDN: &str = "https://unpkg.com/swagger-ui-dist@5";
+
+/// A JSON response body with an OpenAPI schema.
+pub trait ApiSchema {
+    /// Its name under `components.schemas`.
+    const NAME: &'static str;
+    fn schema() -> Value;
+}
+
+fn reference<T: ApiSchema>() -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", T::NAME) })
+}
+
+fn json_response<T: ApiSchema>(description: &str) -> Value {
+    json!({ "description": description, "content": { "application/json": { "schema": reference::<T>() } } })
+}
+
+fn error_response(description: &str) -> Value {
+    json!({ "description": description, "content": { "text/plain": { "schema": { "type": "string" } } } })
+}
+
+fn schemas() -> Map<String, Value> {
+    let mut schemas = Map::new();
+    let mut add = |name: &str, schema: Value| {
+        schemas.insert(name.to_string(), schema);
+    };
+    add(crate::SealReceipt::NAME, crate::SealReceipt::schema());
+    add(crate::health::HealthResponse::NAME, crate::health::HealthResponse::schema());
+    add(crate::transparency::ProofResponse::NAME, crate::transparency::ProofResponse::schema());
+    add(crate::transparency::ConsistencyResponse::NAME, crate::transparency::ConsistencyResponse::schema());
+    add(crate::wal::Resolved::NAME, crate::wal::Resolved::schema());
+    add(crate::rollup::RollupResponse::NAME, crate::rollup::RollupResponse::schema());
+    add(crate::rollup::RollupProofResponse::NAME, crate::rollup::RollupProofResponse::schema());
+    #[cfg(feature = "verifier")]
+    add(VerifyVerdict::NAME, VerifyVerdict::schema());
+    schemas
+}
+
+/// The document served from /openapi.json.
+pub fn document(auth: &AuthPolicy) -> Value {
+    let mut paths = Map::new();
+    for (method, path) in capabilities::endpoints() {
+        let mut operation = operation(method, path);
+        let parameters: Vec<Value> = path
+            .split('/')
+            .filter_map(|segment| segment.strip_prefix('{')?.strip_suffix('}'))
+            .map(|name| json!({ "name": name, "in": "path", "required": true, "schema": { "type": "string" } }))
+            .chain(operation["parameters"].as_array().into_iter().flatten().cloned())
+            .collect();
+        if !parameters.is_empty() {
+            operation["parameters"] = json!(parameters);
+        }
+        if auth.access_for(path) == Access::Authenticated {
+            operation["security"] = json!([{ "apiKey": [] }, { "bearer": [] }]);
+            operation["responses"]["401"] = error_response("No valid API key was presented.");
+        }
+        let item = paths.entry(path.to_string()).or_insert_with(|| json!({}));
+        item[method.to_ascii_lowercase()] = operation;
+    }
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "Aegis sealer",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Seals images and other payloads into signed .aegis containers, and verifies them.",
+        },
+        "paths": paths,
+        "components": {
+            "schemas": schemas(),
+            "securitySchemes": {
+                "apiKey": { "type": "apiKey", "in": "header", "name": "x-api-key" },
+                "bearer": { "type": "http", "scheme": "bearer" },
+            },
+        },
+    })
+}
+
+/// The page served from /docs.
+pub fn swagger_ui() -> String {
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Aegis sealer API</title>\n\
+         <link rel=\"stylesheet\" href=\"{cdn}/swagger-ui.css\"></head>\n\
+         <body><div id=\"swagger-ui\"></div>\n\
+         <script src=\"{cdn}/swagger-ui-bundle.js\"></script>\n\
+         <script>SwaggerUIBundle({{ url: \"/openapi.json\", dom_id: \"#swagger-ui\" }});</script>\n\
+         </body></html>\n",
+        cdn = SWAGGER_UI_CDN,
+    )
+}
+
+fn flag(description: &str) -> Value {
+    json!({ "type": "string", "enum": ["true", "false", "1", "0"], "description": description })
+}
+
+fn operation(method: &str, path: &str) -> Value {
+    match (method, path) {
+        ("POST", "/seal") => json!({
+            "summary": "Seal a payload",
+            "description": "Signs the payload with its metadata and returns the sealed container, or with `output` a sealed copy, payload manifest or in-toto attestation instead.",
+            "parameters": [
+                { "name": "format", "in": "query", "schema": { "type": "string" }, "description": "Same as the `output` field, which overrides it." },
+                { "name": "store", "in": "query", "schema": { "type": "boolean" }, "description": "Store the container and return a receipt." },
+            ],
+            "requestBody": {
+                "required": true,
+                "content": { "multipart/form-data": { "schema": {
+                    "type": "object",
+                    "required": ["metadata"],
+                    "properties": {
+                        "payload": { "type": "string", "format": "binary", "description": "The file to seal; its Content-Type is its media type. `image` is accepted as well." },
+                        "image": { "type": "string", "format": "binary" },
+                        "metadata": { "type": "string", "description": "A JSON object matching the metadata schema, or any string." },
+                        "media_type": { "type": "string", "description": "The payload's media type, overriding the part's Content-Type." },
+                        "detached": flag("Return a .aegis.sig sidecar instead of a container."),
+                        "external_metadata": flag("Keep the metadata in the header and sign a reference to it."),
+                        "import_embedded": flag("Add the image's XMP and IPTC properties to the metadata."),
+                        "extract_exif": flag("Fill empty capture fields from the image's EXIF."),
+                        "output": { "type": "string", "enum": ["container", "xmp", "c2pa", "manifest", "intoto"] },
+                        "text": { "type": "string", "enum": ["none", "lf", "crlf"], "description": "Seal a text document, normalizing its line endings." },
+                        "compress": { "type": "string", "enum": ["metadata", "image", "all", "none"] },
+                    },
+                    "additionalProperties": { "description": "`extension:<name>` parts become signed extensions." },
+                } } },
+            },
+            "responses": {
+                "200": {
+                    "description": "The sealed container, copy or sidecar; with `store=true` a receipt, and with `output=manifest` or `output=intoto` a JSON document.",
+                    "content": {
+                        "application/octet-stream": { "schema": { "type": "string", "format": "binary" } },
+                        "application/json": { "schema": { "oneOf": [reference::<crate::SealReceipt>(), { "type": "object" }] } },
+                    },
+                },
+                "400": error_response("The request is malformed or its options conflict."),
+                "413": error_response("The payload is too large."),
+                "422": error_response("The payload or metadata was refused."),
+            },
+        }),
+        ("POST", "/verify") => json!({
+            "summary": "Verify a sealed container",
+            "description": "Checks a container sent as the body or a multipart file part, a sidecar or payload manifest with the original, or a remote container by URL, and judges it against the tenant's key trust.",
+            "parameters": [
+                { "name": "explain", "in": "query", "schema": { "type": "boolean" } },
+                { "name": "lang", "in": "query", "schema": { "type": "string" } },
+                { "name": "level", "in": "query", "schema": { "type": "string", "enum": ["quick", "standard", "forensic"] } },
+            ],
+            "requestBody": {
+                "required": true,
+                "content": {
+                    "application/octet-stream": { "schema": { "type": "string", "format": "binary" } },
+                    "multipart/form-data": { "schema": {
+                        "type": "object",
+                        "properties": {
+                            "container": { "type": "string", "format": "binary" },
+                            "signature": { "type": "string", "format": "binary", "description": "A .aegis.sig sidecar, checked against `original`." },
+                            "manifest": { "type": "string", "format": "binary", "description": "A payload manifest, checked against `original`." },
+                            "original": { "type": "string", "format": "binary" },
+                        },
+                    } },
+                    "application/json": { "schema": {
+                        "type": "object",
+                        "required": ["url"],
+                        "properties": {
+                            "url": { "type": "string", "format": "uri" },
+                            "mode": { "type": "string", "enum": ["quick", "full"] },
+                        },
+                    } },
+                },
+            },
+            "responses": verify_responses(),
+        }),
+        ("GET", "/healthz") | ("GET", "/readyz") => json!({
+            "summary": if path == "/healthz" { "Liveness probe" } else { "Readiness probe" },
+            "responses": {
+                "200": json_response::<crate::health::HealthResponse>("The service is up."),
+                "503": json_response::<crate::health::HealthResponse>("A component the service needs has failed."),
+            },
+        }),
+        ("GET", "/log/proof/{hash}") => json!({
+            "summary": "Inclusion proof of a seal in the transparency log",
+            "parameters": [{ "name": "tree_size", "in": "query", "schema": { "type": "integer" } }],
+            "responses": {
+                "200": json_response::<crate::transparency::ProofResponse>("The proof and a signed tree head."),
+                "404": error_response("No seal of that image is in the log."),
+            },
+        }),
+        ("GET", "/log/consistency") => json!({
+            "summary": "Consistency proof between two sizes of the transparency log",
+            "parameters": [
+                { "name": "first", "in": "query", "required": true, "schema": { "type": "integer" } },
+                { "name": "second", "in": "query", "schema": { "type": "integer" } },
+            ],
+            "responses": {
+                "200": json_response::<crate::transparency::ConsistencyResponse>(
+                    "The proof and a signed tree head of the second size.",
+                ),
+                "400": error_response("The sizes are out of order or beyond the log."),
+            },
+        }),
+        ("GET", "/rollups/{date}") => json!({
+            "summary": "A day's signed rollup of seals",
+            "responses": {
+                "200": json_response::<crate::rollup::RollupResponse>("The signed rollup and the image hashes it covers."),
+                "404": error_response("The day has no rollup."),
+            },
+        }),
+        ("GET", "/rollups/{date}/proof/{hash}") => json!({
+            "summary": "Inclusion proof of a seal in a day's rollup",
+            "responses": {
+                "200": json_response::<crate::rollup::RollupProofResponse>("The proof and the signed rollup."),
+                "404": error_response("No seal of that image is in the day's rollup."),
+            },
+        }),
+        ("POST", "/admin/wal/{id}/resolve") => json!({
+            "summary": "Close an unresolved seal intent",
+            "responses": {
+                "200": json_response::<crate::wal::Resolved>("The intent was closed."),
+                "404": error_response("No unresolved entry has that ID."),
+            },
+        }),
+        _ => json!({
+            "summary": summary(method, path),
+            "responses": { "200": { "description": "OK" } },
+        }),
+    }
+}
+
+#[cfg(feature = "verifier")]
+fn verify_responses() -> Value {
+    json!({
+        "200": json_response::<VerifyVerdict>("The verification report and verdict."),
+        "400": error_response("The request is malformed."),
+        "422": error_response("The container could not be parsed."),
+    })
+}
+
+#[cfg(not(feature = "verifier"))]
+fn verify_responses() -> Value {
+    json!({ "200": { "description": "OK" } })
+}
+
+fn summary(method: &str, path: &str) -> &'static str {
+    match (method, path) {
+        ("GET", "/capabilities") => "Features and limits of this service",
+        ("GET", "/openapi.json") => "This document",
+        ("GET", "/docs") => "Swagger UI for this document",
+        ("GET", "/keys") => "The service's public keys as a JWKS",
+        ("GET", "/keys/dns") => "DNS records publishing the service's keys",
+        ("POST", "/seal/batch") => "Seal several payloads",
+        ("GET", "/feed/json") => "Recent seals as a signed JSON feed",
+        ("GET", "/feed/atom") => "Recent seals as a signed Atom feed",
+        ("POST", "/ingest/dam") => "Seal assets announced by a DAM webhook",
+        ("POST", "/export/bundle") => "Export containers as an offline verification bundle",
+        ("GET", "/sealed/{name}") => "Download a stored container",
+        ("GET", "/sealed/{name}/notifications") => "The verification notification registration of a stored seal",
+        ("PUT", "/sealed/{name}/notifications") => "Register for notifications when a stored seal is verified",
+        ("DELETE", "/sealed/{name}/notifications") => "Stop notifications for a stored seal",
+        ("GET", "/metrics") => "Prometheus metrics",
+        ("GET", "/admin/wal") => "Unresolved seal intents in the write-ahead log",
+        ("GET", "/audit") => "The seal audit log",
+        ("GET", "/events/schema") => "Schemas of every seal event version",
+        ("GET", "/events/schema/{version}") => "Schema of one seal event version",
+        ("GET", "/admin/captures") => "Captured failed requests",
+        ("GET", "/admin/captures/{request_id}") => "One captured failed request",
+        ("POST", "/admin/rollups") => "Roll up finished days of seals now",
+        ("POST", "/reseal") => "Re-sign a container with the current key",
+        ("GET", "/debug/memory") => "Heap statistics",
+        _ => "",
+    }
+}
+
+/// The body of a /verify response: a `VerificationReport` (or a bundle or
+/// remote report) with the tenant's `Judgement` alongside.
+#[cfg(feature = "verifier")]
+struct VerifyVerdict;
+
+#[cfg(feature = "verifier")]
+impl ApiSchema for VerifyVerdict {
+    const NAME: &'static str = "VerifyVerdict";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["signature_valid", "key_fingerprint", "verdict", "warnings"],
+            "properties": {
+                "signature_valid": { "type": ["boolean", "null"] },
+                "key_fingerprint": { "type": "string", "description": "Hex SHA-256 of the SEC1 public key." },
+                "key_id": { "type": ["string", "null"] },
+                "signature_scheme": { "type": "string" },
+                "timestamp": { "type": ["object", "null"] },
+                "digest": { "type": "string" },
+                "metadata": { "type": "string" },
+                "external_metadata_valid": { "type": ["boolean", "null"] },
+                "extensions": { "type": "array", "items": { "type": "object" } },
+                "cosigners": { "type": "array", "items": { "type": "object" } },
+                "certificate_chain": { "type": "object" },
+                "payload_size": { "type": "integer" },
+                "media_type": { "type": "string" },
+                "text": { "type": "object" },
+                "verdict": {
+                    "type": "string",
+                    "enum": ["VALID", "VALID_WITH_WARNING", "UNTRUSTED_KEY", "REVOKED_KEY", "INVALID_SIGNATURE", "UNVERIFIED"],
+                },
+                "tenant": { "type": ["string", "null"] },
+                "warnings": { "type": "array", "items": { "type": "string" } },
+                "endorsement": { "type": "object" },
+            },
+        })
+    }
+}
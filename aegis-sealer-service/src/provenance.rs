@@ -0,0 +1,176 @@
+// aegis-sealer-service/src/provenance.rs
+
+// Submission provenance: facts about the HTTP request that delivered an
+// upload, sealed with it so investigators can tell how it was submitted.
+// With `AEGIS_PROVENANCE=true`, a `submission` object is added to JSON
+// object metadata before signing, so it is covered by the signature:
+//
+//     "submission": {
+//       "channel": "/seal",
+//       "received_at": "2025-01-01T12:00:00Z",
+//       "principal": "tenant:acme",
+//       "client_cert_subject": "CN=scanner-7,O=Acme",
+//       "user_agent_class": "cli"
+//     }
+//
+// The client certificate subject is read from the header named by
+// `AEGIS_PROVENANCE_CLIENT_CERT_HEADER`, which the TLS-terminating proxy must
+// set (and strip from client requests); without it the field is left out.
+// `AEGIS_PROVENANCE_REDACT` holds comma-separated `field=omit|hash` rules,
+// e.g. `principal=hash,client_cert_subject=omit`; hashed fields hold
+// `sha256:` and the hex SHA-256 of the value. The channel is never redacted.
+//
+// A `submission` key sent by the client is replaced. Metadata that is not a
+// JSON object is sealed as sent, without provenance.
+
+use crate::{auth::Principal, AppState};
+use aegis_core::time::rfc3339;
+use axum::{
+    extract::{FromRequestParts, MatchedPath},
+    http::{header, request::Parts, HeaderName},
+};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::env;
+use std::time::SystemTime;
+use tracing::warn;
+
+pub(crate) const FIELDS: [&str; 4] = ["received_at", "principal", "client_cert_subject", "user_agent_class"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Rule {
+    Omit,
+    Hash,
+}
+
+pub struct ProvenanceConfig {
+    enabled: bool,
+    client_cert_header: Option<HeaderName>,
+    redact: HashMap<&'static str, Rule>,
+}
+
+impl ProvenanceConfig {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let enabled = matches!(env::var("AEGIS_PROVENANCE").as_deref(), Ok("true" | "1"));
+        let client_cert_header = match env::var("AEGIS_PROVENANCE_CLIENT_CERT_HEADER") {
+            Ok(name) if !name.is_empty() => Some(
+                HeaderName::try_from(name.trim())
+                    .map_err(|_| anyhow::anyhow!("AEGIS_PROVENANCE_CLIENT_CERT_HEADER is not a header name: '{}'", name))?,
+            ),
+            _ => None,
+        };
+        let mut redact = HashMap::new();
+        for entry in env::var("AEGIS_PROVENANCE_REDACT").unwrap_or_default().split(',').map(str::trim) {
+            if entry.is_empty() {
+                continue;
+            }
+            let (field, rule) = entry
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("AEGIS_PROVENANCE_REDACT entry '{}' is not field=omit|hash", entry))?;
+            let field = FIELDS
+                .into_iter()
+                .find(|f| *f == field.trim())
+                .ok_or_else(|| anyhow::anyhow!("AEGIS_PROVENANCE_REDACT names unknown field '{}'", field))?;
+            let rule = match rule.trim() {
+                "omit" => Rule::Omit,
+                "hash" => Rule::Hash,
+                other => anyhow::bail!("AEGIS_PROVENANCE_REDACT rule for {} must be omit or hash, got '{}'", field, other),
+            };
+            redact.insert(field, rule);
+        }
+        Ok(ProvenanceConfig { enabled, client_cert_header, redact })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn insert(&self, block: &mut Map<String, Value>, field: &'static str, value: Option<String>) {
+        let Some(value) = value else {
+            return;
+        };
+        let value = match self.redact.get(field) {
+            Some(Rule::Omit) => return,
+            Some(Rule::Hash) => format!("sha256:{}", hex::encode(Sha256::digest(value.as_bytes()))),
+            None => value,
+        };
+        block.insert(field.to_string(), Value::String(value));
+    }
+}
+
+/// The submission block for one request, captured as a handler argument.
+/// Empty when provenance is off.
+pub struct Submission(Option<Map<String, Value>>);
+
+impl Submission {
+    /// Adds the block to `metadata` if it is a JSON object.
+    pub fn attach(&self, metadata: String) -> String {
+        let Some(block) = &self.0 else {
+            return metadata;
+        };
+        match serde_json::from_str::<Value>(&metadata) {
+            Ok(Value::Object(mut object)) => {
+                object.insert("submission".to_string(), Value::Object(block.clone()));
+                Value::Object(object).to_string()
+            }
+            _ => {
+                warn!("Metadata is not a JSON object; sealing without submission provenance.");
+                metadata
+            }
+        }
+    }
+}
+
+impl FromRequestParts<AppState> for Submission {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let config = &state.config.provenance;
+        if !config.enabled {
+            return Ok(Submission(None));
+        }
+        let get = |name: &HeaderName| parts.headers.get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+        let channel = parts
+            .extensions
+            .get::<MatchedPath>()
+            .map(|p| p.as_str().to_string())
+            .unwrap_or_else(|| parts.uri.path().to_string());
+        let mut block = Map::new();
+        block.insert("channel".to_string(), Value::String(channel));
+        config.insert(&mut block, "received_at", Some(rfc3339(SystemTime::now())));
+        config.insert(&mut block, "principal", parts.extensions.get::<Principal>().map(|p| p.0.clone()));
+        config.insert(&mut block, "client_cert_subject", config.client_cert_header.as_ref().and_then(get));
+        config.insert(
+            &mut block,
+            "user_agent_class",
+            Some(user_agent_class(get(&header::USER_AGENT).as_deref()).to_string()),
+        );
+        Ok(Submission(Some(block)))
+    }
+}
+
+/// Coarse class of a User-Agent, so the block identifies the kind of client
+/// without fingerprinting it.
+fn user_agent_class(user_agent: Option<&str>) -> &'static str {
+    let Some(ua) = user_agent.map(str::to_ascii_lowercase) else {
+        return "none";
+    };
+    const CLI: [&str; 3] = ["curl/", "wget/", "httpie/"];
+    const LIBRARY: [&str; 8] = [
+        "python-requests", "python-urllib", "aiohttp", "go-http-client", "okhttp", "axios", "node-fetch", "reqwest",
+    ];
+    const BOT: [&str; 3] = ["bot", "crawler", "spider"];
+    if CLI.iter().any(|p| ua.starts_with(p)) {
+        "cli"
+    } else if LIBRARY.iter().any(|p| ua.contains(p)) {
+        "library"
+    } else if BOT.iter().any(|p| ua.contains(p)) {
+        "bot"
+    } else if ua.starts_with("mozilla/") {
+        "browser"
+    } else {
+        "other"
+    }
+}
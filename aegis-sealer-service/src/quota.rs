@@ -0,0 +1,217 @@
+// aegis-sealer-service/src/quota.rs
+
+// Per-client quotas on the sealing endpoints: a request rate and a daily
+// upload volume, keyed by the caller's API key (or tenant) when it presented
+// one and by client address otherwise. Bytes are charged by declared
+// Content-Length when a request is let through, whatever its outcome. Every
+// response from a limited endpoint reports the caller's remaining quota.
+
+use crate::{auth::Principal, AppError};
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+struct Usage {
+    tokens: f64,
+    updated: Instant,
+    // Days since the Unix epoch, UTC, that `bytes` was counted in.
+    day: u64,
+    bytes: u64,
+}
+
+pub struct Quotas {
+    requests_per_minute: Option<u64>,
+    bytes_per_day: Option<u64>,
+    trust_proxy: bool,
+    usage: Mutex<HashMap<String, Usage>>,
+}
+
+// What a client has left after a request was charged, or would have had.
+struct Remaining {
+    requests: Option<u64>,
+    bytes: Option<u64>,
+    // Seconds until the daily byte count starts over.
+    bytes_reset: u64,
+}
+
+enum Verdict {
+    Allowed(Remaining),
+    Limited { retry_after: u64, reason: &'static str, remaining: Remaining },
+}
+
+impl Quotas {
+    /// Builds the quotas from `AEGIS_SEAL_RATE_PER_MIN` and
+    /// `AEGIS_SEAL_BYTES_PER_DAY`. Either left unset (or 0) is not enforced.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let limit = |name: &str| -> anyhow::Result<Option<u64>> {
+            match env::var(name) {
+                Ok(v) => match v.trim().parse::<u64>() {
+                    Ok(0) => Ok(None),
+                    Ok(n) => Ok(Some(n)),
+                    Err(_) => anyhow::bail!("{} must be a whole number, got '{}'", name, v),
+                },
+                Err(_) => Ok(None),
+            }
+        };
+        let quotas = Quotas {
+            requests_per_minute: limit("AEGIS_SEAL_RATE_PER_MIN")?,
+            bytes_per_day: limit("AEGIS_SEAL_BYTES_PER_DAY")?,
+            trust_proxy: matches!(env::var("AEGIS_TRUST_PROXY").as_deref(), Ok("true" | "1")),
+            usage: Mutex::new(HashMap::new()),
+        };
+        if quotas.is_enabled() {
+            info!(
+                requests_per_minute = quotas.requests_per_minute,
+                bytes_per_day = quotas.bytes_per_day,
+                "Per-client seal quotas enabled."
+            );
+        }
+        Ok(quotas)
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.requests_per_minute.is_some() || self.bytes_per_day.is_some()
+    }
+
+    /// The quotas for the capabilities document, `None` when there are none.
+    pub fn describe(&self) -> Option<Value> {
+        self.is_enabled().then(|| {
+            json!({
+                "requests_per_minute": self.requests_per_minute,
+                "bytes_per_day": self.bytes_per_day,
+            })
+        })
+    }
+
+    // The principal set by `auth::enforce`, else the client address.
+    fn client(&self, request: &Request) -> Option<String> {
+        if let Some(Principal(principal)) = request.extensions().get::<Principal>() {
+            return Some(principal.clone());
+        }
+        crate::auth::client_ip(request, self.trust_proxy).map(|ip| format!("ip:{}", ip))
+    }
+
+    fn charge(&self, client: String, bytes: u64) -> Verdict {
+        let now = Instant::now();
+        let unix = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let today = unix / SECONDS_PER_DAY;
+        let bytes_reset = (today + 1) * SECONDS_PER_DAY - unix;
+        let per_minute = self.requests_per_minute.unwrap_or(0) as f64;
+
+        let mut usage = self.usage.lock().unwrap();
+        // Keep the table from growing without bound.
+        if usage.len() > 100_000 {
+            usage.retain(|_, u| u.day == today && u.bytes > 0);
+        }
+        let entry = usage.entry(client).or_insert(Usage {
+            tokens: per_minute,
+            updated: now,
+            day: today,
+            bytes: 0,
+        });
+        entry.tokens = (entry.tokens + now.duration_since(entry.updated).as_secs_f64() * per_minute / 60.0)
+            .min(per_minute);
+        entry.updated = now;
+        if entry.day != today {
+            entry.day = today;
+            entry.bytes = 0;
+        }
+
+        let remaining = |entry: &Usage| Remaining {
+            requests: self.requests_per_minute.map(|_| entry.tokens.floor() as u64),
+            bytes: self.bytes_per_day.map(|limit| limit.saturating_sub(entry.bytes)),
+            bytes_reset,
+        };
+        if self.requests_per_minute.is_some() && entry.tokens < 1.0 {
+            let retry_after = ((1.0 - entry.tokens) * 60.0 / per_minute).ceil() as u64;
+            return Verdict::Limited {
+                retry_after: retry_after.max(1),
+                reason: "Seal rate limit exceeded. Retry later.",
+                remaining: remaining(entry),
+            };
+        }
+        if let Some(limit) = self.bytes_per_day
+            && entry.bytes.saturating_add(bytes) > limit
+        {
+            return Verdict::Limited {
+                retry_after: bytes_reset,
+                reason: "Daily upload quota exceeded. Retry after it resets.",
+                remaining: remaining(entry),
+            };
+        }
+        if self.requests_per_minute.is_some() {
+            entry.tokens -= 1.0;
+        }
+        entry.bytes += bytes;
+        Verdict::Allowed(remaining(entry))
+    }
+}
+
+impl Remaining {
+    fn write(&self, quotas: &Quotas, headers: &mut HeaderMap) {
+        let mut set = |name: &'static str, value: u64| {
+            headers.insert(name, HeaderValue::from(value));
+        };
+        if let (Some(limit), Some(remaining)) = (quotas.requests_per_minute, self.requests) {
+            set("x-ratelimit-limit", limit);
+            set("x-ratelimit-remaining", remaining);
+        }
+        if let (Some(limit), Some(remaining)) = (quotas.bytes_per_day, self.bytes) {
+            set("x-quota-bytes-limit", limit);
+            set("x-quota-bytes-remaining", remaining);
+            set("x-quota-bytes-reset", self.bytes_reset);
+        }
+    }
+}
+
+/// Middleware charging each request against its client's quotas, answering
+/// 429 with `Retry-After` once either is spent.
+pub async fn limit(State(quotas): State<Arc<Quotas>>, request: Request, next: Next) -> Result<Response, AppError> {
+    if !quotas.is_enabled() {
+        return Ok(next.run(request).await);
+    }
+    let Some(client) = quotas.client(&request) else {
+        return Ok(next.run(request).await);
+    };
+    let declared_len = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    let bytes = match declared_len {
+        Some(len) => len,
+        None if quotas.bytes_per_day.is_some() => {
+            return Err(AppError(
+                StatusCode::LENGTH_REQUIRED,
+                "A Content-Length is required while upload quotas are enforced.".into(),
+            ));
+        }
+        None => 0,
+    };
+    match quotas.charge(client.clone(), bytes) {
+        Verdict::Allowed(remaining) => {
+            let mut response = next.run(request).await;
+            remaining.write(&quotas, response.headers_mut());
+            Ok(response)
+        }
+        Verdict::Limited { retry_after, reason, remaining } => {
+            warn!(client = %client, reason, "Seal quota exceeded.");
+            let mut response =
+                (StatusCode::TOO_MANY_REQUESTS, [(header::RETRY_AFTER, retry_after.to_string())], reason)
+                    .into_response();
+            remaining.write(&quotas, response.headers_mut());
+            Ok(response)
+        }
+    }
+}
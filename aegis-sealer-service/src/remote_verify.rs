@@ -0,0 +1,328 @@
+// aegis-sealer-service/src/remote_verify.rs
+
+// Verification of containers that live elsewhere (an `s3://` object or an
+// HTTP URL) using Range requests, so nothing is downloaded in one piece.
+//
+// - `quick` fetches only the header blocks and checks that they parse, that
+//   the key and signature are well-formed, and that the declared image length
+//   matches the object size. The signature itself is not checked.
+// - `full` additionally streams the image through the signing hash in
+//   fixed-size ranges and checks the signature. A compressed image is
+//   fetched the same way but held until it can be decompressed whole, so one
+//   whose stored size could not decompress within the inflate limit is
+//   refused before any of it is fetched.
+//
+// Fetching arbitrary URLs from a public endpoint would be an SSRF vector, so
+// only URLs under a prefix listed in `AEGIS_VERIFY_URL_ALLOW`
+// (comma-separated, e.g. `s3://photos/,http://cdn.internal/`) are accepted.
+// The URL and prefix are compared parsed, not as strings: the scheme, host
+// (or bucket) and port must be the same, and the path must be the prefix's
+// or lie under it, ending on a `/` boundary and without `.` or `..`
+// segments. So `http://cdn.internal/` allows neither
+// `http://cdn.internal.evil.com/` nor `http://cdn.internal@evil.com/`.
+
+use crate::{config::Config, http_client::{self, HttpResponse}, s3::{self, S3Config}, AppError};
+use aegis_core::{
+    crypto::{self, SigningHasher},
+    format::{self, ContainerHeader},
+    keys::Fingerprint,
+};
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::info;
+
+/// Bytes fetched for the first look at the header; doubled while the
+/// metadata does not fit.
+const INITIAL_HEADER_RANGE: u64 = 64 * 1024;
+const MAX_HEADER_RANGE: u64 = 16 * 1024 * 1024;
+/// Range size used when streaming the image in `full` mode.
+const FULL_RANGE: u64 = 8 * 1024 * 1024;
+/// zstd stores data it cannot shrink in raw blocks of up to 128 KiB with a
+/// 3-byte header each, after a frame header of at most 18 bytes, so a block
+/// that inflates to `n` bytes is never stored in more than
+/// `n + n / STORED_OVERHEAD_DIVISOR + STORED_OVERHEAD` bytes.
+const STORED_OVERHEAD_DIVISOR: u64 = 1024;
+const STORED_OVERHEAD: u64 = 64;
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    #[default]
+    Quick,
+    Full,
+}
+
+#[derive(Deserialize)]
+pub struct RemoteRequest {
+    pub url: String,
+    /// Falls back to the tenant's `remote_mode`, then to `quick`.
+    #[serde(default)]
+    pub mode: Option<Mode>,
+}
+
+#[derive(Serialize)]
+pub struct RemoteReport {
+    pub url: String,
+    pub mode: Mode,
+    /// `None` in quick mode, where the signature is not checked.
+    pub signature_valid: Option<bool>,
+    pub key_fingerprint: String,
+    pub key_valid: bool,
+    pub signature_well_formed: bool,
+    pub signature_scheme: &'static str,
+    pub metadata: String,
+    /// Signed extensions, as `format::Extension::to_json()` gives them.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extensions: Vec<serde_json::Value>,
+    /// Co-signers' status; checked in full mode only.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cosigners: Vec<crypto::CosignerReport>,
+    pub payload_size: u64,
+    /// As declared in the signed metadata; the payload is not sniffed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    pub object_size: Option<u64>,
+    /// Whether the object is exactly as long as its header says.
+    pub size_consistent: Option<bool>,
+    pub bytes_fetched: u64,
+}
+
+enum Source {
+    S3 { config: Arc<S3Config>, bucket: String, key: String },
+    Http(String),
+}
+
+impl Source {
+    fn parse(config: &Config, url: &str) -> Result<Self, AppError> {
+        if !config.verify_url_allow.iter().any(|prefix| allows(prefix, url)) {
+            return Err(AppError(
+                StatusCode::FORBIDDEN,
+                "Remote verification of this URL is not allowed.".into(),
+            ));
+        }
+        if url.starts_with("s3://") {
+            let (bucket, key) = s3::parse_url(url)
+                .ok_or_else(|| AppError(StatusCode::BAD_REQUEST, "Expected s3://bucket/key.".into()))?;
+            return Ok(Source::S3 {
+                config: config
+                    .s3
+                    .clone()
+                    .ok_or_else(|| anyhow::anyhow!("AEGIS_S3_ENDPOINT must be set to use s3:// URLs"))?,
+                bucket: bucket.to_string(),
+                key: key.to_string(),
+            });
+        }
+        Ok(Source::Http(url.to_string()))
+    }
+
+    /// Fetches `len` bytes from `start`, returning them with the object's
+    /// total size when the server reports it.
+    async fn range(&self, start: u64, len: u64) -> anyhow::Result<(Vec<u8>, Option<u64>)> {
+        let range = format!("bytes={}-{}", start, start + len - 1);
+        let headers = [("Range", range.as_str())];
+        let response = match self {
+            Source::S3 { config, bucket, key } => {
+                config.request("GET", bucket, key, &headers, len as usize).await?
+            }
+            Source::Http(url) => http_client::request("GET", url, &headers, &[], len as usize).await?,
+        };
+        match response.status {
+            206 => Ok((response.body.clone(), total_size(&response))),
+            // Past the end of the object.
+            416 => Ok((Vec::new(), total_size(&response))),
+            200 => anyhow::bail!("server ignored the Range request; refusing a full download"),
+            status => anyhow::bail!("range request returned HTTP {}", status),
+        }
+    }
+}
+
+/// Whether the allow-list entry `prefix` covers `url`. Either one failing
+/// to parse means no.
+fn allows(prefix: &str, url: &str) -> bool {
+    if let Some(bucket_prefix) = prefix.strip_prefix("s3://") {
+        let (bucket, key_prefix) = bucket_prefix.split_once('/').unwrap_or((bucket_prefix, ""));
+        return s3::parse_url(url).is_some_and(|(url_bucket, key)| {
+            url_bucket == bucket && within(&format!("/{}", key), &format!("/{}", key_prefix))
+        });
+    }
+    let (Ok((host, port, path)), Ok((prefix_host, prefix_port, prefix_path))) =
+        (http_client::components(url), http_client::components(prefix))
+    else {
+        return false;
+    };
+    host.eq_ignore_ascii_case(&prefix_host) && port == prefix_port && within(&path, &prefix_path)
+}
+
+/// Whether `path` is `prefix` or below it, with no `.` or `..` segment that
+/// a server could resolve to somewhere else.
+fn within(path: &str, prefix: &str) -> bool {
+    let dotted = path.split(['?', '#']).next().unwrap_or_default().split('/').any(|segment| {
+        let segment = segment.to_ascii_lowercase().replace("%2e", ".");
+        segment == "." || segment == ".."
+    });
+    if dotted {
+        return false;
+    }
+    match path.strip_prefix(prefix) {
+        Some(rest) => prefix.ends_with('/') || rest.is_empty() || rest.starts_with(['/', '?']),
+        None => false,
+    }
+}
+
+/// The total from `Content-Range: bytes 0-99/1234` (or `bytes */1234`).
+fn total_size(response: &HttpResponse) -> Option<u64> {
+    response
+        .header("content-range")?
+        .rsplit_once('/')?
+        .1
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Verifies the container at `request.url`, with each compressed block
+/// allowed to decompress to `inflate_limit` bytes at most.
+pub async fn verify(
+    config: &Config,
+    request: RemoteRequest,
+    default_mode: Mode,
+    inflate_limit: usize,
+) -> Result<RemoteReport, AppError> {
+    let mode = request.mode.unwrap_or(default_mode);
+    let source = Source::parse(config, &request.url)?;
+    let unprocessable = |msg: String| AppError(StatusCode::UNPROCESSABLE_ENTITY, msg);
+
+    let mut fetched = 0u64;
+    let mut prefix = Vec::new();
+    let mut object_size = None;
+    let mut want = INITIAL_HEADER_RANGE;
+    let header: ContainerHeader = loop {
+        let (bytes, total) = source.range(prefix.len() as u64, want - prefix.len() as u64).await?;
+        fetched += bytes.len() as u64;
+        object_size = object_size.or(total);
+        let exhausted = bytes.is_empty();
+        prefix.extend_from_slice(&bytes);
+        match format::parse_header_limited(&prefix, inflate_limit) {
+            Ok(Some(header)) => break header,
+            Ok(None) if exhausted || want >= MAX_HEADER_RANGE => {
+                return Err(unprocessable("Container header is truncated or too large.".into()));
+            }
+            Ok(None) => want = (want * 2).min(MAX_HEADER_RANGE),
+            Err(e) => return Err(unprocessable(format!("Not a valid .aegis container: {}", e))),
+        }
+    };
+
+    let scheme = crypto::SignatureScheme::of(&header.header).map_err(|e| unprocessable(e.to_string()))?;
+    let extensions = header.header.extensions().map_err(|e| unprocessable(e.to_string()))?;
+    let mut report = RemoteReport {
+        url: request.url.clone(),
+        mode,
+        signature_valid: None,
+        key_fingerprint: Fingerprint::of(&header.public_key).to_hex(),
+        key_valid: p256::PublicKey::from_sec1_bytes(&header.public_key).is_ok(),
+        signature_well_formed: header.signature.len() == 64,
+        signature_scheme: scheme.name(),
+        metadata: header.metadata.clone(),
+        extensions: extensions.iter().map(format::Extension::to_json).collect(),
+        cosigners: Vec::new(),
+        payload_size: header.image_len,
+        media_type: aegis_core::media_type::from_metadata(&header.metadata),
+        object_size,
+        size_consistent: object_size.map(|size| size == header.header_len + header.image_len),
+        bytes_fetched: fetched,
+    };
+
+    if mode == Mode::Full {
+        let mut hasher =
+            SigningHasher::for_header(&header.header, &header.metadata).map_err(|e| unprocessable(e.to_string()))?;
+        // A compressed image is gathered whole and decompressed before it
+        // is hashed, so it may be no larger than what could inflate within
+        // the limit.
+        let mut compressed = header.header.compression().image.then(Vec::new);
+        let max_stored = inflate_limit as u64 + inflate_limit as u64 / STORED_OVERHEAD_DIVISOR + STORED_OVERHEAD;
+        if compressed.is_some() && header.image_len > max_stored {
+            return Err(unprocessable(format!(
+                "Compressed image of {} bytes is larger than this service decompresses ({} bytes).",
+                header.image_len, inflate_limit
+            )));
+        }
+        let mut received = 0u64;
+        let mut take = |bytes: &[u8], hasher: &mut SigningHasher| {
+            received += bytes.len() as u64;
+            match &mut compressed {
+                Some(stored) => stored.extend_from_slice(bytes),
+                None => hasher.update(bytes),
+            }
+            received
+        };
+        // Image bytes already fetched along with the header.
+        let already = &prefix[(header.header_len as usize).min(prefix.len())..];
+        let mut done = take(&already[..(header.image_len as usize).min(already.len())], &mut hasher);
+        while done < header.image_len {
+            let len = FULL_RANGE.min(header.image_len - done);
+            let (bytes, _) = source.range(header.header_len + done, len).await?;
+            if bytes.is_empty() {
+                return Err(unprocessable("Container image is truncated.".into()));
+            }
+            fetched += bytes.len() as u64;
+            done = take(&bytes[..bytes.len().min(len as usize)], &mut hasher);
+        }
+        if let Some(stored) = compressed {
+            let image = format::decompress_block(&stored, inflate_limit).map_err(|e| unprocessable(e.to_string()))?;
+            hasher.update(&image);
+        }
+        let digest = crypto::container_digest(&header.header, &hasher.finalize())
+            .map_err(|e| unprocessable(e.to_string()))?;
+        report.signature_valid = Some(
+            scheme
+                .verify_digest(&header.public_key, &header.signature, &digest)
+                .map_err(|e| unprocessable(e.to_string()))?,
+        );
+        report.cosigners = crypto::verify_cosignatures(&header.header, &header.public_key, &digest)
+            .map_err(|e| unprocessable(e.to_string()))?;
+        report.bytes_fetched = fetched;
+    }
+
+    info!(
+        url = %report.url,
+        mode = ?report.mode,
+        bytes_fetched = report.bytes_fetched,
+        signature_valid = ?report.signature_valid,
+        "Remote container verified."
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows_urls_under_a_prefix() {
+        assert!(allows("http://cdn.internal/", "http://cdn.internal/a.aegis"));
+        assert!(allows("http://cdn.internal/", "http://CDN.internal:80/photos/a.aegis?v=2"));
+        assert!(allows("http://cdn.internal", "http://cdn.internal/a.aegis"));
+        assert!(allows("http://cdn.internal/photos", "http://cdn.internal/photos/a.aegis"));
+        assert!(allows("http://cdn.internal:8080/photos/", "http://cdn.internal:8080/photos/a.aegis"));
+        assert!(allows("s3://photos/", "s3://photos/2024/a.aegis"));
+        assert!(allows("s3://photos", "s3://photos/a.aegis"));
+        assert!(allows("s3://photos/2024/", "s3://photos/2024/a.aegis"));
+    }
+
+    #[test]
+    fn refuses_urls_that_only_start_with_a_prefix() {
+        assert!(!allows("http://cdn.internal", "http://cdn.internal.evil.com/a.aegis"));
+        assert!(!allows("http://cdn.internal/", "http://cdn.internal@evil.com/a.aegis"));
+        assert!(!allows("http://cdn.internal", "http://cdn.internal@evil.com/a.aegis"));
+        assert!(!allows("http://cdn.internal", "http://cdn.internal:8080/a.aegis"));
+        assert!(!allows("http://cdn.internal/photos", "http://cdn.internal/photos-private/a.aegis"));
+        assert!(!allows("http://cdn.internal/photos/", "http://cdn.internal/photos/../admin"));
+        assert!(!allows("http://cdn.internal/photos/", "http://cdn.internal/photos/%2E%2E/admin"));
+        assert!(!allows("http://cdn.internal/", "https://cdn.internal/a.aegis"));
+        assert!(!allows("s3://photos", "s3://photos-private/a.aegis"));
+        assert!(!allows("s3://photos/2024", "s3://photos/2024-private/a.aegis"));
+        assert!(!allows("s3://photos/", "http://photos/a.aegis"));
+        assert!(!allows("not a url", "http://cdn.internal/a.aegis"));
+    }
+}
@@ -0,0 +1,95 @@
+// aegis-sealer-service/src/reseal.rs
+
+// Re-sealing refreshes the proof on an existing container without
+// discarding it: the original container, signature included, becomes the
+// payload of a new container signed with the current key. The new
+// metadata records what was countersigned and when.
+
+use crate::{audit::AuditAction, audit_log::Caller, hooks::SealEvent, sealed_response, tsa, AppError, AppState};
+use aegis_core::{keys::Fingerprint, time::rfc3339};
+use aegis_core::prelude::Verifier;
+use axum::{body::Bytes, extract::State, http::StatusCode, response::Response};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::time::SystemTime;
+use tracing::{info, instrument, warn};
+
+#[instrument(skip_all, fields(original_size = body.len()))]
+pub async fn reseal_handler(
+    State(state): State<AppState>,
+    caller: Caller,
+    body: Bytes,
+) -> Result<Response, AppError> {
+    info!("Received new request for /reseal endpoint.");
+    // Only containers that still verify are worth countersigning.
+    let original = Verifier::new().verify_bytes(&body).map_err(|e| {
+        warn!(error = %e, "Refusing to reseal a container that does not verify.");
+        AppError(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("The submitted container does not verify: {}", e),
+        )
+    })?;
+
+    let original_sha256 = hex::encode(Sha256::digest(&body));
+    let metadata = json!({
+        "aegis_reseal": {
+            "original_sha256": original_sha256,
+            "original_key_fingerprint": original.fingerprint.to_hex(),
+            "original_metadata": original.metadata,
+            "resealed_at": rfc3339(SystemTime::now()),
+        }
+    })
+    .to_string();
+
+    let mut event = SealEvent {
+        action: AuditAction::Reseal.as_str(),
+        tenant: caller.tenant,
+        request_id: caller.request_id,
+        caller: caller.principal,
+        metadata: metadata.clone(),
+        image_sha256: original_sha256.clone(),
+        image_size: body.len() as u64,
+        audit_id: None,
+        key_fingerprint: None,
+        key_id: None,
+        delegation: None,
+    };
+    state.hooks.before(&event).await?;
+    let signer = state.signer.pin()?;
+    let public_key = signer.public_key()?.to_sec1_bytes();
+    let wal_id = state
+        .wal
+        .begin(AuditAction::Reseal, &public_key, &metadata, &original_sha256, body.len())
+        .await?;
+    let mut ancient = match signer.seal(metadata, body.to_vec()).await {
+        Ok(ancient) => ancient,
+        Err(e) => {
+            state.wal.abort(wal_id, &e.1).await;
+            return Err(e);
+        }
+    };
+    state.config.certify(&mut ancient.header, &public_key);
+    if let Err(e) = tsa::stamp(state.tsa.as_deref(), &mut ancient.header, &ancient.signature).await {
+        state.wal.abort(wal_id, &e.1).await;
+        return Err(e);
+    }
+    let record = state.audit.record_action(AuditAction::Reseal, &ancient);
+    state.wal.complete(wal_id, record.id).await;
+    state.metrics.record_seal(ancient.image_data.len() as u64);
+    info!(
+        audit_id = record.id,
+        original_sha256 = %original_sha256,
+        original_key = %original.fingerprint,
+        new_key = %Fingerprint::of(&ancient.public_key),
+        "Container resealed and recorded in audit store."
+    );
+    event.audit_id = Some(record.id);
+    event.key_fingerprint = Some(record.key_fingerprint.to_hex());
+    event.key_id = ancient.header.key_id().map(str::to_string);
+    state.audit_log.append(&event).await?;
+    if let Some(proof) = state.transparency.append(&original_sha256).await? {
+        ancient.header.set_log_inclusion(&proof);
+    }
+    state.hooks.after(&event).await;
+    sealed_response(ancient, "resealed.aegis")
+}
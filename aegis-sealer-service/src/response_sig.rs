@@ -0,0 +1,183 @@
+// aegis-sealer-service/src/response_sig.rs
+
+// Signs JSON responses with RFC 9421 HTTP Message Signatures so clients can
+// tell a verdict came from this service even through intermediaries. See
+// `aegis_core::http_sig` for the covered components and how to verify.
+// Set `AEGIS_SIGN_RESPONSES=false` to turn it off (see `config`).
+
+use crate::{signer::ServiceSigner, AppState};
+use aegis_core::http_sig;
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// JSON bodies are small; anything larger is passed through unsigned.
+const MAX_SIGNED_BODY: usize = 16 * 1024 * 1024;
+
+fn is_json(content_type: &str) -> bool {
+    let essence = content_type.split(';').next().unwrap_or("").trim();
+    essence == "application/json" || essence.ends_with("+json")
+}
+
+/// Middleware adding `Content-Digest`, `Signature-Input` and `Signature` to
+/// JSON responses.
+pub async fn sign(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    if !state.config.sign_responses {
+        return response;
+    }
+    sign_response(&state.signer, response).await
+}
+
+async fn sign_response(signer: &ServiceSigner, response: Response) -> Response {
+    let Some(content_type) = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .filter(|ct| is_json(ct))
+        .map(str::to_string)
+    else {
+        return response;
+    };
+
+    let (mut parts, body) = response.into_parts();
+    let body = match to_bytes(body, MAX_SIGNED_BODY).await {
+        Ok(body) => body,
+        Err(e) => {
+            warn!(error = %e, "Failed to buffer JSON response for signing.");
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+
+    let signer = match signer.pin() {
+        Ok(signer) => signer,
+        Err(e) => {
+            warn!(error = %e.1, "Response left unsigned: signer key unavailable.");
+            return Response::from_parts(parts, Body::from(body));
+        }
+    };
+    let key = match signer.public_key() {
+        Ok(key) => key,
+        Err(e) => {
+            warn!(error = %e.1, "Response left unsigned: signer key unavailable.");
+            return Response::from_parts(parts, Body::from(body));
+        }
+    };
+    let created = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let digest = http_sig::content_digest(&body);
+    let params = http_sig::signature_params(created, &key);
+    let base = http_sig::signature_base(parts.status.as_u16(), &content_type, &digest, &params);
+    match signer.sign(base.as_bytes()).await {
+        Ok(signature) => {
+            let (input, signature) = http_sig::signature_headers(&params, &signature.to_bytes());
+            for (name, value) in [
+                ("content-digest", digest),
+                ("signature-input", input),
+                ("signature", signature),
+            ] {
+                if let Ok(value) = HeaderValue::from_str(&value) {
+                    parts.headers.insert(HeaderName::from_static(name), value);
+                }
+            }
+        }
+        Err(e) => warn!(error = %e.1, "Response left unsigned: signing failed."),
+    }
+    Response::from_parts(parts, Body::from(body))
+}
+
+#[cfg(all(test, feature = "verifier"))]
+mod tests {
+    use super::*;
+    use axum::http::{HeaderMap, StatusCode};
+    use p256::ecdsa::SigningKey;
+    use std::sync::Arc;
+
+    fn signer() -> (ServiceSigner, p256::ecdsa::VerifyingKey) {
+        let key = SigningKey::from_slice(&[0x11; 32]).unwrap();
+        let public = *key.verifying_key();
+        (ServiceSigner::Local(Arc::new(key)), public)
+    }
+
+    async fn respond(status: StatusCode, content_type: &str, body: &'static str) -> (HeaderMap, Vec<u8>) {
+        let response = Response::builder()
+            .status(status)
+            .header(header::CONTENT_TYPE, content_type)
+            .body(Body::from(body))
+            .unwrap();
+        let (parts, body) = sign_response(&signer().0, response).await.into_parts();
+        (parts.headers, to_bytes(body, usize::MAX).await.unwrap().to_vec())
+    }
+
+    fn verify(status: u16, headers: &HeaderMap, body: &[u8]) -> Result<u64, aegis_core::error::AegisError> {
+        let get = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).unwrap_or("");
+        http_sig::verify_response(
+            status,
+            get("content-type"),
+            get("content-digest"),
+            get("signature-input"),
+            get("signature"),
+            body,
+            &signer().1,
+        )
+    }
+
+    #[tokio::test]
+    async fn signs_json_responses() {
+        let (headers, body) = respond(StatusCode::OK, "application/json", r#"{"valid":true}"#).await;
+        assert_eq!(body, br#"{"valid":true}"#);
+        assert!(headers["signature-input"].to_str().unwrap().starts_with("sig1=(\"@status\""));
+        verify(200, &headers, &body).unwrap();
+
+        let (headers, body) = respond(StatusCode::NOT_FOUND, "application/problem+json; charset=utf-8", "{}").await;
+        verify(404, &headers, &body).unwrap();
+    }
+
+    #[tokio::test]
+    async fn rejects_tampered_headers() {
+        let (headers, body) = respond(StatusCode::OK, "application/json", r#"{"valid":true}"#).await;
+        assert!(verify(500, &headers, &body).is_err());
+
+        let mut retyped = headers.clone();
+        retyped.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json; charset=latin1"));
+        assert!(verify(200, &retyped, &body).is_err());
+
+        let forged = br#"{"valid":false}"#;
+        assert!(verify(200, &headers, forged).is_err());
+        let mut redigested = headers.clone();
+        let digest = http_sig::content_digest(forged);
+        redigested.insert("content-digest", HeaderValue::from_str(&digest).unwrap());
+        assert!(verify(200, &redigested, forged).is_err());
+
+        let mut backdated = headers.clone();
+        let input = headers["signature-input"].to_str().unwrap();
+        let (before, _) = input.split_once(";created=").unwrap();
+        let (_, after) = input.split_once(";keyid=").unwrap();
+        let input = format!("{};created=1;keyid={}", before, after);
+        backdated.insert("signature-input", HeaderValue::from_str(&input).unwrap());
+        assert!(verify(200, &backdated, &body).is_err());
+    }
+
+    #[tokio::test]
+    async fn leaves_other_responses_unsigned() {
+        let (headers, body) = respond(StatusCode::OK, "application/octet-stream", "sealed").await;
+        assert_eq!(body, b"sealed");
+        for name in ["content-digest", "signature-input", "signature"] {
+            assert!(!headers.contains_key(name), "{}", name);
+        }
+        assert!(is_json("application/json"));
+        assert!(is_json(" application/json ; charset=utf-8"));
+        assert!(is_json("application/problem+json"));
+        assert!(!is_json("application/jsonx"));
+        assert!(!is_json("text/json+html"));
+        assert!(!is_json(""));
+    }
+}
@@ -0,0 +1,357 @@
+// aegis-sealer-service/src/rollup.rs
+
+// Daily rollups of the seals in the audit log (see `aegis_core::rollup`):
+// one signed, timestamped summary proving everything sealed on a UTC day.
+// Once a day is over, the service builds a Merkle tree over the image
+// SHA-256 of each of its audit log entries, signs the root, gets an RFC 3161
+// token over the signature if a TSA is configured (`AEGIS_TSA_URL`), and
+// stores the rollup with every seal's inclusion proof.
+//
+// Rollups are written to `AEGIS_ROLLUP_DIR` (default `aegis-rollups`), one
+// `YYYY-MM-DD.json` per day; set it to an empty string to disable them.
+// They are built from the audit log, so they are disabled with it too. A
+// background job runs hourly and rolls up each finished day that has seals
+// but no rollup yet; POST /admin/rollups runs it now. A written rollup is
+// never rebuilt. A day whose TSA request fails keeps no token, unless
+// `AEGIS_TSA_REQUIRED=true`, in which case it is left for the next run.
+//
+// GET /rollups/{date} returns a day's signed rollup and the image hashes it
+// covers, in order; GET /rollups/{date}/proof/{hash} the inclusion proof of
+// the first seal of an image that day, which
+// `rollup::verify_inclusion()` checks against the service's public key:
+//
+//     GET /rollups/2025-06-01/proof/9f86d0...
+
+use crate::{openapi::ApiSchema, AppError, AppState};
+use aegis_core::{
+    crypto::SignatureContext,
+    merkle::{self, InclusionProof, MerkleTree},
+    rollup::{self, RollupHead, SignedRollup},
+    time,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::env;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+const RUN_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+pub struct Rollups {
+    dir: Option<PathBuf>,
+    /// Held while the job runs, so two runs never build the same day.
+    running: Mutex<()>,
+}
+
+impl Rollups {
+    /// Opens the rollup directory from `AEGIS_ROLLUP_DIR`, creating it.
+    pub async fn open(audit_log_enabled: bool) -> anyhow::Result<Self> {
+        let dir = env::var("AEGIS_ROLLUP_DIR").unwrap_or_else(|_| "aegis-rollups".into());
+        let dir = if dir.is_empty() {
+            warn!("AEGIS_ROLLUP_DIR is empty; seals are not rolled up.");
+            None
+        } else if !audit_log_enabled {
+            warn!("The audit log is disabled; seals are not rolled up.");
+            None
+        } else {
+            tokio::fs::create_dir_all(&dir).await?;
+            info!(dir = %dir, "Daily rollups enabled.");
+            Some(PathBuf::from(dir))
+        };
+        Ok(Rollups { dir, running: Mutex::new(()) })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.dir.is_some()
+    }
+}
+
+/// A rollup as stored: the signed rollup and every seal it covers.
+#[derive(Serialize, Deserialize)]
+struct Stored {
+    rollup: SignedRollup,
+    seals: Vec<Seal>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Seal {
+    image_sha256: String,
+    /// The seal's entry in the audit log.
+    audit_seq: u64,
+    proof: InclusionProof,
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)
+}
+
+/// Starts the hourly job if rollups are enabled.
+pub fn spawn(state: AppState) {
+    if !state.rollups.is_enabled() {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(RUN_INTERVAL);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = run(&state).await {
+                warn!(error = %e.1, "Rolling up seals failed; retrying on the next run.");
+            }
+        }
+    });
+}
+
+/// Rolls up each finished day with seals but no rollup, returning the days
+/// rolled up.
+pub async fn run(state: &AppState) -> Result<Vec<String>, AppError> {
+    let Some(dir) = &state.rollups.dir else {
+        return Ok(Vec::new());
+    };
+    let _running = state.rollups.running.lock().await;
+    let Some(entries) = state.audit_log.entries().await? else {
+        return Ok(Vec::new());
+    };
+    let today = rollup::date_of(unix_now());
+    let mut days: BTreeMap<String, Vec<(u64, [u8; 32])>> = BTreeMap::new();
+    for entry in entries {
+        let Some(at) = time::parse_rfc3339(&entry.at) else {
+            continue;
+        };
+        let date = rollup::date_of(at);
+        if date >= today {
+            continue;
+        }
+        let image_sha256: [u8; 32] = hex::decode(&entry.image_sha256)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| {
+                AppError(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Audit log entry {} has no image SHA-256.", entry.seq),
+                )
+            })?;
+        days.entry(date).or_default().push((entry.seq, image_sha256));
+    }
+
+    let mut rolled_up = Vec::new();
+    for (date, seals) in days {
+        let path = dir.join(format!("{}.json", date));
+        if tokio::fs::try_exists(&path).await? {
+            continue;
+        }
+        let stored = build(state, &date, &seals).await?;
+        let temp = dir.join(format!("{}.json.tmp", date));
+        tokio::fs::write(&temp, serde_json::to_vec_pretty(&stored)?).await?;
+        tokio::fs::File::open(&temp).await?.sync_all().await?;
+        tokio::fs::rename(&temp, &path).await?;
+        info!(date = %date, seals = seals.len(), "Rolled up a day of seals.");
+        rolled_up.push(date);
+    }
+    Ok(rolled_up)
+}
+
+/// Builds and signs the rollup of one day's seals, as (audit log sequence
+/// number, image SHA-256) in order.
+async fn build(state: &AppState, date: &str, seals: &[(u64, [u8; 32])]) -> Result<Stored, AppError> {
+    let mut tree = MerkleTree::new();
+    for (_, image_sha256) in seals {
+        tree.push(merkle::leaf_hash(image_sha256));
+    }
+    let size = tree.len();
+    let head = RollupHead {
+        date: date.to_string(),
+        seal_count: size,
+        root_hash: tree.root_at(size)?,
+        signed_at: unix_now(),
+    };
+    let signer = state.signer.pin()?;
+    let signature = signer.sign(&SignatureContext::Rollup.message(&head.statement())).await?;
+    let signature = signature.to_bytes().to_vec();
+    let timestamp_token = match &state.tsa {
+        None => None,
+        Some(tsa) => match tsa.token(&signature).await {
+            Ok(token) => Some(token),
+            Err(e) if tsa.required() => {
+                return Err(AppError(
+                    StatusCode::BAD_GATEWAY,
+                    format!("Timestamping the rollup of {} failed: {}", date, e),
+                ));
+            }
+            Err(e) => {
+                warn!(error = %e, url = %tsa.url(), date = %date, "Timestamping failed; storing the rollup without a timestamp.");
+                None
+            }
+        },
+    };
+    let seals = seals
+        .iter()
+        .enumerate()
+        .map(|(index, (audit_seq, image_sha256))| {
+            Ok(Seal {
+                image_sha256: hex::encode(image_sha256),
+                audit_seq: *audit_seq,
+                proof: tree.inclusion_proof(index as u64, size)?,
+            })
+        })
+        .collect::<Result<_, AppError>>()?;
+    Ok(Stored {
+        rollup: SignedRollup {
+            head,
+            public_key: signer.public_key()?.to_sec1_bytes().to_vec(),
+            signature,
+            timestamp_token,
+        },
+        seals,
+    })
+}
+
+/// Reads a stored rollup, failing with 404 when there is none.
+async fn load(state: &AppState, date: &str) -> Result<Stored, AppError> {
+    let Some(dir) = &state.rollups.dir else {
+        return Err(AppError(
+            StatusCode::NOT_FOUND,
+            "Rollups are disabled (AEGIS_ROLLUP_DIR, AEGIS_AUDIT_LOG).".into(),
+        ));
+    };
+    if rollup::day_start(date).is_none() {
+        return Err(AppError(StatusCode::BAD_REQUEST, "The date must be a YYYY-MM-DD day.".into()));
+    }
+    let contents = match tokio::fs::read(dir.join(format!("{}.json", date))).await {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(AppError(
+                StatusCode::NOT_FOUND,
+                format!("There is no rollup of {}; days are rolled up once they are over, if they have seals.", date),
+            ));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    Ok(serde_json::from_slice(&contents)?)
+}
+
+/// GET /rollups/{date}
+pub async fn rollup_handler(
+    State(state): State<AppState>,
+    Path(date): Path<String>,
+) -> Result<Json<RollupResponse>, AppError> {
+    let stored = load(&state, &date).await?;
+    Ok(Json(RollupResponse {
+        rollup: stored.rollup,
+        image_sha256: stored.seals.into_iter().map(|seal| seal.image_sha256).collect(),
+    }))
+}
+
+/// GET /rollups/{date}/proof/{hash}
+pub async fn proof_handler(
+    State(state): State<AppState>,
+    Path((date, hash)): Path<(String, String)>,
+) -> Result<Json<RollupProofResponse>, AppError> {
+    let hash = hash.to_ascii_lowercase();
+    let stored = load(&state, &date).await?;
+    let Some(seal) = stored.seals.into_iter().find(|seal| seal.image_sha256 == hash) else {
+        return Err(AppError(
+            StatusCode::NOT_FOUND,
+            format!("No seal of that image is in the rollup of {}.", date),
+        ));
+    };
+    Ok(Json(RollupProofResponse {
+        image_sha256: seal.image_sha256,
+        audit_seq: seal.audit_seq,
+        proof: seal.proof,
+        rollup: stored.rollup,
+    }))
+}
+
+/// POST /admin/rollups
+pub async fn run_handler(State(state): State<AppState>) -> Result<Json<Value>, AppError> {
+    if !state.rollups.is_enabled() {
+        return Err(AppError(
+            StatusCode::NOT_FOUND,
+            "Rollups are disabled (AEGIS_ROLLUP_DIR, AEGIS_AUDIT_LOG).".into(),
+        ));
+    }
+    Ok(Json(json!({ "rolled_up": run(&state).await? })))
+}
+
+fn rollup_schema() -> Value {
+    let hex = json!({ "type": "string", "pattern": "^[0-9a-f]*$" });
+    json!({
+        "type": "object",
+        "required": ["date", "seal_count", "root_hash", "signed_at", "public_key", "signature"],
+        "properties": {
+            "date": { "type": "string", "format": "date" },
+            "seal_count": { "type": "integer" },
+            "root_hash": hex,
+            "signed_at": { "type": "string", "format": "date-time" },
+            "public_key": hex,
+            "signature": hex,
+            "timestamp_token": { "type": "string", "contentEncoding": "base64" },
+        },
+    })
+}
+
+/// The body of /rollups/{date}.
+#[derive(Serialize)]
+pub struct RollupResponse {
+    rollup: SignedRollup,
+    /// The leaves' image hashes, in order.
+    image_sha256: Vec<String>,
+}
+
+impl ApiSchema for RollupResponse {
+    const NAME: &'static str = "RollupResponse";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["rollup", "image_sha256"],
+            "properties": {
+                "rollup": rollup_schema(),
+                "image_sha256": { "type": "array", "items": { "type": "string", "pattern": "^[0-9a-f]{64}$" } },
+            },
+        })
+    }
+}
+
+/// The body of /rollups/{date}/proof/{hash}.
+#[derive(Serialize)]
+pub struct RollupProofResponse {
+    image_sha256: String,
+    audit_seq: u64,
+    proof: InclusionProof,
+    rollup: SignedRollup,
+}
+
+impl ApiSchema for RollupProofResponse {
+    const NAME: &'static str = "RollupProofResponse";
+
+    fn schema() -> Value {
+        let hex = json!({ "type": "string", "pattern": "^[0-9a-f]*$" });
+        json!({
+            "type": "object",
+            "required": ["image_sha256", "audit_seq", "proof", "rollup"],
+            "properties": {
+                "image_sha256": hex,
+                "audit_seq": { "type": "integer" },
+                "proof": {
+                    "type": "object",
+                    "required": ["leaf_index", "tree_size", "audit_path"],
+                    "properties": {
+                        "leaf_index": { "type": "integer" },
+                        "tree_size": { "type": "integer" },
+                        "audit_path": { "type": "array", "items": hex },
+                    },
+                },
+                "rollup": rollup_schema(),
+            },
+        })
+    }
+}
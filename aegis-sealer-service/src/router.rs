@@ -0,0 +1,242 @@
+// aegis-sealer-service/src/router.rs
+
+// Route table and middleware stack. `router()` gives the stock service;
+// `RouterBuilder` is the extension point for embedders.
+
+use crate::{
+    admission::{self, Admission},
+    audit_log,
+    auth::{self, Access, AuthPolicy},
+    batch, capabilities, capture, cron_job_handler, events, export, feed, health, ingest, jwks, metrics,
+    mirror::{self, Mirror},
+    openapi,
+    quota::{self, Quotas},
+    response_sig, rollup, root_redirect_handler, seal_handler, sealed_download_handler,
+    static_docs::CachedDocument, transparency, verify_notify, wal, AppState,
+};
+use axum::{
+    extract::{DefaultBodyLimit, Request},
+    http::{header, Method},
+    middleware,
+    response::IntoResponse,
+    routing::{get, post, MethodRouter, Route},
+    Router,
+};
+use std::convert::Infallible;
+use std::sync::Arc;
+use tower::{Layer, Service};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use tracing::warn;
+
+/// Routes anonymous callers may reach. Routes not listed here require an
+/// API key once keys are configured.
+pub(crate) const PUBLIC_ROUTES: [(&str, Access); 18] = [
+    ("/", Access::Public),
+    ("/cron", Access::Public),
+    ("/healthz", Access::Public),
+    ("/readyz", Access::Public),
+    ("/capabilities", Access::Public),
+    ("/openapi.json", Access::Public),
+    ("/docs", Access::Public),
+    ("/keys", Access::Public),
+    ("/keys/dns", Access::Public),
+    // Proofs are for third parties checking a file they hold.
+    ("/log/proof/{hash}", Access::Public),
+    ("/log/consistency", Access::Public),
+    ("/rollups/{date}", Access::Public),
+    ("/rollups/{date}/proof/{hash}", Access::Public),
+    // Consumers need the schemas before they hold a key.
+    ("/events/schema", Access::Public),
+    ("/events/schema/{version}", Access::Public),
+    ("/feed/json", Access::Public),
+    ("/feed/atom", Access::Public),
+    // Verification only reads what the caller already holds.
+    ("/verify", Access::Public),
+];
+
+type SealLayer = Box<dyn Fn(MethodRouter<AppState>) -> MethodRouter<AppState> + Send + Sync>;
+
+/// The service's routes with all of its middleware, ready for
+/// `.with_state(state)`.
+pub fn router(state: &AppState) -> anyhow::Result<Router<AppState>> {
+    RouterBuilder::new(state).build()
+}
+
+/// Builds the service router with embedder additions:
+///
+/// - `seal_layer` wraps the endpoints that sign new containers (`/seal`,
+///   `/seal/batch`, `/reseal`, `/ingest/dam`) in a tower layer, inside authentication and
+///   admission control.
+/// - `routes` adds routes that sit behind the same authentication and
+///   middleware as the built-in ones. Routes added to the finished router
+///   with `Router::route` would bypass them.
+///
+/// For per-seal logic that needs the seal's contents rather than the raw
+/// request, register a `hooks::SealHook` on the state instead.
+pub struct RouterBuilder {
+    state: AppState,
+    seal_layers: Vec<SealLayer>,
+    extra: Router<AppState>,
+    public_paths: Vec<&'static str>,
+}
+
+impl RouterBuilder {
+    pub fn new(state: &AppState) -> Self {
+        RouterBuilder {
+            state: state.clone(),
+            seal_layers: Vec::new(),
+            extra: Router::new(),
+            public_paths: Vec::new(),
+        }
+    }
+
+    /// Wraps every sealing endpoint in `layer`. Layers added later wrap
+    /// those added earlier.
+    pub fn seal_layer<L>(mut self, layer: L) -> Self
+    where
+        L: Layer<Route> + Clone + Send + Sync + 'static,
+        L::Service: Service<Request> + Clone + Send + Sync + 'static,
+        <L::Service as Service<Request>>::Response: IntoResponse + 'static,
+        <L::Service as Service<Request>>::Error: Into<Infallible> + 'static,
+        <L::Service as Service<Request>>::Future: Send + 'static,
+    {
+        self.seal_layers.push(Box::new(move |route| route.layer(layer.clone())));
+        self
+    }
+
+    /// Adds routes behind the service's middleware. They require an API key
+    /// once keys are configured, unless listed with `public`.
+    pub fn routes(mut self, routes: Router<AppState>) -> Self {
+        self.extra = self.extra.merge(routes);
+        self
+    }
+
+    /// Lets anonymous callers reach an added route.
+    pub fn public(mut self, path: &'static str) -> Self {
+        self.public_paths.push(path);
+        self
+    }
+
+    pub fn build(self) -> anyhow::Result<Router<AppState>> {
+        let RouterBuilder {
+            state,
+            seal_layers,
+            extra,
+            public_paths,
+        } = self;
+
+        let origins = match &state.config.cors_origins {
+            Some(origins) => AllowOrigin::list(origins.iter().cloned()),
+            None => {
+                warn!("CORS is configured to allow all origins. This is a potential security risk.");
+                AllowOrigin::from(Any)
+            }
+        };
+        let cors = CorsLayer::new()
+            .allow_origin(origins)
+            .allow_methods([Method::POST, Method::OPTIONS, Method::GET, Method::HEAD, Method::PUT, Method::DELETE])
+            .allow_headers([
+                header::CONTENT_TYPE,
+                header::AUTHORIZATION,
+                header::HeaderName::from_static("x-api-key"),
+            ]);
+
+        let admission = Arc::new(Admission::from_env()?);
+        let mirror = Arc::new(Mirror::from_env()?);
+        let quotas = Arc::new(Quotas::from_env()?);
+        let mut public = PUBLIC_ROUTES.to_vec();
+        // The DAM authenticates with its own webhook signature, so the
+        // webhook is public only when there is a secret to check it with;
+        // without one it needs an API key like any other sealing route.
+        if state.config.dam.is_authenticated() {
+            public.push(("/ingest/dam", Access::Public));
+        }
+        public.extend(public_paths.into_iter().map(|path| (path, Access::Public)));
+        let auth_policy = Arc::new(AuthPolicy::from_env(&public)?);
+
+        let capabilities = CachedDocument::json(
+            &capabilities::document(&state, &admission, &quotas, &auth_policy),
+            &state.config.cache_control,
+        )?;
+        let openapi = CachedDocument::json(&openapi::document(&auth_policy), &state.config.cache_control)?;
+        let docs = CachedDocument::new("text/html; charset=utf-8", openapi::swagger_ui(), &state.config.cache_control)?;
+
+        let sealing = |route: MethodRouter<AppState>| seal_layers.iter().fold(route, |route, layer| layer(route));
+        // Outermost on the sealing routes, so requests turned away by
+        // admission control are counted, and captured, too.
+        let instrumented = |route: MethodRouter<AppState>| {
+            route
+                .layer(middleware::from_fn_with_state(state.captures.clone(), capture::record))
+                .layer(middleware::from_fn_with_state(state.metrics.clone(), metrics::instrument_seals))
+        };
+        let app = Router::new();
+        #[cfg(feature = "alloc-stats")]
+        let app = app.route("/debug/memory", get(crate::alloc_stats::memory_handler));
+        #[cfg(feature = "verifier")]
+        let app = app
+            .route("/reseal", instrumented(sealing(post(crate::reseal::reseal_handler))))
+            .route("/verify", post(crate::verify_handler));
+        let app = app
+            .route(
+                "/seal",
+                // Mirroring sits inside admission control so shadowed requests
+                // are buffered only once they have been admitted. Quotas come
+                // first, so over-quota clients never hold an admission slot.
+                instrumented(
+                    sealing(post(seal_handler))
+                        .layer(middleware::from_fn_with_state(mirror, mirror::shadow))
+                        .layer(middleware::from_fn_with_state(admission.clone(), admission::limit))
+                        .layer(middleware::from_fn_with_state(quotas.clone(), quota::limit)),
+                ),
+            )
+            .route(
+                "/seal/batch",
+                instrumented(
+                    sealing(post(batch::batch_seal_handler))
+                        .layer(middleware::from_fn_with_state(admission.clone(), admission::limit))
+                        .layer(middleware::from_fn_with_state(quotas, quota::limit)),
+                ),
+            )
+            .route("/feed/json", get(feed::json_feed_handler))
+            .route("/feed/atom", get(feed::atom_feed_handler))
+            .route("/ingest/dam", instrumented(sealing(post(ingest::dam_webhook_handler))))
+            .route("/export/bundle", post(export::bundle_handler))
+            .route("/sealed/{name}", get(sealed_download_handler))
+            .route(
+                "/sealed/{name}/notifications",
+                get(verify_notify::get_handler)
+                    .put(verify_notify::put_handler)
+                    .delete(verify_notify::delete_handler),
+            )
+            .route("/metrics", get(metrics::metrics_handler))
+            .route("/admin/wal", get(wal::unresolved_handler))
+            .route("/audit", get(audit_log::audit_handler))
+            .route("/log/proof/{hash}", get(transparency::proof_handler))
+            .route("/log/consistency", get(transparency::consistency_handler))
+            .route("/rollups/{date}", get(rollup::rollup_handler))
+            .route("/rollups/{date}/proof/{hash}", get(rollup::proof_handler))
+            .route("/admin/rollups", post(rollup::run_handler))
+            .route("/events/schema", get(events::schemas_handler))
+            .route("/events/schema/{version}", get(events::schema_handler))
+            .route("/admin/captures", get(capture::list_handler))
+            .route("/admin/captures/{request_id}", get(capture::get_handler))
+            .route("/admin/wal/{id}/resolve", post(wal::resolve_handler))
+            .route("/capabilities", capabilities.route())
+            .route("/openapi.json", openapi.route())
+            .route("/docs", docs.route())
+            .route("/keys", get(jwks::keys_handler))
+            .route("/keys/dns", get(jwks::dns_records_handler))
+            .route("/cron", get(cron_job_handler))
+            .route("/healthz", get(health::healthz_handler))
+            .route("/readyz", get(health::readyz_handler))
+            .route("/", get(root_redirect_handler).head(root_redirect_handler))
+            .merge(extra)
+            .route_layer(middleware::from_fn_with_state(auth_policy, auth::enforce))
+            .layer(middleware::from_fn_with_state(state.metrics.clone(), metrics::count_responses))
+            .layer(middleware::from_fn_with_state(state.clone(), response_sig::sign))
+            .layer(middleware::from_fn(audit_log::request_id))
+            .layer(DefaultBodyLimit::max(state.config.max_body))
+            .layer(cors);
+        Ok(app)
+    }
+}
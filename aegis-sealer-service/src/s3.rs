@@ -0,0 +1,151 @@
+// aegis-sealer-service/src/s3.rs
+
+// Minimal S3 access: path-style object URLs against a configured endpoint,
+// signed with AWS Signature Version 4 when credentials are present. /verify
+// reads `s3://` URLs through it and `storage` writes sealed files with it. The
+// endpoint must be plain HTTP (MinIO, a VPC endpoint behind a TLS sidecar,
+// ...), as with every outbound call made by `http_client`.
+
+use crate::http_client::{self, HttpResponse};
+use crate::sigv4::{self, Credentials};
+use std::env;
+use std::time::SystemTime;
+
+pub struct S3Config {
+    endpoint: String,
+    region: String,
+    credentials: Option<Credentials>,
+}
+
+impl S3Config {
+    /// Reads `AEGIS_S3_ENDPOINT` (required), `AEGIS_S3_REGION` (default
+    /// `us-east-1`) and the standard `AWS_ACCESS_KEY_ID`,
+    /// `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN`. Without credentials
+    /// requests are sent unsigned.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let endpoint = env::var("AEGIS_S3_ENDPOINT")
+            .map_err(|_| anyhow::anyhow!("AEGIS_S3_ENDPOINT must be set to use S3"))?;
+        Ok(S3Config {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            region: env::var("AEGIS_S3_REGION").unwrap_or_else(|_| "us-east-1".into()),
+            credentials: Credentials::from_env(),
+        })
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Checks that the endpoint answers. Any HTTP answer will do, as there
+    /// is no bucket to ask about until a URL names one.
+    #[cfg(feature = "verifier")]
+    pub async fn check(&self) -> anyhow::Result<()> {
+        http_client::get(&format!("{}/", self.endpoint), 64 * 1024).await.map(|_| ())
+    }
+
+    /// Sends a bodiless request for an object, e.g. a ranged `GET`.
+    pub async fn request(
+        &self,
+        method: &str,
+        bucket: &str,
+        key: &str,
+        extra_headers: &[(&str, &str)],
+        max_body: usize,
+    ) -> anyhow::Result<HttpResponse> {
+        self.send(method, bucket, key, extra_headers, &[], max_body).await
+    }
+
+    /// Writes an object with a single `PUT`.
+    pub async fn put(&self, bucket: &str, key: &str, content_type: &str, body: &[u8]) -> anyhow::Result<()> {
+        let resp = self.send("PUT", bucket, key, &[("Content-Type", content_type)], body, 64 * 1024).await?;
+        if !resp.is_success() {
+            anyhow::bail!(
+                "S3 PUT s3://{}/{} returned HTTP {}: {}",
+                bucket,
+                key,
+                resp.status,
+                String::from_utf8_lossy(&resp.body).chars().take(200).collect::<String>()
+            );
+        }
+        Ok(())
+    }
+
+    async fn send(
+        &self,
+        method: &str,
+        bucket: &str,
+        key: &str,
+        extra_headers: &[(&str, &str)],
+        body: &[u8],
+        max_body: usize,
+    ) -> anyhow::Result<HttpResponse> {
+        let path = format!("/{}/{}", uri_encode(bucket, false), uri_encode(key, true));
+        let url = format!("{}{}", self.endpoint, path);
+        let mut headers: Vec<(String, String)> = extra_headers
+            .iter()
+            .map(|(n, v)| (n.to_string(), v.to_string()))
+            .collect();
+        if let Some(credentials) = &self.credentials {
+            let host = http_client::authority(&url)?;
+            let request = sigv4::Request {
+                service: "s3",
+                region: &self.region,
+                method,
+                path: &path,
+                query: "",
+                host: &host,
+                headers: &[],
+                payload: body,
+            };
+            headers.extend(sigv4::sign(credentials, &request, SystemTime::now()));
+        }
+        let header_refs: Vec<(&str, &str)> = headers.iter().map(|(n, v)| (n.as_str(), v.as_str())).collect();
+        http_client::request(method, &url, &header_refs, body, max_body).await
+    }
+}
+
+/// Percent-encodes per SigV4: everything but unreserved characters, and `/`
+/// when encoding an object key.
+fn uri_encode(s: &str, keep_slash: bool) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => out.push(b as char),
+            b'/' if keep_slash => out.push('/'),
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+/// Splits `s3://bucket/key` into its bucket and key.
+#[cfg(feature = "verifier")]
+pub fn parse_url(url: &str) -> Option<(&str, &str)> {
+    let (bucket, key) = url.strip_prefix("s3://")?.split_once('/')?;
+    (!bucket.is_empty() && !key.is_empty()).then_some((bucket, key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_object_paths() {
+        assert_eq!(uri_encode("photos", false), "photos");
+        assert_eq!(uri_encode("2024/a b+c.aegis", true), "2024/a%20b%2Bc.aegis");
+        assert_eq!(uri_encode("a/b", false), "a%2Fb");
+        assert_eq!(uri_encode("test$file.text", true), "test%24file.text");
+        assert_eq!(uri_encode("-_.~", true), "-_.~");
+        assert_eq!(uri_encode("é", true), "%C3%A9");
+    }
+
+    #[cfg(feature = "verifier")]
+    #[test]
+    fn parses_s3_urls() {
+        assert_eq!(parse_url("s3://photos/2024/a.aegis"), Some(("photos", "2024/a.aegis")));
+        assert_eq!(parse_url("s3://photos/"), None);
+        assert_eq!(parse_url("s3:///a.aegis"), None);
+        assert_eq!(parse_url("s3://photos"), None);
+        assert_eq!(parse_url("http://photos/a.aegis"), None);
+    }
+}
@@ -0,0 +1,752 @@
+// aegis-sealer-service/src/settings.rs
+
+// The catalogue of every environment setting the service reads, with its
+// type, default and allowed values. It backs `aegis config schema`, which
+// prints the catalogue as a JSON Schema, and the check run at startup and by
+// `aegis config check`, which rejects a value the service would misread and
+// says where it came from and what is allowed:
+//
+//     .env:12: AEGIS_SIGNER: 'vaul' is not one of env, vault-kv, vault-transit, azure-keyvault, aws-kms, gcp-kms, keyring
+//
+// The schema gives values their JSON types (a boolean, an integer, an
+// array for comma-separated lists); in the environment and `.env` they are
+// written as text. Unknown `AEGIS_*` names are reported as warnings, since a
+// misspelt setting is otherwise silently ignored.
+//
+// Settings may also come from a TOML file named by `AEGIS_CONFIG` (see
+// `config_file`), which is checked the same way; the environment and `.env`
+// override it.
+//
+// Modules still read their own settings from the environment; a new
+// setting must be added here as well.
+
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy)]
+pub enum Kind {
+    Text,
+    Bool,
+    Integer { min: u64, max: u64 },
+    /// A non-negative number, possibly fractional.
+    Number,
+    OneOf(&'static [&'static str]),
+    /// A comma-separated list of values from the given set.
+    ListOf(&'static [&'static str]),
+    /// A comma-separated list of free-form values.
+    List,
+    Url,
+    Path,
+    Json,
+    /// Checked by the function; the text describes what it accepts.
+    Custom(fn(&str) -> bool, &'static str),
+}
+
+pub struct Setting {
+    pub name: &'static str,
+    pub kind: Kind,
+    pub default: Option<&'static str>,
+    pub description: &'static str,
+    /// Never echoed in problems.
+    pub secret: bool,
+}
+
+const fn setting(name: &'static str, kind: Kind, default: Option<&'static str>, description: &'static str) -> Setting {
+    Setting { name, kind, default, description, secret: false }
+}
+
+const fn secret(name: &'static str, kind: Kind, description: &'static str) -> Setting {
+    Setting { name, kind, default: None, description, secret: true }
+}
+
+pub const SETTINGS: &[Setting] = &[
+    setting("AEGIS_CONFIG", Kind::Path, None, "TOML file of settings, which the environment overrides."),
+    setting("PORT", Kind::Integer { min: 1, max: 65535 }, Some("10000"), "Port the service listens on."),
+    setting(
+        "AEGIS_LISTEN",
+        Kind::Custom(is_socket_address, "an address and port such as 0.0.0.0:10000 or [::]:10000"),
+        None,
+        "Address and port the service listens on; overrides PORT.",
+    ),
+    setting(
+        "AEGIS_CORS_ORIGINS",
+        Kind::Custom(is_cors_origins, "* or a list of origins such as https://app.example"),
+        Some("*"),
+        "Origins browsers may call the API from, or * for any.",
+    ),
+    setting(
+        "AEGIS_MAX_BODY",
+        Kind::Integer { min: 1, max: u32::MAX as u64 },
+        Some("104857600"),
+        "Largest request body accepted, in bytes.",
+    ),
+    setting(
+        "AEGIS_MAX_INFLATE",
+        Kind::Integer { min: 1, max: u32::MAX as u64 },
+        Some("67108864"),
+        "Most a compressed block in a container sent to /verify may decompress to, in bytes.",
+    ),
+    setting("AEGIS_ROOT_REDIRECT", Kind::Url, Some("https://www.google.com"), "Where GET / redirects."),
+    setting(
+        "HTTP_PROXY",
+        Kind::Custom(is_http_proxy, "an http:// URL or host:port"),
+        None,
+        "Forward proxy for outbound requests.",
+    ),
+    setting("NO_PROXY", Kind::List, None, "Hosts outbound requests reach without the proxy."),
+    setting(
+        "AEGIS_SIGNER",
+        Kind::OneOf(&["env", "vault-kv", "vault-transit", "azure-keyvault", "aws-kms", "gcp-kms", "keyring"]),
+        Some("env"),
+        "Where the signing key comes from.",
+    ),
+    secret(
+        "AEGIS_PRIVATE_KEY",
+        Kind::Custom(is_private_key, "64 hex digits"),
+        "The P-256 private scalar in hex, for the env signer.",
+    ),
+    setting("AEGIS_KEYRING", Kind::Path, None, "Keyring file or directory, for the keyring signer."),
+    setting("AEGIS_VAULT_KV_MOUNT", Kind::Text, Some("secret"), "Vault KV v2 mount holding the key."),
+    setting("AEGIS_VAULT_KV_PATH", Kind::Text, None, "Path of the Vault KV secret holding the key."),
+    setting("AEGIS_VAULT_KV_FIELD", Kind::Text, Some("private_key"), "Field of the Vault KV secret holding the key."),
+    setting("AEGIS_VAULT_TRANSIT_MOUNT", Kind::Text, Some("transit"), "Vault Transit mount."),
+    setting("AEGIS_VAULT_TRANSIT_KEY", Kind::Text, None, "Vault Transit key name."),
+    setting("VAULT_ADDR", Kind::Url, None, "Vault server address."),
+    secret("VAULT_TOKEN", Kind::Text, "Vault token."),
+    setting("VAULT_ROLE_ID", Kind::Text, None, "Vault AppRole role ID."),
+    secret("VAULT_SECRET_ID", Kind::Text, "Vault AppRole secret ID."),
+    setting("VAULT_NAMESPACE", Kind::Text, None, "Vault Enterprise namespace."),
+    setting("AZURE_KEYVAULT_URL", Kind::Url, None, "Azure Key Vault URL."),
+    setting("AZURE_KEYVAULT_KEY", Kind::Text, None, "Azure Key Vault key name."),
+    setting("AZURE_KEYVAULT_KEY_VERSION", Kind::Text, None, "Azure Key Vault key version; the latest if unset."),
+    setting("AZURE_TENANT_ID", Kind::Text, None, "Azure AD tenant."),
+    setting("AZURE_CLIENT_ID", Kind::Text, None, "Azure AD client ID."),
+    secret("AZURE_CLIENT_SECRET", Kind::Text, "Azure AD client secret."),
+    setting("AZURE_AUTHORITY_URL", Kind::Url, Some("https://login.microsoftonline.com"), "Azure AD authority."),
+    setting("AEGIS_AWS_KMS_KEY_ID", Kind::Text, None, "AWS KMS key ID, ARN or alias, for the aws-kms signer."),
+    setting("AEGIS_AWS_KMS_ENDPOINT", Kind::Url, None, "AWS KMS endpoint, through a TLS egress sidecar."),
+    setting("AEGIS_AWS_KMS_REGION", Kind::Text, Some("us-east-1"), "AWS KMS signing region."),
+    setting("AEGIS_GCP_KMS_KEY", Kind::Text, None, "Cloud KMS crypto key version name, for the gcp-kms signer."),
+    setting("AEGIS_GCP_KMS_ENDPOINT", Kind::Url, None, "Cloud KMS endpoint, through a TLS egress sidecar."),
+    setting("GCE_METADATA_HOST", Kind::Text, Some("metadata.google.internal"), "GCP metadata server for access tokens."),
+    secret("AEGIS_API_KEYS", Kind::List, "API keys, each optionally prefixed with `tenant:`."),
+    setting(
+        "AEGIS_ROUTE_ACCESS",
+        Kind::Custom(is_route_access, "a list of path=public or path=authenticated"),
+        None,
+        "Per-route access overrides.",
+    ),
+    setting("AEGIS_TRUST_PROXY", Kind::Bool, Some("false"), "Take client addresses from the last X-Forwarded-For entry."),
+    setting("AEGIS_ANON_RATE_PER_MIN", Kind::Number, Some("30"), "Anonymous requests per minute per address."),
+    setting("AEGIS_ANON_BURST", Kind::Number, Some("10"), "Anonymous request burst per address."),
+    setting("AEGIS_ANON_MAX_BODY", Kind::Number, Some("10485760"), "Largest body accepted from anonymous callers."),
+    setting(
+        "AEGIS_ANON_MAX_INFLATE",
+        Kind::Number,
+        Some("16777216"),
+        "Most a compressed block from an anonymous caller may decompress to.",
+    ),
+    setting(
+        "AEGIS_DELEGATION_KEYS",
+        Kind::List,
+        None,
+        "Hex SEC1 admin keys whose delegation tokens may authorize seals.",
+    ),
+    setting(
+        "AEGIS_SEAL_RATE_PER_MIN",
+        Kind::Integer { min: 0, max: u32::MAX as u64 },
+        Some("0"),
+        "Seal requests per minute per API key or address; 0 for no limit.",
+    ),
+    setting(
+        "AEGIS_SEAL_BYTES_PER_DAY",
+        Kind::Integer { min: 0, max: u64::MAX },
+        Some("0"),
+        "Bytes sealed per API key or address per UTC day; 0 for no limit.",
+    ),
+    setting("AEGIS_TENANTS", Kind::Json, None, "Tenant trust configuration as JSON."),
+    setting("AEGIS_TENANTS_FILE", Kind::Path, None, "File holding the tenant trust configuration."),
+    setting("AEGIS_REVOKED_KEYS", Kind::List, None, "Key fingerprints revoked for every tenant."),
+    setting("AEGIS_STORAGE", Kind::OneOf(&["files", "chunked", "s3"]), Some("files"), "How sealed files are stored."),
+    setting("AEGIS_STORAGE_DIR", Kind::Path, None, "Where sealed files are stored; AEGIS_INGEST_DIR if unset."),
+    setting("AEGIS_INGEST_DIR", Kind::Path, Some("sealed"), "Where sealed files are stored (older name)."),
+    setting("AEGIS_STORAGE_BUCKET", Kind::Text, None, "Bucket sealed files are stored in with AEGIS_STORAGE=s3."),
+    setting("AEGIS_STORAGE_PREFIX", Kind::Text, None, "Key prefix of sealed files stored in S3."),
+    setting("AEGIS_WAL_PATH", Kind::Path, Some("aegis.wal"), "Write-ahead log of seals."),
+    setting("AEGIS_AUDIT_LOG", Kind::Path, Some("aegis-audit.log"), "Hash-chained audit log of seals; empty disables."),
+    setting(
+        "AEGIS_TRANSPARENCY_LOG",
+        Kind::Path,
+        Some("aegis-transparency.log"),
+        "Merkle tree log of sealed image hashes; empty disables.",
+    ),
+    setting(
+        "AEGIS_ROLLUP_DIR",
+        Kind::Path,
+        Some("aegis-rollups"),
+        "Directory of signed daily rollups of the audit log's seals; empty disables.",
+    ),
+    setting("AEGIS_CAPTURE_FAILURES", Kind::Bool, Some("false"), "Keep redacted replay bundles of failed seal requests."),
+    setting(
+        "AEGIS_CAPTURE_LIMIT",
+        Kind::Integer { min: 1, max: 100_000 },
+        Some("100"),
+        "Failed requests kept when capturing them.",
+    ),
+    setting(
+        "AEGIS_IMAGE_FORMATS",
+        Kind::Custom(is_image_formats, "a list of jpeg, png, heic, tiff or webp"),
+        Some("jpeg,png,heic,tiff,webp"),
+        "Image formats sealed, recognized by their leading bytes.",
+    ),
+    setting(
+        "AEGIS_UNRECOGNIZED_IMAGES",
+        Kind::OneOf(&["reject", "flag"]),
+        Some("reject"),
+        "Whether uploads in other formats are refused or sealed with a flag.",
+    ),
+    setting(
+        "AEGIS_PAYLOAD_TYPES",
+        Kind::Custom(is_media_types, "a list of media types or type/* patterns"),
+        Some(crate::intake::DEFAULT_PAYLOAD_TYPES),
+        "Media types of payloads other than images that are sealed when declared.",
+    ),
+    setting("AEGIS_RECORD_IMAGE_INFO", Kind::Bool, Some("false"), "Seal the format and dimensions of each image."),
+    setting("AEGIS_TSA_URL", Kind::Url, None, "RFC 3161 timestamp authority."),
+    setting("AEGIS_TSA_REQUIRED", Kind::Bool, Some("false"), "Fail seals that cannot be timestamped."),
+    setting("AEGIS_STARTUP_CHECKS", Kind::Bool, Some("true"), "Check dependencies before taking traffic."),
+    setting(
+        "AEGIS_DEPENDENCY_POLICY",
+        Kind::Custom(is_dependency_policy, "a list of dependency=fail or dependency=degrade"),
+        None,
+        "Whether an unavailable kms, storage, tsa or s3 fails the service or degrades it.",
+    ),
+    setting(
+        "AEGIS_SIZE_CLASSES",
+        Kind::Custom(is_size_classes, "a list of name=max_bytes/concurrency/queue_limit"),
+        Some("small=2097152/32/128,medium=20971520/8/32,large=104857600/2/8"),
+        "Upload size classes for admission control.",
+    ),
+    setting("AEGIS_SHADOW_URL", Kind::Url, None, "Service that a sample of traffic is mirrored to."),
+    setting(
+        "AEGIS_SHADOW_SAMPLE_PERCENT",
+        Kind::Integer { min: 0, max: 100 },
+        Some("100"),
+        "Percentage of requests mirrored.",
+    ),
+    setting("AEGIS_TELEMETRY_URL", Kind::Url, None, "Where usage telemetry is sent."),
+    setting(
+        "AEGIS_TELEMETRY_INTERVAL_SECS",
+        Kind::Integer { min: 1, max: u32::MAX as u64 },
+        Some("60"),
+        "Seconds between telemetry flushes.",
+    ),
+    setting(
+        "AEGIS_TELEMETRY_BUFFER",
+        Kind::Integer { min: 1, max: u32::MAX as u64 },
+        Some("1000"),
+        "Telemetry events held while the collector is unreachable.",
+    ),
+    setting("AEGIS_INSTANCE_ID", Kind::Text, None, "Instance name in telemetry; HOSTNAME if unset."),
+    setting("AEGIS_PUBLIC_URL", Kind::Url, None, "Base URL of the service, for links in feeds."),
+    setting(
+        "AEGIS_FEED_REDACT",
+        Kind::ListOf(&["metadata", "image_size", "key_fingerprint"]),
+        Some("metadata"),
+        "Fields left out of the public feed.",
+    ),
+    setting("AEGIS_SPOOL_DIR", Kind::Path, None, "Where uploads are spooled; the temporary directory if unset."),
+    setting("AEGIS_SIGN_RESPONSES", Kind::Bool, Some("true"), "Sign responses with HTTP message signatures."),
+    setting(
+        "AEGIS_REQUIRE_STRUCTURED_METADATA",
+        Kind::Bool,
+        Some("false"),
+        "Reject metadata that is not a JSON object.",
+    ),
+    setting(
+        "AEGIS_CACHE_CONTROL",
+        Kind::Custom(is_header_value, "a header value"),
+        Some(crate::static_docs::DEFAULT_CACHE_CONTROL),
+        "Cache-Control for documents such as /capabilities.",
+    ),
+    setting("AEGIS_PROVENANCE", Kind::Bool, Some("false"), "Record submission provenance in metadata."),
+    setting(
+        "AEGIS_PROVENANCE_CLIENT_CERT_HEADER",
+        Kind::Custom(is_header_name, "a header name"),
+        None,
+        "Header carrying the client certificate from a TLS terminator.",
+    ),
+    setting(
+        "AEGIS_PROVENANCE_REDACT",
+        Kind::Custom(is_provenance_redact, "a list of field=omit or field=hash"),
+        None,
+        "How provenance fields are redacted.",
+    ),
+    setting("AEGIS_CERT_CHAIN", Kind::Path, None, "PEM certificate chain for the signing key, leaf first."),
+    setting(
+        "AEGIS_TRUST_HINT",
+        Kind::Custom(is_trust_hint, "DOMAIN or DOMAIN:SELECTOR"),
+        None,
+        "Where the signing keys are published in DNS, named in every container.",
+    ),
+    setting("AEGIS_ENDORSEMENTS", Kind::Path, None, "JSON endorsements of the signing keys and their issuers."),
+    setting("AEGIS_EMBED_ENDORSEMENTS", Kind::Bool, Some("false"), "Put the signing key's endorsements in every container."),
+    setting("AEGIS_TRUST_ANCHORS", Kind::Path, None, "PEM CA certificates /verify checks certificate chains against."),
+    setting(
+        "AEGIS_TRUSTED_TSAS",
+        Kind::Custom(is_fingerprints, "a list of hex key fingerprints"),
+        None,
+        "Key fingerprints of the TSAs whose timestamps /verify accepts.",
+    ),
+    setting("AEGIS_TSA_ANCHORS", Kind::Path, None, "PEM CA certificates trusted TSA certificates chain to."),
+    setting(
+        "AEGIS_ENDORSEMENT_ROOTS",
+        Kind::List,
+        None,
+        "Hex SEC1 root keys /verify requires sealing keys to be endorsed by.",
+    ),
+    setting("AEGIS_VERIFY_URL_ALLOW", Kind::List, None, "URL prefixes /verify may fetch from."),
+    setting("AEGIS_VERIFY_SLA", Kind::Bool, Some("false"), "Verification SLA mode for kiosk terminals."),
+    setting(
+        "AEGIS_VERIFY_SLA_BUDGET_MS",
+        Kind::Integer { min: 1, max: 60_000 },
+        Some("50"),
+        "Latency budget per verification in SLA mode.",
+    ),
+    setting(
+        "AEGIS_VERIFY_SLA_MAX_BYTES",
+        Kind::Integer { min: 1, max: u32::MAX as u64 },
+        Some("2097152"),
+        "Largest body verified in SLA mode.",
+    ),
+    setting("AEGIS_S3_ENDPOINT", Kind::Url, None, "S3 endpoint for s3:// URLs at /verify and S3 storage."),
+    setting("AEGIS_S3_REGION", Kind::Text, Some("us-east-1"), "S3 signing region."),
+    setting("AWS_ACCESS_KEY_ID", Kind::Text, None, "AWS access key, for S3 and AWS KMS."),
+    secret("AWS_SECRET_ACCESS_KEY", Kind::Text, "AWS secret key."),
+    secret("AWS_SESSION_TOKEN", Kind::Text, "AWS session token."),
+    secret("AEGIS_DAM_WEBHOOK_SECRET", Kind::Text, "Secret that DAM webhook payloads are signed with."),
+    setting(
+        "AEGIS_DAM_URL_FIELD",
+        Kind::Custom(is_json_pointer, "a JSON pointer"),
+        Some("/asset_url"),
+        "Where DAM webhooks carry the asset URL.",
+    ),
+    setting(
+        "AEGIS_DAM_CALLBACK_FIELD",
+        Kind::Custom(is_json_pointer, "a JSON pointer"),
+        Some("/callback_url"),
+        "Where DAM webhooks carry the callback URL.",
+    ),
+    setting(
+        "AEGIS_DAM_METADATA_MAP",
+        Kind::Custom(is_metadata_map, "a list of name=/json/pointer"),
+        None,
+        "DAM webhook fields copied into metadata.",
+    ),
+    setting("AEGIS_DAM_ALLOWED_HOSTS", Kind::List, None, "Hosts DAM asset and callback URLs may name."),
+    setting("AEGIS_WEBHOOK_URLS", Kind::List, None, "URLs seal events are posted to."),
+    secret("AEGIS_WEBHOOK_SECRET", Kind::Text, "Secret that seal webhook bodies are signed with."),
+    setting(
+        "AEGIS_WEBHOOK_RETRIES",
+        Kind::Integer { min: 0, max: 20 },
+        Some("5"),
+        "Retries of a failed webhook delivery.",
+    ),
+    setting(
+        "AEGIS_WEBHOOK_BACKOFF_MS",
+        Kind::Integer { min: 1, max: 60_000 },
+        Some("500"),
+        "Wait before the first webhook retry, doubled for each after.",
+    ),
+    setting(
+        "AEGIS_WEBHOOK_FORMAT",
+        Kind::Custom(is_event_format, "json or protobuf, optionally with ;version=N"),
+        Some("json;version=1"),
+        "Encoding and schema version of webhook events.",
+    ),
+    setting(
+        "AEGIS_VERIFY_NOTIFICATIONS",
+        Kind::Path,
+        None,
+        "Registry of stored seals' owners and verification notification endpoints; unset disables.",
+    ),
+    setting(
+        "AEGIS_NOTIFY_ALLOWED_HOSTS",
+        Kind::List,
+        None,
+        "Hosts verification notification endpoints may name; unset allows any host with public addresses.",
+    ),
+    setting(
+        "AEGIS_GEO_HEADER",
+        Kind::Custom(is_header_name, "a header name"),
+        None,
+        "Header with the client's two-letter country, set by a CDN or proxy.",
+    ),
+    setting(
+        "AEGIS_DISPLAY_TZ",
+        Kind::Custom(is_offset, "UTC or an offset such as +02:00"),
+        Some("UTC"),
+        "Time zone for times shown by the command-line tools.",
+    ),
+    setting(
+        "AEGIS_DISPLAY_LOCALE",
+        Kind::Custom(is_locale, "a locale such as en-US or de"),
+        Some("rfc3339"),
+        "Locale for times shown by the command-line tools.",
+    ),
+];
+
+pub fn find(name: &str) -> Option<&'static Setting> {
+    SETTINGS.iter().find(|s| s.name == name)
+}
+
+/// The catalogue as a JSON Schema (draft 2020-12).
+pub fn schema() -> Value {
+    let mut properties = Map::new();
+    for setting in SETTINGS {
+        let mut property = match setting.kind {
+            Kind::Text | Kind::Path => json!({"type": "string"}),
+            Kind::Bool => json!({"type": "boolean"}),
+            Kind::Integer { min, max } => json!({"type": "integer", "minimum": min, "maximum": max}),
+            Kind::Number => json!({"type": "number", "minimum": 0}),
+            Kind::OneOf(values) => json!({"type": "string", "enum": values}),
+            Kind::ListOf(values) => json!({"type": "array", "items": {"enum": values}, "uniqueItems": true}),
+            Kind::List => json!({"type": "array", "items": {"type": "string"}}),
+            Kind::Url => json!({"type": "string", "format": "uri", "pattern": "^https?://"}),
+            Kind::Json => json!({"type": "object"}),
+            Kind::Custom(_, expected) => json!({"type": "string", "$comment": expected}),
+        };
+        let property = property.as_object_mut().expect("schema properties are objects");
+        property.insert("description".into(), setting.description.into());
+        if let Some(default) = setting.default {
+            property.insert("default".into(), typed(setting.kind, default));
+        }
+        properties.insert(setting.name.into(), Value::Object(property.clone()));
+    }
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "Aegis sealer service configuration",
+        "type": "object",
+        "properties": properties,
+    })
+}
+
+// A default as the JSON type the schema gives its setting.
+fn typed(kind: Kind, value: &str) -> Value {
+    match kind {
+        Kind::Bool => Value::Bool(value == "true"),
+        Kind::Integer { .. } | Kind::Number => match (value.parse::<u64>(), value.parse::<f64>()) {
+            (Ok(n), _) => n.into(),
+            (_, Ok(n)) => n.into(),
+            _ => value.into(),
+        },
+        Kind::ListOf(_) | Kind::List => list(value).map(Value::from).collect(),
+        _ => value.into(),
+    }
+}
+
+fn list(value: &str) -> impl Iterator<Item = &str> {
+    value.split(',').map(str::trim).filter(|item| !item.is_empty())
+}
+
+impl Setting {
+    /// Checks a value, describing what is wrong with it.
+    pub fn validate(&self, value: &str) -> Result<(), String> {
+        let shown = if self.secret { "the value".to_string() } else { format!("'{}'", value) };
+        match self.kind {
+            Kind::Text | Kind::Path | Kind::List => Ok(()),
+            Kind::Bool => match value {
+                "true" | "false" | "1" | "0" => Ok(()),
+                _ => Err(format!("{} is not a boolean; use true or false", shown)),
+            },
+            Kind::Integer { min, max } => match value.parse::<u64>() {
+                Ok(n) if (min..=max).contains(&n) => Ok(()),
+                _ => Err(format!("{} is not a whole number from {} to {}", shown, min, max)),
+            },
+            Kind::Number => match value.parse::<f64>() {
+                Ok(n) if n.is_finite() && n >= 0.0 => Ok(()),
+                _ => Err(format!("{} is not a non-negative number", shown)),
+            },
+            Kind::OneOf(values) if values.contains(&value) => Ok(()),
+            Kind::OneOf(values) => Err(format!("{} is not one of {}", shown, values.join(", "))),
+            Kind::ListOf(values) => match list(value).find(|item| !values.contains(item)) {
+                Some(item) => Err(format!("'{}' in {} is not one of {}", item, shown, values.join(", "))),
+                None => Ok(()),
+            },
+            Kind::Url if value.starts_with("http://") || value.starts_with("https://") => Ok(()),
+            Kind::Url => Err(format!("{} is not an http:// or https:// URL", shown)),
+            Kind::Json => serde_json::from_str::<Value>(value)
+                .map(|_| ())
+                .map_err(|e| format!("{} is not valid JSON: {}", shown, e)),
+            Kind::Custom(check, _) if check(value.trim()) => Ok(()),
+            Kind::Custom(_, expected) => Err(format!("{} is not {}", shown, expected)),
+        }
+    }
+}
+
+fn is_socket_address(value: &str) -> bool {
+    value.parse::<std::net::SocketAddr>().is_ok()
+}
+
+fn is_cors_origins(value: &str) -> bool {
+    value == "*"
+        || list(value).all(|origin| {
+            (origin.starts_with("http://") || origin.starts_with("https://")) && is_header_value(origin)
+        })
+}
+
+fn is_fingerprints(value: &str) -> bool {
+    list(value).all(|fingerprint| fingerprint.len() == 64 && fingerprint.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+fn is_private_key(value: &str) -> bool {
+    value.len() == 64 && value.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn is_route_access(value: &str) -> bool {
+    list(value).all(|entry| {
+        entry
+            .split_once('=')
+            .is_some_and(|(_, access)| matches!(access.trim(), "public" | "authenticated"))
+    })
+}
+
+fn is_dependency_policy(value: &str) -> bool {
+    crate::health::parse_policy(value).is_ok()
+}
+
+fn is_image_formats(value: &str) -> bool {
+    crate::intake::parse_formats(value).is_ok()
+}
+
+fn is_media_types(value: &str) -> bool {
+    crate::intake::parse_media_types(value).is_ok()
+}
+
+fn is_trust_hint(value: &str) -> bool {
+    aegis_core::dns_trust::TrustHint::parse(value).is_ok()
+}
+
+fn is_size_classes(value: &str) -> bool {
+    value.split(',').all(|entry| crate::admission::parse_class(entry.trim()).is_ok())
+}
+
+fn is_event_format(value: &str) -> bool {
+    crate::events::EventFormat::parse(value).is_some()
+}
+
+fn is_header_value(value: &str) -> bool {
+    axum::http::HeaderValue::try_from(value).is_ok()
+}
+
+fn is_http_proxy(value: &str) -> bool {
+    let authority = value.strip_prefix("http://").unwrap_or(value);
+    !authority.contains("://") && crate::http_client::authority(&format!("http://{}", authority)).is_ok()
+}
+
+fn is_header_name(value: &str) -> bool {
+    axum::http::HeaderName::try_from(value).is_ok()
+}
+
+fn is_provenance_redact(value: &str) -> bool {
+    list(value).all(|entry| {
+        entry.split_once('=').is_some_and(|(field, rule)| {
+            crate::provenance::FIELDS.contains(&field.trim()) && matches!(rule.trim(), "omit" | "hash")
+        })
+    })
+}
+
+fn is_json_pointer(value: &str) -> bool {
+    value.is_empty() || value.starts_with('/')
+}
+
+fn is_metadata_map(value: &str) -> bool {
+    list(value).all(|entry| entry.split_once('=').is_some_and(|(_, pointer)| is_json_pointer(pointer.trim())))
+}
+
+fn is_offset(value: &str) -> bool {
+    aegis_core::time::parse_offset(value).is_some()
+}
+
+fn is_locale(value: &str) -> bool {
+    aegis_core::time::Locale::from_tag(value).is_some()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+pub struct Problem {
+    pub severity: Severity,
+    /// `path:line` of the `.env` line that set the value, if it came from one.
+    pub location: Option<String>,
+    pub name: String,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(location) = &self.location {
+            write!(f, "{}: ", location)?;
+        }
+        write!(f, "{}: {}", self.name, self.message)
+    }
+}
+
+/// A `.env` or config file's settings, with the line each was set on.
+pub struct EnvFile {
+    pub path: PathBuf,
+    values: Vec<(String, String, usize)>,
+}
+
+impl EnvFile {
+    /// Reads a `.env` file without changing the environment. Parse errors
+    /// name the file and line.
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        let mut values = Vec::new();
+        for item in dotenvy::from_path_iter(path)? {
+            let (name, value) = item.map_err(|e| describe_parse_error(path, &text, e))?;
+            // The first assignment wins, as when dotenvy loads the file.
+            if values.iter().any(|(n, _, _)| *n == name) {
+                continue;
+            }
+            let line = text
+                .lines()
+                .position(|l| {
+                    let l = l.trim_start();
+                    let l = l.strip_prefix("export ").unwrap_or(l).trim_start();
+                    l.strip_prefix(name.as_str()).is_some_and(|rest| rest.trim_start().starts_with('='))
+                })
+                .map_or(0, |i| i + 1);
+            values.push((name, value, line));
+        }
+        Ok(EnvFile { path: path.to_path_buf(), values })
+    }
+
+    /// Finds `.env` in the working directory or one of its parents, where
+    /// dotenvy looks, and loads it into the environment without overriding
+    /// variables that are already set. `None` if there is no such file.
+    pub fn load() -> anyhow::Result<Option<Self>> {
+        let mut dir = std::env::current_dir()?;
+        let path = loop {
+            let candidate = dir.join(".env");
+            if candidate.is_file() {
+                break candidate;
+            }
+            if !dir.pop() {
+                return Ok(None);
+            }
+        };
+        let file = Self::read(&path)?;
+        file.apply();
+        Ok(Some(file))
+    }
+
+    /// Settings read from another kind of file (see `config_file`).
+    pub(crate) fn from_values(path: &Path, values: Vec<(String, String, usize)>) -> Self {
+        EnvFile { path: path.to_path_buf(), values }
+    }
+
+    /// The value the file sets for `name`, if any.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.iter().find(|(n, _, _)| n == name).map(|(_, value, _)| value.as_str())
+    }
+
+    /// Sets the file's values in the environment, except for variables
+    /// that are already set.
+    pub(crate) fn apply(&self) {
+        for (name, value, _) in &self.values {
+            if std::env::var_os(name).is_none() {
+                // SAFETY: only called at startup, before the service starts
+                // anything that reads the environment, as dotenvy does.
+                unsafe { std::env::set_var(name, value) };
+            }
+        }
+    }
+}
+
+fn describe_parse_error(path: &Path, text: &str, error: dotenvy::Error) -> anyhow::Error {
+    match error {
+        dotenvy::Error::LineParse(line, index) => {
+            let first = line.lines().next().unwrap_or_default();
+            let number = text.lines().position(|l| l == first).map_or(0, |i| i + 1);
+            anyhow::anyhow!("{}:{}: cannot parse '{}' at column {}", path.display(), number, first, index + 1)
+        }
+        other => anyhow::anyhow!("{}: {}", path.display(), other),
+    }
+}
+
+/// Checks the environment, as the service would see it after loading
+/// `files` in order: a variable already set in the environment or by an
+/// earlier file wins over a later file's value, and only values that came
+/// from a file get a location.
+pub fn check(files: &[&EnvFile]) -> Vec<Problem> {
+    let mut values: HashMap<String, (String, Option<String>)> = std::env::vars()
+        .filter(|(name, _)| name.starts_with("AEGIS_") || find(name).is_some())
+        .map(|(name, value)| (name, (value, None)))
+        .collect();
+    for file in files {
+        for (name, value, line) in &file.values {
+            if !(name.starts_with("AEGIS_") || find(name).is_some()) {
+                continue;
+            }
+            let location = Some(format!("{}:{}", file.path.display(), line));
+            match values.get_mut(name) {
+                Some(existing) if existing.0 == *value && existing.1.is_none() => existing.1 = location,
+                Some(_) => {}
+                None => {
+                    values.insert(name.clone(), (value.clone(), location));
+                }
+            }
+        }
+    }
+
+    let mut problems: Vec<Problem> = values
+        .into_iter()
+        .filter_map(|(name, (value, location))| {
+            let (severity, message) = match find(&name) {
+                Some(setting) => (Severity::Error, setting.validate(&value).err()?),
+                None => (
+                    Severity::Warning,
+                    match closest(&name) {
+                        Some(known) => format!("not a known setting; did you mean {}?", known),
+                        None => "not a known setting".to_string(),
+                    },
+                ),
+            };
+            Some(Problem { severity, location, name, message })
+        })
+        .collect();
+    problems.sort_by(|a, b| a.name.cmp(&b.name));
+    problems
+}
+
+// The known setting nearest to a misspelt name, if any is close.
+pub(crate) fn closest(name: &str) -> Option<&'static str> {
+    SETTINGS
+        .iter()
+        .map(|s| (edit_distance(name, s.name), s.name))
+        .filter(|(distance, _)| *distance <= 3)
+        .min()
+        .map(|(_, known)| known)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous + usize::from(ca != *cb);
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(previous + 1);
+        }
+    }
+    row[b.len()]
+}
@@ -0,0 +1,253 @@
+// aegis-sealer-service/src/signer.rs
+
+// Where the service's signatures come from. Selected at startup with
+// `AEGIS_SIGNER`:
+//
+// - `env` (default): the hex private key in `AEGIS_PRIVATE_KEY`, read once
+//   at startup.
+// - `vault-kv`: a hex private key read once from Vault KV v2
+//   (`AEGIS_VAULT_KV_MOUNT`, default `secret`; `AEGIS_VAULT_KV_PATH`;
+//   `AEGIS_VAULT_KV_FIELD`, default `private_key`).
+// - `vault-transit`: signing delegated to the Vault transit engine
+//   (`AEGIS_VAULT_TRANSIT_MOUNT`, default `transit`; `AEGIS_VAULT_TRANSIT_KEY`).
+// - `azure-keyvault`: signing delegated to Azure Key Vault (see `azure.rs`).
+// - `aws-kms`: signing delegated to AWS KMS (see `aws_kms.rs`).
+// - `gcp-kms`: signing delegated to Google Cloud KMS (see `gcp_kms.rs`).
+// - `keyring`: a rotating set of keys loaded from the file or directory in
+//   `AEGIS_KEYRING` (see `aegis_core::keys::Keyring`). The key active at seal
+//   time signs, and every key in the ring, retired or not, is trusted.
+//
+// Code that embeds the public key next to a signature should `pin()` the
+// signer first, so a keyring rotation between the two calls cannot pair a
+// signature with the wrong key.
+
+use crate::{aws_kms, azure, gcp_kms, vault, AppError};
+use aegis_core::{
+    crypto,
+    format::AegisAncient,
+    keys::{Fingerprint, Keyring, KeyringEntry},
+};
+use anyhow::Context;
+use p256::ecdsa::{signature::Signer, signature::Verifier, Signature, SigningKey, VerifyingKey};
+use std::env;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{error, info};
+
+#[derive(Clone)]
+pub enum ServiceSigner {
+    Env(Arc<SigningKey>),
+    Local(Arc<SigningKey>),
+    VaultTransit(Arc<vault::TransitKey>),
+    AzureKeyVault(Arc<azure::AzureKeyVaultKey>),
+    AwsKms(Arc<aws_kms::AwsKmsKey>),
+    GcpKms(Arc<gcp_kms::GcpKmsKey>),
+    Keyring(Arc<Keyring>),
+    /// One key of a keyring, as returned by `pin()`.
+    KeyringKey(Arc<KeyringEntry>),
+}
+
+impl ServiceSigner {
+    pub async fn from_env() -> anyhow::Result<Self> {
+        let kind = env::var("AEGIS_SIGNER").unwrap_or_else(|_| "env".to_string());
+        match kind.as_str() {
+            "env" => Ok(ServiceSigner::Env(Arc::new(load_signing_key()?))),
+            "vault-kv" => {
+                let client = vault::VaultClient::from_env().await?;
+                let mount = env::var("AEGIS_VAULT_KV_MOUNT").unwrap_or_else(|_| "secret".into());
+                let path = env::var("AEGIS_VAULT_KV_PATH").context("AEGIS_VAULT_KV_PATH must be set")?;
+                let field = env::var("AEGIS_VAULT_KV_FIELD").unwrap_or_else(|_| "private_key".into());
+                let key_hex = client.read_kv(&mount, &path, &field).await?;
+                let key = SigningKey::from_slice(&hex::decode(key_hex.trim())?)
+                    .map_err(|e| anyhow::anyhow!("invalid private key in Vault: {}", e))?;
+                info!(path = %path, "Loaded signing key from Vault KV.");
+                Ok(ServiceSigner::Local(Arc::new(key)))
+            }
+            "vault-transit" => {
+                let client = vault::VaultClient::from_env().await?;
+                let mount = env::var("AEGIS_VAULT_TRANSIT_MOUNT").unwrap_or_else(|_| "transit".into());
+                let name = env::var("AEGIS_VAULT_TRANSIT_KEY")
+                    .context("AEGIS_VAULT_TRANSIT_KEY must be set")?;
+                let key = vault::TransitKey::load(client, &mount, &name).await?;
+                Ok(ServiceSigner::VaultTransit(Arc::new(key)))
+            }
+            "azure-keyvault" => {
+                let key = azure::AzureKeyVaultKey::from_env().await?;
+                Ok(ServiceSigner::AzureKeyVault(Arc::new(key)))
+            }
+            "aws-kms" => Ok(ServiceSigner::AwsKms(Arc::new(aws_kms::AwsKmsKey::from_env().await?))),
+            "gcp-kms" => Ok(ServiceSigner::GcpKms(Arc::new(gcp_kms::GcpKmsKey::from_env().await?))),
+            "keyring" => {
+                let path = env::var("AEGIS_KEYRING").context("AEGIS_KEYRING must be set")?;
+                let keyring = Keyring::load(&path).with_context(|| format!("loading keyring {}", path))?;
+                let active = keyring.active(unix_now()).map(|e| e.id.clone());
+                info!(path = %path, keys = keyring.entries().len(), active = ?active, "Loaded signing keyring.");
+                if active.is_none() {
+                    anyhow::bail!("keyring {} has no key active now", path);
+                }
+                Ok(ServiceSigner::Keyring(Arc::new(keyring)))
+            }
+            other => anyhow::bail!("unknown AEGIS_SIGNER '{}'", other),
+        }
+    }
+
+    /// The backend name, as accepted by `AEGIS_SIGNER`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ServiceSigner::Env(_) => "env",
+            ServiceSigner::Local(_) => "local",
+            ServiceSigner::VaultTransit(_) => "vault-transit",
+            ServiceSigner::AzureKeyVault(_) => "azure-keyvault",
+            ServiceSigner::AwsKms(_) => "aws-kms",
+            ServiceSigner::GcpKms(_) => "gcp-kms",
+            ServiceSigner::Keyring(_) | ServiceSigner::KeyringKey(_) => "keyring",
+        }
+    }
+
+    /// A signer fixed to the key that signs right now. Only differs from
+    /// `self` for a keyring, where the active key can change over time.
+    pub fn pin(&self) -> Result<ServiceSigner, AppError> {
+        match self {
+            ServiceSigner::Keyring(keyring) => {
+                let entry = keyring.active(unix_now()).ok_or_else(|| {
+                    error!("No key in the keyring is active.");
+                    AppError(
+                        axum::http::StatusCode::SERVICE_UNAVAILABLE,
+                        "No signing key is currently active.".into(),
+                    )
+                })?;
+                Ok(ServiceSigner::KeyringKey(Arc::new(entry.clone())))
+            }
+            other => Ok(other.clone()),
+        }
+    }
+
+    /// The keyring ID of a pinned keyring key.
+    pub fn key_id(&self) -> Option<&str> {
+        match self {
+            ServiceSigner::KeyringKey(entry) => Some(&entry.id),
+            _ => None,
+        }
+    }
+
+    /// The keyring behind a keyring signer.
+    pub fn keyring(&self) -> Option<&Keyring> {
+        match self {
+            ServiceSigner::Keyring(keyring) => Some(keyring),
+            _ => None,
+        }
+    }
+
+    /// Every key that signatures from this service may carry: the whole
+    /// keyring, or the single signing key.
+    pub fn known_keys(&self) -> Result<Vec<VerifyingKey>, AppError> {
+        match self {
+            ServiceSigner::Keyring(keyring) => Ok(keyring.entries().iter().map(|e| e.public_key).collect()),
+            other => Ok(vec![other.public_key()?]),
+        }
+    }
+
+    pub fn public_key(&self) -> Result<VerifyingKey, AppError> {
+        Ok(match self {
+            ServiceSigner::Env(key) | ServiceSigner::Local(key) => *key.verifying_key(),
+            ServiceSigner::VaultTransit(key) => *key.public_key(),
+            ServiceSigner::AzureKeyVault(key) => *key.public_key(),
+            ServiceSigner::AwsKms(key) => *key.public_key(),
+            ServiceSigner::GcpKms(key) => *key.public_key(),
+            ServiceSigner::Keyring(_) => return self.pin()?.public_key(),
+            ServiceSigner::KeyringKey(entry) => entry.public_key,
+        })
+    }
+
+    /// Checks, without network calls, that this signer can sign now: a local
+    /// key signs a probe that its public key verifies, and a keyring has an
+    /// active key. Returns the fingerprint of the key that would sign.
+    pub fn check_local(&self) -> anyhow::Result<Fingerprint> {
+        let signer = self.pin().map_err(|e| anyhow::anyhow!(e.1))?;
+        let key = match &signer {
+            ServiceSigner::Env(key) | ServiceSigner::Local(key) => Some(key.as_ref()),
+            ServiceSigner::KeyringKey(entry) => entry.signing_key.as_ref(),
+            _ => None,
+        };
+        let public_key = signer.public_key().map_err(|e| anyhow::anyhow!(e.1))?;
+        if let Some(key) = key {
+            // Never 32 bytes long, so the probe signature can never pass as
+            // a container signature.
+            let probe = b"aegis/health-probe\0";
+            let signature: Signature = key.try_sign(probe)?;
+            public_key
+                .verify(probe, &signature)
+                .map_err(|_| anyhow::anyhow!("signing key does not match its public key"))?;
+        }
+        Ok(Fingerprint::of(&public_key.to_sec1_bytes()))
+    }
+
+    /// Asks a remote signing backend whether it is reachable and still holds
+    /// the key version in use. `None` for keys held in process.
+    pub async fn check_remote(&self) -> Option<anyhow::Result<()>> {
+        match self {
+            ServiceSigner::VaultTransit(key) => Some(key.check().await),
+            ServiceSigner::AzureKeyVault(key) => Some(key.check().await),
+            ServiceSigner::AwsKms(key) => Some(key.check().await),
+            ServiceSigner::GcpKms(key) => Some(key.check().await),
+            _ => None,
+        }
+    }
+
+    /// Signs `message` with ECDSA P-256 / SHA-256.
+    pub async fn sign(&self, message: &[u8]) -> Result<Signature, AppError> {
+        match self {
+            ServiceSigner::Env(key) | ServiceSigner::Local(key) => Ok(key.try_sign(message)?),
+            ServiceSigner::VaultTransit(key) => key.sign(message).await.map_err(|e| {
+                error!(error = %e, "Vault transit signing failed.");
+                AppError::from(e)
+            }),
+            ServiceSigner::AzureKeyVault(key) => key.sign(message).await.map_err(|e| {
+                error!(error = %e, "Azure Key Vault signing failed.");
+                AppError::from(e)
+            }),
+            ServiceSigner::AwsKms(key) => key.sign(message).await.map_err(|e| {
+                error!(error = %e, "AWS KMS signing failed.");
+                AppError::from(e)
+            }),
+            ServiceSigner::GcpKms(key) => key.sign(message).await.map_err(|e| {
+                error!(error = %e, "Cloud KMS signing failed.");
+                AppError::from(e)
+            }),
+            ServiceSigner::Keyring(_) => Box::pin(self.pin()?.sign(message)).await,
+            ServiceSigner::KeyringKey(entry) => {
+                let key = entry.signing_key.as_ref().expect("pinned keyring keys can sign");
+                Ok(key.try_sign(message)?)
+            }
+        }
+    }
+
+    /// Hashes, signs and packages the data, like `crypto::seal`. A keyring
+    /// key's ID is recorded in the container header.
+    pub async fn seal(&self, metadata: String, image_data: Vec<u8>) -> Result<AegisAncient, AppError> {
+        let signer = self.pin()?;
+        let digest = crypto::signing_digest(&metadata, &image_data);
+        let signature = signer.sign(&digest).await?;
+        let mut ancient = crypto::assemble(metadata, image_data, &signer.public_key()?, &signature);
+        if let Some(id) = signer.key_id() {
+            ancient.header.set_key_id(id);
+        }
+        Ok(ancient)
+    }
+}
+
+/// Loads the service signing key from the `AEGIS_PRIVATE_KEY` environment variable.
+fn load_signing_key() -> anyhow::Result<SigningKey> {
+    let pk_hex = env::var("AEGIS_PRIVATE_KEY")
+        .context("AEGIS_PRIVATE_KEY must be set when AEGIS_SIGNER is env")?;
+    let pk_bytes = hex::decode(pk_hex.trim()).context("AEGIS_PRIVATE_KEY is not valid hex")?;
+    SigningKey::from_slice(&pk_bytes)
+        .map_err(|e| anyhow::anyhow!("AEGIS_PRIVATE_KEY is not a valid P-256 private key: {}", e))
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
@@ -0,0 +1,122 @@
+// aegis-sealer-service/src/sla.rs
+
+// Verification SLA mode, for kiosk terminals that need a verdict on a small
+// file within a fixed latency budget. Set `AEGIS_VERIFY_SLA=true` and /verify:
+//
+// - refuses remote (`{"url": ...}`) verification, which needs network reads;
+// - refuses bodies over `AEGIS_VERIFY_SLA_MAX_BYTES` (default 2 MiB) with 413
+//   before reading them, which also bounds the parsing and hashing work;
+// - adds a `latency` block to the verdict, splitting the time spent into
+//   read, parse, verify and judge stages against the budget
+//   `AEGIS_VERIFY_SLA_BUDGET_MS` (default 50), and the same stages as a
+//   `Server-Timing` header.
+//
+// Trust data (tenants, trusted keys and revocations) is loaded once at
+// startup and held in memory in any mode; verification never fetches it. In
+// SLA mode the service also verifies and judges a throwaway container at
+// startup, so the first kiosk request does not pay for cold caches and
+// lazily built tables.
+
+use crate::AppState;
+use serde_json::{json, Value};
+use std::env;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+const DEFAULT_BUDGET_MS: u64 = 50;
+const DEFAULT_MAX_BYTES: u64 = 2 * 1024 * 1024;
+
+pub struct Sla {
+    pub budget: Duration,
+    pub max_bytes: u64,
+}
+
+impl Sla {
+    /// `None` unless `AEGIS_VERIFY_SLA` is set.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        if !matches!(env::var("AEGIS_VERIFY_SLA").as_deref(), Ok("true" | "1")) {
+            return Ok(None);
+        }
+        let number = |name: &str, default: u64| -> anyhow::Result<u64> {
+            match env::var(name) {
+                Ok(v) => v.parse().map_err(|_| anyhow::anyhow!("{} must be a whole number, got '{}'", name, v)),
+                Err(_) => Ok(default),
+            }
+        };
+        let sla = Sla {
+            budget: Duration::from_millis(number("AEGIS_VERIFY_SLA_BUDGET_MS", DEFAULT_BUDGET_MS)?),
+            max_bytes: number("AEGIS_VERIFY_SLA_MAX_BYTES", DEFAULT_MAX_BYTES)?,
+        };
+        info!(budget_ms = sla.budget.as_millis() as u64, max_bytes = sla.max_bytes, "Verification SLA mode enabled.");
+        Ok(Some(sla))
+    }
+}
+
+/// Times the stages of one verification.
+pub struct Stopwatch {
+    start: Instant,
+    last: Instant,
+    stages: Vec<(&'static str, Duration)>,
+}
+
+impl Stopwatch {
+    pub fn start() -> Self {
+        let now = Instant::now();
+        Stopwatch { start: now, last: now, stages: Vec::new() }
+    }
+
+    /// Ends `stage`, which began when the previous one ended.
+    pub fn lap(&mut self, stage: &'static str) {
+        let now = Instant::now();
+        self.stages.push((stage, now - self.last));
+        self.last = now;
+    }
+
+    /// The `latency` block of a verdict.
+    pub fn report(&self, sla: &Sla) -> Value {
+        let total = self.last - self.start;
+        let within_budget = total <= sla.budget;
+        if !within_budget {
+            warn!(total_ms = millis(total), budget_ms = millis(sla.budget), "Verification exceeded its latency budget.");
+        }
+        let mut stages = serde_json::Map::new();
+        for (stage, duration) in &self.stages {
+            stages.insert(format!("{}_ms", stage), json!(millis(*duration)));
+        }
+        json!({
+            "budget_ms": millis(sla.budget),
+            "total_ms": millis(total),
+            "within_budget": within_budget,
+            "stages": stages,
+        })
+    }
+
+    /// The stages as a `Server-Timing` header value.
+    pub fn server_timing(&self) -> String {
+        self.stages
+            .iter()
+            .map(|(stage, duration)| format!("{};dur={:.3}", stage, millis(*duration)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+// Milliseconds, to the microsecond.
+fn millis(duration: Duration) -> f64 {
+    duration.as_micros() as f64 / 1000.0
+}
+
+/// Verifies and judges a container sealed with a throwaway key, so the code
+/// and tables the first real verification needs are already warm.
+pub fn warm_up(state: &AppState) -> anyhow::Result<()> {
+    let started = Instant::now();
+    let key = p256::ecdsa::SigningKey::from_slice(&[1; 32]).expect("a valid scalar");
+    let ancient = aegis_core::crypto::seal("{}".into(), vec![0; 1024], &key)?;
+    let report = aegis_core::crypto::verify(&ancient)?;
+    if !report.signature_valid {
+        anyhow::bail!("warm-up container did not verify");
+    }
+    state.tenants.judge(None, &report.key_fingerprint, Some(true));
+    info!(elapsed_ms = millis(started.elapsed()), "Verification path warmed up.");
+    Ok(())
+}
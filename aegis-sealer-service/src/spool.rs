@@ -0,0 +1,74 @@
+// aegis-sealer-service/src/spool.rs
+
+// Temporary on-disk spool for uploads. Large images are streamed to a spool
+// file as they arrive instead of being collected in memory; the file is
+// removed when the spool is dropped, including when a response stream
+// reading from it is cancelled.
+
+use std::io::{self, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+const READ_CHUNK: usize = 64 * 1024;
+
+static NEXT_SPOOL: AtomicU64 = AtomicU64::new(0);
+
+pub struct Spool {
+    path: PathBuf,
+    file: File,
+    len: u64,
+}
+
+impl Spool {
+    /// Creates an empty spool file in `dir`.
+    pub async fn create(dir: &Path) -> io::Result<Self> {
+        let path = dir.join(format!(
+            "aegis-{}-{}.spool",
+            std::process::id(),
+            NEXT_SPOOL.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .await?;
+        Ok(Spool { path, file, len: 0 })
+    }
+
+    pub async fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        self.file.write_all(data).await?;
+        self.len += data.len() as u64;
+        Ok(())
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Flushes pending writes and moves back to the start for reading.
+    pub async fn rewind(&mut self) -> io::Result<()> {
+        self.file.flush().await?;
+        self.file.seek(SeekFrom::Start(0)).await?;
+        Ok(())
+    }
+
+    /// Reads the next chunk, or `None` at the end of the file.
+    pub async fn read_chunk(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut buf = vec![0u8; READ_CHUNK];
+        let n = self.file.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        buf.truncate(n);
+        Ok(Some(buf))
+    }
+}
+
+impl Drop for Spool {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
@@ -0,0 +1,88 @@
+// aegis-sealer-service/src/static_docs.rs
+
+// Documents that only change when the service restarts, such as
+// /capabilities. Each is rendered once when the router is built and kept in
+// memory with its ETag (a hash of the body) and Last-Modified time (the
+// build time), so requests are answered without regenerating it and
+// conditional requests get `304 Not Modified`. `If-None-Match` is checked
+// first; `If-Modified-Since` only when it is absent, as RFC 9110 requires.
+//
+// Responses carry `Cache-Control` from `AEGIS_CACHE_CONTROL`, default
+// `public, max-age=300`.
+
+use crate::AppState;
+use aegis_core::time::{http_date, parse_http_date};
+use axum::{
+    body::Bytes,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, MethodRouter},
+};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_CACHE_CONTROL: &str = "public, max-age=300";
+
+pub struct CachedDocument {
+    body: Bytes,
+    content_type: &'static str,
+    etag: HeaderValue,
+    last_modified: i64,
+    cache_control: HeaderValue,
+}
+
+impl CachedDocument {
+    pub fn new(content_type: &'static str, body: impl Into<Bytes>, cache_control: &str) -> anyhow::Result<Self> {
+        let body = body.into();
+        let etag = format!("\"{}\"", &hex::encode(Sha256::digest(&body))[..32]);
+        Ok(CachedDocument {
+            body,
+            content_type,
+            etag: HeaderValue::try_from(etag)?,
+            last_modified: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0),
+            cache_control: HeaderValue::try_from(cache_control)
+                .map_err(|_| anyhow::anyhow!("AEGIS_CACHE_CONTROL is not a valid header value: '{}'", cache_control))?,
+        })
+    }
+
+    pub fn json(value: &serde_json::Value, cache_control: &str) -> anyhow::Result<Self> {
+        Self::new("application/json", serde_json::to_vec(value)?, cache_control)
+    }
+
+    /// A GET route serving this document.
+    pub fn route(self) -> MethodRouter<AppState> {
+        let document = Arc::new(self);
+        get(move |headers: HeaderMap| async move { document.respond(&headers) })
+    }
+
+    fn not_modified(&self, request: &HeaderMap) -> bool {
+        if let Some(tags) = request.get(header::IF_NONE_MATCH).and_then(|v| v.to_str().ok()) {
+            // Weak comparison, as for GET.
+            let ours = self.etag.to_str().unwrap_or_default();
+            return tags.split(',').map(str::trim).any(|tag| tag == "*" || tag.trim_start_matches("W/") == ours);
+        }
+        request
+            .get(header::IF_MODIFIED_SINCE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_http_date)
+            .is_some_and(|since| self.last_modified <= since)
+    }
+
+    fn respond(&self, request: &HeaderMap) -> Response {
+        let validators = [
+            (header::ETAG, self.etag.clone()),
+            (header::LAST_MODIFIED, HeaderValue::try_from(http_date(self.last_modified)).expect("HTTP dates are ASCII")),
+            (header::CACHE_CONTROL, self.cache_control.clone()),
+        ];
+        if self.not_modified(request) {
+            return (StatusCode::NOT_MODIFIED, validators).into_response();
+        }
+        (
+            validators,
+            [(header::CONTENT_TYPE, HeaderValue::from_static(self.content_type))],
+            self.body.clone(),
+        )
+            .into_response()
+    }
+}
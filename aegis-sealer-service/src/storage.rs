@@ -0,0 +1,226 @@
+// aegis-sealer-service/src/storage.rs
+
+// Storage for sealed containers produced by the service. Containers are kept
+// either as whole files or, with `AEGIS_STORAGE=chunked`, split into
+// content-defined chunks stored once by hash plus a manifest per object, so
+// near-identical assets share most of their bytes. With `AEGIS_STORAGE=s3`
+// they are written as objects to an S3-compatible bucket instead (see `s3`),
+// so that clients need not download a sealed file only to upload it there.
+// Either way, callers put and get whole containers by name.
+
+use crate::cdc;
+use crate::s3::S3Config;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+
+/// Largest object read back from S3.
+const MAX_OBJECT_SIZE: usize = 1024 * 1024 * 1024;
+
+pub enum SealedStore {
+    Files(PathBuf),
+    Chunked(PathBuf),
+    S3 { s3: S3Config, bucket: String, prefix: String },
+}
+
+#[derive(Serialize, Deserialize)]
+struct ChunkRef {
+    sha256: String,
+    size: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ObjectManifest {
+    name: String,
+    size: usize,
+    sha256: String,
+    chunks: Vec<ChunkRef>,
+}
+
+impl SealedStore {
+    /// Reads `AEGIS_STORAGE` (`files`, `chunked` or `s3`, default `files`)
+    /// and the root directory from `AEGIS_STORAGE_DIR`, falling back to
+    /// `AEGIS_INGEST_DIR` and then `sealed`. For `s3`, objects are written to
+    /// `AEGIS_STORAGE_BUCKET` under the key prefix `AEGIS_STORAGE_PREFIX`,
+    /// at the endpoint and with the credentials of `S3Config::from_env()`.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let root: PathBuf = env::var("AEGIS_STORAGE_DIR")
+            .or_else(|_| env::var("AEGIS_INGEST_DIR"))
+            .unwrap_or_else(|_| "sealed".into())
+            .into();
+        match env::var("AEGIS_STORAGE").as_deref().unwrap_or("files") {
+            "files" => Ok(SealedStore::Files(root)),
+            "chunked" => {
+                info!(root = %root.display(), "Using deduplicating chunked storage.");
+                Ok(SealedStore::Chunked(root))
+            }
+            "s3" => {
+                let bucket = env::var("AEGIS_STORAGE_BUCKET")
+                    .map_err(|_| anyhow::anyhow!("AEGIS_STORAGE_BUCKET must be set when AEGIS_STORAGE is s3"))?;
+                let prefix = env::var("AEGIS_STORAGE_PREFIX").unwrap_or_default();
+                let s3 = S3Config::from_env()?;
+                info!(endpoint = %s3.endpoint(), bucket = %bucket, prefix = %prefix, "Storing sealed files in S3.");
+                Ok(SealedStore::S3 { s3, bucket, prefix })
+            }
+            other => anyhow::bail!("unknown AEGIS_STORAGE '{}'", other),
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SealedStore::Files(_) => "files",
+            SealedStore::Chunked(_) => "chunked",
+            SealedStore::S3 { .. } => "s3",
+        }
+    }
+
+    /// Checks that the root directory can be created and written to, or
+    /// that the bucket can be written to.
+    pub async fn check(&self) -> anyhow::Result<()> {
+        let root = match self {
+            SealedStore::Files(root) | SealedStore::Chunked(root) => root,
+            SealedStore::S3 { s3, bucket, prefix } => {
+                let probe = format!("{}.probe-{}", prefix, std::process::id());
+                s3.put(bucket, &probe, "application/octet-stream", b"").await?;
+                s3.request("DELETE", bucket, &probe, &[], 64 * 1024).await?;
+                return Ok(());
+            }
+        };
+        let probe = root.join(format!(".probe-{}", std::process::id()));
+        async {
+            tokio::fs::create_dir_all(root).await?;
+            tokio::fs::write(&probe, b"").await?;
+            tokio::fs::remove_file(&probe).await
+        }
+        .await
+        .map_err(|e| anyhow::anyhow!("{}: {}", root.display(), e))
+    }
+
+    /// Stores a container under `name` and returns where it was written: a
+    /// path, or an `s3://` URL.
+    pub async fn put(&self, name: &str, bytes: &[u8]) -> anyhow::Result<String> {
+        check_name(name)?;
+        match self {
+            SealedStore::Files(root) => {
+                tokio::fs::create_dir_all(root).await?;
+                let path = root.join(format!("{}.aegis", name));
+                tokio::fs::write(&path, bytes).await?;
+                Ok(path.display().to_string())
+            }
+            SealedStore::Chunked(root) => put_chunked(root, name, bytes).await,
+            SealedStore::S3 { s3, bucket, prefix } => {
+                let key = format!("{}{}.aegis", prefix, name);
+                s3.put(bucket, &key, "application/octet-stream", bytes).await?;
+                Ok(format!("s3://{}/{}", bucket, key))
+            }
+        }
+    }
+
+    /// Returns the container stored under `name`, if any.
+    pub async fn get(&self, name: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        check_name(name)?;
+        match self {
+            SealedStore::Files(root) => read_optional(&root.join(format!("{}.aegis", name))).await,
+            SealedStore::Chunked(root) => get_chunked(root, name).await,
+            SealedStore::S3 { s3, bucket, prefix } => {
+                let key = format!("{}{}.aegis", prefix, name);
+                let resp = s3.request("GET", bucket, &key, &[], MAX_OBJECT_SIZE).await?;
+                match resp.status {
+                    404 => Ok(None),
+                    _ if resp.is_success() => Ok(Some(resp.body)),
+                    status => anyhow::bail!("S3 GET s3://{}/{} returned HTTP {}", bucket, key, status),
+                }
+            }
+        }
+    }
+}
+
+// Names become file names, so only plain identifiers are allowed.
+fn check_name(name: &str) -> anyhow::Result<()> {
+    if name.is_empty() || !name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_') {
+        anyhow::bail!("invalid object name '{}'", name);
+    }
+    Ok(())
+}
+
+async fn read_optional(path: &Path) -> anyhow::Result<Option<Vec<u8>>> {
+    match tokio::fs::read(path).await {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn chunk_path(root: &Path, hash: &str) -> PathBuf {
+    root.join("chunks").join(&hash[..2]).join(hash)
+}
+
+fn manifest_path(root: &Path, name: &str) -> PathBuf {
+    root.join("manifests").join(format!("{}.json", name))
+}
+
+async fn put_chunked(root: &Path, name: &str, bytes: &[u8]) -> anyhow::Result<String> {
+    let mut chunks = Vec::new();
+    let mut new_bytes = 0;
+    for chunk in cdc::chunks(bytes) {
+        let hash = hex::encode(Sha256::digest(chunk));
+        let path = chunk_path(root, &hash);
+        if tokio::fs::try_exists(&path).await? {
+            debug!(chunk = %hash, "Chunk already stored.");
+        } else {
+            let dir = path.parent().expect("chunk paths have a parent");
+            tokio::fs::create_dir_all(dir).await?;
+            // Write then rename so a crash never leaves a truncated chunk
+            // under its content hash.
+            let tmp = dir.join(format!("{}.tmp", hash));
+            tokio::fs::write(&tmp, chunk).await?;
+            tokio::fs::rename(&tmp, &path).await?;
+            new_bytes += chunk.len();
+        }
+        chunks.push(ChunkRef { sha256: hash, size: chunk.len() });
+    }
+
+    let manifest = ObjectManifest {
+        name: name.to_string(),
+        size: bytes.len(),
+        sha256: hex::encode(Sha256::digest(bytes)),
+        chunks,
+    };
+    let path = manifest_path(root, name);
+    tokio::fs::create_dir_all(path.parent().expect("manifest paths have a parent")).await?;
+    tokio::fs::write(&path, serde_json::to_vec_pretty(&manifest)?).await?;
+    info!(
+        name,
+        size = bytes.len(),
+        chunks = manifest.chunks.len(),
+        new_bytes,
+        deduplicated_bytes = bytes.len() - new_bytes,
+        "Stored container as chunks."
+    );
+    Ok(path.display().to_string())
+}
+
+async fn get_chunked(root: &Path, name: &str) -> anyhow::Result<Option<Vec<u8>>> {
+    let Some(manifest) = read_optional(&manifest_path(root, name)).await? else {
+        return Ok(None);
+    };
+    let manifest: ObjectManifest = serde_json::from_slice(&manifest)?;
+    let mut bytes = Vec::with_capacity(manifest.size);
+    for chunk in &manifest.chunks {
+        if chunk.sha256.len() != 64 || !chunk.sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+            anyhow::bail!("manifest for '{}' has a malformed chunk hash", name);
+        }
+        let data = tokio::fs::read(chunk_path(root, &chunk.sha256)).await?;
+        if hex::encode(Sha256::digest(&data)) != chunk.sha256 {
+            anyhow::bail!("chunk {} of '{}' is corrupt", chunk.sha256, name);
+        }
+        bytes.extend_from_slice(&data);
+    }
+    if hex::encode(Sha256::digest(&bytes)) != manifest.sha256 {
+        anyhow::bail!("reassembled '{}' does not match its manifest", name);
+    }
+    Ok(Some(bytes))
+}
@@ -0,0 +1,120 @@
+// aegis-sealer-service/src/telemetry.rs
+
+// Opt-in fleet telemetry. When `AEGIS_TELEMETRY_URL` is set, the service
+// periodically posts a signed heartbeat with aggregate counters — never
+// payloads or metadata — to a collector. Reports that cannot be delivered
+// are buffered in memory (oldest dropped first) and resent with the next
+// successful delivery. The body is signed with the service key in
+// `SignatureContext::Telemetry`.
+
+use crate::{http_client, mirror, AppState};
+use aegis_core::{accel, crypto::SignatureContext, keys::Fingerprint, time::rfc3339};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::env;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{debug, info, warn};
+
+const MAX_COLLECTOR_RESPONSE: usize = 64 * 1024;
+
+struct TelemetryConfig {
+    url: String,
+    interval: Duration,
+    instance_id: String,
+    buffer_limit: usize,
+}
+
+impl TelemetryConfig {
+    fn from_env() -> Option<Self> {
+        let url = env::var("AEGIS_TELEMETRY_URL").ok()?;
+        let interval = env::var("AEGIS_TELEMETRY_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(60);
+        Some(TelemetryConfig {
+            url,
+            interval: Duration::from_secs(interval),
+            instance_id: env::var("AEGIS_INSTANCE_ID")
+                .or_else(|_| env::var("HOSTNAME"))
+                .unwrap_or_else(|_| "unknown".into()),
+            buffer_limit: env::var("AEGIS_TELEMETRY_BUFFER")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1000),
+        })
+    }
+}
+
+/// Starts the reporter if telemetry is configured.
+pub fn spawn(state: AppState) {
+    let Some(config) = TelemetryConfig::from_env() else {
+        return;
+    };
+    info!(url = %config.url, interval_secs = config.interval.as_secs(), "Telemetry reporting enabled.");
+    tokio::spawn(async move {
+        let started = Instant::now();
+        let mut buffer: VecDeque<Value> = VecDeque::new();
+        let mut seq = 0u64;
+        let mut ticker = tokio::time::interval(config.interval);
+        loop {
+            ticker.tick().await;
+            seq += 1;
+            if buffer.len() == config.buffer_limit {
+                buffer.pop_front();
+            }
+            buffer.push_back(heartbeat(&state, &config, started, seq));
+            match deliver(&state, &config, &buffer).await {
+                Ok(()) => {
+                    debug!(reports = buffer.len(), "Telemetry delivered.");
+                    buffer.clear();
+                }
+                Err(e) => warn!(error = %e, buffered = buffer.len(), "Telemetry delivery failed; buffering."),
+            }
+        }
+    });
+}
+
+fn heartbeat(state: &AppState, config: &TelemetryConfig, started: Instant, seq: u64) -> Value {
+    let (seals_total, _) = state.audit.checkpoint();
+    #[allow(unused_mut)]
+    let mut report = json!({
+        "instance_id": config.instance_id,
+        "seq": seq,
+        "sent_at": rfc3339(SystemTime::now()),
+        "version": env!("CARGO_PKG_VERSION"),
+        "uptime_secs": started.elapsed().as_secs(),
+        "sha256_backend": accel::sha256_backend(),
+        "seals_total": seals_total,
+        "responses": state.metrics.responses(),
+        "mirror": mirror::stats(),
+    });
+    #[cfg(feature = "alloc-stats")]
+    {
+        report["heap_in_use_bytes"] = json!(crate::alloc_stats::in_use());
+    }
+    report
+}
+
+async fn deliver(state: &AppState, config: &TelemetryConfig, buffer: &VecDeque<Value>) -> anyhow::Result<()> {
+    let body = json!({ "reports": buffer }).to_string();
+    let signer = state.signer.pin().map_err(|e| anyhow::anyhow!(e.1))?;
+    let signature = signer.sign(&SignatureContext::Telemetry.message(body.as_bytes())).await.map_err(|e| anyhow::anyhow!(e.1))?;
+    let fingerprint = Fingerprint::of(&signer.public_key().map_err(|e| anyhow::anyhow!(e.1))?.to_sec1_bytes());
+    let signature_hex = hex::encode(signature.to_bytes());
+    let fingerprint_hex = fingerprint.to_hex();
+    let resp = http_client::post(
+        &config.url,
+        &[
+            ("Content-Type", "application/json"),
+            ("X-Aegis-Signature", &signature_hex),
+            ("X-Aegis-Key-Fingerprint", &fingerprint_hex),
+        ],
+        body.as_bytes(),
+        MAX_COLLECTOR_RESPONSE,
+    )
+    .await?;
+    if !resp.is_success() {
+        anyhow::bail!("collector returned HTTP {}", resp.status);
+    }
+    Ok(())
+}
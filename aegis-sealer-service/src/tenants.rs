@@ -0,0 +1,319 @@
+// aegis-sealer-service/src/tenants.rs
+
+// Per-tenant trust. A tenant is attached to a request by the API key that
+// authenticated it (`tenant:key` entries in `AEGIS_API_KEYS`), and decides
+// which keys /verify treats as trusted, which it treats as revoked, and what
+// goes into the trust bundle from /export/bundle.
+//
+// Configured with JSON from `AEGIS_TENANTS`, or from the file named by
+// `AEGIS_TENANTS_FILE`:
+//
+//     {
+//       "cross_tenant": "untrusted",
+//       "tenants": {
+//         "acme": {
+//           "trusted_keys": ["04ab..."],
+//           "revoked_keys": ["<fingerprint>"],
+//           "trust_service_key": true,
+//           "remote_mode": "full"
+//         }
+//       }
+//     }
+//
+// `cross_tenant` controls what a validly signed container from a key that only
+// another tenant trusts verifies as: `untrusted` (UNTRUSTED_KEY, the default)
+// or `warn` (VALID_WITH_WARNING). Fingerprints in `AEGIS_REVOKED_KEYS` are
+// revoked for every tenant.
+
+use aegis_core::bundle::TrustedKey;
+#[cfg(feature = "verifier")]
+use aegis_core::{
+    endorsement::ChainReport,
+    levels::{LevelReport, Outcome},
+};
+use anyhow::Context;
+use p256::ecdsa::VerifyingKey;
+use serde::Deserialize;
+#[cfg(feature = "verifier")]
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::env;
+use tracing::info;
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CrossTenant {
+    #[default]
+    Untrusted,
+    Warn,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TenantConfig {
+    /// Hex SEC1 public keys the tenant trusts.
+    #[serde(default)]
+    trusted_keys: Vec<String>,
+    /// Hex fingerprints the tenant has revoked.
+    #[serde(default)]
+    revoked_keys: Vec<String>,
+    #[serde(default = "default_true")]
+    trust_service_key: bool,
+    /// Mode used for remote verification when the request names none.
+    #[cfg(feature = "verifier")]
+    #[serde(default)]
+    remote_mode: Option<crate::remote_verify::Mode>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct TenantsConfig {
+    #[serde(default)]
+    cross_tenant: CrossTenant,
+    #[serde(default)]
+    tenants: BTreeMap<String, TenantConfig>,
+}
+
+struct TenantTrust {
+    trusted: Vec<TrustedKey>,
+    revoked: Vec<String>,
+    trust_service_key: bool,
+    #[cfg(feature = "verifier")]
+    remote_mode: Option<crate::remote_verify::Mode>,
+}
+
+pub struct Tenants {
+    #[cfg(feature = "verifier")]
+    cross_tenant: CrossTenant,
+    tenants: BTreeMap<String, TenantTrust>,
+    /// Revoked for every tenant, from `AEGIS_REVOKED_KEYS`.
+    revoked: Vec<String>,
+    service_keys: Vec<TrustedKey>,
+}
+
+#[cfg(feature = "verifier")]
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Verdict {
+    Valid,
+    ValidWithWarning,
+    UntrustedKey,
+    RevokedKey,
+    InvalidSignature,
+    /// The key is acceptable but the signature was not checked (quick remote
+    /// verification).
+    Unverified,
+}
+
+#[cfg(feature = "verifier")]
+#[derive(Serialize)]
+pub struct Judgement {
+    pub verdict: Verdict,
+    pub tenant: Option<String>,
+    pub warnings: Vec<String>,
+    /// The sealing key's endorsement chain, when roots are configured (see
+    /// `require_endorsement()`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endorsement: Option<ChainReport>,
+}
+
+#[cfg(feature = "verifier")]
+impl Judgement {
+    /// Judges a key without a valid endorsement chain to a configured root
+    /// untrusted, whatever the tenant's trust in it.
+    pub fn require_endorsement(&mut self, chain: ChainReport) {
+        if !chain.valid && matches!(self.verdict, Verdict::Valid | Verdict::ValidWithWarning | Verdict::Unverified) {
+            self.verdict = Verdict::UntrustedKey;
+            self.warnings.extend(chain.error.clone());
+        }
+        self.endorsement = Some(chain);
+    }
+}
+
+/// Adds the tenant's key trust and revocation list to the checks of a
+/// verification level, as `key_trust` and `revocation`, and the endorsement
+/// chain, if one was required, as `endorsement`.
+#[cfg(feature = "verifier")]
+pub fn add_checks(checks: &mut LevelReport, judgement: &Judgement) {
+    let revoked = judgement.verdict == Verdict::RevokedKey;
+    checks.add("revocation", if revoked { Outcome::Fail } else { Outcome::Pass }, None);
+    let trust = match (&judgement.verdict, &judgement.tenant) {
+        // A revoked key's trust is not looked at.
+        (Verdict::RevokedKey, _) => Outcome::NotApplicable,
+        (Verdict::UntrustedKey, _) => Outcome::Fail,
+        (_, None) => Outcome::NotApplicable,
+        _ => Outcome::Pass,
+    };
+    checks.add("key_trust", trust, judgement.warnings.first().cloned());
+    if let Some(chain) = &judgement.endorsement {
+        let outcome = if chain.valid { Outcome::Pass } else { Outcome::Fail };
+        checks.add("endorsement", outcome, chain.error.clone().or_else(|| chain.root.clone()));
+    }
+}
+
+/// A verification report with the tenant's judgement of it alongside.
+#[cfg(feature = "verifier")]
+#[derive(Serialize)]
+pub struct Judged<T> {
+    #[serde(flatten)]
+    pub report: T,
+    #[serde(flatten)]
+    pub judgement: Judgement,
+}
+
+impl Tenants {
+    /// Loads the tenant configuration described at the top of this module.
+    /// The service's own signing keys are trusted by every tenant that does
+    /// not opt out with `trust_service_key: false`.
+    pub fn from_env(service_keys: &[VerifyingKey]) -> anyhow::Result<Self> {
+        let config: TenantsConfig = match (env::var("AEGIS_TENANTS"), env::var("AEGIS_TENANTS_FILE")) {
+            (Ok(json), _) => serde_json::from_str(&json).context("invalid AEGIS_TENANTS")?,
+            (_, Ok(path)) => {
+                let json = std::fs::read_to_string(&path)
+                    .with_context(|| format!("reading AEGIS_TENANTS_FILE {}", path))?;
+                serde_json::from_str(&json).with_context(|| format!("invalid tenants file {}", path))?
+            }
+            _ => TenantsConfig::default(),
+        };
+
+        let mut tenants = BTreeMap::new();
+        for (name, tenant) in config.tenants {
+            let trusted = tenant
+                .trusted_keys
+                .iter()
+                .map(|k| {
+                    let sec1 = hex::decode(k.trim())
+                        .with_context(|| format!("tenant '{}': trusted key is not hex", name))?;
+                    let key = VerifyingKey::from_sec1_bytes(&sec1)
+                        .map_err(|_| anyhow::anyhow!("tenant '{}': trusted key is not a P-256 key", name))?;
+                    Ok(TrustedKey::from_verifying_key(&key))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let revoked = tenant.revoked_keys.iter().map(|f| f.trim().to_ascii_lowercase()).collect();
+            tenants.insert(
+                name,
+                TenantTrust {
+                    trusted,
+                    revoked,
+                    trust_service_key: tenant.trust_service_key,
+                    #[cfg(feature = "verifier")]
+                    remote_mode: tenant.remote_mode,
+                },
+            );
+        }
+        if !tenants.is_empty() {
+            info!(tenants = tenants.len(), cross_tenant = ?config.cross_tenant, "Tenant trust configured.");
+        }
+
+        let revoked = env::var("AEGIS_REVOKED_KEYS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|s| s.trim().to_ascii_lowercase())
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        Ok(Tenants {
+            #[cfg(feature = "verifier")]
+            cross_tenant: config.cross_tenant,
+            tenants,
+            revoked,
+            service_keys: service_keys.iter().map(TrustedKey::from_verifying_key).collect(),
+        })
+    }
+
+    fn trust_for(&self, tenant: &str) -> Option<&TenantTrust> {
+        self.tenants.get(tenant)
+    }
+
+    /// Keys `tenant` trusts, or the service keys alone without a tenant.
+    pub fn trusted_keys(&self, tenant: Option<&str>) -> Vec<TrustedKey> {
+        let Some(trust) = tenant.and_then(|t| self.trust_for(t)) else {
+            return self.service_keys.clone();
+        };
+        let mut keys = Vec::new();
+        if trust.trust_service_key {
+            keys.extend(self.service_keys.iter().cloned());
+        }
+        for key in &trust.trusted {
+            if !keys.iter().any(|k| k.fingerprint == key.fingerprint) {
+                keys.push(key.clone());
+            }
+        }
+        keys
+    }
+
+    /// Fingerprints revoked for `tenant`, including the global revocations.
+    pub fn revoked_keys(&self, tenant: Option<&str>) -> Vec<String> {
+        let mut revoked = self.revoked.clone();
+        if let Some(trust) = tenant.and_then(|t| self.trust_for(t)) {
+            for fingerprint in &trust.revoked {
+                if !revoked.contains(fingerprint) {
+                    revoked.push(fingerprint.clone());
+                }
+            }
+        }
+        revoked
+    }
+
+    /// The tenant's default mode for remote verification.
+    #[cfg(feature = "verifier")]
+    pub fn remote_mode(&self, tenant: Option<&str>) -> Option<crate::remote_verify::Mode> {
+        tenant.and_then(|t| self.trust_for(t)).and_then(|t| t.remote_mode)
+    }
+
+    /// Decides what a container signed by the key with hex `fingerprint`
+    /// verifies as for `tenant`. `signature_valid` is `None` when the
+    /// signature was not checked.
+    #[cfg(feature = "verifier")]
+    pub fn judge(&self, tenant: Option<&str>, fingerprint: &str, signature_valid: Option<bool>) -> Judgement {
+        let fingerprint = fingerprint.to_ascii_lowercase();
+        let mut warnings = Vec::new();
+        let judgement = |verdict, warnings| Judgement {
+            verdict,
+            tenant: tenant.map(str::to_string),
+            warnings,
+            endorsement: None,
+        };
+        let valid = match signature_valid {
+            Some(false) => return judgement(Verdict::InvalidSignature, warnings),
+            Some(true) => Verdict::Valid,
+            None => Verdict::Unverified,
+        };
+        if self.revoked_keys(tenant).contains(&fingerprint) {
+            return judgement(Verdict::RevokedKey, warnings);
+        }
+        let Some(tenant_name) = tenant else {
+            warnings.push("No tenant context; key trust was not evaluated.".to_string());
+            return judgement(valid, warnings);
+        };
+        if self.trusted_keys(tenant).iter().any(|k| k.fingerprint == fingerprint) {
+            return judgement(valid, warnings);
+        }
+
+        let owners: Vec<&str> = self
+            .tenants
+            .iter()
+            .filter(|(name, _)| name.as_str() != tenant_name)
+            .filter(|(name, _)| self.trusted_keys(Some(name.as_str())).iter().any(|k| k.fingerprint == fingerprint))
+            .map(|(name, _)| name.as_str())
+            .collect();
+        if owners.is_empty() {
+            return judgement(Verdict::UntrustedKey, warnings);
+        }
+        warnings.push(format!(
+            "Key {} is trusted by tenant(s) {} but not by {}.",
+            fingerprint,
+            owners.join(", "),
+            tenant_name
+        ));
+        match (self.cross_tenant, valid) {
+            (CrossTenant::Warn, Verdict::Valid) => judgement(Verdict::ValidWithWarning, warnings),
+            (CrossTenant::Warn, _) => judgement(valid, warnings),
+            (CrossTenant::Untrusted, _) => judgement(Verdict::UntrustedKey, warnings),
+        }
+    }
+}
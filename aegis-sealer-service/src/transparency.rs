@@ -0,0 +1,443 @@
+// aegis-sealer-service/src/transparency.rs
+
+// The service's transparency log (see `aegis_core::merkle`). Where the audit
+// log records who asked for each seal, this log lets anyone holding a file
+// confirm that this service sealed it: the SHA-256 of every sealed image is
+// appended to a Merkle tree, each new container carries its inclusion proof
+// (`format::FIELD_LOG_INCLUSION`), and the service signs tree heads with its
+// sealing key on request.
+//
+// Leaves are stored at `AEGIS_TRANSPARENCY_LOG` (default
+// `aegis-transparency.log`) as the raw 32-byte image hashes, in order; set
+// it to an empty string to disable the log. The tree is rebuilt from the
+// file on startup, dropping a torn final entry. Entries are synced before
+// the sealed container is returned, and a seal whose entry cannot be
+// written fails.
+//
+// GET /log/proof/{hash} proves the first entry for an image SHA-256:
+//
+//     GET /log/proof/9f86d0...?tree_size=1200
+//
+// at the given size or, without one, the current size. The response holds
+// the proof and a tree head of that size signed now, which
+// `merkle::verify_inclusion()` checks against the service's public key.
+//
+// GET /log/consistency proves that the log only grew between two sizes:
+//
+//     GET /log/consistency?first=1000&second=1200
+//
+// up to the given second size or, without one, the current size. The
+// response holds the proof and a tree head of the second size signed now;
+// `merkle::ConsistencyProof::verify()` checks it against the root of a head
+// fetched earlier at the first size.
+
+use crate::{openapi::ApiSchema, AppError, AppState};
+use aegis_core::{
+    crypto::SignatureContext,
+    merkle::{self, ConsistencyProof, InclusionProof, MerkleTree, SignedTreeHead, TreeHead},
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+const LEAF_LEN: u64 = 32;
+
+struct Log {
+    file: File,
+    tree: MerkleTree,
+    /// The first entry for each image SHA-256.
+    entries: HashMap<[u8; 32], u64>,
+}
+
+pub struct TransparencyLog {
+    log: Option<Mutex<Log>>,
+}
+
+impl TransparencyLog {
+    /// Opens the log from `AEGIS_TRANSPARENCY_LOG` and rebuilds its tree.
+    pub async fn open() -> anyhow::Result<Self> {
+        let path = env::var("AEGIS_TRANSPARENCY_LOG").unwrap_or_else(|_| "aegis-transparency.log".into());
+        if path.is_empty() {
+            warn!("AEGIS_TRANSPARENCY_LOG is empty; seals are not entered in a transparency log.");
+            return Ok(TransparencyLog { log: None });
+        }
+        Self::open_at(&path).await
+    }
+
+    async fn open_at(path: &str) -> anyhow::Result<Self> {
+        let contents = match tokio::fs::read(&path).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let file = OpenOptions::new().create(true).append(true).open(&path).await?;
+        let whole = contents.len() as u64 / LEAF_LEN * LEAF_LEN;
+        if whole != contents.len() as u64 {
+            warn!("Dropping a partially written final transparency log entry.");
+            file.set_len(whole).await?;
+        }
+        let mut log = Log { file, tree: MerkleTree::new(), entries: HashMap::new() };
+        for chunk in contents[..whole as usize].chunks(LEAF_LEN as usize) {
+            let image_sha256: [u8; 32] = chunk.try_into().expect("32 bytes");
+            let index = log.tree.push(merkle::leaf_hash(&image_sha256));
+            log.entries.entry(image_sha256).or_insert(index);
+        }
+        info!(path, entries = log.tree.len(), "Transparency log loaded.");
+        Ok(TransparencyLog { log: Some(Mutex::new(log)) })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.log.is_some()
+    }
+
+    /// Logs a sealed image by its hex SHA-256, returning the proof of its
+    /// inclusion at the new tree size, or `None` if the log is disabled.
+    pub async fn append(&self, image_sha256: &str) -> Result<Option<InclusionProof>, AppError> {
+        let Some(log) = &self.log else {
+            return Ok(None);
+        };
+        let image_sha256 = parse_hash(image_sha256)?;
+        let mut log = log.lock().await;
+        let written = async {
+            log.file.write_all(&image_sha256).await?;
+            log.file.sync_data().await
+        };
+        if let Err(e) = written.await {
+            warn!(error = %e, "Failed to append to the transparency log.");
+            return Err(AppError(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "The seal could not be entered in the transparency log.".into(),
+            ));
+        }
+        let index = log.tree.push(merkle::leaf_hash(&image_sha256));
+        log.entries.entry(image_sha256).or_insert(index);
+        Ok(Some(log.tree.inclusion_proof(index, index + 1)?))
+    }
+}
+
+// Signs a head of the log at `tree_size` with the sealing key, as of now.
+async fn sign_head(state: &AppState, tree_size: u64, root_hash: [u8; 32]) -> Result<SignedTreeHead, AppError> {
+    let head = TreeHead {
+        tree_size,
+        root_hash,
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0),
+    };
+    let signer = state.signer.pin()?;
+    let signature = signer.sign(&SignatureContext::TreeHead.message(&head.statement())).await?;
+    Ok(SignedTreeHead {
+        head,
+        public_key: signer.public_key()?.to_sec1_bytes().to_vec(),
+        signature: signature.to_bytes().to_vec(),
+    })
+}
+
+fn disabled() -> AppError {
+    AppError(StatusCode::NOT_FOUND, "The transparency log is disabled (AEGIS_TRANSPARENCY_LOG).".into())
+}
+
+fn parse_hash(hex_hash: &str) -> Result<[u8; 32], AppError> {
+    hex::decode(hex_hash)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| AppError(StatusCode::BAD_REQUEST, "The hash must be a hex SHA-256.".into()))
+}
+
+#[derive(Deserialize)]
+pub struct ProofQuery {
+    tree_size: Option<u64>,
+}
+
+/// GET /log/proof/{hash}
+pub async fn proof_handler(
+    State(state): State<AppState>,
+    Path(hash): Path<String>,
+    Query(query): Query<ProofQuery>,
+) -> Result<Json<ProofResponse>, AppError> {
+    let Some(log) = &state.transparency.log else {
+        return Err(disabled());
+    };
+    let image_sha256 = parse_hash(&hash.to_ascii_lowercase())?;
+    let (proof, root_hash) = {
+        let log = log.lock().await;
+        let Some(&index) = log.entries.get(&image_sha256) else {
+            return Err(AppError(StatusCode::NOT_FOUND, "No seal of that image is in the log.".into()));
+        };
+        let size = query.tree_size.unwrap_or(log.tree.len());
+        if size <= index || size > log.tree.len() {
+            return Err(AppError(
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "'tree_size' must be from {} (when the image was logged) to {}.",
+                    index + 1,
+                    log.tree.len()
+                ),
+            ));
+        }
+        (log.tree.inclusion_proof(index, size)?, log.tree.root_at(size)?)
+    };
+
+    let tree_head = sign_head(&state, proof.tree_size, root_hash).await?;
+    Ok(Json(ProofResponse { image_sha256: hex::encode(image_sha256), proof, tree_head }))
+}
+
+#[derive(Deserialize)]
+pub struct ConsistencyQuery {
+    first: u64,
+    second: Option<u64>,
+}
+
+/// GET /log/consistency
+pub async fn consistency_handler(
+    State(state): State<AppState>,
+    Query(query): Query<ConsistencyQuery>,
+) -> Result<Json<ConsistencyResponse>, AppError> {
+    let Some(log) = &state.transparency.log else {
+        return Err(disabled());
+    };
+    let (proof, root_hash) = {
+        let log = log.lock().await;
+        let second = query.second.unwrap_or(log.tree.len());
+        if second > log.tree.len() || query.first > second {
+            return Err(AppError(
+                StatusCode::BAD_REQUEST,
+                format!("'first' and 'second' must satisfy first <= second <= {}.", log.tree.len()),
+            ));
+        }
+        (log.tree.consistency_proof(query.first, second)?, log.tree.root_at(second)?)
+    };
+    let tree_head = sign_head(&state, proof.new_size, root_hash).await?;
+    Ok(Json(ConsistencyResponse { proof, tree_head }))
+}
+
+/// The body of /log/proof/{hash}.
+#[derive(Serialize)]
+pub struct ProofResponse {
+    image_sha256: String,
+    proof: InclusionProof,
+    tree_head: SignedTreeHead,
+}
+
+impl ApiSchema for ProofResponse {
+    const NAME: &'static str = "ProofResponse";
+
+    fn schema() -> Value {
+        let hex = hex_schema();
+        json!({
+            "type": "object",
+            "required": ["image_sha256", "proof", "tree_head"],
+            "properties": {
+                "image_sha256": hex,
+                "proof": {
+                    "type": "object",
+                    "required": ["leaf_index", "tree_size", "audit_path"],
+                    "properties": {
+                        "leaf_index": { "type": "integer" },
+                        "tree_size": { "type": "integer" },
+                        "audit_path": { "type": "array", "items": hex },
+                    },
+                },
+                "tree_head": tree_head_schema(),
+            },
+        })
+    }
+}
+
+/// The body of /log/consistency.
+#[derive(Serialize)]
+pub struct ConsistencyResponse {
+    proof: ConsistencyProof,
+    tree_head: SignedTreeHead,
+}
+
+impl ApiSchema for ConsistencyResponse {
+    const NAME: &'static str = "ConsistencyResponse";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["proof", "tree_head"],
+            "properties": {
+                "proof": {
+                    "type": "object",
+                    "required": ["old_size", "new_size", "path"],
+                    "properties": {
+                        "old_size": { "type": "integer" },
+                        "new_size": { "type": "integer" },
+                        "path": { "type": "array", "items": hex_schema() },
+                    },
+                },
+                "tree_head": tree_head_schema(),
+            },
+        })
+    }
+}
+
+fn hex_schema() -> Value {
+    json!({ "type": "string", "pattern": "^[0-9a-f]*$" })
+}
+
+fn tree_head_schema() -> Value {
+    let hex = hex_schema();
+    json!({
+        "type": "object",
+        "required": ["tree_size", "root_hash", "timestamp", "public_key", "signature"],
+        "properties": {
+            "tree_size": { "type": "integer" },
+            "root_hash": hex,
+            "timestamp": { "type": "string", "format": "date-time" },
+            "public_key": hex,
+            "signature": hex,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+
+    // A fresh log file under the temp directory, removed on drop.
+    struct TempLog(std::path::PathBuf);
+
+    impl TempLog {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("aegis-transparency-{}-{}.log", name, std::process::id()));
+            let _ = std::fs::remove_file(&path);
+            TempLog(path)
+        }
+
+        fn path(&self) -> &str {
+            self.0.to_str().unwrap()
+        }
+
+        async fn open(&self) -> TransparencyLog {
+            TransparencyLog::open_at(self.path()).await.unwrap()
+        }
+    }
+
+    impl Drop for TempLog {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn image(n: u8) -> [u8; 32] {
+        Sha256::digest([n]).into()
+    }
+
+    async fn tree(log: &TransparencyLog) -> MerkleTree {
+        let log = log.log.as_ref().unwrap().lock().await;
+        let mut tree = MerkleTree::new();
+        for index in 0..log.tree.len() {
+            tree.push(*log.tree.leaf(index).unwrap());
+        }
+        tree
+    }
+
+    #[tokio::test]
+    async fn appends_entries_with_proofs_of_inclusion() {
+        let file = TempLog::new("append");
+        let log = file.open().await;
+        let mut roots = Vec::new();
+        for n in 0..8 {
+            let proof = log.append(&hex::encode(image(n))).await.unwrap().unwrap();
+            assert_eq!((proof.leaf_index, proof.tree_size), (n as u64, n as u64 + 1));
+            let root = proof.root_from(&merkle::leaf_hash(&image(n))).unwrap();
+            assert_ne!(proof.root_from(&merkle::leaf_hash(&image(n + 1))).unwrap(), root);
+            roots.push(root);
+        }
+        let tree = tree(&log).await;
+        for (size, root) in (1..).zip(&roots) {
+            assert_eq!(&tree.root_at(size).unwrap(), root);
+        }
+        assert!(log.append("not a hash").await.is_err());
+        assert_eq!(std::fs::metadata(file.path()).unwrap().len(), 8 * LEAF_LEN);
+    }
+
+    #[tokio::test]
+    async fn proves_each_head_extends_the_last() {
+        let file = TempLog::new("consistency");
+        let log = file.open().await;
+        let mut roots = vec![merkle::empty_root()];
+        for n in 0..8 {
+            let proof = log.append(&hex::encode(image(n))).await.unwrap().unwrap();
+            roots.push(proof.root_from(&merkle::leaf_hash(&image(n))).unwrap());
+        }
+        let tree = tree(&log).await;
+        for second in 0..=8u64 {
+            for first in 0..=second {
+                let proof = tree.consistency_proof(first, second).unwrap();
+                proof.verify(&roots[first as usize], &roots[second as usize]).unwrap();
+            }
+        }
+        // A log that rewrote an entry cannot prove it extends its old head.
+        let mut rewritten = MerkleTree::new();
+        for n in 0..8 {
+            rewritten.push(merkle::leaf_hash(&image(if n == 2 { 9 } else { n })));
+        }
+        let proof = rewritten.consistency_proof(3, 8).unwrap();
+        assert!(proof.verify(&roots[3], &rewritten.root_at(8).unwrap()).is_err());
+        let mut tampered = tree.consistency_proof(3, 8).unwrap();
+        tampered.path[0][0] ^= 1;
+        assert!(tampered.verify(&roots[3], &roots[8]).is_err());
+    }
+
+    #[tokio::test]
+    async fn rebuilds_the_tree_on_open() {
+        let file = TempLog::new("reopen");
+        let log = file.open().await;
+        for n in [0, 1, 0, 2] {
+            log.append(&hex::encode(image(n))).await.unwrap();
+        }
+        let before = tree(&log).await.root_at(4).unwrap();
+        drop(log);
+
+        let log = file.open().await;
+        let reopened = log.log.as_ref().unwrap().lock().await;
+        assert_eq!(reopened.tree.root_at(4).unwrap(), before);
+        // The first entry for an image is the one proven.
+        assert_eq!(reopened.entries[&image(0)], 0);
+        assert_eq!(reopened.entries[&image(2)], 3);
+    }
+
+    #[tokio::test]
+    async fn drops_a_torn_final_entry() {
+        let file = TempLog::new("torn");
+        let log = file.open().await;
+        for n in 0..3 {
+            log.append(&hex::encode(image(n))).await.unwrap();
+        }
+        drop(log);
+        let mut contents = std::fs::read(file.path()).unwrap();
+        contents.extend_from_slice(&image(3)[..10]);
+        std::fs::write(file.path(), &contents).unwrap();
+
+        let log = file.open().await;
+        assert_eq!(std::fs::metadata(file.path()).unwrap().len(), 3 * LEAF_LEN);
+        let proof = log.append(&hex::encode(image(3))).await.unwrap().unwrap();
+        assert_eq!(proof.leaf_index, 3);
+        let mut expected = MerkleTree::new();
+        for n in 0..4 {
+            expected.push(merkle::leaf_hash(&image(n)));
+        }
+        assert_eq!(proof.root_from(&merkle::leaf_hash(&image(3))).unwrap(), expected.root_at(4).unwrap());
+    }
+
+    #[tokio::test]
+    async fn is_disabled_by_an_empty_path() {
+        let log = TransparencyLog { log: None };
+        assert!(!log.is_enabled());
+        assert!(log.append(&hex::encode(image(0))).await.unwrap().is_none());
+    }
+}
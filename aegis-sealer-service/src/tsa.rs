@@ -0,0 +1,79 @@
+// aegis-sealer-service/src/tsa.rs
+
+// Optional RFC 3161 timestamping of new containers. With `AEGIS_TSA_URL` set,
+// the signature of every container the service seals is sent to that
+// time-stamping authority and the token it returns goes into the container
+// header, where /verify reports the attested time.
+//
+// A TSA that cannot be reached leaves the container without a timestamp and
+// logs a warning, unless `AEGIS_TSA_REQUIRED=true`, in which case the seal
+// fails. Like every outbound call this speaks plain HTTP (see `http_client`).
+
+use crate::{http_client, AppError};
+use aegis_core::{format::FormatHeader, timestamp};
+use axum::http::StatusCode;
+use std::env;
+use tracing::{info, warn};
+
+/// Largest TSA response accepted; tokens are a few kilobytes.
+const MAX_RESPONSE: usize = 256 * 1024;
+
+pub struct Tsa {
+    url: String,
+    required: bool,
+}
+
+impl Tsa {
+    pub fn from_env() -> Option<Self> {
+        let url = env::var("AEGIS_TSA_URL").ok().filter(|url| !url.is_empty())?;
+        let required = matches!(env::var("AEGIS_TSA_REQUIRED").as_deref(), Ok("true" | "1"));
+        info!(url = %url, required, "Seals are timestamped by an RFC 3161 TSA.");
+        Some(Tsa { url, required })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn required(&self) -> bool {
+        self.required
+    }
+
+    /// Gets a token over `signature` from the TSA.
+    pub async fn token(&self, signature: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let request = timestamp::TimestampRequest::new(signature);
+        let response = http_client::post(
+            &self.url,
+            &[("Content-Type", timestamp::REQUEST_CONTENT_TYPE)],
+            request.to_der(),
+            MAX_RESPONSE,
+        )
+        .await?;
+        if !response.is_success() {
+            anyhow::bail!("TSA returned HTTP {}", response.status);
+        }
+        Ok(request.accept(&response.body)?)
+    }
+}
+
+/// Timestamps `signature` into `header` if a TSA is configured. Fails only
+/// when the TSA is required.
+pub async fn stamp(tsa: Option<&Tsa>, header: &mut FormatHeader, signature: &[u8]) -> Result<(), AppError> {
+    let Some(tsa) = tsa else {
+        return Ok(());
+    };
+    match tsa.token(signature).await {
+        Ok(token) => {
+            header.set_timestamp_token(token);
+            Ok(())
+        }
+        Err(e) if tsa.required => {
+            warn!(error = %e, url = %tsa.url, "Timestamping failed; refusing to seal without a timestamp.");
+            Err(AppError(StatusCode::BAD_GATEWAY, format!("Timestamping failed: {}", e)))
+        }
+        Err(e) => {
+            warn!(error = %e, url = %tsa.url, "Timestamping failed; sealing without a timestamp.");
+            Ok(())
+        }
+    }
+}
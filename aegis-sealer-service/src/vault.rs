@@ -0,0 +1,208 @@
+// aegis-sealer-service/src/vault.rs
+
+// HashiCorp Vault integration: token or AppRole authentication with
+// background token renewal, reading software keys from KV v2, and signing
+// through the transit engine so the private key never leaves Vault.
+
+use crate::http_client;
+use anyhow::{anyhow, bail, Context};
+use base64ct::{Base64, Base64UrlUnpadded, Encoding};
+use p256::ecdsa::{Signature, VerifyingKey};
+use p256::pkcs8::DecodePublicKey;
+use serde_json::{json, Value};
+use std::env;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+const MAX_RESPONSE: usize = 1024 * 1024;
+// Renew well before expiry; never more often than this.
+const MIN_RENEW_INTERVAL: Duration = Duration::from_secs(30);
+
+pub struct VaultClient {
+    addr: String,
+    namespace: Option<String>,
+    token: RwLock<String>,
+    approle: Option<(String, String)>,
+}
+
+impl VaultClient {
+    /// Connects using `VAULT_ADDR` and either `VAULT_TOKEN` or AppRole
+    /// credentials (`VAULT_ROLE_ID` + `VAULT_SECRET_ID`). `VAULT_NAMESPACE` is
+    /// sent when set. A renewal task keeps the token alive.
+    pub async fn from_env() -> anyhow::Result<Arc<Self>> {
+        let addr = env::var("VAULT_ADDR").context("VAULT_ADDR must be set to use Vault")?;
+        let approle = match (env::var("VAULT_ROLE_ID"), env::var("VAULT_SECRET_ID")) {
+            (Ok(role), Ok(secret)) => Some((role, secret)),
+            _ => None,
+        };
+        let client = Arc::new(VaultClient {
+            addr: addr.trim_end_matches('/').to_string(),
+            namespace: env::var("VAULT_NAMESPACE").ok(),
+            token: RwLock::new(env::var("VAULT_TOKEN").unwrap_or_default()),
+            approle,
+        });
+
+        let lease = if client.approle.is_some() {
+            client.login_approle().await?
+        } else if client.token.read().unwrap().is_empty() {
+            bail!("set VAULT_TOKEN or VAULT_ROLE_ID and VAULT_SECRET_ID");
+        } else {
+            let lookup = client.request("GET", "auth/token/lookup-self", None).await?;
+            lookup["data"]["ttl"].as_u64().map(Duration::from_secs)
+        };
+        if let Some(lease) = lease.filter(|l| !l.is_zero()) {
+            client.clone().spawn_renewal(lease);
+        }
+        info!(addr = %client.addr, "Authenticated to Vault.");
+        Ok(client)
+    }
+
+    async fn login_approle(&self) -> anyhow::Result<Option<Duration>> {
+        let (role_id, secret_id) = self.approle.clone().expect("approle credentials");
+        let resp = self
+            .request(
+                "POST",
+                "auth/approle/login",
+                Some(json!({ "role_id": role_id, "secret_id": secret_id })),
+            )
+            .await?;
+        let token = resp["auth"]["client_token"]
+            .as_str()
+            .ok_or_else(|| anyhow!("AppRole login returned no token"))?;
+        *self.token.write().unwrap() = token.to_string();
+        Ok(resp["auth"]["lease_duration"].as_u64().map(Duration::from_secs))
+    }
+
+    fn spawn_renewal(self: Arc<Self>, mut lease: Duration) {
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep((lease / 2).max(MIN_RENEW_INTERVAL)).await;
+                let renewed = match self.request("POST", "auth/token/renew-self", Some(json!({}))).await {
+                    Ok(resp) => resp["auth"]["lease_duration"].as_u64().map(Duration::from_secs),
+                    Err(e) if self.approle.is_some() => {
+                        warn!(error = %e, "Vault token renewal failed; logging in again.");
+                        self.login_approle().await.unwrap_or_else(|e| {
+                            error!(error = %e, "Vault AppRole re-login failed.");
+                            None
+                        })
+                    }
+                    Err(e) => {
+                        error!(error = %e, "Vault token renewal failed.");
+                        None
+                    }
+                };
+                lease = renewed.unwrap_or(MIN_RENEW_INTERVAL * 2);
+            }
+        });
+    }
+
+    async fn request(&self, method: &str, path: &str, body: Option<Value>) -> anyhow::Result<Value> {
+        let url = format!("{}/v1/{}", self.addr, path);
+        let token = self.token.read().unwrap().clone();
+        let mut headers = vec![("Content-Type", "application/json")];
+        if !token.is_empty() {
+            headers.push(("X-Vault-Token", token.as_str()));
+        }
+        if let Some(ns) = &self.namespace {
+            headers.push(("X-Vault-Namespace", ns.as_str()));
+        }
+        let body = body.map(|b| b.to_string()).unwrap_or_default();
+        let resp = http_client::request(method, &url, &headers, body.as_bytes(), MAX_RESPONSE).await?;
+        if !resp.is_success() {
+            bail!(
+                "Vault {} {} returned HTTP {}: {}",
+                method,
+                path,
+                resp.status,
+                String::from_utf8_lossy(&resp.body)
+            );
+        }
+        if resp.body.is_empty() {
+            return Ok(Value::Null);
+        }
+        Ok(serde_json::from_slice(&resp.body)?)
+    }
+
+    /// Reads one field of a KV v2 secret, e.g. `read_kv("secret", "aegis/signing", "private_key")`.
+    pub async fn read_kv(&self, mount: &str, path: &str, field: &str) -> anyhow::Result<String> {
+        let resp = self.request("GET", &format!("{}/data/{}", mount, path), None).await?;
+        resp["data"]["data"][field]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("Vault secret {}/{} has no string field '{}'", mount, path, field))
+    }
+}
+
+/// An ECDSA P-256 key held by the Vault transit engine.
+pub struct TransitKey {
+    client: Arc<VaultClient>,
+    mount: String,
+    name: String,
+    version: u64,
+    public_key: VerifyingKey,
+}
+
+impl TransitKey {
+    /// Loads the latest version of the named key and its public half.
+    pub async fn load(client: Arc<VaultClient>, mount: &str, name: &str) -> anyhow::Result<Self> {
+        let resp = client.request("GET", &format!("{}/keys/{}", mount, name), None).await?;
+        let data = &resp["data"];
+        if data["type"].as_str() != Some("ecdsa-p256") {
+            bail!("transit key '{}' must be of type ecdsa-p256", name);
+        }
+        let version = data["latest_version"]
+            .as_u64()
+            .ok_or_else(|| anyhow!("transit key '{}' has no latest_version", name))?;
+        let pem = data["keys"][version.to_string()]["public_key"]
+            .as_str()
+            .ok_or_else(|| anyhow!("transit key '{}' has no public key", name))?;
+        let public_key = VerifyingKey::from_public_key_pem(pem)
+            .map_err(|e| anyhow!("invalid transit public key: {}", e))?;
+        info!(key = name, version, "Loaded Vault transit signing key.");
+        Ok(TransitKey {
+            client,
+            mount: mount.to_string(),
+            name: name.to_string(),
+            version,
+            public_key,
+        })
+    }
+
+    pub fn public_key(&self) -> &VerifyingKey {
+        &self.public_key
+    }
+
+    /// Confirms Vault is reachable and the key still has the version we sign with.
+    pub async fn check(&self) -> anyhow::Result<()> {
+        let resp = self.client.request("GET", &format!("{}/keys/{}", self.mount, self.name), None).await?;
+        if resp["data"]["keys"][self.version.to_string()].is_null() {
+            bail!("transit key '{}' no longer has version {}", self.name, self.version);
+        }
+        Ok(())
+    }
+
+    /// Signs `message` with ECDSA/SHA-256, matching `SigningKey::sign`.
+    pub async fn sign(&self, message: &[u8]) -> anyhow::Result<Signature> {
+        let resp = self
+            .client
+            .request(
+                "POST",
+                &format!("{}/sign/{}/sha2-256", self.mount, self.name),
+                Some(json!({
+                    "input": Base64::encode_string(message),
+                    "key_version": self.version,
+                    // JWS marshaling yields the fixed-size r || s encoding we store.
+                    "marshaling_algorithm": "jws",
+                })),
+            )
+            .await?;
+        let encoded = resp["data"]["signature"]
+            .as_str()
+            .and_then(|s| s.rsplit(':').next())
+            .ok_or_else(|| anyhow!("transit sign returned no signature"))?;
+        let raw = Base64UrlUnpadded::decode_vec(encoded)
+            .map_err(|e| anyhow!("invalid transit signature encoding: {}", e))?;
+        Signature::from_slice(&raw).map_err(|e| anyhow!("invalid transit signature: {}", e))
+    }
+}
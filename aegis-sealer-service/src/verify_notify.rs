@@ -0,0 +1,450 @@
+// aegis-sealer-service/src/verify_notify.rs
+
+// Verification notifications, so that publishers learn when, and roughly
+// where, their content is being checked. With `AEGIS_VERIFY_NOTIFICATIONS`
+// naming a registry file, every container stored with `?store=true` is
+// recorded there with its owner, the principal that sealed it (see
+// `auth::Principal`); one stored by an anonymous caller has no owner and is
+// not recorded. The owner, and only the owner, may then register an
+// endpoint for it:
+//
+//     PUT /sealed/{name}/notifications
+//     {"url": "https://publisher.example/aegis", "secret": "...", "geo": true}
+//
+// GET returns the registration (without its secret), and DELETE removes it,
+// opting the seal out again. When POST /verify checks a container whose
+// payload is that of a registered seal, a notification is queued and POSTed
+// to the endpoint in the background:
+//
+//     {"event": "verification", "object": "9f86d0...",
+//      "verified_at": "2025-06-01T12:34:00Z", "verdict": "VALID",
+//      "country": "DE"}
+//
+// Nothing else about the verifier is sent: not its address, user agent, API
+// key or tenant. The time is cut to the minute, and `country` is only
+// included for registrations with `geo`, taken from the header named by
+// `AEGIS_GEO_HEADER` (a two-letter code set by a CDN or proxy in front of
+// the service, `CF-IPCountry` say); the service does no geolocation of its
+// own. A verifier opts out of notifications about its request with
+// `DNT: 1`, `Sec-GPC: 1` or `X-Aegis-Notify: off`.
+//
+// An endpoint's host must be one of `AEGIS_NOTIFY_ALLOWED_HOSTS` when that
+// is set. Otherwise any host will do that resolves only to public
+// addresses, checked when the endpoint is registered and again before each
+// delivery, so a key holder cannot make the service POST to loopback,
+// link-local or private addresses (see `http_client::check_destination`).
+//
+// The registry file holds one JSON line per change, the object's record as
+// it stands after the change, and is only appended to. It is rewritten
+// compacted when opened, and when superseded lines far outnumber records.
+//
+// With a `secret`, deliveries are signed as webhooks are (see `webhooks`).
+// A delivery that fails is retried up to `RETRIES` times. At most
+// `QUEUE_LIMIT` notifications wait for delivery; more are dropped, so that
+// a burst of verifications never slows verification down.
+
+use crate::{audit_log::Caller, http_client, AppError, AppState};
+use anyhow::Context;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tracing::info;
+#[cfg(feature = "verifier")]
+use crate::webhooks;
+#[cfg(feature = "verifier")]
+use aegis_core::time::rfc3339;
+#[cfg(feature = "verifier")]
+use axum::http::HeaderMap;
+#[cfg(feature = "verifier")]
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+#[cfg(feature = "verifier")]
+use tokio::sync::mpsc;
+#[cfg(feature = "verifier")]
+use tracing::{debug, warn};
+
+/// Notifications waiting for delivery beyond which new ones are dropped.
+#[cfg(feature = "verifier")]
+const QUEUE_LIMIT: usize = 1024;
+#[cfg(feature = "verifier")]
+const RETRIES: u32 = 3;
+#[cfg(feature = "verifier")]
+const BACKOFF: Duration = Duration::from_secs(1);
+#[cfg(feature = "verifier")]
+const MAX_RECEIVER_RESPONSE: usize = 64 * 1024;
+
+/// Superseded registry lines, beyond those of one per record, past which
+/// the file is compacted.
+const COMPACT_SLACK: usize = 1024;
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Record {
+    /// The principal that stored the seal. New records always have one;
+    /// `None` in older registry files, and such seals have no owner.
+    owner: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    endpoint: Option<Endpoint>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Endpoint {
+    url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    secret: Option<String>,
+    /// Whether notifications carry the verifier's country.
+    #[serde(default)]
+    geo: bool,
+}
+
+/// One line of the registry file: `object`'s record after a change.
+#[derive(Serialize, Deserialize)]
+struct Line {
+    object: String,
+    #[serde(flatten)]
+    record: Record,
+}
+
+/// The records, and the registry file they are appended to.
+struct Registry {
+    records: HashMap<String, Record>,
+    file: Option<File>,
+    /// Lines in the file.
+    lines: usize,
+}
+
+#[derive(Deserialize)]
+pub struct Registration {
+    url: String,
+    #[serde(default)]
+    secret: Option<String>,
+    #[serde(default)]
+    geo: bool,
+}
+
+#[cfg(feature = "verifier")]
+struct Notification {
+    endpoint: Endpoint,
+    body: Vec<u8>,
+    allowed_hosts: Vec<String>,
+}
+
+pub struct VerifyNotifications {
+    path: Option<PathBuf>,
+    #[cfg(feature = "verifier")]
+    geo_header: Option<String>,
+    /// Hosts endpoints may name, from `AEGIS_NOTIFY_ALLOWED_HOSTS`; when
+    /// empty, any host with public addresses.
+    allowed_hosts: Vec<String>,
+    /// Stored seals by object name.
+    registry: Mutex<Registry>,
+    #[cfg(feature = "verifier")]
+    queue: Option<mpsc::Sender<Notification>>,
+}
+
+impl VerifyNotifications {
+    /// Loads the registry named by `AEGIS_VERIFY_NOTIFICATIONS` and starts
+    /// the delivery task; unset or empty disables notifications.
+    pub async fn open() -> anyhow::Result<Self> {
+        let path = env::var("AEGIS_VERIFY_NOTIFICATIONS").ok().filter(|path| !path.is_empty()).map(PathBuf::from);
+        let mut registry = Registry { records: HashMap::new(), file: None, lines: 0 };
+        if let Some(path) = &path {
+            match tokio::fs::read_to_string(path).await {
+                Ok(text) => registry.records = parse(&text).with_context(|| format!("{}", path.display()))?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+            compact(path, &mut registry).await?;
+            let registered = registry.records.values().filter(|r| r.endpoint.is_some()).count();
+            info!(
+                path = %path.display(),
+                stored = registry.records.len(),
+                registered,
+                "Verification notifications enabled."
+            );
+        }
+        Ok(VerifyNotifications {
+            #[cfg(feature = "verifier")]
+            queue: path.is_some().then(|| {
+                let (queue, receiver) = mpsc::channel(QUEUE_LIMIT);
+                tokio::spawn(deliver_all(receiver));
+                queue
+            }),
+            #[cfg(feature = "verifier")]
+            geo_header: env::var("AEGIS_GEO_HEADER").ok().filter(|name| !name.is_empty()),
+            path,
+            allowed_hosts: env::var("AEGIS_NOTIFY_ALLOWED_HOSTS")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|host| !host.is_empty())
+                .map(str::to_string)
+                .collect(),
+            registry: Mutex::new(registry),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// Records the owner of a container just stored as `object`. The first
+    /// owner of an object keeps it, along with any registration. Seals
+    /// stored by anonymous callers are not recorded: nobody can claim them.
+    pub async fn stored(&self, object: &str, caller: &Caller) -> anyhow::Result<()> {
+        let Some(principal) = &caller.principal else {
+            return Ok(());
+        };
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        let mut registry = self.registry.lock().await;
+        if registry.records.contains_key(object) {
+            return Ok(());
+        }
+        self.save(&mut registry, object, Record { owner: Some(principal.clone()), endpoint: None }).await
+    }
+
+    /// Whether any seal has an endpoint, so that verifications of others
+    /// need not hash their payload.
+    #[cfg(feature = "verifier")]
+    pub async fn any_registered(&self) -> bool {
+        self.is_enabled() && self.registry.lock().await.records.values().any(|r| r.endpoint.is_some())
+    }
+
+    /// Queues a notification that the seal of `object` was verified with
+    /// `verdict`, unless the request in `headers` opted out or the seal has
+    /// no endpoint.
+    #[cfg(feature = "verifier")]
+    pub async fn verified(&self, object: &str, verdict: &str, headers: &HeaderMap) {
+        let Some(queue) = &self.queue else { return };
+        if opted_out(headers) {
+            debug!("Verifier opted out of verification notifications.");
+            return;
+        }
+        let Some(endpoint) = self.registry.lock().await.records.get(object).and_then(|r| r.endpoint.clone()) else {
+            return;
+        };
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let country = self.country(headers).filter(|_| endpoint.geo);
+        let body = json!({
+            "event": "verification",
+            "object": object,
+            "verified_at": rfc3339(UNIX_EPOCH + Duration::from_secs(now - now % 60)),
+            "verdict": verdict,
+            "country": country,
+        });
+        let notification = Notification {
+            endpoint,
+            body: body.to_string().into_bytes(),
+            allowed_hosts: self.allowed_hosts.clone(),
+        };
+        if queue.try_send(notification).is_err() {
+            warn!(object, "Verification notification queue is full; dropping a notification.");
+        }
+    }
+
+    /// The verifier's country from `AEGIS_GEO_HEADER`, if that holds a
+    /// two-letter code.
+    #[cfg(feature = "verifier")]
+    fn country(&self, headers: &HeaderMap) -> Option<String> {
+        let value = headers.get(self.geo_header.as_deref()?)?.to_str().ok()?.trim();
+        (value.len() == 2 && value.chars().all(|c| c.is_ascii_alphabetic())).then(|| value.to_ascii_uppercase())
+    }
+
+    /// The record of `object` if `caller` owns it. Anonymous callers own
+    /// nothing, and seals without an owner belong to nobody.
+    async fn owned(&self, object: &str, caller: &Caller) -> Result<Record, AppError> {
+        if !self.is_enabled() {
+            return Err(AppError(StatusCode::NOT_FOUND, "Verification notifications are not enabled.".into()));
+        }
+        // Seals of other owners are reported as missing, not forbidden.
+        self.registry
+            .lock()
+            .await
+            .records
+            .get(object)
+            .filter(|record| record.owner.is_some() && record.owner == caller.principal)
+            .cloned()
+            .ok_or_else(|| AppError(StatusCode::NOT_FOUND, format!("No stored seal named '{}' of yours.", object)))
+    }
+
+    async fn set_endpoint(&self, object: &str, endpoint: Option<Endpoint>) -> Result<(), AppError> {
+        let mut registry = self.registry.lock().await;
+        let Some(record) = registry.records.get(object) else {
+            return Ok(());
+        };
+        let record = Record { endpoint, ..record.clone() };
+        self.save(&mut registry, object, record).await.map_err(AppError::from)
+    }
+
+    /// Sets the record of `object`, appending it to the registry file.
+    async fn save(&self, registry: &mut Registry, object: &str, record: Record) -> anyhow::Result<()> {
+        let mut bytes = serde_json::to_vec(&Line { object: object.to_string(), record: record.clone() })?;
+        bytes.push(b'\n');
+        if let Some(file) = &mut registry.file {
+            file.write_all(&bytes).await?;
+            file.sync_data().await?;
+            registry.lines += 1;
+        }
+        registry.records.insert(object.to_string(), record);
+        if let Some(path) = &self.path
+            && registry.lines > registry.records.len() * 2 + COMPACT_SLACK
+        {
+            compact(path, registry).await?;
+        }
+        Ok(())
+    }
+}
+
+/// The records in a registry file: JSON lines, each superseding earlier
+/// ones for its object, or a single JSON object of records by name as
+/// older versions wrote. A torn final line, from a crash mid-append, is
+/// ignored.
+fn parse(text: &str) -> anyhow::Result<HashMap<String, Record>> {
+    if let Ok(records) = serde_json::from_str(text) {
+        return Ok(records);
+    }
+    let mut records = HashMap::new();
+    let lines: Vec<&str> = text.lines().filter(|line| !line.trim().is_empty()).collect();
+    for (n, text) in lines.iter().enumerate() {
+        match serde_json::from_str::<Line>(text) {
+            Ok(line) => {
+                records.insert(line.object, line.record);
+            }
+            Err(_) if n + 1 == lines.len() => break,
+            Err(e) => anyhow::bail!("line {}: {}", n + 1, e),
+        }
+    }
+    Ok(records)
+}
+
+/// Rewrites the registry file with one line per record, through a
+/// temporary file, and reopens it for appending.
+async fn compact(path: &std::path::Path, registry: &mut Registry) -> anyhow::Result<()> {
+    let mut text = Vec::new();
+    for (object, record) in &registry.records {
+        serde_json::to_writer(&mut text, &Line { object: object.clone(), record: record.clone() })?;
+        text.push(b'\n');
+    }
+    let temporary = path.with_extension("tmp");
+    tokio::fs::write(&temporary, &text).await?;
+    tokio::fs::rename(&temporary, path).await?;
+    registry.file = Some(OpenOptions::new().append(true).open(path).await?);
+    registry.lines = registry.records.len();
+    Ok(())
+}
+
+/// Whether a verification request asked not to be reported.
+#[cfg(feature = "verifier")]
+fn opted_out(headers: &HeaderMap) -> bool {
+    let is = |name: &str, value: &str| {
+        headers.get(name).and_then(|v| v.to_str().ok()).is_some_and(|v| v.trim().eq_ignore_ascii_case(value))
+    };
+    is("dnt", "1") || is("sec-gpc", "1") || is("x-aegis-notify", "off")
+}
+
+#[cfg(feature = "verifier")]
+async fn deliver_all(mut receiver: mpsc::Receiver<Notification>) {
+    while let Some(notification) = receiver.recv().await {
+        deliver(&notification).await;
+    }
+}
+
+#[cfg(feature = "verifier")]
+async fn deliver(notification: &Notification) {
+    let url = notification.endpoint.url.as_str();
+    // The host may resolve elsewhere now than when it was registered.
+    if let Err(e) = http_client::check_destination(url, &notification.allowed_hosts).await {
+        warn!(url, error = %e, "Verification notification endpoint is not allowed; not delivering.");
+        return;
+    }
+    let mut backoff = BACKOFF;
+    for attempt in 0..=RETRIES {
+        if attempt > 0 {
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let signature = notification.endpoint.secret.as_ref().map(|secret| webhooks::signature(secret, now, &notification.body));
+        let mut headers = vec![("Content-Type", "application/json")];
+        if let Some(signature) = &signature {
+            headers.push(("X-Aegis-Webhook-Signature", signature));
+        }
+        match http_client::post(url, &headers, &notification.body, MAX_RECEIVER_RESPONSE).await {
+            Ok(resp) if resp.is_success() => {
+                debug!(url, attempt, "Verification notification delivered.");
+                return;
+            }
+            Ok(resp) if resp.status != 429 && resp.status < 500 => {
+                warn!(url, status = resp.status, "Verification notification refused.");
+                return;
+            }
+            Ok(resp) => warn!(url, attempt, status = resp.status, "Verification notification refused."),
+            Err(e) => warn!(url, attempt, error = %e, "Verification notification failed."),
+        }
+    }
+    warn!(url, attempts = RETRIES + 1, "Giving up on verification notification.");
+}
+
+fn describe(object: &str, endpoint: &Endpoint) -> serde_json::Value {
+    json!({
+        "object": object,
+        "url": endpoint.url,
+        "geo": endpoint.geo,
+        "signed": endpoint.secret.is_some(),
+    })
+}
+
+pub async fn get_handler(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(name): Path<String>,
+) -> Result<Response, AppError> {
+    let record = state.notifications.owned(&name, &caller).await?;
+    let endpoint = record
+        .endpoint
+        .ok_or_else(|| AppError(StatusCode::NOT_FOUND, format!("No notification endpoint for '{}'.", name)))?;
+    Ok(Json(describe(&name, &endpoint)).into_response())
+}
+
+pub async fn put_handler(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(name): Path<String>,
+    Json(registration): Json<Registration>,
+) -> Result<Response, AppError> {
+    state.notifications.owned(&name, &caller).await?;
+    http_client::check_destination(&registration.url, &state.notifications.allowed_hosts)
+        .await
+        .map_err(|e| AppError(StatusCode::BAD_REQUEST, format!("Notification URL is not allowed: {}", e)))?;
+    let endpoint = Endpoint {
+        url: registration.url,
+        secret: registration.secret.filter(|secret| !secret.is_empty()),
+        geo: registration.geo,
+    };
+    let described = describe(&name, &endpoint);
+    state.notifications.set_endpoint(&name, Some(endpoint)).await?;
+    info!(object = %name, "Verification notification endpoint registered.");
+    Ok(Json(described).into_response())
+}
+
+pub async fn delete_handler(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(name): Path<String>,
+) -> Result<Response, AppError> {
+    state.notifications.owned(&name, &caller).await?;
+    state.notifications.set_endpoint(&name, None).await?;
+    info!(object = %name, "Verification notification endpoint removed.");
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
@@ -0,0 +1,272 @@
+// aegis-sealer-service/src/wal.rs
+
+// Append-only write-ahead log for seal operations. An `intent` line carrying
+// everything the audit record needs is written and synced before a container
+// is signed, and a `complete` (or `abort`) line once the audit record exists
+// (or signing failed). If the process dies in between, the intent is left
+// without an outcome and shows up at GET /admin/wal.
+//
+// On startup the log is replayed: completed intents are restored into the
+// in-memory audit store, so its history survives restarts, and intents with
+// no outcome are kept as unresolved until an operator closes them with
+// POST /admin/wal/{id}/resolve. A partially written final line (a crash
+// mid-append) is ignored.
+//
+// The log lives at `AEGIS_WAL_PATH` (default `aegis.wal`); set it to an empty
+// string to disable it.
+
+use crate::{audit::{AuditAction, AuditStore}, openapi::ApiSchema, AppError, AppState};
+use aegis_core::{keys::Fingerprint, time};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::env;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Intent {
+    pub id: u64,
+    pub action: String,
+    pub at: String,
+    /// Hex SEC1 public key of the key about to sign.
+    pub public_key: String,
+    pub key_fingerprint: String,
+    pub image_hash: String,
+    pub image_size: usize,
+    pub metadata: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum Entry {
+    Intent(Intent),
+    Complete { id: u64, audit_id: u64 },
+    Abort { id: u64, reason: String },
+}
+
+pub struct Wal {
+    path: Option<PathBuf>,
+    file: Option<tokio::sync::Mutex<File>>,
+    next_id: AtomicU64,
+    unresolved: Mutex<BTreeMap<u64, Intent>>,
+}
+
+impl Wal {
+    /// Opens the log from `AEGIS_WAL_PATH`, replaying it into `audit`.
+    pub async fn open(audit: &AuditStore) -> anyhow::Result<Self> {
+        let path = env::var("AEGIS_WAL_PATH").unwrap_or_else(|_| "aegis.wal".into());
+        if path.is_empty() {
+            warn!("AEGIS_WAL_PATH is empty; seals are not write-ahead logged.");
+            return Ok(Wal {
+                path: None,
+                file: None,
+                next_id: AtomicU64::new(1),
+                unresolved: Mutex::new(BTreeMap::new()),
+            });
+        }
+        let path = PathBuf::from(path);
+
+        let contents = match tokio::fs::read_to_string(&path).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut pending: BTreeMap<u64, Intent> = BTreeMap::new();
+        let mut completed = Vec::new();
+        let mut last_id = 0;
+        let lines: Vec<&str> = contents.lines().collect();
+        for (n, line) in lines.iter().enumerate() {
+            let entry: Entry = match serde_json::from_str(line) {
+                Ok(entry) => entry,
+                Err(_) if n + 1 == lines.len() && !contents.ends_with('\n') => {
+                    warn!(line = n + 1, "Ignoring partially written final WAL entry.");
+                    break;
+                }
+                Err(e) => anyhow::bail!("{}: line {}: {}", path.display(), n + 1, e),
+            };
+            match entry {
+                Entry::Intent(intent) => {
+                    last_id = last_id.max(intent.id);
+                    pending.insert(intent.id, intent);
+                }
+                Entry::Complete { id, .. } => completed.extend(pending.remove(&id)),
+                Entry::Abort { id, .. } => {
+                    pending.remove(&id);
+                }
+            }
+        }
+        // Restored in the order they completed, which is the order the
+        // audit store originally held them in.
+        for intent in &completed {
+            let Some(action) = AuditAction::parse(&intent.action) else {
+                warn!(id = intent.id, action = %intent.action, "Skipping WAL entry with an unknown action.");
+                continue;
+            };
+            let sealed_at = time::parse_rfc3339(&intent.at)
+                .and_then(|secs| u64::try_from(secs).ok())
+                .map(|secs| UNIX_EPOCH + Duration::from_secs(secs))
+                .unwrap_or(UNIX_EPOCH);
+            audit.record_at(
+                action,
+                &hex::decode(&intent.public_key)?,
+                &intent.metadata,
+                intent.image_hash.clone(),
+                intent.image_size,
+                sealed_at,
+            );
+        }
+        info!(
+            path = %path.display(),
+            restored = completed.len(),
+            unresolved = pending.len(),
+            "Write-ahead log replayed."
+        );
+        for intent in pending.values() {
+            warn!(id = intent.id, at = %intent.at, image_hash = %intent.image_hash, "Seal intent has no recorded outcome.");
+        }
+
+        let mut file = OpenOptions::new().create(true).append(true).open(&path).await?;
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            // Terminate the torn line so the next entry starts cleanly.
+            file.write_all(b"\n").await?;
+        }
+        Ok(Wal {
+            path: Some(path),
+            file: Some(tokio::sync::Mutex::new(file)),
+            next_id: AtomicU64::new(last_id + 1),
+            unresolved: Mutex::new(pending),
+        })
+    }
+
+    async fn append(&self, entry: &Entry) -> std::io::Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        let mut file = file.lock().await;
+        file.write_all(&line).await?;
+        file.sync_data().await
+    }
+
+    /// Logs the intent to seal and returns its ID. Must be called, and must
+    /// succeed, before the container is signed.
+    pub async fn begin(
+        &self,
+        action: AuditAction,
+        public_key: &[u8],
+        metadata: &str,
+        image_hash: &str,
+        image_size: usize,
+    ) -> std::io::Result<u64> {
+        let intent = Intent {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            action: action.as_str().to_string(),
+            at: time::rfc3339(SystemTime::now()),
+            public_key: hex::encode(public_key),
+            key_fingerprint: Fingerprint::of(public_key).to_hex(),
+            image_hash: image_hash.to_string(),
+            image_size,
+            metadata: metadata.to_string(),
+        };
+        let id = intent.id;
+        self.append(&Entry::Intent(intent)).await?;
+        Ok(id)
+    }
+
+    /// Marks an intent as done once its audit record has been written.
+    pub async fn complete(&self, id: u64, audit_id: u64) {
+        if let Err(e) = self.append(&Entry::Complete { id, audit_id }).await {
+            warn!(id, error = %e, "Failed to log seal completion; it will replay as unresolved.");
+        }
+    }
+
+    /// Marks an intent as abandoned because signing failed.
+    pub async fn abort(&self, id: u64, reason: &str) {
+        let entry = Entry::Abort {
+            id,
+            reason: reason.to_string(),
+        };
+        if let Err(e) = self.append(&entry).await {
+            warn!(id, error = %e, "Failed to log seal abort; it will replay as unresolved.");
+        }
+    }
+
+    /// Closes an unresolved intent after an operator has dealt with it.
+    /// Returns false if `id` is not unresolved.
+    pub async fn resolve(&self, id: u64) -> std::io::Result<bool> {
+        if !self.unresolved.lock().unwrap().contains_key(&id) {
+            return Ok(false);
+        }
+        let entry = Entry::Abort {
+            id,
+            reason: "resolved by operator".into(),
+        };
+        self.append(&entry).await?;
+        self.unresolved.lock().unwrap().remove(&id);
+        Ok(true)
+    }
+
+    pub fn path(&self) -> Option<&PathBuf> {
+        self.path.as_ref()
+    }
+
+    /// Intents from before the last restart that never recorded an outcome.
+    pub fn unresolved(&self) -> Vec<Intent> {
+        self.unresolved.lock().unwrap().values().cloned().collect()
+    }
+}
+
+/// Lists seal intents that never recorded an outcome.
+pub async fn unresolved_handler(State(state): State<AppState>) -> Json<Value> {
+    Json(json!({
+        "enabled": state.wal.path().is_some(),
+        "path": state.wal.path().map(|p| p.display().to_string()),
+        "unresolved": state.wal.unresolved(),
+    }))
+}
+
+/// Closes an unresolved intent.
+pub async fn resolve_handler(
+    State(state): State<AppState>,
+    Path(id): Path<u64>,
+) -> Result<Json<Resolved>, AppError> {
+    if !state.wal.resolve(id).await? {
+        return Err(AppError(
+            StatusCode::NOT_FOUND,
+            format!("No unresolved write-ahead log entry {}.", id),
+        ));
+    }
+    info!(id, "Unresolved seal intent closed by operator.");
+    Ok(Json(Resolved { resolved: id }))
+}
+
+/// The body of /admin/wal/{id}/resolve.
+#[derive(Serialize)]
+pub struct Resolved {
+    /// The ID of the intent closed.
+    resolved: u64,
+}
+
+impl ApiSchema for Resolved {
+    const NAME: &'static str = "Resolved";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["resolved"],
+            "properties": { "resolved": { "type": "integer" } },
+        })
+    }
+}
@@ -0,0 +1,311 @@
+// aegis-sealer-service/src/webhooks.rs
+
+// Webhook notifications, so that a pipeline can start its downstream
+// processing when a file is sealed. After every successful seal the service
+// POSTs an event to each URL in `AEGIS_WEBHOOK_URLS` (comma-separated), by
+// default as version 1 JSON:
+//
+//     {"event": "seal", "audit_id": 41, "request_id": "...",
+//      "image_sha256": "9f86d0...", "image_size": 48213,
+//      "metadata": "...", "key_id": "2025-06", "key_fingerprint": "...",
+//      "tenant": null, "delegation": null,
+//      "sealed_at": "2025-06-01T12:00:00Z"}
+//
+// `AEGIS_WEBHOOK_FORMAT` picks another schema version or protobuf (see
+// `events`), and the `Content-Type` of each delivery says which it is. A
+// receiver that answers 415 with an `Accept` header listing formats it
+// reads gets the event again in the best of them, and from then on every
+// event, until the service restarts.
+//
+// With `AEGIS_WEBHOOK_SECRET` set, each delivery carries
+// `X-Aegis-Webhook-Signature: t=<unix seconds>,sha256=<hex>`, the HMAC-SHA256
+// under the secret of the timestamp, a `.`, and the body; receivers should
+// check it and reject stale timestamps. Deliveries run in the background and
+// never hold up or fail the seal. A delivery that cannot connect, times out,
+// or gets a 429 or 5xx response is retried up to `AEGIS_WEBHOOK_RETRIES`
+// times, waiting `AEGIS_WEBHOOK_BACKOFF_MS` before the first retry and
+// twice as long before each one after, up to a minute; other responses are
+// final.
+
+use crate::events::{Event, EventFormat};
+use crate::hooks::{SealEvent, SealHook};
+use crate::http_client;
+use aegis_core::time::rfc3339;
+use anyhow::Context;
+use futures_util::future::BoxFuture;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, info, warn};
+
+const MAX_RECEIVER_RESPONSE: usize = 64 * 1024;
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+pub struct Webhooks {
+    urls: Vec<String>,
+    secret: Option<String>,
+    retries: u32,
+    backoff: Duration,
+    format: EventFormat,
+    /// Formats receivers asked for in place of `format`, by URL.
+    negotiated: Mutex<HashMap<String, EventFormat>>,
+}
+
+impl Webhooks {
+    /// Reads the webhook settings, or `None` if no URLs are configured.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let urls: Vec<String> = env::var("AEGIS_WEBHOOK_URLS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+            .map(str::to_string)
+            .collect();
+        if urls.is_empty() {
+            return Ok(None);
+        }
+        for url in &urls {
+            http_client::authority(url).with_context(|| format!("AEGIS_WEBHOOK_URLS: '{}'", url))?;
+        }
+        let secret = env::var("AEGIS_WEBHOOK_SECRET").ok().filter(|secret| !secret.is_empty());
+        if secret.is_none() {
+            warn!("AEGIS_WEBHOOK_SECRET is not set; webhook deliveries are unsigned.");
+        }
+        let number = |var: &str, default: u64| -> anyhow::Result<u64> {
+            match env::var(var) {
+                Ok(value) => value.parse().with_context(|| format!("{}: '{}' is not a number", var, value)),
+                Err(_) => Ok(default),
+            }
+        };
+        let format = match env::var("AEGIS_WEBHOOK_FORMAT") {
+            Ok(text) => EventFormat::parse(&text)
+                .ok_or_else(|| anyhow::anyhow!("AEGIS_WEBHOOK_FORMAT: '{}' is not an event format", text))?,
+            Err(_) => EventFormat::default(),
+        };
+        let webhooks = Webhooks {
+            urls,
+            secret,
+            retries: number("AEGIS_WEBHOOK_RETRIES", 5)? as u32,
+            backoff: Duration::from_millis(number("AEGIS_WEBHOOK_BACKOFF_MS", 500)?),
+            format,
+            negotiated: Mutex::new(HashMap::new()),
+        };
+        info!(urls = webhooks.urls.len(), signed = webhooks.is_signed(), "Seal webhooks enabled.");
+        Ok(Some(webhooks))
+    }
+
+    pub fn url_count(&self) -> usize {
+        self.urls.len()
+    }
+
+    pub fn is_signed(&self) -> bool {
+        self.secret.is_some()
+    }
+
+    pub fn format(&self) -> EventFormat {
+        self.format
+    }
+
+    fn format_for(&self, url: &str) -> EventFormat {
+        self.negotiated.lock().unwrap().get(url).copied().unwrap_or(self.format)
+    }
+
+    /// The `X-Aegis-Webhook-Signature` value for `body` sent at `timestamp`.
+    fn signature(&self, timestamp: u64, body: &[u8]) -> Option<String> {
+        Some(signature(self.secret.as_ref()?, timestamp, body))
+    }
+
+    /// Delivers `event` to `url`, retrying as configured.
+    async fn deliver(&self, url: &str, event: &Event) {
+        let event_id = event.id.as_str();
+        let mut backoff = self.backoff;
+        let mut renegotiated = false;
+        let mut attempt = 0;
+        while attempt <= self.retries {
+            if attempt > 0 {
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+            let format = self.format_for(url);
+            let body = event.encode(format);
+            let content_type = format.content_type();
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+            let signature = self.signature(now, &body);
+            let mut headers = vec![("Content-Type", content_type.as_str()), ("X-Aegis-Webhook-Id", event_id)];
+            if let Some(signature) = &signature {
+                headers.push(("X-Aegis-Webhook-Signature", signature));
+            }
+            let retryable = match http_client::post(url, &headers, &body, MAX_RECEIVER_RESPONSE).await {
+                Ok(resp) if resp.is_success() => {
+                    debug!(url, event_id, attempt, "Webhook delivered.");
+                    return;
+                }
+                // Sent straight away in the format asked for, once.
+                Ok(resp) if resp.status == 415 && !renegotiated => {
+                    match resp.header("Accept").and_then(EventFormat::negotiate).filter(|f| *f != format) {
+                        Some(wanted) => {
+                            info!(url, content_type = %wanted.content_type(), "Webhook receiver asked for another event format.");
+                            self.negotiated.lock().unwrap().insert(url.to_string(), wanted);
+                            renegotiated = true;
+                            continue;
+                        }
+                        None => {
+                            warn!(url, event_id, "Webhook receiver accepts no event format the service produces.");
+                            return;
+                        }
+                    }
+                }
+                Ok(resp) => {
+                    warn!(url, event_id, attempt, status = resp.status, "Webhook receiver refused the event.");
+                    resp.status == 429 || resp.status >= 500
+                }
+                Err(e) => {
+                    warn!(url, event_id, attempt, error = %e, "Webhook delivery failed.");
+                    true
+                }
+            };
+            if !retryable {
+                return;
+            }
+            attempt += 1;
+        }
+        warn!(url, event_id, attempts = self.retries + 1, "Giving up on webhook delivery.");
+    }
+}
+
+/// `X-Aegis-Webhook-Signature` for `body` sent at `timestamp` under
+/// `secret`.
+pub(crate) fn signature(secret: &str, timestamp: u64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("t={},sha256={}", timestamp, hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Registered on the state by `AppState::from_env` when webhooks are
+/// configured.
+pub(crate) struct WebhookHook(pub Arc<Webhooks>);
+
+impl SealHook for WebhookHook {
+    fn after_seal<'a>(&'a self, event: &'a SealEvent) -> BoxFuture<'a, ()> {
+        let event = Arc::new(Event::new(event, rfc3339(SystemTime::now())));
+        for url in &self.0.urls {
+            let (webhooks, url, event) = (self.0.clone(), url.clone(), event.clone());
+            tokio::spawn(async move { webhooks.deliver(&url, &event).await });
+        }
+        Box::pin(async {})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn webhooks(url: String, secret: Option<&str>) -> Webhooks {
+        Webhooks {
+            urls: vec![url],
+            secret: secret.map(str::to_string),
+            retries: 0,
+            backoff: Duration::from_millis(1),
+            format: EventFormat::default(),
+            negotiated: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn event() -> Event {
+        let seal = SealEvent {
+            action: "seal",
+            tenant: None,
+            request_id: Some("req-1".into()),
+            caller: None,
+            metadata: "{}".into(),
+            image_sha256: "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08".into(),
+            image_size: 4,
+            audit_id: Some(41),
+            key_fingerprint: Some("ab".repeat(32)),
+            key_id: None,
+            delegation: None,
+        };
+        Event::new(&seal, "2025-06-01T12:00:00Z".into())
+    }
+
+    // Accepts one request, answers 204, and returns its headers (names
+    // lowercased) and body.
+    async fn receive(listener: TcpListener) -> (HashMap<String, String>, Vec<u8>) {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = stream.read(&mut buf).await.unwrap();
+            request.extend_from_slice(&buf[..n]);
+            let mut headers = [httparse::EMPTY_HEADER; 32];
+            let mut parsed = httparse::Request::new(&mut headers);
+            if let Ok(httparse::Status::Complete(len)) = parsed.parse(&request) {
+                let headers: HashMap<String, String> = parsed
+                    .headers
+                    .iter()
+                    .map(|h| (h.name.to_ascii_lowercase(), String::from_utf8_lossy(h.value).into_owned()))
+                    .collect();
+                let body_len: usize = headers["content-length"].parse().unwrap();
+                if request.len() >= len + body_len {
+                    let body = request[len..len + body_len].to_vec();
+                    stream.write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n").await.unwrap();
+                    return (headers, body);
+                }
+            }
+            assert!(n > 0, "the connection closed mid-request");
+        }
+    }
+
+    #[test]
+    fn signs_the_timestamp_and_body() {
+        // HMAC-SHA256 under "whsec-test" of `1700000000.{"event":"seal"}`.
+        assert_eq!(
+            signature("whsec-test", 1_700_000_000, br#"{"event":"seal"}"#),
+            "t=1700000000,sha256=a9f349653bd8f7685c7f571debf15b27af43708e002cf88aebfdf3848db46640"
+        );
+        let signed = signature("whsec-test", 1_700_000_000, b"");
+        assert_ne!(signature("whsec-test", 1_700_000_001, b""), signed);
+        assert_ne!(signature("whsec-other", 1_700_000_000, b""), signed);
+        assert!(webhooks(String::new(), None).signature(1_700_000_000, b"").is_none());
+    }
+
+    #[tokio::test]
+    async fn delivers_signed_events() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let receiver = tokio::spawn(receive(listener));
+        let before = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        webhooks(url.clone(), Some("whsec-test")).deliver(&url, &event()).await;
+        let (headers, body) = receiver.await.unwrap();
+
+        assert_eq!(headers["content-type"], EventFormat::default().content_type());
+        assert_eq!(headers["x-aegis-webhook-id"], "seal-41");
+        let value: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(value["audit_id"], 41);
+        // t=<unix seconds>,sha256=<64 hex digits>, over exactly the body sent.
+        let header = &headers["x-aegis-webhook-signature"];
+        let (t, mac) = header.strip_prefix("t=").unwrap().split_once(",sha256=").unwrap();
+        let t: u64 = t.parse().unwrap();
+        assert!(t >= before && t <= before + 60);
+        assert_eq!(mac.len(), 64);
+        assert!(mac.bytes().all(|b| b.is_ascii_hexdigit() && !b.is_ascii_uppercase()));
+        assert_eq!(header, &signature("whsec-test", t, &body));
+    }
+
+    #[tokio::test]
+    async fn leaves_events_unsigned_without_a_secret() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let receiver = tokio::spawn(receive(listener));
+        webhooks(url.clone(), None).deliver(&url, &event()).await;
+        let (headers, _) = receiver.await.unwrap();
+        assert!(!headers.contains_key("x-aegis-webhook-signature"));
+    }
+}
@@ -0,0 +1,218 @@
+// aegis-sealer-service/src/xmp.rs
+
+// XMP/IPTC round-trips for /seal (see `aegis_core::xmp`).
+//
+// With `import_embedded=true`, the XMP and IPTC properties found in the
+// uploaded image are added to JSON object metadata before signing, as
+//
+//     "embedded": {"xmp": {"dc:title": "...", "dc:subject": [...]},
+//                  "iptc": {"Keywords": [...], "By-line": [...]}}
+//
+// replacing any `embedded` key the client sent. With `extract_exif=true`,
+// the capture time, camera and GPS position in the image's EXIF (or XMP)
+// fill in the fields structured metadata left empty, and `auto_extracted`
+// records the source of each (see `aegis_core::exif`):
+//
+//     "captured_at": "2025-01-01T12:00:00+01:00",
+//     "device": {"make": "Canon", "model": "EOS R5"},
+//     "auto_extracted": {"captured_at": "exif:DateTimeOriginal",
+//                        "device.make": "exif:Make", "device.model": "exif:Model"}
+//
+// Only the first `MAX_SCAN_BYTES` of the upload are searched.
+//
+// With `output=xmp`, the image is sanitized before it is sealed, and the
+// response is that sanitized image carrying an XMP packet with the seal
+// instead of a container. The packet repeats the imported `embedded.xmp`
+// properties, so the copy keeps its caption and credits. `/verify` and
+// `aegis verify` accept such a copy in place of a container.
+//
+// `output=c2pa` (or `?format=c2pa`) does the same with a C2PA manifest in
+// place of the XMP packet (see `aegis_core::c2pa`), for Content Credentials
+// tooling. The manifest's claim is signed with the key that sealed the image.
+
+use crate::{signer::ServiceSigner, spool::Spool, AppError};
+use aegis_core::{exif, format, keys::Fingerprint, metadata::Metadata, xmp};
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use tracing::{info, warn};
+
+/// How much of an upload is searched for embedded metadata.
+const MAX_SCAN_BYTES: usize = 16 * 1024 * 1024;
+
+/// What /seal returns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Output {
+    Container,
+    Xmp,
+    C2pa,
+    /// The payload left as it was, with a `.aegis-manifest` (see
+    /// `aegis_core::manifest`); only the manifest is returned.
+    Manifest,
+    /// An in-toto statement about the payload in a DSSE envelope (see
+    /// `aegis_core::intoto`); only the envelope is returned.
+    Intoto,
+}
+
+impl Output {
+    pub fn parse(value: &str) -> Result<Self, AppError> {
+        match value.trim() {
+            "container" => Ok(Output::Container),
+            "xmp" => Ok(Output::Xmp),
+            "c2pa" => Ok(Output::C2pa),
+            "manifest" => Ok(Output::Manifest),
+            "intoto" => Ok(Output::Intoto),
+            other => Err(AppError(
+                StatusCode::BAD_REQUEST,
+                format!("Unknown output '{}'; expected container, xmp, c2pa, manifest or intoto.", other),
+            )),
+        }
+    }
+
+    /// Whether this returns a copy of the image carrying its seal.
+    pub fn is_copy(self) -> bool {
+        matches!(self, Output::Xmp | Output::C2pa)
+    }
+
+    /// Whether this returns the detached signature in another form,
+    /// leaving the payload as it was.
+    pub fn is_detached(self) -> bool {
+        matches!(self, Output::Manifest | Output::Intoto)
+    }
+}
+
+/// Adds the metadata embedded in the spooled image to `metadata` if it is a
+/// JSON object. The spool is left at its start.
+pub async fn import(spool: &mut Spool, metadata: String) -> Result<String, AppError> {
+    let head = read_head(spool).await?;
+    let embedded = xmp::Embedded::read(&head);
+    if embedded.is_empty() {
+        return Ok(metadata);
+    }
+    match serde_json::from_str::<Value>(&metadata) {
+        Ok(Value::Object(mut object)) => {
+            object.insert("embedded".to_string(), embedded.to_json());
+            Ok(Value::Object(object).to_string())
+        }
+        _ => {
+            warn!("Metadata is not a JSON object; sealing without the embedded metadata.");
+            Ok(metadata)
+        }
+    }
+}
+
+/// Fills the capture fields that structured metadata leaves empty from the
+/// spooled image's EXIF and XMP, recording where each value came from under
+/// `auto_extracted`. The spool is left at its start.
+pub async fn extract_exif(spool: &mut Spool, metadata: String) -> Result<String, AppError> {
+    let Ok(Some(mut structured)) = Metadata::parse_structured(&metadata) else {
+        warn!("Metadata is not a JSON object; sealing without EXIF fields.");
+        return Ok(metadata);
+    };
+    let head = read_head(spool).await?;
+    let filled = exif::fill_metadata(&mut structured, &head);
+    if filled.is_empty() {
+        return Ok(metadata);
+    }
+    info!(fields = filled.len(), "Filled metadata from EXIF/XMP.");
+    structured.custom.insert("auto_extracted".to_string(), Value::Object(filled));
+    Ok(structured.to_canonical_json())
+}
+
+// The first `MAX_SCAN_BYTES` of the spooled image, leaving it at its start.
+async fn read_head(spool: &mut Spool) -> Result<Vec<u8>, AppError> {
+    let mut head = Vec::new();
+    spool.rewind().await?;
+    while head.len() < MAX_SCAN_BYTES
+        && let Some(chunk) = spool.read_chunk().await?
+    {
+        head.extend_from_slice(&chunk);
+    }
+    spool.rewind().await?;
+    Ok(head)
+}
+
+/// Strips the embedded metadata from the spooled image into a new spool,
+/// returned with the sanitized image's SHA-256 and kind.
+pub async fn sanitize(spool: &mut Spool, spool_dir: &Path) -> Result<(Spool, String, xmp::ImageKind), AppError> {
+    let image = read_all(spool).await?;
+    let kind = xmp::ImageKind::sniff(&image).ok_or_else(|| {
+        AppError(StatusCode::UNSUPPORTED_MEDIA_TYPE, "XMP and C2PA output need a JPEG or PNG image.".into())
+    })?;
+    let sanitized = xmp::sanitize(&image).map_err(|e| AppError(StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))?;
+    info!(before = image.len(), after = sanitized.len(), "Sanitized image for XMP output.");
+    let mut out = Spool::create(spool_dir).await?;
+    out.write_all(&sanitized).await?;
+    Ok((out, hex::encode(Sha256::digest(&sanitized)), kind))
+}
+
+/// The sealed copy: the sanitized image with the packet describing its seal.
+pub async fn sealed_copy_response(
+    mut spool: Spool,
+    kind: xmp::ImageKind,
+    container_prefix: &[u8],
+    public_key: &[u8],
+    signature: &[u8],
+    metadata: &str,
+) -> Result<Response, AppError> {
+    let image = read_all(&mut spool).await?;
+    let properties = serde_json::from_str::<Value>(metadata)
+        .ok()
+        .and_then(|v| v.pointer("/embedded/xmp").and_then(Value::as_object).cloned())
+        .unwrap_or_default();
+    let packet = xmp::seal_packet(container_prefix, public_key, signature, metadata, &properties);
+    let copy = xmp::embed(&image, &packet).map_err(|e| AppError(StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))?;
+    let fingerprint = Fingerprint::of(public_key);
+    info!(key_fingerprint = %fingerprint.to_hex_groups(), size = copy.len(), "Sealed XMP copy produced.");
+    Ok(copy_response(kind, &fingerprint, copy))
+}
+
+/// The sealed copy with a C2PA manifest. `signer` must be the pinned signer
+/// that sealed the image, so the claim is signed with the same key.
+pub async fn c2pa_copy_response(
+    mut spool: Spool,
+    kind: xmp::ImageKind,
+    signer: &ServiceSigner,
+    container_prefix: &[u8],
+    public_key: &[u8],
+    metadata: &str,
+) -> Result<Response, AppError> {
+    let image = read_all(&mut spool).await?;
+    let unsigned = format::to_c2pa_manifest(&image, container_prefix, public_key, metadata)
+        .map_err(|e| AppError(StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))?;
+    let signature = signer.sign(&unsigned.signing_message()).await?;
+    let copy = unsigned.finish(&signature);
+    let fingerprint = Fingerprint::of(public_key);
+    info!(key_fingerprint = %fingerprint.to_hex_groups(), size = copy.len(), "Sealed C2PA copy produced.");
+    Ok(copy_response(kind, &fingerprint, copy))
+}
+
+fn copy_response(kind: xmp::ImageKind, fingerprint: &Fingerprint, copy: Vec<u8>) -> Response {
+    (
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, kind.media_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"sealed.{}\"", kind.extension()),
+            ),
+            (header::HeaderName::from_static("x-aegis-key-fingerprint"), fingerprint.to_string()),
+        ],
+        copy,
+    )
+        .into_response()
+}
+
+async fn read_all(spool: &mut Spool) -> Result<Vec<u8>, AppError> {
+    let mut data = Vec::with_capacity(spool.len() as usize);
+    spool.rewind().await?;
+    while let Some(chunk) = spool.read_chunk().await? {
+        data.extend_from_slice(&chunk);
+    }
+    spool.rewind().await?;
+    Ok(data)
+}
@@ -1,6 +0,0 @@
-// aegis-sealer-service/src/core/mod.rs
-
-// This file makes the other files in this directory available as a library.
-pub mod crypto;
-pub mod error;
-pub mod format;
\ No newline at end of file